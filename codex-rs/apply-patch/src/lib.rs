@@ -1,4 +1,5 @@
 mod parser;
+mod preview;
 mod seek_sequence;
 mod standalone_executable;
 
@@ -14,6 +15,15 @@ pub use parser::ParseError;
 use parser::ParseError::*;
 use parser::UpdateFileChunk;
 pub use parser::parse_patch;
+pub use preview::FilePreview;
+pub use preview::PatchPreview;
+pub use preview::PreviewChange;
+pub use preview::PreviewHunk;
+pub use preview::PreviewLine;
+pub use preview::preview_patch;
+use seek_sequence::AmbiguousMatch;
+pub use seek_sequence::MatchStrategy;
+use seek_sequence::SequenceMatch;
 use similar::TextDiff;
 use thiserror::Error;
 use tree_sitter::LanguageError;
@@ -116,12 +126,17 @@ pub enum ApplyPatchFileChange {
     Add {
         content: String,
     },
-    Delete,
+    Delete {
+        /// Contents of the file that will be removed.
+        content: String,
+    },
     Update {
         unified_diff: String,
         move_path: Option<PathBuf>,
         /// new_content that will result after the unified_diff is applied.
         new_content: String,
+        /// Loosest strategy any chunk of the update needed to find its lines.
+        match_strategy: MatchStrategy,
     },
 }
 
@@ -165,6 +180,52 @@ impl ApplyPatchAction {
         &self.changes
     }
 
+    /// The part of this action a reviewer accepted, as its own action whose
+    /// [`ApplyPatchAction::patch`] makes only those changes. See
+    /// [`PatchPreview::partial_patch`] for how files and hunks are chosen;
+    /// the hunks are those of the `unified_diff` of each
+    /// [`ApplyPatchFileChange::Update`], located with at most `loosest`, as
+    /// when the action was verified. `None` when nothing was accepted.
+    pub fn select(
+        &self,
+        keep_file: impl Fn(&Path) -> bool,
+        keep_hunk: impl Fn(&Path, &PreviewHunk) -> bool,
+        loosest: MatchStrategy,
+    ) -> Result<Option<ApplyPatchAction>, ApplyPatchError> {
+        let hunks = parse_patch(&self.patch)?.hunks;
+        let Some(patch) =
+            preview::preview_hunks(&hunks, &self.cwd, loosest)?.partial_patch(keep_file, keep_hunk)
+        else {
+            return Ok(None);
+        };
+        let hunks = parse_patch(&patch)?.hunks;
+        let mut changes: HashMap<PathBuf, ApplyPatchFileChange> =
+            preview::preview_hunks(&hunks, &self.cwd, MatchStrategy::Exact)?
+                .files
+                .into_iter()
+                .map(FilePreview::into_file_change)
+                .collect();
+        // The partial patch matches exactly by construction; report how the
+        // original patch had to be matched instead.
+        for (path, change) in &mut changes {
+            if let (
+                ApplyPatchFileChange::Update { match_strategy, .. },
+                Some(ApplyPatchFileChange::Update {
+                    match_strategy: original,
+                    ..
+                }),
+            ) = (change, self.changes.get(path))
+            {
+                *match_strategy = *original;
+            }
+        }
+        Ok(Some(ApplyPatchAction {
+            changes,
+            patch,
+            cwd: self.cwd.clone(),
+        }))
+    }
+
     /// Should be used exclusively for testing. (Not worth the overhead of
     /// creating a feature flag for this.)
     pub fn new_add_for_test(path: &Path, content: String) -> Self {
@@ -200,41 +261,27 @@ impl ApplyPatchAction {
 /// cwd must be an absolute path so that we can resolve relative paths in the
 /// patch.
 pub fn maybe_parse_apply_patch_verified(argv: &[String], cwd: &Path) -> MaybeApplyPatchVerified {
+    maybe_parse_apply_patch_verified_with_strategy(argv, cwd, MatchStrategy::Fuzzy)
+}
+
+/// Like [`maybe_parse_apply_patch_verified`], but chunks whose lines can only
+/// be found with a strategy looser than `loosest` make the patch fail.
+pub fn maybe_parse_apply_patch_verified_with_strategy(
+    argv: &[String],
+    cwd: &Path,
+    loosest: MatchStrategy,
+) -> MaybeApplyPatchVerified {
     match maybe_parse_apply_patch(argv) {
         MaybeApplyPatch::Body(ApplyPatchArgs { patch, hunks }) => {
-            let mut changes = HashMap::new();
-            for hunk in hunks {
-                let path = hunk.resolve_path(cwd);
-                match hunk {
-                    Hunk::AddFile { contents, .. } => {
-                        changes.insert(path, ApplyPatchFileChange::Add { content: contents });
-                    }
-                    Hunk::DeleteFile { .. } => {
-                        changes.insert(path, ApplyPatchFileChange::Delete);
-                    }
-                    Hunk::UpdateFile {
-                        move_path, chunks, ..
-                    } => {
-                        let ApplyPatchFileUpdate {
-                            unified_diff,
-                            content: contents,
-                        } = match unified_diff_from_chunks(&path, &chunks) {
-                            Ok(diff) => diff,
-                            Err(e) => {
-                                return MaybeApplyPatchVerified::CorrectnessError(e);
-                            }
-                        };
-                        changes.insert(
-                            path,
-                            ApplyPatchFileChange::Update {
-                                unified_diff,
-                                move_path: move_path.map(|p| cwd.join(p)),
-                                new_content: contents,
-                            },
-                        );
-                    }
-                }
-            }
+            // The changes shown for approval are the ones a dry run reports.
+            let changes = match preview::preview_hunks(&hunks, cwd, loosest) {
+                Ok(preview) => preview
+                    .files
+                    .into_iter()
+                    .map(FilePreview::into_file_change)
+                    .collect(),
+                Err(e) => return MaybeApplyPatchVerified::CorrectnessError(e),
+            };
             MaybeApplyPatchVerified::Body(ApplyPatchAction {
                 changes,
                 patch,
@@ -446,7 +493,7 @@ fn apply_hunks_to_files(hunks: &[Hunk]) -> anyhow::Result<AffectedPaths> {
                 chunks,
             } => {
                 let AppliedPatch { new_contents, .. } =
-                    derive_new_contents_from_chunks(path, chunks)?;
+                    derive_new_contents_from_chunks(path, chunks, MatchStrategy::Fuzzy)?;
                 if let Some(dest) = move_path {
                     if let Some(parent) = dest.parent()
                         && !parent.as_os_str().is_empty()
@@ -478,6 +525,7 @@ fn apply_hunks_to_files(hunks: &[Hunk]) -> anyhow::Result<AffectedPaths> {
 struct AppliedPatch {
     original_contents: String,
     new_contents: String,
+    match_strategy: MatchStrategy,
 }
 
 /// Return *only* the new file contents (joined into a single `String`) after
@@ -485,6 +533,7 @@ struct AppliedPatch {
 fn derive_new_contents_from_chunks(
     path: &Path,
     chunks: &[UpdateFileChunk],
+    loosest: MatchStrategy,
 ) -> std::result::Result<AppliedPatch, ApplyPatchError> {
     let original_contents = match std::fs::read_to_string(path) {
         Ok(contents) => contents,
@@ -507,7 +556,8 @@ fn derive_new_contents_from_chunks(
         original_lines.pop();
     }
 
-    let replacements = compute_replacements(&original_lines, path, chunks)?;
+    let (replacements, match_strategy) =
+        compute_replacements(&original_lines, path, chunks, loosest)?;
     let new_lines = apply_replacements(original_lines, &replacements);
     let mut new_lines = new_lines;
     if !new_lines.last().is_some_and(|s| s.is_empty()) {
@@ -517,31 +567,45 @@ fn derive_new_contents_from_chunks(
     Ok(AppliedPatch {
         original_contents,
         new_contents,
+        match_strategy,
     })
 }
 
 /// Compute a list of replacements needed to transform `original_lines` into the
 /// new lines, given the patch `chunks`. Each replacement is returned as
-/// `(start_index, old_len, new_lines)`.
+/// `(start_index, old_len, new_lines)`, along with the loosest strategy that
+/// was needed to locate any of them.
 fn compute_replacements(
     original_lines: &[String],
     path: &Path,
     chunks: &[UpdateFileChunk],
-) -> std::result::Result<Vec<(usize, usize, Vec<String>)>, ApplyPatchError> {
+    loosest: MatchStrategy,
+) -> std::result::Result<(Vec<(usize, usize, Vec<String>)>, MatchStrategy), ApplyPatchError> {
     let mut replacements: Vec<(usize, usize, Vec<String>)> = Vec::new();
     let mut line_index: usize = 0;
+    let mut strategy_used = MatchStrategy::Exact;
+    let seek = |pattern: &[String], start: usize, eof: bool, loosest: MatchStrategy| {
+        seek_sequence::seek_sequence(original_lines, pattern, start, eof, loosest).map_err(
+            |AmbiguousMatch { first, second }| {
+                ApplyPatchError::ComputeReplacements(format!(
+                    "Ambiguous match for {pattern:?} in {}: lines {} and {} are equally similar",
+                    path.display(),
+                    first + 1,
+                    second + 1
+                ))
+            },
+        )
+    };
 
     for chunk in chunks {
         // If a chunk has a `change_context`, we use seek_sequence to find it, then
         // adjust our `line_index` to continue from there.
         if let Some(ctx_line) = &chunk.change_context {
-            if let Some(idx) = seek_sequence::seek_sequence(
-                original_lines,
-                std::slice::from_ref(ctx_line),
-                line_index,
-                false,
-            ) {
-                line_index = idx + 1;
+            if let Some(SequenceMatch { index, strategy }) =
+                seek(std::slice::from_ref(ctx_line), line_index, false, loosest)?
+            {
+                line_index = index + 1;
+                strategy_used = strategy_used.max(strategy);
             } else {
                 return Err(ApplyPatchError::ComputeReplacements(format!(
                     "Failed to find context '{}' in {}",
@@ -572,11 +636,12 @@ fn compute_replacements(
         // trailing empty slice emitted by `split('\n')`.  If a direct search
         // fails and the pattern ends with an empty string, retry without that
         // final element so that modifications touching the end‑of‑file can be
-        // located reliably.
+        // located reliably. Fuzzy matching is only tried once both forms have
+        // failed, so that the sentinel cannot be matched against a real line.
 
+        let strict = loosest.min(MatchStrategy::IgnoreWhitespace);
         let mut pattern: &[String] = &chunk.old_lines;
-        let mut found =
-            seek_sequence::seek_sequence(original_lines, pattern, line_index, chunk.is_end_of_file);
+        let mut found = seek(pattern, line_index, chunk.is_end_of_file, strict)?;
 
         let mut new_slice: &[String] = &chunk.new_lines;
 
@@ -588,17 +653,21 @@ fn compute_replacements(
                 new_slice = &new_slice[..new_slice.len() - 1];
             }
 
-            found = seek_sequence::seek_sequence(
-                original_lines,
-                pattern,
-                line_index,
-                chunk.is_end_of_file,
-            );
+            found = seek(pattern, line_index, chunk.is_end_of_file, strict)?;
         }
 
-        if let Some(start_idx) = found {
+        if found.is_none() && loosest == MatchStrategy::Fuzzy {
+            found = seek(pattern, line_index, chunk.is_end_of_file, loosest)?;
+        }
+
+        if let Some(SequenceMatch {
+            index: start_idx,
+            strategy,
+        }) = found
+        {
             replacements.push((start_idx, pattern.len(), new_slice.to_vec()));
             line_index = start_idx + pattern.len();
+            strategy_used = strategy_used.max(strategy);
         } else {
             return Err(ApplyPatchError::ComputeReplacements(format!(
                 "Failed to find expected lines {:?} in {}",
@@ -608,7 +677,7 @@ fn compute_replacements(
         }
     }
 
-    Ok(replacements)
+    Ok((replacements, strategy_used))
 }
 
 /// Apply the `(start_index, old_len, new_lines)` replacements to `original_lines`,
@@ -644,29 +713,36 @@ fn apply_replacements(
 pub struct ApplyPatchFileUpdate {
     unified_diff: String,
     content: String,
+    match_strategy: MatchStrategy,
 }
 
+/// Chunks whose context can only be found with a strategy looser than
+/// `loosest` make the update fail.
 pub fn unified_diff_from_chunks(
     path: &Path,
     chunks: &[UpdateFileChunk],
+    loosest: MatchStrategy,
 ) -> std::result::Result<ApplyPatchFileUpdate, ApplyPatchError> {
-    unified_diff_from_chunks_with_context(path, chunks, 1)
+    unified_diff_from_chunks_with_context(path, chunks, 1, loosest)
 }
 
 pub fn unified_diff_from_chunks_with_context(
     path: &Path,
     chunks: &[UpdateFileChunk],
     context: usize,
+    loosest: MatchStrategy,
 ) -> std::result::Result<ApplyPatchFileUpdate, ApplyPatchError> {
     let AppliedPatch {
         original_contents,
         new_contents,
-    } = derive_new_contents_from_chunks(path, chunks)?;
+        match_strategy,
+    } = derive_new_contents_from_chunks(path, chunks, loosest)?;
     let text_diff = TextDiff::from_lines(&original_contents, &new_contents);
     let unified_diff = text_diff.unified_diff().context_radius(context).to_string();
     Ok(ApplyPatchFileUpdate {
         unified_diff,
         content: new_contents,
+        match_strategy,
     })
 }
 
@@ -1075,7 +1151,8 @@ PATCH"#,
             [Hunk::UpdateFile { chunks, .. }] => chunks,
             _ => panic!("Expected a single UpdateFile hunk"),
         };
-        let diff = unified_diff_from_chunks(&path, update_file_chunks).unwrap();
+        let diff =
+            unified_diff_from_chunks(&path, update_file_chunks, MatchStrategy::Fuzzy).unwrap();
         let expected_diff = r#"@@ -1,4 +1,4 @@
  foo
 -bar
@@ -1087,6 +1164,7 @@ PATCH"#,
         let expected = ApplyPatchFileUpdate {
             unified_diff: expected_diff.to_string(),
             content: "foo\nBAR\nbaz\nQUX\n".to_string(),
+            match_strategy: MatchStrategy::Exact,
         };
         assert_eq!(expected, diff);
     }
@@ -1114,7 +1192,7 @@ PATCH"#,
             _ => panic!("Expected a single UpdateFile hunk"),
         };
 
-        let diff = unified_diff_from_chunks(&path, chunks).unwrap();
+        let diff = unified_diff_from_chunks(&path, chunks, MatchStrategy::Fuzzy).unwrap();
         let expected_diff = r#"@@ -1,2 +1,2 @@
 -foo
 +FOO
@@ -1123,6 +1201,7 @@ PATCH"#,
         let expected = ApplyPatchFileUpdate {
             unified_diff: expected_diff.to_string(),
             content: "FOO\nbar\nbaz\n".to_string(),
+            match_strategy: MatchStrategy::Exact,
         };
         assert_eq!(expected, diff);
     }
@@ -1151,7 +1230,7 @@ PATCH"#,
             _ => panic!("Expected a single UpdateFile hunk"),
         };
 
-        let diff = unified_diff_from_chunks(&path, chunks).unwrap();
+        let diff = unified_diff_from_chunks(&path, chunks, MatchStrategy::Fuzzy).unwrap();
         let expected_diff = r#"@@ -2,2 +2,2 @@
  bar
 -baz
@@ -1160,6 +1239,7 @@ PATCH"#,
         let expected = ApplyPatchFileUpdate {
             unified_diff: expected_diff.to_string(),
             content: "foo\nbar\nBAZ\n".to_string(),
+            match_strategy: MatchStrategy::Exact,
         };
         assert_eq!(expected, diff);
     }
@@ -1186,7 +1266,7 @@ PATCH"#,
             _ => panic!("Expected a single UpdateFile hunk"),
         };
 
-        let diff = unified_diff_from_chunks(&path, chunks).unwrap();
+        let diff = unified_diff_from_chunks(&path, chunks, MatchStrategy::Fuzzy).unwrap();
         let expected_diff = r#"@@ -3 +3,2 @@
  baz
 +quux
@@ -1194,6 +1274,7 @@ PATCH"#,
         let expected = ApplyPatchFileUpdate {
             unified_diff: expected_diff.to_string(),
             content: "foo\nbar\nbaz\nquux\n".to_string(),
+            match_strategy: MatchStrategy::Exact,
         };
         assert_eq!(expected, diff);
     }
@@ -1232,7 +1313,7 @@ PATCH"#,
             _ => panic!("Expected a single UpdateFile hunk"),
         };
 
-        let diff = unified_diff_from_chunks(&path, chunks).unwrap();
+        let diff = unified_diff_from_chunks(&path, chunks, MatchStrategy::Fuzzy).unwrap();
 
         let expected_diff = r#"@@ -1,6 +1,7 @@
  a
@@ -1249,6 +1330,7 @@ PATCH"#,
         let expected = ApplyPatchFileUpdate {
             unified_diff: expected_diff.to_string(),
             content: "a\nB\nc\nd\nE\nf\ng\n".to_string(),
+            match_strategy: MatchStrategy::Exact,
         };
 
         assert_eq!(expected, diff);
@@ -1308,6 +1390,7 @@ g
                         .to_string(),
                         move_path: None,
                         new_content: "updated session directory content\n".to_string(),
+                        match_strategy: MatchStrategy::Exact,
                     },
                 )]),
                 patch: argv[1].clone(),
@@ -1316,6 +1399,94 @@ g
         );
     }
 
+    #[test]
+    fn test_fuzzy_match_is_recorded_and_can_be_refused() {
+        let session_dir = tempdir().unwrap();
+        fs::write(
+            session_dir.path().join("greet.rs"),
+            "fn greet() {\n    println!(\"hello, world\");\n}\n",
+        )
+        .unwrap();
+        // The context line is missing the comma the file has.
+        let argv = vec![
+            "apply_patch".to_string(),
+            r#"*** Begin Patch
+*** Update File: greet.rs
+@@
+ fn greet() {
+-    println!("hello world");
++    println!("goodbye, world");
+ }
+*** End Patch"#
+                .to_string(),
+        ];
+
+        match maybe_parse_apply_patch_verified(&argv, session_dir.path()) {
+            MaybeApplyPatchVerified::Body(action) => {
+                let change = action
+                    .changes()
+                    .get(&session_dir.path().join("greet.rs"))
+                    .unwrap();
+                let ApplyPatchFileChange::Update {
+                    new_content,
+                    match_strategy,
+                    ..
+                } = change
+                else {
+                    panic!("expected an update, got {change:?}");
+                };
+                assert_eq!(*match_strategy, MatchStrategy::Fuzzy);
+                assert_eq!(
+                    new_content,
+                    "fn greet() {\n    println!(\"goodbye, world\");\n}\n"
+                );
+            }
+            other => panic!("expected a fuzzy application, got {other:?}"),
+        }
+
+        let result = maybe_parse_apply_patch_verified_with_strategy(
+            &argv,
+            session_dir.path(),
+            MatchStrategy::Exact,
+        );
+        assert!(
+            matches!(
+                result,
+                MaybeApplyPatchVerified::CorrectnessError(ApplyPatchError::ComputeReplacements(_))
+            ),
+            "exact-only matching should refuse the patch, got {result:?}"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_fuzzy_match_lists_both_locations() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("prices.txt");
+        fs::write(&path, "total += item.price;\nskip\ntotal += item.price;\n").unwrap();
+        let patch = wrap_patch(&format!(
+            r#"*** Update File: {}
+@@
+-total += item.prices;
++total += item.cost;"#,
+            path.display()
+        ));
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        assert!(apply_patch(&patch, &mut stdout, &mut stderr).is_err());
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            format!(
+                "Ambiguous match for [\"total += item.prices;\"] in {}: lines 1 and 3 are equally similar\n",
+                path.display()
+            )
+        );
+        // The file is left untouched.
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "total += item.price;\nskip\ntotal += item.price;\n"
+        );
+    }
+
     #[test]
     fn test_apply_patch_fails_on_write_error() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,454 @@
+//! Resolves a patch against the files it touches without writing anything, so
+//! the result can be shown for review or validated with `apply_patch --dry-run`.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use similar::ChangeTag;
+use similar::TextDiff;
+
+use crate::AppliedPatch;
+use crate::ApplyPatchError;
+use crate::ApplyPatchFileChange;
+use crate::IoError;
+use crate::MatchStrategy;
+use crate::derive_new_contents_from_chunks;
+use crate::parser::Hunk;
+use crate::parser::parse_patch;
+
+/// Lines of context kept around each change in the preview diffs.
+const PREVIEW_CONTEXT_LINES: usize = 1;
+
+/// What applying a patch would do to the filesystem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchPreview {
+    /// Directory the paths in the patch were resolved against.
+    pub cwd: PathBuf,
+    /// One entry per file, in the order the patch touches them.
+    pub files: Vec<FilePreview>,
+}
+
+/// The effect of a patch on a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePreview {
+    /// Absolute path of the file the patch adds, deletes or updates.
+    pub path: PathBuf,
+    pub change: PreviewChange,
+    /// Contents before the patch; empty when the file is added.
+    pub old_content: String,
+    /// Contents after the patch; empty when the file is deleted.
+    pub new_content: String,
+    /// The changed regions of the file, with surrounding context.
+    pub hunks: Vec<PreviewHunk>,
+    /// `hunks` as a unified diff, without file headers.
+    pub unified_diff: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewChange {
+    Add,
+    Delete,
+    Update {
+        /// Absolute path the file is renamed to, if any.
+        move_path: Option<PathBuf>,
+        /// Loosest strategy any chunk needed to find the lines it replaces.
+        match_strategy: MatchStrategy,
+    },
+}
+
+/// A contiguous changed region. Line numbers are 1-based, as in the `@@`
+/// header of a unified diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<PreviewLine>,
+}
+
+/// One line of a hunk, without its trailing newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewLine {
+    Context(String),
+    Insert(String),
+    Delete(String),
+}
+
+impl PatchPreview {
+    /// The whole patch as a unified diff with `---`/`+++` headers, with paths
+    /// shown relative to [`PatchPreview::cwd`] where possible.
+    pub fn unified_diff(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let (old_path, new_path) = match &file.change {
+                PreviewChange::Add => ("/dev/null".to_string(), self.display_path(&file.path)),
+                PreviewChange::Delete => (self.display_path(&file.path), "/dev/null".to_string()),
+                PreviewChange::Update { move_path, .. } => (
+                    self.display_path(&file.path),
+                    self.display_path(move_path.as_ref().unwrap_or(&file.path)),
+                ),
+            };
+            out.push_str(&format!("--- {old_path}\n+++ {new_path}\n"));
+            out.push_str(&file.unified_diff);
+        }
+        out
+    }
+
+    /// A patch that makes only part of these changes: files `keep_file`
+    /// rejects are left alone, as are the hunks of updated files `keep_hunk`
+    /// rejects. `None` when nothing is left.
+    ///
+    /// Each chunk repeats every line since the previous kept hunk as
+    /// context, so it can only match where the hunk was found.
+    pub fn partial_patch(
+        &self,
+        keep_file: impl Fn(&Path) -> bool,
+        keep_hunk: impl Fn(&Path, &PreviewHunk) -> bool,
+    ) -> Option<String> {
+        let mut body = String::new();
+        for file in self.files.iter().filter(|file| keep_file(&file.path)) {
+            let path = self.display_path(&file.path);
+            match &file.change {
+                PreviewChange::Add => {
+                    body.push_str(&format!("*** Add File: {path}\n"));
+                    for line in file.new_content.lines() {
+                        body.push_str(&format!("+{line}\n"));
+                    }
+                }
+                PreviewChange::Delete => body.push_str(&format!("*** Delete File: {path}\n")),
+                PreviewChange::Update { move_path, .. } => {
+                    let old_lines: Vec<&str> = file.old_content.lines().collect();
+                    let mut chunks = String::new();
+                    let mut cursor = 0;
+                    for hunk in &file.hunks {
+                        if !keep_hunk(&file.path, hunk) {
+                            continue;
+                        }
+                        chunks.push_str("@@\n");
+                        let hunk_start = hunk.old_start.saturating_sub(1).max(cursor);
+                        for line in old_lines.get(cursor..hunk_start).unwrap_or_default() {
+                            chunks.push_str(&format!(" {line}\n"));
+                        }
+                        for line in &hunk.lines {
+                            let (prefix, text) = match line {
+                                PreviewLine::Context(text) => (' ', text),
+                                PreviewLine::Insert(text) => ('+', text),
+                                PreviewLine::Delete(text) => ('-', text),
+                            };
+                            chunks.push_str(&format!("{prefix}{text}\n"));
+                        }
+                        cursor = hunk_start + hunk.old_lines;
+                    }
+                    // The patch format cannot express a move without edits.
+                    if chunks.is_empty() {
+                        continue;
+                    }
+                    body.push_str(&format!("*** Update File: {path}\n"));
+                    if let Some(move_path) = move_path {
+                        body.push_str(&format!("*** Move to: {}\n", self.display_path(move_path)));
+                    }
+                    body.push_str(&chunks);
+                }
+            }
+        }
+        (!body.is_empty()).then(|| format!("*** Begin Patch\n{body}*** End Patch"))
+    }
+
+    fn display_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.cwd)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+impl FilePreview {
+    fn new(path: PathBuf, change: PreviewChange, old_content: String, new_content: String) -> Self {
+        let diff = TextDiff::from_lines(&old_content, &new_content);
+        let unified_diff = diff
+            .unified_diff()
+            .context_radius(PREVIEW_CONTEXT_LINES)
+            .to_string();
+        let hunks = diff
+            .grouped_ops(PREVIEW_CONTEXT_LINES)
+            .iter()
+            .filter_map(|ops| {
+                let (first, last) = (ops.first()?, ops.last()?);
+                let old_range = first.old_range().start..last.old_range().end;
+                let new_range = first.new_range().start..last.new_range().end;
+                let lines = ops
+                    .iter()
+                    .flat_map(|op| diff.iter_changes(op))
+                    .map(|change| {
+                        let text = change.value().trim_end_matches('\n').to_string();
+                        match change.tag() {
+                            ChangeTag::Equal => PreviewLine::Context(text),
+                            ChangeTag::Insert => PreviewLine::Insert(text),
+                            ChangeTag::Delete => PreviewLine::Delete(text),
+                        }
+                    })
+                    .collect();
+                Some(PreviewHunk {
+                    old_start: hunk_start(&old_range),
+                    old_lines: old_range.len(),
+                    new_start: hunk_start(&new_range),
+                    new_lines: new_range.len(),
+                    lines,
+                })
+            })
+            .collect();
+        Self {
+            path,
+            change,
+            old_content,
+            new_content,
+            hunks,
+            unified_diff,
+        }
+    }
+
+    /// The change as recorded in an [`crate::ApplyPatchAction`].
+    pub(crate) fn into_file_change(self) -> (PathBuf, ApplyPatchFileChange) {
+        let change = match self.change {
+            PreviewChange::Add => ApplyPatchFileChange::Add {
+                content: self.new_content,
+            },
+            PreviewChange::Delete => ApplyPatchFileChange::Delete {
+                content: self.old_content,
+            },
+            PreviewChange::Update {
+                move_path,
+                match_strategy,
+            } => ApplyPatchFileChange::Update {
+                unified_diff: self.unified_diff,
+                move_path,
+                new_content: self.new_content,
+                match_strategy,
+            },
+        };
+        (self.path, change)
+    }
+}
+
+/// Unified diffs number an empty range by the line before it.
+fn hunk_start(range: &std::ops::Range<usize>) -> usize {
+    if range.is_empty() {
+        range.start
+    } else {
+        range.start + 1
+    }
+}
+
+/// Parses `patch` and resolves it against the files under `cwd` without
+/// modifying them. Fails with the same error applying the patch would.
+pub fn preview_patch(patch: &str, cwd: &Path) -> Result<PatchPreview, ApplyPatchError> {
+    let hunks = parse_patch(patch)?.hunks;
+    preview_hunks(&hunks, cwd, MatchStrategy::Fuzzy)
+}
+
+pub(crate) fn preview_hunks(
+    hunks: &[Hunk],
+    cwd: &Path,
+    loosest: MatchStrategy,
+) -> Result<PatchPreview, ApplyPatchError> {
+    let mut files = Vec::with_capacity(hunks.len());
+    for hunk in hunks {
+        let path = hunk.resolve_path(cwd);
+        let file = match hunk {
+            Hunk::AddFile { contents, .. } => {
+                FilePreview::new(path, PreviewChange::Add, String::new(), contents.clone())
+            }
+            Hunk::DeleteFile { .. } => {
+                let old_content = std::fs::read_to_string(&path).map_err(|source| {
+                    ApplyPatchError::IoError(IoError {
+                        context: format!("Failed to read file to delete {}", path.display()),
+                        source,
+                    })
+                })?;
+                FilePreview::new(path, PreviewChange::Delete, old_content, String::new())
+            }
+            Hunk::UpdateFile {
+                move_path, chunks, ..
+            } => {
+                let AppliedPatch {
+                    original_contents,
+                    new_contents,
+                    match_strategy,
+                } = derive_new_contents_from_chunks(&path, chunks, loosest)?;
+                let change = PreviewChange::Update {
+                    move_path: move_path.as_ref().map(|p| cwd.join(p)),
+                    match_strategy,
+                };
+                FilePreview::new(path, change, original_contents, new_contents)
+            }
+        };
+        files.push(file);
+    }
+    Ok(PatchPreview {
+        cwd: cwd.to_path_buf(),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn preview_describes_changes_without_writing() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("keep.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.path().join("gone.txt"), "bye\n").unwrap();
+        let patch = r#"*** Begin Patch
+*** Add File: new.txt
++hello
+*** Delete File: gone.txt
+*** Update File: keep.txt
+*** Move to: kept.txt
+@@
+ one
+-two
++TWO
+*** End Patch"#;
+
+        let preview = preview_patch(patch, dir.path()).unwrap();
+
+        assert_eq!(
+            preview.files.iter().map(|f| &f.change).collect::<Vec<_>>(),
+            vec![
+                &PreviewChange::Add,
+                &PreviewChange::Delete,
+                &PreviewChange::Update {
+                    move_path: Some(dir.path().join("kept.txt")),
+                    match_strategy: MatchStrategy::Exact,
+                },
+            ]
+        );
+        assert_eq!(
+            preview.files[2].hunks,
+            vec![PreviewHunk {
+                old_start: 1,
+                old_lines: 3,
+                new_start: 1,
+                new_lines: 3,
+                lines: vec![
+                    PreviewLine::Context("one".to_string()),
+                    PreviewLine::Delete("two".to_string()),
+                    PreviewLine::Insert("TWO".to_string()),
+                    PreviewLine::Context("three".to_string()),
+                ],
+            }]
+        );
+        assert_eq!(
+            preview.unified_diff(),
+            r#"--- /dev/null
++++ new.txt
+@@ -0,0 +1 @@
++hello
+--- gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+--- keep.txt
++++ kept.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+"#
+        );
+
+        // Nothing on disk changed.
+        assert!(!dir.path().join("new.txt").exists());
+        assert!(!dir.path().join("kept.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("gone.txt")).unwrap(),
+            "bye\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("keep.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+    }
+
+    #[test]
+    fn partial_patch_keeps_only_accepted_hunks() {
+        let dir = tempdir().unwrap();
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        fs::write(dir.path().join("keep.txt"), original).unwrap();
+        fs::write(dir.path().join("gone.txt"), "bye\n").unwrap();
+        let patch = r#"*** Begin Patch
+*** Delete File: gone.txt
+*** Update File: keep.txt
+@@
+-a
++A
+@@
+ d
+-e
++E
+@@
+ h
+-i
++I
+*** End Patch"#;
+        let preview = preview_patch(patch, dir.path()).unwrap();
+        assert_eq!(preview.files[1].hunks.len(), 3);
+
+        let keep = dir.path().join("keep.txt");
+        let skipped = &preview.files[1].hunks[1];
+        let partial = preview
+            .partial_patch(|path| path == keep, |_, hunk| hunk != skipped)
+            .unwrap();
+        assert_eq!(
+            partial,
+            r#"*** Begin Patch
+*** Update File: keep.txt
+@@
+-a
++A
+ b
+@@
+ c
+ d
+ e
+ f
+ g
+ h
+-i
++I
+*** End Patch"#
+        );
+
+        let partial = preview_patch(&partial, dir.path()).unwrap();
+        assert_eq!(partial.files.len(), 1);
+        assert_eq!(partial.files[0].new_content, "A\nb\nc\nd\ne\nf\ng\nh\nI\n");
+        assert_eq!(
+            preview.partial_patch(|_| true, |_, _| false).unwrap(),
+            "*** Begin Patch\n*** Delete File: gone.txt\n*** End Patch"
+        );
+        assert_eq!(preview.partial_patch(|_| false, |_, _| true), None);
+    }
+
+    #[test]
+    fn preview_fails_when_patch_would_not_apply() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("keep.txt"), "one\n").unwrap();
+        let patch = r#"*** Begin Patch
+*** Update File: keep.txt
+@@
+-something else entirely
++two
+*** End Patch"#;
+
+        assert!(matches!(
+            preview_patch(patch, dir.path()),
+            Err(ApplyPatchError::ComputeReplacements(_))
+        ));
+    }
+}
@@ -1,22 +1,74 @@
+use similar::TextDiff;
+
+/// How far, in lines, the fuzzy pass looks for a chunk away from where the
+/// previous chunk ended (or from the end of the file for end-of-file chunks).
+const FUZZY_WINDOW: usize = 50;
+
+/// Least average line similarity a fuzzy candidate needs to be considered.
+const FUZZY_MIN_SIMILARITY: f32 = 0.85;
+
+/// How closely the lines of a chunk had to match the file for it to be
+/// applied, ordered from strictest to loosest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchStrategy {
+    /// The lines matched byte for byte.
+    Exact,
+    /// The lines matched once surrounding whitespace was ignored and
+    /// typographic punctuation was folded to ASCII.
+    IgnoreWhitespace,
+    /// The lines were the single most similar region near the expected
+    /// position, but did not match exactly.
+    Fuzzy,
+}
+
+impl std::fmt::Display for MatchStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MatchStrategy::Exact => "exact",
+            MatchStrategy::IgnoreWhitespace => "ignore-whitespace",
+            MatchStrategy::Fuzzy => "fuzzy",
+        })
+    }
+}
+
+/// Where a pattern was found and which pass found it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SequenceMatch {
+    pub(crate) index: usize,
+    pub(crate) strategy: MatchStrategy,
+}
+
+/// The fuzzy pass found two regions that are equally similar to the pattern.
+/// Both are zero-based line indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AmbiguousMatch {
+    pub(crate) first: usize,
+    pub(crate) second: usize,
+}
+
 /// Attempt to find the sequence of `pattern` lines within `lines` beginning at or after `start`.
-/// Returns the starting index of the match or `None` if not found. Matches are attempted with
-/// decreasing strictness: exact match, then ignoring trailing whitespace, then ignoring leading
-/// and trailing whitespace. When `eof` is true, we first try starting at the end-of-file (so that
-/// patterns intended to match file endings are applied at the end), and fall back to searching
-/// from `start` if needed.
+/// Returns the starting index of the match and the pass that found it, or `None` if not found.
+/// Matches are attempted with decreasing strictness, stopping after `loosest`: exact match, then
+/// ignoring trailing whitespace, then ignoring leading and trailing whitespace, then normalising
+/// punctuation, and finally a fuzzy search within [`FUZZY_WINDOW`] lines. When `eof` is true,
+/// we search starting at the end-of-file (so that patterns intended to match file endings are
+/// applied at the end).
 ///
 /// Special cases handled defensively:
-///  • Empty `pattern` → returns `Some(start)` (no-op match)
-///  • `pattern.len() > lines.len()` → returns `None` (cannot match, avoids
+///  • Empty `pattern` → returns `Some(start)` (no-op match)
+///  • `pattern.len() > lines.len()` → returns `None` (cannot match, avoids
 ///    out‑of‑bounds panic that occurred pre‑2025‑04‑12)
 pub(crate) fn seek_sequence(
     lines: &[String],
     pattern: &[String],
     start: usize,
     eof: bool,
-) -> Option<usize> {
+    loosest: MatchStrategy,
+) -> Result<Option<SequenceMatch>, AmbiguousMatch> {
+    let found = |index: usize, strategy: MatchStrategy| Ok(Some(SequenceMatch { index, strategy }));
+
     if pattern.is_empty() {
-        return Some(start);
+        return found(start, MatchStrategy::Exact);
     }
 
     // When the pattern is longer than the available input there is no possible
@@ -24,104 +76,133 @@ pub(crate) fn seek_sequence(
     // the search loops below (previously caused a panic when
     // `pattern.len() > lines.len()`).
     if pattern.len() > lines.len() {
-        return None;
+        return Ok(None);
     }
     let search_start = if eof && lines.len() >= pattern.len() {
         lines.len() - pattern.len()
     } else {
         start
     };
+    let last_start = lines.len() - pattern.len();
     // Exact match first.
-    for i in search_start..=lines.len().saturating_sub(pattern.len()) {
+    for i in search_start..=last_start {
         if lines[i..i + pattern.len()] == *pattern {
-            return Some(i);
+            return found(i, MatchStrategy::Exact);
         }
     }
-    // Then rstrip match.
-    for i in search_start..=lines.len().saturating_sub(pattern.len()) {
-        let mut ok = true;
-        for (p_idx, pat) in pattern.iter().enumerate() {
-            if lines[i + p_idx].trim_end() != pat.trim_end() {
-                ok = false;
-                break;
-            }
-        }
-        if ok {
-            return Some(i);
-        }
-    }
-    // Finally, trim both sides to allow more lenience.
-    for i in search_start..=lines.len().saturating_sub(pattern.len()) {
-        let mut ok = true;
-        for (p_idx, pat) in pattern.iter().enumerate() {
-            if lines[i + p_idx].trim() != pat.trim() {
-                ok = false;
-                break;
-            }
-        }
-        if ok {
-            return Some(i);
-        }
+    if loosest == MatchStrategy::Exact {
+        return Ok(None);
     }
 
-    // ------------------------------------------------------------------
-    // Final, most permissive pass – attempt to match after *normalising*
+    // Then rstrip match, trim match, and finally a match after *normalising*
     // common Unicode punctuation to their ASCII equivalents so that diffs
     // authored with plain ASCII characters can still be applied to source
     // files that contain typographic dashes / quotes, etc.  This mirrors the
     // fuzzy behaviour of `git apply` which ignores minor byte-level
     // differences when locating context lines.
-    // ------------------------------------------------------------------
-
-    fn normalise(s: &str) -> String {
-        s.trim()
-            .chars()
-            .map(|c| match c {
-                // Various dash / hyphen code-points → ASCII '-'
-                '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
-                | '\u{2212}' => '-',
-                // Fancy single quotes → '\''
-                '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
-                // Fancy double quotes → '"'
-                '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
-                // Non-breaking space and other odd spaces → normal space
-                '\u{00A0}' | '\u{2002}' | '\u{2003}' | '\u{2004}' | '\u{2005}' | '\u{2006}'
-                | '\u{2007}' | '\u{2008}' | '\u{2009}' | '\u{200A}' | '\u{202F}' | '\u{205F}'
-                | '\u{3000}' => ' ',
-                other => other,
-            })
-            .collect::<String>()
+    let passes: [fn(&str) -> String; 3] = [
+        |s| s.trim_end().to_string(),
+        |s| s.trim().to_string(),
+        normalise,
+    ];
+    for pass in passes {
+        for i in search_start..=last_start {
+            if pattern
+                .iter()
+                .enumerate()
+                .all(|(p_idx, pat)| pass(&lines[i + p_idx]) == pass(pat))
+            {
+                return found(i, MatchStrategy::IgnoreWhitespace);
+            }
+        }
+    }
+    if loosest == MatchStrategy::IgnoreWhitespace {
+        return Ok(None);
     }
 
-    for i in search_start..=lines.len().saturating_sub(pattern.len()) {
-        let mut ok = true;
-        for (p_idx, pat) in pattern.iter().enumerate() {
-            if normalise(&lines[i + p_idx]) != normalise(pat) {
-                ok = false;
-                break;
-            }
+    // Last resort: the most similar region near where the chunk was
+    // expected, as long as it is clearly better than every other candidate.
+    let window_start = search_start.saturating_sub(FUZZY_WINDOW).max(start);
+    let window_end = search_start.saturating_add(FUZZY_WINDOW).min(last_start);
+    let mut best: Option<(usize, f32)> = None;
+    let mut tied_with: Option<usize> = None;
+    for i in window_start..=window_end {
+        let score = similarity(&lines[i..i + pattern.len()], pattern);
+        if score < FUZZY_MIN_SIMILARITY {
+            continue;
         }
-        if ok {
-            return Some(i);
+        match best {
+            Some((_, best_score)) if (score - best_score).abs() <= f32::EPSILON => {
+                tied_with.get_or_insert(i);
+            }
+            Some((_, best_score)) if score < best_score => {}
+            _ => {
+                best = Some((i, score));
+                tied_with = None;
+            }
         }
     }
+    match (best, tied_with) {
+        (Some((first, _)), Some(second)) => Err(AmbiguousMatch { first, second }),
+        (Some((index, _)), None) => found(index, MatchStrategy::Fuzzy),
+        (None, _) => Ok(None),
+    }
+}
 
-    None
+/// Average per-line similarity of `candidate` and `pattern` (which have the
+/// same length), between 0 and 1, ignoring whitespace and punctuation
+/// differences the earlier passes already tolerate.
+fn similarity(candidate: &[String], pattern: &[String]) -> f32 {
+    let total: f32 = candidate
+        .iter()
+        .zip(pattern)
+        .map(|(line, pat)| {
+            let (line, pat) = (normalise(line), normalise(pat));
+            TextDiff::from_chars(line.as_str(), pat.as_str()).ratio()
+        })
+        .sum();
+    total / pattern.len() as f32
+}
+
+fn normalise(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c {
+            // Various dash / hyphen code-points → ASCII '-'
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
+            | '\u{2212}' => '-',
+            // Fancy single quotes → '\''
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+            // Fancy double quotes → '"'
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+            // Non-breaking space and other odd spaces → normal space
+            '\u{00A0}' | '\u{2002}' | '\u{2003}' | '\u{2004}' | '\u{2005}' | '\u{2006}'
+            | '\u{2007}' | '\u{2008}' | '\u{2009}' | '\u{200A}' | '\u{202F}' | '\u{205F}'
+            | '\u{3000}' => ' ',
+            other => other,
+        })
+        .collect::<String>()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::seek_sequence;
+    use super::*;
 
     fn to_vec(strings: &[&str]) -> Vec<String> {
         strings.iter().map(|s| s.to_string()).collect()
     }
 
+    fn seek(lines: &[String], pattern: &[String]) -> Option<usize> {
+        seek_sequence(lines, pattern, 0, false, MatchStrategy::Fuzzy)
+            .unwrap()
+            .map(|m| m.index)
+    }
+
     #[test]
     fn test_exact_match_finds_sequence() {
         let lines = to_vec(&["foo", "bar", "baz"]);
         let pattern = to_vec(&["bar", "baz"]);
-        assert_eq!(seek_sequence(&lines, &pattern, 0, false), Some(1));
+        assert_eq!(seek(&lines, &pattern), Some(1));
     }
 
     #[test]
@@ -129,7 +210,7 @@ mod tests {
         let lines = to_vec(&["foo   ", "bar\t\t"]);
         // Pattern omits trailing whitespace.
         let pattern = to_vec(&["foo", "bar"]);
-        assert_eq!(seek_sequence(&lines, &pattern, 0, false), Some(0));
+        assert_eq!(seek(&lines, &pattern), Some(0));
     }
 
     #[test]
@@ -137,7 +218,7 @@ mod tests {
         let lines = to_vec(&["    foo   ", "   bar\t"]);
         // Pattern omits any additional whitespace.
         let pattern = to_vec(&["foo", "bar"]);
-        assert_eq!(seek_sequence(&lines, &pattern, 0, false), Some(0));
+        assert_eq!(seek(&lines, &pattern), Some(0));
     }
 
     #[test]
@@ -145,6 +226,83 @@ mod tests {
         let lines = to_vec(&["just one line"]);
         let pattern = to_vec(&["too", "many", "lines"]);
         // Should not panic – must return None when pattern cannot possibly fit.
-        assert_eq!(seek_sequence(&lines, &pattern, 0, false), None);
+        assert_eq!(seek(&lines, &pattern), None);
+    }
+
+    #[test]
+    fn test_reports_strategy_and_respects_loosest() {
+        let lines = to_vec(&["foo   ", "bar"]);
+        let pattern = to_vec(&["foo", "bar"]);
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 0, false, MatchStrategy::Fuzzy),
+            Ok(Some(SequenceMatch {
+                index: 0,
+                strategy: MatchStrategy::IgnoreWhitespace
+            }))
+        );
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 0, false, MatchStrategy::Exact),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_finds_drifted_lines() {
+        let lines = to_vec(&[
+            "fn main() {",
+            "    let greeting = \"hello, world\";",
+            "    println!(\"{greeting}\");",
+            "}",
+        ]);
+        let pattern = to_vec(&[
+            "    let greeting = \"hello world\";",
+            "    println!(\"{greeting}\");",
+        ]);
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 0, false, MatchStrategy::Fuzzy),
+            Ok(Some(SequenceMatch {
+                index: 1,
+                strategy: MatchStrategy::Fuzzy
+            }))
+        );
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 0, false, MatchStrategy::IgnoreWhitespace),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_rejects_dissimilar_lines() {
+        let lines = to_vec(&["alpha", "beta", "gamma"]);
+        let pattern = to_vec(&["delta", "epsilon"]);
+        assert_eq!(seek(&lines, &pattern), None);
+    }
+
+    #[test]
+    fn test_fuzzy_match_is_bounded_by_window() {
+        let mut lines = vec!["filler".to_string(); FUZZY_WINDOW + 10];
+        lines.push("let value = compute(1, 2);".to_string());
+        let pattern = to_vec(&["let value = compute(1, 3);"]);
+        assert_eq!(seek(&lines, &pattern), None);
+        // Starting closer brings the line into range.
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 20, false, MatchStrategy::Fuzzy)
+                .unwrap()
+                .map(|m| m.index),
+            Some(FUZZY_WINDOW + 10)
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_rejects_equally_good_candidates() {
+        let lines = to_vec(&["total += item.price;", "", "total += item.price;"]);
+        let pattern = to_vec(&["total += item.prices;"]);
+        assert_eq!(
+            seek_sequence(&lines, &pattern, 0, false, MatchStrategy::Fuzzy),
+            Err(AmbiguousMatch {
+                first: 0,
+                second: 2
+            })
+        );
     }
 }
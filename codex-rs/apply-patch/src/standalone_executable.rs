@@ -1,6 +1,9 @@
 use std::io::Read;
 use std::io::Write;
 
+/// Print the diff the patch would produce instead of applying it.
+const DRY_RUN_FLAG: &str = "--dry-run";
+
 pub fn main() -> ! {
     let exit_code = run_main();
     std::process::exit(exit_code);
@@ -9,9 +12,11 @@ pub fn main() -> ! {
 /// We would prefer to return `std::process::ExitCode`, but its `exit_process()`
 /// method is still a nightly API and we want main() to return !.
 pub fn run_main() -> i32 {
-    // Expect either one argument (the full apply_patch payload) or read it from stdin.
-    let mut args = std::env::args_os();
+    // Expect either one argument (the full apply_patch payload) or read it from stdin,
+    // optionally preceded by `--dry-run`.
+    let mut args = std::env::args_os().peekable();
     let _argv0 = args.next();
+    let dry_run = args.next_if(|arg| arg == DRY_RUN_FLAG).is_some();
 
     let patch_arg = match args.next() {
         Some(arg) => match arg.into_string() {
@@ -27,7 +32,9 @@ pub fn run_main() -> i32 {
             match std::io::stdin().read_to_string(&mut buf) {
                 Ok(_) => {
                     if buf.is_empty() {
-                        eprintln!("Usage: apply_patch 'PATCH'\n       echo 'PATCH' | apply-patch");
+                        eprintln!(
+                            "Usage: apply_patch [--dry-run] 'PATCH'\n       echo 'PATCH' | apply-patch [--dry-run]"
+                        );
                         return 2;
                     }
                     buf
@@ -46,6 +53,10 @@ pub fn run_main() -> i32 {
         return 2;
     }
 
+    if dry_run {
+        return dry_run_main(&patch_arg);
+    }
+
     let mut stdout = std::io::stdout();
     let mut stderr = std::io::stderr();
     match crate::apply_patch(&patch_arg, &mut stdout, &mut stderr) {
@@ -57,3 +68,31 @@ pub fn run_main() -> i32 {
         Err(_) => 1,
     }
 }
+
+/// Prints the unified diff of `patch` against the current directory. Exits
+/// non-zero, without touching any file, when the patch would not apply.
+fn dry_run_main(patch: &str) -> i32 {
+    let cwd = match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(err) => {
+            eprintln!("Error: Failed to determine the current directory.\n{err}");
+            return 1;
+        }
+    };
+    match crate::preview_patch(patch, &cwd) {
+        Ok(preview) if preview.files.is_empty() => {
+            eprintln!("No files were modified.");
+            1
+        }
+        Ok(preview) => {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(preview.unified_diff().as_bytes());
+            let _ = stdout.flush();
+            0
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            1
+        }
+    }
+}
@@ -88,3 +88,61 @@ fn test_apply_patch_cli_stdin_add_and_update() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_apply_patch_cli_dry_run_prints_diff_without_writing() -> anyhow::Result<()> {
+    let tmp = tempdir()?;
+    let file = "cli_dry_run.txt";
+    let absolute_path = tmp.path().join(file);
+    fs::write(&absolute_path, "hello\n")?;
+
+    let update_patch = format!(
+        r#"*** Begin Patch
+*** Update File: {file}
+@@
+-hello
++world
+*** End Patch"#
+    );
+    Command::cargo_bin("apply_patch")
+        .expect("should find apply_patch binary")
+        .arg("--dry-run")
+        .arg(update_patch)
+        .current_dir(tmp.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "--- {file}\n+++ {file}\n@@ -1 +1 @@\n-hello\n+world\n"
+        ));
+    assert_eq!(fs::read_to_string(&absolute_path)?, "hello\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_apply_patch_cli_dry_run_fails_when_patch_does_not_apply() -> anyhow::Result<()> {
+    let tmp = tempdir()?;
+    let file = "cli_dry_run_fail.txt";
+    let absolute_path = tmp.path().join(file);
+    fs::write(&absolute_path, "hello\n")?;
+
+    let update_patch = format!(
+        r#"*** Begin Patch
+*** Update File: {file}
+@@
+-something unrelated that is not in the file
++world
+*** End Patch"#
+    );
+    let mut cmd =
+        assert_cmd::Command::cargo_bin("apply_patch").expect("should find apply_patch binary");
+    cmd.current_dir(tmp.path());
+    cmd.arg("--dry-run")
+        .write_stdin(update_patch)
+        .assert()
+        .failure()
+        .stdout("");
+    assert_eq!(fs::read_to_string(&absolute_path)?, "hello\n");
+
+    Ok(())
+}
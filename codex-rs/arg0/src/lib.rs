@@ -54,6 +54,11 @@ where
     }
 
     let argv1 = args.next().unwrap_or_default();
+    #[cfg(windows)]
+    if argv1 == codex_core::CODEX_WINDOWS_SANDBOX_ARG1 {
+        // Safety: [`run_main`] never returns.
+        codex_core::windows_sandbox::run_main();
+    }
     if argv1 == CODEX_APPLY_PATCH_ARG1 {
         let patch_arg = args.next().and_then(|s| s.to_str().map(|s| s.to_owned()));
         let exit_code = match patch_arg {
@@ -106,13 +111,13 @@ where
 
 const ILLEGAL_ENV_VAR_PREFIX: &str = "CODEX_";
 
-/// Load env vars from ~/.codex/.env and `$(pwd)/.env`.
+/// Load env vars from `.env` in the config directory and `$(pwd)/.env`.
 ///
 /// Security: Do not allow `.env` files to create or modify any variables
 /// with names starting with `CODEX_`.
 fn load_dotenv() {
-    if let Ok(codex_home) = codex_core::config::find_codex_home()
-        && let Ok(iter) = dotenvy::from_path_iter(codex_home.join(".env"))
+    if let Ok(dirs) = codex_core::codex_dirs::CodexDirs::resolve()
+        && let Ok(iter) = dotenvy::from_path_iter(dirs.config_dir().join(".env"))
     {
         set_filtered(iter);
     }
@@ -13,7 +13,7 @@ pub(crate) async fn chatgpt_get_request<T: DeserializeOwned>(
     path: String,
 ) -> anyhow::Result<T> {
     let chatgpt_base_url = &config.chatgpt_base_url;
-    init_chatgpt_token_from_auth(&config.codex_home).await?;
+    init_chatgpt_token_from_auth(config.dirs.config_dir()).await?;
 
     // Make direct HTTP request to ChatGPT backend API with the token
     let client = reqwest::Client::new();
@@ -31,7 +31,7 @@ pub(crate) async fn chatgpt_get_request<T: DeserializeOwned>(
         .bearer_auth(&token.access_token)
         .header("chatgpt-account-id", account_id?)
         .header("Content-Type", "application/json")
-        .header("User-Agent", get_codex_user_agent(None))
+        .header("User-Agent", get_codex_user_agent(None, None))
         .send()
         .await
         .context("Failed to send request")?;
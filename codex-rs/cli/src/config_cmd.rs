@@ -0,0 +1,238 @@
+use codex_common::CliConfigOverrides;
+use codex_core::codex_dirs::migrate_legacy_home;
+use codex_core::config::Config;
+use codex_core::config_types::McpServerStartup;
+use codex_core::config_types::McpServerTransport;
+use serde_json::Value as JsonValue;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::login::load_config_or_exit;
+
+/// Replaces values that may hold secrets, such as header values and
+/// environment variables passed to MCP servers.
+const MASK: &str = "********";
+
+/// Loads the configuration with the `-c` overrides applied and prints the
+/// effective settings, or the first error, which names the offending key.
+pub fn run_config_validate(cli_config_overrides: CliConfigOverrides) -> ! {
+    let config = load_config_or_exit(cli_config_overrides);
+
+    match toml::to_string_pretty(&effective_config(&config)) {
+        Ok(rendered) => {
+            eprintln!(
+                "{} is valid. Effective configuration:\n",
+                config.dirs.config_file().display()
+            );
+            print!("{rendered}");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error rendering the configuration: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Moves `~/.codex` into the platform directories, listing what went where.
+pub fn run_migrate_home() -> ! {
+    match migrate_legacy_home() {
+        Ok(Some(migration)) => {
+            for (from, to) in &migration.moved {
+                eprintln!("{} -> {}", from.display(), to.display());
+            }
+            eprintln!(
+                "Moved {} to {} (config), {} (state) and {} (cache).",
+                migration.legacy_home.display(),
+                migration.dirs.config_dir().display(),
+                migration.dirs.state_dir().display(),
+                migration.dirs.cache_dir().display()
+            );
+            std::process::exit(0);
+        }
+        Ok(None) => {
+            eprintln!("There is no ~/.codex to migrate.");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error migrating ~/.codex: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The resolved configuration, after defaults, the active profile and
+/// overrides, laid out like `config.toml`.
+fn effective_config(config: &Config) -> JsonValue {
+    let model_providers: serde_json::Map<String, JsonValue> = config
+        .model_providers
+        .iter()
+        .map(|(id, provider)| {
+            let mut provider = serde_json::to_value(provider).unwrap_or_default();
+            if let Some(headers) = provider.get_mut("http_headers") {
+                mask_values(headers);
+            }
+            (id.clone(), provider)
+        })
+        .collect();
+    let mcp_servers: serde_json::Map<String, JsonValue> = config
+        .mcp_servers
+        .iter()
+        .map(|(name, server)| {
+            let mut entry = match &server.transport {
+                McpServerTransport::Stdio { command, args, env } => json!({
+                    "command": command,
+                    "args": args,
+                    "env": env.as_ref().map(masked),
+                }),
+                McpServerTransport::StreamableHttp { url, oauth } => json!({
+                    "url": url,
+                    "oauth": {
+                        "client_id": oauth.client_id,
+                        "client_secret": oauth.client_secret.as_ref().map(|_| MASK),
+                        "scopes": oauth.scopes,
+                        "callback_port": oauth.callback_port,
+                    },
+                }),
+            };
+            entry["startup"] = json!(match server.startup {
+                McpServerStartup::Eager => "eager",
+                McpServerStartup::Lazy => "lazy",
+            });
+            entry["startup_timeout_ms"] = json!(server.startup_timeout_ms);
+            entry["enabled_tools"] = json!(server.enabled_tools);
+            entry["disabled_tools"] = json!(server.disabled_tools);
+            entry["tool_prefix"] = json!(server.tool_prefix);
+            entry["tool_names"] = json!(server.tool_names);
+            (name.clone(), entry)
+        })
+        .collect();
+    let trusted_commands: Vec<String> = config
+        .trusted_commands
+        .iter()
+        .map(|words| words.join(" "))
+        .collect();
+
+    let mut effective = json!({
+        "model": config.model,
+        "model_provider": config.model_provider_id,
+        "model_context_window": config.model_context_window,
+        "model_max_output_tokens": config.model_max_output_tokens,
+        "model_reasoning_effort": config.model_reasoning_effort,
+        "model_reasoning_summary": config.model_reasoning_summary,
+        "model_verbosity": config.model_verbosity,
+        "approval_policy": config.approval_policy,
+        "sandbox": config.sandbox_policy,
+        "cwd": config.cwd,
+        "config_dir": config.dirs.config_dir(),
+        "state_dir": config.dirs.state_dir(),
+        "cache_dir": config.dirs.cache_dir(),
+        "auto_compact_threshold": config.auto_compact_threshold,
+        "context_warning_thresholds": config.context_warning_thresholds,
+        "tool_output_budget_bytes": config.tool_output_budget_bytes,
+        "max_parallel_tool_calls": config.max_parallel_tool_calls,
+        "apply_patch_exact_match": config.apply_patch_exact_match,
+        "hide_agent_reasoning": config.hide_agent_reasoning,
+        "show_raw_agent_reasoning": config.show_raw_agent_reasoning,
+        "hide_environment_context": config.hide_environment_context,
+        "retain_scratch_dir": config.retain_scratch_dir,
+        "read_only_sandbox_allow_tmp": config.read_only_sandbox_allow_tmp,
+        "keep_queued_input_on_interrupt": config.keep_queued_input_on_interrupt,
+        "prompt_vars": config.prompt_vars,
+        "strict_prompt_vars": config.strict_prompt_vars,
+        "model_pricing": config.model_pricing,
+        "skip_git_repo_check": config.skip_git_repo_check,
+        "disable_response_storage": config.disable_response_storage,
+        "prompt_cache_key": config.prompt_cache_key,
+        "notify": config.notify,
+        "notifications": config.notifications,
+        "notify_on_approval": config.notify_on_approval,
+        "project_doc_max_bytes": config.project_doc_max_bytes,
+        "project_doc_strict": config.project_doc_strict,
+        "include_plan_tool": config.include_plan_tool,
+        "include_apply_patch_tool": config.include_apply_patch_tool,
+        "user_agent_suffix": config.user_agent_suffix,
+        "approvals": {
+            "trusted_commands": trusted_commands,
+            "safe_commands": config.safe_commands,
+        },
+        "exec": {
+            "max_output_bytes": config.exec_output_limits.max_bytes,
+            "max_output_lines": config.exec_output_limits.max_lines,
+            "timeout_ms": config.exec_timeout_ms,
+            "max_memory_mb": config.exec_limits.max_memory_bytes.map(|bytes| bytes / (1024 * 1024)),
+            "max_captured_output_bytes": config.exec_limits.max_captured_output_bytes,
+            "use_pty": config.exec_use_pty,
+            "pty_rows": config.exec_pty.rows,
+            "pty_cols": config.exec_pty.cols,
+            "pty_stdin_response": config.exec_pty.stdin_response,
+        },
+        "shell_environment_policy": {
+            "inherit": config.shell_environment_policy.inherit.to_string(),
+            "ignore_default_excludes": config.shell_environment_policy.ignore_default_excludes,
+            "set": masked(&config.shell_environment_policy.r#set),
+            "experimental_use_profile": config.shell_environment_policy.use_profile,
+        },
+        "shell_environment": {
+            "policy": if config.shell_environment_policy.use_profile { "profile" } else { "none" },
+        },
+        "tools": {
+            "web_search": config.tools_web_search_request,
+            "web_search_context_size": config.tools_web_search_context_size,
+            "view_image": config.include_view_image_tool,
+            "custom": config.command_tools.iter().map(|tool| tool.name.as_str()).collect::<Vec<_>>(),
+            "pull_request": config.pull_request_tool.as_ref().map(|tool| json!({
+                "provider": tool.provider,
+                "remote": tool.remote,
+                "requires_approval": tool.requires_approval,
+                "dry_run": tool.dry_run,
+            })),
+        },
+        "tui": {
+            "expand_reasoning": config.tui.expand_reasoning,
+            "attach_mentioned_files_max_bytes": config.tui.attach_mentioned_files_max_bytes,
+        },
+        "stream_coalescing": {
+            "window_ms": u64::try_from(config.stream_coalescing.window.as_millis()).unwrap_or(u64::MAX),
+            "max_bytes": config.stream_coalescing.max_bytes,
+        },
+        "rollout": {
+            "fsync_every_entries": config.rollout_fsync.every_entries,
+            "fsync_interval_ms": u64::try_from(config.rollout_fsync.interval.as_millis()).unwrap_or(u64::MAX),
+        },
+        "otel": {
+            "endpoint": config.otel.endpoint,
+            "headers": masked(&config.otel.headers),
+            "include_content": config.otel.include_content,
+        },
+        "model_providers": model_providers,
+        "mcp_servers": mcp_servers,
+        "mcp_event_max_argument_bytes": config.mcp_event_max_argument_bytes,
+    });
+    remove_nulls(&mut effective);
+    effective
+}
+
+fn masked(values: &HashMap<String, String>) -> HashMap<String, &'static str> {
+    values.keys().map(|key| (key.clone(), MASK)).collect()
+}
+
+fn mask_values(value: &mut JsonValue) {
+    if let Some(map) = value.as_object_mut() {
+        for value in map.values_mut() {
+            *value = json!(MASK);
+        }
+    }
+}
+
+/// TOML has no null, so unset settings are left out.
+fn remove_nulls(value: &mut JsonValue) {
+    match value {
+        JsonValue::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        JsonValue::Array(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
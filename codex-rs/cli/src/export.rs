@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use clap::ValueEnum;
+use codex_common::CliConfigOverrides;
+use codex_core::config::Config;
+use codex_core::config::ConfigOverrides;
+use codex_core::rollout::find_rollout_by_id;
+use codex_core::session_export::ExportFormat;
+use codex_core::session_export::Transcript;
+use codex_core::session_index::list_sessions;
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+pub struct ExportCommand {
+    #[clap(skip)]
+    pub config_overrides: CliConfigOverrides,
+
+    /// Id of the session to export (shown by `/status`).
+    #[arg(required_unless_present = "last", conflicts_with = "last")]
+    pub session_id: Option<String>,
+
+    /// Export the most recent session.
+    #[arg(long = "last", default_value_t = false)]
+    pub last: bool,
+
+    /// Format of the transcript.
+    #[arg(long = "format", value_enum, default_value_t = ExportFormatArg::Md)]
+    pub format: ExportFormatArg,
+
+    /// Write the transcript to this file instead of stdout.
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormatArg {
+    Md,
+    Html,
+    Json,
+}
+
+impl From<ExportFormatArg> for ExportFormat {
+    fn from(format: ExportFormatArg) -> Self {
+        match format {
+            ExportFormatArg::Md => ExportFormat::Markdown,
+            ExportFormatArg::Html => ExportFormat::Html,
+            ExportFormatArg::Json => ExportFormat::Json,
+        }
+    }
+}
+
+pub fn run_export(cmd: ExportCommand) -> anyhow::Result<()> {
+    let ExportCommand {
+        config_overrides,
+        session_id,
+        last: _,
+        format,
+        output,
+    } = cmd;
+    let overrides = config_overrides
+        .parse_overrides()
+        .map_err(anyhow::Error::msg)?;
+    let config = Config::load_with_cli_overrides(overrides, ConfigOverrides::default())?;
+
+    let path = match session_id {
+        Some(session_id) => {
+            let id = Uuid::parse_str(&session_id)
+                .map_err(|e| anyhow::anyhow!("invalid session id {session_id}: {e}"))?;
+            find_rollout_by_id(config.dirs.state_dir(), id)?
+                .ok_or_else(|| anyhow::anyhow!("no recorded session with id {id}"))?
+        }
+        None => list_sessions(config.dirs.state_dir())?
+            .into_iter()
+            .next()
+            .map(|session| session.path)
+            .ok_or_else(|| anyhow::anyhow!("no recorded sessions"))?,
+    };
+
+    // Redactions apply on export too, in case they were added after the
+    // session was recorded.
+    let transcript = Transcript::load(&path, &config.redactions)?;
+    let rendered = transcript.render(format.into());
+    match output {
+        Some(output) => std::fs::write(output, rendered)?,
+        None => print!("{rendered}"),
+    }
+    Ok(())
+}
@@ -1,7 +1,11 @@
+pub mod config_cmd;
 pub mod debug_sandbox;
 mod exit_status;
+pub mod export;
 pub mod login;
+pub mod mcp_cmd;
 pub mod proto;
+pub mod sessions_cmd;
 
 use clap::Parser;
 use codex_common::CliConfigOverrides;
@@ -7,9 +7,13 @@ use codex_login::CodexAuth;
 use codex_login::OPENAI_API_KEY_ENV_VAR;
 use codex_login::ServerOptions;
 use codex_login::login_with_api_key;
+use codex_login::login_with_provider_api_key;
 use codex_login::logout;
+use codex_login::logout_provider;
 use codex_login::run_login_server;
 use std::env;
+use std::io::BufRead;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
 pub async fn login_with_chatgpt(codex_home: PathBuf) -> std::io::Result<()> {
@@ -27,7 +31,7 @@ pub async fn login_with_chatgpt(codex_home: PathBuf) -> std::io::Result<()> {
 pub async fn run_login_with_chatgpt(cli_config_overrides: CliConfigOverrides) -> ! {
     let config = load_config_or_exit(cli_config_overrides);
 
-    match login_with_chatgpt(config.codex_home).await {
+    match login_with_chatgpt(config.dirs.config_dir().to_path_buf()).await {
         Ok(_) => {
             eprintln!("Successfully logged in");
             std::process::exit(0);
@@ -39,13 +43,51 @@ pub async fn run_login_with_chatgpt(cli_config_overrides: CliConfigOverrides) ->
     }
 }
 
+/// Logs in with `api_key`, or a key read from stdin, after checking it with
+/// the provider. With `provider_id` the key is stored for that provider only.
 pub async fn run_login_with_api_key(
     cli_config_overrides: CliConfigOverrides,
-    api_key: String,
+    api_key: Option<String>,
+    provider_id: Option<String>,
 ) -> ! {
     let config = load_config_or_exit(cli_config_overrides);
 
-    match login_with_api_key(&config.codex_home, &api_key) {
+    let api_key = match api_key {
+        Some(api_key) => {
+            eprintln!(
+                "Warning: a key passed as an argument ends up in your shell history; pipe it to `codex login --api-key` instead"
+            );
+            api_key
+        }
+        None => match read_api_key_from_stdin() {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                eprintln!("Error reading the API key: {e}");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let provider = match &provider_id {
+        Some(id) => match config.model_providers.get(id) {
+            Some(provider) => provider,
+            None => {
+                eprintln!("Model provider `{id}` not found");
+                std::process::exit(1);
+            }
+        },
+        None => &config.model_provider,
+    };
+    if let Err(e) = provider.check_api_key(&api_key).await {
+        eprintln!("Error checking the API key with {}: {e}", provider.name);
+        std::process::exit(1);
+    }
+
+    let stored = match &provider_id {
+        Some(id) => login_with_provider_api_key(config.dirs.config_dir(), id, &api_key),
+        None => login_with_api_key(config.dirs.config_dir(), &api_key),
+    };
+    match stored {
         Ok(_) => {
             eprintln!("Successfully logged in");
             std::process::exit(0);
@@ -60,7 +102,16 @@ pub async fn run_login_with_api_key(
 pub async fn run_login_status(cli_config_overrides: CliConfigOverrides) -> ! {
     let config = load_config_or_exit(cli_config_overrides);
 
-    match CodexAuth::from_codex_home(&config.codex_home, config.preferred_auth_method) {
+    if let Some(api_key) = &config.model_provider.stored_api_key {
+        eprintln!(
+            "Using the API key stored for {} - {}",
+            config.model_provider_id,
+            safe_format_key(api_key)
+        );
+        std::process::exit(0);
+    }
+
+    match CodexAuth::from_codex_home(config.dirs.config_dir(), config.preferred_auth_method) {
         Ok(Some(auth)) => match auth.mode {
             AuthMode::ApiKey => match auth.get_token().await {
                 Ok(api_key) => {
@@ -96,10 +147,30 @@ pub async fn run_login_status(cli_config_overrides: CliConfigOverrides) -> ! {
     }
 }
 
-pub async fn run_logout(cli_config_overrides: CliConfigOverrides) -> ! {
+pub async fn run_logout(
+    cli_config_overrides: CliConfigOverrides,
+    provider_id: Option<String>,
+) -> ! {
     let config = load_config_or_exit(cli_config_overrides);
 
-    match logout(&config.codex_home) {
+    if let Some(id) = provider_id {
+        match logout_provider(config.dirs.config_dir(), &id) {
+            Ok(true) => {
+                eprintln!("Removed the API key stored for {id}");
+                std::process::exit(0);
+            }
+            Ok(false) => {
+                eprintln!("No API key stored for {id}");
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error logging out: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match logout(config.dirs.config_dir()) {
         Ok(true) => {
             eprintln!("Successfully logged out");
             std::process::exit(0);
@@ -115,7 +186,7 @@ pub async fn run_logout(cli_config_overrides: CliConfigOverrides) -> ! {
     }
 }
 
-fn load_config_or_exit(cli_config_overrides: CliConfigOverrides) -> Config {
+pub(crate) fn load_config_or_exit(cli_config_overrides: CliConfigOverrides) -> Config {
     let cli_overrides = match cli_config_overrides.parse_overrides() {
         Ok(v) => v,
         Err(e) => {
@@ -134,6 +205,25 @@ fn load_config_or_exit(cli_config_overrides: CliConfigOverrides) -> Config {
     }
 }
 
+/// Reads the key from the first line of stdin, asking for it when stdin is a
+/// terminal.
+fn read_api_key_from_stdin() -> std::io::Result<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Paste your API key and press Enter: ");
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let api_key = line.trim();
+    if api_key.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "no API key on stdin",
+        ));
+    }
+    Ok(api_key.to_string())
+}
+
 fn safe_format_key(key: &str) -> String {
     if key.len() <= 13 {
         return "***".to_string();
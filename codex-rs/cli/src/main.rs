@@ -7,14 +7,23 @@ use codex_chatgpt::apply_command::ApplyCommand;
 use codex_chatgpt::apply_command::run_apply_command;
 use codex_cli::LandlockCommand;
 use codex_cli::SeatbeltCommand;
+use codex_cli::config_cmd::run_config_validate;
+use codex_cli::config_cmd::run_migrate_home;
+use codex_cli::export::ExportCommand;
+use codex_cli::export::run_export;
 use codex_cli::login::run_login_status;
 use codex_cli::login::run_login_with_api_key;
 use codex_cli::login::run_login_with_chatgpt;
 use codex_cli::login::run_logout;
+use codex_cli::mcp_cmd::run_mcp_login;
+use codex_cli::mcp_cmd::run_mcp_logout;
 use codex_cli::proto;
+use codex_cli::sessions_cmd::SessionsCommand;
+use codex_cli::sessions_cmd::run_sessions;
 use codex_common::CliConfigOverrides;
 use codex_exec::Cli as ExecCli;
 use codex_tui::Cli as TuiCli;
+use codex_tui::ResumeArgs;
 use std::path::PathBuf;
 
 use crate::proto::ProtoCli;
@@ -50,14 +59,28 @@ enum Subcommand {
     #[clap(visible_alias = "e")]
     Exec(ExecCli),
 
+    /// Resume a previous interactive session. Without a session id, pick
+    /// from the most recent sessions.
+    Resume(ResumeCommand),
+
+    /// Write the transcript of a recorded session as Markdown, HTML or JSON.
+    Export(ExportCommand),
+
+    /// List, inspect or delete recorded sessions.
+    Sessions(SessionsCommand),
+
     /// Manage login.
     Login(LoginCommand),
 
     /// Remove stored authentication credentials.
     Logout(LogoutCommand),
 
-    /// Experimental: run Codex as an MCP server.
-    Mcp,
+    /// Experimental: run Codex as an MCP server, or manage logins to the
+    /// MCP servers Codex connects to.
+    Mcp(McpCommand),
+
+    /// Inspect and validate the configuration.
+    Config(ConfigCommand),
 
     /// Run the Protocol stream via stdin/stdout
     #[clap(visible_alias = "p")]
@@ -78,6 +101,16 @@ enum Subcommand {
     GenerateTs(GenerateTsCommand),
 }
 
+#[derive(Debug, Parser)]
+struct ResumeCommand {
+    /// Id of the session to resume (shown by `/status`).
+    session_id: Option<String>,
+
+    /// Continue in a new session, leaving the original transcript untouched.
+    #[arg(long = "fork", default_value_t = false)]
+    fork: bool,
+}
+
 #[derive(Debug, Parser)]
 struct CompletionCommand {
     /// Shell to generate completions for
@@ -105,8 +138,16 @@ struct LoginCommand {
     #[clap(skip)]
     config_overrides: CliConfigOverrides,
 
-    #[arg(long = "api-key", value_name = "API_KEY")]
-    api_key: Option<String>,
+    /// Log in with an API key read from stdin, which keeps it out of the
+    /// shell history. The key is checked with the provider before it is
+    /// stored.
+    #[arg(long = "api-key", value_name = "API_KEY", num_args = 0..=1)]
+    api_key: Option<Option<String>>,
+
+    /// Store the API key for this model provider (a key of
+    /// `model_providers`) instead of as the login.
+    #[arg(long = "provider", value_name = "PROVIDER_ID", requires = "api_key")]
+    provider: Option<String>,
 
     #[command(subcommand)]
     action: Option<LoginSubcommand>,
@@ -118,10 +159,58 @@ enum LoginSubcommand {
     Status,
 }
 
+#[derive(Debug, Parser)]
+struct McpCommand {
+    #[clap(skip)]
+    config_overrides: CliConfigOverrides,
+
+    #[command(subcommand)]
+    action: Option<McpSubcommand>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum McpSubcommand {
+    /// Authorize Codex with an MCP server that requires OAuth.
+    Login {
+        /// Name of the server in `mcp_servers`.
+        name: String,
+    },
+
+    /// Remove the stored OAuth tokens for an MCP server.
+    Logout {
+        /// Name of the server in `mcp_servers`.
+        name: String,
+    },
+}
+
+#[derive(Debug, Parser)]
+struct ConfigCommand {
+    #[clap(skip)]
+    config_overrides: CliConfigOverrides,
+
+    #[command(subcommand)]
+    action: ConfigSubcommand,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum ConfigSubcommand {
+    /// Check config.toml and the -c overrides, then print the effective
+    /// configuration with secrets masked.
+    Validate,
+
+    /// Move a ~/.codex left by an earlier version into the platform's
+    /// configuration, state and cache directories.
+    MigrateHome,
+}
+
 #[derive(Debug, Parser)]
 struct LogoutCommand {
     #[clap(skip)]
     config_overrides: CliConfigOverrides,
+
+    /// Remove only the API key stored for this model provider.
+    #[arg(long = "provider", value_name = "PROVIDER_ID")]
+    provider: Option<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -147,19 +236,51 @@ async fn cli_main(codex_linux_sandbox_exe: Option<PathBuf>) -> anyhow::Result<()
 
     match cli.subcommand {
         None => {
+            run_interactive(
+                cli.interactive,
+                cli.config_overrides,
+                codex_linux_sandbox_exe,
+            )
+            .await?;
+        }
+        Some(Subcommand::Resume(ResumeCommand { session_id, fork })) => {
             let mut tui_cli = cli.interactive;
-            prepend_config_flags(&mut tui_cli.config_overrides, cli.config_overrides);
-            let usage = codex_tui::run_main(tui_cli, codex_linux_sandbox_exe).await?;
-            if !usage.is_zero() {
-                println!("{}", codex_core::protocol::FinalOutput::from(usage));
-            }
+            tui_cli.resume = Some(ResumeArgs { session_id, fork });
+            run_interactive(tui_cli, cli.config_overrides, codex_linux_sandbox_exe).await?;
+        }
+        Some(Subcommand::Export(mut export_cli)) => {
+            prepend_config_flags(&mut export_cli.config_overrides, cli.config_overrides);
+            run_export(export_cli)?;
+        }
+        Some(Subcommand::Sessions(mut sessions_cli)) => {
+            prepend_config_flags(&mut sessions_cli.config_overrides, cli.config_overrides);
+            run_sessions(sessions_cli)?;
         }
         Some(Subcommand::Exec(mut exec_cli)) => {
             prepend_config_flags(&mut exec_cli.config_overrides, cli.config_overrides);
             codex_exec::run_main(exec_cli, codex_linux_sandbox_exe).await?;
         }
-        Some(Subcommand::Mcp) => {
-            codex_mcp_server::run_main(codex_linux_sandbox_exe, cli.config_overrides).await?;
+        Some(Subcommand::Mcp(mut mcp_cli)) => {
+            prepend_config_flags(&mut mcp_cli.config_overrides, cli.config_overrides);
+            match mcp_cli.action {
+                Some(McpSubcommand::Login { name }) => {
+                    run_mcp_login(mcp_cli.config_overrides, name).await;
+                }
+                Some(McpSubcommand::Logout { name }) => {
+                    run_mcp_logout(mcp_cli.config_overrides, name).await;
+                }
+                None => {
+                    codex_mcp_server::run_main(codex_linux_sandbox_exe, mcp_cli.config_overrides)
+                        .await?;
+                }
+            }
+        }
+        Some(Subcommand::Config(mut config_cli)) => {
+            prepend_config_flags(&mut config_cli.config_overrides, cli.config_overrides);
+            match config_cli.action {
+                ConfigSubcommand::Validate => run_config_validate(config_cli.config_overrides),
+                ConfigSubcommand::MigrateHome => run_migrate_home(),
+            }
         }
         Some(Subcommand::Login(mut login_cli)) => {
             prepend_config_flags(&mut login_cli.config_overrides, cli.config_overrides);
@@ -169,7 +290,12 @@ async fn cli_main(codex_linux_sandbox_exe: Option<PathBuf>) -> anyhow::Result<()
                 }
                 None => {
                     if let Some(api_key) = login_cli.api_key {
-                        run_login_with_api_key(login_cli.config_overrides, api_key).await;
+                        run_login_with_api_key(
+                            login_cli.config_overrides,
+                            api_key,
+                            login_cli.provider,
+                        )
+                        .await;
                     } else {
                         run_login_with_chatgpt(login_cli.config_overrides).await;
                     }
@@ -178,7 +304,7 @@ async fn cli_main(codex_linux_sandbox_exe: Option<PathBuf>) -> anyhow::Result<()
         }
         Some(Subcommand::Logout(mut logout_cli)) => {
             prepend_config_flags(&mut logout_cli.config_overrides, cli.config_overrides);
-            run_logout(logout_cli.config_overrides).await;
+            run_logout(logout_cli.config_overrides, logout_cli.provider).await;
         }
         Some(Subcommand::Proto(mut proto_cli)) => {
             prepend_config_flags(&mut proto_cli.config_overrides, cli.config_overrides);
@@ -217,6 +343,19 @@ async fn cli_main(codex_linux_sandbox_exe: Option<PathBuf>) -> anyhow::Result<()
     Ok(())
 }
 
+async fn run_interactive(
+    mut tui_cli: TuiCli,
+    config_overrides: CliConfigOverrides,
+    codex_linux_sandbox_exe: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    prepend_config_flags(&mut tui_cli.config_overrides, config_overrides);
+    let usage = codex_tui::run_main(tui_cli, codex_linux_sandbox_exe).await?;
+    if !usage.is_zero() {
+        println!("{}", codex_core::protocol::FinalOutput::from(usage));
+    }
+    Ok(())
+}
+
 /// Prepend root-level overrides so they have lower precedence than
 /// CLI-specific ones specified after the subcommand (if any).
 fn prepend_config_flags(
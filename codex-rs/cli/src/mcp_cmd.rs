@@ -0,0 +1,77 @@
+use codex_common::CliConfigOverrides;
+use codex_core::config_types::McpServerTransport;
+use codex_mcp_client::OAuthCredentialStore;
+use codex_mcp_client::OAuthLoginOptions;
+use codex_mcp_client::perform_oauth_login;
+
+use crate::login::load_config_or_exit;
+
+pub async fn run_mcp_login(cli_config_overrides: CliConfigOverrides, server_name: String) -> ! {
+    let config = load_config_or_exit(cli_config_overrides);
+
+    let (url, oauth) = match config
+        .mcp_servers
+        .get(&server_name)
+        .map(|cfg| &cfg.transport)
+    {
+        Some(McpServerTransport::StreamableHttp { url, oauth }) => (url.clone(), oauth.clone()),
+        Some(McpServerTransport::Stdio { .. }) => {
+            eprintln!("MCP server `{server_name}` is launched locally and does not use OAuth");
+            std::process::exit(1);
+        }
+        None => {
+            eprintln!("No MCP server named `{server_name}` in config.toml");
+            std::process::exit(1);
+        }
+    };
+
+    let options = OAuthLoginOptions {
+        client_id: oauth.client_id,
+        client_secret: oauth.client_secret,
+        scopes: oauth.scopes,
+        callback_port: oauth.callback_port,
+        open_browser: true,
+    };
+    let result = perform_oauth_login(
+        &server_name,
+        &url,
+        config.dirs.config_dir(),
+        options,
+        |authorize_url| {
+            eprintln!(
+                "If your browser did not open, navigate to this URL to authorize Codex:\n\n{authorize_url}\n"
+            );
+        },
+    )
+    .await;
+
+    match result {
+        Ok(()) => {
+            eprintln!("Successfully logged in to `{server_name}`");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error logging in to `{server_name}`: {e:#}");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub async fn run_mcp_logout(cli_config_overrides: CliConfigOverrides, server_name: String) -> ! {
+    let config = load_config_or_exit(cli_config_overrides);
+
+    match OAuthCredentialStore::new(config.dirs.config_dir()).remove(&server_name) {
+        Ok(true) => {
+            eprintln!("Removed stored credentials for `{server_name}`");
+            std::process::exit(0);
+        }
+        Ok(false) => {
+            eprintln!("No stored credentials for `{server_name}`");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error removing credentials for `{server_name}`: {e:#}");
+            std::process::exit(1);
+        }
+    }
+}
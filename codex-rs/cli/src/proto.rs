@@ -38,7 +38,7 @@ pub async fn run_main(opts: ProtoCli) -> anyhow::Result<()> {
     let config = Config::load_with_cli_overrides(overrides_vec, ConfigOverrides::default())?;
     // Use conversation_manager API to start a conversation
     let conversation_manager = ConversationManager::new(AuthManager::shared(
-        config.codex_home.clone(),
+        config.dirs.config_dir().to_path_buf(),
         config.preferred_auth_method,
     ));
     let NewConversation {
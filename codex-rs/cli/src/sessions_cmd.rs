@@ -0,0 +1,367 @@
+//! `codex sessions`: list, inspect and delete recorded sessions.
+
+use std::time::Duration;
+use std::time::SystemTime;
+
+use clap::Parser;
+use codex_common::CliConfigOverrides;
+use codex_core::config::Config;
+use codex_core::config::ConfigOverrides;
+use codex_core::model_pricing::format_usd;
+use codex_core::session_index::SessionIndexEntry;
+use codex_core::session_index::delete_sessions;
+use codex_core::session_index::list_sessions;
+
+/// Longest last message shown by `codex sessions list`, in characters.
+const LIST_MESSAGE_CHARS: usize = 60;
+
+#[derive(Debug, Parser)]
+pub struct SessionsCommand {
+    #[clap(skip)]
+    pub config_overrides: CliConfigOverrides,
+
+    #[command(subcommand)]
+    pub action: SessionsSubcommand,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SessionsSubcommand {
+    /// List recorded sessions, most recent first.
+    List {
+        /// Show at most this many sessions.
+        #[arg(short = 'n', long = "limit", default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Show what a session did and the tokens it used.
+    Show {
+        /// Id of the session, or enough of its start to be unambiguous.
+        session_id: String,
+    },
+
+    /// Delete recorded sessions.
+    Delete {
+        /// Id of the session, or enough of its start to be unambiguous.
+        #[arg(required_unless_present = "older_than", conflicts_with = "older_than")]
+        session_id: Option<String>,
+
+        /// Delete every session not written to for this long, e.g. `30d`,
+        /// `12h` or `2w`.
+        #[arg(long = "older-than", value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<Duration>,
+
+        /// List the sessions that would be deleted without deleting them.
+        #[arg(long = "dry-run", default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+pub fn run_sessions(cmd: SessionsCommand) -> anyhow::Result<()> {
+    let overrides = cmd
+        .config_overrides
+        .parse_overrides()
+        .map_err(anyhow::Error::msg)?;
+    let config = Config::load_with_cli_overrides(overrides, ConfigOverrides::default())?;
+    let state_dir = config.dirs.state_dir();
+    let sessions = list_sessions(state_dir)?;
+
+    match cmd.action {
+        SessionsSubcommand::List { limit } => {
+            if sessions.is_empty() {
+                println!("No recorded sessions.");
+            } else {
+                print!("{}", render_table(&sessions[..limit.min(sessions.len())]));
+            }
+        }
+        SessionsSubcommand::Show { session_id } => {
+            print!("{}", render_details(find_session(&sessions, &session_id)?));
+        }
+        SessionsSubcommand::Delete {
+            session_id,
+            older_than,
+            dry_run,
+        } => {
+            let doomed: Vec<&SessionIndexEntry> = match (session_id, older_than) {
+                (Some(session_id), _) => vec![find_session(&sessions, &session_id)?],
+                (None, Some(age)) => {
+                    let cutoff = SystemTime::now()
+                        .checked_sub(age)
+                        .ok_or_else(|| anyhow::anyhow!("--older-than is too far back"))?;
+                    sessions
+                        .iter()
+                        .filter(|session| {
+                            std::fs::metadata(&session.path)
+                                .and_then(|metadata| metadata.modified())
+                                .is_ok_and(|modified| modified < cutoff)
+                        })
+                        .collect()
+                }
+                (None, None) => anyhow::bail!("give a session id or --older-than"),
+            };
+            for session in &doomed {
+                println!("{}  {}", session.id, session.path.display());
+            }
+            if dry_run {
+                println!("Would delete {}.", count_sessions(doomed.len()));
+            } else {
+                let paths = doomed
+                    .iter()
+                    .map(|session| session.path.clone())
+                    .collect::<Vec<_>>();
+                delete_sessions(state_dir, &paths)?;
+                println!("Deleted {}.", count_sessions(doomed.len()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn count_sessions(count: usize) -> String {
+    match count {
+        1 => "1 session".to_string(),
+        n => format!("{n} sessions"),
+    }
+}
+
+/// The session whose id is `id` or starts with it.
+fn find_session<'a>(
+    sessions: &'a [SessionIndexEntry],
+    id: &str,
+) -> anyhow::Result<&'a SessionIndexEntry> {
+    let id = id.to_ascii_lowercase();
+    let matches = sessions
+        .iter()
+        .filter(|session| session.id.to_string().starts_with(&id))
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [session] => Ok(*session),
+        [] => anyhow::bail!("no recorded session with id {id}"),
+        _ => anyhow::bail!(
+            "{} match `{id}`; give more of the id",
+            count_sessions(matches.len())
+        ),
+    }
+}
+
+/// Parses an age such as `30d`: a number followed by `s`, `m`, `h`, `d` or
+/// `w`.
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid age `{age}`; use e.g. 30d, 12h or 2w");
+    let unit_start = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (count, unit) = age.split_at(unit_start);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    count
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// "2025-08-01T10:00:00.000Z" -> "2025-08-01 10:00"
+fn short_timestamp(timestamp: &str) -> String {
+    timestamp.get(..16).unwrap_or(timestamp).replace('T', " ")
+}
+
+fn first_line(text: &str, max_chars: usize) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+fn render_table(sessions: &[SessionIndexEntry]) -> String {
+    let rows = sessions
+        .iter()
+        .map(|session| {
+            let message = session
+                .last_user_message
+                .as_deref()
+                .map(|message| first_line(message, LIST_MESSAGE_CHARS))
+                .unwrap_or_default();
+            let message = match &session.problem {
+                Some(problem) => format!("[{problem}] {message}").trim_end().to_string(),
+                None => message,
+            };
+            [
+                session.id.to_string()[..8].to_string(),
+                short_timestamp(&session.timestamp),
+                session
+                    .cwd
+                    .as_ref()
+                    .map(|cwd| cwd.display().to_string())
+                    .unwrap_or_default(),
+                session.model.clone().unwrap_or_default(),
+                session.turns.to_string(),
+                message,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["ID", "STARTED", "CWD", "MODEL", "TURNS", "LAST MESSAGE"];
+    let mut widths = header.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in std::iter::once(header.map(String::from)).chain(rows) {
+        let cells = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn render_details(session: &SessionIndexEntry) -> String {
+    let usage = &session.token_usage;
+    let mut fields = vec![
+        ("Session", session.id.to_string()),
+        ("Started", short_timestamp(&session.timestamp)),
+    ];
+    if let Some(cwd) = &session.cwd {
+        fields.push(("Directory", cwd.display().to_string()));
+    }
+    if let Some(model) = &session.model {
+        fields.push(("Model", model.clone()));
+    }
+    fields.push(("Turns", session.turns.to_string()));
+    let mut tokens = format!("{} total ({} input", usage.total_tokens, usage.input_tokens);
+    if let Some(cached) = usage.cached_input_tokens {
+        tokens.push_str(&format!(", {cached} cached"));
+    }
+    tokens.push_str(&format!(", {} output", usage.output_tokens));
+    if let Some(reasoning) = usage.reasoning_output_tokens {
+        tokens.push_str(&format!(", {reasoning} reasoning"));
+    }
+    tokens.push(')');
+    fields.push(("Tokens", tokens));
+    fields.push((
+        "Cost",
+        session
+            .cost_usd
+            .map(format_usd)
+            .unwrap_or_else(|| "unknown".to_string()),
+    ));
+    if let Some(message) = &session.first_user_message {
+        fields.push(("First message", first_line(message, LIST_MESSAGE_CHARS)));
+    }
+    if let Some(message) = &session.last_user_message {
+        fields.push(("Last message", first_line(message, LIST_MESSAGE_CHARS)));
+    }
+    fields.push(("File", session.path.display().to_string()));
+    if let Some(problem) = &session.problem {
+        fields.push(("Problem", problem.clone()));
+    }
+
+    let width = fields
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    fields
+        .into_iter()
+        .map(|(name, value)| format!("{name:<width$}  {value}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codex_core::protocol::TokenUsage;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn session(id: &str, problem: Option<&str>) -> SessionIndexEntry {
+        SessionIndexEntry {
+            id: Uuid::parse_str(id).unwrap(),
+            path: PathBuf::from(format!("/state/sessions/rollout-{id}.jsonl")),
+            timestamp: "2025-08-01T10:00:00.000Z".to_string(),
+            cwd: Some(PathBuf::from("/work/app")),
+            model: Some("o3".to_string()),
+            turns: 2,
+            first_user_message: Some("fix the build".to_string()),
+            last_user_message: Some("now add a test\nfor the parser".to_string()),
+            token_usage: TokenUsage {
+                input_tokens: 10,
+                cached_input_tokens: Some(4),
+                output_tokens: 5,
+                reasoning_output_tokens: None,
+                total_tokens: 15,
+            },
+            cost_usd: Some(0.5),
+            rollout_len: 100,
+            problem: problem.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn ages_have_a_unit() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+        for age in ["30", "d", "30x", "-1d", "1.5d", ""] {
+            assert!(parse_age(age).is_err(), "{age:?}");
+        }
+    }
+
+    #[test]
+    fn sessions_are_found_by_id_prefix() {
+        let sessions = [
+            session("5973b6c0-94b8-487b-a530-2aeb6098ae0e", None),
+            session("5973b7aa-0000-487b-a530-2aeb6098ae0e", None),
+        ];
+        assert_eq!(
+            find_session(&sessions, "5973B6").unwrap().id,
+            sessions[0].id
+        );
+        assert_eq!(
+            find_session(&sessions, "5973").unwrap_err().to_string(),
+            "2 sessions match `5973`; give more of the id"
+        );
+        assert!(find_session(&sessions, "ffff").is_err());
+    }
+
+    #[test]
+    fn table_flags_damaged_sessions() {
+        let sessions = [
+            session("5973b6c0-94b8-487b-a530-2aeb6098ae0e", None),
+            session(
+                "0b1c2d3e-94b8-487b-a530-2aeb6098ae0e",
+                Some("1 unreadable entry"),
+            ),
+        ];
+        assert_eq!(
+            render_table(&sessions),
+            "\
+ID        STARTED           CWD        MODEL  TURNS  LAST MESSAGE
+5973b6c0  2025-08-01 10:00  /work/app  o3     2      now add a test
+0b1c2d3e  2025-08-01 10:00  /work/app  o3     2      [1 unreadable entry] now add a test
+"
+        );
+    }
+
+    #[test]
+    fn details_include_token_totals() {
+        let details = render_details(&session("5973b6c0-94b8-487b-a530-2aeb6098ae0e", None));
+        assert!(
+            details.contains("Tokens         15 total (10 input, 4 cached, 5 output)\n"),
+            "{details}"
+        );
+        assert!(details.contains("Cost           $0.50\n"), "{details}");
+    }
+}
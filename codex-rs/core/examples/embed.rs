@@ -0,0 +1,53 @@
+//! Runs one turn through the embedding API and prints what the agent says.
+//!
+//! ```text
+//! cargo run -p codex-core --example embed -- "explain this repository"
+//! ```
+//!
+//! Commands run in a read-only sandbox; anything that needs more is denied.
+
+use codex_core::AskForApproval;
+use codex_core::CodexBuilder;
+use codex_core::SandboxPolicy;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::ReviewDecision;
+use futures::StreamExt;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let prompt = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "Summarize the README in this directory.".to_string());
+
+    let codex = CodexBuilder::new()
+        .cwd(std::env::current_dir()?)
+        .sandbox_policy(SandboxPolicy::new_read_only_policy())
+        .approval_policy(AskForApproval::OnRequest)
+        .user_instructions("Answer in at most three sentences.")
+        .build()
+        .await?;
+
+    {
+        let mut events = std::pin::pin!(codex.submit(prompt).await?);
+        while let Some(event) = events.next().await {
+            match event.msg {
+                EventMsg::AgentMessage(ev) => println!("{}", ev.message),
+                EventMsg::ExecApprovalRequest(ev) => {
+                    eprintln!("denying `{}`", ev.command.join(" "));
+                    codex.approve(event.id, ReviewDecision::Denied).await?;
+                }
+                EventMsg::ApplyPatchApprovalRequest(_) => {
+                    codex.approve(event.id, ReviewDecision::Denied).await?;
+                }
+                EventMsg::TokenCount(usage) => {
+                    eprintln!("tokens used: {}", usage.total_tokens);
+                }
+                EventMsg::Error(ev) => eprintln!("error: {}", ev.message),
+                _ => {}
+            }
+        }
+    }
+
+    codex.shutdown().await?;
+    Ok(())
+}
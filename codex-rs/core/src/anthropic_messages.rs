@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use bytes::Bytes;
+use eventsource_stream::Eventsource;
+use futures::Stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use serde_json::Value;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::Instrument;
+use tracing::debug;
+use tracing::trace;
+
+use crate::ModelProviderInfo;
+use crate::cassette;
+use crate::cassette::Cassette;
+use crate::client_common::Prompt;
+use crate::client_common::ResponseEvent;
+use crate::client_common::ResponseStream;
+use crate::client_common::StreamMetrics;
+use crate::client_common::spawn_response_task;
+use crate::error::CodexErr;
+use crate::error::ProviderErrorDetails;
+use crate::error::Result;
+use crate::error::http_error;
+use crate::model_family::ModelFamily;
+use crate::openai_tools::create_tools_json_for_anthropic_api;
+use crate::otel;
+use crate::protocol::TokenUsage;
+use crate::util::parse_retry_after;
+use codex_protocol::models::ContentItem;
+use codex_protocol::models::ResponseItem;
+
+/// Value sent in the `anthropic-version` header on every request.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires `max_tokens`; used when the user has not
+/// configured `model_max_output_tokens`.
+const DEFAULT_MAX_TOKENS: u64 = 8192;
+
+/// Implementation for the Anthropic Messages API.
+pub(crate) async fn stream_anthropic_messages(
+    prompt: &Prompt,
+    model_family: &ModelFamily,
+    max_output_tokens: Option<u64>,
+    client: &reqwest::Client,
+    provider: &ModelProviderInfo,
+    cassette: Option<&Cassette>,
+) -> Result<ResponseStream> {
+    let full_instructions = prompt.get_full_instructions(model_family)?;
+    let messages = build_messages(prompt.get_formatted_input());
+    let tools_json = create_tools_json_for_anthropic_api(&prompt.tools)?;
+
+    let mut payload = json!({
+        "model": model_family.slug,
+        "system": full_instructions,
+        "messages": messages,
+        "max_tokens": max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        "stream": true,
+    });
+    if !tools_json.is_empty()
+        && let Some(obj) = payload.as_object_mut()
+    {
+        obj.insert("tools".to_string(), json!(tools_json));
+    }
+    // The Messages API has no `seed` parameter, so only temperature and
+    // top_p are forwarded.
+    if let Some(obj) = payload.as_object_mut() {
+        if let Some(temperature) = provider.temperature {
+            obj.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(top_p) = provider.top_p {
+            obj.insert("top_p".to_string(), json!(top_p));
+        }
+    }
+
+    debug!(
+        "POST to {}: {}",
+        provider.get_full_url(&None),
+        serde_json::to_string_pretty(&payload).unwrap_or_default()
+    );
+
+    let started = Instant::now();
+    let mut attempt = 0;
+    let max_retries = provider.request_max_retries();
+    loop {
+        attempt += 1;
+
+        let req_builder = provider.create_request_builder(client, &None).await?;
+
+        let http_span = otel::http_request_span(attempt);
+        let req_builder = req_builder
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .json(&payload);
+        let res = cassette::send(cassette, req_builder)
+            .instrument(http_span.clone())
+            .await?;
+        otel::record_http_result(&http_span, &res);
+
+        match res {
+            Ok(resp) if resp.status().is_success() => {
+                let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+                let stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
+                spawn_response_task(
+                    tx_event.clone(),
+                    process_anthropic_sse(stream, tx_event, provider.stream_idle_timeout()),
+                );
+                let metrics = Arc::new(StreamMetrics::new(started));
+                metrics.add_retries(attempt - 1);
+                return Ok(ResponseStream::new(rx_event, metrics));
+            }
+            Ok(res) => {
+                let status = res.status();
+                if !provider.should_retry_status(status) {
+                    let body = (res.text().await).unwrap_or_default();
+                    return Err(http_error(status, body));
+                }
+
+                if attempt > max_retries {
+                    return Err(CodexErr::RetryLimit(status));
+                }
+
+                let retry_after = parse_retry_after(res.headers());
+                let delay = provider.request_retry_delay(attempt, retry_after);
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => {
+                if attempt > max_retries {
+                    return Err(e.into());
+                }
+                let delay = provider.request_retry_delay(attempt, None);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Converts the conversation history into Anthropic `messages`. The API only
+/// accepts alternating `user`/`assistant` turns, so consecutive items for the
+/// same role are merged into a single message with several content blocks.
+fn build_messages<'a>(input: impl IntoIterator<Item = &'a ResponseItem>) -> Vec<Value> {
+    let mut messages: Vec<Value> = Vec::new();
+    let mut push_block = |role: &str, block: Value| {
+        if let Some(last) = messages.last_mut()
+            && last.get("role").and_then(Value::as_str) == Some(role)
+            && let Some(content) = last.get_mut("content").and_then(Value::as_array_mut)
+        {
+            content.push(block);
+            return;
+        }
+        messages.push(json!({"role": role, "content": [block]}));
+    };
+
+    for item in input {
+        match item {
+            ResponseItem::Message { role, content, .. } => {
+                let role = if role == "assistant" {
+                    "assistant"
+                } else {
+                    "user"
+                };
+                for c in content {
+                    let block = match c {
+                        ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                            if text.is_empty() {
+                                continue;
+                            }
+                            json!({"type": "text", "text": text})
+                        }
+                        ContentItem::InputImage { image_url } => image_block(image_url),
+                    };
+                    push_block(role, block);
+                }
+            }
+            ResponseItem::FunctionCall {
+                name,
+                arguments,
+                call_id,
+                ..
+            } => {
+                let input = serde_json::from_str::<Value>(arguments)
+                    .ok()
+                    .filter(Value::is_object)
+                    .unwrap_or_else(|| json!({}));
+                push_block(
+                    "assistant",
+                    json!({
+                        "type": "tool_use",
+                        "id": call_id,
+                        "name": name,
+                        "input": input,
+                    }),
+                );
+            }
+            ResponseItem::FunctionCallOutput { call_id, output } => {
+                push_block(
+                    "user",
+                    json!({
+                        "type": "tool_result",
+                        "tool_use_id": call_id,
+                        "content": output.content,
+                        "is_error": output.success == Some(false),
+                    }),
+                );
+            }
+            ResponseItem::CustomToolCall {
+                call_id,
+                name,
+                input,
+                ..
+            } => {
+                push_block(
+                    "assistant",
+                    json!({
+                        "type": "tool_use",
+                        "id": call_id,
+                        "name": name,
+                        "input": {"input": input},
+                    }),
+                );
+            }
+            ResponseItem::CustomToolCallOutput { call_id, output } => {
+                push_block(
+                    "user",
+                    json!({
+                        "type": "tool_result",
+                        "tool_use_id": call_id,
+                        "content": output,
+                    }),
+                );
+            }
+            ResponseItem::LocalShellCall { .. }
+            | ResponseItem::Reasoning { .. }
+            | ResponseItem::WebSearchCall { .. }
+            | ResponseItem::Other => {
+                // Omit these items from the conversation history.
+                continue;
+            }
+        }
+    }
+
+    messages
+}
+
+/// Maps an image URL onto an Anthropic image block. Inline `data:` URLs are
+/// sent as base64 sources; anything else is passed by reference.
+fn image_block(image_url: &str) -> Value {
+    if let Some(rest) = image_url.strip_prefix("data:")
+        && let Some((media_type, data)) = rest.split_once(";base64,")
+    {
+        return json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": media_type, "data": data},
+        });
+    }
+    json!({
+        "type": "image",
+        "source": {"type": "url", "url": image_url},
+    })
+}
+
+/// A content block that is still being streamed, keyed by its `index`.
+enum ContentBlock {
+    Text(String),
+    ToolUse {
+        id: String,
+        name: String,
+        input_json: String,
+    },
+}
+
+/// SSE processor for the Anthropic Messages streaming format. Text and
+/// `tool_use` blocks are mapped onto the same [`ResponseEvent`]s the
+/// Responses API produces, so the rest of the pipeline needs no special
+/// handling for this provider.
+async fn process_anthropic_sse<S>(
+    stream: S,
+    tx_event: mpsc::Sender<Result<ResponseEvent>>,
+    idle_timeout: Duration,
+) where
+    S: Stream<Item = Result<Bytes>> + Unpin,
+{
+    let mut stream = stream.eventsource();
+
+    let mut blocks: HashMap<u64, ContentBlock> = HashMap::new();
+    let mut response_id = String::new();
+    let mut usage = AnthropicUsage::default();
+
+    loop {
+        let sse = match timeout(idle_timeout, stream.next()).await {
+            Ok(Some(Ok(ev))) => ev,
+            Ok(Some(Err(e))) => {
+                let _ = tx_event
+                    .send(Err(CodexErr::stream_disconnected(e.to_string())))
+                    .await;
+                return;
+            }
+            Ok(None) => {
+                let _ = tx_event
+                    .send(Err(CodexErr::stream_disconnected(
+                        "stream closed before message_stop",
+                    )))
+                    .await;
+                return;
+            }
+            Err(_) => {
+                let _ = tx_event
+                    .send(Err(CodexErr::stream_disconnected(
+                        "idle timeout waiting for SSE",
+                    )))
+                    .await;
+                return;
+            }
+        };
+
+        let event: Value = match serde_json::from_str(&sse.data) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        trace!("anthropic received SSE event: {event:?}");
+
+        let index = event.get("index").and_then(Value::as_u64).unwrap_or(0);
+        match event
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+        {
+            "message_start" => {
+                if let Some(message) = event.get("message") {
+                    if let Some(id) = message.get("id").and_then(Value::as_str) {
+                        response_id = id.to_string();
+                    }
+                    if let Some(u) = message.get("usage") {
+                        usage.update(u);
+                    }
+                }
+                let _ = tx_event.send(Ok(ResponseEvent::Created)).await;
+                let _ = tx_event
+                    .send(Ok(ResponseEvent::UsageUpdate(usage.into())))
+                    .await;
+            }
+            "content_block_start" => {
+                let Some(block) = event.get("content_block") else {
+                    continue;
+                };
+                match block.get("type").and_then(Value::as_str) {
+                    Some("text") => {
+                        blocks.insert(index, ContentBlock::Text(String::new()));
+                    }
+                    Some("tool_use") => {
+                        let field = |name: &str| {
+                            block
+                                .get(name)
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_string()
+                        };
+                        blocks.insert(
+                            index,
+                            ContentBlock::ToolUse {
+                                id: field("id"),
+                                name: field("name"),
+                                input_json: String::new(),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_delta" => {
+                let Some(delta) = event.get("delta") else {
+                    continue;
+                };
+                match (
+                    blocks.get_mut(&index),
+                    delta.get("type").and_then(Value::as_str),
+                ) {
+                    (Some(ContentBlock::Text(text)), Some("text_delta")) => {
+                        if let Some(t) = delta.get("text").and_then(Value::as_str) {
+                            text.push_str(t);
+                            let _ = tx_event
+                                .send(Ok(ResponseEvent::OutputTextDelta(t.to_string())))
+                                .await;
+                        }
+                    }
+                    (
+                        Some(ContentBlock::ToolUse { id, input_json, .. }),
+                        Some("input_json_delta"),
+                    ) => {
+                        if let Some(partial) = delta.get("partial_json").and_then(Value::as_str) {
+                            input_json.push_str(partial);
+                            let _ = tx_event
+                                .send(Ok(ResponseEvent::FunctionCallArgumentsDelta {
+                                    call_id: id.clone(),
+                                    delta: partial.to_string(),
+                                }))
+                                .await;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_stop" => {
+                let item = match blocks.remove(&index) {
+                    Some(ContentBlock::Text(text)) => ResponseItem::Message {
+                        id: None,
+                        role: "assistant".to_string(),
+                        content: vec![ContentItem::OutputText { text }],
+                    },
+                    Some(ContentBlock::ToolUse {
+                        id,
+                        name,
+                        input_json,
+                    }) => ResponseItem::FunctionCall {
+                        id: None,
+                        name,
+                        // A tool call without arguments streams no deltas.
+                        arguments: if input_json.is_empty() {
+                            "{}".to_string()
+                        } else {
+                            input_json
+                        },
+                        call_id: id,
+                    },
+                    None => continue,
+                };
+                let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
+            }
+            "message_delta" => {
+                if let Some(u) = event.get("usage") {
+                    usage.update(u);
+                    let _ = tx_event
+                        .send(Ok(ResponseEvent::UsageUpdate(usage.into())))
+                        .await;
+                }
+            }
+            "message_stop" => {
+                let _ = tx_event
+                    .send(Ok(ResponseEvent::Completed {
+                        response_id,
+                        token_usage: Some(usage.into()),
+                    }))
+                    .await;
+                return;
+            }
+            "error" => {
+                let error = event
+                    .get("error")
+                    .cloned()
+                    .and_then(|e| serde_json::from_value::<ProviderErrorDetails>(e).ok())
+                    .unwrap_or_default();
+                let error = ProviderErrorDetails {
+                    message: error.message.or_else(|| Some("unknown error".to_string())),
+                    ..error
+                };
+                let _ = tx_event.send(Err(error.into_stream_error())).await;
+                return;
+            }
+            // `ping` and any event types added in the future.
+            _ => {}
+        }
+    }
+}
+
+/// Running totals from the `usage` objects of `message_start` and
+/// `message_delta`. Later values replace earlier ones.
+#[derive(Debug, Default, Clone, Copy)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    cache_creation_input_tokens: u64,
+    cache_read_input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl AnthropicUsage {
+    fn update(&mut self, usage: &Value) {
+        let fields = [
+            ("input_tokens", &mut self.input_tokens),
+            (
+                "cache_creation_input_tokens",
+                &mut self.cache_creation_input_tokens,
+            ),
+            ("cache_read_input_tokens", &mut self.cache_read_input_tokens),
+            ("output_tokens", &mut self.output_tokens),
+        ];
+        for (key, value) in fields {
+            if let Some(v) = usage.get(key).and_then(Value::as_u64) {
+                *value = v;
+            }
+        }
+    }
+}
+
+impl From<AnthropicUsage> for TokenUsage {
+    fn from(usage: AnthropicUsage) -> Self {
+        // Anthropic reports cached input separately, whereas `TokenUsage`
+        // follows OpenAI in counting it as part of `input_tokens`.
+        let input_tokens =
+            usage.input_tokens + usage.cache_creation_input_tokens + usage.cache_read_input_tokens;
+        TokenUsage {
+            input_tokens,
+            cached_input_tokens: Some(usage.cache_read_input_tokens),
+            output_tokens: usage.output_tokens,
+            reasoning_output_tokens: None,
+            total_tokens: input_tokens + usage.output_tokens,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codex_protocol::models::FunctionCallOutputPayload;
+    use pretty_assertions::assert_eq;
+    use tokio_util::io::ReaderStream;
+
+    async fn run_sse(events: Vec<Value>) -> Vec<Result<ResponseEvent>> {
+        let mut body = String::new();
+        for e in events {
+            let kind = e
+                .get("type")
+                .and_then(|v| v.as_str())
+                .expect("fixture event missing type");
+            body.push_str(&format!("event: {kind}\ndata: {e}\n\n"));
+        }
+
+        let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let stream = ReaderStream::new(std::io::Cursor::new(body)).map_err(CodexErr::Io);
+        tokio::spawn(process_anthropic_sse(stream, tx, Duration::from_secs(5)));
+
+        let mut out = Vec::new();
+        while let Some(ev) = rx.recv().await {
+            out.push(ev);
+        }
+        out
+    }
+
+    #[test]
+    fn build_messages_merges_roles_and_maps_tool_calls() {
+        let input = vec![
+            ResponseItem::Message {
+                id: None,
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "<environment_context/>".to_string(),
+                }],
+            },
+            ResponseItem::Message {
+                id: None,
+                role: "user".to_string(),
+                content: vec![
+                    ContentItem::InputText {
+                        text: "what's here?".to_string(),
+                    },
+                    ContentItem::InputImage {
+                        image_url: "data:image/png;base64,AAAA".to_string(),
+                    },
+                ],
+            },
+            ResponseItem::Message {
+                id: None,
+                role: "assistant".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: "Let me look.".to_string(),
+                }],
+            },
+            ResponseItem::FunctionCall {
+                id: None,
+                name: "shell".to_string(),
+                arguments: r#"{"command":["ls"]}"#.to_string(),
+                call_id: "toolu_1".to_string(),
+            },
+            ResponseItem::FunctionCallOutput {
+                call_id: "toolu_1".to_string(),
+                output: FunctionCallOutputPayload {
+                    content: "README.md".to_string(),
+                    success: Some(true),
+                },
+            },
+        ];
+
+        assert_eq!(
+            build_messages(&input),
+            vec![
+                json!({"role": "user", "content": [
+                    {"type": "text", "text": "<environment_context/>"},
+                    {"type": "text", "text": "what's here?"},
+                    {"type": "image", "source": {
+                        "type": "base64", "media_type": "image/png", "data": "AAAA",
+                    }},
+                ]}),
+                json!({"role": "assistant", "content": [
+                    {"type": "text", "text": "Let me look."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "shell",
+                     "input": {"command": ["ls"]}},
+                ]}),
+                json!({"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1",
+                     "content": "README.md", "is_error": false},
+                ]}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_text_and_tool_use_events() {
+        let events = run_sse(vec![
+            json!({"type": "message_start", "message": {
+                "id": "msg_1",
+                "usage": {"input_tokens": 100, "cache_read_input_tokens": 20, "output_tokens": 1},
+            }}),
+            json!({"type": "content_block_start", "index": 0,
+                   "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "ping"}),
+            json!({"type": "content_block_delta", "index": 0,
+                   "delta": {"type": "text_delta", "text": "Hel"}}),
+            json!({"type": "content_block_delta", "index": 0,
+                   "delta": {"type": "text_delta", "text": "lo"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {
+                "type": "tool_use", "id": "toolu_1", "name": "shell", "input": {},
+            }}),
+            json!({"type": "content_block_delta", "index": 1,
+                   "delta": {"type": "input_json_delta", "partial_json": "{\"command\":"}}),
+            json!({"type": "content_block_delta", "index": 1,
+                   "delta": {"type": "input_json_delta", "partial_json": "[\"ls\"]}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"},
+                   "usage": {"output_tokens": 30}}),
+            json!({"type": "message_stop"}),
+        ])
+        .await;
+
+        let events: Vec<ResponseEvent> = events.into_iter().map(|e| e.unwrap()).collect();
+        assert_eq!(events.len(), 10, "unexpected events: {events:?}");
+        assert!(matches!(events[0], ResponseEvent::Created));
+        assert!(matches!(&events[1], ResponseEvent::UsageUpdate(u) if u.input_tokens == 120));
+        assert!(matches!(&events[2], ResponseEvent::OutputTextDelta(d) if d == "Hel"));
+        assert!(matches!(&events[3], ResponseEvent::OutputTextDelta(d) if d == "lo"));
+        match &events[4] {
+            ResponseEvent::OutputItemDone(ResponseItem::Message { role, content, .. }) => {
+                assert_eq!(role, "assistant");
+                assert_eq!(
+                    content,
+                    &vec![ContentItem::OutputText {
+                        text: "Hello".to_string()
+                    }]
+                );
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(
+            &events[5],
+            ResponseEvent::FunctionCallArgumentsDelta { call_id, .. } if call_id == "toolu_1"
+        ));
+        match &events[7] {
+            ResponseEvent::OutputItemDone(ResponseItem::FunctionCall {
+                name,
+                arguments,
+                call_id,
+                ..
+            }) => {
+                assert_eq!(name, "shell");
+                assert_eq!(arguments, r#"{"command":["ls"]}"#);
+                assert_eq!(call_id, "toolu_1");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(&events[8], ResponseEvent::UsageUpdate(u) if u.output_tokens == 30));
+        match &events[9] {
+            ResponseEvent::Completed {
+                response_id,
+                token_usage,
+            } => {
+                assert_eq!(response_id, "msg_1");
+                let usage = token_usage.as_ref().expect("usage reported");
+                assert_eq!(usage.input_tokens, 120);
+                assert_eq!(usage.cached_input_tokens, Some(20));
+                assert_eq!(usage.output_tokens, 30);
+                assert_eq!(usage.total_tokens, 150);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_event_fails_the_stream() {
+        let events = run_sse(vec![
+            json!({"type": "message_start", "message": {"id": "msg_1"}}),
+            json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}),
+        ])
+        .await;
+
+        assert_eq!(events.len(), 3);
+        match &events[2] {
+            Err(CodexErr::StreamDisconnected { message, .. }) => assert_eq!(message, "Overloaded"),
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use crate::codex::ApprovalResponse;
+use crate::codex::FIRST_WRITE_CONFIRMATION_REASON;
 use crate::codex::Session;
 use crate::codex::TurnContext;
 use crate::protocol::FileChange;
+use crate::protocol::HunkRange;
+use crate::protocol::PatchMatchStrategy;
+use crate::protocol::PatchSelection;
 use crate::protocol::ReviewDecision;
 use crate::safety::SafetyCheck;
 use crate::safety::assess_patch_safety;
+use crate::safety::paths_outside_writable_roots;
+use crate::safety::write_exemption_roots;
 use codex_apply_patch::ApplyPatchAction;
 use codex_apply_patch::ApplyPatchFileChange;
+use codex_apply_patch::MatchStrategy;
 use codex_protocol::models::FunctionCallOutputPayload;
 use codex_protocol::models::ResponseInputItem;
 use std::collections::HashMap;
@@ -23,14 +31,20 @@ pub(crate) enum InternalApplyPatchInvocation {
     /// appears that it should be allowed based on the user's sandbox policy
     /// *or* because the user explicitly approved it. In either case, we use
     /// exec with [`CODEX_APPLY_PATCH_ARG1`] to realize the `apply_patch` call,
-    /// but [`ApplyPatchExec::auto_approved`] is used to determine the sandbox
-    /// used with the `exec()`.
+    /// but [`ApplyPatchExec::write_exemptions`] widen the sandbox used with
+    /// the `exec()` when the user approved writes outside the writable roots.
     DelegateToExec(ApplyPatchExec),
 }
 
 pub(crate) struct ApplyPatchExec {
     pub(crate) action: ApplyPatchAction,
     pub(crate) user_explicitly_approved_this_action: bool,
+    /// Paths the sandbox lets this patch, and nothing else, write under in
+    /// addition to the policy's writable roots.
+    pub(crate) write_exemptions: Vec<PathBuf>,
+    /// Tells the model which parts of its patch the user rejected, when
+    /// `action` is only the accepted part.
+    pub(crate) rejection_note: Option<String>,
 }
 
 impl From<ResponseInputItem> for InternalApplyPatchInvocation {
@@ -52,40 +66,98 @@ pub(crate) async fn apply_patch(
         &turn_context.sandbox_policy,
         &turn_context.cwd,
     ) {
+        SafetyCheck::AutoApprove { .. } if sess.needs_write_confirmation(turn_context) => {
+            // The patch keeps its sandbox; the user only confirms that Codex
+            // may start writing outside a clean git repository.
+            let rx_approve = sess
+                .request_patch_approval(
+                    sub_id.to_owned(),
+                    call_id.to_owned(),
+                    &action,
+                    Some(FIRST_WRITE_CONFIRMATION_REASON.to_string()),
+                    None,
+                )
+                .await;
+            let ApprovalResponse {
+                decision,
+                selection,
+                feedback,
+            } = rx_approve.await.unwrap_or_default();
+            match decision {
+                ReviewDecision::Approved | ReviewDecision::ApprovedForSession => {
+                    let (action, rejection_note) =
+                        match apply_selection(sess, action, selection, call_id) {
+                            Ok(selected) => selected,
+                            Err(output) => return output,
+                        };
+                    sess.write_confirmed();
+                    InternalApplyPatchInvocation::DelegateToExec(ApplyPatchExec {
+                        action,
+                        user_explicitly_approved_this_action: false,
+                        write_exemptions: Vec::new(),
+                        rejection_note,
+                    })
+                }
+                ReviewDecision::Denied | ReviewDecision::Abort => patch_rejected(call_id, feedback),
+            }
+        }
         SafetyCheck::AutoApprove { .. } => {
             InternalApplyPatchInvocation::DelegateToExec(ApplyPatchExec {
                 action,
                 user_explicitly_approved_this_action: false,
+                write_exemptions: Vec::new(),
+                rejection_note: None,
             })
         }
-        SafetyCheck::AskUser => {
-            // Compute a readable summary of path changes to include in the
-            // approval request so the user can make an informed decision.
-            //
-            // Note that it might be worth expanding this approval request to
-            // give the user the option to expand the set of writable roots so
-            // that similar patches can be auto-approved in the future during
-            // this session.
+        SafetyCheck::AskUser { .. } => {
+            // Name exactly the files the sandbox would not let the patch
+            // write, since approving exempts those and nothing else.
+            let outside = paths_outside_writable_roots(
+                &action,
+                &turn_context.sandbox_policy,
+                &turn_context.cwd,
+            );
+            let reason = (!outside.is_empty()).then(|| out_of_policy_reason(&outside));
             let rx_approve = sess
-                .request_patch_approval(sub_id.to_owned(), call_id.to_owned(), &action, None, None)
+                .request_patch_approval(
+                    sub_id.to_owned(),
+                    call_id.to_owned(),
+                    &action,
+                    reason,
+                    None,
+                )
                 .await;
-            match rx_approve.await.unwrap_or_default() {
+            let ApprovalResponse {
+                decision,
+                selection,
+                feedback,
+            } = rx_approve.await.unwrap_or_default();
+            match decision {
                 ReviewDecision::Approved | ReviewDecision::ApprovedForSession => {
+                    let (action, rejection_note) =
+                        match apply_selection(sess, action, selection, call_id) {
+                            Ok(selected) => selected,
+                            Err(output) => return output,
+                        };
+                    // Only the accepted files need to be exempted.
+                    let outside: Vec<PathBuf> = outside
+                        .into_iter()
+                        .filter(|path| action.changes().contains_key(path))
+                        .collect();
+                    sess.write_confirmed();
+                    if !outside.is_empty() {
+                        sess.record_write_exemption(call_id, &outside).await;
+                    }
+                    let write_exemptions =
+                        write_exemption_roots(&action, &outside, &turn_context.cwd);
                     InternalApplyPatchInvocation::DelegateToExec(ApplyPatchExec {
                         action,
                         user_explicitly_approved_this_action: true,
+                        write_exemptions,
+                        rejection_note,
                     })
                 }
-                ReviewDecision::Denied | ReviewDecision::Abort => {
-                    ResponseInputItem::FunctionCallOutput {
-                        call_id: call_id.to_owned(),
-                        output: FunctionCallOutputPayload {
-                            content: "patch rejected by user".to_string(),
-                            success: Some(false),
-                        },
-                    }
-                    .into()
-                }
+                ReviewDecision::Denied | ReviewDecision::Abort => patch_rejected(call_id, feedback),
             }
         }
         SafetyCheck::Reject { reason } => ResponseInputItem::FunctionCallOutput {
@@ -99,6 +171,128 @@ pub(crate) async fn apply_patch(
     }
 }
 
+fn out_of_policy_reason(paths: &[PathBuf]) -> String {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    format!(
+        "Outside the sandbox's writable roots: {}. Approving lets this patch write these files only.",
+        paths.join(", ")
+    )
+}
+
+/// Narrows `action` to the parts of the patch the user accepted, along with a
+/// note for the model listing what was left out. Without a selection the
+/// whole patch is applied.
+fn apply_selection(
+    sess: &Session,
+    action: ApplyPatchAction,
+    selection: Option<PatchSelection>,
+    call_id: &str,
+) -> Result<(ApplyPatchAction, Option<String>), InternalApplyPatchInvocation> {
+    let Some(selection) = selection.filter(|selection| !selection.is_empty()) else {
+        return Ok((action, None));
+    };
+    let failed = |message: String| -> InternalApplyPatchInvocation {
+        ResponseInputItem::FunctionCallOutput {
+            call_id: call_id.to_owned(),
+            output: FunctionCallOutputPayload {
+                content: format!("failed to apply the accepted part of the patch: {message}"),
+                success: Some(false),
+            },
+        }
+        .into()
+    };
+    // A hunk the patch does not have would otherwise be applied silently.
+    if let Some((path, hunk)) = unknown_rejected_hunk(&action, &selection) {
+        return Err(failed(format!(
+            "{} has no hunk `@@ {hunk} @@`",
+            path.display()
+        )));
+    }
+    match action.select(
+        |path| !selection.is_file_rejected(path),
+        |path, hunk| {
+            !selection.is_hunk_rejected(
+                path,
+                HunkRange {
+                    old_start: hunk.old_start,
+                    old_lines: hunk.old_lines,
+                },
+            )
+        },
+        sess.apply_patch_loosest_match(),
+    ) {
+        Ok(Some(selected)) => Ok((selected, Some(rejection_note(&action, &selection)))),
+        Ok(None) => Err(patch_rejected(call_id, None)),
+        Err(e) => Err(failed(e.to_string())),
+    }
+}
+
+/// The hunks of an updated file's `unified_diff`, by the lines they replace.
+fn hunk_ranges(change: Option<&ApplyPatchFileChange>) -> Vec<(HunkRange, &str)> {
+    let Some(ApplyPatchFileChange::Update { unified_diff, .. }) = change else {
+        return Vec::new();
+    };
+    unified_diff
+        .lines()
+        .filter_map(|line| Some((HunkRange::from_header(line)?, line)))
+        .collect()
+}
+
+/// A hunk `selection` rejects that is not among the hunks of `action`.
+fn unknown_rejected_hunk<'a>(
+    action: &ApplyPatchAction,
+    selection: &'a PatchSelection,
+) -> Option<(&'a PathBuf, HunkRange)> {
+    selection.rejected_hunks.iter().find_map(|(path, hunks)| {
+        let known = hunk_ranges(action.changes().get(path));
+        hunks
+            .iter()
+            .find(|hunk| !known.iter().any(|(range, _)| range == *hunk))
+            .map(|hunk| (path, *hunk))
+    })
+}
+
+/// Lists the files and hunks of `action` that `selection` rejects, in the
+/// numbering of the `@@` hunks the user reviewed.
+fn rejection_note(action: &ApplyPatchAction, selection: &PatchSelection) -> String {
+    let mut paths: Vec<&PathBuf> = action.changes().keys().collect();
+    paths.sort();
+    let mut rejected = Vec::new();
+    for path in paths {
+        let display = path.strip_prefix(&action.cwd).unwrap_or(path).display();
+        if selection.is_file_rejected(path) {
+            rejected.push(format!("- {display}: the whole file"));
+            continue;
+        }
+        for (index, (range, header)) in hunk_ranges(action.changes().get(path))
+            .into_iter()
+            .enumerate()
+        {
+            if selection.is_hunk_rejected(path, range) {
+                rejected.push(format!("- {display}: hunk {} `{header}`", index + 1));
+            }
+        }
+    }
+    format!(
+        "The user rejected part of this patch, so these changes were not applied:\n{}",
+        rejected.join("\n")
+    )
+}
+
+fn patch_rejected(call_id: &str, feedback: Option<String>) -> InternalApplyPatchInvocation {
+    ResponseInputItem::FunctionCallOutput {
+        call_id: call_id.to_owned(),
+        output: FunctionCallOutputPayload {
+            content: feedback.unwrap_or_else(|| "patch rejected by user".to_string()),
+            success: Some(false),
+        },
+    }
+    .into()
+}
+
 pub(crate) fn convert_apply_patch_to_protocol(
     action: &ApplyPatchAction,
 ) -> HashMap<PathBuf, FileChange> {
@@ -109,11 +303,14 @@ pub(crate) fn convert_apply_patch_to_protocol(
             ApplyPatchFileChange::Add { content } => FileChange::Add {
                 content: content.clone(),
             },
-            ApplyPatchFileChange::Delete => FileChange::Delete,
+            ApplyPatchFileChange::Delete { content } => FileChange::Delete {
+                content: content.clone(),
+            },
             ApplyPatchFileChange::Update {
                 unified_diff,
                 move_path,
                 new_content: _new_content,
+                match_strategy: _match_strategy,
             } => FileChange::Update {
                 unified_diff: unified_diff.clone(),
                 move_path: move_path.clone(),
@@ -123,3 +320,24 @@ pub(crate) fn convert_apply_patch_to_protocol(
     }
     result
 }
+
+/// How the context of each file the patch updates was located.
+pub(crate) fn apply_patch_match_strategies(
+    action: &ApplyPatchAction,
+) -> HashMap<PathBuf, PatchMatchStrategy> {
+    action
+        .changes()
+        .iter()
+        .filter_map(|(path, change)| match change {
+            ApplyPatchFileChange::Update { match_strategy, .. } => {
+                let strategy = match match_strategy {
+                    MatchStrategy::Exact => PatchMatchStrategy::Exact,
+                    MatchStrategy::IgnoreWhitespace => PatchMatchStrategy::IgnoreWhitespace,
+                    MatchStrategy::Fuzzy => PatchMatchStrategy::Fuzzy,
+                };
+                Some((path.clone(), strategy))
+            }
+            ApplyPatchFileChange::Add { .. } | ApplyPatchFileChange::Delete { .. } => None,
+        })
+        .collect()
+}
@@ -0,0 +1,315 @@
+//! Commands that may run without asking the user: read-only commands,
+//! prefixes listed under `[approvals] trusted_commands` in `config.toml` and
+//! commands the user chose to always allow for the session.
+
+use std::collections::HashSet;
+
+use crate::bash::try_parse_bash;
+use crate::bash::try_parse_word_only_commands_sequence;
+use crate::config_types::SafeCommandRule;
+use crate::is_safe_command::is_known_safe_command;
+use crate::is_safe_command::is_safe_to_call_with_exec;
+use crate::protocol::SandboxPolicy;
+
+/// Subcommands that take the name of the thing to run as their next word,
+/// e.g. `npm run lint`. Approving `npm run` alone would be far too broad.
+const RUNNER_SUBCOMMANDS: &[&str] = &["exec", "run", "x"];
+
+/// Programs that interpret their arguments as a script. They never match an
+/// approved prefix because the prefix says nothing about what they will run.
+const SHELLS: &[&str] = &["bash", "dash", "fish", "ksh", "sh", "zsh"];
+
+/// Interpreters and programs that run another command. Approving one of them
+/// for the session only ever covers the exact command, since `python x.py`
+/// or `env FOO=1 cargo test` say little about what the next call will run.
+const INTERPRETERS_AND_WRAPPERS: &[&str] = &[
+    "bun",
+    "command",
+    "deno",
+    "doas",
+    "env",
+    "exec",
+    "find",
+    "ionice",
+    "lua",
+    "nice",
+    "node",
+    "nodejs",
+    "nohup",
+    "osascript",
+    "perl",
+    "php",
+    "powershell",
+    "pwsh",
+    "python",
+    "python2",
+    "python3",
+    "ruby",
+    "stdbuf",
+    "su",
+    "sudo",
+    "time",
+    "timeout",
+    "watch",
+    "xargs",
+];
+
+#[derive(Debug, Default)]
+pub(crate) struct ApprovedCommands {
+    /// Prefixes from `config.toml`; they apply under every sandbox policy.
+    trusted_prefixes: Vec<Vec<String>>,
+    /// Read-only commands from `[[approvals.safe_commands]]`, on top of the
+    /// built-in ones.
+    safe_rules: Vec<SafeCommandRule>,
+    /// Exact commands approved for the session, e.g. when retrying a command
+    /// outside the sandbox.
+    exact: HashSet<Vec<String>>,
+    /// Prefixes approved for the session together with the sandbox policy
+    /// that was in effect when the user approved them.
+    session_prefixes: Vec<(Vec<String>, SandboxPolicy)>,
+}
+
+impl ApprovedCommands {
+    pub(crate) fn new(
+        trusted_prefixes: Vec<Vec<String>>,
+        safe_rules: Vec<SafeCommandRule>,
+    ) -> Self {
+        Self {
+            trusted_prefixes,
+            safe_rules,
+            ..Default::default()
+        }
+    }
+
+    /// Whether `command` is read-only under the built-in rules or the ones
+    /// from `config.toml`.
+    pub(crate) fn is_known_safe(&self, command: &[String]) -> bool {
+        is_known_safe_command(command, &self.safe_rules)
+    }
+
+    pub(crate) fn approve_exact(&mut self, command: Vec<String>) {
+        self.exact.insert(command);
+    }
+
+    /// Remembers the prefix of `command` for the rest of the session. Falls
+    /// back to approving the exact command when no prefix can be derived, for
+    /// example for a compound shell script.
+    pub(crate) fn approve_for_session(&mut self, command: Vec<String>, policy: &SandboxPolicy) {
+        match session_approval_prefix(&command) {
+            Some(prefix) => {
+                let entry = (prefix, policy.clone());
+                if !self.session_prefixes.contains(&entry) {
+                    self.session_prefixes.push(entry);
+                }
+            }
+            None => self.approve_exact(command),
+        }
+    }
+
+    pub(crate) fn is_approved(&self, command: &[String], policy: &SandboxPolicy) -> bool {
+        if self.exact.contains(command) {
+            return true;
+        }
+        let Some(argvs) = parse_argvs(command) else {
+            return false;
+        };
+        // Every command of a sequence like `cargo fmt && cargo test` must be
+        // approved or known to be safe on its own.
+        !argvs.is_empty()
+            && argvs.iter().all(|argv| {
+                self.matches_prefix(argv, policy)
+                    || is_safe_to_call_with_exec(argv, &self.safe_rules)
+            })
+    }
+
+    fn matches_prefix(&self, argv: &[String], policy: &SandboxPolicy) -> bool {
+        if is_shell(argv) {
+            return false;
+        }
+        self.trusted_prefixes
+            .iter()
+            .any(|prefix| argv.starts_with(prefix))
+            || self
+                .session_prefixes
+                .iter()
+                .any(|(prefix, approved_under)| {
+                    approved_under == policy && argv.starts_with(prefix)
+                })
+    }
+}
+
+/// Splits a `trusted_commands` entry such as `"npm run lint"` into words.
+pub(crate) fn parse_trusted_command(entry: &str) -> Option<Vec<String>> {
+    shlex::split(entry).filter(|words| !words.is_empty())
+}
+
+/// The prefix that approving `command` for the session would let run without
+/// asking, or `None` when only the exact command would be approved.
+pub(crate) fn session_approval_prefix(command: &[String]) -> Option<Vec<String>> {
+    match parse_argvs(command).as_deref() {
+        Some([argv]) => approval_prefix(argv),
+        _ => None,
+    }
+}
+
+/// The argv of every command `command` runs. A `bash -lc` script is only
+/// looked into when it consists of plain commands joined by `&&`, `||`, `;`
+/// or `|`.
+fn parse_argvs(command: &[String]) -> Option<Vec<Vec<String>>> {
+    match command {
+        [bash, flag, script] if bash == "bash" && flag == "-lc" => {
+            let tree = try_parse_bash(script)?;
+            try_parse_word_only_commands_sequence(&tree, script)
+        }
+        _ => Some(vec![command.to_vec()]),
+    }
+}
+
+/// The words that identify what `argv` does: the program and its subcommand,
+/// plus the target of runner subcommands such as `npm run lint`. `None` when
+/// there is no subcommand, or when a flag comes first, as in `rm -rf target`
+/// or `git -C dir status`, since the flag can change what the rest means.
+fn approval_prefix(argv: &[String]) -> Option<Vec<String>> {
+    if is_shell(argv) || is_interpreter_or_wrapper(argv) {
+        return None;
+    }
+    let (program, args) = argv.split_first()?;
+    let mut prefix = vec![program.clone()];
+    for arg in args {
+        if arg.starts_with('-') {
+            return None;
+        }
+        prefix.push(arg.clone());
+        if !RUNNER_SUBCOMMANDS.contains(&arg.as_str()) {
+            return Some(prefix);
+        }
+    }
+    None
+}
+
+fn program_name(argv: &[String]) -> Option<&str> {
+    argv.first()
+        .map(|program| program.rsplit('/').next().unwrap_or(program))
+}
+
+fn is_shell(argv: &[String]) -> bool {
+    program_name(argv).is_some_and(|name| SHELLS.contains(&name))
+}
+
+fn is_interpreter_or_wrapper(argv: &[String]) -> bool {
+    program_name(argv)
+        .is_some_and(|name| INTERPRETERS_AND_WRAPPERS.contains(&name) || name.starts_with("python"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec_str(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    fn bash_lc(script: &str) -> Vec<String> {
+        vec_str(&["bash", "-lc", script])
+    }
+
+    #[test]
+    fn session_approval_covers_the_subcommand() {
+        let policy = SandboxPolicy::ReadOnly;
+        let mut approved = ApprovedCommands::default();
+        approved.approve_for_session(bash_lc("cargo test -p codex-core"), &policy);
+
+        assert!(approved.is_approved(&bash_lc("cargo test --all-features"), &policy));
+        assert!(approved.is_approved(&vec_str(&["cargo", "test"]), &policy));
+        assert!(!approved.is_approved(&bash_lc("cargo testx"), &policy));
+        assert!(!approved.is_approved(&bash_lc("cargo build"), &policy));
+    }
+
+    #[test]
+    fn leading_flags_approve_only_the_exact_command() {
+        let policy = SandboxPolicy::ReadOnly;
+        let mut approved = ApprovedCommands::default();
+        approved.approve_for_session(vec_str(&["rm", "-rf", "target"]), &policy);
+        approved.approve_for_session(vec_str(&["git", "-C", "dir", "status"]), &policy);
+        approved.approve_for_session(vec_str(&["ls"]), &policy);
+        approved.approve_for_session(vec_str(&["npm", "run"]), &policy);
+
+        assert!(approved.is_approved(&vec_str(&["rm", "-rf", "target"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["rm", "-rf", "/"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["git", "push"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["ls", "/etc"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["npm", "run", "deploy"]), &policy));
+    }
+
+    #[test]
+    fn interpreters_and_wrappers_approve_only_the_exact_command() {
+        let policy = SandboxPolicy::ReadOnly;
+        let mut approved = ApprovedCommands::default();
+        for command in [
+            vec_str(&["python3", "scripts/gen.py"]),
+            vec_str(&["/usr/bin/env", "cargo", "test"]),
+            vec_str(&["sudo", "apt", "update"]),
+        ] {
+            assert_eq!(session_approval_prefix(&command), None);
+            approved.approve_for_session(command, &policy);
+        }
+
+        assert!(approved.is_approved(&vec_str(&["python3", "scripts/gen.py"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["python3", "evil.py"]), &policy));
+        assert!(!approved.is_approved(&vec_str(&["sudo", "apt", "remove", "x"]), &policy));
+    }
+
+    #[test]
+    fn session_approval_is_tied_to_the_sandbox_policy() {
+        let mut approved = ApprovedCommands::default();
+        approved.approve_for_session(bash_lc("cargo test"), &SandboxPolicy::ReadOnly);
+
+        assert!(!approved.is_approved(&bash_lc("cargo test"), &SandboxPolicy::DangerFullAccess));
+    }
+
+    #[test]
+    fn runner_subcommands_include_their_target() {
+        let policy = SandboxPolicy::ReadOnly;
+        let mut approved = ApprovedCommands::default();
+        approved.approve_for_session(bash_lc("npm run lint"), &policy);
+
+        assert!(approved.is_approved(&bash_lc("npm run lint -- --fix"), &policy));
+        assert!(!approved.is_approved(&bash_lc("npm run deploy"), &policy));
+    }
+
+    #[test]
+    fn trusted_commands_apply_to_every_command_of_a_sequence() {
+        let policy = SandboxPolicy::ReadOnly;
+        let approved = ApprovedCommands::new(
+            vec![vec_str(&["cargo", "test"]), vec_str(&["cargo", "fmt"])],
+            Vec::new(),
+        );
+
+        assert!(approved.is_approved(&bash_lc("cargo fmt && cargo test"), &policy));
+        assert!(approved.is_approved(&bash_lc("cargo test | head -n 20"), &policy));
+        assert!(!approved.is_approved(&bash_lc("cargo test && rm -rf target"), &policy));
+        assert!(!approved.is_approved(&bash_lc("cargo test $(rm -rf target)"), &policy));
+    }
+
+    #[test]
+    fn shells_never_match() {
+        let policy = SandboxPolicy::ReadOnly;
+        let mut approved = ApprovedCommands::new(vec![vec_str(&["bash"])], Vec::new());
+        approved.approve_for_session(vec_str(&["bash", "-c", "rm -rf /"]), &policy);
+
+        assert!(!approved.is_approved(&vec_str(&["bash", "-c", "echo hi"]), &policy));
+        assert!(!approved.is_approved(&bash_lc("/bin/sh -c 'cargo test'"), &policy));
+        // The exact command approved for the session still runs.
+        assert!(approved.is_approved(&vec_str(&["bash", "-c", "rm -rf /"]), &policy));
+    }
+
+    #[test]
+    fn parses_trusted_command_entries() {
+        assert_eq!(
+            parse_trusted_command("npm run 'lint:fix'"),
+            Some(vec_str(&["npm", "run", "lint:fix"]))
+        );
+        assert_eq!(parse_trusted_command("  "), None);
+        assert_eq!(parse_trusted_command("echo 'unterminated"), None);
+    }
+}
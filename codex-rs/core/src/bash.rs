@@ -13,6 +13,19 @@ pub fn try_parse_bash(bash_lc_arg: &str) -> Option<Tree> {
     parser.parse(bash_lc_arg, old_tree)
 }
 
+/// The script of a `bash -c SCRIPT` or `bash -lc SCRIPT` invocation (`sh` is
+/// accepted as well), or `None` for any other command.
+pub fn shell_script_arg(command: &[String]) -> Option<&str> {
+    match command {
+        [shell, flag, script]
+            if matches!(shell.as_str(), "bash" | "sh") && matches!(flag.as_str(), "-c" | "-lc") =>
+        {
+            Some(script)
+        }
+        _ => None,
+    }
+}
+
 /// Parse a script which may contain multiple simple commands joined only by
 /// the safe logical/pipe/sequencing operators: `&&`, `||`, `;`, `|`.
 ///
@@ -0,0 +1,587 @@
+//! Record and replay of model requests, so that tests of anything that talks
+//! to the model can run without a live API key.
+//!
+//! With `CODEX_RECORD=<path>`, every request sent to the model provider is
+//! written to the cassette at `<path>` together with its response, including
+//! the complete SSE stream. With `CODEX_REPLAY=<path>`, requests are answered
+//! from that cassette instead of the network, and a request that was not
+//! recorded fails the turn with [`CodexErr::CassetteMiss`], naming the first
+//! field in which it differs from the closest recorded request.
+//!
+//! Requests are matched on their method, path, query and JSON body, with
+//! object keys sorted, so that a cassette recorded against one server replays
+//! against another. UUIDs, such as the session id in the scratch directory,
+//! are masked, and `prompt_cache_key`, which defaults to the session id, is
+//! not matched at all. Headers that may carry credentials are stripped before
+//! anything is written.
+//!
+//! Tests use [`Cassette::record`] and [`Cassette::replay`] with
+//! [`Config::cassette`](crate::config::Config::cassette) or
+//! [`CodexBuilder::cassette`](crate::CodexBuilder::cassette) instead of the
+//! environment variables.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use sha1::Digest;
+use sha1::Sha1;
+use tracing::warn;
+
+use crate::error::CodexErr;
+use crate::error::Result;
+
+const RECORD_ENV_VAR: &str = "CODEX_RECORD";
+const REPLAY_ENV_VAR: &str = "CODEX_REPLAY";
+
+/// Body fields that differ between otherwise identical sessions.
+const VOLATILE_BODY_FIELDS: &[&str] = &["prompt_cache_key"];
+
+/// Headers whose lowercase name contains one of these are not recorded.
+const SENSITIVE_HEADER_PARTS: &[&str] = &["auth", "key", "token", "secret", "cookie", "account"];
+
+/// Serializes writes to cassette files, which several sessions may share.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A cassette file that model requests are recorded to or replayed from.
+#[derive(Clone)]
+pub struct Cassette {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    mode: Mode,
+}
+
+enum Mode {
+    Record,
+    Replay {
+        interactions: Vec<Interaction>,
+        /// How many times each request has been answered. Repeated requests
+        /// get the responses recorded for them in order, then the last one.
+        served: Mutex<HashMap<String, usize>>,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    /// Hash of the matched parts of `request`.
+    key: String,
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    /// Path and query, without the host.
+    url: String,
+    /// Not matched; kept to make cassettes easier to read.
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Cassette {
+    /// Records the requests of the sessions using it to `path`, adding to
+    /// the interactions already there.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(path.into(), Mode::Record)
+    }
+
+    /// Answers the requests of the sessions using it from the cassette at
+    /// `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = read_cassette(&path).and_then(|file| {
+            file.ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        });
+        let file = file.map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("cannot read cassette {}: {e}", path.display()),
+            )
+        })?;
+        Ok(Self::new(
+            path,
+            Mode::Replay {
+                interactions: file.interactions,
+                served: Mutex::default(),
+            },
+        ))
+    }
+
+    /// The cassette named by `CODEX_RECORD` or `CODEX_REPLAY`, if either is
+    /// set.
+    pub fn from_env() -> std::io::Result<Option<Self>> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        match (var(RECORD_ENV_VAR), var(REPLAY_ENV_VAR)) {
+            (Some(_), Some(_)) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("set only one of {RECORD_ENV_VAR} and {REPLAY_ENV_VAR}"),
+            )),
+            (Some(path), None) => Ok(Some(Self::record(path))),
+            (None, Some(path)) => Self::replay(path).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn new(path: PathBuf, mode: Mode) -> Self {
+        Self {
+            inner: Arc::new(Inner { path, mode }),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.inner.mode, Mode::Replay { .. })
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Result<Response>> {
+        let (client, request) = request.build_split();
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => return Ok(Err(e)),
+        };
+        let recorded = RecordedRequest::new(&request);
+        let key = recorded.key();
+        match &self.inner.mode {
+            Mode::Replay {
+                interactions,
+                served,
+            } => {
+                let responses: Vec<&RecordedResponse> = interactions
+                    .iter()
+                    .filter(|interaction| interaction.key == key)
+                    .map(|interaction| &interaction.response)
+                    .collect();
+                let Some(last) = responses.last() else {
+                    return Err(self.miss(&recorded, interactions));
+                };
+                let mut served = served.lock().unwrap_or_else(PoisonError::into_inner);
+                let count = served.entry(key).or_default();
+                let response = responses.get(*count).unwrap_or(last);
+                *count += 1;
+                Ok(Ok(response.to_response()))
+            }
+            Mode::Record => Ok(client
+                .execute(request)
+                .await
+                .map(|response| self.tee(key, recorded, response))),
+        }
+    }
+
+    /// Passes `response` through, recording its body as it is read. A body
+    /// cut off by a network error is not recorded.
+    fn tee(&self, key: String, request: RecordedRequest, response: Response) -> Response {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let recording = Recording {
+            cassette: self.clone(),
+            interaction: Some(Interaction {
+                key,
+                request,
+                response: RecordedResponse {
+                    status: status.as_u16(),
+                    headers: recorded_headers(&headers),
+                    body: String::new(),
+                },
+            }),
+            body: Vec::new(),
+        };
+        let chunks = Box::pin(response.bytes_stream());
+        let body = futures::stream::unfold(Some((chunks, recording)), |state| async move {
+            let (mut chunks, mut recording) = state?;
+            match chunks.next().await {
+                Some(Ok(chunk)) => {
+                    recording.body.extend_from_slice(&chunk);
+                    Some((Ok(chunk), Some((chunks, recording))))
+                }
+                Some(Err(e)) => {
+                    recording.interaction = None;
+                    Some((Err(e), None))
+                }
+                None => None,
+            }
+        });
+        let mut response = http::Response::new(reqwest::Body::wrap_stream(body));
+        *response.status_mut() = status;
+        *response.headers_mut() = headers;
+        Response::from(response)
+    }
+
+    fn miss(&self, request: &RecordedRequest, interactions: &[Interaction]) -> CodexErr {
+        let matched = request.matched();
+        let closest = interactions
+            .iter()
+            .map(|interaction| {
+                let mut equal = 0;
+                let path =
+                    first_difference(&matched, &interaction.request.matched(), "", &mut equal);
+                (equal, path)
+            })
+            .max_by_key(|(equal, _)| *equal)
+            .and_then(|(_, path)| path);
+        let detail = match closest {
+            Some(path) => format!("; the closest recorded request differs at `{path}`"),
+            None => "; the cassette has no requests".to_string(),
+        };
+        CodexErr::CassetteMiss(format!(
+            "no response recorded in {} for {} {}{detail}",
+            self.inner.path.display(),
+            request.method,
+            request.url
+        ))
+    }
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.inner.mode {
+            Mode::Record => "record",
+            Mode::Replay { .. } => "replay",
+        };
+        f.debug_struct("Cassette")
+            .field("path", &self.inner.path)
+            .field("mode", &mode)
+            .finish()
+    }
+}
+
+/// Two cassettes are equal when they are clones of each other.
+impl PartialEq for Cassette {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+/// Sends `request`, through `cassette` when there is one. The outer error is
+/// a request missing from a replayed cassette; the inner one is whatever
+/// sending the request returned.
+pub(crate) async fn send(
+    cassette: Option<&Cassette>,
+    request: RequestBuilder,
+) -> Result<reqwest::Result<Response>> {
+    match cassette {
+        Some(cassette) => cassette.send(request).await,
+        None => Ok(request.send().await),
+    }
+}
+
+/// A response whose body is being read. It is written to the cassette when
+/// the reader is done with it, which for Chat Completions is at `[DONE]`
+/// rather than at the end of the stream.
+struct Recording {
+    cassette: Cassette,
+    /// `None` once the body failed to arrive.
+    interaction: Option<Interaction>,
+    body: Vec<u8>,
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        let Some(mut interaction) = self.interaction.take() else {
+            return;
+        };
+        interaction.response.body = String::from_utf8_lossy(&self.body).into_owned();
+        let path = &self.cassette.inner.path;
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = read_cassette(path).and_then(|file| {
+            let mut file = file.unwrap_or_default();
+            file.interactions.push(interaction);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let json = serde_json::to_string_pretty(&file).map_err(std::io::Error::other)?;
+            std::fs::write(path, json)
+        });
+        if let Err(e) = result {
+            warn!("failed to write cassette {}: {e}", path.display());
+        }
+    }
+}
+
+impl RecordedRequest {
+    fn new(request: &reqwest::Request) -> Self {
+        let url = request.url();
+        let url = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        let body = match request.body().and_then(reqwest::Body::as_bytes) {
+            Some(bytes) => serde_json::from_slice(bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned())),
+            None => Value::Null,
+        };
+        let mut body = canonicalize(body);
+        if let Value::Object(fields) = &mut body {
+            for field in VOLATILE_BODY_FIELDS {
+                fields.remove(*field);
+            }
+        }
+        Self {
+            method: request.method().to_string(),
+            url,
+            headers: recorded_headers(request.headers()),
+            body,
+        }
+    }
+
+    /// The parts of the request that must match for a recorded response to
+    /// be served.
+    fn matched(&self) -> Value {
+        serde_json::json!({
+            "method": self.method,
+            "url": self.url,
+            "body": self.body,
+        })
+    }
+
+    fn key(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(canonicalize(self.matched()).to_string());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Response {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        let mut response = http::Response::new(reqwest::Body::from(Bytes::from(
+            self.body.clone().into_bytes(),
+        )));
+        *response.status_mut() =
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        *response.headers_mut() = headers;
+        Response::from(response)
+    }
+}
+
+/// `None` when there is no file at `path` yet.
+fn read_cassette(path: &Path) -> std::io::Result<Option<CassetteFile>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn recorded_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            !SENSITIVE_HEADER_PARTS
+                .iter()
+                .any(|part| name.contains(part))
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// `value` with the keys of every object in sorted order, whether or not
+/// `serde_json` preserves insertion order in this build, and UUIDs masked.
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::String(text) => match mask_uuids(&text) {
+            Cow::Borrowed(_) => Value::String(text),
+            Cow::Owned(masked) => Value::String(masked),
+        },
+        Value::Object(fields) => {
+            let mut fields: Vec<(String, Value)> = fields.into_iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        value => value,
+    }
+}
+
+fn mask_uuids(text: &str) -> Cow<'_, str> {
+    const UUID_LEN: usize = 36;
+    let is_uuid = |candidate: &[u8]| {
+        candidate.iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+    };
+    let bytes = text.as_bytes();
+    let mut masked = String::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while i + UUID_LEN <= bytes.len() {
+        if is_uuid(&bytes[i..i + UUID_LEN]) {
+            masked.push_str(&text[copied..i]);
+            masked.push_str("<uuid>");
+            i += UUID_LEN;
+            copied = i;
+        } else {
+            i += 1;
+        }
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    masked.push_str(&text[copied..]);
+    Cow::Owned(masked)
+}
+
+/// Path of the first field, in key order, in which `a` and `b` differ.
+/// `equal` counts the leaves that matched before it.
+fn first_difference(a: &Value, b: &Value, path: &str, equal: &mut usize) -> Option<String> {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                let (Some(a), Some(b)) = (a.get(key), b.get(key)) else {
+                    return Some(path);
+                };
+                if let Some(path) = first_difference(a, b, &path, equal) {
+                    return Some(path);
+                }
+            }
+            None
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for (index, (a, b)) in a.iter().zip(b).enumerate() {
+                if let Some(path) = first_difference(a, b, &format!("{path}[{index}]"), equal) {
+                    return Some(path);
+                }
+            }
+            (a.len() != b.len()).then(|| format!("{path}[{}]", a.len().min(b.len())))
+        }
+        _ if a == b => {
+            *equal += 1;
+            None
+        }
+        _ => Some(path.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn request(body: Value) -> RecordedRequest {
+        let request = reqwest::Client::new()
+            .post("http://127.0.0.1:1234/v1/responses?api-version=1")
+            .bearer_auth("sk-secret")
+            .header("x-api-key", "sk-secret")
+            .header("session_id", "abc")
+            .json(&body)
+            .build()
+            .unwrap();
+        RecordedRequest::new(&request)
+    }
+
+    #[test]
+    fn requests_are_matched_without_host_key_order_or_volatile_fields() {
+        let a = request(json!({"model": "gpt-5", "input": [], "prompt_cache_key": "1"}));
+        let b = request(json!({"prompt_cache_key": "2", "input": [], "model": "gpt-5"}));
+        assert_eq!(a.key(), b.key());
+        assert_eq!(a.url, "/v1/responses?api-version=1");
+        assert_ne!(a.key(), request(json!({"model": "o3", "input": []})).key());
+    }
+
+    #[test]
+    fn uuids_are_masked() {
+        let a = request(json!({"input": "/cache/scratch/0199a213-81c0-7800-8aa1-bbab2a035a53"}));
+        let b = request(json!({"input": "/cache/scratch/6ba7b810-9dad-11d1-80b4-00c04fd430c8"}));
+        assert_eq!(a.body, json!({"input": "/cache/scratch/<uuid>"}));
+        assert_eq!(a.key(), b.key());
+        assert_eq!(mask_uuids("not-a-uuid"), "not-a-uuid");
+    }
+
+    #[test]
+    fn credentials_are_not_recorded() {
+        let recorded = request(json!({}));
+        assert_eq!(
+            recorded.headers,
+            vec![
+                ("session_id".to_string(), "abc".to_string()),
+                ("content-type".to_string(), "application/json".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_difference_names_the_field() {
+        let recorded = json!({
+            "model": "gpt-5",
+            "input": [{"role": "user", "content": [{"text": "hi"}]}],
+        });
+        let cases = [
+            (
+                json!({"model": "gpt-5", "input": [{"role": "user", "content": [{"text": "hello"}]}]}),
+                Some("input[0].content[0].text"),
+            ),
+            (
+                json!({"model": "gpt-5", "input": [{"role": "user", "content": [{"text": "hi"}]}, {}]}),
+                Some("input[1]"),
+            ),
+            (json!({"input": []}), Some("input[0]")),
+            (recorded.clone(), None),
+        ];
+        for (request, expected) in cases {
+            let mut equal = 0;
+            assert_eq!(
+                first_difference(&request, &recorded, "", &mut equal).as_deref(),
+                expected
+            );
+        }
+    }
+}
@@ -1,29 +1,44 @@
+use std::collections::BTreeMap;
 use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
+use chrono::Utc;
 use eventsource_stream::Eventsource;
 use futures::Stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use reqwest::StatusCode;
+use serde::Deserialize;
 use serde_json::json;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
+use tracing::Instrument;
 use tracing::debug;
 use tracing::trace;
 
 use crate::ModelProviderInfo;
+use crate::cassette;
+use crate::cassette::Cassette;
 use crate::client_common::Prompt;
 use crate::client_common::ResponseEvent;
 use crate::client_common::ResponseStream;
+use crate::client_common::StreamMetrics;
+use crate::client_common::spawn_response_task;
 use crate::error::CodexErr;
 use crate::error::Result;
+use crate::error::http_error;
 use crate::model_family::ModelFamily;
 use crate::openai_tools::create_tools_json_for_chat_completions_api;
-use crate::util::backoff;
+use crate::otel;
+use crate::protocol::TokenUsage;
+use crate::rate_limits;
+use crate::rate_limits::parse_rate_limits;
+use crate::util::parse_retry_after;
 use codex_protocol::models::ContentItem;
 use codex_protocol::models::ReasoningItemContent;
 use codex_protocol::models::ResponseItem;
@@ -32,18 +47,18 @@ use codex_protocol::models::ResponseItem;
 pub(crate) async fn stream_chat_completions(
     prompt: &Prompt,
     model_family: &ModelFamily,
+    max_output_tokens: Option<u64>,
     client: &reqwest::Client,
     provider: &ModelProviderInfo,
+    cassette: Option<&Cassette>,
 ) -> Result<ResponseStream> {
     // Build messages array
     let mut messages = Vec::<serde_json::Value>::new();
 
-    let full_instructions = prompt.get_full_instructions(model_family);
+    let full_instructions = prompt.get_full_instructions(model_family)?;
     messages.push(json!({"role": "system", "content": full_instructions}));
 
-    let input = prompt.get_formatted_input();
-
-    for item in &input {
+    for item in prompt.get_formatted_input() {
         match item {
             ResponseItem::Message { role, content, .. } => {
                 let mut text = String::new();
@@ -137,14 +152,54 @@ pub(crate) async fn stream_chat_completions(
             }
         }
     }
+    if let Some(prefix) = &model_family.assistant_prefix {
+        // Servers that support prefilling continue this message rather
+        // than starting a new one.
+        messages.push(json!({"role": "assistant", "content": prefix}));
+    }
 
     let tools_json = create_tools_json_for_chat_completions_api(&prompt.tools)?;
-    let payload = json!({
+    let mut payload = json!({
         "model": model_family.slug,
         "messages": messages,
         "stream": true,
         "tools": tools_json,
     });
+    if provider.stream_include_usage()
+        && let Some(obj) = payload.as_object_mut()
+    {
+        // Ask for a final chunk carrying the token usage of the request.
+        obj.insert("stream_options".to_string(), json!({"include_usage": true}));
+    }
+    if let Some(max_output_tokens) = max_output_tokens
+        && let Some(obj) = payload.as_object_mut()
+    {
+        // OpenAI reasoning models reject `max_tokens` in favor of
+        // `max_completion_tokens`, while most OpenAI-compatible servers only
+        // understand `max_tokens`.
+        let key = if model_family.supports_reasoning_summaries {
+            "max_completion_tokens"
+        } else {
+            "max_tokens"
+        };
+        obj.insert(key.to_string(), json!(max_output_tokens));
+    }
+    if let Some(obj) = payload.as_object_mut() {
+        if !model_family.stop.is_empty() {
+            obj.insert("stop".to_string(), json!(model_family.stop));
+        }
+        if let Some(temperature) = provider.temperature {
+            obj.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(top_p) = provider.top_p {
+            obj.insert("top_p".to_string(), json!(top_p));
+        }
+        if let Some(seed) = provider.seed {
+            obj.insert("seed".to_string(), json!(seed));
+        }
+    }
+    provider.shape_request_body(&mut payload);
+    let expect_usage = payload.get("stream_options").is_some();
 
     debug!(
         "POST to {}: {}",
@@ -152,6 +207,7 @@ pub(crate) async fn stream_chat_completions(
         serde_json::to_string_pretty(&payload).unwrap_or_default()
     );
 
+    let started = Instant::now();
     let mut attempt = 0;
     let max_retries = provider.request_max_retries();
     loop {
@@ -159,109 +215,194 @@ pub(crate) async fn stream_chat_completions(
 
         let req_builder = provider.create_request_builder(client, &None).await?;
 
-        let res = req_builder
+        let http_span = otel::http_request_span(attempt);
+        let req_builder = req_builder
             .header(reqwest::header::ACCEPT, "text/event-stream")
-            .json(&payload)
-            .send()
-            .await;
+            .json(&payload);
+        let res = cassette::send(cassette, req_builder)
+            .instrument(http_span.clone())
+            .await?;
+        otel::record_http_result(&http_span, &res);
 
         match res {
             Ok(resp) if resp.status().is_success() => {
                 let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+                if let Some(snapshot) = parse_rate_limits(resp.headers(), Utc::now()) {
+                    // The channel is new and empty, so this cannot fail for lack of space.
+                    tx_event
+                        .try_send(Ok(ResponseEvent::RateLimits(snapshot)))
+                        .ok();
+                }
                 let stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
-                tokio::spawn(process_chat_sse(
-                    stream,
-                    tx_event,
-                    provider.stream_idle_timeout(),
-                ));
-                return Ok(ResponseStream { rx_event });
+                spawn_response_task(
+                    tx_event.clone(),
+                    process_chat_sse(
+                        stream,
+                        tx_event,
+                        provider.stream_idle_timeout(),
+                        model_family.assistant_prefix.clone(),
+                        expect_usage,
+                    ),
+                );
+                let metrics = Arc::new(StreamMetrics::new(started));
+                metrics.add_retries(attempt - 1);
+                return Ok(ResponseStream::new(rx_event, metrics));
             }
             Ok(res) => {
                 let status = res.status();
-                if !(status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
+                if status == StatusCode::TOO_MANY_REQUESTS
+                    && (!provider.should_retry_status(status) || attempt > max_retries)
+                {
+                    let now = Utc::now();
+                    let limit_resets_in = parse_rate_limits(res.headers(), now)
+                        .as_ref()
+                        .and_then(rate_limits::limit_resets_at)
+                        .map(|resets_at| rate_limits::until(resets_at, now));
+                    let body = (res.text().await).unwrap_or_default();
+                    // The body of a 429 is raw JSON; the reset time says more,
+                    // unless the account is out of quota.
+                    return Err(match http_error(status, body) {
+                        err @ CodexErr::QuotaExceeded { .. } => err,
+                        _ => CodexErr::RateLimitReached(limit_resets_in),
+                    });
+                }
+                if !provider.should_retry_status(status) {
                     let body = (res.text().await).unwrap_or_default();
-                    return Err(CodexErr::UnexpectedStatus(status, body));
+                    return Err(http_error(status, body));
                 }
 
                 if attempt > max_retries {
                     return Err(CodexErr::RetryLimit(status));
                 }
 
-                let retry_after_secs = res
-                    .headers()
-                    .get(reqwest::header::RETRY_AFTER)
-                    .and_then(|v| v.to_str().ok())
-                    .and_then(|s| s.parse::<u64>().ok());
-
-                let delay = retry_after_secs
-                    .map(|s| Duration::from_millis(s * 1_000))
-                    .unwrap_or_else(|| backoff(attempt));
+                let retry_after = parse_retry_after(res.headers());
+                let delay = provider.request_retry_delay(attempt, retry_after);
                 tokio::time::sleep(delay).await;
             }
             Err(e) => {
                 if attempt > max_retries {
                     return Err(e.into());
                 }
-                let delay = backoff(attempt);
+                let delay = provider.request_retry_delay(attempt, None);
                 tokio::time::sleep(delay).await;
             }
         }
     }
 }
 
+/// Removes the `assistant_prefix` from the start of the answer for servers
+/// that echo it back. Text is held back while it could still be the start
+/// of the prefix.
+struct EchoedPrefix {
+    prefix: Option<String>,
+    held: String,
+}
+
+impl EchoedPrefix {
+    fn new(prefix: Option<String>) -> Self {
+        Self {
+            prefix,
+            held: String::new(),
+        }
+    }
+
+    /// The part of `delta` to pass on.
+    fn strip(&mut self, delta: &str) -> String {
+        let Some(prefix) = &self.prefix else {
+            return delta.to_string();
+        };
+        self.held.push_str(delta);
+        if let Some(rest) = self.held.strip_prefix(prefix.as_str()) {
+            let rest = rest.to_string();
+            self.prefix = None;
+            self.held.clear();
+            return rest;
+        }
+        if prefix.starts_with(self.held.as_str()) {
+            return String::new();
+        }
+        self.prefix = None;
+        std::mem::take(&mut self.held)
+    }
+
+    /// Text still held back when the answer ends: it was not the prefix.
+    fn finish(&mut self) -> String {
+        self.prefix = None;
+        std::mem::take(&mut self.held)
+    }
+}
+
+/// How long to wait after the final choice for the usage chunk requested via
+/// `stream_options.include_usage`, for servers that never send it.
+const USAGE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
 /// Lightweight SSE processor for the Chat Completions streaming format. The
 /// output is mapped onto Codex's internal [`ResponseEvent`] so that the rest
 /// of the pipeline can stay agnostic of the underlying wire format.
+///
+/// `Completed` is sent as soon as the response is known to be over: once a
+/// choice reports its `finish_reason` and, if `expect_usage`, the usage chunk
+/// has arrived or [`USAGE_GRACE_PERIOD`] has passed without it. Servers may
+/// hold the connection open long after that before sending `[DONE]`.
 async fn process_chat_sse<S>(
     stream: S,
     tx_event: mpsc::Sender<Result<ResponseEvent>>,
     idle_timeout: Duration,
+    assistant_prefix: Option<String>,
+    expect_usage: bool,
 ) where
     S: Stream<Item = Result<Bytes>> + Unpin,
 {
     let mut stream = stream.eventsource();
 
-    // State to accumulate a function call across streaming chunks.
-    // OpenAI may split the `arguments` string over multiple `delta` events
-    // until the chunk whose `finish_reason` is `tool_calls` is emitted. We
-    // keep collecting the pieces here and forward a single
-    // `ResponseItem::FunctionCall` once the call is complete.
+    // State to accumulate function calls across streaming chunks. Providers
+    // split each call's `arguments` string over multiple `delta` events and
+    // may interleave the fragments of several calls, so the pieces are kept
+    // per `tool_calls[].index` until the chunk whose `finish_reason` is
+    // `tool_calls`. That chunk forwards one `ResponseItem::FunctionCall` per
+    // call, in index order.
     #[derive(Default)]
     struct FunctionCallState {
         name: Option<String>,
         arguments: String,
         call_id: Option<String>,
-        active: bool,
     }
 
-    let mut fn_call_state = FunctionCallState::default();
+    let mut fn_calls: BTreeMap<u64, FunctionCallState> = BTreeMap::new();
     let mut assistant_text = String::new();
+    let mut echoed_prefix = EchoedPrefix::new(assistant_prefix);
     let mut reasoning_text = String::new();
+    // Set once a choice reports its `finish_reason`. The usage chunk
+    // requested via `stream_options.include_usage` arrives after it, or in
+    // the same chunk.
+    let mut finished = false;
+    let mut token_usage: Option<TokenUsage> = None;
 
     loop {
-        let sse = match timeout(idle_timeout, stream.next()).await {
+        if finished && (token_usage.is_some() || !expect_usage) {
+            break;
+        }
+        let wait = if finished {
+            USAGE_GRACE_PERIOD
+        } else {
+            idle_timeout
+        };
+        let sse = match timeout(wait, stream.next()).await {
             Ok(Some(Ok(ev))) => ev,
-            Ok(Some(Err(e))) => {
+            Ok(Some(Err(e))) if !finished => {
                 let _ = tx_event
-                    .send(Err(CodexErr::Stream(e.to_string(), None)))
-                    .await;
-                return;
-            }
-            Ok(None) => {
-                // Stream closed gracefully – emit Completed with dummy id.
-                let _ = tx_event
-                    .send(Ok(ResponseEvent::Completed {
-                        response_id: String::new(),
-                        token_usage: None,
-                    }))
+                    .send(Err(CodexErr::stream_disconnected(e.to_string())))
                     .await;
                 return;
             }
+            // Stream closed gracefully, or the server does not send the
+            // usage chunk after all.
+            Ok(None) | Ok(Some(Err(_))) => break,
+            Err(_) if finished => break,
             Err(_) => {
                 let _ = tx_event
-                    .send(Err(CodexErr::Stream(
-                        "idle timeout waiting for SSE".into(),
-                        None,
+                    .send(Err(CodexErr::stream_disconnected(
+                        "idle timeout waiting for SSE",
                     )))
                     .await;
                 return;
@@ -270,38 +411,7 @@ async fn process_chat_sse<S>(
 
         // OpenAI Chat streaming sends a literal string "[DONE]" when finished.
         if sse.data.trim() == "[DONE]" {
-            // Emit any finalized items before closing so downstream consumers receive
-            // terminal events for both assistant content and raw reasoning.
-            if !assistant_text.is_empty() {
-                let item = ResponseItem::Message {
-                    role: "assistant".to_string(),
-                    content: vec![ContentItem::OutputText {
-                        text: std::mem::take(&mut assistant_text),
-                    }],
-                    id: None,
-                };
-                let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
-            }
-
-            if !reasoning_text.is_empty() {
-                let item = ResponseItem::Reasoning {
-                    id: String::new(),
-                    summary: Vec::new(),
-                    content: Some(vec![ReasoningItemContent::ReasoningText {
-                        text: std::mem::take(&mut reasoning_text),
-                    }]),
-                    encrypted_content: None,
-                };
-                let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
-            }
-
-            let _ = tx_event
-                .send(Ok(ResponseEvent::Completed {
-                    response_id: String::new(),
-                    token_usage: None,
-                }))
-                .await;
-            return;
+            break;
         }
 
         // Parse JSON chunk
@@ -311,6 +421,16 @@ async fn process_chat_sse<S>(
         };
         trace!("chat_completions received SSE chunk: {chunk:?}");
 
+        if let Some(usage) = chunk
+            .get("usage")
+            .filter(|u| !u.is_null())
+            .and_then(|u| serde_json::from_value::<ChatCompletionUsage>(u.clone()).ok())
+        {
+            let usage = TokenUsage::from(usage);
+            token_usage = Some(usage.clone());
+            let _ = tx_event.send(Ok(ResponseEvent::UsageUpdate(usage))).await;
+        }
+
         let choice_opt = chunk.get("choices").and_then(|c| c.get(0));
 
         if let Some(choice) = choice_opt {
@@ -321,17 +441,24 @@ async fn process_chat_sse<S>(
                 .and_then(|c| c.as_str())
                 && !content.is_empty()
             {
-                assistant_text.push_str(content);
-                let _ = tx_event
-                    .send(Ok(ResponseEvent::OutputTextDelta(content.to_string())))
-                    .await;
+                let content = echoed_prefix.strip(content);
+                send_text_delta(&tx_event, &mut assistant_text, content).await;
             }
 
-            // Forward any reasoning/thinking deltas if present.
-            // Some providers stream `reasoning` as a plain string while others
-            // nest the text under an object (e.g. `{ "reasoning": { "text": "…" } }`).
-            if let Some(reasoning_val) = choice.get("delta").and_then(|d| d.get("reasoning")) {
-                let mut maybe_text = reasoning_val.as_str().map(|s| s.to_string());
+            // Forward any reasoning/thinking deltas if present. DeepSeek and
+            // similar providers stream raw chain-of-thought as
+            // `reasoning_content`; others stream `reasoning` as a plain string
+            // or nest the text under an object (e.g. `{ "reasoning": { "text": "…" } }`).
+            let delta = choice.get("delta");
+            let reasoning_val = delta
+                .and_then(|d| d.get("reasoning_content"))
+                .filter(|v| v.as_str().is_some_and(|s| !s.is_empty()))
+                .or_else(|| delta.and_then(|d| d.get("reasoning")));
+            if let Some(reasoning_val) = reasoning_val {
+                let mut maybe_text = reasoning_val
+                    .as_str()
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string());
 
                 if maybe_text.is_none() && reasoning_val.is_object() {
                     if let Some(s) = reasoning_val
@@ -350,6 +477,9 @@ async fn process_chat_sse<S>(
                 }
 
                 if let Some(reasoning) = maybe_text {
+                    // Kept for the terminal `Reasoning` item, which records it
+                    // in the rollout.
+                    reasoning_text.push_str(&reasoning);
                     let _ = tx_event
                         .send(Ok(ResponseEvent::ReasoningContentDelta(reasoning)))
                         .await;
@@ -361,25 +491,46 @@ async fn process_chat_sse<S>(
                 .get("delta")
                 .and_then(|d| d.get("tool_calls"))
                 .and_then(|tc| tc.as_array())
-                && let Some(tool_call) = tool_calls.first()
             {
-                // Mark that we have an active function call in progress.
-                fn_call_state.active = true;
-
-                // Extract call_id if present.
-                if let Some(id) = tool_call.get("id").and_then(|v| v.as_str()) {
-                    fn_call_state.call_id.get_or_insert_with(|| id.to_string());
-                }
+                for (position, tool_call) in tool_calls.iter().enumerate() {
+                    let id = tool_call
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .filter(|id| !id.is_empty());
+                    // `index` is required by the API, but some providers omit
+                    // it; fall back to the call with the same id, then to the
+                    // position within this delta.
+                    let index = tool_call
+                        .get("index")
+                        .and_then(|i| i.as_u64())
+                        .or_else(|| {
+                            fn_calls
+                                .iter()
+                                .find(|(_, call)| id.is_some() && call.call_id.as_deref() == id)
+                                .map(|(index, _)| *index)
+                        })
+                        .unwrap_or(position as u64);
+                    let call = fn_calls.entry(index).or_default();
 
-                // Extract function details if present.
-                if let Some(function) = tool_call.get("function") {
-                    if let Some(name) = function.get("name").and_then(|n| n.as_str()) {
-                        fn_call_state.name.get_or_insert_with(|| name.to_string());
+                    // The id may arrive after the first argument fragment.
+                    if let Some(id) = id {
+                        call.call_id.get_or_insert_with(|| id.to_string());
                     }
 
-                    if let Some(args_fragment) = function.get("arguments").and_then(|a| a.as_str())
-                    {
-                        fn_call_state.arguments.push_str(args_fragment);
+                    if let Some(function) = tool_call.get("function") {
+                        if let Some(name) = function
+                            .get("name")
+                            .and_then(|n| n.as_str())
+                            .filter(|n| !n.is_empty())
+                        {
+                            call.name.get_or_insert_with(|| name.to_string());
+                        }
+
+                        if let Some(args_fragment) =
+                            function.get("arguments").and_then(|a| a.as_str())
+                        {
+                            call.arguments.push_str(args_fragment);
+                        }
                     }
                 }
             }
@@ -387,7 +538,7 @@ async fn process_chat_sse<S>(
             // Emit end-of-turn when finish_reason signals completion.
             if let Some(finish_reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
                 match finish_reason {
-                    "tool_calls" if fn_call_state.active => {
+                    "tool_calls" if !fn_calls.is_empty() => {
                         // First, flush the terminal raw reasoning so UIs can finalize
                         // the reasoning stream before any exec/tool events begin.
                         if !reasoning_text.is_empty() {
@@ -402,17 +553,20 @@ async fn process_chat_sse<S>(
                             let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
                         }
 
-                        // Then emit the FunctionCall response item.
-                        let item = ResponseItem::FunctionCall {
-                            id: None,
-                            name: fn_call_state.name.clone().unwrap_or_else(|| "".to_string()),
-                            arguments: fn_call_state.arguments.clone(),
-                            call_id: fn_call_state.call_id.clone().unwrap_or_else(String::new),
-                        };
-
-                        let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
+                        // Then emit one FunctionCall response item per call.
+                        for call in std::mem::take(&mut fn_calls).into_values() {
+                            let item = ResponseItem::FunctionCall {
+                                id: None,
+                                name: call.name.unwrap_or_default(),
+                                arguments: call.arguments,
+                                call_id: call.call_id.unwrap_or_default(),
+                            };
+                            let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
+                        }
                     }
                     "stop" => {
+                        send_text_delta(&tx_event, &mut assistant_text, echoed_prefix.finish())
+                            .await;
                         // Regular turn without tool-call. Emit the final assistant message
                         // as a single OutputItemDone so non-delta consumers see the result.
                         if !assistant_text.is_empty() {
@@ -441,19 +595,90 @@ async fn process_chat_sse<S>(
                     _ => {}
                 }
 
-                // Emit Completed regardless of reason so the agent can advance.
-                let _ = tx_event
-                    .send(Ok(ResponseEvent::Completed {
-                        response_id: String::new(),
-                        token_usage: None,
-                    }))
-                    .await;
+                // Completed follows the usage chunk, if one is expected.
+                finished = true;
+                fn_calls.clear();
+            }
+        }
+    }
 
-                // Prepare for potential next turn (should not happen in same stream).
-                // fn_call_state = FunctionCallState::default();
+    send_text_delta(&tx_event, &mut assistant_text, echoed_prefix.finish()).await;
+    // Emit any finalized items before completing so downstream consumers
+    // receive terminal events for both assistant content and raw reasoning.
+    if !assistant_text.is_empty() {
+        let item = ResponseItem::Message {
+            role: "assistant".to_string(),
+            content: vec![ContentItem::OutputText {
+                text: std::mem::take(&mut assistant_text),
+            }],
+            id: None,
+        };
+        let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
+    }
+    if !reasoning_text.is_empty() {
+        let item = ResponseItem::Reasoning {
+            id: String::new(),
+            summary: Vec::new(),
+            content: Some(vec![ReasoningItemContent::ReasoningText {
+                text: std::mem::take(&mut reasoning_text),
+            }]),
+            encrypted_content: None,
+        };
+        let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
+    }
+    let _ = tx_event
+        .send(Ok(ResponseEvent::Completed {
+            response_id: String::new(),
+            token_usage,
+        }))
+        .await;
+}
 
-                return; // End processing for this SSE stream.
-            }
+/// Forwards a piece of the answer, unless it is empty.
+async fn send_text_delta(
+    tx_event: &mpsc::Sender<Result<ResponseEvent>>,
+    assistant_text: &mut String,
+    text: String,
+) {
+    if text.is_empty() {
+        return;
+    }
+    assistant_text.push_str(&text);
+    let _ = tx_event
+        .send(Ok(ResponseEvent::OutputTextDelta(text)))
+        .await;
+}
+
+/// `usage` object of the final Chat Completions chunk.
+#[derive(Debug, Deserialize)]
+struct ChatCompletionUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    total_tokens: u64,
+    prompt_tokens_details: Option<ChatCompletionPromptTokensDetails>,
+    completion_tokens_details: Option<ChatCompletionCompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionPromptTokensDetails {
+    cached_tokens: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionCompletionTokensDetails {
+    reasoning_tokens: Option<u64>,
+}
+
+impl From<ChatCompletionUsage> for TokenUsage {
+    fn from(val: ChatCompletionUsage) -> Self {
+        TokenUsage {
+            input_tokens: val.prompt_tokens,
+            cached_input_tokens: val.prompt_tokens_details.and_then(|d| d.cached_tokens),
+            output_tokens: val.completion_tokens,
+            reasoning_output_tokens: val
+                .completion_tokens_details
+                .and_then(|d| d.reasoning_tokens),
+            total_tokens: val.total_tokens,
         }
     }
 }
@@ -513,6 +738,18 @@ where
 
                     let is_assistant_delta = matches!(&item, codex_protocol::models::ResponseItem::Message { role, .. } if role == "assistant");
 
+                    // The streamed reasoning is emitted as one item on
+                    // Completed when aggregating, so drop the terminal one.
+                    if matches!(this.mode, AggregateMode::AggregatedOnly)
+                        && !this.cumulative_reasoning.is_empty()
+                        && matches!(
+                            &item,
+                            codex_protocol::models::ResponseItem::Reasoning { .. }
+                        )
+                    {
+                        continue;
+                    }
+
                     if is_assistant_delta {
                         // Only use the final assistant message if we have not
                         // seen any deltas; otherwise, deltas already built the
@@ -628,6 +865,24 @@ where
                 Poll::Ready(Some(Ok(ResponseEvent::WebSearchCallBegin { call_id }))) => {
                     return Poll::Ready(Some(Ok(ResponseEvent::WebSearchCallBegin { call_id })));
                 }
+                Poll::Ready(Some(Ok(
+                    event @ (ResponseEvent::RateLimited { .. }
+                    | ResponseEvent::RateLimits(_)
+                    | ResponseEvent::UsageUpdate(_)
+                    | ResponseEvent::WireApiSelected(_)
+                    | ResponseEvent::BackgroundProgress { .. }),
+                ))) => {
+                    return Poll::Ready(Some(Ok(event)));
+                }
+                Poll::Ready(Some(Ok(ResponseEvent::FunctionCallArgumentsDelta {
+                    call_id,
+                    delta,
+                }))) => {
+                    return Poll::Ready(Some(Ok(ResponseEvent::FunctionCallArgumentsDelta {
+                        call_id,
+                        delta,
+                    })));
+                }
             }
         }
     }
@@ -676,3 +931,203 @@ impl<S> AggregatedChatStream<S> {
         Self::new(inner, AggregateMode::Streaming)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio_util::io::ReaderStream;
+
+    /// Runs the Chat Completions SSE parser over `body` and returns the
+    /// function calls it emitted.
+    async fn function_calls(body: &'static str) -> Vec<ResponseItem> {
+        let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let stream = ReaderStream::new(std::io::Cursor::new(body)).map_err(CodexErr::Io);
+        tokio::spawn(process_chat_sse(
+            stream,
+            tx,
+            Duration::from_secs(5),
+            None,
+            true,
+        ));
+
+        let mut calls = Vec::new();
+        while let Some(event) = rx.recv().await {
+            if let ResponseEvent::OutputItemDone(item @ ResponseItem::FunctionCall { .. }) =
+                event.expect("stream error")
+            {
+                calls.push(item);
+            }
+        }
+        calls
+    }
+
+    fn shell_call(call_id: &str, command: &str) -> ResponseItem {
+        ResponseItem::FunctionCall {
+            id: None,
+            name: "shell".to_string(),
+            arguments: format!("{{\"command\":[\"{command}\"]}}"),
+            call_id: call_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_calls_are_kept_apart() {
+        let fixtures = [
+            include_str!("../tests/fixtures/chat_tool_calls_openai.sse"),
+            include_str!("../tests/fixtures/chat_tool_calls_groq.sse"),
+            // Interleaves the fragments of both calls and sends the first
+            // call's id after its first argument fragment.
+            include_str!("../tests/fixtures/chat_tool_calls_together.sse"),
+        ];
+        for body in fixtures {
+            assert_eq!(
+                function_calls(body).await,
+                vec![shell_call("call_a", "ls"), shell_call("call_b", "pwd")]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_calls_without_index_fall_back_to_id_and_position() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[",
+            "{\"id\":\"call_a\",\"function\":{\"name\":\"shell\",\"arguments\":\"{\\\"command\\\":\"}},",
+            "{\"id\":\"call_b\",\"function\":{\"name\":\"shell\",\"arguments\":\"{\\\"command\\\":\"}}",
+            "]},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[",
+            "{\"id\":\"call_b\",\"function\":{\"arguments\":\"[\\\"pwd\\\"]}\"}}",
+            "]},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[",
+            "{\"id\":\"call_a\",\"function\":{\"arguments\":\"[\\\"ls\\\"]}\"}}",
+            "]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        assert_eq!(
+            function_calls(body).await,
+            vec![shell_call("call_a", "ls"), shell_call("call_b", "pwd")]
+        );
+    }
+
+    /// Runs the parser over `body` with `assistant_prefix` and returns the
+    /// text deltas and the final message it emitted.
+    async fn answer(body: &'static str, assistant_prefix: &str) -> (Vec<String>, String) {
+        let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let stream = ReaderStream::new(std::io::Cursor::new(body)).map_err(CodexErr::Io);
+        tokio::spawn(process_chat_sse(
+            stream,
+            tx,
+            Duration::from_secs(5),
+            Some(assistant_prefix.to_string()),
+            true,
+        ));
+
+        let mut deltas = Vec::new();
+        let mut message = String::new();
+        while let Some(event) = rx.recv().await {
+            match event.expect("stream error") {
+                ResponseEvent::OutputTextDelta(delta) => deltas.push(delta),
+                ResponseEvent::OutputItemDone(ResponseItem::Message { content, .. }) => {
+                    for item in content {
+                        if let ContentItem::OutputText { text } = item {
+                            message.push_str(&text);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        (deltas, message)
+    }
+
+    #[tokio::test]
+    async fn echoed_assistant_prefix_is_stripped_from_deltas() {
+        // The echoed prefix is split across deltas and runs into the answer.
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"```js\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"on\\n{\\\"ok\\\"\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\": true}\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (deltas, message) = answer(body, "```json\n").await;
+        assert_eq!(deltas, vec!["{\"ok\"".to_string(), ": true}".to_string()]);
+        assert_eq!(message, "{\"ok\": true}");
+    }
+
+    #[tokio::test]
+    async fn answer_without_the_prefix_is_passed_on_unchanged() {
+        // llama.cpp continues after the prefix without repeating it.
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"`\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"ok\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (deltas, message) = answer(body, "```json\n").await;
+        assert_eq!(deltas, vec!["`ok".to_string()]);
+        assert_eq!(message, "`ok");
+
+        // An answer that ends while it still looks like the prefix is kept.
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"``\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        assert_eq!(
+            answer(body, "```json\n").await,
+            (vec!["``".to_string()], "``".to_string())
+        );
+    }
+
+    /// Runs the parser over `chunks` on a connection the server then holds
+    /// open, and returns the usage reported by `Completed`.
+    async fn completed_usage(chunks: &[&'static str], expect_usage: bool) -> Option<TokenUsage> {
+        let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let stream = futures::stream::iter(
+            chunks
+                .iter()
+                .map(|chunk| Ok::<_, CodexErr>(Bytes::from_static(chunk.as_bytes())))
+                .collect::<Vec<_>>(),
+        )
+        .chain(futures::stream::pending());
+        tokio::spawn(process_chat_sse(
+            stream,
+            tx,
+            Duration::from_secs(600),
+            None,
+            expect_usage,
+        ));
+
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("no Completed before the connection closed");
+            if let Some(Ok(ResponseEvent::Completed { token_usage, .. })) = event {
+                return token_usage;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn completes_once_usage_follows_the_final_choice() {
+        let usage = completed_usage(
+            &[
+                "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":2,\"total_tokens\":9}}\n\n",
+            ],
+            true,
+        )
+        .await;
+        assert_eq!(usage.map(|usage| usage.total_tokens), Some(9));
+    }
+
+    #[tokio::test]
+    async fn completes_after_the_final_choice_without_usage() {
+        let chunk =
+            "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"},\"finish_reason\":\"stop\"}]}\n\n";
+        assert_eq!(completed_usage(&[chunk], false).await, None);
+        // Servers that ignore `include_usage` hold things up only briefly.
+        assert_eq!(completed_usage(&[chunk], true).await, None);
+    }
+}
@@ -1,8 +1,13 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io::BufRead;
 use std::path::Path;
 use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
+use chrono::Utc;
 use codex_login::AuthManager;
 use codex_login::AuthMode;
 use eventsource_stream::Eventsource;
@@ -14,51 +19,58 @@ use serde_json::Value;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 use tokio_util::io::ReaderStream;
+use tracing::Instrument;
 use tracing::debug;
 use tracing::trace;
 use tracing::warn;
 use uuid::Uuid;
 
+use crate::anthropic_messages::stream_anthropic_messages;
+use crate::cassette;
 use crate::chat_completions::AggregateStreamExt;
 use crate::chat_completions::stream_chat_completions;
 use crate::client_common::Prompt;
+use crate::client_common::RequestItem;
 use crate::client_common::ResponseEvent;
 use crate::client_common::ResponseStream;
 use crate::client_common::ResponsesApiRequest;
+use crate::client_common::StreamMetrics;
 use crate::client_common::create_reasoning_param_for_request;
 use crate::client_common::create_text_param_for_request;
+use crate::client_common::spawn_response_task;
 use crate::config::Config;
+use crate::delta_coalescing::coalesce_deltas;
 use crate::error::CodexErr;
+use crate::error::ProviderErrorDetails;
 use crate::error::Result;
 use crate::error::UsageLimitReachedError;
+use crate::error::http_error;
 use crate::flags::CODEX_RS_SSE_FIXTURE;
 use crate::model_family::ModelFamily;
+use crate::model_pricing::ModelPricing;
+use crate::model_pricing::find_model_pricing;
 use crate::model_provider_info::ModelProviderInfo;
 use crate::model_provider_info::WireApi;
 use crate::openai_model_info::get_model_info;
 use crate::openai_tools::create_tools_json_for_responses_api;
+use crate::otel;
 use crate::protocol::TokenUsage;
+use crate::rate_limits;
+use crate::rate_limits::parse_rate_limits;
 use crate::user_agent::get_codex_user_agent;
-use crate::util::backoff;
+use crate::util::parse_retry_after;
+use crate::wire_api_cache::WireApiCache;
 use codex_protocol::config_types::ReasoningEffort as ReasoningEffortConfig;
 use codex_protocol::config_types::ReasoningSummary as ReasoningSummaryConfig;
 use codex_protocol::models::ResponseItem;
 use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 
-#[derive(Debug, Deserialize)]
-struct ErrorResponse {
-    error: Error,
-}
-
-#[derive(Debug, Deserialize)]
-struct Error {
-    r#type: Option<String>,
-    message: Option<String>,
-
-    // Optional fields available on "usage_limit_reached" and "usage_not_included" errors
-    plan_type: Option<String>,
-    resets_in_seconds: Option<u64>,
-}
+/// How long a background response's stream may stay quiet before Codex
+/// checks on the response and reports that it is still running.
+const BACKGROUND_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone)]
 pub struct ModelClient {
@@ -69,6 +81,12 @@ pub struct ModelClient {
     session_id: Uuid,
     effort: ReasoningEffortConfig,
     summary: ReasoningSummaryConfig,
+    /// The wire API found for a provider that does not configure one. Unset
+    /// until the first request has been probed, unless it was cached.
+    probed_wire_api: Arc<OnceLock<WireApi>>,
+    /// Set once the provider rejected `background: true`; later requests of
+    /// the session are sent without it.
+    background_rejected: Arc<AtomicBool>,
 }
 
 impl ModelClient {
@@ -80,6 +98,12 @@ impl ModelClient {
         summary: ReasoningSummaryConfig,
         session_id: Uuid,
     ) -> Self {
+        let probed_wire_api = OnceLock::new();
+        if provider.probes_wire_api()
+            && let Some(wire_api) = WireApiCache::new(config.dirs.cache_dir()).get(&provider)
+        {
+            let _ = probed_wire_api.set(wire_api);
+        }
         Self {
             config,
             auth_manager,
@@ -88,6 +112,8 @@ impl ModelClient {
             session_id,
             effort,
             summary,
+            probed_wire_api: Arc::new(probed_wire_api),
+            background_rejected: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -97,48 +123,185 @@ impl ModelClient {
             .or_else(|| get_model_info(&self.config.model_family).map(|info| info.context_window))
     }
 
-    /// Dispatches to either the Responses or Chat implementation depending on
-    /// the provider config.  Public callers always invoke `stream()` – the
-    /// specialised helpers are private to avoid accidental misuse.
+    /// Key that lets the provider reuse its prompt cache. Derived from the
+    /// session id, which is restored from the rollout when a session is
+    /// resumed, unless pinned via `prompt_cache_key` in the config.
+    fn prompt_cache_key(&self) -> String {
+        self.config
+            .prompt_cache_key
+            .clone()
+            .unwrap_or_else(|| self.session_id.to_string())
+    }
+
+    /// Dispatches to the Responses, Chat or Anthropic implementation depending
+    /// on the provider config.  Public callers always invoke `stream()` – the
+    /// specialised helpers are private to avoid accidental misuse. Runs of
+    /// text and reasoning deltas are merged per the `stream_coalescing`
+    /// config.
     pub async fn stream(&self, prompt: &Prompt) -> Result<ResponseStream> {
-        match self.provider.wire_api {
-            WireApi::Responses => self.stream_responses(prompt).await,
+        let stream = self.stream_uncoalesced(prompt).await?;
+        Ok(coalesce_deltas(stream, self.config.stream_coalescing))
+    }
+
+    async fn stream_uncoalesced(&self, prompt: &Prompt) -> Result<ResponseStream> {
+        if self.provider.probes_wire_api() && self.probed_wire_api.get().is_none() {
+            return self.stream_probing_wire_api(prompt).await;
+        }
+        match self.get_wire_api() {
+            WireApi::Responses => {
+                self.warn_if_chat_only_settings_ignored("Responses");
+                self.stream_responses(prompt).await
+            }
             WireApi::Chat => {
-                // Create the raw streaming connection first.
-                let response_stream = stream_chat_completions(
+                let provider = ModelProviderInfo {
+                    wire_api: Some(WireApi::Chat),
+                    ..self.provider.clone()
+                };
+                self.stream_chat(prompt, &provider).await
+            }
+            WireApi::Anthropic => {
+                self.warn_if_chat_only_settings_ignored("Anthropic Messages");
+                stream_anthropic_messages(
                     prompt,
                     &self.config.model_family,
+                    self.config.model_max_output_tokens,
                     &self.client,
                     &self.provider,
+                    self.config.cassette.as_ref(),
                 )
-                .await?;
+                .await
+            }
+        }
+    }
 
-                // Wrap it with the aggregation adapter so callers see *only*
-                // the final assistant message per turn (matching the
-                // behaviour of the Responses API).
-                let mut aggregated = if self.config.show_raw_agent_reasoning {
-                    crate::chat_completions::AggregatedChatStream::streaming_mode(response_stream)
-                } else {
-                    response_stream.aggregate()
+    /// Sends the first request for a provider without a `wire_api` to the
+    /// Responses API, and falls back to Chat Completions when the server has
+    /// no such endpoint. The outcome is kept for the session, cached per
+    /// provider and announced with [`ResponseEvent::WireApiSelected`].
+    async fn stream_probing_wire_api(&self, prompt: &Prompt) -> Result<ResponseStream> {
+        let mut stream = self.stream_responses(prompt).await?;
+        // Rate limits and retries are reported before the request's outcome.
+        let mut events = Vec::new();
+        let first = loop {
+            match stream.rx_event.recv().await {
+                Some(Ok(
+                    event @ (ResponseEvent::RateLimited { .. } | ResponseEvent::RateLimits(_)),
+                )) => events.push(Ok(event)),
+                first => break first,
+            }
+        };
+        match first {
+            Some(Err(e)) if is_missing_endpoint(&e) => {
+                debug!("falling back to Chat Completions: {e}");
+                let provider = ModelProviderInfo {
+                    wire_api: Some(WireApi::Chat),
+                    ..self.provider.clone()
                 };
+                // Only a server that accepts the request is known to speak
+                // Chat Completions.
+                let stream = self.stream_chat(prompt, &provider).await?;
+                self.remember_wire_api(WireApi::Chat);
+                events.push(Ok(ResponseEvent::WireApiSelected(WireApi::Chat)));
+                Ok(prepend_events(events, stream))
+            }
+            Some(Ok(event)) => {
+                self.remember_wire_api(WireApi::Responses);
+                events.push(Ok(ResponseEvent::WireApiSelected(WireApi::Responses)));
+                events.push(Ok(event));
+                Ok(prepend_events(events, stream))
+            }
+            // Any other failure says nothing about the API; the next request
+            // probes again.
+            Some(Err(e)) => {
+                events.push(Err(e));
+                Ok(prepend_events(events, stream))
+            }
+            None => Ok(prepend_events(events, stream)),
+        }
+    }
 
-                // Bridge the aggregated stream back into a standard
-                // `ResponseStream` by forwarding events through a channel.
-                let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+    /// `stop` and `assistant_prefix` have no equivalent outside Chat
+    /// Completions.
+    fn warn_if_chat_only_settings_ignored(&self, api: &str) {
+        let family = &self.config.model_family;
+        if !family.stop.is_empty() || family.assistant_prefix.is_some() {
+            warn!(
+                "stop and assistant_prefix are ignored by the {api} API; set for model: {}",
+                family.slug
+            );
+        }
+        if self.provider.seed.is_some() {
+            warn!(
+                "seed is ignored by the {api} API; set for provider: {}",
+                self.provider.name
+            );
+        }
+    }
 
-                tokio::spawn(async move {
-                    use futures::StreamExt;
-                    while let Some(ev) = aggregated.next().await {
-                        // Exit early if receiver hung up.
-                        if tx.send(ev).await.is_err() {
-                            break;
-                        }
-                    }
-                });
+    /// Whether to send the request in background mode. Background responses
+    /// must be stored, and resuming their stream is the whole point.
+    fn use_background_mode(&self, store: bool) -> bool {
+        let family = &self.config.model_family;
+        if !family.use_background_mode || self.background_rejected.load(Ordering::Relaxed) {
+            return false;
+        }
+        if !store || !self.provider.kind.capabilities().resumable_streams {
+            warn!(
+                "use_background_mode is ignored without stored responses on a provider that can resume streams; set for model: {}",
+                family.slug
+            );
+            return false;
+        }
+        true
+    }
+
+    fn remember_wire_api(&self, wire_api: WireApi) {
+        let _ = self.probed_wire_api.set(wire_api);
+        WireApiCache::new(self.config.dirs.cache_dir()).put(&self.provider, wire_api);
+    }
+
+    async fn stream_chat(
+        &self,
+        prompt: &Prompt,
+        provider: &ModelProviderInfo,
+    ) -> Result<ResponseStream> {
+        // Create the raw streaming connection first.
+        let response_stream = stream_chat_completions(
+            prompt,
+            &self.config.model_family,
+            self.config.model_max_output_tokens,
+            &self.client,
+            provider,
+            self.config.cassette.as_ref(),
+        )
+        .await?;
+        // The raw stream sees the deltas the aggregation swallows.
+        let metrics = response_stream.metrics.clone();
+
+        // Wrap it with the aggregation adapter so callers see *only*
+        // the final assistant message per turn (matching the
+        // behaviour of the Responses API).
+        let mut aggregated = if self.config.show_raw_agent_reasoning {
+            crate::chat_completions::AggregatedChatStream::streaming_mode(response_stream)
+        } else {
+            response_stream.aggregate()
+        };
+
+        // Bridge the aggregated stream back into a standard
+        // `ResponseStream` by forwarding events through a channel.
+        let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
 
-                Ok(ResponseStream { rx_event: rx })
+        spawn_response_task(tx.clone(), async move {
+            use futures::StreamExt;
+            while let Some(ev) = aggregated.next().await {
+                // Exit early if receiver hung up.
+                if tx.send(ev).await.is_err() {
+                    break;
+                }
             }
-        }
+        });
+
+        Ok(ResponseStream::new(rx, metrics))
     }
 
     /// Implementation for the OpenAI *Responses* experimental API.
@@ -159,7 +322,7 @@ impl ModelClient {
 
         let store = prompt.store && auth_mode != Some(AuthMode::ChatGPT);
 
-        let full_instructions = prompt.get_full_instructions(&self.config.model_family);
+        let full_instructions = prompt.get_full_instructions(&self.config.model_family)?;
         let tools_json = create_tools_json_for_responses_api(&prompt.tools)?;
         let reasoning = create_reasoning_param_for_request(
             &self.config.model_family,
@@ -168,45 +331,371 @@ impl ModelClient {
         );
 
         // Request encrypted COT if we are not storing responses,
-        // otherwise reasoning items will be referenced by ID
+        // otherwise reasoning items will be referenced by ID. Models without
+        // reasoning reject the include, so only ask when reasoning is on.
         let include: Vec<String> = if !store && reasoning.is_some() {
             vec!["reasoning.encrypted_content".to_string()]
         } else {
             vec![]
         };
 
-        let input_with_instructions = prompt.get_formatted_input();
+        let (input, dropped_input_items) =
+            request_input(prompt.get_formatted_input(), reasoning.is_some(), store);
 
-        // Only include `text.verbosity` for GPT-5 family models
-        let text = if self.config.model_family.family == "gpt-5" {
-            create_text_param_for_request(self.config.model_verbosity)
-        } else {
-            if self.config.model_verbosity.is_some() {
-                warn!(
-                    "model_verbosity is set but ignored for non-gpt-5 model family: {}",
-                    self.config.model_family.family
-                );
-            }
-            None
-        };
+        // Only include `text.verbosity` for model families that accept it.
+        let text = create_text_param_for_request(
+            &self.config.model_family,
+            self.config.model_verbosity,
+            &prompt.output_schema,
+        );
+        if self.config.model_verbosity.is_some() && !self.config.model_family.supports_verbosity {
+            warn!(
+                "model_verbosity is set but ignored for model family without verbosity support: {}",
+                self.config.model_family.family
+            );
+        }
 
         let payload = ResponsesApiRequest {
             model: &self.config.model,
             instructions: &full_instructions,
-            input: &input_with_instructions,
+            input: &input,
             tools: &tools_json,
             tool_choice: "auto",
-            parallel_tool_calls: false,
+            parallel_tool_calls: self.config.max_parallel_tool_calls > 1,
             reasoning,
             store,
+            background: self.use_background_mode(store),
             stream: true,
             include,
-            prompt_cache_key: Some(self.session_id.to_string()),
+            prompt_cache_key: Some(self.prompt_cache_key()),
             text,
+            max_output_tokens: self.config.model_max_output_tokens,
+            temperature: self.provider.temperature,
+            top_p: self.provider.top_p,
+        };
+
+        // Serialize up front so the request can be sent (and retried) from a
+        // background task that does not borrow `prompt`.
+        let mut background = payload.background;
+        let mut payload = serde_json::to_value(&payload)?;
+        self.provider.shape_request_body(&mut payload);
+
+        let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+        let metrics = Arc::new(StreamMetrics::new(Instant::now()));
+        metrics.add_dropped_input_items(dropped_input_items);
+        let client = self.clone();
+        let task_metrics = metrics.clone();
+        spawn_response_task(tx_event.clone(), async move {
+            let started = Instant::now();
+            let resp = loop {
+                match client
+                    .send_responses_request(&payload, &tx_event, &task_metrics)
+                    .await
+                {
+                    Ok(resp) => break resp,
+                    Err(e) if background && is_background_rejected(&e) => {
+                        warn!(
+                            "the provider rejected background mode; sending requests without it: {e}"
+                        );
+                        client.background_rejected.store(true, Ordering::Relaxed);
+                        background = false;
+                        if let Some(fields) = payload.as_object_mut() {
+                            fields.remove("background");
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx_event.send(Err(e)).await;
+                        return;
+                    }
+                }
+            };
+            let idle_timeout = if background {
+                client
+                    .provider
+                    .stream_idle_timeout()
+                    .min(BACKGROUND_HEARTBEAT_INTERVAL)
+            } else {
+                client.provider.stream_idle_timeout()
+            };
+            let mut guard = CancelOnDrop {
+                client: background.then(|| client.clone()),
+                cursor: SseCursor::default(),
+            };
+            let mut resume_attempts = 0;
+            let mut stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
+            loop {
+                let SseEnd::Disconnected(err) =
+                    forward_sse(stream, &tx_event, idle_timeout, &mut guard.cursor).await
+                else {
+                    if !tx_event.is_closed() {
+                        guard.disarm();
+                    }
+                    return;
+                };
+                let resumed = if background {
+                    client
+                        .resume_background_response(
+                            &guard.cursor,
+                            started,
+                            &tx_event,
+                            &mut resume_attempts,
+                            &task_metrics,
+                        )
+                        .await
+                } else {
+                    client
+                        .resume_responses_stream(
+                            store,
+                            &guard.cursor,
+                            &mut resume_attempts,
+                            &task_metrics,
+                        )
+                        .await
+                };
+                match resumed {
+                    Some(resp) => stream = resp.bytes_stream().map_err(CodexErr::Reqwest),
+                    None => {
+                        let _ = tx_event.send(Err(err)).await;
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(ResponseStream::new(rx_event, metrics))
+    }
+
+    /// Reopens the stream of a response whose connection dropped, continuing
+    /// after the last event received.
+    ///
+    /// Returns `None` when the provider cannot resume streams, the response
+    /// was not stored server-side, no event carried a cursor yet, or the
+    /// provider's stream retry budget is spent. The caller then falls back to
+    /// re-running the turn.
+    async fn resume_responses_stream(
+        &self,
+        store: bool,
+        cursor: &SseCursor,
+        attempt: &mut u64,
+        metrics: &StreamMetrics,
+    ) -> Option<reqwest::Response> {
+        if !store || !self.provider.kind.capabilities().resumable_streams {
+            return None;
+        }
+        let (Some(response_id), Some(starting_after)) =
+            (cursor.response_id.as_deref(), cursor.sequence_number)
+        else {
+            return None;
+        };
+
+        while *attempt < self.provider.stream_max_retries() {
+            *attempt += 1;
+            metrics.add_retries(1);
+            tokio::time::sleep(self.provider.request_retry_delay(*attempt, None)).await;
+
+            match self
+                .reopen_responses_stream(response_id, starting_after)
+                .await
+            {
+                Ok(resp) => return resp,
+                Err(e) => debug!("resuming response {response_id} failed: {e}"),
+            }
+        }
+        None
+    }
+
+    /// Reconnects to a background response whose stream dropped or went
+    /// quiet. Checks that the response is still running on the server,
+    /// reports so with [`ResponseEvent::BackgroundProgress`], and reopens the
+    /// stream after the last event received. A response that finished in
+    /// the meantime streams its remaining events.
+    ///
+    /// Connections are expected to drop during a long background turn, so
+    /// only failed attempts use up the provider's stream retry budget, and
+    /// finding the response still running refills it.
+    async fn resume_background_response(
+        &self,
+        cursor: &SseCursor,
+        started: Instant,
+        tx_event: &mpsc::Sender<Result<ResponseEvent>>,
+        attempt: &mut u64,
+        metrics: &StreamMetrics,
+    ) -> Option<reqwest::Response> {
+        let (Some(response_id), Some(starting_after)) =
+            (cursor.response_id.as_deref(), cursor.sequence_number)
+        else {
+            return None;
         };
 
+        loop {
+            // Also paces a connection that drops as soon as it is reopened.
+            tokio::time::sleep(self.provider.request_retry_delay(*attempt + 1, None)).await;
+            match self.background_response_status(response_id).await {
+                Ok(None) => return None,
+                Ok(Some(status)) if status == "cancelled" => {
+                    debug!("background response {response_id} was cancelled");
+                    return None;
+                }
+                Ok(Some(status)) => {
+                    let running = matches!(status.as_str(), "queued" | "in_progress");
+                    if running {
+                        let event = ResponseEvent::BackgroundProgress {
+                            status,
+                            elapsed: started.elapsed(),
+                        };
+                        if tx_event.send(Ok(event)).await.is_err() {
+                            return None;
+                        }
+                    }
+                    match self
+                        .reopen_responses_stream(response_id, starting_after)
+                        .await
+                    {
+                        Ok(resp) => {
+                            if running && resp.is_some() {
+                                *attempt = 0;
+                            }
+                            return resp;
+                        }
+                        Err(e) => debug!("resuming response {response_id} failed: {e}"),
+                    }
+                }
+                Err(e) => debug!("checking on response {response_id} failed: {e}"),
+            }
+            if *attempt >= self.provider.stream_max_retries() {
+                return None;
+            }
+            *attempt += 1;
+            metrics.add_retries(1);
+        }
+    }
+
+    /// Streams the events of the stored response `response_id` again,
+    /// starting after the event with sequence number `starting_after`.
+    /// `Ok(None)` means the response cannot be resumed, however often it is
+    /// tried.
+    async fn reopen_responses_stream(
+        &self,
+        response_id: &str,
+        starting_after: u64,
+    ) -> Result<Option<reqwest::Response>> {
+        let auth = self.auth_manager.as_ref().and_then(|m| m.auth());
+        let req_builder = match self
+            .provider
+            .create_resume_request_builder(&self.client, &auth, response_id, starting_after)
+            .await
+        {
+            Ok(req_builder) => req_builder,
+            Err(e) => {
+                debug!("cannot resume response {response_id}: {e}");
+                return Ok(None);
+            }
+        };
+        let req_builder = self.with_stored_response_headers(req_builder, "text/event-stream");
+        let resp = cassette::send(self.config.cassette.as_ref(), req_builder).await??;
+        let status = resp.status();
+        if status.is_success() {
+            debug!("resumed response {response_id} after event {starting_after}");
+            Ok(Some(resp))
+        } else if status.is_client_error() {
+            // The provider does not know this response (or cannot stream
+            // it again); retrying will not change that.
+            debug!("cannot resume response {response_id}: {status}");
+            Ok(None)
+        } else {
+            Err(http_error(status, resp.text().await.unwrap_or_default()))
+        }
+    }
+
+    /// Status of the stored response `response_id`, e.g. `"in_progress"`,
+    /// or `None` when the provider does not know it.
+    async fn background_response_status(&self, response_id: &str) -> Result<Option<String>> {
+        #[derive(Deserialize)]
+        struct StoredResponse {
+            status: String,
+        }
+
+        let auth = self.auth_manager.as_ref().and_then(|m| m.auth());
+        let req_builder = self
+            .provider
+            .create_retrieve_request_builder(&self.client, &auth, response_id)
+            .await?;
+        let req_builder = self.with_stored_response_headers(req_builder, "application/json");
+        let resp = cassette::send(self.config.cassette.as_ref(), req_builder).await??;
+        let status = resp.status();
+        let body = resp.text().await?;
+        if status.is_client_error() {
+            debug!("cannot check on response {response_id}: {status}");
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(http_error(status, body));
+        }
+        Ok(Some(serde_json::from_str::<StoredResponse>(&body)?.status))
+    }
+
+    /// Stops the background response `response_id`, whose output nobody is
+    /// waiting for anymore.
+    async fn cancel_background_response(&self, response_id: &str) {
+        let auth = self.auth_manager.as_ref().and_then(|m| m.auth());
+        let res = async {
+            let req_builder = self
+                .provider
+                .create_cancel_request_builder(&self.client, &auth, response_id)
+                .await?;
+            let req_builder = self.with_stored_response_headers(req_builder, "application/json");
+            Ok::<_, CodexErr>(cassette::send(self.config.cassette.as_ref(), req_builder).await??)
+        }
+        .await;
+        match res {
+            Ok(resp) if resp.status().is_success() => {
+                debug!("cancelled background response {response_id}");
+            }
+            Ok(resp) => debug!(
+                "cancelling background response {response_id} failed: {}",
+                resp.status()
+            ),
+            Err(e) => debug!("cancelling background response {response_id} failed: {e}"),
+        }
+    }
+
+    /// Adds the headers of the request that created a stored response to a
+    /// follow-up request about it.
+    fn with_stored_response_headers(
+        &self,
+        req_builder: reqwest::RequestBuilder,
+        accept: &'static str,
+    ) -> reqwest::RequestBuilder {
+        let originator = &self.config.responses_originator_header;
+        req_builder
+            .header("OpenAI-Beta", "responses=experimental")
+            .header("session_id", self.session_id.to_string())
+            .header(reqwest::header::ACCEPT, accept)
+            .header("originator", originator)
+            .header(
+                "User-Agent",
+                get_codex_user_agent(Some(originator), self.config.user_agent_suffix.as_deref()),
+            )
+    }
+
+    /// POSTs `payload` to the Responses endpoint, retrying according to the
+    /// provider's retry policy until a successful response is received.
+    ///
+    /// Retries only happen before any of the response body has been read, so
+    /// they can never duplicate output items. Each scheduled retry after a
+    /// retryable status is announced on `tx_event` as
+    /// [`ResponseEvent::RateLimited`], and the rate limits reported by every
+    /// response as [`ResponseEvent::RateLimits`].
+    async fn send_responses_request(
+        &self,
+        payload: &Value,
+        tx_event: &mpsc::Sender<Result<ResponseEvent>>,
+        metrics: &StreamMetrics,
+    ) -> Result<reqwest::Response> {
+        let auth_manager = self.auth_manager.clone();
         let mut attempt = 0;
         let max_retries = self.provider.request_max_retries();
+        let mut refreshed_token = false;
 
         loop {
             attempt += 1;
@@ -214,11 +703,7 @@ impl ModelClient {
             // Always fetch the latest auth in case a prior attempt refreshed the token.
             let auth = auth_manager.as_ref().and_then(|m| m.auth());
 
-            trace!(
-                "POST to {}: {}",
-                self.provider.get_full_url(&auth),
-                serde_json::to_string(&payload)?
-            );
+            trace!("POST to {}: {payload}", self.provider.get_full_url(&auth));
 
             let mut req_builder = self
                 .provider
@@ -229,7 +714,7 @@ impl ModelClient {
                 .header("OpenAI-Beta", "responses=experimental")
                 .header("session_id", self.session_id.to_string())
                 .header(reqwest::header::ACCEPT, "text/event-stream")
-                .json(&payload);
+                .json(payload);
 
             if let Some(auth) = auth.as_ref()
                 && auth.mode == AuthMode::ChatGPT
@@ -240,9 +725,16 @@ impl ModelClient {
 
             let originator = &self.config.responses_originator_header;
             req_builder = req_builder.header("originator", originator);
-            req_builder = req_builder.header("User-Agent", get_codex_user_agent(Some(originator)));
+            req_builder = req_builder.header(
+                "User-Agent",
+                get_codex_user_agent(Some(originator), self.config.user_agent_suffix.as_deref()),
+            );
 
-            let res = req_builder.send().await;
+            let http_span = otel::http_request_span(attempt);
+            let res = cassette::send(self.config.cassette.as_ref(), req_builder)
+                .instrument(http_span.clone())
+                .await?;
+            otel::record_http_result(&http_span, &res);
             if let Ok(resp) = &res {
                 trace!(
                     "Response status: {}, request-id: {}",
@@ -254,35 +746,48 @@ impl ModelClient {
                 );
             }
 
+            let limits = match &res {
+                Ok(resp) => parse_rate_limits(resp.headers(), Utc::now()),
+                Err(_) => None,
+            };
+            if let Some(snapshot) = &limits
+                && tx_event
+                    .send(Ok(ResponseEvent::RateLimits(snapshot.clone())))
+                    .await
+                    .is_err()
+            {
+                // Nobody is listening for the result anymore.
+                return Err(CodexErr::Interrupted);
+            }
+
             match res {
                 Ok(resp) if resp.status().is_success() => {
-                    let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
-
-                    // spawn task to process SSE
-                    let stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
-                    tokio::spawn(process_sse(
-                        stream,
-                        tx_event,
-                        self.provider.stream_idle_timeout(),
-                    ));
-
-                    return Ok(ResponseStream { rx_event });
+                    metrics.add_retries(attempt - 1);
+                    return Ok(resp);
                 }
                 Ok(res) => {
                     let status = res.status();
 
                     // Pull out Retry‑After header if present.
-                    let retry_after_secs = res
-                        .headers()
-                        .get(reqwest::header::RETRY_AFTER)
-                        .and_then(|v| v.to_str().ok())
-                        .and_then(|s| s.parse::<u64>().ok());
-
-                    if status == StatusCode::UNAUTHORIZED
-                        && let Some(manager) = auth_manager.as_ref()
-                        && manager.auth().is_some()
-                    {
-                        let _ = manager.refresh_token().await;
+                    let retry_after = parse_retry_after(res.headers());
+                    let limit_resets_at = limits.as_ref().and_then(rate_limits::limit_resets_at);
+                    let limit_resets_in =
+                        limit_resets_at.map(|resets_at| rate_limits::until(resets_at, Utc::now()));
+
+                    // An expired ChatGPT access token is refreshed and the
+                    // request sent again, once. Waiting does not fix a 401.
+                    if status == StatusCode::UNAUTHORIZED {
+                        if !refreshed_token
+                            && let Some(manager) = auth_manager.as_ref()
+                            && auth.as_ref().is_some_and(|a| a.mode == AuthMode::ChatGPT)
+                        {
+                            refreshed_token = true;
+                            match manager.refresh_token().await {
+                                Ok(_) => continue,
+                                Err(e) => warn!("failed to refresh the access token: {e}"),
+                            }
+                        }
+                        return Err(CodexErr::AuthExpired);
                     }
 
                     // The OpenAI Responses endpoint returns structured JSON bodies even for 4xx/5xx
@@ -292,19 +797,23 @@ impl ModelClient {
                     // exact error message (e.g. "Unknown parameter: 'input[0].metadata'"). The body is
                     // small and this branch only runs on error paths so the extra allocation is
                     // negligible.
-                    if !(status == StatusCode::TOO_MANY_REQUESTS
-                        || status == StatusCode::UNAUTHORIZED
-                        || status.is_server_error())
-                    {
+                    if !self.provider.should_retry_status(status) {
                         // Surface the error body to callers. Use `unwrap_or_default` per Clippy.
                         let body = res.text().await.unwrap_or_default();
-                        return Err(CodexErr::UnexpectedStatus(status, body));
+                        let err = http_error(status, body);
+                        // The body of a 429 is raw JSON; the reset time says more.
+                        if status == StatusCode::TOO_MANY_REQUESTS
+                            && matches!(err, CodexErr::ProviderError { .. })
+                        {
+                            return Err(CodexErr::RateLimitReached(limit_resets_in));
+                        }
+                        return Err(err);
                     }
 
                     if status == StatusCode::TOO_MANY_REQUESTS {
-                        let body = res.json::<ErrorResponse>().await.ok();
-                        if let Some(ErrorResponse { error }) = body {
-                            if error.r#type.as_deref() == Some("usage_limit_reached") {
+                        let body = res.text().await.unwrap_or_default();
+                        if let Some(error) = ProviderErrorDetails::from_body(&body) {
+                            if error.kind.as_deref() == Some("usage_limit_reached") {
                                 // Prefer the plan_type provided in the error message if present
                                 // because it's more up to date than the one encoded in the auth
                                 // token.
@@ -316,9 +825,15 @@ impl ModelClient {
                                     plan_type,
                                     resets_in_seconds,
                                 }));
-                            } else if error.r#type.as_deref() == Some("usage_not_included") {
+                            } else if error.kind.as_deref() == Some("usage_not_included") {
                                 return Err(CodexErr::UsageNotIncluded);
                             }
+                            // Running out of quota is not fixed by waiting.
+                            if let err @ CodexErr::QuotaExceeded { .. } =
+                                error.into_http_error(status)
+                            {
+                                return Err(err);
+                            }
                         }
                     }
 
@@ -326,20 +841,41 @@ impl ModelClient {
                         if status == StatusCode::INTERNAL_SERVER_ERROR {
                             return Err(CodexErr::InternalServerError);
                         }
+                        if status == StatusCode::TOO_MANY_REQUESTS {
+                            return Err(CodexErr::RateLimitReached(limit_resets_in));
+                        }
 
                         return Err(CodexErr::RetryLimit(status));
                     }
 
-                    let delay = retry_after_secs
-                        .map(|s| Duration::from_millis(s * 1_000))
-                        .unwrap_or_else(|| backoff(attempt));
+                    let delay = self.provider.request_retry_delay(attempt, retry_after);
+                    if self.provider.should_retry_status(status) {
+                        let resets_at = Utc::now()
+                            + chrono::Duration::from_std(delay)
+                                .unwrap_or_else(|_| chrono::Duration::zero());
+                        let event = ResponseEvent::RateLimited {
+                            status,
+                            attempt,
+                            max_retries,
+                            resets_at,
+                            limit_resets_at: if status == StatusCode::TOO_MANY_REQUESTS {
+                                limit_resets_at
+                            } else {
+                                None
+                            },
+                        };
+                        if tx_event.send(Ok(event)).await.is_err() {
+                            // Nobody is listening for the result anymore.
+                            return Err(CodexErr::Interrupted);
+                        }
+                    }
                     tokio::time::sleep(delay).await;
                 }
                 Err(e) => {
                     if attempt > max_retries {
                         return Err(e.into());
                     }
-                    let delay = backoff(attempt);
+                    let delay = self.provider.request_retry_delay(attempt, None);
                     tokio::time::sleep(delay).await;
                 }
             }
@@ -350,11 +886,25 @@ impl ModelClient {
         self.provider.clone()
     }
 
+    /// The wire API requests are sent with: the configured one, else the one
+    /// probed for the provider, else the one probing starts with.
+    pub fn get_wire_api(&self) -> WireApi {
+        self.probed_wire_api
+            .get()
+            .copied()
+            .unwrap_or_else(|| self.provider.wire_api())
+    }
+
     /// Returns the currently configured model slug.
     pub fn get_model(&self) -> String {
         self.config.model.clone()
     }
 
+    /// Prices of the configured model, if known.
+    pub fn get_model_pricing(&self) -> Option<ModelPricing> {
+        find_model_pricing(&self.config.model, &self.config.model_pricing)
+    }
+
     /// Returns the currently configured model family.
     pub fn get_model_family(&self) -> ModelFamily {
         self.config.model_family.clone()
@@ -375,13 +925,119 @@ impl ModelClient {
     }
 }
 
+/// Whether `err` means the server has no Responses endpoint, rather than
+/// that it rejected this particular request.
+fn is_missing_endpoint(err: &CodexErr) -> bool {
+    match err {
+        CodexErr::ProviderError { status, .. } => matches!(
+            *status,
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        ),
+        CodexErr::RetryLimit(status) => *status == StatusCode::NOT_IMPLEMENTED,
+        _ => false,
+    }
+}
+
+/// Whether the provider rejected a request because of its `background`
+/// field, e.g. with "Unknown parameter: 'background'".
+fn is_background_rejected(err: &CodexErr) -> bool {
+    match err {
+        CodexErr::ProviderError {
+            status, message, ..
+        } => {
+            matches!(
+                *status,
+                StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+            ) && message.to_ascii_lowercase().contains("background")
+        }
+        _ => false,
+    }
+}
+
+/// Sends `events` ahead of the rest of `stream`. Reads the stream's channel
+/// directly, so that its events are only timed once.
+fn prepend_events(
+    events: Vec<Result<ResponseEvent>>,
+    mut stream: ResponseStream,
+) -> ResponseStream {
+    let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+    let metrics = stream.metrics.clone();
+    spawn_response_task(tx.clone(), async move {
+        for event in events {
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+        while let Some(event) = stream.rx_event.recv().await {
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+    });
+    ResponseStream::new(rx, metrics)
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct SseEvent {
     #[serde(rename = "type")]
     kind: String,
     response: Option<Value>,
     item: Option<Value>,
+    item_id: Option<String>,
     delta: Option<String>,
+    sequence_number: Option<u64>,
+}
+
+/// How far a Responses stream got, so that a dropped connection can be
+/// resumed without repeating what was already forwarded.
+#[derive(Debug, Default)]
+struct SseCursor {
+    /// Id of the response, announced by `response.created`.
+    response_id: Option<String>,
+    /// `sequence_number` of the last event received.
+    sequence_number: Option<u64>,
+    /// Ids of the output items already forwarded as
+    /// [`ResponseEvent::OutputItemDone`].
+    delivered_items: HashSet<String>,
+    /// Argument deltas reference the output item id; map it back to the
+    /// call_id announced in `response.output_item.added`.
+    function_call_ids: HashMap<String, String>,
+}
+
+/// Reads a background response, and cancels it on the server if the reader
+/// is dropped before the response finished, e.g. because the turn was
+/// interrupted. Closing the connection alone would leave it running.
+struct CancelOnDrop {
+    /// `None` for a response that is not in background mode, or once it
+    /// finished.
+    client: Option<ModelClient>,
+    cursor: SseCursor,
+}
+
+impl CancelOnDrop {
+    fn disarm(&mut self) {
+        self.client = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take()
+            && let Some(response_id) = self.cursor.response_id.take()
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            runtime.spawn(async move { client.cancel_background_response(&response_id).await });
+        }
+    }
+}
+
+/// Why [`forward_sse`] stopped reading a stream.
+enum SseEnd {
+    /// The response completed or failed, or nobody is listening anymore.
+    /// Everything worth reporting has been sent.
+    Done,
+    /// The connection dropped before the response finished.
+    Disconnected(CodexErr),
 }
 
 #[derive(Debug, Deserialize)]
@@ -393,7 +1049,7 @@ struct ResponseCompleted {
     usage: Option<ResponseCompletedUsage>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct ResponseCompletedUsage {
     input_tokens: u64,
     input_tokens_details: Option<ResponseCompletedInputTokensDetails>,
@@ -414,22 +1070,192 @@ impl From<ResponseCompletedUsage> for TokenUsage {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct ResponseCompletedInputTokensDetails {
     cached_tokens: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct ResponseCompletedOutputTokensDetails {
     reasoning_tokens: u64,
 }
 
+/// The `input` of a Responses request for the items of a prompt, and how many
+/// of the items were left out because the provider would reject them.
+fn request_input(
+    items: Vec<&ResponseItem>,
+    model_reasons: bool,
+    store: bool,
+) -> (Vec<RequestItem<'_>>, u64) {
+    let total = items.len();
+    let items = retain_replayable_reasoning(items, model_reasons, store);
+    let items: Vec<Cow<ResponseItem>> = if store {
+        items.into_iter().map(Cow::Borrowed).collect()
+    } else {
+        strip_server_state(items)
+    };
+    let input: Vec<RequestItem> = items
+        .into_iter()
+        .filter_map(|item| {
+            let kept = RequestItem::new(item);
+            if kept.is_none() {
+                debug!("leaving a history item of an unknown type out of the request");
+            }
+            kept
+        })
+        .collect();
+    let dropped = u64::try_from(total - input.len()).unwrap_or(u64::MAX);
+    (input, dropped)
+}
+
+/// Drops the reasoning items the model cannot be given back. Models that do
+/// not reason reject them outright. Otherwise an item is restored from the
+/// server's copy (only kept with `store`) or from its encrypted content, so
+/// items with neither, such as reasoning recorded from a Chat Completions
+/// provider, are dropped. A reasoning item must also directly precede the
+/// output it led to; an interrupted turn or a tool call dropped on resume can
+/// leave it without one.
+fn retain_replayable_reasoning(
+    items: Vec<&ResponseItem>,
+    model_reasons: bool,
+    store: bool,
+) -> Vec<&ResponseItem> {
+    let mut kept = Vec::with_capacity(items.len());
+    // Walk backwards so each reasoning item knows what follows it.
+    let mut precedes_output = false;
+    for item in items.into_iter().rev() {
+        match item {
+            ResponseItem::Reasoning {
+                id,
+                encrypted_content,
+                ..
+            } => {
+                let restorable = (store && !id.is_empty()) || encrypted_content.is_some();
+                if !(model_reasons && restorable && precedes_output) {
+                    debug!(
+                        "leaving reasoning item {id:?} out of the request: it cannot be replayed"
+                    );
+                    continue;
+                }
+            }
+            ResponseItem::Message { role, .. } => precedes_output = role == "assistant",
+            ResponseItem::FunctionCall { .. }
+            | ResponseItem::CustomToolCall { .. }
+            | ResponseItem::LocalShellCall { .. }
+            | ResponseItem::WebSearchCall { .. } => precedes_output = true,
+            ResponseItem::FunctionCallOutput { .. }
+            | ResponseItem::CustomToolCallOutput { .. }
+            | ResponseItem::Other => precedes_output = false,
+        }
+        kept.push(item);
+    }
+    kept.reverse();
+    kept
+}
+
+/// Prepares history for a request sent with `store: false`. The server keeps
+/// nothing from such responses, so item ids (including reasoning ids) point at
+/// objects it cannot find and must be dropped; reasoning is replayed from its
+/// encrypted content instead (see [`retain_replayable_reasoning`]).
+///
+/// Only the items that carry an id are copied; the rest are sent as they are
+/// in the history.
+fn strip_server_state(items: Vec<&ResponseItem>) -> Vec<Cow<'_, ResponseItem>> {
+    items
+        .into_iter()
+        .filter_map(|item| match item {
+            ResponseItem::Message {
+                id: Some(_),
+                role,
+                content,
+            } => Some(Cow::Owned(ResponseItem::Message {
+                id: None,
+                role: role.clone(),
+                content: content.clone(),
+            })),
+            ResponseItem::Reasoning {
+                id,
+                summary,
+                content,
+                encrypted_content,
+            } if !id.is_empty() => Some(Cow::Owned(ResponseItem::Reasoning {
+                id: String::new(),
+                summary: summary.clone(),
+                content: content.clone(),
+                encrypted_content: encrypted_content.clone(),
+            })),
+            ResponseItem::FunctionCall {
+                id: Some(_),
+                name,
+                arguments,
+                call_id,
+            } => Some(Cow::Owned(ResponseItem::FunctionCall {
+                id: None,
+                name: name.clone(),
+                arguments: arguments.clone(),
+                call_id: call_id.clone(),
+            })),
+            ResponseItem::CustomToolCall {
+                id: Some(_),
+                status,
+                call_id,
+                name,
+                input,
+            } => Some(Cow::Owned(ResponseItem::CustomToolCall {
+                id: None,
+                status: status.clone(),
+                call_id: call_id.clone(),
+                name: name.clone(),
+                input: input.clone(),
+            })),
+            // Without a `call_id` the `id` is the call id (Chat Completions)
+            // rather than a server item id, so it has to stay.
+            ResponseItem::LocalShellCall {
+                id: Some(_),
+                call_id: Some(call_id),
+                status,
+                action,
+            } => Some(Cow::Owned(ResponseItem::LocalShellCall {
+                id: None,
+                call_id: Some(call_id.clone()),
+                status: status.clone(),
+                action: action.clone(),
+            })),
+            // Searches live on the server; their results are already in the
+            // assistant messages that follow.
+            ResponseItem::WebSearchCall { .. } => None,
+            other => Some(Cow::Borrowed(other)),
+        })
+        .collect()
+}
+
 async fn process_sse<S>(
     stream: S,
     tx_event: mpsc::Sender<Result<ResponseEvent>>,
     idle_timeout: Duration,
 ) where
     S: Stream<Item = Result<Bytes>> + Unpin,
+{
+    let mut cursor = SseCursor::default();
+    if let SseEnd::Disconnected(err) =
+        forward_sse(stream, &tx_event, idle_timeout, &mut cursor).await
+    {
+        let _ = tx_event.send(Err(err)).await;
+    }
+}
+
+/// Forwards the events of one Responses SSE connection to `tx_event`,
+/// advancing `cursor` as it goes. Events at or before the cursor and output
+/// items that were already forwarded are skipped, so a resumed connection
+/// picks up exactly where the previous one stopped.
+async fn forward_sse<S>(
+    stream: S,
+    tx_event: &mpsc::Sender<Result<ResponseEvent>>,
+    idle_timeout: Duration,
+    cursor: &mut SseCursor,
+) -> SseEnd
+where
+    S: Stream<Item = Result<Bytes>> + Unpin,
 {
     let mut stream = stream.eventsource();
 
@@ -443,41 +1269,38 @@ async fn process_sse<S>(
             Ok(Some(Ok(sse))) => sse,
             Ok(Some(Err(e))) => {
                 debug!("SSE Error: {e:#}");
-                let event = CodexErr::Stream(e.to_string(), None);
-                let _ = tx_event.send(Err(event)).await;
-                return;
+                return SseEnd::Disconnected(CodexErr::stream_disconnected(e.to_string()));
             }
             Ok(None) => {
-                match response_completed {
-                    Some(ResponseCompleted {
-                        id: response_id,
-                        usage,
-                    }) => {
+                match (response_completed, response_error) {
+                    (
+                        Some(ResponseCompleted {
+                            id: response_id,
+                            usage,
+                        }),
+                        _,
+                    ) => {
                         let event = ResponseEvent::Completed {
                             response_id,
                             token_usage: usage.map(Into::into),
                         };
                         let _ = tx_event.send(Ok(event)).await;
                     }
-                    None => {
-                        let _ = tx_event
-                            .send(Err(response_error.unwrap_or(CodexErr::Stream(
-                                "stream closed before response.completed".into(),
-                                None,
-                            ))))
-                            .await;
+                    (None, Some(err)) => {
+                        let _ = tx_event.send(Err(err)).await;
+                    }
+                    (None, None) => {
+                        return SseEnd::Disconnected(CodexErr::stream_disconnected(
+                            "stream closed before response.completed",
+                        ));
                     }
                 }
-                return;
+                return SseEnd::Done;
             }
             Err(_) => {
-                let _ = tx_event
-                    .send(Err(CodexErr::Stream(
-                        "idle timeout waiting for SSE".into(),
-                        None,
-                    )))
-                    .await;
-                return;
+                return SseEnd::Disconnected(CodexErr::stream_disconnected(
+                    "idle timeout waiting for SSE",
+                ));
             }
         };
 
@@ -491,6 +1314,15 @@ async fn process_sse<S>(
                 continue;
             }
         };
+        if let Some(sequence_number) = event.sequence_number {
+            if cursor
+                .sequence_number
+                .is_some_and(|last| sequence_number <= last)
+            {
+                continue;
+            }
+            cursor.sequence_number = Some(sequence_number);
+        }
 
         match event.kind.as_str() {
             // Individual output item finalised. Forward immediately so the
@@ -513,6 +1345,11 @@ async fn process_sse<S>(
             // drop the duplicated list inside `response.completed`.
             "response.output_item.done" => {
                 let Some(item_val) = event.item else { continue };
+                if let Some(id) = item_val.get("id").and_then(|v| v.as_str())
+                    && !cursor.delivered_items.insert(id.to_string())
+                {
+                    continue;
+                }
                 let Ok(item) = serde_json::from_value::<ResponseItem>(item_val) else {
                     debug!("failed to parse ResponseItem from output_item.done");
                     continue;
@@ -520,14 +1357,14 @@ async fn process_sse<S>(
 
                 let event = ResponseEvent::OutputItemDone(item);
                 if tx_event.send(Ok(event)).await.is_err() {
-                    return;
+                    return SseEnd::Done;
                 }
             }
             "response.output_text.delta" => {
                 if let Some(delta) = event.delta {
                     let event = ResponseEvent::OutputTextDelta(delta);
                     if tx_event.send(Ok(event)).await.is_err() {
-                        return;
+                        return SseEnd::Done;
                     }
                 }
             }
@@ -535,7 +1372,7 @@ async fn process_sse<S>(
                 if let Some(delta) = event.delta {
                     let event = ResponseEvent::ReasoningSummaryDelta(delta);
                     if tx_event.send(Ok(event)).await.is_err() {
-                        return;
+                        return SseEnd::Done;
                     }
                 }
             }
@@ -543,29 +1380,31 @@ async fn process_sse<S>(
                 if let Some(delta) = event.delta {
                     let event = ResponseEvent::ReasoningContentDelta(delta);
                     if tx_event.send(Ok(event)).await.is_err() {
-                        return;
+                        return SseEnd::Done;
                     }
                 }
             }
             "response.created" => {
-                if event.response.is_some() {
+                if let Some(resp_val) = event.response {
+                    cursor.response_id = resp_val
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string);
                     let _ = tx_event.send(Ok(ResponseEvent::Created {})).await;
                 }
             }
             "response.failed" => {
                 if let Some(resp_val) = event.response {
-                    response_error = Some(CodexErr::Stream(
-                        "response.failed event received".to_string(),
-                        None,
+                    response_error = Some(CodexErr::stream_disconnected(
+                        "response.failed event received",
                     ));
 
                     let error = resp_val.get("error");
 
                     if let Some(error) = error {
-                        match serde_json::from_value::<Error>(error.clone()) {
+                        match serde_json::from_value::<ProviderErrorDetails>(error.clone()) {
                             Ok(error) => {
-                                let message = error.message.unwrap_or_default();
-                                response_error = Some(CodexErr::Stream(message, None));
+                                response_error = Some(error.into_stream_error());
                             }
                             Err(e) => {
                                 debug!("failed to parse ErrorResponse: {e}");
@@ -579,6 +1418,12 @@ async fn process_sse<S>(
                 if let Some(resp_val) = event.response {
                     match serde_json::from_value::<ResponseCompleted>(resp_val) {
                         Ok(r) => {
+                            if let Some(usage) = r.usage.clone() {
+                                let event = ResponseEvent::UsageUpdate(usage.into());
+                                if tx_event.send(Ok(event)).await.is_err() {
+                                    return SseEnd::Done;
+                                }
+                            }
                             response_completed = Some(r);
                         }
                         Err(e) => {
@@ -588,8 +1433,21 @@ async fn process_sse<S>(
                     };
                 };
             }
+            "response.function_call_arguments.delta" => {
+                if let Some(delta) = event.delta {
+                    let item_id = event.item_id.unwrap_or_default();
+                    let call_id = cursor
+                        .function_call_ids
+                        .get(&item_id)
+                        .cloned()
+                        .unwrap_or(item_id);
+                    let event = ResponseEvent::FunctionCallArgumentsDelta { call_id, delta };
+                    if tx_event.send(Ok(event)).await.is_err() {
+                        return SseEnd::Done;
+                    }
+                }
+            }
             "response.content_part.done"
-            | "response.function_call_arguments.delta"
             | "response.custom_tool_call_input.delta"
             | "response.custom_tool_call_input.done" // also emitted as response.output_item.done
             | "response.in_progress"
@@ -607,16 +1465,27 @@ async fn process_sse<S>(
                             .to_string();
                         let ev = ResponseEvent::WebSearchCallBegin { call_id };
                         if tx_event.send(Ok(ev)).await.is_err() {
-                            return;
+                            return SseEnd::Done;
                         }
                     }
+
+                    if item.get("type").and_then(|v| v.as_str()) == Some("function_call")
+                        && let (Some(id), Some(call_id)) = (
+                            item.get("id").and_then(|v| v.as_str()),
+                            item.get("call_id").and_then(|v| v.as_str()),
+                        )
+                    {
+                        cursor
+                            .function_call_ids
+                            .insert(id.to_string(), call_id.to_string());
+                    }
                 }
             }
             "response.reasoning_summary_part.added" => {
                 // Boundary between reasoning summary sections (e.g., titles).
                 let event = ResponseEvent::ReasoningSummaryPartAdded;
                 if tx_event.send(Ok(event)).await.is_err() {
-                    return;
+                    return SseEnd::Done;
                 }
             }
             "response.reasoning_summary_text.done" => {}
@@ -648,12 +1517,18 @@ async fn stream_from_fixture(
         tx_event,
         provider.stream_idle_timeout(),
     ));
-    Ok(ResponseStream { rx_event })
+    Ok(ResponseStream::new(
+        rx_event,
+        Arc::new(StreamMetrics::new(Instant::now())),
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model_provider_info::ProviderKind;
+    use codex_protocol::models::ContentItem;
+    use codex_protocol::models::FunctionCallOutputPayload;
     use serde_json::json;
     use tokio::sync::mpsc;
     use tokio_test::io::Builder as IoBuilder;
@@ -757,14 +1632,26 @@ mod tests {
             base_url: Some("https://test.com".to_string()),
             env_key: Some("TEST_API_KEY".to_string()),
             env_key_instructions: None,
-            wire_api: WireApi::Responses,
+            wire_api: Some(WireApi::Responses),
             query_params: None,
             http_headers: None,
             env_http_headers: None,
             request_max_retries: Some(0),
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: Some(0),
             stream_idle_timeout_ms: Some(1000),
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let events = collect_events(
@@ -817,14 +1704,26 @@ mod tests {
             base_url: Some("https://test.com".to_string()),
             env_key: Some("TEST_API_KEY".to_string()),
             env_key_instructions: None,
-            wire_api: WireApi::Responses,
+            wire_api: Some(WireApi::Responses),
             query_params: None,
             http_headers: None,
             env_http_headers: None,
             request_max_retries: Some(0),
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: Some(0),
             stream_idle_timeout_ms: Some(1000),
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let events = collect_events(&[sse1.as_bytes()], provider).await;
@@ -834,8 +1733,8 @@ mod tests {
         matches!(events[0], Ok(ResponseEvent::OutputItemDone(_)));
 
         match &events[1] {
-            Err(CodexErr::Stream(msg, _)) => {
-                assert_eq!(msg, "stream closed before response.completed")
+            Err(CodexErr::StreamDisconnected { message, .. }) => {
+                assert_eq!(message, "stream closed before response.completed")
             }
             other => panic!("unexpected second event: {other:?}"),
         }
@@ -851,14 +1750,26 @@ mod tests {
             base_url: Some("https://test.com".to_string()),
             env_key: Some("TEST_API_KEY".to_string()),
             env_key_instructions: None,
-            wire_api: WireApi::Responses,
+            wire_api: Some(WireApi::Responses),
             query_params: None,
             http_headers: None,
             env_http_headers: None,
             request_max_retries: Some(0),
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: Some(0),
             stream_idle_timeout_ms: Some(1000),
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let events = collect_events(&[sse1.as_bytes()], provider).await;
@@ -866,12 +1777,17 @@ mod tests {
         assert_eq!(events.len(), 1);
 
         match &events[0] {
-            Err(CodexErr::Stream(msg, delay)) => {
+            Err(CodexErr::StreamDisconnected {
+                message,
+                retryable,
+                retry_after,
+            }) => {
                 assert_eq!(
-                    msg,
+                    message,
                     "Rate limit reached for gpt-5 in organization org-AAA on tokens per min (TPM): Limit 30000, Used 22999, Requested 12528. Please try again in 11.054s. Visit https://platform.openai.com/account/rate-limits to learn more."
                 );
-                assert_eq!(*delay, None);
+                assert!(*retryable);
+                assert_eq!(*retry_after, None);
             }
             other => panic!("unexpected second event: {other:?}"),
         }
@@ -898,8 +1814,8 @@ mod tests {
         fn is_output(ev: &ResponseEvent) -> bool {
             matches!(ev, ResponseEvent::OutputItemDone(_))
         }
-        fn is_completed(ev: &ResponseEvent) -> bool {
-            matches!(ev, ResponseEvent::Completed { .. })
+        fn is_usage_update(ev: &ResponseEvent) -> bool {
+            matches!(ev, ResponseEvent::UsageUpdate(_))
         }
 
         let completed = json!({
@@ -922,7 +1838,7 @@ mod tests {
                 name: "created",
                 event: json!({"type": "response.created", "response": {}}),
                 expect_first: is_created,
-                expected_len: 2,
+                expected_len: 3,
             },
             TestCase {
                 name: "output_item.done",
@@ -937,13 +1853,13 @@ mod tests {
                     }
                 }),
                 expect_first: is_output,
-                expected_len: 2,
+                expected_len: 3,
             },
             TestCase {
                 name: "unknown",
                 event: json!({"type": "response.new_tool_event"}),
-                expect_first: is_completed,
-                expected_len: 1,
+                expect_first: is_usage_update,
+                expected_len: 2,
             },
         ];
 
@@ -956,14 +1872,26 @@ mod tests {
                 base_url: Some("https://test.com".to_string()),
                 env_key: Some("TEST_API_KEY".to_string()),
                 env_key_instructions: None,
-                wire_api: WireApi::Responses,
+                wire_api: Some(WireApi::Responses),
                 query_params: None,
                 http_headers: None,
                 env_http_headers: None,
                 request_max_retries: Some(0),
+                request_retry_base_delay_ms: None,
+                request_retry_max_delay_ms: None,
+                request_retry_on_status: None,
                 stream_max_retries: Some(0),
                 stream_idle_timeout_ms: Some(1000),
+                temperature: None,
+                top_p: None,
+                seed: None,
+                stream_include_usage: None,
+                api_version: None,
+                deployment: None,
+                auth_header: None,
+                kind: ProviderKind::OpenAi,
                 requires_openai_auth: false,
+                stored_api_key: None,
             };
 
             let out = run_sse(evs, provider).await;
@@ -975,4 +1903,374 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn function_call_argument_deltas_use_call_id() {
+        let events = vec![
+            json!({
+                "type": "response.output_item.added",
+                "output_index": 0,
+                "item": {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_1",
+                    "name": "shell",
+                    "arguments": ""
+                }
+            }),
+            json!({
+                "type": "response.function_call_arguments.delta",
+                "item_id": "fc_1",
+                "output_index": 0,
+                "delta": "{\"command\":[\"git\","
+            }),
+            json!({
+                "type": "response.function_call_arguments.delta",
+                "item_id": "fc_1",
+                "output_index": 0,
+                "delta": "\"diff\"]}"
+            }),
+            json!({
+                "type": "response.output_item.done",
+                "item": {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_1",
+                    "name": "shell",
+                    "arguments": "{\"command\":[\"git\",\"diff\"]}"
+                }
+            }),
+            json!({
+                "type": "response.completed",
+                "response": {"id": "resp1"}
+            }),
+        ];
+
+        let provider = ModelProviderInfo {
+            name: "test".to_string(),
+            base_url: Some("https://test.com".to_string()),
+            env_key: Some("TEST_API_KEY".to_string()),
+            env_key_instructions: None,
+            wire_api: Some(WireApi::Responses),
+            query_params: None,
+            http_headers: None,
+            env_http_headers: None,
+            request_max_retries: Some(0),
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
+            stream_max_retries: Some(0),
+            stream_idle_timeout_ms: Some(1000),
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
+            requires_openai_auth: false,
+            stored_api_key: None,
+        };
+
+        let out = run_sse(events, provider).await;
+        assert_eq!(out.len(), 4);
+
+        let deltas: Vec<(&str, &str)> = out
+            .iter()
+            .filter_map(|ev| match ev {
+                ResponseEvent::FunctionCallArgumentsDelta { call_id, delta } => {
+                    Some((call_id.as_str(), delta.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            deltas,
+            vec![
+                ("call_1", "{\"command\":[\"git\","),
+                ("call_1", "\"diff\"]}")
+            ]
+        );
+        assert!(matches!(
+            &out[2],
+            ResponseEvent::OutputItemDone(ResponseItem::FunctionCall { arguments, .. })
+                if arguments == "{\"command\":[\"git\",\"diff\"]}"
+        ));
+        assert!(matches!(out[3], ResponseEvent::Completed { .. }));
+    }
+
+    #[tokio::test]
+    async fn resumed_stream_skips_events_already_forwarded() {
+        fn sse_body(events: &[serde_json::Value]) -> String {
+            events
+                .iter()
+                .map(|e| format!("event: {}\ndata: {e}\n\n", e["type"].as_str().unwrap()))
+                .collect()
+        }
+        fn message(id: &str, sequence_number: u64) -> serde_json::Value {
+            json!({
+                "type": "response.output_item.done",
+                "sequence_number": sequence_number,
+                "item": {
+                    "type": "message",
+                    "id": id,
+                    "role": "assistant",
+                    "content": [{"type": "output_text", "text": id}]
+                }
+            })
+        }
+
+        let first = sse_body(&[
+            json!({"type": "response.created", "sequence_number": 0, "response": {"id": "resp_1"}}),
+            message("msg_1", 1),
+        ]);
+        // The resumed stream starts over at the item that was cut off.
+        let second = sse_body(&[
+            message("msg_1", 1),
+            message("msg_2", 2),
+            json!({"type": "response.completed", "sequence_number": 3, "response": {"id": "resp_1"}}),
+        ]);
+
+        let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let idle_timeout = Duration::from_secs(1);
+        let mut cursor = SseCursor::default();
+
+        let stream = ReaderStream::new(std::io::Cursor::new(first)).map_err(CodexErr::Io);
+        let end = forward_sse(stream, &tx, idle_timeout, &mut cursor).await;
+        assert!(matches!(end, SseEnd::Disconnected(_)));
+        assert_eq!(cursor.response_id.as_deref(), Some("resp_1"));
+        assert_eq!(cursor.sequence_number, Some(1));
+
+        let stream = ReaderStream::new(std::io::Cursor::new(second)).map_err(CodexErr::Io);
+        let end = forward_sse(stream, &tx, idle_timeout, &mut cursor).await;
+        assert!(matches!(end, SseEnd::Done));
+        drop(tx);
+
+        let mut messages = Vec::new();
+        let mut completed = false;
+        while let Some(event) = rx.recv().await {
+            match event.unwrap() {
+                ResponseEvent::OutputItemDone(ResponseItem::Message { id, .. }) => {
+                    messages.push(id.unwrap());
+                }
+                ResponseEvent::Completed { response_id, .. } => {
+                    assert_eq!(response_id, "resp_1");
+                    completed = true;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(messages, vec!["msg_1", "msg_2"]);
+        assert!(completed);
+    }
+
+    #[test]
+    fn only_restorable_reasoning_that_precedes_output_is_replayed() {
+        let reasoning = |id: &str, encrypted: Option<&str>| ResponseItem::Reasoning {
+            id: id.to_string(),
+            summary: Vec::new(),
+            content: None,
+            encrypted_content: encrypted.map(str::to_string),
+        };
+        let message = |role: &str| ResponseItem::Message {
+            id: None,
+            role: role.to_string(),
+            content: Vec::new(),
+        };
+        let call = ResponseItem::FunctionCall {
+            id: None,
+            name: "shell".to_string(),
+            arguments: "{}".to_string(),
+            call_id: "call_1".to_string(),
+        };
+        let items = vec![
+            message("user"),
+            reasoning("rs_1", Some("opaque")),
+            call.clone(),
+            reasoning("rs_2", None),
+            message("assistant"),
+            reasoning("", None),
+            message("assistant"),
+            // Interrupted before producing any output.
+            reasoning("rs_3", Some("opaque")),
+            message("user"),
+        ];
+        let retained = |model_reasons, store| {
+            retain_replayable_reasoning(items.iter().collect(), model_reasons, store)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            retained(true, false),
+            vec![
+                message("user"),
+                reasoning("rs_1", Some("opaque")),
+                call.clone(),
+                message("assistant"),
+                message("assistant"),
+                message("user"),
+            ]
+        );
+        assert_eq!(
+            retained(true, true),
+            vec![
+                message("user"),
+                reasoning("rs_1", Some("opaque")),
+                call.clone(),
+                reasoning("rs_2", None),
+                message("assistant"),
+                message("assistant"),
+                message("user"),
+            ]
+        );
+        assert_eq!(
+            retained(false, true),
+            vec![
+                message("user"),
+                call,
+                message("assistant"),
+                message("assistant"),
+                message("user"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_server_state_copies_only_items_with_ids() {
+        let output = ResponseItem::FunctionCallOutput {
+            call_id: "call_1".to_string(),
+            output: FunctionCallOutputPayload {
+                content: "ok".to_string(),
+                success: Some(true),
+            },
+        };
+        let message = ResponseItem::Message {
+            id: Some("msg_1".to_string()),
+            role: "assistant".to_string(),
+            content: Vec::new(),
+        };
+        let stripped = strip_server_state(vec![&output, &message]);
+        assert!(matches!(&stripped[0], Cow::Borrowed(item) if std::ptr::eq(*item, &output)));
+        assert!(matches!(
+            &stripped[1],
+            Cow::Owned(ResponseItem::Message { id: None, .. })
+        ));
+    }
+
+    #[test]
+    fn request_input_leaves_out_unknown_items_and_unusable_reasoning() {
+        let message = |role: &str, text: &str| ResponseItem::Message {
+            id: None,
+            role: role.to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+        };
+        let history = vec![
+            message("user", "hi"),
+            ResponseItem::Other,
+            ResponseItem::Reasoning {
+                id: "rs_1".to_string(),
+                summary: Vec::new(),
+                content: None,
+                encrypted_content: Some("opaque".to_string()),
+            },
+            message("assistant", "hello"),
+        ];
+
+        // A model that does not reason rejects reasoning items as well.
+        let (input, dropped) = request_input(history.iter().collect(), false, false);
+        assert_eq!(dropped, 2);
+        assert_eq!(
+            serde_json::to_value(&input).expect("json"),
+            json!([
+                {"type": "message", "id": null, "role": "user", "content": [{"type": "input_text", "text": "hi"}]},
+                {"type": "message", "id": null, "role": "assistant", "content": [{"type": "input_text", "text": "hello"}]},
+            ])
+        );
+
+        let (input, dropped) = request_input(history.iter().collect(), true, false);
+        assert_eq!(dropped, 1);
+        assert_eq!(input.len(), 3);
+
+        // The history itself keeps every item.
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[1], ResponseItem::Other);
+    }
+
+    /// Times building the input of a request from a 500-item history with
+    /// large tool outputs and images, sharing the history items as the session
+    /// does against copying them as it used to. It only reports the timings,
+    /// which vary too much between machines to assert on. Run it with
+    /// `cargo test -p codex-core --release -- --ignored prompt_input_benchmark --nocapture`.
+    #[test]
+    #[ignore]
+    #[expect(clippy::print_stdout)]
+    fn prompt_input_benchmark() {
+        const ITERATIONS: u32 = 200;
+        let large_text = "lorem ipsum dolor sit amet ".repeat(2_000);
+        let image_url = format!("data:image/png;base64,{}", "A".repeat(200_000));
+        let mut history = crate::conversation_history::ConversationHistory::new();
+        let items = (0..500)
+            .map(|i| match i % 5 {
+                0 => ResponseItem::Message {
+                    id: None,
+                    role: "user".to_string(),
+                    content: vec![
+                        ContentItem::InputText {
+                            text: large_text.clone(),
+                        },
+                        ContentItem::InputImage {
+                            image_url: image_url.clone(),
+                        },
+                    ],
+                },
+                1 | 3 => ResponseItem::FunctionCall {
+                    id: Some(format!("fc_{i}")),
+                    name: "shell".to_string(),
+                    arguments: r#"{"command":["cat","src/lib.rs"]}"#.to_string(),
+                    call_id: format!("call_{i}"),
+                },
+                2 | 4 => ResponseItem::FunctionCallOutput {
+                    call_id: format!("call_{}", i - 1),
+                    output: FunctionCallOutputPayload {
+                        content: large_text.clone(),
+                        success: Some(true),
+                    },
+                },
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        history.record_items(items.iter());
+
+        let time = |build: &dyn Fn() -> usize| {
+            let start = Instant::now();
+            let mut built = 0;
+            for _ in 0..ITERATIONS {
+                built += build();
+            }
+            assert_eq!(built, 500 * ITERATIONS as usize);
+            start.elapsed() / ITERATIONS
+        };
+        let copied = time(&|| {
+            let input: Vec<ResponseItem> = history.contents();
+            input.len()
+        });
+        let shared = time(&|| {
+            let prompt = Prompt {
+                input: history.shared_contents(),
+                ..Default::default()
+            };
+            strip_server_state(retain_replayable_reasoning(
+                prompt.get_formatted_input(),
+                true,
+                false,
+            ))
+            .len()
+        });
+        println!("input of a 500-item history: copied {copied:?}, shared {shared:?} per turn");
+    }
 }
@@ -1,34 +1,68 @@
 use crate::config_types::Verbosity as VerbosityConfig;
 use crate::error::Result;
 use crate::model_family::ModelFamily;
+use crate::model_provider_info::WireApi;
 use crate::openai_tools::OpenAiTool;
+use crate::prompt_vars::PromptVars;
+use crate::protocol::ContextBreakdown;
+use crate::protocol::RateLimitSnapshot;
 use crate::protocol::TokenUsage;
+use crate::protocol::TurnMetrics;
+use crate::rollout::is_session_prefix;
+use crate::token_estimate::Encoding;
+use crate::token_estimate::estimate_response_item_tokens;
+use crate::token_estimate::estimate_text_tokens;
+use chrono::DateTime;
+use chrono::Utc;
 use codex_apply_patch::APPLY_PATCH_TOOL_INSTRUCTIONS;
 use codex_protocol::config_types::ReasoningEffort as ReasoningEffortConfig;
 use codex_protocol::config_types::ReasoningSummary as ReasoningSummaryConfig;
 use codex_protocol::models::ContentItem;
 use codex_protocol::models::ResponseItem;
 use futures::Stream;
+use reqwest::StatusCode;
 use serde::Serialize;
 use std::borrow::Cow;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::sync::mpsc;
 
 /// The `instructions` field in the payload sent to a model should always start
 /// with this content.
 const BASE_INSTRUCTIONS: &str = include_str!("../prompt.md");
 
+/// Appended to the base instructions when the `update_plan` tool is offered.
+const PLAN_TOOL_INSTRUCTIONS: &str = include_str!("../plan_tool_instructions.md");
+
 /// wraps user instructions message in a tag for the model to parse more easily.
-const USER_INSTRUCTIONS_START: &str = "<user_instructions>\n\n";
+pub(crate) const USER_INSTRUCTIONS_START: &str = "<user_instructions>\n\n";
 const USER_INSTRUCTIONS_END: &str = "\n\n</user_instructions>";
 
+/// Whether `item` is the user instructions or environment context message
+/// Codex adds to every conversation.
+fn is_environment_item(item: &ResponseItem) -> bool {
+    match item {
+        ResponseItem::Message { role, content, .. } if role == "user" => {
+            content.iter().any(|c| match c {
+                ContentItem::InputText { text } => is_session_prefix(text),
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
 /// API request payload for a single model turn
 #[derive(Default, Debug, Clone)]
 pub struct Prompt {
-    /// Conversation context input items.
-    pub input: Vec<ResponseItem>,
+    /// Conversation context input items, shared with the session history.
+    pub input: Vec<Arc<ResponseItem>>,
 
     /// Whether to store response on server side (disable_response_storage = !store).
     pub store: bool,
@@ -39,15 +73,32 @@ pub struct Prompt {
 
     /// Optional override for the built-in BASE_INSTRUCTIONS.
     pub base_instructions_override: Option<String>,
+
+    /// Optional JSON schema the model's final message must match.
+    pub output_schema: Option<serde_json::Value>,
+
+    /// Values for the `{{name}}` placeholders in the base instructions.
+    pub(crate) prompt_vars: PromptVars,
 }
 
 impl Prompt {
-    pub(crate) fn get_full_instructions(&self, model: &ModelFamily) -> Cow<'_, str> {
-        let base = self
-            .base_instructions_override
-            .as_deref()
-            .unwrap_or(BASE_INSTRUCTIONS);
-        let mut sections: Vec<&str> = vec![base];
+    /// The base instructions, with their placeholders filled in, followed by
+    /// the tool instructions that go with them.
+    pub(crate) fn get_full_instructions(&self, model: &ModelFamily) -> Result<Cow<'_, str>> {
+        let base = self.prompt_vars.render(
+            self.base_instructions_override
+                .as_deref()
+                .unwrap_or(BASE_INSTRUCTIONS),
+        )?;
+        let mut sections: Vec<&str> = vec![&base];
+
+        let is_plan_tool_present = self.tools.iter().any(|tool| match tool {
+            OpenAiTool::Function(f) => f.name == "update_plan",
+            _ => false,
+        });
+        if self.base_instructions_override.is_none() && is_plan_tool_present {
+            sections.push(PLAN_TOOL_INSTRUCTIONS);
+        }
 
         // When there are no custom instructions, add apply_patch_tool_instructions if either:
         // - the model needs special instructions (4.1), or
@@ -62,26 +113,81 @@ impl Prompt {
         {
             sections.push(APPLY_PATCH_TOOL_INSTRUCTIONS);
         }
-        Cow::Owned(sections.join("\n"))
+        Ok(Cow::Owned(sections.join("\n")))
+    }
+
+    /// Approximate number of input tokens this prompt will consume when sent
+    /// to `model`: the full instructions, the tool definitions and every item
+    /// in `input` (which already carries the user instructions and the
+    /// environment context).
+    ///
+    /// The estimate is split into the overhead every request carries and the
+    /// conversation itself.
+    pub(crate) fn estimated_context_breakdown(&self, model: &ModelFamily) -> ContextBreakdown {
+        let encoding = Encoding::for_model_family(model);
+        // Instructions that fail to render fail the turn before they count.
+        let instructions_tokens = self
+            .get_full_instructions(model)
+            .map(|instructions| estimate_text_tokens(&instructions, encoding))
+            .unwrap_or_default();
+        let tool_schema_tokens = serde_json::to_string(&self.tools)
+            .map(|json| estimate_text_tokens(&json, encoding))
+            .unwrap_or_default();
+        let mut environment_tokens = 0;
+        let mut conversation_tokens = 0;
+        for item in &self.input {
+            let tokens = estimate_response_item_tokens(item, encoding);
+            if is_environment_item(item) {
+                environment_tokens += tokens;
+            } else {
+                conversation_tokens += tokens;
+            }
+        }
+        ContextBreakdown {
+            instructions_tokens: instructions_tokens as u64,
+            tool_schema_tokens: tool_schema_tokens as u64,
+            environment_tokens: environment_tokens as u64,
+            conversation_tokens: conversation_tokens as u64,
+        }
     }
 
-    pub(crate) fn get_formatted_input(&self) -> Vec<ResponseItem> {
-        self.input.clone()
+    /// The input items, borrowed so that serializing a request does not
+    /// copy the history.
+    pub(crate) fn get_formatted_input(&self) -> Vec<&ResponseItem> {
+        self.input.iter().map(AsRef::as_ref).collect()
     }
 
-    /// Creates a formatted user instructions message from a string
-    pub(crate) fn format_user_instructions_message(ui: &str) -> ResponseItem {
-        ResponseItem::Message {
+    /// Creates a formatted user instructions message from a string, with
+    /// its placeholders filled in from `vars`.
+    pub(crate) fn format_user_instructions_message(
+        ui: &str,
+        vars: &PromptVars,
+    ) -> Result<ResponseItem> {
+        let ui = vars.render(ui)?;
+        Ok(ResponseItem::Message {
             id: None,
             role: "user".to_string(),
             content: vec![ContentItem::InputText {
                 text: format!("{USER_INSTRUCTIONS_START}{ui}{USER_INSTRUCTIONS_END}"),
             }],
-        }
+        })
+    }
+
+    /// The most recent user instructions message in `items`, if any.
+    pub(crate) fn last_user_instructions_message(items: &[ResponseItem]) -> Option<&ResponseItem> {
+        items.iter().rev().find(|item| match item {
+            ResponseItem::Message { role, content, .. } if role == "user" => {
+                content.iter().any(|c| match c {
+                    ContentItem::InputText { text } => text.starts_with(USER_INSTRUCTIONS_START),
+                    _ => false,
+                })
+            }
+            _ => false,
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ResponseEvent {
     Created,
     OutputItemDone(ResponseItem),
@@ -96,6 +202,38 @@ pub enum ResponseEvent {
     WebSearchCallBegin {
         call_id: String,
     },
+    /// Partial JSON arguments for a function call that is still being
+    /// streamed. The complete call is delivered later via `OutputItemDone`.
+    FunctionCallArgumentsDelta {
+        call_id: String,
+        delta: String,
+    },
+    /// Cumulative token usage of the response so far. May be sent more than
+    /// once per response; each update supersedes the previous one.
+    UsageUpdate(TokenUsage),
+    /// The request failed with a retryable status (e.g. 429 or 503) and will
+    /// be retried automatically at `resets_at`. Emitted before any output.
+    RateLimited {
+        status: StatusCode,
+        attempt: u64,
+        max_retries: u64,
+        resets_at: DateTime<Utc>,
+        /// Unix time at which the exhausted rate limit resets, when the
+        /// response's rate limit headers report it.
+        limit_resets_at: Option<u64>,
+    },
+    /// Rate limits reported by the headers of the latest response, sent
+    /// before any output of a successful response and on every retry.
+    RateLimits(RateLimitSnapshot),
+    /// The wire API probed for a provider that does not configure one. Sent
+    /// once, before the output of the first response.
+    WireApiSelected(WireApi),
+    /// A background response is still `queued` or `in_progress` on the
+    /// server. Sent whenever its stream has been quiet for a while.
+    BackgroundProgress {
+        status: String,
+        elapsed: Duration,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -104,11 +242,30 @@ pub(crate) struct Reasoning {
     pub(crate) summary: ReasoningSummaryConfig,
 }
 
-/// Controls under the `text` field in the Responses API for GPT-5.
-#[derive(Debug, Serialize, Default, Clone, Copy)]
+/// Controls under the `text` field in the Responses API.
+#[derive(Debug, Serialize, Default, Clone)]
 pub(crate) struct TextControls {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) verbosity: Option<OpenAiVerbosity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<TextFormat>,
+}
+
+/// Structured output format: the final message must be JSON matching
+/// `schema`.
+#[derive(Debug, Serialize, Clone)]
+pub(crate) struct TextFormat {
+    pub(crate) r#type: TextFormatType,
+    pub(crate) name: String,
+    pub(crate) strict: bool,
+    pub(crate) schema: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TextFormatType {
+    #[default]
+    JsonSchema,
 }
 
 #[derive(Debug, Serialize, Default, Clone, Copy)]
@@ -130,28 +287,54 @@ impl From<VerbosityConfig> for OpenAiVerbosity {
     }
 }
 
+/// An item of the `input` of a Responses API request. It never holds
+/// [`ResponseItem::Other`], an item of a type this client does not know,
+/// which the API rejects when it is sent back.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub(crate) struct RequestItem<'a>(Cow<'a, ResponseItem>);
+
+impl<'a> RequestItem<'a> {
+    /// `None` for [`ResponseItem::Other`].
+    pub(crate) fn new(item: Cow<'a, ResponseItem>) -> Option<Self> {
+        match item.as_ref() {
+            ResponseItem::Other => None,
+            _ => Some(Self(item)),
+        }
+    }
+}
+
 /// Request object that is serialized as JSON and POST'ed when using the
 /// Responses API.
 #[derive(Debug, Serialize)]
 pub(crate) struct ResponsesApiRequest<'a> {
     pub(crate) model: &'a str,
     pub(crate) instructions: &'a str,
-    // TODO(mbolin): ResponseItem::Other should not be serialized. Currently,
-    // we code defensively to avoid this case, but perhaps we should use a
-    // separate enum for serialization.
-    pub(crate) input: &'a Vec<ResponseItem>,
+    pub(crate) input: &'a [RequestItem<'a>],
     pub(crate) tools: &'a [serde_json::Value],
     pub(crate) tool_choice: &'static str,
     pub(crate) parallel_tool_calls: bool,
     pub(crate) reasoning: Option<Reasoning>,
     /// true when using the Responses API.
     pub(crate) store: bool,
+    /// Run the response in background mode, so that it keeps going on the
+    /// server when the connection drops.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) background: bool,
     pub(crate) stream: bool,
     pub(crate) include: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) prompt_cache_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) text: Option<TextControls>,
+    /// Upper bound on the number of tokens the model may generate, including
+    /// reasoning tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_output_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f64>,
 }
 
 pub(crate) fn create_reasoning_param_for_request(
@@ -159,30 +342,155 @@ pub(crate) fn create_reasoning_param_for_request(
     effort: ReasoningEffortConfig,
     summary: ReasoningSummaryConfig,
 ) -> Option<Reasoning> {
-    if model_family.supports_reasoning_summaries {
-        Some(Reasoning { effort, summary })
-    } else {
-        None
+    if !model_family.supports_reasoning_summaries {
+        return None;
     }
+    let effort = match effort {
+        ReasoningEffortConfig::Minimal if !model_family.supports_minimal_reasoning_effort => {
+            ReasoningEffortConfig::Low
+        }
+        effort => effort,
+    };
+    Some(Reasoning { effort, summary })
 }
 
 pub(crate) fn create_text_param_for_request(
+    model_family: &ModelFamily,
     verbosity: Option<VerbosityConfig>,
+    output_schema: &Option<serde_json::Value>,
 ) -> Option<TextControls> {
-    verbosity.map(|v| TextControls {
-        verbosity: Some(v.into()),
-    })
+    let verbosity = verbosity
+        .filter(|_| model_family.supports_verbosity)
+        .map(OpenAiVerbosity::from);
+    let format = output_schema.as_ref().map(|schema| TextFormat {
+        r#type: TextFormatType::JsonSchema,
+        name: "codex_output_schema".to_string(),
+        strict: true,
+        schema: schema.clone(),
+    });
+    if verbosity.is_none() && format.is_none() {
+        return None;
+    }
+    Some(TextControls { verbosity, format })
 }
 
 pub(crate) struct ResponseStream {
     pub(crate) rx_event: mpsc::Receiver<Result<ResponseEvent>>,
+    /// Shared with the task feeding `rx_event`, which counts its retries.
+    pub(crate) metrics: Arc<StreamMetrics>,
+}
+
+impl ResponseStream {
+    pub(crate) fn new(
+        rx_event: mpsc::Receiver<Result<ResponseEvent>>,
+        metrics: Arc<StreamMetrics>,
+    ) -> Self {
+        Self { rx_event, metrics }
+    }
+
+    /// Latency of the response so far.
+    pub(crate) fn metrics(&self) -> TurnMetrics {
+        self.metrics.snapshot()
+    }
+}
+
+/// Times the events of one response, from when its first request was sent.
+#[derive(Debug)]
+pub(crate) struct StreamMetrics {
+    started: Instant,
+    state: Mutex<StreamMetricsState>,
+}
+
+#[derive(Debug, Default)]
+struct StreamMetricsState {
+    first_byte: Option<Instant>,
+    first_token: Option<Instant>,
+    completed: Option<Instant>,
+    retries: u64,
+    dropped_input_items: u64,
+}
+
+impl StreamMetrics {
+    pub(crate) fn new(started: Instant) -> Self {
+        Self {
+            started,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Records `count` retried requests or reconnects.
+    pub(crate) fn add_retries(&self, count: u64) {
+        self.state().retries += count;
+    }
+
+    /// Records `count` history items left out of the request.
+    pub(crate) fn add_dropped_input_items(&self, count: u64) {
+        self.state().dropped_input_items += count;
+    }
+
+    fn record(&self, event: &ResponseEvent) {
+        let now = Instant::now();
+        let mut state = self.state();
+        match event {
+            // Sent from response headers and while waiting to retry, not
+            // from the stream itself.
+            ResponseEvent::RateLimited { .. }
+            | ResponseEvent::RateLimits(_)
+            | ResponseEvent::WireApiSelected(_)
+            | ResponseEvent::BackgroundProgress { .. } => return,
+            ResponseEvent::OutputTextDelta(_) => {
+                state.first_token.get_or_insert(now);
+            }
+            ResponseEvent::Completed { .. } => state.completed = Some(now),
+            _ => {}
+        }
+        state.first_byte.get_or_insert(now);
+    }
+
+    fn snapshot(&self) -> TurnMetrics {
+        let state = self.state();
+        let millis = |at: Instant| {
+            u64::try_from(at.duration_since(self.started).as_millis()).unwrap_or(u64::MAX)
+        };
+        TurnMetrics {
+            time_to_first_byte_ms: state.first_byte.map(millis),
+            time_to_first_token_ms: state.first_token.map(millis),
+            stream_duration_ms: millis(state.completed.unwrap_or_else(Instant::now)),
+            retries: state.retries,
+            dropped_input_items: state.dropped_input_items,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, StreamMetricsState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Spawns `fut`, which feeds `tx_event`, and cancels it as soon as the
+/// receiving [`ResponseStream`] is dropped (e.g. because the turn was
+/// interrupted). This closes the HTTP connection right away instead of
+/// leaving it open until the next event fails to send.
+pub(crate) fn spawn_response_task<F>(tx_event: mpsc::Sender<Result<ResponseEvent>>, fut: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tx_event.closed() => {}
+            _ = fut => {}
+        }
+    });
 }
 
 impl Stream for ResponseStream {
     type Item = Result<ResponseEvent>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.rx_event.poll_recv(cx)
+        let poll = self.rx_event.poll_recv(cx);
+        if let Poll::Ready(Some(Ok(event))) = &poll {
+            self.metrics.record(event);
+        }
+        poll
     }
 }
 
@@ -199,13 +507,153 @@ mod tests {
         };
         let expected = format!("{BASE_INSTRUCTIONS}\n{APPLY_PATCH_TOOL_INSTRUCTIONS}");
         let model_family = find_family_for_model("gpt-4.1").expect("known model slug");
-        let full = prompt.get_full_instructions(&model_family);
+        let full = prompt.get_full_instructions(&model_family).unwrap();
+        assert_eq!(full, expected);
+    }
+
+    #[test]
+    fn get_full_instructions_with_plan_tool() {
+        let prompt = Prompt {
+            tools: vec![crate::plan_tool::PLAN_TOOL.clone()],
+            ..Default::default()
+        };
+        let expected = format!(
+            "{BASE_INSTRUCTIONS}\n{PLAN_TOOL_INSTRUCTIONS}\n{APPLY_PATCH_TOOL_INSTRUCTIONS}"
+        );
+        let model_family = find_family_for_model("gpt-4.1").expect("known model slug");
+        let full = prompt.get_full_instructions(&model_family).unwrap();
         assert_eq!(full, expected);
     }
 
+    #[test]
+    fn base_instructions_override_is_templated() {
+        let user_vars =
+            std::collections::HashMap::from([("project".to_string(), "codex".to_string())]);
+        let prompt = Prompt {
+            base_instructions_override: Some("Work on {{project}} in {{cwd}}.".to_string()),
+            prompt_vars: PromptVars::new(
+                std::path::Path::new("/work/codex"),
+                "gpt-5",
+                &user_vars,
+                false,
+            ),
+            ..Default::default()
+        };
+        let model_family = find_family_for_model("gpt-5").expect("known model slug");
+        let full = prompt.get_full_instructions(&model_family).unwrap();
+        assert_eq!(full, "Work on codex in /work/codex.");
+    }
+
+    #[test]
+    fn minimal_effort_is_gated_on_the_model_family() {
+        let effort = |slug: &str| {
+            let model_family = find_family_for_model(slug).expect("known model slug");
+            create_reasoning_param_for_request(
+                &model_family,
+                ReasoningEffortConfig::Minimal,
+                ReasoningSummaryConfig::Auto,
+            )
+            .map(|reasoning| reasoning.effort)
+        };
+        assert_eq!(effort("gpt-5"), Some(ReasoningEffortConfig::Minimal));
+        assert_eq!(effort("o3"), Some(ReasoningEffortConfig::Low));
+        assert_eq!(effort("gpt-4.1"), None);
+    }
+
+    #[test]
+    fn estimated_token_count_grows_with_input() {
+        let model_family = find_family_for_model("gpt-5").expect("known model slug");
+        let empty = Prompt::default();
+        let baseline = empty.estimated_context_breakdown(&model_family).total();
+        assert!(baseline > 0, "instructions alone should cost tokens");
+
+        let prompt = Prompt {
+            input: vec![Arc::new(ResponseItem::Message {
+                id: None,
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "The quick brown fox jumps over the lazy dog.".to_string(),
+                }],
+            })],
+            ..Default::default()
+        };
+        // 10 tokens of text plus the role and per-item overhead.
+        assert_eq!(
+            prompt.estimated_context_breakdown(&model_family).total(),
+            baseline + 15
+        );
+    }
+
+    #[test]
+    fn context_breakdown_separates_environment_from_conversation() {
+        let model_family = find_family_for_model("gpt-5").expect("known model slug");
+        let message = |text: &str| {
+            Arc::new(ResponseItem::Message {
+                id: None,
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: text.to_string(),
+                }],
+            })
+        };
+        let prompt = Prompt {
+            input: vec![
+                message("<user_instructions>\n\nRun cargo test.\n\n</user_instructions>"),
+                message("<environment_context>\n  <cwd>/repo</cwd>\n</environment_context>"),
+                message("Fix the flaky test."),
+            ],
+            ..Default::default()
+        };
+        let breakdown = prompt.estimated_context_breakdown(&model_family);
+        assert!(breakdown.instructions_tokens > 0);
+        assert!(breakdown.environment_tokens > breakdown.conversation_tokens);
+        assert!(breakdown.conversation_tokens > 0);
+    }
+
+    #[test]
+    fn text_param_is_gated_by_model_family() {
+        let gpt5 = find_family_for_model("gpt-5").expect("known model slug");
+        let o3 = find_family_for_model("o3").expect("known model slug");
+
+        assert!(create_text_param_for_request(&gpt5, None, &None).is_none());
+        let text = create_text_param_for_request(&gpt5, Some(VerbosityConfig::Low), &None)
+            .expect("gpt-5 supports verbosity");
+        assert_eq!(
+            serde_json::to_value(text).unwrap(),
+            serde_json::json!({"verbosity": "low"})
+        );
+        assert!(create_text_param_for_request(&o3, Some(VerbosityConfig::Low), &None).is_none());
+    }
+
+    #[test]
+    fn text_param_carries_output_schema() {
+        let o3 = find_family_for_model("o3").expect("known model slug");
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"answer": {"type": "string"}},
+            "required": ["answer"],
+            "additionalProperties": false,
+        });
+
+        let text =
+            create_text_param_for_request(&o3, Some(VerbosityConfig::Low), &Some(schema.clone()))
+                .expect("schema is sent regardless of verbosity support");
+        assert_eq!(
+            serde_json::to_value(text).unwrap(),
+            serde_json::json!({
+                "format": {
+                    "type": "json_schema",
+                    "name": "codex_output_schema",
+                    "strict": true,
+                    "schema": schema,
+                }
+            })
+        );
+    }
+
     #[test]
     fn serializes_text_verbosity_when_set() {
-        let input: Vec<ResponseItem> = vec![];
+        let input: Vec<RequestItem> = vec![];
         let tools: Vec<serde_json::Value> = vec![];
         let req = ResponsesApiRequest {
             model: "gpt-5",
@@ -216,12 +664,17 @@ mod tests {
             parallel_tool_calls: false,
             reasoning: None,
             store: true,
+            background: false,
             stream: true,
             include: vec![],
             prompt_cache_key: None,
             text: Some(TextControls {
                 verbosity: Some(OpenAiVerbosity::Low),
+                format: None,
             }),
+            max_output_tokens: None,
+            temperature: None,
+            top_p: None,
         };
 
         let v = serde_json::to_value(&req).expect("json");
@@ -235,7 +688,7 @@ mod tests {
 
     #[test]
     fn omits_text_when_not_set() {
-        let input: Vec<ResponseItem> = vec![];
+        let input: Vec<RequestItem> = vec![];
         let tools: Vec<serde_json::Value> = vec![];
         let req = ResponsesApiRequest {
             model: "gpt-5",
@@ -246,13 +699,85 @@ mod tests {
             parallel_tool_calls: false,
             reasoning: None,
             store: true,
+            background: false,
             stream: true,
             include: vec![],
             prompt_cache_key: None,
             text: None,
+            max_output_tokens: None,
+            temperature: None,
+            top_p: None,
         };
 
         let v = serde_json::to_value(&req).expect("json");
         assert!(v.get("text").is_none());
     }
+
+    #[test]
+    fn serializes_max_output_tokens_only_when_set() {
+        let input: Vec<RequestItem> = vec![];
+        let tools: Vec<serde_json::Value> = vec![];
+        let mut req = ResponsesApiRequest {
+            model: "gpt-5",
+            instructions: "i",
+            input: &input,
+            tools: &tools,
+            tool_choice: "auto",
+            parallel_tool_calls: false,
+            reasoning: None,
+            store: true,
+            background: false,
+            stream: true,
+            include: vec![],
+            prompt_cache_key: None,
+            text: None,
+            max_output_tokens: None,
+            temperature: None,
+            top_p: None,
+        };
+
+        let v = serde_json::to_value(&req).expect("json");
+        assert!(v.get("max_output_tokens").is_none());
+
+        req.max_output_tokens = Some(2048);
+        let v = serde_json::to_value(&req).expect("json");
+        assert_eq!(
+            v.get("max_output_tokens").and_then(|n| n.as_u64()),
+            Some(2048)
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_metrics_time_the_response_body() {
+        use futures::StreamExt;
+
+        let (tx, rx) = mpsc::channel(8);
+        let metrics = Arc::new(StreamMetrics::new(Instant::now()));
+        metrics.add_retries(2);
+        let mut stream = ResponseStream::new(rx, metrics);
+
+        tx.send(Ok(ResponseEvent::RateLimits(RateLimitSnapshot::default())))
+            .await
+            .expect("send");
+        stream.next().await;
+        let before_body = stream.metrics();
+        assert_eq!(before_body.time_to_first_byte_ms, None);
+
+        for event in [
+            ResponseEvent::Created,
+            ResponseEvent::OutputTextDelta("hi".to_string()),
+            ResponseEvent::Completed {
+                response_id: "resp_1".to_string(),
+                token_usage: None,
+            },
+        ] {
+            tx.send(Ok(event)).await.expect("send");
+            stream.next().await;
+        }
+        let metrics = stream.metrics();
+        assert_eq!(metrics.retries, 2);
+        let first_byte = metrics.time_to_first_byte_ms.expect("first byte");
+        let first_token = metrics.time_to_first_token_ms.expect("first token");
+        assert!(first_byte <= first_token && first_token <= metrics.stream_duration_ms);
+    }
 }
@@ -1,29 +1,40 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU64;
 use std::time::Duration;
 
 use async_channel::Receiver;
 use async_channel::Sender;
+use chrono::Utc;
 use codex_apply_patch::ApplyPatchAction;
+use codex_apply_patch::MatchStrategy;
 use codex_apply_patch::MaybeApplyPatchVerified;
 use codex_apply_patch::maybe_parse_apply_patch_verified;
+use codex_apply_patch::maybe_parse_apply_patch_verified_with_strategy;
 use codex_login::AuthManager;
 use codex_protocol::protocol::ConversationHistoryResponseEvent;
 use codex_protocol::protocol::TaskStartedEvent;
 use codex_protocol::protocol::TurnAbortReason;
 use codex_protocol::protocol::TurnAbortedEvent;
+use futures::future::BoxFuture;
 use futures::prelude::*;
+use futures::stream::FuturesUnordered;
 use mcp_types::CallToolResult;
+use mcp_types::ProgressNotificationParams;
 use serde::Serialize;
 use serde_json;
+use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use tokio::task::AbortHandle;
+use tracing::Instrument;
+use tracing::Span;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -36,19 +47,36 @@ use crate::apply_patch;
 use crate::apply_patch::ApplyPatchExec;
 use crate::apply_patch::CODEX_APPLY_PATCH_ARG1;
 use crate::apply_patch::InternalApplyPatchInvocation;
+use crate::apply_patch::apply_patch_match_strategies;
 use crate::apply_patch::convert_apply_patch_to_protocol;
+use crate::approved_commands::ApprovedCommands;
+use crate::approved_commands::session_approval_prefix;
 use crate::client::ModelClient;
 use crate::client_common::Prompt;
 use crate::client_common::ResponseEvent;
+use crate::codex_dirs::MCP_RESOURCES_SUBDIR;
+use crate::command_tools::CommandTool;
+use crate::compact::AUTO_COMPACT_KEEP_FRACTION;
+use crate::compact::SUMMARIZATION_PROMPT;
+use crate::compact::SUMMARIZATION_TRIGGER;
+use crate::compact::estimate_items_tokens;
+use crate::compact::plan_compaction;
+use crate::compact::summary_message;
 use crate::config::Config;
+use crate::config_types::ExecOutputLimits;
 use crate::config_types::ShellEnvironmentPolicy;
 use crate::conversation_history::ConversationHistory;
 use crate::environment_context::EnvironmentContext;
+use crate::environment_context::Platform;
+use crate::environment_context::environment_context_delta;
+use crate::environment_context::recorded_environment;
 use crate::error::CodexErr;
 use crate::error::Result as CodexResult;
 use crate::error::SandboxErr;
 use crate::error::get_error_message_ui;
+use crate::exec::ExecLimits;
 use crate::exec::ExecParams;
+use crate::exec::ExecPty;
 use crate::exec::ExecToolCallOutput;
 use crate::exec::SandboxType;
 use crate::exec::StdoutStream;
@@ -60,17 +88,37 @@ use crate::exec_command::ExecSessionManager;
 use crate::exec_command::WRITE_STDIN_TOOL_NAME;
 use crate::exec_command::WriteStdinParams;
 use crate::exec_env::create_env;
+use crate::exec_env::create_env_from;
+use crate::exec_env::summarize_env;
+use crate::git_info::VersionControl;
+use crate::git_info::collect_git_head_state;
+use crate::git_info::resolve_git_common_dir;
+use crate::image_input::read_viewed_image;
+use crate::image_input::validate_input_images;
+use crate::mcp_connection_manager::ClientStartErrors;
 use crate::mcp_connection_manager::McpConnectionManager;
+use crate::mcp_prompt::render_prompt;
+use crate::mcp_prompt::resolve_prompt;
 use crate::mcp_tool_call::handle_mcp_tool_call;
-use crate::model_family::find_family_for_model;
-use crate::openai_model_info::get_model_info;
+use crate::model_family::find_family_for_model_with_overrides;
+use crate::model_family::get_model_context_window;
+use crate::model_provider_info::WireApi;
+use crate::network_proxy::NetworkProxy;
 use crate::openai_tools::ApplyPatchToolArgs;
 use crate::openai_tools::ToolsConfig;
 use crate::openai_tools::ToolsConfigParams;
 use crate::openai_tools::get_openai_tools;
+use crate::otel;
 use crate::parse_command::parse_command;
+use crate::patch_undo::PatchSnapshot;
+use crate::patch_undo::PatchUndoStack;
+use crate::plan_tool::UpdatePlanArgs;
 use crate::plan_tool::handle_update_plan;
+use crate::plan_tool::plan_checklist;
+use crate::project_doc::UserInstructions;
 use crate::project_doc::get_user_instructions;
+use crate::project_doc::load_user_instructions;
+use crate::prompt_vars::PromptVars;
 use crate::protocol::AgentMessageDeltaEvent;
 use crate::protocol::AgentMessageEvent;
 use crate::protocol::AgentReasoningDeltaEvent;
@@ -80,42 +128,99 @@ use crate::protocol::AgentReasoningRawContentEvent;
 use crate::protocol::AgentReasoningSectionBreakEvent;
 use crate::protocol::ApplyPatchApprovalRequestEvent;
 use crate::protocol::AskForApproval;
+use crate::protocol::AuthExpiredEvent;
 use crate::protocol::BackgroundEventEvent;
+use crate::protocol::BackgroundResponseProgressEvent;
+use crate::protocol::ContextBreakdown;
+use crate::protocol::ContextUsageEvent;
+use crate::protocol::ContextWindowWarningEvent;
+use crate::protocol::ConversationCompactedEvent;
 use crate::protocol::ErrorEvent;
+use crate::protocol::EscalationReason;
 use crate::protocol::Event;
 use crate::protocol::EventMsg;
 use crate::protocol::ExecApprovalRequestEvent;
 use crate::protocol::ExecCommandBeginEvent;
 use crate::protocol::ExecCommandEndEvent;
 use crate::protocol::FileChange;
+use crate::protocol::FunctionCallArgumentsDeltaEvent;
+use crate::protocol::GitRepoWarningEvent;
+use crate::protocol::GitRepoWarningKind;
 use crate::protocol::InputItem;
 use crate::protocol::ListCustomPromptsResponseEvent;
+use crate::protocol::McpListPromptsResponseEvent;
+use crate::protocol::McpListResourcesResponseEvent;
+use crate::protocol::McpServerStatus;
+use crate::protocol::McpStartupWarningEvent;
+use crate::protocol::ModelStatus;
 use crate::protocol::Op;
 use crate::protocol::PatchApplyBeginEvent;
 use crate::protocol::PatchApplyEndEvent;
+use crate::protocol::PatchMatchStrategy;
+use crate::protocol::PatchSelection;
+use crate::protocol::PatchUndoneEvent;
+use crate::protocol::ProjectDocTruncatedEvent;
+use crate::protocol::PromptTokenEstimateEvent;
+use crate::protocol::ProviderStatus;
+use crate::protocol::PullRequestEvent;
+use crate::protocol::QueuedInput;
+use crate::protocol::QueuedInputEvent;
+use crate::protocol::RateLimitSnapshot;
 use crate::protocol::ReviewDecision;
+use crate::protocol::ReviewFindingsEvent;
 use crate::protocol::SandboxPolicy;
+use crate::protocol::SearchHistoryResponseEvent;
 use crate::protocol::SessionConfiguredEvent;
+use crate::protocol::SessionStatus;
 use crate::protocol::StreamErrorEvent;
 use crate::protocol::Submission;
 use crate::protocol::TaskCompleteEvent;
+use crate::protocol::TokenUsage;
+use crate::protocol::ToolOutputBudgetExceededEvent;
+use crate::protocol::TruncatedProjectDoc;
 use crate::protocol::TurnDiffEvent;
+use crate::protocol::UsageUpdateEvent;
 use crate::protocol::WebSearchBeginEvent;
 use crate::protocol::WebSearchEndEvent;
+use crate::pull_request::PULL_REQUEST_TOOL_NAME;
+use crate::pull_request::PullRequestTool;
+use crate::pull_request::find_pull_request_url;
+use crate::rate_limits;
+use crate::redact::Redactor;
+use crate::review;
+use crate::review::REVIEW_PROMPT;
 use crate::rollout::RolloutRecorder;
+use crate::rollout::SESSIONS_SUBDIR;
+use crate::rollout::UsageRecord;
+use crate::rollout::WriteExemption;
+use crate::rollout::replayable_items;
 use crate::safety::SafetyCheck;
 use crate::safety::assess_command_safety;
 use crate::safety::assess_safety_for_untrusted_command;
+use crate::safety::effective_approval_policy;
+use crate::safety::get_platform_sandbox;
+use crate::safety::sandbox_policy_for_publishing;
+use crate::safety::sandbox_policy_with_write_exemptions;
+use crate::sandbox_denial::escalation_note;
+use crate::sandbox_denial::sandbox_denial_reason;
 use crate::shell;
+use crate::spawn::CODEX_SANDBOX_TMPDIR_ENV_VAR;
+use crate::spawn::CODEX_SCRATCH_DIR_ENV_VAR;
+use crate::token_estimate::Encoding;
+use crate::tool_output_budget::ToolOutputBudget;
+use crate::tool_output_budget::default_tool_output_budget;
 use crate::turn_diff_tracker::TurnDiffTracker;
 use crate::user_notification::UserNotification;
+use crate::user_notification::UserNotifier;
 use crate::util::backoff;
+use crate::util::strip_terminal_escapes;
 use codex_protocol::config_types::ReasoningEffort as ReasoningEffortConfig;
 use codex_protocol::config_types::ReasoningSummary as ReasoningSummaryConfig;
 use codex_protocol::custom_prompts::CustomPrompt;
 use codex_protocol::models::ContentItem;
 use codex_protocol::models::FunctionCallOutputPayload;
 use codex_protocol::models::LocalShellAction;
+use codex_protocol::models::LocalShellExecAction;
 use codex_protocol::models::ReasoningItemContent;
 use codex_protocol::models::ReasoningItemReasoningSummary;
 use codex_protocol::models::ResponseInputItem;
@@ -157,13 +262,6 @@ pub struct CodexSpawnOk {
 pub(crate) const INITIAL_SUBMIT_ID: &str = "";
 pub(crate) const SUBMISSION_CHANNEL_CAPACITY: usize = 64;
 
-// Model-formatting limits: clients get full streams; oonly content sent to the model is truncated.
-pub(crate) const MODEL_FORMAT_MAX_BYTES: usize = 10 * 1024; // 10 KiB
-pub(crate) const MODEL_FORMAT_MAX_LINES: usize = 256; // lines
-pub(crate) const MODEL_FORMAT_HEAD_LINES: usize = MODEL_FORMAT_MAX_LINES / 2;
-pub(crate) const MODEL_FORMAT_TAIL_LINES: usize = MODEL_FORMAT_MAX_LINES - MODEL_FORMAT_HEAD_LINES; // 128
-pub(crate) const MODEL_FORMAT_HEAD_BYTES: usize = MODEL_FORMAT_MAX_BYTES / 2;
-
 impl Codex {
     /// Spawn a new [`Codex`] and initialize the session.
     pub async fn spawn(
@@ -174,7 +272,20 @@ impl Codex {
         let (tx_sub, rx_sub) = async_channel::bounded(SUBMISSION_CHANNEL_CAPACITY);
         let (tx_event, rx_event) = async_channel::unbounded();
 
-        let user_instructions = get_user_instructions(&config).await;
+        let UserInstructions {
+            text: user_instructions,
+            project_docs,
+            truncated: truncated_project_docs,
+        } = get_user_instructions(&config).await;
+        if config.project_doc_strict
+            && let Some(doc) = truncated_project_docs.first()
+        {
+            return Err(CodexErr::ProjectDocTooLarge {
+                path: doc.path.clone(),
+                size: doc.size_bytes,
+                limit: config.project_doc_max_bytes,
+            });
+        }
 
         let config = Arc::new(config);
         let resume_path = config.experimental_resume.clone();
@@ -185,6 +296,8 @@ impl Codex {
             model_reasoning_effort: config.model_reasoning_effort,
             model_reasoning_summary: config.model_reasoning_summary,
             user_instructions,
+            project_docs,
+            truncated_project_docs,
             base_instructions: config.base_instructions.clone(),
             approval_policy: config.approval_policy,
             sandbox_policy: config.sandbox_policy.clone(),
@@ -256,14 +369,153 @@ impl Codex {
     }
 }
 
+/// The user's answer to an approval request.
+#[derive(Debug, Default)]
+pub(crate) struct ApprovalResponse {
+    pub(crate) decision: ReviewDecision,
+    /// Parts of a patch the user rejected; only patch approvals carry one.
+    pub(crate) selection: Option<PatchSelection>,
+    /// Replaces the default rejection message the model gets on a denial.
+    pub(crate) feedback: Option<String>,
+}
+
+impl ApprovalResponse {
+    /// What the model is told when the request was denied.
+    pub(crate) fn rejection_message(self, default: String) -> String {
+        self.feedback.unwrap_or(default)
+    }
+}
+
 /// Mutable state of the agent
 #[derive(Default)]
 struct State {
-    approved_commands: HashSet<Vec<String>>,
+    approved_commands: ApprovedCommands,
     current_task: Option<AgentTask>,
-    pending_approvals: HashMap<String, oneshot::Sender<ReviewDecision>>,
+    pending_approvals: HashMap<String, oneshot::Sender<ApprovalResponse>>,
     pending_input: Vec<ResponseInputItem>,
     history: ConversationHistory,
+    /// Token usage summed over every completed model response.
+    total_token_usage: TokenUsage,
+    /// Token usage of the most recently completed model response.
+    last_token_usage: TokenUsage,
+    /// Latest usage reported for the model response that is still streaming.
+    pending_token_usage: Option<TokenUsage>,
+    /// Estimated cost of `total_token_usage` in US dollars, meaningful only
+    /// while `has_unpriced_usage` is false.
+    total_cost_usd: f64,
+    /// Estimated cost of `last_token_usage`.
+    last_cost_usd: Option<f64>,
+    /// Whether a completed response came from a model without known prices.
+    has_unpriced_usage: bool,
+    /// Rate limits reported with the most recent model response.
+    rate_limits: Option<RateLimitSnapshot>,
+    /// Output of the model response that is still streaming, committed to
+    /// the history if the turn is interrupted.
+    partial_turn: Option<PartialTurn>,
+    /// Prompt size reported for the most recent model response.
+    last_prompt_tokens: Option<PromptTokens>,
+    /// Estimated split of the most recent prompt.
+    context_breakdown: Option<ContextBreakdown>,
+    /// How full the context window was after the latest model response.
+    context_usage: Option<ContextUsageEvent>,
+    /// Highest `context_warning_thresholds` entry that usage was past at the
+    /// last report, so each threshold warns once until usage drops below it.
+    warned_context_threshold: Option<f64>,
+    /// Set by `Op::Compact` while a task is running so that the task compacts
+    /// the history before its next request.
+    compact_requested: bool,
+    /// Latest plan published through the `update_plan` tool.
+    plan: Option<UpdatePlanArgs>,
+    /// Input submitted with `Op::QueueUserInput` while a task was running,
+    /// oldest first.
+    queued_input: Vec<QueuedInput>,
+    /// Turn context the queued input is sent with; the latest one in effect
+    /// when the queue was last touched.
+    queued_turn_context: Option<Arc<TurnContext>>,
+    /// Custom tools the user approved for the rest of the session.
+    approved_command_tools: HashSet<String>,
+}
+
+/// Input tokens the provider reported for a request, together with the
+/// length of the history at that point. Items recorded since then are
+/// estimated on top of it to project the size of the next request.
+#[derive(Debug, Clone, Copy)]
+struct PromptTokens {
+    input_tokens: u64,
+    history_len: usize,
+}
+
+/// How often a turn waiting in [`wait_for_login`] checks `auth.json`.
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Appended to a truncated assistant message so the model can tell, on the
+/// next turn, that its previous answer was cut off.
+const TURN_INTERRUPTED_NOTE: &str = "[response interrupted by the user]";
+
+/// Reason shown when the first write outside a clean git repository is
+/// confirmed.
+/// Directory under the cache dir holding the sessions' scratch directories.
+const SCRATCH_SUBDIR: &str = "scratch";
+
+pub(crate) const FIRST_WRITE_CONFIRMATION_REASON: &str = "This is the first change Codex makes in this directory, which is not a clean git repository, so it may be hard to review or undo.";
+
+/// What a model response has streamed so far: completed output items plus
+/// the text of the assistant message that is still in progress.
+#[derive(Debug, Default)]
+struct PartialTurn {
+    items: Vec<ResponseItem>,
+    text: String,
+}
+
+impl PartialTurn {
+    fn into_items(self) -> Vec<ResponseItem> {
+        let mut items = self.items;
+        if !self.text.is_empty() {
+            items.push(ResponseItem::Message {
+                id: None,
+                role: "assistant".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: format!("{}\n\n{TURN_INTERRUPTED_NOTE}", self.text),
+                }],
+            });
+        }
+        items
+    }
+}
+
+impl State {
+    /// Commits whatever the in-flight response produced to the history and
+    /// returns those items so they can be written to the rollout as well.
+    fn commit_partial_turn(&mut self) -> Vec<ResponseItem> {
+        let items = self
+            .partial_turn
+            .take()
+            .map(PartialTurn::into_items)
+            .unwrap_or_default();
+        self.history.record_items(&items);
+        items
+    }
+
+    fn usage_update_event(&self) -> UsageUpdateEvent {
+        let (total_token_usage, last_token_usage) = match &self.pending_token_usage {
+            Some(pending) => (self.total_token_usage.add_usage(pending), pending.clone()),
+            None => (
+                self.total_token_usage.clone(),
+                self.last_token_usage.clone(),
+            ),
+        };
+        UsageUpdateEvent {
+            total_token_usage,
+            last_token_usage,
+            total_cost_usd: self.total_cost_usd(),
+            last_cost_usd: self.last_cost_usd,
+        }
+    }
+
+    /// `None` before the first priced response and after an unpriced one.
+    fn total_cost_usd(&self) -> Option<f64> {
+        (self.last_cost_usd.is_some() && !self.has_unpriced_usage).then_some(self.total_cost_usd)
+    }
 }
 
 /// Context for an initialized model agent
@@ -277,9 +529,9 @@ pub(crate) struct Session {
     mcp_connection_manager: McpConnectionManager,
     session_manager: ExecSessionManager,
 
-    /// External notifier command (will be passed as args to exec()). When
-    /// `None` this feature is disabled.
-    notify: Option<Vec<String>>,
+    /// Delivers turn-complete and approval notifications to the user's
+    /// `notify` command or the desktop.
+    notifier: UserNotifier,
 
     /// Optional rollout recorder for persisting the conversation transcript so
     /// sessions can be replayed or inspected later.
@@ -288,6 +540,63 @@ pub(crate) struct Session {
     codex_linux_sandbox_exe: Option<PathBuf>,
     user_shell: shell::Shell,
     show_raw_agent_reasoning: bool,
+    hide_environment_context: bool,
+    auto_compact_threshold: f64,
+    /// Ascending fractions of the context window that trigger a warning.
+    context_warning_thresholds: Vec<f64>,
+    /// Upper bound on read-only tool calls from one response that run at once.
+    max_parallel_tool_calls: usize,
+    /// Loosest way `apply_patch` may locate the lines a chunk replaces.
+    apply_patch_loosest_match: MatchStrategy,
+    /// How much of a command's output is shown to the model.
+    exec_output_limits: ExecOutputLimits,
+    /// Configured per-turn tool output budget; derived from the context
+    /// window when unset.
+    tool_output_budget_bytes: Option<usize>,
+    /// Timeout for commands the model does not give one of its own.
+    exec_timeout_ms: u64,
+    exec_limits: ExecLimits,
+    exec_use_pty: bool,
+    exec_pty: ExecPty,
+    /// Where the full output of commands truncated for the model is written,
+    /// so the user can inspect it.
+    exec_output_spool_dir: PathBuf,
+    /// Per-session directory for temporary files, exported to commands as
+    /// `CODEX_SCRATCH_DIR` and writable under every sandbox policy. `None`
+    /// if it could not be created.
+    scratch_dir: Option<PathBuf>,
+    /// Whether `scratch_dir` survives shutdown.
+    retain_scratch_dir: bool,
+    /// Whether commands may write to the temp directory under a read-only
+    /// sandbox.
+    read_only_sandbox_allow_tmp: bool,
+    /// Whether queued input survives `Op::Interrupt`.
+    keep_queued_input_on_interrupt: bool,
+    /// The `[prompt_vars]` config table.
+    prompt_vars: HashMap<String, String>,
+    strict_prompt_vars: bool,
+    /// Patches applied in this session, kept under the session directory so
+    /// `Op::UndoLastPatch` works after a crash.
+    patch_undo: PatchUndoStack,
+    /// Applied to tool output, the environment context and user instructions
+    /// before they are recorded in the conversation history.
+    redactor: Redactor,
+    /// Proxy enforcing the sandbox network allowlist, started by the first
+    /// sandboxed command that needs it.
+    network_proxy: Mutex<Option<NetworkProxy>>,
+    /// Number of model turns started so far, used to label tracing spans.
+    turns_started: AtomicU64,
+    /// Set while the first write in a working directory that is not a clean
+    /// git repository still needs the user's confirmation.
+    confirm_first_write: AtomicBool,
+    /// Whether tracing spans may carry prompt, command and output text.
+    otel_include_content: bool,
+    /// Size above which MCP tool call arguments are truncated in events.
+    mcp_event_max_argument_bytes: usize,
+    /// Tools from `[tools.custom]` that run a local command.
+    command_tools: Vec<CommandTool>,
+    /// The `create_pull_request` tool from `[tools.pull_request]`.
+    pull_request_tool: Option<PullRequestTool>,
 }
 
 /// The context needed for a single turn of the conversation.
@@ -300,11 +609,23 @@ pub(crate) struct TurnContext {
     pub(crate) cwd: PathBuf,
     pub(crate) base_instructions: Option<String>,
     pub(crate) user_instructions: Option<String>,
+    /// The `AGENTS.md` files merged into `user_instructions`, root first.
+    pub(crate) project_docs: Vec<PathBuf>,
     pub(crate) approval_policy: AskForApproval,
     pub(crate) sandbox_policy: SandboxPolicy,
     pub(crate) shell_environment_policy: ShellEnvironmentPolicy,
+    /// Environment of the user's login shell, captured at session start when
+    /// the policy uses the shell profile. Commands start from it instead of
+    /// Codex's own environment.
+    pub(crate) profile_env: Option<Arc<HashMap<String, String>>>,
     pub(crate) disable_response_storage: bool,
     pub(crate) tools_config: ToolsConfig,
+    /// Set for the turns of an `Op::Review` task to the ref being reviewed
+    /// against; the task then reports `EventMsg::ReviewFindings`.
+    pub(crate) review_base: Option<String>,
+    /// JSON schema the final assistant message must match, set by
+    /// `Op::UserTurn`.
+    pub(crate) final_output_json_schema: Option<serde_json::Value>,
 }
 
 impl TurnContext {
@@ -313,6 +634,14 @@ impl TurnContext {
             .map(PathBuf::from)
             .map_or_else(|| self.cwd.clone(), |p| self.cwd.join(p))
     }
+
+    /// The environment a command from the model runs with.
+    fn exec_env(&self) -> HashMap<String, String> {
+        match &self.profile_env {
+            Some(profile_env) => create_env_from(profile_env, &self.shell_environment_policy),
+            None => create_env(&self.shell_environment_policy),
+        }
+    }
 }
 
 /// Configure the model session.
@@ -329,6 +658,12 @@ struct ConfigureSession {
     /// Model instructions that are appended to the base instructions.
     user_instructions: Option<String>,
 
+    /// The `AGENTS.md` files merged into `user_instructions`.
+    project_docs: Vec<PathBuf>,
+
+    /// The `AGENTS.md` files that did not fit `project_doc_max_bytes`.
+    truncated_project_docs: Vec<TruncatedProjectDoc>,
+
     /// Base instructions override.
     base_instructions: Option<String>,
 
@@ -370,6 +705,8 @@ impl Session {
             model_reasoning_effort,
             model_reasoning_summary,
             user_instructions,
+            project_docs,
+            truncated_project_docs,
             base_instructions,
             approval_policy,
             sandbox_policy,
@@ -392,49 +729,102 @@ impl Session {
         // - spin up MCP connection manager
         // - perform default shell discovery
         // - load history metadata
+        // - read git HEAD state for the environment context
         let rollout_fut = async {
             match resume_path.as_ref() {
-                Some(path) => RolloutRecorder::resume(path, cwd.clone())
+                Some(path) if config.experimental_fork => RolloutRecorder::fork(&config, path)
                     .await
                     .map(|(rec, saved)| (saved.session_id, Some(saved), rec)),
+                Some(path) => RolloutRecorder::resume(
+                    path,
+                    config.dirs.state_dir(),
+                    cwd.clone(),
+                    config.rollout_fsync,
+                )
+                .await
+                .map(|(rec, saved)| (saved.session_id, Some(saved), rec)),
                 None => {
                     let session_id = Uuid::new_v4();
-                    RolloutRecorder::new(&config, session_id, user_instructions.clone())
+                    // The rollout must never contain unredacted instructions.
+                    let instructions = user_instructions
+                        .as_deref()
+                        .map(|ui| config.redactions.redact(ui));
+                    RolloutRecorder::new(&config, session_id, instructions)
                         .await
                         .map(|rec| (session_id, None, rec))
                 }
             }
         };
 
-        let mcp_fut = McpConnectionManager::new(config.mcp_servers.clone());
+        let mcp_fut = McpConnectionManager::new(
+            config.mcp_servers.clone(),
+            config.dirs.config_dir(),
+            &config.shell_environment_policy,
+        );
         let default_shell_fut = shell::default_user_shell();
         let history_meta_fut = crate::message_history::history_metadata(&config);
+        let git_state_fut = collect_git_head_state(&cwd);
 
         // Join all independent futures.
-        let (rollout_res, mcp_res, default_shell, (history_log_id, history_entry_count)) =
-            tokio::join!(rollout_fut, mcp_fut, default_shell_fut, history_meta_fut);
+        let (rollout_res, mcp_res, default_shell, history_meta, git_state) = tokio::join!(
+            rollout_fut,
+            mcp_fut,
+            default_shell_fut,
+            history_meta_fut,
+            git_state_fut
+        );
+        let (history_log_id, history_entry_count) = history_meta;
+        let profile_env = if config.shell_environment_policy.use_profile {
+            let profile_env = default_shell.profile_env(&cwd).await;
+            if profile_env.is_none() {
+                warn!("could not capture the shell profile environment; sourcing it per command");
+            }
+            profile_env.map(Arc::new)
+        } else {
+            None
+        };
+        let version_control = VersionControl::detect(&cwd, git_state.as_ref());
+        let git_repo_warning = if config.skip_git_repo_check {
+            None
+        } else {
+            git_repo_warning_kind(version_control, &sandbox_policy)
+        };
+        if let Some(kind) = git_repo_warning {
+            post_session_configured_error_events.push(Event {
+                id: INITIAL_SUBMIT_ID.to_owned(),
+                msg: EventMsg::GitRepoWarning(GitRepoWarningEvent {
+                    cwd: cwd.clone(),
+                    kind,
+                    confirmation_required: approval_policy != AskForApproval::Never,
+                }),
+            });
+        }
 
         // Handle rollout result, which determines the session_id.
         struct RolloutResult {
             session_id: Uuid,
             rollout_recorder: Option<RolloutRecorder>,
             restored_items: Option<Vec<ResponseItem>>,
+            /// User instructions recorded by the resumed session, which take
+            /// precedence over the ones on disk now.
+            resumed_instructions: Option<Option<String>>,
         }
         let rollout_result = match rollout_res {
             Ok((session_id, maybe_saved, recorder)) => {
+                let resumed_instructions = maybe_saved
+                    .as_ref()
+                    .map(|saved_session| saved_session.session.instructions.clone());
                 let restored_items: Option<Vec<ResponseItem>> = initial_history.or_else(|| {
                     maybe_saved.and_then(|saved_session| {
-                        if saved_session.items.is_empty() {
-                            None
-                        } else {
-                            Some(saved_session.items)
-                        }
+                        let items = replayable_items(saved_session.items);
+                        if items.is_empty() { None } else { Some(items) }
                     })
                 });
                 RolloutResult {
                     session_id,
                     rollout_recorder: Some(recorder),
                     restored_items,
+                    resumed_instructions,
                 }
             }
             Err(e) => {
@@ -449,6 +839,7 @@ impl Session {
                     id: INITIAL_SUBMIT_ID.to_owned(),
                     msg: EventMsg::Error(ErrorEvent {
                         message: message.clone(),
+                        kind: None,
                     }),
                 });
                 warn!("{message}");
@@ -457,6 +848,7 @@ impl Session {
                     session_id: Uuid::new_v4(),
                     rollout_recorder: None,
                     restored_items: None,
+                    resumed_instructions: None,
                 }
             }
         };
@@ -465,14 +857,43 @@ impl Session {
             session_id,
             rollout_recorder,
             restored_items,
+            resumed_instructions,
         } = rollout_result;
+        // Only report the docs on disk when they are what the session uses.
+        let project_docs = if resumed_instructions.is_some() {
+            Vec::new()
+        } else {
+            if !truncated_project_docs.is_empty() {
+                post_session_configured_error_events.push(Event {
+                    id: INITIAL_SUBMIT_ID.to_owned(),
+                    msg: EventMsg::ProjectDocTruncated(ProjectDocTruncatedEvent {
+                        limit_bytes: config.project_doc_max_bytes,
+                        files: truncated_project_docs,
+                    }),
+                });
+            }
+            project_docs
+        };
+        let user_instructions = resumed_instructions.unwrap_or(user_instructions);
 
         // Create the mutable state for the Session.
         let mut state = State {
+            approved_commands: ApprovedCommands::new(
+                config.trusted_commands.clone(),
+                config.safe_commands.clone(),
+            ),
             history: ConversationHistory::new(),
             ..Default::default()
         };
+        // Resumed and forked sessions already told the model about their
+        // instructions and environment; remember what so only changes are
+        // sent again.
+        let mut recorded_instructions = None;
+        let mut recorded_environment_fields = HashMap::new();
         if let Some(restored_items) = restored_items {
+            recorded_instructions =
+                Prompt::last_user_instructions_message(&restored_items).cloned();
+            recorded_environment_fields = recorded_environment(&restored_items);
             state.history.record_items(&restored_items);
         }
 
@@ -484,22 +905,21 @@ impl Session {
                 error!("{message}");
                 post_session_configured_error_events.push(Event {
                     id: INITIAL_SUBMIT_ID.to_owned(),
-                    msg: EventMsg::Error(ErrorEvent { message }),
+                    msg: EventMsg::Error(ErrorEvent {
+                        message,
+                        kind: None,
+                    }),
                 });
                 (McpConnectionManager::default(), Default::default())
             }
         };
 
-        // Surface individual client start-up failures to the user.
-        if !failed_clients.is_empty() {
-            for (server_name, err) in failed_clients {
-                let message = format!("MCP client for `{server_name}` failed to start: {err:#}");
-                error!("{message}");
-                post_session_configured_error_events.push(Event {
-                    id: INITIAL_SUBMIT_ID.to_owned(),
-                    msg: EventMsg::Error(ErrorEvent { message }),
-                });
-            }
+        // Surface client start-up failures to the user in a single warning.
+        if let Some(msg) = mcp_startup_warning(failed_clients) {
+            post_session_configured_error_events.push(Event {
+                id: INITIAL_SUBMIT_ID.to_owned(),
+                msg,
+            });
         }
 
         // Now that `session_id` is final (may have been updated by resume),
@@ -521,42 +941,127 @@ impl Session {
                 include_plan_tool: config.include_plan_tool,
                 include_apply_patch_tool: config.include_apply_patch_tool,
                 include_web_search_request: config.tools_web_search_request,
+                web_search_context_size: config.tools_web_search_context_size,
                 use_streamable_shell_tool: config.use_experimental_streamable_shell_tool,
                 include_view_image_tool: config.include_view_image_tool,
             }),
             user_instructions,
+            project_docs: project_docs.clone(),
             base_instructions,
             approval_policy,
             sandbox_policy,
             shell_environment_policy: config.shell_environment_policy.clone(),
+            profile_env,
             cwd,
             disable_response_storage,
+            review_base: None,
+            final_output_json_schema: None,
+        };
+        let session_dir = config
+            .dirs
+            .state_dir()
+            .join(SESSIONS_SUBDIR)
+            .join(session_id.to_string());
+        let scratch_dir = match create_scratch_dir(config.dirs.cache_dir(), session_id) {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                warn!("failed to create scratch directory: {e}");
+                None
+            }
         };
+        if let (Some(rec), Some(dir)) = (rollout_recorder.as_ref(), scratch_dir.as_ref())
+            && let Err(e) = rec.record_scratch_dir(dir.clone()).await
+        {
+            error!("failed to record scratch directory: {e:#}");
+        }
+        let rollout_path = rollout_recorder
+            .as_ref()
+            .map(|recorder| recorder.path().to_path_buf());
         let sess = Arc::new(Session {
             session_id,
             tx_event: tx_event.clone(),
             mcp_connection_manager,
             session_manager: ExecSessionManager::default(),
-            notify,
+            notifier: UserNotifier::new(notify, config.notifications, config.notify_on_approval),
             state: Mutex::new(state),
             rollout: Mutex::new(rollout_recorder),
             codex_linux_sandbox_exe: config.codex_linux_sandbox_exe.clone(),
             user_shell: default_shell,
             show_raw_agent_reasoning: config.show_raw_agent_reasoning,
+            hide_environment_context: config.hide_environment_context,
+            auto_compact_threshold: config.auto_compact_threshold,
+            context_warning_thresholds: config.context_warning_thresholds.clone(),
+            max_parallel_tool_calls: config.max_parallel_tool_calls,
+            apply_patch_loosest_match: if config.apply_patch_exact_match {
+                MatchStrategy::Exact
+            } else {
+                MatchStrategy::Fuzzy
+            },
+            exec_output_limits: config.exec_output_limits,
+            tool_output_budget_bytes: config.tool_output_budget_bytes,
+            exec_timeout_ms: config.exec_timeout_ms,
+            exec_limits: config.exec_limits,
+            exec_use_pty: config.exec_use_pty,
+            exec_pty: config.exec_pty.clone(),
+            patch_undo: PatchUndoStack::new(session_dir.join("undo")),
+            exec_output_spool_dir: session_dir,
+            scratch_dir: scratch_dir.clone(),
+            retain_scratch_dir: config.retain_scratch_dir,
+            read_only_sandbox_allow_tmp: config.read_only_sandbox_allow_tmp,
+            keep_queued_input_on_interrupt: config.keep_queued_input_on_interrupt,
+            prompt_vars: config.prompt_vars.clone(),
+            strict_prompt_vars: config.strict_prompt_vars,
+            redactor: config.redactions.clone(),
+            network_proxy: Mutex::new(None),
+            turns_started: AtomicU64::new(0),
+            confirm_first_write: AtomicBool::new(git_repo_warning.is_some()),
+            otel_include_content: config.otel.include_content,
+            mcp_event_max_argument_bytes: config.mcp_event_max_argument_bytes,
+            command_tools: config.command_tools.clone(),
+            pull_request_tool: config.pull_request_tool.clone(),
         });
 
-        // record the initial user instructions and environment context,
-        // regardless of whether we restored items.
+        // record the initial user instructions and environment context. When
+        // resuming, identical instructions are not repeated and the
+        // environment context only lists what changed, e.g. a different cwd.
         let mut conversation_items = Vec::<ResponseItem>::with_capacity(2);
         if let Some(user_instructions) = turn_context.user_instructions.as_deref() {
-            conversation_items.push(Prompt::format_user_instructions_message(user_instructions));
-        }
-        conversation_items.push(ResponseItem::from(EnvironmentContext::new(
-            Some(turn_context.cwd.clone()),
-            Some(turn_context.approval_policy),
-            Some(turn_context.sandbox_policy.clone()),
-            Some(sess.user_shell.clone()),
-        )));
+            let message = Prompt::format_user_instructions_message(
+                user_instructions,
+                &sess.prompt_vars(&turn_context),
+            )?;
+            let message = sess.redactor.redact_item(message);
+            if recorded_instructions.as_ref() != Some(&message) {
+                conversation_items.push(message);
+            }
+        }
+        if !sess.hide_environment_context {
+            let environment_context = EnvironmentContext {
+                scratch_dir,
+                shell_profile: shell_profile_description(&sess.user_shell, &turn_context),
+                ..EnvironmentContext::new(
+                    Some(turn_context.cwd.clone()),
+                    Some(effective_approval_policy(
+                        turn_context.approval_policy,
+                        &turn_context.sandbox_policy,
+                        get_platform_sandbox(),
+                    )),
+                    Some(turn_context.sandbox_policy.clone()),
+                    Some(Platform::detect()),
+                    Some(sess.user_shell.clone()),
+                    Some(summarize_env(&config.shell_environment_policy)),
+                    git_state,
+                    Some(version_control),
+                )
+            };
+            let environment_context = sess
+                .redactor
+                .redact_item(ResponseItem::from(environment_context));
+            conversation_items.extend(environment_context_delta(
+                environment_context,
+                &recorded_environment_fields,
+            ));
+        }
         sess.record_conversation_items(&conversation_items).await;
 
         // Dispatch the SessionConfiguredEvent first and then report any errors.
@@ -567,6 +1072,8 @@ impl Session {
                 model,
                 history_log_id,
                 history_entry_count,
+                project_docs,
+                rollout_path,
             }),
         })
         .chain(post_session_configured_error_events.into_iter());
@@ -581,8 +1088,11 @@ impl Session {
 
     pub fn set_task(&self, task: AgentTask) {
         let mut state = self.state.lock_unchecked();
-        if let Some(current_task) = state.current_task.take() {
-            current_task.abort(TurnAbortReason::Replaced);
+        if let Some(current_task) = state.current_task.take()
+            && !current_task.handle.is_finished()
+        {
+            let partial_items = state.commit_partial_turn();
+            current_task.abort(TurnAbortReason::Replaced, partial_items);
         }
         state.current_task = Some(task);
     }
@@ -596,6 +1106,127 @@ impl Session {
         }
     }
 
+    /// Starts tracking the output of a new model response.
+    fn begin_partial_turn(&self) {
+        self.state.lock_unchecked().partial_turn = Some(PartialTurn::default());
+    }
+
+    /// Remembers a completed output item of the in-flight response. A
+    /// finished message supersedes the text streamed for it.
+    fn push_partial_item(&self, item: &ResponseItem) {
+        let mut state = self.state.lock_unchecked();
+        if let Some(partial) = state.partial_turn.as_mut() {
+            if matches!(item, ResponseItem::Message { .. }) {
+                partial.text.clear();
+            }
+            partial.items.push(item.clone());
+        }
+    }
+
+    fn push_partial_text(&self, delta: &str) {
+        let mut state = self.state.lock_unchecked();
+        if let Some(partial) = state.partial_turn.as_mut() {
+            partial.text.push_str(delta);
+        }
+    }
+
+    /// Stops tracking once the response's items have been recorded normally.
+    fn clear_partial_turn(&self) {
+        self.state.lock_unchecked().partial_turn = None;
+    }
+
+    /// Remembers the plan most recently published by the model.
+    pub(crate) fn set_plan(&self, plan: UpdatePlanArgs) {
+        self.state.lock_unchecked().plan = Some(plan);
+    }
+
+    /// Starts lazy MCP servers and lists the tools again for servers that
+    /// announced a change, warning about the servers that failed to start.
+    async fn refresh_mcp_tools(&self, sub_id: &str) {
+        let failures = self.mcp_connection_manager.refresh_tools().await;
+        if let Some(msg) = mcp_startup_warning(failures) {
+            self.send_event(Event {
+                id: sub_id.to_string(),
+                msg,
+            })
+            .await;
+        }
+    }
+
+    /// Snapshot of the session for `Op::GetStatus`. Lazy MCP servers are not
+    /// started for it.
+    fn status(&self, turn_context: &TurnContext, config: &Config) -> SessionStatus {
+        let client = &turn_context.client;
+        let model_family = client.get_model_family();
+        let provider = client.get_provider();
+        let tools_by_server = self.mcp_connection_manager.list_tools_by_server();
+        let mut mcp_servers: Vec<McpServerStatus> = config
+            .mcp_servers
+            .keys()
+            .map(|name| McpServerStatus {
+                name: name.clone(),
+                running: tools_by_server.contains_key(name),
+                tool_count: tools_by_server
+                    .get(name)
+                    .map_or(0, |server| server.tools.len()),
+            })
+            .collect();
+        mcp_servers.sort_by(|a, b| a.name.cmp(&b.name));
+        let (usage, context_usage) = {
+            let state = self.state.lock_unchecked();
+            (state.usage_update_event(), state.context_usage.clone())
+        };
+        SessionStatus {
+            session_id: self.session_id,
+            model: ModelStatus {
+                slug: client.get_model(),
+                family: model_family.family.clone(),
+                context_window: client.get_model_context_window(),
+                max_output_tokens: config.model_max_output_tokens,
+                supports_reasoning_summaries: model_family.supports_reasoning_summaries,
+                supports_image_input: model_family.supports_image_input,
+                uses_local_shell_tool: model_family.uses_local_shell_tool,
+            },
+            provider: ProviderStatus {
+                id: config.model_provider_id.clone(),
+                name: provider.name.clone(),
+                wire_api: match client.get_wire_api() {
+                    WireApi::Responses => "responses",
+                    WireApi::Chat => "chat",
+                    WireApi::Anthropic => "anthropic",
+                }
+                .to_string(),
+            },
+            reasoning_effort: client.get_reasoning_effort(),
+            reasoning_summary: client.get_reasoning_summary(),
+            approval_policy: turn_context.approval_policy,
+            sandbox_policy: turn_context.sandbox_policy.clone(),
+            writable_roots: turn_context
+                .sandbox_policy
+                .get_writable_roots_with_cwd(&turn_context.cwd)
+                .into_iter()
+                .map(|writable_root| writable_root.root)
+                .collect(),
+            scratch_dir: self.scratch_dir.clone(),
+            network_access: turn_context.sandbox_policy.has_full_network_access(),
+            cwd: turn_context.cwd.clone(),
+            instruction_files: turn_context.project_docs.clone(),
+            mcp_servers,
+            token_usage: usage.total_token_usage,
+            total_cost_usd: usage.total_cost_usd,
+            rollout_path: self
+                .rollout
+                .lock_unchecked()
+                .as_ref()
+                .map(|recorder| recorder.path().to_path_buf()),
+            context_usage,
+            auth_mode: client
+                .get_auth_manager()
+                .and_then(|manager| manager.auth())
+                .map(|auth| auth.mode),
+        }
+    }
+
     /// Sends the given event to the client and swallows the send event, if
     /// any, logging it as an error.
     pub(crate) async fn send_event(&self, event: Event) {
@@ -611,8 +1242,19 @@ impl Session {
         command: Vec<String>,
         cwd: PathBuf,
         reason: Option<String>,
-    ) -> oneshot::Receiver<ReviewDecision> {
+        escalation_reason: Option<EscalationReason>,
+    ) -> oneshot::Receiver<ApprovalResponse> {
         let (tx_approve, rx_approve) = oneshot::channel();
+        let session_prefix = session_approval_prefix(&command);
+        self.maybe_notify(UserNotification::ApprovalRequested {
+            turn_id: sub_id.clone(),
+            session_id: self.session_id,
+            cwd: cwd.clone(),
+            summary: format!("run `{}`", command.join(" ")),
+            reason: reason
+                .clone()
+                .or_else(|| escalation_reason.as_ref().map(ToString::to_string)),
+        });
         let event = Event {
             id: sub_id.clone(),
             msg: EventMsg::ExecApprovalRequest(ExecApprovalRequestEvent {
@@ -620,6 +1262,8 @@ impl Session {
                 command,
                 cwd,
                 reason,
+                escalation_reason,
+                session_prefix,
             }),
         };
         let _ = self.tx_event.send(event).await;
@@ -637,8 +1281,19 @@ impl Session {
         action: &ApplyPatchAction,
         reason: Option<String>,
         grant_root: Option<PathBuf>,
-    ) -> oneshot::Receiver<ReviewDecision> {
+    ) -> oneshot::Receiver<ApprovalResponse> {
         let (tx_approve, rx_approve) = oneshot::channel();
+        let file_count = action.changes().len();
+        self.maybe_notify(UserNotification::ApprovalRequested {
+            turn_id: sub_id.clone(),
+            session_id: self.session_id,
+            cwd: action.cwd.clone(),
+            summary: format!(
+                "apply a patch to {file_count} file{}",
+                if file_count == 1 { "" } else { "s" }
+            ),
+            reason: reason.clone(),
+        });
         let event = Event {
             id: sub_id.clone(),
             msg: EventMsg::ApplyPatchApprovalRequest(ApplyPatchApprovalRequestEvent {
@@ -656,14 +1311,14 @@ impl Session {
         rx_approve
     }
 
-    pub fn notify_approval(&self, sub_id: &str, decision: ReviewDecision) {
+    pub(crate) fn notify_approval(&self, sub_id: &str, response: ApprovalResponse) {
         let entry = {
             let mut state = self.state.lock_unchecked();
             state.pending_approvals.remove(sub_id)
         };
         match entry {
             Some(tx_approve) => {
-                tx_approve.send(decision).ok();
+                tx_approve.send(response).ok();
             }
             None => {
                 warn!("No pending approval found for sub_id: {sub_id}");
@@ -671,9 +1326,73 @@ impl Session {
         }
     }
 
+    /// Whether a command or patch that the policies would let run must be
+    /// confirmed first because the working directory is not a clean git
+    /// repository. Nothing can be confirmed under `AskForApproval::Never`,
+    /// and nothing can be written under a read-only sandbox.
+    /// Whether `command` is read-only under the built-in rules or the user's
+    /// `[[approvals.safe_commands]]`.
+    fn is_known_safe_command(&self, command: &[String]) -> bool {
+        self.state
+            .lock_unchecked()
+            .approved_commands
+            .is_known_safe(command)
+    }
+
+    pub(crate) fn needs_write_confirmation(&self, turn_context: &TurnContext) -> bool {
+        turn_context.approval_policy != AskForApproval::Never
+            && !matches!(turn_context.sandbox_policy, SandboxPolicy::ReadOnly)
+            && self
+                .confirm_first_write
+                .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Records that the user approved a write, lifting the confirmation
+    /// required by [`Session::needs_write_confirmation`].
+    pub(crate) fn write_confirmed(&self) {
+        self.confirm_first_write
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Exports the directories the sandbox keeps writable on top of
+    /// `sandbox_policy` in a command's `env`: the scratch directory, and the
+    /// command's temp directory when a read-only policy allows it.
+    fn export_sandbox_dirs(
+        &self,
+        env: &mut HashMap<String, String>,
+        sandbox_policy: &SandboxPolicy,
+    ) {
+        if let Some(scratch_dir) = &self.scratch_dir {
+            env.insert(
+                CODEX_SCRATCH_DIR_ENV_VAR.to_string(),
+                scratch_dir.to_string_lossy().into_owned(),
+            );
+        }
+        if self.read_only_sandbox_allow_tmp && matches!(sandbox_policy, SandboxPolicy::ReadOnly) {
+            let tmp_dir = env
+                .get("TMPDIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir);
+            env.insert(
+                CODEX_SANDBOX_TMPDIR_ENV_VAR.to_string(),
+                tmp_dir.to_string_lossy().into_owned(),
+            );
+        }
+    }
+
     pub fn add_approved_command(&self, cmd: Vec<String>) {
         let mut state = self.state.lock_unchecked();
-        state.approved_commands.insert(cmd);
+        state.approved_commands.approve_exact(cmd);
+    }
+
+    /// Lets commands that start like `cmd` run without asking for the rest of
+    /// the session, as long as the sandbox policy stays the same.
+    fn approve_command_for_session(&self, cmd: Vec<String>, sandbox_policy: &SandboxPolicy) {
+        let mut state = self.state.lock_unchecked();
+        state
+            .approved_commands
+            .approve_for_session(cmd, sandbox_policy);
     }
 
     /// Records items to both the rollout and the chat completions/ZDR
@@ -685,6 +1404,39 @@ impl Session {
         self.state.lock_unchecked().history.record_items(items);
     }
 
+    /// Notes in the rollout that the user let patch `call_id` write `paths`
+    /// outside the sandbox's writable roots.
+    pub(crate) async fn record_write_exemption(&self, call_id: &str, paths: &[PathBuf]) {
+        let recorder = {
+            let guard = self.rollout.lock_unchecked();
+            guard.as_ref().cloned()
+        };
+        if let Some(rec) = recorder
+            && let Err(e) = rec
+                .record_write_exemption(WriteExemption {
+                    call_id: call_id.to_string(),
+                    paths: paths.to_vec(),
+                })
+                .await
+        {
+            error!("failed to record write exemption: {e:#}");
+        }
+    }
+
+    /// Lets the rollout refer to an image read by `view_image` by its path
+    /// instead of storing a copy.
+    async fn record_viewed_image(&self, path: PathBuf, bytes: &[u8]) {
+        let recorder = {
+            let guard = self.rollout.lock_unchecked();
+            guard.as_ref().cloned()
+        };
+        if let Some(rec) = recorder
+            && let Err(e) = rec.record_viewed_image(path, bytes).await
+        {
+            error!("failed to record viewed image: {e:#}");
+        }
+    }
+
     async fn record_state_snapshot(&self, items: &[ResponseItem]) {
         let snapshot = { crate::rollout::SessionStateSnapshot {} };
 
@@ -719,6 +1471,7 @@ impl Session {
             Some(ApplyPatchCommandContext {
                 user_explicitly_approved_this_action,
                 changes,
+                ..
             }) => {
                 turn_diff_tracker.on_patch_begin(&changes);
 
@@ -751,7 +1504,7 @@ impl Session {
         sub_id: &str,
         call_id: &str,
         output: &ExecToolCallOutput,
-        is_apply_patch: bool,
+        apply_patch_match_strategies: Option<HashMap<PathBuf, PatchMatchStrategy>>,
     ) {
         let ExecToolCallOutput {
             stdout,
@@ -759,19 +1512,23 @@ impl Session {
             aggregated_output,
             duration,
             exit_code,
+            terminated,
+            ..
         } = output;
         // Send full stdout/stderr to clients; do not truncate.
         let stdout = stdout.text.clone();
         let stderr = stderr.text.clone();
-        let formatted_output = format_exec_output_str(output);
+        let formatted_output = format_exec_output_str(output, self.exec_output_limits);
         let aggregated_output: String = aggregated_output.text.clone();
 
-        let msg = if is_apply_patch {
+        let is_apply_patch = apply_patch_match_strategies.is_some();
+        let msg = if let Some(match_strategies) = apply_patch_match_strategies {
             EventMsg::PatchApplyEnd(PatchApplyEndEvent {
                 call_id: call_id.to_string(),
                 stdout,
                 stderr,
                 success: *exit_code == 0,
+                match_strategies,
             })
         } else {
             EventMsg::ExecCommandEnd(ExecCommandEndEvent {
@@ -782,6 +1539,7 @@ impl Session {
                 exit_code: *exit_code,
                 duration: *duration,
                 formatted_output,
+                terminated: *terminated,
             })
         };
 
@@ -805,6 +1563,26 @@ impl Session {
             }
         }
     }
+
+    /// Environment variables that route a sandboxed command through the proxy
+    /// enforcing `allowed_domains`. The proxy is (re)started when the
+    /// allowlist differs from the one it was started with.
+    fn network_proxy_env(
+        &self,
+        allowed_domains: &[String],
+    ) -> std::io::Result<Vec<(String, String)>> {
+        let mut network_proxy = self.network_proxy.lock_unchecked();
+        if let Some(proxy) = network_proxy.as_ref()
+            && proxy.allowed_domains() == allowed_domains
+        {
+            return Ok(proxy.env_vars());
+        }
+        let proxy = NetworkProxy::start(allowed_domains.to_vec())?;
+        let env = proxy.env_vars();
+        *network_proxy = Some(proxy);
+        Ok(env)
+    }
+
     /// Runs the exec tool call and emits events for the begin and end of the
     /// command even on error.
     ///
@@ -813,23 +1591,52 @@ impl Session {
         &self,
         turn_diff_tracker: &mut TurnDiffTracker,
         begin_ctx: ExecCommandContext,
-        exec_args: ExecInvokeArgs<'a>,
+        mut exec_args: ExecInvokeArgs<'a>,
     ) -> crate::error::Result<ExecToolCallOutput> {
-        let is_apply_patch = begin_ctx.apply_patch.is_some();
+        let apply_patch_match_strategies = begin_ctx
+            .apply_patch
+            .as_ref()
+            .map(|ctx| ctx.match_strategies.clone());
+
+        // Without the proxy variables the sandbox blocks the network entirely.
+        if exec_args.sandbox_type != SandboxType::None
+            && let Some(allowed_domains) = exec_args.sandbox_policy.network_allowlist()
+        {
+            match self.network_proxy_env(allowed_domains) {
+                Ok(env) => exec_args.params.env.extend(env),
+                Err(e) => error!("failed to start the sandbox network proxy: {e:#}"),
+            }
+        }
         let sub_id = begin_ctx.sub_id.clone();
         let call_id = begin_ctx.call_id.clone();
 
+        let patch_snapshot = begin_ctx
+            .apply_patch
+            .as_ref()
+            .map(|ctx| PatchSnapshot::capture(&ctx.changes));
+
         self.on_exec_command_begin(turn_diff_tracker, begin_ctx.clone())
             .await;
 
-        let result = process_exec_tool_call(
+        let exec_span = otel::exec_span(&exec_args.params.command, self.otel_include_content);
+        let mut result = process_exec_tool_call(
             exec_args.params,
             exec_args.sandbox_type,
             exec_args.sandbox_policy,
             exec_args.codex_linux_sandbox_exe,
             exec_args.stdout_stream,
         )
+        .instrument(exec_span.clone())
         .await;
+        if let Ok(output) = &mut result {
+            self.spool_exec_output(&call_id, output).await;
+            if let Some(snapshot) = patch_snapshot
+                && output.exit_code == 0
+                && let Err(e) = self.patch_undo.push(&call_id, snapshot)
+            {
+                warn!("failed to record patch {call_id} for undo: {e}");
+            }
+        }
 
         let output_stderr;
         let borrowed: &ExecToolCallOutput = match &result {
@@ -841,22 +1648,64 @@ impl Session {
                     stderr: StreamOutput::new(get_error_message_ui(e)),
                     aggregated_output: StreamOutput::new(get_error_message_ui(e)),
                     duration: Duration::default(),
+                    full_output_path: None,
+                    terminated: None,
                 };
                 &output_stderr
             }
         };
+        otel::record_exec_result(&exec_span, borrowed, self.otel_include_content);
         self.on_exec_command_end(
             turn_diff_tracker,
             &sub_id,
             &call_id,
             borrowed,
-            is_apply_patch,
+            apply_patch_match_strategies,
         )
         .await;
 
         result
     }
 
+    /// Writes the full output of `call_id` under the session's spool directory
+    /// when it is too long to show the model, and records where it went.
+    async fn spool_exec_output(&self, call_id: &str, output: &mut ExecToolCallOutput) {
+        let text = &output.aggregated_output.text;
+        if !exceeds_exec_output_limits(text, self.exec_output_limits) {
+            return;
+        }
+        let file_name: String = call_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = self.exec_output_spool_dir.join(format!("{file_name}.log"));
+        let written = match tokio::fs::create_dir_all(&self.exec_output_spool_dir).await {
+            Ok(()) => tokio::fs::write(&path, text).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => output.full_output_path = Some(path),
+            Err(e) => warn!("failed to write full command output to {path:?}: {e}"),
+        }
+    }
+
+    /// Values for the instruction placeholders of a turn run in
+    /// `turn_context`.
+    fn prompt_vars(&self, turn_context: &TurnContext) -> PromptVars {
+        PromptVars::new(
+            &turn_context.cwd,
+            &turn_context.client.get_model(),
+            &self.prompt_vars,
+            self.strict_prompt_vars,
+        )
+    }
+
     /// Helper that emits a BackgroundEvent with the given message. This keeps
     /// the call‑sites terse so adding more diagnostics does not clutter the
     /// core agent logic.
@@ -870,6 +1719,82 @@ impl Session {
         let _ = self.tx_event.send(event).await;
     }
 
+    /// Keeps the latest rate limit snapshot and reports it.
+    async fn update_rate_limits(&self, sub_id: &str, snapshot: RateLimitSnapshot) {
+        self.state.lock_unchecked().rate_limits = Some(snapshot.clone());
+        let event = Event {
+            id: sub_id.to_string(),
+            msg: EventMsg::RateLimits(snapshot),
+        };
+        let _ = self.tx_event.send(event).await;
+    }
+
+    /// Records the latest cumulative usage of the in-flight model response
+    /// and reports the session's running totals.
+    async fn update_token_usage(&self, sub_id: &str, usage: TokenUsage) {
+        let event = {
+            let mut state = self.state.lock_unchecked();
+            state.pending_token_usage = Some(usage);
+            state.usage_update_event()
+        };
+        self.send_usage_update(sub_id, event).await;
+    }
+
+    /// Folds the usage of the model response that just completed into the
+    /// session totals, priced for the turn's model, and notes it in the
+    /// rollout. `final_usage`, when reported, supersedes any earlier update
+    /// for the same response.
+    async fn commit_token_usage(
+        &self,
+        sub_id: &str,
+        turn_context: &TurnContext,
+        final_usage: Option<TokenUsage>,
+    ) {
+        let pricing = turn_context.client.get_model_pricing();
+        let (event, usage_record) = {
+            let mut state = self.state.lock_unchecked();
+            let usage_record = match final_usage.or_else(|| state.pending_token_usage.take()) {
+                Some(usage) => {
+                    let cost_usd = pricing.map(|pricing| pricing.cost(&usage));
+                    match cost_usd {
+                        Some(cost) => state.total_cost_usd += cost,
+                        None => state.has_unpriced_usage = true,
+                    }
+                    state.last_cost_usd = cost_usd;
+                    state.total_token_usage = state.total_token_usage.add_usage(&usage);
+                    state.last_token_usage = usage.clone();
+                    Some(UsageRecord {
+                        model: turn_context.client.get_model(),
+                        token_usage: usage,
+                        cost_usd,
+                    })
+                }
+                None => None,
+            };
+            state.pending_token_usage = None;
+            (state.usage_update_event(), usage_record)
+        };
+        self.send_usage_update(sub_id, event).await;
+
+        let recorder = {
+            let guard = self.rollout.lock_unchecked();
+            guard.as_ref().cloned()
+        };
+        if let (Some(rec), Some(usage_record)) = (recorder, usage_record)
+            && let Err(e) = rec.record_usage(usage_record).await
+        {
+            error!("failed to record token usage: {e:#}");
+        }
+    }
+
+    async fn send_usage_update(&self, sub_id: &str, event: UsageUpdateEvent) {
+        let event = Event {
+            id: sub_id.to_string(),
+            msg: EventMsg::UsageUpdate(event),
+        };
+        let _ = self.tx_event.send(event).await;
+    }
+
     async fn notify_stream_error(&self, sub_id: &str, message: impl Into<String>) {
         let event = Event {
             id: sub_id.to_string(),
@@ -881,9 +1806,12 @@ impl Session {
     }
 
     /// Build the full turn input by concatenating the current conversation
-    /// history with additional items for this turn.
-    pub fn turn_input_with_history(&self, extra: Vec<ResponseItem>) -> Vec<ResponseItem> {
-        [self.state.lock_unchecked().history.contents(), extra].concat()
+    /// history with additional items for this turn. The history items are
+    /// shared, not copied.
+    pub fn turn_input_with_history(&self, extra: Vec<ResponseItem>) -> Vec<Arc<ResponseItem>> {
+        let mut input = self.state.lock_unchecked().history.shared_contents();
+        input.extend(extra.into_iter().map(Arc::new));
+        input
     }
 
     /// Returns the input if there was no task running to inject into
@@ -897,67 +1825,334 @@ impl Session {
         }
     }
 
-    pub fn get_pending_input(&self) -> Vec<ResponseInputItem> {
-        let mut state = self.state.lock_unchecked();
-        if state.pending_input.is_empty() {
-            Vec::with_capacity(0)
-        } else {
-            let mut ret = Vec::new();
-            std::mem::swap(&mut ret, &mut state.pending_input);
-            ret
+    /// Adds `entry` to the queued input and starts it right away if no task
+    /// is running.
+    async fn queue_input(self: &Arc<Self>, entry: QueuedInput, turn_context: Arc<TurnContext>) {
+        let id = entry.id.clone();
+        let queue = {
+            let mut state = self.state.lock_unchecked();
+            state.queued_input.push(entry);
+            state.queued_turn_context = Some(turn_context);
+            if state.current_task.is_none() {
+                None
+            } else {
+                Some(state.queued_input.clone())
+            }
+        };
+        match queue {
+            Some(queue) => {
+                self.send_queued_input_update(
+                    id,
+                    QueuedInputEvent {
+                        queue,
+                        started: Vec::new(),
+                        cleared: Vec::new(),
+                    },
+                )
+                .await
+            }
+            None => self.start_queued_input().await,
         }
     }
 
-    pub async fn call_tool(
-        &self,
-        server: &str,
-        tool: &str,
-        arguments: Option<serde_json::Value>,
-        timeout: Option<Duration>,
-    ) -> anyhow::Result<CallToolResult> {
-        self.mcp_connection_manager
-            .call_tool(server, tool, arguments, timeout)
-            .await
+    /// Starts a task with all of the queued input, concatenated in order,
+    /// unless a task is already running or nothing is queued. The task takes
+    /// the id of the oldest entry.
+    async fn start_queued_input(self: &Arc<Self>) {
+        let started = {
+            let mut state = self.state.lock_unchecked();
+            if state.current_task.is_some() || state.queued_input.is_empty() {
+                return;
+            }
+            let Some(turn_context) = state.queued_turn_context.take() else {
+                return;
+            };
+            let started = std::mem::take(&mut state.queued_input);
+            let items = started
+                .iter()
+                .flat_map(|entry| entry.items.iter().cloned())
+                .collect();
+            let sub_id = started[0].id.clone();
+            state.current_task = Some(AgentTask::spawn(
+                Arc::clone(self),
+                turn_context,
+                sub_id,
+                items,
+            ));
+            started
+        };
+        self.send_queued_input_update(
+            started[0].id.clone(),
+            QueuedInputEvent {
+                queue: Vec::new(),
+                started,
+                cleared: Vec::new(),
+            },
+        )
+        .await;
     }
 
-    fn interrupt_task(&self) {
-        info!("interrupt received: abort current task, if any");
-        let mut state = self.state.lock_unchecked();
-        state.pending_approvals.clear();
-        state.pending_input.clear();
-        if let Some(task) = state.current_task.take() {
-            task.abort(TurnAbortReason::Interrupted);
+    /// Puts the input kept across an interrupt in front of `items`, for
+    /// input that is about to start a task.
+    async fn prepend_queued_input(&self, sub_id: &str, items: Vec<InputItem>) -> Vec<InputItem> {
+        let started = {
+            let mut state = self.state.lock_unchecked();
+            state.queued_turn_context = None;
+            std::mem::take(&mut state.queued_input)
+        };
+        if started.is_empty() {
+            return items;
         }
+        let mut queued: Vec<InputItem> = started
+            .iter()
+            .flat_map(|entry| entry.items.iter().cloned())
+            .collect();
+        queued.extend(items);
+        self.send_queued_input_update(
+            sub_id.to_string(),
+            QueuedInputEvent {
+                queue: Vec::new(),
+                started,
+                cleared: Vec::new(),
+            },
+        )
+        .await;
+        queued
     }
 
-    /// Spawn the configured notifier (if any) with the given JSON payload as
-    /// the last argument. Failures are logged but otherwise ignored so that
-    /// notification issues do not interfere with the main workflow.
-    fn maybe_notify(&self, notification: UserNotification) {
-        let Some(notify_command) = &self.notify else {
-            return;
+    /// Takes the entry submitted as `id` out of the queue.
+    async fn remove_queued_input(&self, sub_id: String, id: &str) {
+        let queue = {
+            let mut state = self.state.lock_unchecked();
+            state.queued_input.retain(|entry| entry.id != id);
+            if state.queued_input.is_empty() {
+                state.queued_turn_context = None;
+            }
+            state.queued_input.clone()
         };
+        self.send_queued_input_update(
+            sub_id,
+            QueuedInputEvent {
+                queue,
+                started: Vec::new(),
+                cleared: Vec::new(),
+            },
+        )
+        .await;
+    }
 
-        if notify_command.is_empty() {
+    /// Queued input is sent with the turn context in effect when it starts,
+    /// so later overrides apply to it as well.
+    fn update_queued_turn_context(&self, turn_context: &Arc<TurnContext>) {
+        let mut state = self.state.lock_unchecked();
+        if !state.queued_input.is_empty() {
+            state.queued_turn_context = Some(Arc::clone(turn_context));
+        }
+    }
+
+    fn queued_input(&self) -> Vec<QueuedInput> {
+        self.state.lock_unchecked().queued_input.clone()
+    }
+
+    async fn send_queued_input_update(&self, id: String, update: QueuedInputEvent) {
+        self.send_event(Event {
+            id,
+            msg: EventMsg::QueuedInputUpdated(update),
+        })
+        .await;
+    }
+
+    /// Aborts the running task for `Op::Interrupt` and drops the queued
+    /// input unless `keep_queued_input_on_interrupt` is set.
+    async fn interrupt(&self, sub_id: String) {
+        self.interrupt_task();
+        if self.keep_queued_input_on_interrupt {
             return;
         }
+        let cleared = {
+            let mut state = self.state.lock_unchecked();
+            state.queued_turn_context = None;
+            std::mem::take(&mut state.queued_input)
+        };
+        if !cleared.is_empty() {
+            self.send_queued_input_update(
+                sub_id,
+                QueuedInputEvent {
+                    queue: Vec::new(),
+                    started: Vec::new(),
+                    cleared,
+                },
+            )
+            .await;
+        }
+    }
+
+    fn has_running_task(&self) -> bool {
+        self.state.lock_unchecked().current_task.is_some()
+    }
+
+    /// Asks the running task to compact the history before its next request.
+    /// Returns `false` if no task is running.
+    fn request_compaction(&self) -> bool {
+        let mut state = self.state.lock_unchecked();
+        if state.current_task.is_some() {
+            state.compact_requested = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_compaction_request(&self) -> bool {
+        std::mem::take(&mut self.state.lock_unchecked().compact_requested)
+    }
+
+    /// Remembers the prompt size of the response that just completed. Without
+    /// reported usage the next projection falls back to an estimate of the
+    /// whole history.
+    fn record_prompt_tokens(&self, usage: Option<&TokenUsage>) {
+        let mut state = self.state.lock_unchecked();
+        state.last_prompt_tokens = usage.map(|usage| PromptTokens {
+            input_tokens: usage.input_tokens,
+            history_len: state.history.len(),
+        });
+    }
+
+    /// Projected number of input tokens of a request built from the current
+    /// history. Without reported usage the estimate adds the instructions and
+    /// tool schemas of the latest prompt, which are not part of the history.
+    fn projected_prompt_tokens(&self, encoding: Encoding) -> u64 {
+        let state = self.state.lock_unchecked();
+        let items = state.history.items();
+        match state.last_prompt_tokens {
+            Some(PromptTokens {
+                input_tokens,
+                history_len,
+            }) if history_len <= items.len() => {
+                input_tokens + estimate_items_tokens(&items[history_len..], encoding)
+            }
+            _ => {
+                let overhead = state.context_breakdown.map_or(0, |breakdown| {
+                    breakdown.instructions_tokens + breakdown.tool_schema_tokens
+                });
+                overhead + estimate_items_tokens(items, encoding)
+            }
+        }
+    }
+
+    fn record_context_breakdown(&self, breakdown: ContextBreakdown) {
+        self.state.lock_unchecked().context_breakdown = Some(breakdown);
+    }
 
-        let Ok(json) = serde_json::to_string(&notification) else {
-            error!("failed to serialise notification payload");
+    /// Tells the client how full the context window will be for the next
+    /// request, and warns when usage crossed one of the
+    /// `context_warning_thresholds` since the last report.
+    async fn report_context_usage(&self, turn_context: &TurnContext, sub_id: &str) {
+        let Some(context_window) = turn_context
+            .client
+            .get_model_context_window()
+            .filter(|context_window| *context_window > 0)
+        else {
             return;
         };
+        let encoding = Encoding::for_model_family(&turn_context.client.get_model_family());
+        let tokens_in_context = self.projected_prompt_tokens(encoding);
+        let used = tokens_in_context as f64 / context_window as f64;
+        let percent_left = ((1.0 - used) * 100.0).clamp(0.0, 100.0) as u8;
+        let crossed = self
+            .context_warning_thresholds
+            .iter()
+            .copied()
+            .rfind(|threshold| used >= *threshold);
+        let (usage, warning) = {
+            let mut state = self.state.lock_unchecked();
+            let warning = match (crossed, state.warned_context_threshold) {
+                (Some(threshold), Some(warned)) if threshold <= warned => None,
+                (Some(threshold), _) => Some(ContextWindowWarningEvent {
+                    threshold_percent: (threshold * 100.0).round() as u8,
+                    percent_left,
+                }),
+                (None, _) => None,
+            };
+            state.warned_context_threshold = crossed;
+            let usage = ContextUsageEvent {
+                tokens_in_context,
+                context_window,
+                percent_left,
+                breakdown: state.context_breakdown.unwrap_or_default(),
+            };
+            state.context_usage = Some(usage.clone());
+            (usage, warning)
+        };
+        self.send_event(Event {
+            id: sub_id.to_string(),
+            msg: EventMsg::ContextUsage(usage),
+        })
+        .await;
+        if let Some(warning) = warning {
+            self.send_event(Event {
+                id: sub_id.to_string(),
+                msg: EventMsg::ContextWindowWarning(warning),
+            })
+            .await;
+        }
+    }
 
-        let mut command = std::process::Command::new(&notify_command[0]);
-        if notify_command.len() > 1 {
-            command.args(&notify_command[1..]);
+    pub fn get_pending_input(&self) -> Vec<ResponseInputItem> {
+        let mut state = self.state.lock_unchecked();
+        if state.pending_input.is_empty() {
+            Vec::with_capacity(0)
+        } else {
+            let mut ret = Vec::new();
+            std::mem::swap(&mut ret, &mut state.pending_input);
+            ret
         }
-        command.arg(json);
+    }
 
-        // Fire-and-forget – we do not wait for completion.
-        if let Err(e) = command.spawn() {
-            warn!("failed to spawn notifier '{}': {e}", notify_command[0]);
+    pub async fn call_tool(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: Option<serde_json::Value>,
+        timeout: Option<Duration>,
+        progress: mpsc::UnboundedSender<ProgressNotificationParams>,
+    ) -> anyhow::Result<CallToolResult> {
+        self.mcp_connection_manager
+            .call_tool(server, tool, arguments, timeout, progress)
+            .await
+    }
+
+    pub(crate) fn otel_include_content(&self) -> bool {
+        self.otel_include_content
+    }
+
+    pub(crate) fn mcp_event_max_argument_bytes(&self) -> usize {
+        self.mcp_event_max_argument_bytes
+    }
+
+    pub(crate) fn apply_patch_loosest_match(&self) -> MatchStrategy {
+        self.apply_patch_loosest_match
+    }
+
+    fn interrupt_task(&self) {
+        info!("interrupt received: abort current task, if any");
+        let mut state = self.state.lock_unchecked();
+        state.pending_approvals.clear();
+        state.pending_input.clear();
+        if let Some(task) = state.current_task.take()
+            && !task.handle.is_finished()
+        {
+            let partial_items = state.commit_partial_turn();
+            task.abort(TurnAbortReason::Interrupted, partial_items);
         }
     }
+
+    /// Hand `notification` to the configured notifier, if any. Failures are
+    /// logged but otherwise ignored so that notification issues do not
+    /// interfere with the main workflow.
+    fn maybe_notify(&self, notification: UserNotification) {
+        self.notifier.notify(&notification);
+    }
 }
 
 impl Drop for Session {
@@ -975,10 +2170,21 @@ pub(crate) struct ExecCommandContext {
     pub(crate) apply_patch: Option<ApplyPatchCommandContext>,
 }
 
+impl ExecCommandContext {
+    fn rejection_note(&self) -> Option<&str> {
+        self.apply_patch
+            .as_ref()
+            .and_then(|apply_patch| apply_patch.rejection_note.as_deref())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct ApplyPatchCommandContext {
     pub(crate) user_explicitly_approved_this_action: bool,
     pub(crate) changes: HashMap<PathBuf, FileChange>,
+    pub(crate) match_strategies: HashMap<PathBuf, PatchMatchStrategy>,
+    /// Which parts of the patch the user rejected, for the model.
+    pub(crate) rejection_note: Option<String>,
 }
 
 /// A series of Turns in response to user input.
@@ -999,8 +2205,11 @@ impl AgentTask {
             let sess = sess.clone();
             let sub_id = sub_id.clone();
             let tc = Arc::clone(&turn_context);
-            tokio::spawn(async move { run_task(sess, tc.as_ref(), sub_id, input).await })
-                .abort_handle()
+            tokio::spawn(async move {
+                run_task(Arc::clone(&sess), tc.as_ref(), sub_id, input).await;
+                sess.start_queued_input().await;
+            })
+            .abort_handle()
         };
         Self {
             sess,
@@ -1009,19 +2218,14 @@ impl AgentTask {
         }
     }
 
-    fn compact(
-        sess: Arc<Session>,
-        turn_context: Arc<TurnContext>,
-        sub_id: String,
-        input: Vec<InputItem>,
-        compact_instructions: String,
-    ) -> Self {
+    fn compact(sess: Arc<Session>, turn_context: Arc<TurnContext>, sub_id: String) -> Self {
         let handle = {
             let sess = sess.clone();
             let sub_id = sub_id.clone();
             let tc = Arc::clone(&turn_context);
             tokio::spawn(async move {
-                run_compact_task(sess, tc.as_ref(), sub_id, input, compact_instructions).await
+                run_compact_task(Arc::clone(&sess), tc.as_ref(), sub_id).await;
+                sess.start_queued_input().await;
             })
             .abort_handle()
         };
@@ -1032,7 +2236,10 @@ impl AgentTask {
         }
     }
 
-    fn abort(self, reason: TurnAbortReason) {
+    /// Aborts the task, killing any exec children it spawned, and writes
+    /// `partial_items` (already committed to the history) to the rollout
+    /// before announcing the aborted turn.
+    fn abort(self, reason: TurnAbortReason, partial_items: Vec<ResponseItem>) {
         // TOCTOU?
         if !self.handle.is_finished() {
             self.handle.abort();
@@ -1040,14 +2247,86 @@ impl AgentTask {
                 id: self.sub_id,
                 msg: EventMsg::TurnAborted(TurnAbortedEvent { reason }),
             };
-            let tx_event = self.sess.tx_event.clone();
+            let sess = self.sess;
             tokio::spawn(async move {
-                tx_event.send(event).await.ok();
+                if !partial_items.is_empty() {
+                    sess.record_state_snapshot(&partial_items).await;
+                }
+                sess.tx_event.send(event).await.ok();
             });
         }
     }
 }
 
+/// Turn context for an `Op::Review` task: the session's model, cwd and
+/// instructions, but a read-only sandbox that never escalates, the review
+/// prompt as base instructions and no tools that edit files.
+fn review_turn_context(turn_context: &TurnContext, config: &Config, base: String) -> TurnContext {
+    let approval_policy = AskForApproval::Never;
+    let sandbox_policy = SandboxPolicy::ReadOnly;
+    let mut tools_config = ToolsConfig::new(&ToolsConfigParams {
+        model_family: &turn_context.client.get_model_family(),
+        approval_policy,
+        sandbox_policy: sandbox_policy.clone(),
+        include_plan_tool: false,
+        include_apply_patch_tool: false,
+        include_web_search_request: config.tools_web_search_request,
+        web_search_context_size: config.tools_web_search_context_size,
+        use_streamable_shell_tool: config.use_experimental_streamable_shell_tool,
+        include_view_image_tool: config.include_view_image_tool,
+    });
+    // Some model families always get the tool; a review has no use for it.
+    tools_config.apply_patch_tool_type = None;
+    TurnContext {
+        client: turn_context.client.clone(),
+        cwd: turn_context.cwd.clone(),
+        base_instructions: Some(REVIEW_PROMPT.to_string()),
+        user_instructions: turn_context.user_instructions.clone(),
+        project_docs: turn_context.project_docs.clone(),
+        approval_policy,
+        sandbox_policy,
+        shell_environment_policy: turn_context.shell_environment_policy.clone(),
+        profile_env: turn_context.profile_env.clone(),
+        disable_response_storage: turn_context.disable_response_storage,
+        tools_config,
+        review_base: Some(base),
+        final_output_json_schema: None,
+    }
+}
+
+/// Why the session's working directory deserves a warning before Codex
+/// changes anything in it, if it does.
+fn git_repo_warning_kind(
+    version_control: VersionControl,
+    sandbox_policy: &SandboxPolicy,
+) -> Option<GitRepoWarningKind> {
+    match version_control {
+        VersionControl::None => Some(GitRepoWarningKind::NotARepo),
+        VersionControl::Git {
+            is_dirty: Some(true),
+        } if !matches!(sandbox_policy, SandboxPolicy::ReadOnly) => Some(GitRepoWarningKind::Dirty),
+        VersionControl::Git { .. } => None,
+    }
+}
+
+/// Builds the warning for MCP servers that failed to start, if any did.
+fn mcp_startup_warning(failures: ClientStartErrors) -> Option<EventMsg> {
+    if failures.is_empty() {
+        return None;
+    }
+    let failures = failures
+        .into_iter()
+        .map(|(server_name, err)| {
+            let message = format!("{err:#}");
+            warn!("MCP server `{server_name}` failed to start: {message}");
+            (server_name, message)
+        })
+        .collect();
+    Some(EventMsg::McpStartupWarning(McpStartupWarningEvent {
+        failures,
+    }))
+}
+
 async fn submission_loop(
     sess: Arc<Session>,
     turn_context: TurnContext,
@@ -1061,7 +2340,7 @@ async fn submission_loop(
         debug!(?sub, "Submission");
         match sub.op {
             Op::Interrupt => {
-                sess.interrupt_task();
+                sess.interrupt(sub.id).await;
             }
             Op::OverrideTurnContext {
                 cwd,
@@ -1077,8 +2356,8 @@ async fn submission_loop(
 
                 // Effective model + family
                 let (effective_model, effective_family) = if let Some(m) = model {
-                    let fam =
-                        find_family_for_model(&m).unwrap_or_else(|| config.model_family.clone());
+                    let fam = find_family_for_model_with_overrides(&m, &config.model_families)
+                        .unwrap_or_else(|| config.model_family.clone());
                     (m, fam)
                 } else {
                     (prev.client.get_model(), prev.client.get_model_family())
@@ -1094,8 +2373,10 @@ async fn submission_loop(
                 let mut updated_config = (*config).clone();
                 updated_config.model = effective_model.clone();
                 updated_config.model_family = effective_family.clone();
-                if let Some(model_info) = get_model_info(&effective_family) {
-                    updated_config.model_context_window = Some(model_info.context_window);
+                if let Some(context_window) =
+                    get_model_context_window(&effective_family, &config.model_families)
+                {
+                    updated_config.model_context_window = Some(context_window);
                 }
 
                 let client = ModelClient::new(
@@ -1112,6 +2393,34 @@ async fn submission_loop(
                     .clone()
                     .unwrap_or(prev.sandbox_policy.clone());
                 let new_cwd = cwd.clone().unwrap_or_else(|| prev.cwd.clone());
+                // A different cwd may sit under different AGENTS.md files.
+                let (new_user_instructions, new_project_docs) = match cwd.as_deref() {
+                    Some(cwd) => {
+                        let UserInstructions {
+                            text,
+                            project_docs,
+                            truncated,
+                        } = load_user_instructions(
+                            cwd,
+                            config.project_doc_max_bytes,
+                            config.user_instructions.as_deref(),
+                        )
+                        .await;
+                        // Too late to refuse to start, even when strict.
+                        if !truncated.is_empty() {
+                            sess.send_event(Event {
+                                id: sub.id.clone(),
+                                msg: EventMsg::ProjectDocTruncated(ProjectDocTruncatedEvent {
+                                    limit_bytes: config.project_doc_max_bytes,
+                                    files: truncated,
+                                }),
+                            })
+                            .await;
+                        }
+                        (text, project_docs)
+                    }
+                    None => (prev.user_instructions.clone(), prev.project_docs.clone()),
+                };
 
                 let tools_config = ToolsConfig::new(&ToolsConfigParams {
                     model_family: &effective_family,
@@ -1120,6 +2429,7 @@ async fn submission_loop(
                     include_plan_tool: config.include_plan_tool,
                     include_apply_patch_tool: config.include_apply_patch_tool,
                     include_web_search_request: config.tools_web_search_request,
+                    web_search_context_size: config.tools_web_search_context_size,
                     use_streamable_shell_tool: config.use_experimental_streamable_shell_tool,
                     include_view_image_tool: config.include_view_image_tool,
                 });
@@ -1127,32 +2437,97 @@ async fn submission_loop(
                 let new_turn_context = TurnContext {
                     client,
                     tools_config,
-                    user_instructions: prev.user_instructions.clone(),
+                    user_instructions: new_user_instructions.clone(),
+                    project_docs: new_project_docs,
                     base_instructions: prev.base_instructions.clone(),
                     approval_policy: new_approval_policy,
                     sandbox_policy: new_sandbox_policy.clone(),
                     shell_environment_policy: prev.shell_environment_policy.clone(),
+                    profile_env: prev.profile_env.clone(),
                     cwd: new_cwd.clone(),
                     disable_response_storage: prev.disable_response_storage,
+                    review_base: None,
+                    final_output_json_schema: None,
                 };
 
                 // Install the new persistent context for subsequent tasks/turns.
                 turn_context = Arc::new(new_turn_context);
-                if cwd.is_some() || approval_policy.is_some() || sandbox_policy.is_some() {
-                    sess.record_conversation_items(&[ResponseItem::from(EnvironmentContext::new(
-                        cwd,
-                        approval_policy,
-                        sandbox_policy,
-                        // Shell is not configurable from turn to turn
-                        None,
-                    ))])
-                    .await;
+                sess.update_queued_turn_context(&turn_context);
+                if new_user_instructions != prev.user_instructions
+                    && let Some(user_instructions) = new_user_instructions.as_deref()
+                {
+                    match Prompt::format_user_instructions_message(
+                        user_instructions,
+                        &sess.prompt_vars(&turn_context),
+                    ) {
+                        Ok(message) => {
+                            let message = sess.redactor.redact_item(message);
+                            sess.record_conversation_items(&[message]).await;
+                        }
+                        Err(e) => {
+                            sess.send_event(Event {
+                                id: sub.id.clone(),
+                                msg: EventMsg::Error(ErrorEvent {
+                                    message: e.to_string(),
+                                    kind: None,
+                                }),
+                            })
+                            .await;
+                        }
+                    }
+                }
+                if !sess.hide_environment_context
+                    && (cwd.is_some() || approval_policy.is_some() || sandbox_policy.is_some())
+                {
+                    // Only re-read the repository state when the cwd moved.
+                    let (git_state, version_control) = match cwd.as_deref() {
+                        Some(cwd) => {
+                            let git_state = collect_git_head_state(cwd).await;
+                            let version_control = VersionControl::detect(cwd, git_state.as_ref());
+                            (git_state, Some(version_control))
+                        }
+                        None => (None, None),
+                    };
+                    let environment_context =
+                        sess.redactor
+                            .redact_item(ResponseItem::from(EnvironmentContext::new(
+                                cwd,
+                                approval_policy.map(|approval_policy| {
+                                    effective_approval_policy(
+                                        approval_policy,
+                                        &new_sandbox_policy,
+                                        get_platform_sandbox(),
+                                    )
+                                }),
+                                sandbox_policy,
+                                // Platform, shell and environment variables do not
+                                // change from turn to turn
+                                None,
+                                None,
+                                None,
+                                git_state,
+                                version_control,
+                            )));
+                    sess.record_conversation_items(&[environment_context]).await;
                 }
             }
             Op::UserInput { items } => {
+                let model_family = turn_context.client.get_model_family();
+                if let Err(message) = validate_input_images(&items, &model_family) {
+                    sess.send_event(Event {
+                        id: sub.id,
+                        msg: EventMsg::Error(ErrorEvent {
+                            message,
+                            kind: None,
+                        }),
+                    })
+                    .await;
+                    continue;
+                }
                 // attempt to inject input into current task
                 if let Err(items) = sess.inject_input(items) {
                     // no current task, spawn a new one
+                    let items = sess.prepend_queued_input(&sub.id, items).await;
                     let task =
                         AgentTask::spawn(sess.clone(), Arc::clone(&turn_context), sub.id, items);
                     sess.set_task(task);
@@ -1166,23 +2541,38 @@ async fn submission_loop(
                 model,
                 effort,
                 summary,
+                final_output_json_schema,
             } => {
+                // Derive a model family for the requested model; fall back to the session's.
+                let model_family =
+                    find_family_for_model_with_overrides(&model, &config.model_families)
+                        .unwrap_or_else(|| config.model_family.clone());
+                if let Err(message) = validate_input_images(&items, &model_family) {
+                    sess.send_event(Event {
+                        id: sub.id,
+                        msg: EventMsg::Error(ErrorEvent {
+                            message,
+                            kind: None,
+                        }),
+                    })
+                    .await;
+                    continue;
+                }
                 // attempt to inject input into current task
                 if let Err(items) = sess.inject_input(items) {
+                    let items = sess.prepend_queued_input(&sub.id, items).await;
                     // Derive a fresh TurnContext for this turn using the provided overrides.
                     let provider = turn_context.client.get_provider();
                     let auth_manager = turn_context.client.get_auth_manager();
 
-                    // Derive a model family for the requested model; fall back to the session's.
-                    let model_family = find_family_for_model(&model)
-                        .unwrap_or_else(|| config.model_family.clone());
-
                     // Create a per‑turn Config clone with the requested model/family.
                     let mut per_turn_config = (*config).clone();
                     per_turn_config.model = model.clone();
                     per_turn_config.model_family = model_family.clone();
-                    if let Some(model_info) = get_model_info(&model_family) {
-                        per_turn_config.model_context_window = Some(model_info.context_window);
+                    if let Some(context_window) =
+                        get_model_context_window(&model_family, &config.model_families)
+                    {
+                        per_turn_config.model_context_window = Some(context_window);
                     }
 
                     // Build a new client with per‑turn reasoning settings.
@@ -1191,7 +2581,7 @@ async fn submission_loop(
                         Arc::new(per_turn_config),
                         auth_manager,
                         provider,
-                        effort,
+                        effort.unwrap_or(turn_context.client.get_reasoning_effort()),
                         summary,
                         sess.session_id,
                     );
@@ -1205,17 +2595,22 @@ async fn submission_loop(
                             include_plan_tool: config.include_plan_tool,
                             include_apply_patch_tool: config.include_apply_patch_tool,
                             include_web_search_request: config.tools_web_search_request,
+                            web_search_context_size: config.tools_web_search_context_size,
                             use_streamable_shell_tool: config
                                 .use_experimental_streamable_shell_tool,
                             include_view_image_tool: config.include_view_image_tool,
                         }),
                         user_instructions: turn_context.user_instructions.clone(),
+                        project_docs: turn_context.project_docs.clone(),
                         base_instructions: turn_context.base_instructions.clone(),
                         approval_policy,
                         sandbox_policy,
                         shell_environment_policy: turn_context.shell_environment_policy.clone(),
+                        profile_env: turn_context.profile_env.clone(),
                         cwd,
                         disable_response_storage: turn_context.disable_response_storage,
+                        review_base: None,
+                        final_output_json_schema,
                     };
                     // TODO: record the new environment context in the conversation history
                     // no current task, spawn a new one with the per‑turn context
@@ -1224,17 +2619,71 @@ async fn submission_loop(
                     sess.set_task(task);
                 }
             }
-            Op::ExecApproval { id, decision } => match decision {
+            Op::QueueUserInput { items } => {
+                let model_family = turn_context.client.get_model_family();
+                if let Err(message) = validate_input_images(&items, &model_family) {
+                    sess.send_event(Event {
+                        id: sub.id,
+                        msg: EventMsg::Error(ErrorEvent {
+                            message,
+                            kind: None,
+                        }),
+                    })
+                    .await;
+                    continue;
+                }
+                let entry = QueuedInput { id: sub.id, items };
+                sess.queue_input(entry, Arc::clone(&turn_context)).await;
+            }
+            Op::ListQueuedInput => {
+                let queue = sess.queued_input();
+                sess.send_queued_input_update(
+                    sub.id,
+                    QueuedInputEvent {
+                        queue,
+                        started: Vec::new(),
+                        cleared: Vec::new(),
+                    },
+                )
+                .await;
+            }
+            Op::RemoveQueuedInput { id } => {
+                sess.remove_queued_input(sub.id, &id).await;
+            }
+            Op::ExecApproval {
+                id,
+                decision,
+                feedback,
+            } => match decision {
                 ReviewDecision::Abort => {
-                    sess.interrupt_task();
+                    sess.interrupt(sub.id).await;
                 }
-                other => sess.notify_approval(&id, other),
+                decision => sess.notify_approval(
+                    &id,
+                    ApprovalResponse {
+                        decision,
+                        selection: None,
+                        feedback,
+                    },
+                ),
             },
-            Op::PatchApproval { id, decision } => match decision {
+            Op::PatchApproval {
+                id,
+                decision,
+                selection,
+                feedback,
+            } => match decision {
                 ReviewDecision::Abort => {
-                    sess.interrupt_task();
+                    sess.interrupt(sub.id).await;
                 }
-                other => sess.notify_approval(&id, other),
+                decision => sess.notify_approval(
+                    &id,
+                    ApprovalResponse {
+                        decision,
+                        selection,
+                        feedback,
+                    },
+                ),
             },
             Op::AddToHistory { text } => {
                 let id = sess.session_id;
@@ -1282,21 +2731,113 @@ async fn submission_loop(
                     }
                 });
             }
-            Op::ListMcpTools => {
-                let tx_event = sess.tx_event.clone();
+            Op::SearchHistory { query, limit } => {
+                let config = config.clone();
+                let sess = sess.clone();
                 let sub_id = sub.id.clone();
 
-                // This is a cheap lookup from the connection manager's cache.
-                let tools = sess.mcp_connection_manager.list_all_tools();
-                let event = Event {
-                    id: sub_id,
-                    msg: EventMsg::McpListToolsResponse(
-                        crate::protocol::McpListToolsResponseEvent { tools },
-                    ),
-                };
-                if let Err(e) = tx_event.send(event).await {
-                    warn!("failed to send McpListToolsResponse event: {e}");
-                }
+                tokio::spawn(async move {
+                    let search_query = query.clone();
+                    let (log_id, matches) = tokio::task::spawn_blocking(move || {
+                        crate::message_history::search(&search_query, limit, &config)
+                    })
+                    .await
+                    .unwrap_or_default();
+
+                    sess.send_event(Event {
+                        id: sub_id,
+                        msg: EventMsg::SearchHistoryResponse(SearchHistoryResponseEvent {
+                            query,
+                            log_id,
+                            matches,
+                        }),
+                    })
+                    .await;
+                });
+            }
+            Op::ListMcpTools => {
+                let sess = sess.clone();
+                let sub_id = sub.id.clone();
+                tokio::spawn(async move {
+                    // Lazy servers are started here, so the list is complete.
+                    sess.refresh_mcp_tools(&sub_id).await;
+                    let tools = sess.mcp_connection_manager.list_all_tools();
+                    let servers = sess.mcp_connection_manager.list_tools_by_server();
+                    sess.send_event(Event {
+                        id: sub_id,
+                        msg: EventMsg::McpListToolsResponse(
+                            crate::protocol::McpListToolsResponseEvent { tools, servers },
+                        ),
+                    })
+                    .await;
+                });
+            }
+            Op::ListMcpResources => {
+                let sess = sess.clone();
+                let sub_id = sub.id.clone();
+                tokio::spawn(async move {
+                    sess.refresh_mcp_tools(&sub_id).await;
+                    let resources = sess.mcp_connection_manager.list_all_resources().await;
+                    sess.send_event(Event {
+                        id: sub_id,
+                        msg: EventMsg::McpListResourcesResponse(McpListResourcesResponseEvent {
+                            resources,
+                        }),
+                    })
+                    .await;
+                });
+            }
+            Op::ListMcpPrompts => {
+                let sess = sess.clone();
+                let sub_id = sub.id.clone();
+                tokio::spawn(async move {
+                    sess.refresh_mcp_tools(&sub_id).await;
+                    let prompts = sess
+                        .mcp_connection_manager
+                        .list_all_prompts()
+                        .await
+                        .into_iter()
+                        .map(|(name, info)| (name, info.prompt))
+                        .collect();
+                    sess.send_event(Event {
+                        id: sub_id,
+                        msg: EventMsg::McpListPromptsResponse(McpListPromptsResponseEvent {
+                            prompts,
+                        }),
+                    })
+                    .await;
+                });
+            }
+            Op::McpPrompt { name, arguments } => {
+                let sess = sess.clone();
+                let turn_context = Arc::clone(&turn_context);
+                let attachments_dir = config
+                    .dirs
+                    .state_dir()
+                    .join(MCP_RESOURCES_SUBDIR)
+                    .join(sess.session_id.to_string());
+                let sub_id = sub.id.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = start_mcp_prompt_task(
+                        &sess,
+                        turn_context,
+                        &sub_id,
+                        &name,
+                        arguments,
+                        &attachments_dir,
+                    )
+                    .await
+                    {
+                        sess.send_event(Event {
+                            id: sub_id,
+                            msg: EventMsg::Error(ErrorEvent {
+                                message: format!("{e:#}"),
+                                kind: None,
+                            }),
+                        })
+                        .await;
+                    }
+                });
             }
             Op::ListCustomPrompts => {
                 let tx_event = sess.tx_event.clone();
@@ -1320,26 +2861,57 @@ async fn submission_loop(
                 }
             }
             Op::Compact => {
-                // Create a summarization request as user input
-                const SUMMARIZATION_PROMPT: &str = include_str!("prompt_for_compact_command.md");
-
-                // Attempt to inject input into current task
-                if let Err(items) = sess.inject_input(vec![InputItem::Text {
-                    text: "Start Summarization".to_string(),
-                }]) {
-                    let task = AgentTask::compact(
-                        sess.clone(),
-                        Arc::clone(&turn_context),
-                        sub.id,
-                        items,
-                        SUMMARIZATION_PROMPT.to_string(),
-                    );
+                // A running task compacts before its next request; otherwise
+                // compact right away.
+                if !sess.request_compaction() {
+                    let task = AgentTask::compact(sess.clone(), Arc::clone(&turn_context), sub.id);
                     sess.set_task(task);
                 }
             }
+            Op::Review { base } => {
+                let items = match review::review_input(&turn_context.cwd, &base).await {
+                    Ok(items) => items,
+                    Err(message) => {
+                        sess.send_event(Event {
+                            id: sub.id,
+                            msg: EventMsg::Error(ErrorEvent {
+                                message,
+                                kind: None,
+                            }),
+                        })
+                        .await;
+                        continue;
+                    }
+                };
+                let review_context = review_turn_context(&turn_context, &config, base);
+                let task = AgentTask::spawn(sess.clone(), Arc::new(review_context), sub.id, items);
+                sess.set_task(task);
+            }
+            Op::UndoLastPatch => {
+                let msg = match sess.patch_undo.undo_last() {
+                    Ok(undone) => EventMsg::PatchUndone(PatchUndoneEvent {
+                        call_id: undone.call_id,
+                        restored: undone.restored,
+                        removed: undone.removed,
+                    }),
+                    Err(message) => EventMsg::Error(ErrorEvent {
+                        message,
+                        kind: None,
+                    }),
+                };
+                sess.send_event(Event { id: sub.id, msg }).await;
+            }
             Op::Shutdown => {
                 info!("Shutting down Codex instance");
 
+                if let Some(dir) = sess.scratch_dir.as_ref()
+                    && !sess.retain_scratch_dir
+                    && let Err(e) = tokio::fs::remove_dir_all(dir).await
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    warn!("failed to remove scratch directory {}: {e}", dir.display());
+                }
+
                 // Gracefully flush and shutdown rollout recorder on session end so tests
                 // that inspect the rollout file do not race with the background writer.
                 let recorder_opt = sess.rollout.lock_unchecked().take();
@@ -1351,6 +2923,7 @@ async fn submission_loop(
                         id: sub.id.clone(),
                         msg: EventMsg::Error(ErrorEvent {
                             message: "Failed to shutdown rollout recorder".to_string(),
+                            kind: None,
                         }),
                     };
                     if let Err(e) = sess.tx_event.send(event).await {
@@ -1382,6 +2955,24 @@ async fn submission_loop(
                     warn!("failed to send ConversationHistory event: {e}");
                 }
             }
+            Op::GetStatus => {
+                let status = sess.status(&turn_context, &config);
+                sess.send_event(Event {
+                    id: sub.id,
+                    msg: EventMsg::StatusResponse(status),
+                })
+                .await;
+            }
+            Op::GetUsage => {
+                let (event, rate_limits) = {
+                    let state = sess.state.lock_unchecked();
+                    (state.usage_update_event(), state.rate_limits.clone())
+                };
+                sess.send_usage_update(&sub.id, event).await;
+                if let Some(snapshot) = rate_limits {
+                    sess.update_rate_limits(&sub.id, snapshot).await;
+                }
+            }
             _ => {
                 // Ignore unknown ops; enum is non_exhaustive to allow extensions.
             }
@@ -1390,6 +2981,54 @@ async fn submission_loop(
     debug!("Agent loop exited");
 }
 
+/// Renders the MCP prompt `name` and starts a task with it: all but its last
+/// message are recorded in the history and the last one is the task's input.
+async fn start_mcp_prompt_task(
+    sess: &Arc<Session>,
+    turn_context: Arc<TurnContext>,
+    sub_id: &str,
+    name: &str,
+    arguments: HashMap<String, String>,
+    attachments_dir: &Path,
+) -> anyhow::Result<()> {
+    if sess.has_running_task() {
+        anyhow::bail!("MCP prompts cannot be used while a task is running");
+    }
+    let manager = &sess.mcp_connection_manager;
+    let info = resolve_prompt(manager.list_all_prompts().await, name, &arguments)?;
+    let result = manager
+        .get_prompt(&info.server_name, &info.prompt.name, arguments)
+        .await?;
+    let rendered = render_prompt(manager, &info.server_name, name, result, attachments_dir).await?;
+    // The user may have started a task while the prompt was being fetched.
+    if sess.has_running_task() {
+        anyhow::bail!("MCP prompts cannot be used while a task is running");
+    }
+
+    sess.notify_background_event(
+        sub_id,
+        format!(
+            "Using MCP prompt `{}` from `{}`",
+            info.prompt.name, info.server_name
+        ),
+    )
+    .await;
+    let history: Vec<ResponseItem> = rendered
+        .history
+        .into_iter()
+        .map(|item| sess.redactor.redact_item(item))
+        .collect();
+    sess.record_conversation_items(&history).await;
+    let task = AgentTask::spawn(
+        Arc::clone(sess),
+        turn_context,
+        sub_id.to_string(),
+        rendered.input,
+    );
+    sess.set_task(task);
+    Ok(())
+}
+
 /// Takes a user message as input and runs a loop where, at each turn, the model
 /// replies with either:
 ///
@@ -1430,6 +3069,13 @@ async fn run_task(
     // Although from the perspective of codex.rs, TurnDiffTracker has the lifecycle of a Task which contains
     // many turns, from the perspective of the user, it is a single turn.
     let mut turn_diff_tracker = TurnDiffTracker::new();
+    // Set once the history was compacted because a request overflowed the
+    // context window, so a request that still does not fit is not retried.
+    let mut compacted_after_overflow = false;
+    let mut tool_output_budget =
+        ToolOutputBudget::new(sess.tool_output_budget_bytes.unwrap_or_else(|| {
+            default_tool_output_budget(turn_context.client.get_model_context_window())
+        }));
 
     loop {
         // Note that pending_input would be something like a message the user
@@ -1442,16 +3088,28 @@ async fn run_task(
             .collect::<Vec<ResponseItem>>();
         sess.record_conversation_items(&pending_input).await;
 
+        // A failed compaction does not cost the user their turn: the request
+        // is sent with the full history, and is not compacted again if it
+        // turns out not to fit.
+        match maybe_compact_history(&sess, turn_context, &sub_id).await {
+            Ok(()) => {}
+            Err(CodexErr::Interrupted) => break,
+            Err(e) => {
+                report_compaction_error(&sess, &sub_id, e).await;
+                compacted_after_overflow = true;
+            }
+        }
+
         // Construct the input that we will send to the model. When using the
         // Chat completions API (or ZDR clients), the model needs the full
         // conversation history on each turn. The rollout file, however, should
         // only record the new items that originated in this turn so that it
         // represents an append-only log without duplicates.
-        let turn_input: Vec<ResponseItem> = sess.turn_input_with_history(pending_input);
+        let turn_input: Vec<Arc<ResponseItem>> = sess.turn_input_with_history(pending_input);
 
         let turn_input_messages: Vec<String> = turn_input
             .iter()
-            .filter_map(|item| match item {
+            .filter_map(|item| match item.as_ref() {
                 ResponseItem::Message { content, .. } => Some(content),
                 _ => None,
             })
@@ -1462,6 +3120,13 @@ async fn run_task(
                 })
             })
             .collect();
+        let turn_span = otel::turn_span(
+            sess.session_id,
+            sess.turns_started
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            &turn_context.client.get_model(),
+            &turn_context.client.get_provider().name,
+        );
         match run_turn(
             &sess,
             turn_context,
@@ -1469,6 +3134,7 @@ async fn run_task(
             sub_id.clone(),
             turn_input,
         )
+        .instrument(turn_span.clone())
         .await
         {
             Ok(turn_output) => {
@@ -1487,10 +3153,10 @@ async fn run_task(
                         ) => {
                             items_to_record_in_conversation_history.push(item);
                             items_to_record_in_conversation_history.push(
-                                ResponseItem::FunctionCallOutput {
+                                sess.redactor.redact_item(ResponseItem::FunctionCallOutput {
                                     call_id: call_id.clone(),
                                     output: output.clone(),
-                                },
+                                }),
                             );
                         }
                         (
@@ -1499,10 +3165,10 @@ async fn run_task(
                         ) => {
                             items_to_record_in_conversation_history.push(item);
                             items_to_record_in_conversation_history.push(
-                                ResponseItem::FunctionCallOutput {
+                                sess.redactor.redact_item(ResponseItem::FunctionCallOutput {
                                     call_id: call_id.clone(),
                                     output: output.clone(),
-                                },
+                                }),
                             );
                         }
                         (
@@ -1511,10 +3177,11 @@ async fn run_task(
                         ) => {
                             items_to_record_in_conversation_history.push(item);
                             items_to_record_in_conversation_history.push(
-                                ResponseItem::CustomToolCallOutput {
-                                    call_id: call_id.clone(),
-                                    output: output.clone(),
-                                },
+                                sess.redactor
+                                    .redact_item(ResponseItem::CustomToolCallOutput {
+                                        call_id: call_id.clone(),
+                                        output: output.clone(),
+                                    }),
                             );
                         }
                         (
@@ -1534,10 +3201,10 @@ async fn run_task(
                                 },
                             };
                             items_to_record_in_conversation_history.push(
-                                ResponseItem::FunctionCallOutput {
+                                sess.redactor.redact_item(ResponseItem::FunctionCallOutput {
                                     call_id: call_id.clone(),
                                     output,
-                                },
+                                }),
                             );
                         }
                         (
@@ -1565,11 +3232,40 @@ async fn run_task(
                     }
                 }
 
+                for item in &mut items_to_record_in_conversation_history {
+                    let output = match item {
+                        ResponseItem::FunctionCallOutput { output, .. } => &mut output.content,
+                        ResponseItem::CustomToolCallOutput { output, .. } => output,
+                        _ => continue,
+                    };
+                    if tool_output_budget.apply(output) {
+                        sess.send_event(Event {
+                            id: sub_id.clone(),
+                            msg: EventMsg::ToolOutputBudgetExceeded(
+                                ToolOutputBudgetExceededEvent {
+                                    budget_bytes: tool_output_budget.limit() as u64,
+                                },
+                            ),
+                        })
+                        .await;
+                    }
+                }
+
+                if sess.otel_include_content
+                    && let Some(message) = get_last_assistant_message_from_turn(
+                        &items_to_record_in_conversation_history,
+                    )
+                {
+                    turn_span.record("output", message.as_str());
+                }
+
                 // Only attempt to take the lock if there is something to record.
                 if !items_to_record_in_conversation_history.is_empty() {
                     sess.record_conversation_items(&items_to_record_in_conversation_history)
                         .await;
                 }
+                sess.clear_partial_turn();
+                sess.report_context_usage(turn_context, &sub_id).await;
 
                 if responses.is_empty() {
                     debug!("Turn completed");
@@ -1578,18 +3274,32 @@ async fn run_task(
                     );
                     sess.maybe_notify(UserNotification::AgentTurnComplete {
                         turn_id: sub_id.clone(),
+                        session_id: sess.session_id,
+                        cwd: turn_context.cwd.clone(),
                         input_messages: turn_input_messages,
                         last_assistant_message: last_agent_message.clone(),
                     });
                     break;
                 }
             }
+            Err(CodexErr::ContextWindowExceeded) if !compacted_after_overflow => {
+                // Summarize older turns and send the request again; a second
+                // overflow is reported below.
+                sess.clear_partial_turn();
+                compacted_after_overflow = true;
+                if let Err(e) = compact_history(&sess, turn_context, &sub_id, true).await {
+                    report_compaction_error(&sess, &sub_id, e).await;
+                    break;
+                }
+            }
             Err(e) => {
+                sess.clear_partial_turn();
                 info!("Turn error: {e:#}");
                 let event = Event {
                     id: sub_id.clone(),
                     msg: EventMsg::Error(ErrorEvent {
                         message: e.to_string(),
+                        kind: e.kind(),
                     }),
                 };
                 sess.tx_event.send(event).await.ok();
@@ -1598,6 +3308,28 @@ async fn run_task(
             }
         }
     }
+    // A task that ended in an error has already reported it.
+    if let Some(base) = &turn_context.review_base
+        && let Some(message) = last_agent_message.as_deref()
+    {
+        let msg = match review::parse_review_findings(message) {
+            Ok(findings) => EventMsg::ReviewFindings(ReviewFindingsEvent {
+                base: base.clone(),
+                findings,
+            }),
+            Err(message) => EventMsg::Error(ErrorEvent {
+                message,
+                kind: None,
+            }),
+        };
+        sess.tx_event
+            .send(Event {
+                id: sub_id.clone(),
+                msg,
+            })
+            .await
+            .ok();
+    }
     sess.remove_task(&sub_id);
     let event = Event {
         id: sub_id,
@@ -1611,36 +3343,65 @@ async fn run_turn(
     turn_context: &TurnContext,
     turn_diff_tracker: &mut TurnDiffTracker,
     sub_id: String,
-    input: Vec<ResponseItem>,
+    input: Vec<Arc<ResponseItem>>,
 ) -> CodexResult<Vec<ProcessedResponseItem>> {
-    let tools = get_openai_tools(
+    sess.refresh_mcp_tools(&sub_id).await;
+    let mut tools = get_openai_tools(
         &turn_context.tools_config,
         Some(sess.mcp_connection_manager.list_all_tools()),
     );
+    tools.extend(sess.command_tools.iter().map(CommandTool::openai_tool));
+    tools.extend(
+        sess.pull_request_tool
+            .as_ref()
+            .map(PullRequestTool::openai_tool),
+    );
 
     let prompt = Prompt {
         input,
         store: !turn_context.disable_response_storage,
         tools,
         base_instructions_override: turn_context.base_instructions.clone(),
+        output_schema: turn_context.final_output_json_schema.clone(),
+        prompt_vars: sess.prompt_vars(turn_context),
+    };
+
+    let breakdown = prompt.estimated_context_breakdown(&turn_context.client.get_model_family());
+    sess.record_context_breakdown(breakdown);
+    let event = Event {
+        id: sub_id.clone(),
+        msg: EventMsg::PromptTokenEstimate(PromptTokenEstimateEvent {
+            estimated_tokens: breakdown.total(),
+            model_context_window: turn_context.client.get_model_context_window(),
+        }),
     };
+    sess.tx_event.send(event).await.ok();
 
     let mut retries = 0;
     loop {
-        match try_run_turn(sess, turn_context, turn_diff_tracker, &sub_id, &prompt).await {
+        match try_run_turn(
+            sess,
+            turn_context,
+            turn_diff_tracker,
+            &sub_id,
+            &prompt,
+            retries,
+        )
+        .await
+        {
             Ok(output) => return Ok(output),
-            Err(CodexErr::Interrupted) => return Err(CodexErr::Interrupted),
-            Err(CodexErr::EnvVar(var)) => return Err(CodexErr::EnvVar(var)),
-            Err(e @ (CodexErr::UsageLimitReached(_) | CodexErr::UsageNotIncluded)) => {
-                return Err(e);
-            }
+            Err(CodexErr::AuthExpired) => wait_for_login(sess, turn_context, &sub_id).await?,
+            Err(e) if !e.is_retryable() => return Err(e),
             Err(e) => {
                 // Use the configured provider-specific stream retry budget.
                 let max_retries = turn_context.client.get_provider().stream_max_retries();
                 if retries < max_retries {
                     retries += 1;
                     let delay = match e {
-                        CodexErr::Stream(_, Some(delay)) => delay,
+                        CodexErr::StreamDisconnected {
+                            retry_after: Some(delay),
+                            ..
+                        } => delay,
                         _ => backoff(retries),
                     };
                     warn!(
@@ -1667,6 +3428,62 @@ async fn run_turn(
     }
 }
 
+/// Tells the client that the credentials were rejected and waits until
+/// `auth.json` changes, e.g. after `codex login` in another terminal, so that
+/// the turn can be sent again. `Op::Interrupt` abandons the turn meanwhile.
+async fn wait_for_login(
+    sess: &Session,
+    turn_context: &TurnContext,
+    sub_id: &str,
+) -> CodexResult<()> {
+    let Some(auth_manager) = turn_context.client.get_auth_manager() else {
+        return Err(CodexErr::AuthExpired);
+    };
+    let auth_file = auth_manager.auth_file();
+    let modified = || {
+        std::fs::metadata(&auth_file)
+            .and_then(|m| m.modified())
+            .ok()
+    };
+    let loaded = modified();
+    sess.send_event(Event {
+        id: sub_id.to_string(),
+        msg: EventMsg::AuthExpired(AuthExpiredEvent {
+            message: CodexErr::AuthExpired.to_string(),
+        }),
+    })
+    .await;
+    while modified() == loaded {
+        tokio::time::sleep(LOGIN_POLL_INTERVAL).await;
+    }
+    info!("auth.json changed; sending the turn again");
+    auth_manager.reload();
+    Ok(())
+}
+
+/// Decides what a turn whose stream failed with `err` (after the client gave
+/// up resuming it) hands back to [`run_turn`].
+///
+/// Re-running the turn would run the tool calls the model already issued a
+/// second time. So once any has run, wait for the ones still in flight and
+/// return the partial output as if the response had completed: the next
+/// request carries their results and the model carries on from there.
+/// Otherwise nothing had side effects and `err` is returned for a retry.
+async fn keep_executed_tool_calls(
+    mut output: Vec<ProcessedResponseItem>,
+    mut in_flight: FuturesUnordered<ParallelToolCall<'_>>,
+    err: CodexErr,
+) -> CodexResult<Vec<ProcessedResponseItem>> {
+    if !err.is_retryable() || !output.iter().any(|processed| is_tool_call(&processed.item)) {
+        return Err(err);
+    }
+    while let Some((index, response)) = in_flight.next().await {
+        output[index].response = response?;
+    }
+    warn!("stream disconnected after tool calls ran; continuing from their results: {err}");
+    Ok(output)
+}
+
 /// When the model is prompted, it returns a stream of events. Some of these
 /// events map to a `ResponseItem`. A `ResponseItem` may need to be
 /// "handled" such that it produces a `ResponseInputItem` that needs to be
@@ -1677,18 +3494,81 @@ struct ProcessedResponseItem {
     response: Option<ResponseInputItem>,
 }
 
+/// A tool call running concurrently with others from the same response,
+/// resolving to its position in the turn's output and its result.
+type ParallelToolCall<'a> = BoxFuture<'a, (usize, CodexResult<Option<ResponseInputItem>>)>;
+
+fn is_tool_call(item: &ResponseItem) -> bool {
+    matches!(
+        item,
+        ResponseItem::FunctionCall { .. }
+            | ResponseItem::LocalShellCall { .. }
+            | ResponseItem::CustomToolCall { .. }
+    )
+}
+
+/// Whether `item` is a tool call that may run concurrently with its
+/// neighbours: an MCP tool call, or a shell command that can neither write to
+/// the workspace nor stop to ask for approval.
+fn can_run_in_parallel(sess: &Session, turn_context: &TurnContext, item: &ResponseItem) -> bool {
+    match item {
+        ResponseItem::FunctionCall {
+            name,
+            arguments,
+            call_id,
+            ..
+        } => match name.as_str() {
+            "container.exec" | "shell" => {
+                parse_container_exec_arguments(arguments.clone(), turn_context, call_id)
+                    .is_ok_and(|params| is_read_only_exec(sess, &params, turn_context))
+            }
+            _ => sess.mcp_connection_manager.parse_tool_name(name).is_some(),
+        },
+        ResponseItem::LocalShellCall {
+            action: LocalShellAction::Exec(action),
+            ..
+        } => is_read_only_exec(
+            sess,
+            &local_shell_exec_params(action.clone(), turn_context),
+            turn_context,
+        ),
+        _ => false,
+    }
+}
+
+/// A command is read-only when it is on the known-safe list, or when the
+/// platform sandbox enforces a read-only policy and a failure cannot turn
+/// into an approval request.
+fn is_read_only_exec(sess: &Session, params: &ExecParams, turn_context: &TurnContext) -> bool {
+    if params.with_escalated_permissions.unwrap_or(false) {
+        return false;
+    }
+    if sess.is_known_safe_command(&params.command) {
+        return true;
+    }
+    matches!(turn_context.sandbox_policy, SandboxPolicy::ReadOnly)
+        && turn_context.approval_policy == AskForApproval::Never
+        && get_platform_sandbox().is_some()
+        && matches!(
+            maybe_parse_apply_patch_verified(&params.command, &params.cwd),
+            MaybeApplyPatchVerified::NotApplyPatch
+        )
+}
+
 async fn try_run_turn(
     sess: &Session,
     turn_context: &TurnContext,
     turn_diff_tracker: &mut TurnDiffTracker,
     sub_id: &str,
     prompt: &Prompt,
+    // Earlier attempts at this turn whose stream failed.
+    turn_retries: u64,
 ) -> CodexResult<Vec<ProcessedResponseItem>> {
     // call_ids that are part of this response.
     let completed_call_ids = prompt
         .input
         .iter()
-        .filter_map(|ri| match ri {
+        .filter_map(|ri| match ri.as_ref() {
             ResponseItem::FunctionCallOutput { call_id, .. } => Some(call_id),
             ResponseItem::LocalShellCall {
                 call_id: Some(call_id),
@@ -1706,7 +3586,7 @@ async fn try_run_turn(
         prompt
             .input
             .iter()
-            .filter_map(|ri| match ri {
+            .filter_map(|ri| match ri.as_ref() {
                 ResponseItem::FunctionCall { call_id, .. } => Some(call_id),
                 ResponseItem::LocalShellCall {
                     call_id: Some(call_id),
@@ -1722,9 +3602,11 @@ async fn try_run_turn(
                     Some(call_id.clone())
                 }
             })
-            .map(|call_id| ResponseItem::CustomToolCallOutput {
-                call_id: call_id.clone(),
-                output: "aborted".to_string(),
+            .map(|call_id| {
+                Arc::new(ResponseItem::CustomToolCallOutput {
+                    call_id,
+                    output: "aborted".to_string(),
+                })
             })
             .collect::<Vec<_>>()
     };
@@ -1740,35 +3622,86 @@ async fn try_run_turn(
     };
 
     let mut stream = turn_context.client.clone().stream(&prompt).await?;
+    // Retried attempts start over, so only track the current one.
+    sess.begin_partial_turn();
 
-    let mut output = Vec::new();
+    let mut output: Vec<ProcessedResponseItem> = Vec::new();
+    // Read-only tool calls that are still running, tagged with the position of
+    // their item in `output` so results land in emission order.
+    let mut in_flight: FuturesUnordered<ParallelToolCall<'_>> = FuturesUnordered::new();
 
     loop {
         // Poll the next item from the model stream. We must inspect *both* Ok and Err
         // cases so that transient stream failures (e.g., dropped SSE connection before
         // `response.completed`) bubble up and trigger the caller's retry logic.
-        let event = stream.next().await;
-        let Some(event) = event else {
-            // Channel closed without yielding a final Completed event or explicit error.
-            // Treat as a disconnected stream so the caller can retry.
-            return Err(CodexErr::Stream(
-                "stream closed before response.completed".into(),
-                None,
-            ));
+        let event = if in_flight.is_empty() {
+            stream.next().await
+        } else {
+            tokio::select! {
+                event = stream.next() => event,
+                Some((index, response)) = in_flight.next() => {
+                    output[index].response = response?;
+                    continue;
+                }
+            }
         };
-
         let event = match event {
-            Ok(ev) => ev,
-            Err(e) => {
-                // Propagate the underlying stream error to the caller (run_turn), which
-                // will apply the configured `stream_max_retries` policy.
-                return Err(e);
+            Some(Ok(ev)) => ev,
+            // Propagate the underlying stream error to the caller (run_turn), which
+            // will apply the configured `stream_max_retries` policy.
+            Some(Err(e)) => return keep_executed_tool_calls(output, in_flight, e).await,
+            // Channel closed without yielding a final Completed event or explicit error.
+            // Treat as a disconnected stream so the caller can retry.
+            None => {
+                let e = CodexErr::stream_disconnected("stream closed before response.completed");
+                return keep_executed_tool_calls(output, in_flight, e).await;
             }
         };
 
         match event {
             ResponseEvent::Created => {}
             ResponseEvent::OutputItemDone(item) => {
+                sess.push_partial_item(&item);
+                if sess.max_parallel_tool_calls > 1
+                    && can_run_in_parallel(sess, turn_context, &item)
+                {
+                    while in_flight.len() >= sess.max_parallel_tool_calls {
+                        if let Some((index, response)) = in_flight.next().await {
+                            output[index].response = response?;
+                        }
+                    }
+                    let index = output.len();
+                    output.push(ProcessedResponseItem {
+                        item: item.clone(),
+                        response: None,
+                    });
+                    in_flight.push(
+                        async move {
+                            // Read-only calls cannot change files, so there is
+                            // no diff to track.
+                            let mut diff_tracker = TurnDiffTracker::new();
+                            let response = handle_response_item(
+                                sess,
+                                turn_context,
+                                &mut diff_tracker,
+                                sub_id,
+                                item,
+                            )
+                            .await;
+                            (index, response)
+                        }
+                        .boxed(),
+                    );
+                    continue;
+                }
+
+                // Any other tool call runs in emission order, after every
+                // call that came before it has finished.
+                if is_tool_call(&item) {
+                    while let Some((index, response)) = in_flight.next().await {
+                        output[index].response = response?;
+                    }
+                }
                 let response = handle_response_item(
                     sess,
                     turn_context,
@@ -1792,7 +3725,15 @@ async fn try_run_turn(
                 response_id: _,
                 token_usage,
             } => {
+                while let Some((index, response)) = in_flight.next().await {
+                    output[index].response = response?;
+                }
+                sess.record_prompt_tokens(token_usage.as_ref());
+                sess.commit_token_usage(sub_id, turn_context, token_usage.clone())
+                    .await;
                 if let Some(token_usage) = token_usage {
+                    // The enclosing span is the turn this response belongs to.
+                    otel::record_token_usage(&Span::current(), &token_usage);
                     sess.tx_event
                         .send(Event {
                             id: sub_id.to_string(),
@@ -1801,6 +3742,15 @@ async fn try_run_turn(
                         .await
                         .ok();
                 }
+                let mut metrics = stream.metrics();
+                metrics.retries += turn_retries;
+                sess.tx_event
+                    .send(Event {
+                        id: sub_id.to_string(),
+                        msg: EventMsg::TurnMetrics(metrics),
+                    })
+                    .await
+                    .ok();
 
                 let unified_diff = turn_diff_tracker.get_unified_diff();
                 if let Ok(Some(unified_diff)) = unified_diff {
@@ -1814,7 +3764,37 @@ async fn try_run_turn(
 
                 return Ok(output);
             }
+            ResponseEvent::UsageUpdate(usage) => {
+                sess.update_token_usage(sub_id, usage).await;
+            }
+            ResponseEvent::RateLimits(snapshot) => {
+                sess.update_rate_limits(sub_id, snapshot).await;
+            }
+            ResponseEvent::WireApiSelected(wire_api) => {
+                let api = match wire_api {
+                    WireApi::Responses => "the Responses API",
+                    WireApi::Chat => "the Chat Completions API",
+                    WireApi::Anthropic => "the Anthropic Messages API",
+                };
+                let base_url = turn_context.client.get_provider().base_url;
+                let message = format!(
+                    "Using {api} for {}; set `wire_api` on the provider to skip this check.",
+                    base_url.unwrap_or_default()
+                );
+                sess.notify_background_event(sub_id, message).await;
+            }
+            ResponseEvent::BackgroundProgress { status, elapsed } => {
+                let event = Event {
+                    id: sub_id.to_string(),
+                    msg: EventMsg::BackgroundResponseProgress(BackgroundResponseProgressEvent {
+                        status,
+                        elapsed_seconds: elapsed.as_secs(),
+                    }),
+                };
+                sess.tx_event.send(event).await.ok();
+            }
             ResponseEvent::OutputTextDelta(delta) => {
+                sess.push_partial_text(&delta);
                 let event = Event {
                     id: sub_id.to_string(),
                     msg: EventMsg::AgentMessageDelta(AgentMessageDeltaEvent { delta }),
@@ -1835,6 +3815,35 @@ async fn try_run_turn(
                 };
                 sess.tx_event.send(event).await.ok();
             }
+            ResponseEvent::RateLimited {
+                status,
+                attempt,
+                max_retries,
+                resets_at,
+                limit_resets_at,
+            } => {
+                let now = Utc::now();
+                let wait_ms = (resets_at - now).num_milliseconds().max(0) as u64;
+                let wait_secs = wait_ms.div_ceil(1_000);
+                let message = match limit_resets_at {
+                    Some(limit_resets_at) => format!(
+                        "rate limit reached, resets in {}; retrying {attempt}/{max_retries} in {wait_secs}s…",
+                        rate_limits::format_wait(rate_limits::until(limit_resets_at, now)),
+                    ),
+                    None => format!("{status}; retrying {attempt}/{max_retries} in {wait_secs}s…"),
+                };
+                sess.notify_stream_error(sub_id, message).await;
+            }
+            ResponseEvent::FunctionCallArgumentsDelta { call_id, delta } => {
+                let event = Event {
+                    id: sub_id.to_string(),
+                    msg: EventMsg::FunctionCallArgumentsDelta(FunctionCallArgumentsDeltaEvent {
+                        call_id,
+                        delta,
+                    }),
+                };
+                sess.tx_event.send(event).await.ok();
+            }
             ResponseEvent::ReasoningContentDelta(delta) => {
                 if sess.show_raw_agent_reasoning {
                     let event = Event {
@@ -1850,13 +3859,7 @@ async fn try_run_turn(
     }
 }
 
-async fn run_compact_task(
-    sess: Arc<Session>,
-    turn_context: &TurnContext,
-    sub_id: String,
-    input: Vec<InputItem>,
-    compact_instructions: String,
-) {
+async fn run_compact_task(sess: Arc<Session>, turn_context: &TurnContext, sub_id: String) {
     let model_context_window = turn_context.client.get_model_context_window();
     let start_event = Event {
         id: sub_id.clone(),
@@ -1868,74 +3871,157 @@ async fn run_compact_task(
         return;
     }
 
-    let initial_input_for_turn: ResponseInputItem = ResponseInputItem::from(input);
-    let turn_input: Vec<ResponseItem> =
-        sess.turn_input_with_history(vec![initial_input_for_turn.clone().into()]);
+    match compact_history(&sess, turn_context, &sub_id, false).await {
+        Ok(()) => {}
+        Err(CodexErr::Interrupted) => return,
+        Err(e) => report_compaction_error(&sess, &sub_id, e).await,
+    }
+
+    sess.remove_task(&sub_id);
+    let event = Event {
+        id: sub_id.clone(),
+        msg: EventMsg::TaskComplete(TaskCompleteEvent {
+            last_agent_message: None,
+        }),
+    };
+    sess.send_event(event).await;
+}
+
+/// Tells the client that compaction failed and the history is unchanged.
+/// Interruptions are reported by the code that aborts the task.
+async fn report_compaction_error(sess: &Session, sub_id: &str, e: CodexErr) {
+    info!("Compaction error: {e:#}");
+    if matches!(e, CodexErr::Interrupted) {
+        return;
+    }
+    let event = Event {
+        id: sub_id.to_string(),
+        msg: EventMsg::Error(ErrorEvent {
+            message: format!("failed to compact the conversation: {e}"),
+            kind: e.kind(),
+        }),
+    };
+    sess.send_event(event).await;
+}
+
+/// Compacts the history before the next request of a task if `Op::Compact`
+/// was submitted while it ran, or if the request is projected to fill more
+/// than `auto_compact_threshold` of the context window.
+async fn maybe_compact_history(
+    sess: &Session,
+    turn_context: &TurnContext,
+    sub_id: &str,
+) -> CodexResult<()> {
+    if sess.take_compaction_request() {
+        return compact_history(sess, turn_context, sub_id, false).await;
+    }
+    let Some(context_window) = turn_context.client.get_model_context_window() else {
+        return Ok(());
+    };
+    let encoding = Encoding::for_model_family(&turn_context.client.get_model_family());
+    let projected = sess.projected_prompt_tokens(encoding);
+    if (projected as f64) < context_window as f64 * sess.auto_compact_threshold {
+        return Ok(());
+    }
+    compact_history(sess, turn_context, sub_id, true).await
+}
 
+/// Asks the model to summarize the older part of the history and replaces
+/// those items with the summary. Automatic compaction keeps the most recent
+/// turns verbatim; manual compaction summarizes the whole conversation. The
+/// rollout keeps the original items and gets the summary appended. If the
+/// model answers without a summary, the history is left alone.
+async fn compact_history(
+    sess: &Session,
+    turn_context: &TurnContext,
+    sub_id: &str,
+    automatic: bool,
+) -> CodexResult<()> {
+    let encoding = Encoding::for_model_family(&turn_context.client.get_model_family());
+    let keep_tokens = match turn_context.client.get_model_context_window() {
+        Some(context_window) if automatic => {
+            (context_window as f64 * AUTO_COMPACT_KEEP_FRACTION) as u64
+        }
+        _ => 0,
+    };
+    let items = sess.state.lock_unchecked().history.shared_contents();
+    let Some(range) = plan_compaction(&items, keep_tokens, encoding) else {
+        return Ok(());
+    };
+    let summarized = &items[range.clone()];
+
+    let mut input = summarized.to_vec();
+    input.push(Arc::new(ResponseItem::Message {
+        id: None,
+        role: "user".to_string(),
+        content: vec![ContentItem::InputText {
+            text: SUMMARIZATION_TRIGGER.to_string(),
+        }],
+    }));
     let prompt = Prompt {
-        input: turn_input,
+        input,
         store: !turn_context.disable_response_storage,
         tools: Vec::new(),
-        base_instructions_override: Some(compact_instructions.clone()),
+        base_instructions_override: Some(SUMMARIZATION_PROMPT.to_string()),
+        output_schema: None,
+        prompt_vars: PromptVars::default(),
     };
 
     let max_retries = turn_context.client.get_provider().stream_max_retries();
     let mut retries = 0;
-
-    loop {
-        let attempt_result = drain_to_completed(&sess, turn_context, &sub_id, &prompt).await;
-
-        match attempt_result {
-            Ok(()) => break,
-            Err(CodexErr::Interrupted) => return,
-            Err(e) => {
-                if retries < max_retries {
-                    retries += 1;
-                    let delay = backoff(retries);
-                    sess.notify_stream_error(
-                        &sub_id,
-                        format!(
-                            "stream error: {e}; retrying {retries}/{max_retries} in {delay:?}…"
-                        ),
-                    )
-                    .await;
-                    tokio::time::sleep(delay).await;
-                    continue;
-                } else {
-                    let event = Event {
-                        id: sub_id.clone(),
-                        msg: EventMsg::Error(ErrorEvent {
-                            message: e.to_string(),
-                        }),
-                    };
-                    sess.send_event(event).await;
-                    return;
-                }
+    let output = loop {
+        match drain_to_completed(sess, turn_context, sub_id, &prompt).await {
+            Ok(output) => break output,
+            Err(e @ CodexErr::Interrupted) => return Err(e),
+            Err(e) if e.is_retryable() && retries < max_retries => {
+                retries += 1;
+                let delay = backoff(retries);
+                sess.notify_stream_error(
+                    sub_id,
+                    format!("stream error: {e}; retrying {retries}/{max_retries} in {delay:?}…"),
+                )
+                .await;
+                tokio::time::sleep(delay).await;
             }
+            Err(e) => return Err(e),
         }
+    };
+    let Some(mut summary) =
+        get_last_assistant_message_from_turn(&output).filter(|summary| !summary.trim().is_empty())
+    else {
+        return Err(CodexErr::EmptyCompactionSummary);
+    };
+    // The plan lived in the summarized tool calls; carry it over so the model
+    // keeps working from it.
+    if let Some(plan) = sess.state.lock_unchecked().plan.as_ref() {
+        summary.push_str("\n\n");
+        summary.push_str(&plan_checklist(plan));
     }
+    let summary_item = summary_message(&summary);
 
-    sess.remove_task(&sub_id);
-
+    let tokens_before = estimate_items_tokens(summarized, encoding);
+    let tokens_after = estimate_items_tokens(std::slice::from_ref(&summary_item), encoding);
     {
         let mut state = sess.state.lock_unchecked();
-        state.history.keep_last_messages(1);
+        state
+            .history
+            .replace_range(range.clone(), summary_item.clone());
+        state.last_prompt_tokens = None;
     }
+    sess.record_state_snapshot(std::slice::from_ref(&summary_item))
+        .await;
 
     let event = Event {
-        id: sub_id.clone(),
-        msg: EventMsg::AgentMessage(AgentMessageEvent {
-            message: "Compact task completed".to_string(),
-        }),
-    };
-    sess.send_event(event).await;
-    let event = Event {
-        id: sub_id.clone(),
-        msg: EventMsg::TaskComplete(TaskCompleteEvent {
-            last_agent_message: None,
+        id: sub_id.to_string(),
+        msg: EventMsg::ConversationCompacted(ConversationCompactedEvent {
+            summarized_items: range.len(),
+            tokens_before,
+            tokens_after,
+            automatic,
         }),
     };
     sess.send_event(event).await;
+    Ok(())
 }
 
 async fn handle_response_item(
@@ -2022,13 +4108,6 @@ async fn handle_response_item(
         } => {
             let LocalShellAction::Exec(action) = action;
             tracing::info!("LocalShellCall: {action:?}");
-            let params = ShellToolCallParams {
-                command: action.command,
-                workdir: action.working_directory,
-                timeout_ms: action.timeout_ms,
-                with_escalated_permissions: None,
-                justification: None,
-            };
             let effective_call_id = match (call_id, id) {
                 (Some(call_id), _) => call_id,
                 (None, Some(id)) => id,
@@ -2044,7 +4123,9 @@ async fn handle_response_item(
                 }
             };
 
-            let exec_params = to_exec_params(params, turn_context);
+            // The output goes back as a `function_call_output` for the same
+            // call id, which is how the API pairs it with the call.
+            let exec_params = local_shell_exec_params(action, turn_context);
             Some(
                 handle_container_exec_with_params(
                     exec_params,
@@ -2144,9 +4225,39 @@ async fn handle_function_call(
                 }
             };
             let abs = turn_context.resolve_path(Some(args.path));
-            let output = match sess.inject_input(vec![InputItem::LocalImage { path: abs }]) {
+            let mut roots: Vec<PathBuf> = turn_context
+                .sandbox_policy
+                .get_writable_roots_with_cwd(&turn_context.cwd)
+                .into_iter()
+                .map(|writable_root| writable_root.root)
+                .collect();
+            roots.push(turn_context.cwd.clone());
+            let image = read_viewed_image(&abs, &roots).and_then(|image| {
+                let items = vec![InputItem::Image {
+                    image_url: image.data_url.clone(),
+                }];
+                validate_input_images(&items, &turn_context.client.get_model_family())
+                    .map(|()| (image, items))
+            });
+            let (image, items) = match image {
+                Ok(image) => image,
+                Err(message) => {
+                    return ResponseInputItem::FunctionCallOutput {
+                        call_id,
+                        output: FunctionCallOutputPayload {
+                            content: message,
+                            success: Some(false),
+                        },
+                    };
+                }
+            };
+            sess.record_viewed_image(image.path, &image.bytes).await;
+            let output = match sess.inject_input(items) {
                 Ok(()) => FunctionCallOutputPayload {
-                    content: "attached local image path".to_string(),
+                    content: format!(
+                        "attached {}; the image follows this tool output",
+                        abs.display()
+                    ),
                     success: Some(true),
                 },
                 Err(_) => FunctionCallOutputPayload {
@@ -2176,6 +4287,10 @@ async fn handle_function_call(
                 env: HashMap::new(),
                 with_escalated_permissions: None,
                 justification: None,
+                limits: ExecLimits::default(),
+                use_pty: None,
+                pty: ExecPty::default(),
+                stdin: None,
             };
             handle_container_exec_with_params(
                 exec_params,
@@ -2237,6 +4352,32 @@ async fn handle_function_call(
             }
         }
         _ => {
+            if name == PULL_REQUEST_TOOL_NAME
+                && let Some(tool) = &sess.pull_request_tool
+            {
+                return handle_pull_request_call(
+                    tool,
+                    arguments,
+                    sess,
+                    turn_context,
+                    turn_diff_tracker,
+                    sub_id,
+                    call_id,
+                )
+                .await;
+            }
+            if let Some(tool) = sess.command_tools.iter().find(|tool| tool.name == name) {
+                return handle_command_tool_call(
+                    tool,
+                    arguments,
+                    sess,
+                    turn_context,
+                    turn_diff_tracker,
+                    sub_id,
+                    call_id,
+                )
+                .await;
+            }
             match sess.mcp_connection_manager.parse_tool_name(&name) {
                 Some((server, tool_name)) => {
                     // TODO(mbolin): Determine appropriate timeout for tool call.
@@ -2261,55 +4402,419 @@ async fn handle_function_call(
     }
 }
 
-async fn handle_custom_tool_call(
+async fn handle_custom_tool_call(
+    sess: &Session,
+    turn_context: &TurnContext,
+    turn_diff_tracker: &mut TurnDiffTracker,
+    sub_id: String,
+    name: String,
+    input: String,
+    call_id: String,
+) -> ResponseInputItem {
+    info!("CustomToolCall: {name} {input}");
+    match name.as_str() {
+        "apply_patch" => {
+            let exec_params = ExecParams {
+                command: vec!["apply_patch".to_string(), input.clone()],
+                cwd: turn_context.cwd.clone(),
+                timeout_ms: None,
+                env: HashMap::new(),
+                with_escalated_permissions: None,
+                justification: None,
+                limits: ExecLimits::default(),
+                use_pty: None,
+                pty: ExecPty::default(),
+                stdin: None,
+            };
+            let resp = handle_container_exec_with_params(
+                exec_params,
+                sess,
+                turn_context,
+                turn_diff_tracker,
+                sub_id,
+                call_id,
+            )
+            .await;
+
+            // Convert function-call style output into a custom tool call output
+            match resp {
+                ResponseInputItem::FunctionCallOutput { call_id, output } => {
+                    ResponseInputItem::CustomToolCallOutput {
+                        call_id,
+                        output: output.content,
+                    }
+                }
+                // Pass through if already a custom tool output or other variant
+                other => other,
+            }
+        }
+        _ => {
+            debug!("unexpected CustomToolCall from stream");
+            ResponseInputItem::CustomToolCallOutput {
+                call_id,
+                output: format!("unsupported custom tool call: {name}"),
+            }
+        }
+    }
+}
+
+/// Runs a tool from `[tools.custom]`. The command keeps the sandbox of the
+/// current policy even when the user is asked to approve it.
+async fn handle_command_tool_call(
+    tool: &CommandTool,
+    arguments: String,
+    sess: &Session,
+    turn_context: &TurnContext,
+    turn_diff_tracker: &mut TurnDiffTracker,
+    sub_id: String,
+    call_id: String,
+) -> ResponseInputItem {
+    let failure = |content: String| ResponseInputItem::FunctionCallOutput {
+        call_id: call_id.clone(),
+        output: FunctionCallOutputPayload {
+            content,
+            success: Some(false),
+        },
+    };
+    let invocation = match tool.invocation(&arguments) {
+        Ok(invocation) => invocation,
+        Err(message) => return failure(message),
+    };
+    let mut params = ExecParams {
+        command: invocation.command,
+        cwd: turn_context.cwd.clone(),
+        timeout_ms: Some(tool.timeout_ms.unwrap_or(sess.exec_timeout_ms)),
+        env: turn_context.exec_env(),
+        with_escalated_permissions: None,
+        justification: None,
+        limits: sess.exec_limits,
+        use_pty: Some(false),
+        pty: ExecPty::default(),
+        stdin: invocation.stdin,
+    };
+    sess.export_sandbox_dirs(&mut params.env, &turn_context.sandbox_policy);
+
+    let sandbox_type = match turn_context.sandbox_policy {
+        SandboxPolicy::DangerFullAccess => SandboxType::None,
+        _ => get_platform_sandbox().unwrap_or(SandboxType::None),
+    };
+    let approved_for_session = sess
+        .state
+        .lock_unchecked()
+        .approved_command_tools
+        .contains(&tool.name);
+    let escalation_reason = match assess_safety_for_untrusted_command(
+        turn_context.approval_policy,
+        &turn_context.sandbox_policy,
+        false,
+    ) {
+        SafetyCheck::Reject { reason } => {
+            return failure(format!("{} call rejected: {reason}", tool.name));
+        }
+        _ if approved_for_session => None,
+        SafetyCheck::AskUser { reason } => Some(reason),
+        SafetyCheck::AutoApprove { .. } if tool.requires_approval => {
+            if turn_context.approval_policy == AskForApproval::Never {
+                return failure(format!(
+                    "{} call rejected: the tool requires approval, which the approval policy never asks for",
+                    tool.name
+                ));
+            }
+            Some(EscalationReason::ToolRequiresApproval {
+                tool: tool.name.clone(),
+            })
+        }
+        SafetyCheck::AutoApprove { .. } => sess
+            .needs_write_confirmation(turn_context)
+            .then_some(EscalationReason::FirstWriteOutsideGit),
+    };
+
+    if let Some(escalation_reason) = escalation_reason {
+        let justification = match escalation_reason {
+            EscalationReason::FirstWriteOutsideGit => FIRST_WRITE_CONFIRMATION_REASON.to_string(),
+            _ => format!("Codex wants to call the `{}` tool.", tool.name),
+        };
+        let rx_approve = sess
+            .request_command_approval(
+                sub_id.clone(),
+                call_id.clone(),
+                params.command.clone(),
+                params.cwd.clone(),
+                Some(justification),
+                Some(escalation_reason.clone()),
+            )
+            .await;
+        let response = rx_approve.await.unwrap_or_default();
+        match response.decision {
+            ReviewDecision::Approved => sess.write_confirmed(),
+            ReviewDecision::ApprovedForSession => {
+                sess.write_confirmed();
+                sess.state
+                    .lock_unchecked()
+                    .approved_command_tools
+                    .insert(tool.name.clone());
+            }
+            ReviewDecision::Denied | ReviewDecision::Abort => {
+                return failure(response.rejection_message(format!(
+                    "{} call rejected by user. {}",
+                    tool.name,
+                    escalation_note(&escalation_reason)
+                )));
+            }
+        }
+    }
+
+    let exec_command_context = ExecCommandContext {
+        sub_id: sub_id.clone(),
+        call_id: call_id.clone(),
+        command_for_display: params.command.clone(),
+        cwd: params.cwd.clone(),
+        apply_patch: None,
+    };
+    let output_result = sess
+        .run_exec_with_events(
+            turn_diff_tracker,
+            exec_command_context.clone(),
+            ExecInvokeArgs {
+                params: params.clone(),
+                sandbox_type,
+                sandbox_policy: &turn_context.sandbox_policy,
+                codex_linux_sandbox_exe: &sess.codex_linux_sandbox_exe,
+                stdout_stream: Some(StdoutStream {
+                    sub_id: sub_id.clone(),
+                    call_id: call_id.clone(),
+                    tx_event: sess.tx_event.clone(),
+                }),
+            },
+        )
+        .await;
+
+    match output_result {
+        // A successful call returns what the command printed on stdout; a
+        // failed one also shows stderr and the exit code.
+        Ok(output) if output.exit_code == 0 => {
+            let stdout = ExecToolCallOutput {
+                aggregated_output: StreamOutput::new(output.stdout.text.clone()),
+                ..output
+            };
+            ResponseInputItem::FunctionCallOutput {
+                call_id,
+                output: FunctionCallOutputPayload {
+                    content: format_exec_output_str(&stdout, sess.exec_output_limits),
+                    success: Some(true),
+                },
+            }
+        }
+        Ok(output) => failure(format_exec_output(&output, sess.exec_output_limits, None)),
+        Err(CodexErr::Sandbox(error)) => {
+            handle_sandbox_error(
+                turn_diff_tracker,
+                params,
+                exec_command_context,
+                error,
+                sandbox_type,
+                sess,
+                turn_context,
+            )
+            .await
+        }
+        Err(e) => failure(format!("execution error: {e}")),
+    }
+}
+
+/// Runs the `create_pull_request` script. It has to write `.git` and reach
+/// the remote, so it runs in a sandbox that allows both on top of the
+/// policy, and needs approval whenever the policy is a sandboxed one.
+async fn handle_pull_request_call(
+    tool: &PullRequestTool,
+    arguments: String,
     sess: &Session,
     turn_context: &TurnContext,
     turn_diff_tracker: &mut TurnDiffTracker,
     sub_id: String,
-    name: String,
-    input: String,
     call_id: String,
 ) -> ResponseInputItem {
-    info!("CustomToolCall: {name} {input}");
-    match name.as_str() {
-        "apply_patch" => {
-            let exec_params = ExecParams {
-                command: vec!["apply_patch".to_string(), input.clone()],
-                cwd: turn_context.cwd.clone(),
-                timeout_ms: None,
-                env: HashMap::new(),
-                with_escalated_permissions: None,
-                justification: None,
+    let failure = |content: String| ResponseInputItem::FunctionCallOutput {
+        call_id: call_id.clone(),
+        output: FunctionCallOutputPayload {
+            content,
+            success: Some(false),
+        },
+    };
+    let (args, script) = match tool
+        .parse_args(&arguments)
+        .and_then(|args| tool.script(&args).map(|script| (args, script)))
+    {
+        Ok(plan) => plan,
+        Err(message) => return failure(message),
+    };
+    let noun = tool.provider.request_noun();
+    let pull_request_event = |url: Option<String>, dry_run_script: Option<String>| Event {
+        id: sub_id.clone(),
+        msg: EventMsg::PullRequest(PullRequestEvent {
+            call_id: call_id.clone(),
+            provider: tool.provider,
+            title: args.title.clone(),
+            head: args.head.clone(),
+            base: args.base.clone(),
+            url,
+            dry_run_script,
+        }),
+    };
+
+    if tool.dry_run {
+        sess.send_event(pull_request_event(None, Some(script.clone())))
+            .await;
+        return ResponseInputItem::FunctionCallOutput {
+            call_id: call_id.clone(),
+            output: FunctionCallOutputPayload {
+                content: format!(
+                    "Dry run: nothing was committed or pushed and no {noun} was opened. The call would have run:
+
+{script}"
+                ),
+                success: Some(true),
+            },
+        };
+    }
+
+    let Some(git_dir) = resolve_git_common_dir(&turn_context.cwd) else {
+        return failure(format!(
+            "{PULL_REQUEST_TOOL_NAME} needs a git repository, and {} is not in one",
+            turn_context.cwd.display()
+        ));
+    };
+    let command = vec!["sh".to_string(), "-c".to_string(), script];
+    let sandboxed = !matches!(turn_context.sandbox_policy, SandboxPolicy::DangerFullAccess);
+    let approved_for_session = sess
+        .state
+        .lock_unchecked()
+        .approved_command_tools
+        .contains(PULL_REQUEST_TOOL_NAME);
+    let needs_approval = sandboxed
+        || tool.requires_approval
+        || turn_context.approval_policy == AskForApproval::UnlessTrusted;
+    if needs_approval && !approved_for_session {
+        if turn_context.approval_policy == AskForApproval::Never {
+            let what = if sandboxed {
+                "to write .git and reach the network from the sandbox"
+            } else {
+                "before every call"
             };
-            let resp = handle_container_exec_with_params(
-                exec_params,
-                sess,
-                turn_context,
-                turn_diff_tracker,
-                sub_id,
-                call_id,
+            return failure(format!(
+                "{PULL_REQUEST_TOOL_NAME} call rejected: the tool needs approval {what}, which the approval policy never asks for"
+            ));
+        }
+        // The sandbox is widened rather than left, so only a tool that does
+        // not itself require approval is asked about as an escalation.
+        let escalation_reason = if sandboxed && !tool.requires_approval {
+            EscalationReason::EscalationRequested
+        } else {
+            EscalationReason::ToolRequiresApproval {
+                tool: PULL_REQUEST_TOOL_NAME.to_string(),
+            }
+        };
+        let rx_approve = sess
+            .request_command_approval(
+                sub_id.clone(),
+                call_id.clone(),
+                command.clone(),
+                turn_context.cwd.clone(),
+                Some(format!(
+                    "Codex wants to push `{}` to `{}` and open a {noun}.",
+                    args.head, tool.remote
+                )),
+                Some(escalation_reason.clone()),
             )
             .await;
-
-            // Convert function-call style output into a custom tool call output
-            match resp {
-                ResponseInputItem::FunctionCallOutput { call_id, output } => {
-                    ResponseInputItem::CustomToolCallOutput {
-                        call_id,
-                        output: output.content,
-                    }
-                }
-                // Pass through if already a custom tool output or other variant
-                other => other,
+        let response = rx_approve.await.unwrap_or_default();
+        match response.decision {
+            ReviewDecision::Approved => sess.write_confirmed(),
+            ReviewDecision::ApprovedForSession => {
+                sess.write_confirmed();
+                sess.state
+                    .lock_unchecked()
+                    .approved_command_tools
+                    .insert(PULL_REQUEST_TOOL_NAME.to_string());
+            }
+            ReviewDecision::Denied | ReviewDecision::Abort => {
+                return failure(response.rejection_message(format!(
+                    "{PULL_REQUEST_TOOL_NAME} call rejected by user. {}",
+                    escalation_note(&escalation_reason)
+                )));
             }
         }
-        _ => {
-            debug!("unexpected CustomToolCall from stream");
-            ResponseInputItem::CustomToolCallOutput {
-                call_id,
-                output: format!("unsupported custom tool call: {name}"),
+    }
+
+    let sandbox_policy = sandbox_policy_for_publishing(&turn_context.sandbox_policy, &git_dir);
+    let sandbox_type = match sandbox_policy {
+        SandboxPolicy::DangerFullAccess => SandboxType::None,
+        _ => get_platform_sandbox().unwrap_or(SandboxType::None),
+    };
+    let mut env = turn_context.exec_env();
+    env.extend(tool.env());
+    sess.export_sandbox_dirs(&mut env, &sandbox_policy);
+    let params = ExecParams {
+        command,
+        cwd: turn_context.cwd.clone(),
+        timeout_ms: Some(tool.timeout_ms.unwrap_or(sess.exec_timeout_ms)),
+        env,
+        with_escalated_permissions: None,
+        justification: None,
+        limits: sess.exec_limits,
+        use_pty: Some(false),
+        pty: ExecPty::default(),
+        stdin: None,
+    };
+    let exec_command_context = ExecCommandContext {
+        sub_id: sub_id.clone(),
+        call_id: call_id.clone(),
+        command_for_display: params.command.clone(),
+        cwd: params.cwd.clone(),
+        apply_patch: None,
+    };
+    let output_result = sess
+        .run_exec_with_events(
+            turn_diff_tracker,
+            exec_command_context,
+            ExecInvokeArgs {
+                params,
+                sandbox_type,
+                sandbox_policy: &sandbox_policy,
+                codex_linux_sandbox_exe: &sess.codex_linux_sandbox_exe,
+                stdout_stream: Some(StdoutStream {
+                    sub_id: sub_id.clone(),
+                    call_id: call_id.clone(),
+                    tx_event: sess.tx_event.clone(),
+                }),
+            },
+        )
+        .await;
+
+    match output_result {
+        Ok(output) if output.exit_code == 0 => {
+            let content = match find_pull_request_url(&output.stdout.text) {
+                Some(url) => {
+                    sess.send_event(pull_request_event(Some(url.clone()), None))
+                        .await;
+                    format!("Opened {noun} {url}")
+                }
+                None => format!(
+                    "The script succeeded but printed no {noun} URL:
+{}",
+                    format_exec_output_str(&output, sess.exec_output_limits)
+                ),
+            };
+            ResponseInputItem::FunctionCallOutput {
+                call_id: call_id.clone(),
+                output: FunctionCallOutputPayload {
+                    content,
+                    success: Some(true),
+                },
             }
         }
+        Ok(output) => failure(format_exec_output(&output, sess.exec_output_limits, None)),
+        Err(e) => failure(format!("execution error: {e}")),
     }
 }
 
@@ -2318,12 +4823,32 @@ fn to_exec_params(params: ShellToolCallParams, turn_context: &TurnContext) -> Ex
         command: params.command,
         cwd: turn_context.resolve_path(params.workdir.clone()),
         timeout_ms: params.timeout_ms,
-        env: create_env(&turn_context.shell_environment_policy),
+        env: turn_context.exec_env(),
         with_escalated_permissions: params.with_escalated_permissions,
         justification: params.justification,
+        limits: ExecLimits::default(),
+        use_pty: params.tty,
+        pty: ExecPty::default(),
+        stdin: None,
     }
 }
 
+/// Exec parameters for a `local_shell_call` action. Variables the model sets
+/// on the action are applied on top of the shell environment policy.
+fn local_shell_exec_params(action: LocalShellExecAction, turn_context: &TurnContext) -> ExecParams {
+    let params = ShellToolCallParams {
+        command: action.command,
+        workdir: action.working_directory,
+        timeout_ms: action.timeout_ms,
+        with_escalated_permissions: None,
+        justification: None,
+        tty: None,
+    };
+    let mut exec_params = to_exec_params(params, turn_context);
+    exec_params.env.extend(action.env.unwrap_or_default());
+    exec_params
+}
+
 fn parse_container_exec_arguments(
     arguments: String,
     turn_context: &TurnContext,
@@ -2346,6 +4871,19 @@ fn parse_container_exec_arguments(
     }
 }
 
+/// Creates the scratch directory of session `session_id` under `cache_dir`,
+/// readable by the current user only.
+fn create_scratch_dir(cache_dir: &Path, session_id: Uuid) -> std::io::Result<PathBuf> {
+    let dir = cache_dir.join(SCRATCH_SUBDIR).join(session_id.to_string());
+    std::fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
+
 pub struct ExecInvokeArgs<'a> {
     pub params: ExecParams,
     pub sandbox_type: SandboxType,
@@ -2354,13 +4892,36 @@ pub struct ExecInvokeArgs<'a> {
     pub stdout_stream: Option<StdoutStream>,
 }
 
+/// What the environment context says about the shell profile: whether
+/// commands get a snapshot of its environment or run under the user's shell.
+fn shell_profile_description(
+    user_shell: &crate::shell::Shell,
+    turn_context: &TurnContext,
+) -> Option<String> {
+    if !turn_context.shell_environment_policy.use_profile {
+        return None;
+    }
+    let shell_name = user_shell.name()?;
+    if turn_context.profile_env.is_some() {
+        return Some(format!(
+            "{shell_name} login environment (variables only; aliases and functions are not loaded)"
+        ));
+    }
+    user_shell
+        .format_default_shell_invocation(vec!["true".to_string()])
+        .map(|_| format!("each command runs in `{shell_name} -lc` after its rc file"))
+}
+
 fn maybe_translate_shell_command(
     params: ExecParams,
     sess: &Session,
     turn_context: &TurnContext,
 ) -> ExecParams {
+    // With a snapshot of the profile's environment there is no need to run
+    // each command under the user's shell.
     let should_translate = matches!(sess.user_shell, crate::shell::Shell::PowerShell(_))
-        || turn_context.shell_environment_policy.use_profile;
+        || (turn_context.shell_environment_policy.use_profile
+            && turn_context.profile_env.is_none());
 
     if should_translate
         && let Some(command) = sess
@@ -2373,15 +4934,25 @@ fn maybe_translate_shell_command(
 }
 
 async fn handle_container_exec_with_params(
-    params: ExecParams,
+    mut params: ExecParams,
     sess: &Session,
     turn_context: &TurnContext,
     turn_diff_tracker: &mut TurnDiffTracker,
     sub_id: String,
     call_id: String,
 ) -> ResponseInputItem {
+    params.timeout_ms.get_or_insert(sess.exec_timeout_ms);
+    sess.export_sandbox_dirs(&mut params.env, &turn_context.sandbox_policy);
+    params.limits = sess.exec_limits;
+    params.use_pty.get_or_insert(sess.exec_use_pty);
+    params.pty = sess.exec_pty.clone();
+
     // check if this was a patch, and apply it if so
-    let apply_patch_exec = match maybe_parse_apply_patch_verified(&params.command, &params.cwd) {
+    let apply_patch_exec = match maybe_parse_apply_patch_verified_with_strategy(
+        &params.command,
+        &params.cwd,
+        sess.apply_patch_loosest_match,
+    ) {
         MaybeApplyPatchVerified::Body(changes) => {
             match apply_patch::apply_patch(sess, turn_context, &sub_id, &call_id, changes).await {
                 InternalApplyPatchInvocation::Output(item) => return item,
@@ -2413,6 +4984,7 @@ async fn handle_container_exec_with_params(
         Some(ApplyPatchExec {
             action: ApplyPatchAction { patch, cwd, .. },
             user_explicitly_approved_this_action,
+            ..
         }) => {
             let path_to_codex = std::env::current_exe()
                 .ok()
@@ -2438,10 +5010,20 @@ async fn handle_container_exec_with_params(
                 env: HashMap::new(),
                 with_escalated_permissions: params.with_escalated_permissions,
                 justification: params.justification.clone(),
+                limits: params.limits,
+                use_pty: Some(false),
+                pty: ExecPty::default(),
+                stdin: None,
             };
+            // An approved patch still runs sandboxed where possible, with
+            // write access to the approved paths added for this call only.
             let safety = if *user_explicitly_approved_this_action {
+                let sandbox_type = match turn_context.sandbox_policy {
+                    SandboxPolicy::DangerFullAccess => None,
+                    _ => get_platform_sandbox(),
+                };
                 SafetyCheck::AutoApprove {
-                    sandbox_type: SandboxType::None,
+                    sandbox_type: sandbox_type.unwrap_or(SandboxType::None),
                 }
             } else {
                 assess_safety_for_untrusted_command(
@@ -2472,28 +5054,76 @@ async fn handle_container_exec_with_params(
         }
     };
 
+    // Outside a clean git repository, the first command that may write is
+    // confirmed even when the policies would let it run.
+    // The command keeps the sandbox it would have run in.
+    let confirmed_sandbox_type = match safety {
+        SafetyCheck::AutoApprove { sandbox_type }
+            if sess.needs_write_confirmation(turn_context)
+                && !sess.is_known_safe_command(&params.command) =>
+        {
+            Some(sandbox_type)
+        }
+        _ => None,
+    };
+    let safety = if confirmed_sandbox_type.is_some() {
+        SafetyCheck::AskUser {
+            reason: EscalationReason::FirstWriteOutsideGit,
+        }
+    } else {
+        safety
+    };
+
     let sandbox_type = match safety {
         SafetyCheck::AutoApprove { sandbox_type } => sandbox_type,
-        SafetyCheck::AskUser => {
+        SafetyCheck::AskUser {
+            reason: escalation_reason,
+        } => {
+            let reason = if confirmed_sandbox_type.is_some() {
+                Some(FIRST_WRITE_CONFIRMATION_REASON.to_string())
+            } else {
+                params.justification.clone()
+            };
             let rx_approve = sess
                 .request_command_approval(
                     sub_id.clone(),
                     call_id.clone(),
                     params.command.clone(),
                     params.cwd.clone(),
-                    params.justification.clone(),
+                    reason,
+                    Some(escalation_reason.clone()),
                 )
                 .await;
-            match rx_approve.await.unwrap_or_default() {
+            let response = rx_approve.await.unwrap_or_default();
+            let decision = response.decision;
+            if matches!(
+                decision,
+                ReviewDecision::Approved | ReviewDecision::ApprovedForSession
+            ) {
+                sess.write_confirmed();
+            }
+            match decision {
                 ReviewDecision::Approved => (),
                 ReviewDecision::ApprovedForSession => {
-                    sess.add_approved_command(params.command.clone());
+                    // A patch is only ever approved as is; any other command
+                    // is remembered by its prefix.
+                    if apply_patch_exec.is_some() {
+                        sess.add_approved_command(params.command.clone());
+                    } else {
+                        sess.approve_command_for_session(
+                            params.command.clone(),
+                            &turn_context.sandbox_policy,
+                        );
+                    }
                 }
                 ReviewDecision::Denied | ReviewDecision::Abort => {
                     return ResponseInputItem::FunctionCallOutput {
                         call_id,
                         output: FunctionCallOutputPayload {
-                            content: "exec command rejected by user".to_string(),
+                            content: response.rejection_message(format!(
+                                "exec command rejected by user. {}",
+                                escalation_note(&escalation_reason)
+                            )),
                             success: None,
                         },
                     };
@@ -2503,7 +5133,16 @@ async fn handle_container_exec_with_params(
             // explicit approval. Often, we end up in this case because
             // the command cannot be run in a sandbox, such as
             // installing a new dependency that requires network access.
-            SandboxType::None
+            // The exception is a network command asked about only because
+            // the sandbox cannot restrict the network here: it keeps the
+            // sandbox, which leaves it the network but not the filesystem.
+            let network_sandbox_type = match escalation_reason {
+                EscalationReason::NetworkNeeded => get_platform_sandbox(),
+                _ => None,
+            };
+            confirmed_sandbox_type
+                .or(network_sandbox_type)
+                .unwrap_or(SandboxType::None)
         }
         SafetyCheck::Reject { reason } => {
             return ResponseInputItem::FunctionCallOutput {
@@ -2516,6 +5155,19 @@ async fn handle_container_exec_with_params(
         }
     };
 
+    let exempted_sandbox_policy = apply_patch_exec
+        .as_ref()
+        .filter(|apply_patch_exec| !apply_patch_exec.write_exemptions.is_empty())
+        .map(|apply_patch_exec| {
+            sandbox_policy_with_write_exemptions(
+                &turn_context.sandbox_policy,
+                &apply_patch_exec.write_exemptions,
+            )
+        });
+    let sandbox_policy = exempted_sandbox_policy
+        .as_ref()
+        .unwrap_or(&turn_context.sandbox_policy);
+
     let exec_command_context = ExecCommandContext {
         sub_id: sub_id.clone(),
         call_id: call_id.clone(),
@@ -2525,9 +5177,13 @@ async fn handle_container_exec_with_params(
             |ApplyPatchExec {
                  action,
                  user_explicitly_approved_this_action,
+                 rejection_note,
+                 ..
              }| ApplyPatchCommandContext {
                 user_explicitly_approved_this_action,
                 changes: convert_apply_patch_to_protocol(&action),
+                match_strategies: apply_patch_match_strategies(&action),
+                rejection_note,
             },
         ),
     };
@@ -2540,7 +5196,7 @@ async fn handle_container_exec_with_params(
             ExecInvokeArgs {
                 params: params.clone(),
                 sandbox_type,
-                sandbox_policy: &turn_context.sandbox_policy,
+                sandbox_policy,
                 codex_linux_sandbox_exe: &sess.codex_linux_sandbox_exe,
                 stdout_stream: if exec_command_context.apply_patch.is_some() {
                     None
@@ -2560,7 +5216,11 @@ async fn handle_container_exec_with_params(
             let ExecToolCallOutput { exit_code, .. } = &output;
 
             let is_success = *exit_code == 0;
-            let content = format_exec_output(&output);
+            let content = format_exec_output(
+                &output,
+                sess.exec_output_limits,
+                exec_command_context.rejection_note(),
+            );
             ResponseInputItem::FunctionCallOutput {
                 call_id: call_id.clone(),
                 output: FunctionCallOutputPayload {
@@ -2603,6 +5263,7 @@ async fn handle_sandbox_error(
     let call_id = exec_command_context.call_id.clone();
     let sub_id = exec_command_context.sub_id.clone();
     let cwd = exec_command_context.cwd.clone();
+    let escalation_reason = sandbox_denial_reason(&error, &cwd);
 
     // Early out if either the user never wants to be asked for approval, or
     // we're letting the model manage escalation requests. Otherwise, continue
@@ -2612,7 +5273,8 @@ async fn handle_sandbox_error(
                 call_id,
                 output: FunctionCallOutputPayload {
                     content: format!(
-                        "failed in sandbox {sandbox_type:?} with execution error: {error}"
+                        "failed in sandbox {sandbox_type:?} with execution error: {error}\n{}",
+                        escalation_note(&escalation_reason)
                     ),
                     success: Some(false),
                 },
@@ -2621,20 +5283,6 @@ async fn handle_sandbox_error(
         AskForApproval::UnlessTrusted | AskForApproval::OnFailure => (),
     }
 
-    // similarly, if the command timed out, we can simply return this failure to the model
-    if matches!(error, SandboxErr::Timeout) {
-        return ResponseInputItem::FunctionCallOutput {
-            call_id,
-            output: FunctionCallOutputPayload {
-                content: format!(
-                    "command timed out after {} milliseconds",
-                    params.timeout_duration().as_millis()
-                ),
-                success: Some(false),
-            },
-        };
-    }
-
     // Note that when `error` is `SandboxErr::Denied`, it could be a false
     // positive. That is, it may have exited with a non-zero exit code, not
     // because the sandbox denied it, but because that is its expected behavior,
@@ -2654,10 +5302,12 @@ async fn handle_sandbox_error(
             params.command.clone(),
             cwd.clone(),
             Some("command failed; retry without sandbox?".to_string()),
+            Some(escalation_reason.clone()),
         )
         .await;
 
-    match rx_approve.await.unwrap_or_default() {
+    let response = rx_approve.await.unwrap_or_default();
+    match response.decision {
         ReviewDecision::Approved | ReviewDecision::ApprovedForSession => {
             // Persist this command as pre‑approved for the
             // remainder of the session so future
@@ -2697,7 +5347,11 @@ async fn handle_sandbox_error(
                     let ExecToolCallOutput { exit_code, .. } = &retry_output;
 
                     let is_success = *exit_code == 0;
-                    let content = format_exec_output(&retry_output);
+                    let content = format_exec_output(
+                        &retry_output,
+                        sess.exec_output_limits,
+                        exec_command_context.rejection_note(),
+                    );
 
                     ResponseInputItem::FunctionCallOutput {
                         call_id: call_id.clone(),
@@ -2721,7 +5375,10 @@ async fn handle_sandbox_error(
             ResponseInputItem::FunctionCallOutput {
                 call_id,
                 output: FunctionCallOutputPayload {
-                    content: "exec command rejected by user".to_string(),
+                    content: response.rejection_message(format!(
+                        "exec command rejected by user. {}",
+                        escalation_note(&escalation_reason)
+                    )),
                     success: None,
                 },
             }
@@ -2729,65 +5386,70 @@ async fn handle_sandbox_error(
     }
 }
 
-fn format_exec_output_str(exec_output: &ExecToolCallOutput) -> String {
+fn exceeds_exec_output_limits(s: &str, limits: ExecOutputLimits) -> bool {
+    s.len() > limits.max_bytes || s.lines().count() > limits.max_lines
+}
+
+fn format_exec_output_str(exec_output: &ExecToolCallOutput, limits: ExecOutputLimits) -> String {
     let ExecToolCallOutput {
-        aggregated_output, ..
+        aggregated_output,
+        full_output_path,
+        ..
     } = exec_output;
 
     // Head+tail truncation for the model: show the beginning and end with an elision.
     // Clients still receive full streams; only this formatted summary is capped.
 
-    let s = aggregated_output.text.as_str();
-    let total_lines = s.lines().count();
-    if s.len() <= MODEL_FORMAT_MAX_BYTES && total_lines <= MODEL_FORMAT_MAX_LINES {
+    // Terminal escapes are noise to the model; the full output spooled to
+    // disk keeps them.
+    let text = strip_terminal_escapes(&aggregated_output.text);
+    let s = text.as_ref();
+    if !exceeds_exec_output_limits(s, limits) {
         return s.to_string();
     }
-
-    let lines: Vec<&str> = s.lines().collect();
-    let head_take = MODEL_FORMAT_HEAD_LINES.min(lines.len());
-    let tail_take = MODEL_FORMAT_TAIL_LINES.min(lines.len().saturating_sub(head_take));
-    let omitted = lines.len().saturating_sub(head_take + tail_take);
-
-    // Join head and tail blocks (lines() strips newlines; reinsert them)
-    let head_block = lines
-        .iter()
-        .take(head_take)
-        .cloned()
-        .collect::<Vec<_>>()
-        .join("\n");
-    let tail_block = if tail_take > 0 {
-        lines[lines.len() - tail_take..].join("\n")
-    } else {
-        String::new()
+    let total_lines = s.lines().count();
+    let total_bytes = s.len();
+    let marker = |omitted_lines: usize, omitted_bytes: usize| {
+        let full_output = match full_output_path {
+            Some(path) => format!("; full output in {}", path.display()),
+            None => String::new(),
+        };
+        format!(
+            "\n[... omitted {omitted_lines} of {total_lines} lines, {omitted_bytes} of {total_bytes} bytes{full_output} ...]\n\n"
+        )
     };
-    let marker = format!("\n[... omitted {omitted} of {total_lines} lines ...]\n\n");
-
-    // Byte budgets for head/tail around the marker
-    let mut head_budget = MODEL_FORMAT_HEAD_BYTES.min(MODEL_FORMAT_MAX_BYTES);
-    let tail_budget = MODEL_FORMAT_MAX_BYTES.saturating_sub(head_budget + marker.len());
-    if tail_budget == 0 && marker.len() >= MODEL_FORMAT_MAX_BYTES {
-        // Degenerate case: marker alone exceeds budget; return a clipped marker
-        return take_bytes_at_char_boundary(&marker, MODEL_FORMAT_MAX_BYTES).to_string();
-    }
-    if tail_budget == 0 {
-        // Make room for the marker by shrinking head
-        head_budget = MODEL_FORMAT_MAX_BYTES.saturating_sub(marker.len());
-    }
-
-    // Enforce line-count cap by trimming head/tail lines
-    let head_lines_text = head_block;
-    let tail_lines_text = tail_block;
-    // Build final string respecting byte budgets
-    let head_part = take_bytes_at_char_boundary(&head_lines_text, head_budget);
-    let mut result = String::with_capacity(MODEL_FORMAT_MAX_BYTES.min(s.len()));
-    result.push_str(head_part);
-    result.push_str(&marker);
-
-    let remaining = MODEL_FORMAT_MAX_BYTES.saturating_sub(result.len());
-    let tail_budget_final = remaining;
-    let tail_part = take_last_bytes_at_char_boundary(&tail_lines_text, tail_budget_final);
-    result.push_str(tail_part);
 
+    // Keep the first half of the allowed lines and the last half.
+    let head_lines = limits.max_lines / 2;
+    let tail_lines = limits.max_lines - head_lines;
+    // When only the byte limit is exceeded these regions overlap, but the byte
+    // budgets below still keep the head and tail apart.
+    let head_end = match head_lines.checked_sub(1) {
+        Some(n) => s.match_indices('\n').nth(n).map_or(s.len(), |(i, _)| i),
+        None => 0,
+    };
+    let tail_start = s
+        .trim_end_matches('\n')
+        .rmatch_indices('\n')
+        .nth(tail_lines.saturating_sub(1))
+        .map_or(0, |(i, _)| i + 1);
+
+    // The marker can only get shorter than this, so reserving room for it
+    // keeps the result within the byte limit.
+    let worst_case_marker = marker(total_lines, total_bytes);
+    if worst_case_marker.len() >= limits.max_bytes {
+        return take_bytes_at_char_boundary(&worst_case_marker, limits.max_bytes).to_string();
+    }
+    let content_budget = limits.max_bytes - worst_case_marker.len();
+    let head = take_bytes_at_char_boundary(&s[..head_end], content_budget / 2);
+    let tail = take_last_bytes_at_char_boundary(&s[tail_start..], content_budget - head.len());
+
+    let omitted = &s[head.len()..s.len() - tail.len()];
+    let omitted_lines = total_lines.saturating_sub(head.lines().count() + tail.lines().count());
+    let mut result = String::with_capacity(limits.max_bytes);
+    result.push_str(head);
+    result.push_str(&marker(omitted_lines, omitted.len()));
+    result.push_str(tail);
     result
 }
 
@@ -2831,10 +5493,17 @@ fn take_last_bytes_at_char_boundary(s: &str, maxb: usize) -> &str {
 }
 
 /// Exec output is a pre-serialized JSON payload
-fn format_exec_output(exec_output: &ExecToolCallOutput) -> String {
+/// `note` is appended to the output, for what the model should know about
+/// the call besides what the command printed.
+fn format_exec_output(
+    exec_output: &ExecToolCallOutput,
+    limits: ExecOutputLimits,
+    note: Option<&str>,
+) -> String {
     let ExecToolCallOutput {
         exit_code,
         duration,
+        terminated,
         ..
     } = exec_output;
 
@@ -2842,6 +5511,9 @@ fn format_exec_output(exec_output: &ExecToolCallOutput) -> String {
     struct ExecMetadata {
         exit_code: i32,
         duration_seconds: f32,
+        /// Why Codex killed the command, in which case the output is partial.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        terminated: Option<String>,
     }
 
     #[derive(Serialize)]
@@ -2853,13 +5525,17 @@ fn format_exec_output(exec_output: &ExecToolCallOutput) -> String {
     // round to 1 decimal place
     let duration_seconds = ((duration.as_secs_f32()) * 10.0).round() / 10.0;
 
-    let formatted_output = format_exec_output_str(exec_output);
+    let mut formatted_output = format_exec_output_str(exec_output, limits);
+    if let Some(note) = note {
+        formatted_output.push_str(&format!("\n\n{note}"));
+    }
 
     let payload = ExecOutput {
         output: &formatted_output,
         metadata: ExecMetadata {
             exit_code: *exit_code,
             duration_seconds,
+            terminated: terminated.map(|terminated| format!("command {terminated}")),
         },
     };
 
@@ -2892,26 +5568,24 @@ async fn drain_to_completed(
     turn_context: &TurnContext,
     sub_id: &str,
     prompt: &Prompt,
-) -> CodexResult<()> {
+) -> CodexResult<Vec<ResponseItem>> {
     let mut stream = turn_context.client.clone().stream(prompt).await?;
+    let mut output = Vec::new();
     loop {
         let maybe_event = stream.next().await;
         let Some(event) = maybe_event else {
-            return Err(CodexErr::Stream(
-                "stream closed before response.completed".into(),
-                None,
+            return Err(CodexErr::stream_disconnected(
+                "stream closed before response.completed",
             ));
         };
         match event {
-            Ok(ResponseEvent::OutputItemDone(item)) => {
-                // Record only to in-memory conversation history; avoid state snapshot.
-                let mut state = sess.state.lock_unchecked();
-                state.history.record_items(std::slice::from_ref(&item));
-            }
+            Ok(ResponseEvent::OutputItemDone(item)) => output.push(item),
             Ok(ResponseEvent::Completed {
                 response_id: _,
                 token_usage,
             }) => {
+                sess.commit_token_usage(sub_id, turn_context, token_usage.clone())
+                    .await;
                 // some providers don't return token usage, so we default
                 // TODO: consider approximate token usage
                 let token_usage = token_usage.unwrap_or_default();
@@ -2923,7 +5597,7 @@ async fn drain_to_completed(
                     .await
                     .ok();
 
-                return Ok(());
+                return Ok(output);
             }
             Ok(_) => continue,
             Err(e) => return Err(e),
@@ -2969,6 +5643,7 @@ fn convert_call_tool_result_to_function_call_output_payload(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::ExecTermination;
     use mcp_types::ContentBlock;
     use mcp_types::TextContent;
     use pretty_assertions::assert_eq;
@@ -3009,43 +5684,72 @@ mod tests {
     }
 
     #[test]
-    fn model_truncation_head_tail_by_lines() {
-        // Build 400 short lines so line-count limit, not byte budget, triggers truncation
-        let lines: Vec<String> = (1..=400).map(|i| format!("line{i}")).collect();
-        let full = lines.join("\n");
+    fn git_repo_warning_depends_on_repo_state_and_sandbox() {
+        let dirty = VersionControl::Git {
+            is_dirty: Some(true),
+        };
+        let writable = SandboxPolicy::new_workspace_write_policy();
+        assert_eq!(
+            git_repo_warning_kind(VersionControl::None, &SandboxPolicy::ReadOnly),
+            Some(GitRepoWarningKind::NotARepo)
+        );
+        assert_eq!(
+            git_repo_warning_kind(dirty, &writable),
+            Some(GitRepoWarningKind::Dirty)
+        );
+        // Uncommitted changes cannot be mixed with edits nobody can make.
+        assert_eq!(git_repo_warning_kind(dirty, &SandboxPolicy::ReadOnly), None);
+        assert_eq!(
+            git_repo_warning_kind(
+                VersionControl::Git {
+                    is_dirty: Some(false)
+                },
+                &writable
+            ),
+            None
+        );
+    }
 
-        let exec = ExecToolCallOutput {
+    fn exec_output(aggregated_output: String) -> ExecToolCallOutput {
+        ExecToolCallOutput {
             exit_code: 0,
             stdout: StreamOutput::new(String::new()),
             stderr: StreamOutput::new(String::new()),
-            aggregated_output: StreamOutput::new(full.clone()),
+            aggregated_output: StreamOutput::new(aggregated_output),
             duration: StdDuration::from_secs(1),
-        };
-
-        let out = format_exec_output_str(&exec);
+            full_output_path: None,
+            terminated: None,
+        }
+    }
 
-        // Expect elision marker with correct counts
-        let omitted = 400 - MODEL_FORMAT_MAX_LINES; // 144
-        let marker = format!("\n[... omitted {omitted} of 400 lines ...]\n\n");
-        assert!(out.contains(&marker), "missing marker: {out}");
+    #[test]
+    fn model_truncation_head_tail_by_lines() {
+        // Build 400 short lines so line-count limit, not byte budget, triggers truncation
+        let lines: Vec<String> = (1..=400).map(|i| format!("line{i}")).collect();
+        let full = lines.join("\n");
+        let limits = ExecOutputLimits::default();
 
-        // Validate head and tail
-        let parts: Vec<&str> = out.split(&marker).collect();
-        assert_eq!(parts.len(), 2, "expected one marker split");
-        let head = parts[0];
-        let tail = parts[1];
+        let out = format_exec_output_str(&exec_output(full.clone()), limits);
 
-        let expected_head: String = (1..=MODEL_FORMAT_HEAD_LINES)
+        let head_lines = limits.max_lines / 2;
+        let tail_lines = limits.max_lines - head_lines;
+        let expected_head: String = (1..=head_lines)
             .map(|i| format!("line{i}"))
             .collect::<Vec<_>>()
             .join("\n");
-        assert!(head.starts_with(&expected_head), "head mismatch");
-
-        let expected_tail: String = ((400 - MODEL_FORMAT_TAIL_LINES + 1)..=400)
+        let expected_tail: String = ((400 - tail_lines + 1)..=400)
             .map(|i| format!("line{i}"))
             .collect::<Vec<_>>()
             .join("\n");
-        assert!(tail.ends_with(&expected_tail), "tail mismatch");
+
+        // Expect elision marker with correct counts
+        let omitted_lines = 400 - limits.max_lines; // 144
+        let omitted_bytes = full.len() - expected_head.len() - expected_tail.len();
+        let marker = format!(
+            "\n[... omitted {omitted_lines} of 400 lines, {omitted_bytes} of {} bytes ...]\n\n",
+            full.len()
+        );
+        assert_eq!(out, format!("{expected_head}{marker}{expected_tail}"));
     }
 
     #[test]
@@ -3055,17 +5759,10 @@ mod tests {
         let full = std::iter::repeat_n(big_line.clone(), 1000)
             .collect::<Vec<_>>()
             .join("\n");
+        let limits = ExecOutputLimits::default();
 
-        let exec = ExecToolCallOutput {
-            exit_code: 0,
-            stdout: StreamOutput::new(String::new()),
-            stderr: StreamOutput::new(String::new()),
-            aggregated_output: StreamOutput::new(full.clone()),
-            duration: StdDuration::from_secs(1),
-        };
-
-        let out = format_exec_output_str(&exec);
-        assert!(out.len() <= MODEL_FORMAT_MAX_BYTES, "exceeds byte budget");
+        let out = format_exec_output_str(&exec_output(full.clone()), limits);
+        assert!(out.len() <= limits.max_bytes, "exceeds byte budget");
         assert!(out.contains("omitted"), "should contain elision marker");
 
         // Ensure head and tail are drawn from the original
@@ -3084,6 +5781,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn model_truncation_uses_configured_limits_and_names_full_output() {
+        let full = (1..=10)
+            .map(|i| format!("line{i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut exec = exec_output(full);
+        exec.full_output_path = Some(PathBuf::from("/codex/sessions/id/call.log"));
+        let limits = ExecOutputLimits {
+            max_bytes: 1024,
+            max_lines: 4,
+        };
+
+        let out = format_exec_output_str(&exec, limits);
+
+        assert_eq!(
+            out,
+            "line1\nline2\n[... omitted 6 of 10 lines, 37 of 60 bytes; full output in /codex/sessions/id/call.log ...]\n\nline9\nline10"
+        );
+    }
+
+    #[test]
+    fn terminated_commands_report_the_reason_to_the_model() {
+        let mut exec = exec_output("partial".to_string());
+        exec.exit_code = 137;
+        exec.terminated = Some(ExecTermination::TimedOut { timeout_ms: 30_000 });
+
+        let payload: serde_json::Value = serde_json::from_str(&format_exec_output(
+            &exec,
+            ExecOutputLimits::default(),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(payload["output"], "partial");
+        assert_eq!(
+            payload["metadata"]["terminated"],
+            "command timed out after 30 seconds"
+        );
+    }
+
+    #[test]
+    fn model_output_omits_terminal_escapes() {
+        let exec = exec_output("\x1b[32mok\x1b[0m 3 tests\r\n".to_string());
+
+        let out = format_exec_output_str(&exec, ExecOutputLimits::default());
+
+        assert_eq!(out, "ok 3 tests\n");
+    }
+
     #[test]
     fn falls_back_to_content_when_structured_is_null() {
         let ctr = CallToolResult {
@@ -3135,4 +5882,46 @@ mod tests {
 
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn interrupted_partial_turn_keeps_streamed_text() {
+        let mut state = State::default();
+        state.partial_turn = Some(PartialTurn {
+            items: vec![ResponseItem::Reasoning {
+                id: "r1".to_string(),
+                summary: Vec::new(),
+                content: None,
+                encrypted_content: None,
+            }],
+            text: "The failing test is".to_string(),
+        });
+
+        let items = state.commit_partial_turn();
+
+        let expected = vec![
+            ResponseItem::Reasoning {
+                id: "r1".to_string(),
+                summary: Vec::new(),
+                content: None,
+                encrypted_content: None,
+            },
+            ResponseItem::Message {
+                id: None,
+                role: "assistant".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: format!("The failing test is\n\n{TURN_INTERRUPTED_NOTE}"),
+                }],
+            },
+        ];
+        assert_eq!(items, expected);
+        assert_eq!(state.history.contents(), expected);
+        assert!(state.partial_turn.is_none());
+    }
+
+    #[test]
+    fn committing_without_partial_turn_records_nothing() {
+        let mut state = State::default();
+        assert!(state.commit_partial_turn().is_empty());
+        assert!(state.history.contents().is_empty());
+    }
 }
@@ -0,0 +1,416 @@
+//! Where Codex keeps its files.
+//!
+//! `CODEX_HOME`, when set, holds everything. Otherwise a `~/.codex` left by
+//! an earlier version does, until `codex config migrate-home` moves it.
+//! Without either, files are split by kind following platform conventions:
+//!
+//! |        | Linux and other Unix                          | macOS                                 | Windows                           |
+//! | ------ | --------------------------------------------- | ------------------------------------- | --------------------------------- |
+//! | config | `$XDG_CONFIG_HOME/codex` (`~/.config/codex`)  | `~/Library/Application Support/codex` | `%APPDATA%\codex`                 |
+//! | state  | `$XDG_STATE_HOME/codex` (`~/.local/state/codex`) | `~/Library/Application Support/codex` | `%LOCALAPPDATA%\codex`         |
+//! | cache  | `$XDG_CACHE_HOME/codex` (`~/.cache/codex`)    | `~/Library/Caches/codex`              | `%LOCALAPPDATA%\codex\cache`      |
+//!
+//! Configuration is what a user writes or would copy to another machine:
+//! `config.toml`, `.env`, `AGENTS.md`, custom prompts and credentials. State
+//! is what Codex records as it runs: history, sessions and logs. The cache
+//! can be deleted at any time.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use dirs::home_dir;
+
+use crate::config::CONFIG_TOML_FILE;
+use crate::message_history::HISTORY_FILENAME;
+use crate::rollout::SESSIONS_SUBDIR;
+
+/// Name of the directory under the platform directories.
+const APP_DIR: &str = "codex";
+
+/// Directory in the home directory used by earlier versions for everything.
+const LEGACY_HOME: &str = ".codex";
+
+/// Subdirectory of the state directory holding log files.
+pub const LOG_SUBDIR: &str = "log";
+
+/// Subdirectory of the state directory holding MCP resources attached to
+/// sessions.
+pub const MCP_RESOURCES_SUBDIR: &str = "mcp_resources";
+
+/// Entries of a legacy home that belong in the state directory.
+const STATE_ENTRIES: &[&str] = &[
+    HISTORY_FILENAME,
+    SESSIONS_SUBDIR,
+    LOG_SUBDIR,
+    MCP_RESOURCES_SUBDIR,
+];
+
+/// Entries of a legacy home that belong in the cache directory. The TUI
+/// keeps the latest known release in `version.json`.
+const CACHE_ENTRIES: &[&str] = &["version.json"];
+
+/// The directories holding Codex's configuration, state and cache. They are
+/// one and the same directory unless the platform layout is in use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodexDirs {
+    config: PathBuf,
+    state: PathBuf,
+    cache: PathBuf,
+}
+
+/// Everything in one directory, as with `CODEX_HOME`.
+impl From<PathBuf> for CodexDirs {
+    fn from(home: PathBuf) -> Self {
+        Self::single(home)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Platform {
+    Unix,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    fn current() -> Self {
+        if cfg!(windows) {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Unix
+        }
+    }
+}
+
+impl CodexDirs {
+    /// Keeps configuration, state and cache in `home`.
+    pub fn single(home: PathBuf) -> Self {
+        Self {
+            config: home.clone(),
+            state: home.clone(),
+            cache: home,
+        }
+    }
+
+    /// Resolves the directories of the current user.
+    ///
+    /// Fails if `CODEX_HOME` is set to a directory that does not exist, or if
+    /// there is no home directory to fall back on. Does not create any
+    /// directory.
+    pub fn resolve() -> io::Result<Self> {
+        resolve_with(
+            |name| std::env::var_os(name),
+            home_dir().as_deref(),
+            Platform::current(),
+        )
+    }
+
+    /// `config.toml`, `.env`, `AGENTS.md`, `prompts/` and credentials.
+    pub fn config_dir(&self) -> &Path {
+        &self.config
+    }
+
+    /// History, sessions and logs.
+    pub fn state_dir(&self) -> &Path {
+        &self.state
+    }
+
+    /// Files Codex can recreate, such as the latest known release.
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config.join(CONFIG_TOML_FILE)
+    }
+}
+
+fn resolve_with(
+    env: impl Fn(&str) -> Option<OsString>,
+    home: Option<&Path>,
+    platform: Platform,
+) -> io::Result<CodexDirs> {
+    let env_dir = |name: &str| {
+        env(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+
+    // Honor `CODEX_HOME` so users (and tests) can put everything elsewhere.
+    if let Some(codex_home) = env_dir("CODEX_HOME") {
+        return codex_home.canonicalize().map(CodexDirs::single);
+    }
+
+    let home = home
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find home directory"))?;
+    let legacy = home.join(LEGACY_HOME);
+    if legacy.is_dir() {
+        return Ok(CodexDirs::single(legacy));
+    }
+    Ok(platform_dirs(env_dir, home, platform))
+}
+
+fn platform_dirs(
+    env_dir: impl Fn(&str) -> Option<PathBuf>,
+    home: &Path,
+    platform: Platform,
+) -> CodexDirs {
+    match platform {
+        Platform::Unix => {
+            // The XDG spec says to ignore relative paths.
+            let base = |var: &str, default: &str| {
+                env_dir(var)
+                    .filter(|dir| dir.is_absolute())
+                    .unwrap_or_else(|| home.join(default))
+                    .join(APP_DIR)
+            };
+            CodexDirs {
+                config: base("XDG_CONFIG_HOME", ".config"),
+                state: base("XDG_STATE_HOME", ".local/state"),
+                cache: base("XDG_CACHE_HOME", ".cache"),
+            }
+        }
+        Platform::MacOs => {
+            let library = home.join("Library");
+            let support = library.join("Application Support").join(APP_DIR);
+            CodexDirs {
+                config: support.clone(),
+                state: support,
+                cache: library.join("Caches").join(APP_DIR),
+            }
+        }
+        Platform::Windows => {
+            let roaming = env_dir("APPDATA")
+                .unwrap_or_else(|| home.join("AppData").join("Roaming"))
+                .join(APP_DIR);
+            let local = env_dir("LOCALAPPDATA")
+                .unwrap_or_else(|| home.join("AppData").join("Local"))
+                .join(APP_DIR);
+            CodexDirs {
+                config: roaming,
+                state: local.clone(),
+                cache: local.join("cache"),
+            }
+        }
+    }
+}
+
+/// A move of a legacy `~/.codex` into the platform directories.
+#[derive(Debug)]
+pub struct LegacyHomeMigration {
+    pub legacy_home: PathBuf,
+    pub dirs: CodexDirs,
+    /// Every moved entry, with where it went.
+    pub moved: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Moves a `~/.codex` left by an earlier version into the platform
+/// directories and removes it. Returns `None` when there is none, and fails
+/// without moving anything when `CODEX_HOME` is set or an entry already
+/// exists at its destination.
+pub fn migrate_legacy_home() -> io::Result<Option<LegacyHomeMigration>> {
+    if std::env::var_os("CODEX_HOME").is_some_and(|value| !value.is_empty()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "CODEX_HOME is set, so Codex does not use ~/.codex",
+        ));
+    }
+    let home = home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find home directory"))?;
+    let legacy_home = home.join(LEGACY_HOME);
+    if !legacy_home.is_dir() {
+        return Ok(None);
+    }
+    let dirs = platform_dirs(
+        |name| {
+            std::env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        },
+        &home,
+        Platform::current(),
+    );
+    let moved = move_legacy_home(&legacy_home, &dirs)?;
+    Ok(Some(LegacyHomeMigration {
+        legacy_home,
+        dirs,
+        moved,
+    }))
+}
+
+fn move_legacy_home(legacy_home: &Path, dirs: &CodexDirs) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moves = Vec::new();
+    for entry in std::fs::read_dir(legacy_home)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let dir = match name.to_str() {
+            Some(name) if STATE_ENTRIES.contains(&name) => dirs.state_dir(),
+            Some(name) if CACHE_ENTRIES.contains(&name) => dirs.cache_dir(),
+            _ => dirs.config_dir(),
+        };
+        let to = dir.join(&name);
+        if to.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", to.display()),
+            ));
+        }
+        moves.push((entry.path(), to));
+    }
+    moves.sort();
+
+    for (from, to) in &moves {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(from, to)?;
+    }
+    std::fs::remove_dir(legacy_home)?;
+    Ok(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn resolve(vars: &[(&str, &Path)], home: &Path, platform: Platform) -> io::Result<CodexDirs> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_os_str().to_owned()))
+            .collect();
+        resolve_with(|name| vars.get(name).cloned(), Some(home), platform)
+    }
+
+    #[test]
+    fn codex_home_takes_precedence_over_everything() -> io::Result<()> {
+        let home = TempDir::new()?;
+        let codex_home = TempDir::new()?;
+        std::fs::create_dir(home.path().join(".codex"))?;
+        let dirs = resolve(
+            &[
+                ("CODEX_HOME", codex_home.path()),
+                ("XDG_CONFIG_HOME", home.path()),
+            ],
+            home.path(),
+            Platform::Unix,
+        )?;
+        assert_eq!(dirs, CodexDirs::single(codex_home.path().canonicalize()?));
+
+        let missing = home.path().join("missing");
+        assert!(resolve(&[("CODEX_HOME", &missing)], home.path(), Platform::Unix).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_home_is_used_while_it_exists() -> io::Result<()> {
+        let home = TempDir::new()?;
+        let xdg = TempDir::new()?;
+        std::fs::create_dir(home.path().join(".codex"))?;
+        let dirs = resolve(
+            &[("XDG_CONFIG_HOME", xdg.path())],
+            home.path(),
+            Platform::Unix,
+        )?;
+        assert_eq!(dirs, CodexDirs::single(home.path().join(".codex")));
+        Ok(())
+    }
+
+    #[test]
+    fn xdg_variables_split_config_state_and_cache() -> io::Result<()> {
+        let home = TempDir::new()?;
+        let config = TempDir::new()?;
+        let state = TempDir::new()?;
+        let dirs = resolve(
+            &[
+                ("XDG_CONFIG_HOME", config.path()),
+                ("XDG_STATE_HOME", state.path()),
+                // Relative paths are ignored.
+                ("XDG_CACHE_HOME", Path::new("relative/cache")),
+            ],
+            home.path(),
+            Platform::Unix,
+        )?;
+        assert_eq!(dirs.config_dir(), config.path().join("codex"));
+        assert_eq!(dirs.state_dir(), state.path().join("codex"));
+        assert_eq!(dirs.cache_dir(), home.path().join(".cache/codex"));
+
+        let defaults = resolve(&[], home.path(), Platform::Unix)?;
+        assert_eq!(defaults.config_dir(), home.path().join(".config/codex"));
+        assert_eq!(defaults.state_dir(), home.path().join(".local/state/codex"));
+        Ok(())
+    }
+
+    #[test]
+    fn macos_and_windows_use_their_conventional_dirs() -> io::Result<()> {
+        let home = TempDir::new()?;
+        let mac = resolve(&[], home.path(), Platform::MacOs)?;
+        let support = home.path().join("Library/Application Support/codex");
+        assert_eq!(mac.config_dir(), support);
+        assert_eq!(mac.state_dir(), support);
+        assert_eq!(mac.cache_dir(), home.path().join("Library/Caches/codex"));
+
+        let roaming = TempDir::new()?;
+        let local = TempDir::new()?;
+        let windows = resolve(
+            &[("APPDATA", roaming.path()), ("LOCALAPPDATA", local.path())],
+            home.path(),
+            Platform::Windows,
+        )?;
+        assert_eq!(windows.config_dir(), roaming.path().join("codex"));
+        assert_eq!(windows.state_dir(), local.path().join("codex"));
+        assert_eq!(
+            windows.cache_dir(),
+            local.path().join("codex").join("cache")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_home_moves_entries_by_kind() -> io::Result<()> {
+        let root = TempDir::new()?;
+        let legacy = root.path().join(".codex");
+        std::fs::create_dir_all(legacy.join("sessions/2025"))?;
+        std::fs::write(legacy.join("config.toml"), "model = \"o3\"\n")?;
+        std::fs::write(legacy.join("history.jsonl"), "")?;
+        std::fs::write(legacy.join("version.json"), "{}")?;
+        let dirs = CodexDirs {
+            config: root.path().join("config"),
+            state: root.path().join("state"),
+            cache: root.path().join("cache"),
+        };
+
+        let moved = move_legacy_home(&legacy, &dirs)?;
+
+        assert_eq!(moved.len(), 4);
+        assert!(!legacy.exists());
+        assert!(dirs.config_file().is_file());
+        assert!(dirs.state_dir().join("history.jsonl").is_file());
+        assert!(dirs.state_dir().join("sessions/2025").is_dir());
+        assert!(dirs.cache_dir().join("version.json").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn migration_refuses_to_overwrite() -> io::Result<()> {
+        let root = TempDir::new()?;
+        let legacy = root.path().join(".codex");
+        std::fs::create_dir_all(&legacy)?;
+        std::fs::write(legacy.join("config.toml"), "")?;
+        std::fs::write(legacy.join("history.jsonl"), "")?;
+        let dirs = CodexDirs::single(root.path().join("new"));
+        std::fs::create_dir_all(dirs.config_dir())?;
+        std::fs::write(dirs.config_file(), "")?;
+
+        let err = move_legacy_home(&legacy, &dirs).expect_err("destination exists");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(legacy.join("history.jsonl").is_file());
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! Tools defined in `[tools.custom.<name>]` that run a local command with the
+//! arguments the model passed.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value as JsonValue;
+
+use crate::config_types::CustomToolInput;
+use crate::config_types::CustomToolToml;
+use crate::openai_tools::JsonSchema;
+use crate::openai_tools::OpenAiTool;
+use crate::openai_tools::ResponsesApiTool;
+use crate::openai_tools::sanitize_json_schema;
+
+/// Names of built-in tools, which a custom tool may not take.
+const RESERVED_NAMES: &[&str] = &[
+    "apply_patch",
+    "container.exec",
+    "create_pull_request",
+    "exec_command",
+    "local_shell",
+    "shell",
+    "update_plan",
+    "view_image",
+    "web_search",
+    "write_stdin",
+];
+
+/// A validated `[tools.custom.<name>]` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandTool {
+    pub name: String,
+    description: String,
+    parameters: JsonSchema,
+    command: Vec<String>,
+    input: CustomToolInput,
+    pub requires_approval: bool,
+    pub timeout_ms: Option<u64>,
+}
+
+/// What a call to a [`CommandTool`] runs.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CommandToolInvocation {
+    pub command: Vec<String>,
+    pub stdin: Option<String>,
+}
+
+impl CommandTool {
+    /// Validates the `[tools.custom]` table. Returns a descriptive error for
+    /// an invalid name, parameter schema or command.
+    pub fn from_config(custom: &BTreeMap<String, CustomToolToml>) -> Result<Vec<Self>, String> {
+        custom
+            .iter()
+            .map(|(name, toml)| Self::new(name, toml))
+            .collect()
+    }
+
+    fn new(name: &str, toml: &CustomToolToml) -> Result<Self, String> {
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(format!(
+                "invalid custom tool name `{name}`: use letters, digits, `_` and `-`"
+            ));
+        }
+        if RESERVED_NAMES.contains(&name) {
+            return Err(format!(
+                "custom tool `{name}` has the name of a built-in tool"
+            ));
+        }
+        if toml.command.is_empty() {
+            return Err(format!("custom tool `{name}` has an empty command"));
+        }
+
+        let parameters = match &toml.parameters {
+            Some(schema) => parse_parameters(schema)
+                .map_err(|e| format!("invalid parameters for custom tool `{name}`: {e}"))?,
+            None => JsonSchema::Object {
+                properties: BTreeMap::new(),
+                required: None,
+                additional_properties: Some(false),
+            },
+        };
+        if toml.input == CustomToolInput::Argv
+            && let JsonSchema::Object { properties, .. } = &parameters
+            && let Some(unknown) = toml.command.iter().flat_map(|arg| segments(arg)).find_map(
+                |segment| match segment {
+                    Segment::Placeholder(param) if !properties.contains_key(param) => Some(param),
+                    _ => None,
+                },
+            )
+        {
+            return Err(format!(
+                "custom tool `{name}` uses `{{{unknown}}}` in its command but has no parameter `{unknown}`"
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            description: toml.description.clone(),
+            parameters,
+            command: toml.command.clone(),
+            input: toml.input,
+            requires_approval: toml.requires_approval,
+            timeout_ms: toml.timeout_ms,
+        })
+    }
+
+    pub(crate) fn openai_tool(&self) -> OpenAiTool {
+        OpenAiTool::Function(ResponsesApiTool {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            strict: false,
+            parameters: self.parameters.clone(),
+        })
+    }
+
+    /// Checks the arguments of a call against the tool's schema and builds
+    /// the command it runs. The error is meant for the model.
+    pub(crate) fn invocation(&self, arguments: &str) -> Result<CommandToolInvocation, String> {
+        let arguments: JsonValue = if arguments.trim().is_empty() {
+            JsonValue::Object(serde_json::Map::new())
+        } else {
+            serde_json::from_str(arguments)
+                .map_err(|e| format!("failed to parse function arguments: {e}"))?
+        };
+        validate(&self.parameters, &arguments, "arguments")
+            .map_err(|e| format!("invalid arguments for `{}`: {e}", self.name))?;
+
+        Ok(match self.input {
+            CustomToolInput::Argv => CommandToolInvocation {
+                command: self
+                    .command
+                    .iter()
+                    .flat_map(|arg| expand(arg, &arguments))
+                    .collect(),
+                stdin: None,
+            },
+            CustomToolInput::Stdin => CommandToolInvocation {
+                command: self.command.clone(),
+                stdin: Some(arguments.to_string()),
+            },
+        })
+    }
+}
+
+fn parse_parameters(schema: &JsonValue) -> Result<JsonSchema, String> {
+    let mut schema = schema.clone();
+    sanitize_json_schema(&mut schema);
+    match serde_json::from_value(schema) {
+        Ok(schema @ JsonSchema::Object { .. }) => Ok(schema),
+        Ok(_) => Err("the schema must describe an object".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Checks `value` against `schema`. `path` names `value` in the error.
+fn validate(schema: &JsonSchema, value: &JsonValue, path: &str) -> Result<(), String> {
+    match (schema, value) {
+        (JsonSchema::Boolean { .. }, JsonValue::Bool(_))
+        | (JsonSchema::String { .. }, JsonValue::String(_))
+        | (JsonSchema::Number { .. }, JsonValue::Number(_)) => Ok(()),
+        (JsonSchema::Array { items, .. }, JsonValue::Array(values)) => values
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, value)| validate(items, value, &format!("{path}[{i}]"))),
+        (
+            JsonSchema::Object {
+                properties,
+                required,
+                additional_properties,
+            },
+            JsonValue::Object(map),
+        ) => {
+            if let Some(missing) = required
+                .iter()
+                .flatten()
+                .find(|name| !map.contains_key(*name))
+            {
+                return Err(format!("{path}.{missing} is required"));
+            }
+            for (name, value) in map {
+                match properties.get(name) {
+                    Some(schema) => validate(schema, value, &format!("{path}.{name}"))?,
+                    None if *additional_properties == Some(false) => {
+                        return Err(format!("{path}.{name} is not a parameter of this tool"));
+                    }
+                    None => {}
+                }
+            }
+            Ok(())
+        }
+        _ => {
+            let expected = match schema {
+                JsonSchema::Boolean { .. } => "a boolean",
+                JsonSchema::String { .. } => "a string",
+                JsonSchema::Number { .. } => "a number",
+                JsonSchema::Array { .. } => "an array",
+                JsonSchema::Object { .. } => "an object",
+            };
+            Err(format!("{path} must be {expected}, got {value}"))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Splits a command argument into literal text and `{name}` placeholders.
+/// Braces around anything but a name, as in `{}`, are literal text.
+fn segments(arg: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) if is_placeholder_name(&after[..end]) => {
+                if start > 0 {
+                    segments.push(Segment::Literal(&rest[..start]));
+                }
+                segments.push(Segment::Placeholder(&after[..end]));
+                rest = &after[end + 1..];
+            }
+            _ => {
+                segments.push(Segment::Literal(&rest[..=start]));
+                rest = after;
+            }
+        }
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    segments
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Substitutes the arguments into one argument of the command template. An
+/// argument that is a placeholder on its own is dropped when the argument is
+/// missing, and becomes one argument per element for an array.
+fn expand(arg: &str, arguments: &JsonValue) -> Vec<String> {
+    let segments = segments(arg);
+    if let [Segment::Placeholder(name)] = segments.as_slice() {
+        return match arguments.get(name) {
+            None | Some(JsonValue::Null) => Vec::new(),
+            Some(JsonValue::Array(values)) => values.iter().map(argument_text).collect(),
+            Some(value) => vec![argument_text(value)],
+        };
+    }
+    let expanded = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => text.to_string(),
+            Segment::Placeholder(name) => {
+                arguments.get(name).map(argument_text).unwrap_or_default()
+            }
+        })
+        .collect();
+    vec![expanded]
+}
+
+fn argument_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(text) => text.clone(),
+        JsonValue::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn tool(command: &[&str], input: CustomToolInput) -> CustomToolToml {
+        CustomToolToml {
+            description: "Runs the tests".to_string(),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "filter": { "type": "string" },
+                    "jobs": { "type": "integer" },
+                    "files": { "type": "array", "items": { "type": "string" } },
+                },
+                "required": ["filter"],
+                "additionalProperties": false,
+            })),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            input,
+            requires_approval: false,
+            timeout_ms: None,
+        }
+    }
+
+    fn command_tool(command: &[&str], input: CustomToolInput) -> CommandTool {
+        CommandTool::new("run_tests", &tool(command, input)).unwrap()
+    }
+
+    #[test]
+    fn substitutes_arguments_into_the_command() {
+        let tool = command_tool(
+            &[
+                "./run-tests",
+                "--filter={filter}",
+                "-j",
+                "{jobs}",
+                "{files}",
+                "{}",
+            ],
+            CustomToolInput::Argv,
+        );
+        assert_eq!(
+            tool.invocation(r#"{"filter": "unit", "jobs": 4, "files": ["a.rs", "b rs"]}"#),
+            Ok(CommandToolInvocation {
+                command: vec![
+                    "./run-tests".to_string(),
+                    "--filter=unit".to_string(),
+                    "-j".to_string(),
+                    "4".to_string(),
+                    "a.rs".to_string(),
+                    "b rs".to_string(),
+                    "{}".to_string(),
+                ],
+                stdin: None,
+            })
+        );
+        // Missing optional arguments drop placeholders that stand alone.
+        assert_eq!(
+            tool.invocation(r#"{"filter": ""}"#).unwrap().command,
+            vec!["./run-tests", "--filter=", "-j", "{}"]
+        );
+    }
+
+    #[test]
+    fn stdin_tools_get_the_arguments_as_json() {
+        let tool = command_tool(&["./run-tests", "--json"], CustomToolInput::Stdin);
+        assert_eq!(
+            tool.invocation(r#"{"filter": "unit"}"#),
+            Ok(CommandToolInvocation {
+                command: vec!["./run-tests".to_string(), "--json".to_string()],
+                stdin: Some(r#"{"filter":"unit"}"#.to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn arguments_are_checked_against_the_schema() {
+        let tool = command_tool(&["./run-tests", "{filter}"], CustomToolInput::Argv);
+        let cases = [
+            ("{}", "arguments.filter is required"),
+            (
+                r#"{"filter": "unit", "jobs": "four"}"#,
+                "arguments.jobs must be a number, got \"four\"",
+            ),
+            (
+                r#"{"filter": "unit", "files": ["a.rs", 2]}"#,
+                "arguments.files[1] must be a string, got 2",
+            ),
+            (
+                r#"{"filter": "unit", "verbose": true}"#,
+                "arguments.verbose is not a parameter of this tool",
+            ),
+            (r#"["unit"]"#, "arguments must be an object, got [\"unit\"]"),
+        ];
+        for (arguments, error) in cases {
+            assert_eq!(
+                tool.invocation(arguments),
+                Err(format!("invalid arguments for `run_tests`: {error}")),
+                "{arguments}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_definitions_are_rejected() {
+        let mut custom = BTreeMap::new();
+        custom.insert(
+            "run_tests".to_string(),
+            tool(&["./run-tests", "{filtr}"], CustomToolInput::Argv),
+        );
+        assert_eq!(
+            CommandTool::from_config(&custom),
+            Err("custom tool `run_tests` uses `{filtr}` in its command but has no parameter `filtr`".to_string())
+        );
+
+        let mut custom = BTreeMap::new();
+        custom.insert(
+            "shell".to_string(),
+            tool(&["./run-tests"], CustomToolInput::Argv),
+        );
+        assert_eq!(
+            CommandTool::from_config(&custom),
+            Err("custom tool `shell` has the name of a built-in tool".to_string())
+        );
+
+        let mut not_an_object = tool(&["./run-tests"], CustomToolInput::Argv);
+        not_an_object.parameters = Some(json!({ "type": "string" }));
+        assert_eq!(
+            CommandTool::new("run_tests", &not_an_object),
+            Err(
+                "invalid parameters for custom tool `run_tests`: the schema must describe an object"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Replacing the older part of the conversation history with a summary
+//! written by the model, so long sessions fit in the context window.
+
+use std::borrow::Borrow;
+use std::ops::Range;
+
+use codex_protocol::models::ContentItem;
+use codex_protocol::models::ResponseItem;
+
+use crate::rollout::is_session_prefix;
+use crate::token_estimate::Encoding;
+use crate::token_estimate::estimate_response_item_tokens;
+
+/// Base instructions for the summarization request.
+pub(crate) const SUMMARIZATION_PROMPT: &str = include_str!("prompt_for_compact_command.md");
+
+/// User message appended to the items being summarized.
+pub(crate) const SUMMARIZATION_TRIGGER: &str = "Start Summarization";
+
+pub(crate) const CONVERSATION_SUMMARY_START: &str = "<conversation_summary>";
+pub(crate) const CONVERSATION_SUMMARY_END: &str = "</conversation_summary>";
+
+/// Share of the context window that automatic compaction keeps verbatim,
+/// starting from the newest items.
+pub(crate) const AUTO_COMPACT_KEEP_FRACTION: f64 = 0.25;
+
+/// Picks the items to summarize: everything after the messages Codex adds at
+/// the start of a session, up to the oldest user message from which the rest
+/// of the history fits in `keep_tokens`. Cutting only in front of a user
+/// message keeps each turn, including its tool calls, outputs and
+/// reasoning, together. If no such message fits, the whole history after the
+/// session prefix is summarized.
+///
+/// Returns `None` when there is nothing to summarize.
+pub(crate) fn plan_compaction<T: Borrow<ResponseItem>>(
+    items: &[T],
+    keep_tokens: u64,
+    encoding: Encoding,
+) -> Option<Range<usize>> {
+    let start = items
+        .iter()
+        .take_while(|item| {
+            let item: &ResponseItem = (*item).borrow();
+            user_message_text(item).is_some_and(is_session_prefix)
+        })
+        .count();
+
+    let mut end = items.len();
+    let mut kept_tokens = 0u64;
+    for (idx, item) in items.iter().enumerate().skip(start + 1).rev() {
+        let item: &ResponseItem = item.borrow();
+        kept_tokens += estimate_response_item_tokens(item, encoding) as u64;
+        if kept_tokens > keep_tokens {
+            break;
+        }
+        if user_message_text(item).is_some() {
+            end = idx;
+        }
+    }
+
+    (start < end).then_some(start..end)
+}
+
+/// The user message that replaces the summarized items in the history.
+pub(crate) fn summary_message(summary: &str) -> ResponseItem {
+    ResponseItem::Message {
+        id: None,
+        role: "user".to_string(),
+        content: vec![ContentItem::InputText {
+            text: format!("{CONVERSATION_SUMMARY_START}\n{summary}\n{CONVERSATION_SUMMARY_END}"),
+        }],
+    }
+}
+
+pub(crate) fn estimate_items_tokens<T: Borrow<ResponseItem>>(
+    items: &[T],
+    encoding: Encoding,
+) -> u64 {
+    items
+        .iter()
+        .map(|item| estimate_response_item_tokens(item.borrow(), encoding) as u64)
+        .sum()
+}
+
+fn user_message_text(item: &ResponseItem) -> Option<&str> {
+    match item {
+        ResponseItem::Message { role, content, .. } if role == "user" => {
+            content.iter().find_map(|c| match c {
+                ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                    Some(text.as_str())
+                }
+                ContentItem::InputImage { .. } => None,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codex_protocol::models::FunctionCallOutputPayload;
+    use pretty_assertions::assert_eq;
+
+    fn message(role: &str, text: &str) -> ResponseItem {
+        ResponseItem::Message {
+            id: None,
+            role: role.to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    fn history() -> Vec<ResponseItem> {
+        vec![
+            message(
+                "user",
+                "<user_instructions>\n\nbe brief\n\n</user_instructions>",
+            ),
+            message("user", "<environment_context>\n</environment_context>"),
+            message("user", "first"),
+            ResponseItem::FunctionCall {
+                id: None,
+                name: "shell".to_string(),
+                arguments: "{}".to_string(),
+                call_id: "c1".to_string(),
+            },
+            ResponseItem::FunctionCallOutput {
+                call_id: "c1".to_string(),
+                output: FunctionCallOutputPayload {
+                    content: "ok".to_string(),
+                    success: Some(true),
+                },
+            },
+            message("assistant", "done"),
+            message("user", "second"),
+            message("assistant", "also done"),
+        ]
+    }
+
+    #[test]
+    fn manual_compaction_summarizes_everything_after_the_prefix() {
+        let items = history();
+        assert_eq!(
+            plan_compaction(&items, 0, Encoding::O200kBase),
+            Some(2..items.len())
+        );
+    }
+
+    #[test]
+    fn keeps_recent_turns_that_fit_the_budget() {
+        let items = history();
+        let last_turn = estimate_items_tokens(&items[6..], Encoding::O200kBase);
+        assert_eq!(
+            plan_compaction(&items, last_turn, Encoding::O200kBase),
+            Some(2..6)
+        );
+    }
+
+    #[test]
+    fn never_cuts_between_a_call_and_its_output() {
+        let items = history();
+        // Enough for the output and the rest, but not for the call itself.
+        let budget = estimate_items_tokens(&items[4..], Encoding::O200kBase);
+        assert_eq!(
+            plan_compaction(&items, budget, Encoding::O200kBase),
+            Some(2..6)
+        );
+    }
+
+    #[test]
+    fn nothing_to_summarize_without_conversation() {
+        let items = history();
+        assert_eq!(plan_compaction(&items[..2], 0, Encoding::O200kBase), None);
+    }
+}
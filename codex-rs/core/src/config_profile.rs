@@ -1,20 +1,26 @@
 use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::Error as _;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::config_types::Verbosity;
 use crate::protocol::AskForApproval;
 use codex_protocol::config_types::ReasoningEffort;
 use codex_protocol::config_types::ReasoningSummary;
+use codex_protocol::config_types::SandboxMode;
 
 /// Collection of common configuration options that a user can define as a unit
 /// in `config.toml`.
 #[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigProfile {
     pub model: Option<String>,
     /// The key in the `model_providers` map identifying the
     /// [`ModelProviderInfo`] to use.
     pub model_provider: Option<String>,
     pub approval_policy: Option<AskForApproval>,
+    pub sandbox_mode: Option<SandboxMode>,
     pub disable_response_storage: Option<bool>,
     pub model_reasoning_effort: Option<ReasoningEffort>,
     pub model_reasoning_summary: Option<ReasoningSummary>,
@@ -22,3 +28,23 @@ pub struct ConfigProfile {
     pub chatgpt_base_url: Option<String>,
     pub experimental_instructions_file: Option<PathBuf>,
 }
+
+/// Deserializes the `profiles` table one profile at a time so that an invalid
+/// profile is reported by name rather than by its position in the file.
+pub(crate) fn deserialize_profiles<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, ConfigProfile>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = HashMap::<String, toml::Value>::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(name, value)| match value.try_into::<ConfigProfile>() {
+            Ok(profile) => Ok((name, profile)),
+            Err(e) => Err(D::Error::custom(format!(
+                "invalid profile `{name}`: {}",
+                e.message()
+            ))),
+        })
+        .collect()
+}
@@ -11,15 +11,91 @@ use serde::Deserialize;
 use serde::Serialize;
 use strum_macros::Display;
 
+use crate::tool_apply_patch::ApplyPatchToolType;
+use codex_protocol::config_types::PullRequestProvider;
+
+/// An entry in `mcp_servers`.
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct McpServerConfig {
-    pub command: String,
+    #[serde(flatten)]
+    pub transport: McpServerTransport,
+
+    /// When to start the server.
+    #[serde(default)]
+    pub startup: McpServerStartup,
+
+    /// How long the server may take to start and answer `initialize` and
+    /// `tools/list` before Codex gives up on it. Defaults to 10 seconds.
+    pub startup_timeout_ms: Option<u64>,
+
+    /// Only these tools are offered to the model. Defaults to all of them.
+    #[serde(default)]
+    pub enabled_tools: Option<Vec<String>>,
+
+    /// Tools never offered to the model.
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+
+    /// Prepended to the names of the server's tools instead of
+    /// `<server>__`. May be empty.
+    pub tool_prefix: Option<String>,
 
+    /// Tool name on the server -> name offered to the model, used as is.
     #[serde(default)]
-    pub args: Vec<String>,
+    pub tool_names: HashMap<String, String>,
+}
+
+/// When Codex starts an MCP server.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum McpServerStartup {
+    /// With the session.
+    #[default]
+    Eager,
+    /// The first time its tools are needed, i.e. before the first turn.
+    Lazy,
+}
+
+/// How to reach an MCP server: either a command that Codex spawns and talks
+/// to over stdio, or the URL of a server that speaks the Streamable HTTP
+/// transport.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum McpServerTransport {
+    Stdio {
+        command: String,
+
+        #[serde(default)]
+        args: Vec<String>,
+
+        #[serde(default)]
+        env: Option<HashMap<String, String>>,
+    },
+    StreamableHttp {
+        url: String,
+
+        /// Used by `codex mcp login` when the server requires OAuth.
+        #[serde(default)]
+        oauth: McpOAuthConfig,
+    },
+}
+
+/// OAuth client settings for an MCP server reached over HTTP. By default
+/// Codex registers itself with the authorization server.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct McpOAuthConfig {
+    /// Client registered ahead of time with the authorization server.
+    pub client_id: Option<String>,
+
+    pub client_secret: Option<String>,
 
     #[serde(default)]
-    pub env: Option<HashMap<String, String>>,
+    pub scopes: Vec<String>,
+
+    /// Port for the local server that receives the authorization code.
+    /// Needed when the registered redirect URI has a fixed port.
+    pub callback_port: Option<u16>,
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
@@ -55,6 +131,7 @@ impl UriBasedFileOpener {
 
 /// Settings that govern if and what will be written to `~/.codex/history.jsonl`.
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
 pub struct History {
     /// If true, history entries will not be written to disk.
     pub persistence: HistoryPersistence,
@@ -62,6 +139,9 @@ pub struct History {
     /// If set, the maximum size of the history file in bytes.
     /// TODO(mbolin): Not currently honored.
     pub max_bytes: Option<usize>,
+
+    /// If set, only this many of the newest entries are kept.
+    pub max_entries: Option<usize>,
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq, Default)]
@@ -74,24 +154,276 @@ pub enum HistoryPersistence {
     None,
 }
 
+/// Settings for commands that may run without asking the user.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Approvals {
+    /// Command prefixes, such as `"cargo test"`, that never need approval.
+    /// They are split into words like a shell would and matched against the
+    /// leading words of each command.
+    #[serde(default)]
+    pub trusted_commands: Vec<String>,
+
+    /// Read-only commands added to the built-in list of commands that never
+    /// need approval.
+    #[serde(default)]
+    pub safe_commands: Vec<SafeCommandRule>,
+}
+
+/// A read-only program, or program and subcommand, that may run without
+/// approval as long as none of its forbidden flags is passed. The built-in
+/// rules and `[[approvals.safe_commands]]` entries share this shape.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SafeCommandRule {
+    /// Program name as it appears in the command, e.g. `"rg"`.
+    pub program: String,
+    /// When not empty, the first argument must be one of these, e.g. `"log"`
+    /// for `git log`.
+    #[serde(default)]
+    pub subcommands: Vec<String>,
+    /// Flags that make the command unsafe, such as ones that write files or
+    /// run other programs. A long flag also matches its `--flag=value` form
+    /// and a single-letter flag also matches inside a group such as `-ni`.
+    #[serde(default)]
+    pub forbidden_flags: Vec<String>,
+    /// Most arguments not starting with `-` the command may take after the
+    /// subcommand, for programs whose later operands are written to, such as
+    /// `uniq IN OUT`.
+    #[serde(default)]
+    pub max_operands: Option<usize>,
+}
+
+/// Settings for the `[exec]` table, which limits how much command output is
+/// shown to the model, how long and how large commands may run and whether
+/// they run under a pseudo-terminal.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExecToml {
+    /// Defaults to 10 KiB.
+    pub max_output_bytes: Option<usize>,
+    /// Defaults to 256.
+    pub max_output_lines: Option<usize>,
+    /// Wall-clock timeout for commands that do not set their own. Defaults to
+    /// 10 seconds.
+    pub timeout_ms: Option<u64>,
+    /// Address space limit for each command, in MiB. Linux only; unlimited by
+    /// default.
+    pub max_memory_mb: Option<u64>,
+    /// Kill a command once it has written this many bytes of output.
+    /// Unlimited by default.
+    pub max_captured_output_bytes: Option<usize>,
+    /// Run commands under a pseudo-terminal unless the model asks otherwise.
+    /// Defaults to false.
+    pub use_pty: Option<bool>,
+    /// Size of the pseudo-terminal. Defaults to 24 rows and 80 columns.
+    pub pty_rows: Option<u16>,
+    pub pty_cols: Option<u16>,
+    /// Text fed to stdin of commands run under a pseudo-terminal, such as
+    /// `"y\n"`. By default reading from stdin hits EOF right away.
+    pub pty_stdin_response: Option<String>,
+}
+
+/// Settings for the `[otel]` table, which exports tracing spans to an
+/// OpenTelemetry collector. Only used in builds with the `otel` feature.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OtelToml {
+    /// OTLP/HTTP endpoint, e.g. `"http://localhost:4318/v1/traces"`. Nothing
+    /// is exported when unset.
+    pub endpoint: Option<String>,
+    /// Headers sent with every export request, e.g. for authentication.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Attach prompt, command and output text to spans. Defaults to false.
+    pub include_content: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OtelConfig {
+    pub endpoint: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub include_content: bool,
+}
+
+impl From<OtelToml> for OtelConfig {
+    fn from(toml: OtelToml) -> Self {
+        Self {
+            endpoint: toml.endpoint,
+            headers: toml.headers,
+            include_content: toml.include_content.unwrap_or(false),
+        }
+    }
+}
+
+/// Settings for the `[stream_coalescing]` table, which controls how streamed
+/// text and reasoning deltas are merged before they reach the frontends.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct StreamCoalescingToml {
+    /// How long deltas are held back to be merged, in milliseconds.
+    /// Defaults to 16; 0 forwards every delta as the model sent it.
+    pub window_ms: Option<u64>,
+    /// Send the merged delta once it reaches this many bytes. Defaults to
+    /// 4096.
+    pub max_bytes: Option<usize>,
+}
+
+/// Settings for the `[rollout]` table, which controls how often the session
+/// rollout file is synced to disk.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RolloutToml {
+    /// Sync after this many entries have been written since the last sync.
+    /// Defaults to 32; 1 syncs every entry.
+    pub fsync_every_entries: Option<usize>,
+    /// Sync once the oldest unsynced entry is this many milliseconds old.
+    /// Defaults to 1000.
+    pub fsync_interval_ms: Option<u64>,
+}
+
+/// A `[tools.custom.<name>]` entry: a tool offered to the model that runs a
+/// local command.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CustomToolToml {
+    /// What the tool does, shown to the model.
+    pub description: String,
+    /// JSON Schema of the tool's arguments. Defaults to an object with no
+    /// properties.
+    #[serde(default)]
+    pub parameters: Option<serde_json::Value>,
+    /// The program and its arguments. With `input = "argv"`, `{name}` in an
+    /// argument is replaced with the argument `name` the model passed.
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub input: CustomToolInput,
+    /// Ask the user before every call, even when the approval policy would
+    /// run the command without asking.
+    #[serde(default)]
+    pub requires_approval: bool,
+    /// Timeout for the command. Defaults to the `[exec]` timeout.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// How a custom tool's command receives the arguments the model passed.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomToolInput {
+    /// Substituted for `{name}` placeholders in `command`.
+    #[default]
+    Argv,
+    /// Written to stdin as a JSON object.
+    Stdin,
+}
+
+/// `[tools.pull_request]`: offers the model the `create_pull_request` tool.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PullRequestToolToml {
+    #[serde(default)]
+    pub provider: PullRequestProvider,
+    /// The remote to push to. Defaults to `origin`.
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// The branch to merge into when the model does not name one. Defaults
+    /// to the repository's default branch.
+    #[serde(default)]
+    pub base: Option<String>,
+    /// Environment variable holding the API token. Defaults to `GH_TOKEN` or
+    /// `GITHUB_TOKEN` for GitHub and `GITLAB_TOKEN` for GitLab; without one
+    /// the CLI's own login is used.
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// Ask before every call. Defaults to `true`.
+    #[serde(default)]
+    pub requires_approval: Option<bool>,
+    /// Answer calls with what would run instead of running it.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Timeout for the whole call. Defaults to the `[exec]` timeout.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// How much of a command's output the model sees. Longer output keeps its
+/// first and last lines, with a marker in between saying what was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecOutputLimits {
+    pub max_bytes: usize,
+    pub max_lines: usize,
+}
+
+impl Default for ExecOutputLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024,
+            max_lines: 256,
+        }
+    }
+}
+
+impl From<&ExecToml> for ExecOutputLimits {
+    fn from(toml: &ExecToml) -> Self {
+        let default = Self::default();
+        Self {
+            max_bytes: toml.max_output_bytes.unwrap_or(default.max_bytes),
+            // Keep at least one line so the tail of the output survives.
+            max_lines: toml.max_output_lines.unwrap_or(default.max_lines).max(1),
+        }
+    }
+}
+
 /// Collection of settings that are specific to the TUI.
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
-pub struct Tui {}
+#[serde(deny_unknown_fields)]
+pub struct Tui {
+    /// Show the full text of reasoning summaries instead of only the
+    /// heading of each part. Toggled at runtime with Ctrl+O.
+    #[serde(default)]
+    pub expand_reasoning: bool,
+
+    /// Send the contents of files mentioned with `@` that are at most this
+    /// many bytes along with the message. Unset, only their paths are sent.
+    #[serde(default)]
+    pub attach_mentioned_files_max_bytes: Option<u64>,
+}
 
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
 pub struct SandboxWorkspaceWrite {
     #[serde(default)]
     pub writable_roots: Vec<PathBuf>,
     #[serde(default)]
     pub network_access: bool,
+    /// Hosts reachable through the sandbox network proxy when
+    /// `network_access` is `false`.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
     #[serde(default)]
     pub exclude_tmpdir_env_var: bool,
     #[serde(default)]
     pub exclude_slash_tmp: bool,
+    /// Permit `writable_roots` entries that contain the home directory
+    /// (including `/`), which are otherwise rejected.
+    #[serde(default)]
+    pub allow_home: bool,
 }
 
+/// The `[sandbox]` table: settings that apply whatever the sandbox mode.
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SandboxToml {
+    /// Let commands write to the temp directory (`$TMPDIR`, or the OS default)
+    /// under the `read-only` sandbox.
+    #[serde(default)]
+    pub read_only_allow_tmp: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Display)]
 #[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
 pub enum ShellEnvironmentPolicyInherit {
     /// "Core" environment variables for the platform. On UNIX, this would
     /// include HOME, LOGNAME, PATH, SHELL, and USER, among others.
@@ -106,8 +438,9 @@ pub enum ShellEnvironmentPolicyInherit {
 }
 
 /// Policy for building the `env` when spawning a process via either the
-/// `shell` or `local_shell` tool.
+/// `shell` or `local_shell` tool, or an MCP server over stdio.
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ShellEnvironmentPolicyToml {
     pub inherit: Option<ShellEnvironmentPolicyInherit>,
 
@@ -124,12 +457,33 @@ pub struct ShellEnvironmentPolicyToml {
     pub experimental_use_profile: Option<bool>,
 }
 
+/// `[shell_environment]` in config.toml.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ShellEnvironmentToml {
+    pub policy: Option<ShellProfilePolicy>,
+}
+
+/// Whether commands see the environment set up by the user's shell profile,
+/// e.g. the `PATH` entries added by `nvm` or `pyenv`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellProfilePolicy {
+    /// Commands get the environment Codex was started with.
+    #[default]
+    None,
+    /// Commands get the environment of the user's login shell after its rc
+    /// file has run.
+    Profile,
+}
+
 pub type EnvironmentVariablePattern = WildMatchPattern<'*', '?'>;
 
 /// Deriving the `env` based on this policy works as follows:
 /// 1. Create an initial map based on the `inherit` policy.
 /// 2. If `ignore_default_excludes` is false, filter the map using the default
-///    exclude pattern(s), which are: `"*KEY*"` and `"*TOKEN*"`.
+///    exclude pattern(s), which are: `"*KEY*"`, `"*SECRET*"` and `"*TOKEN*"`.
+///    Patterns always match names case-insensitively.
 /// 3. If `exclude` is not empty, filter the map using the provided patterns.
 /// 4. Insert any entries from `r#set` into the map.
 /// 5. If non-empty, filter the map using the `include_only` patterns.
@@ -139,7 +493,7 @@ pub struct ShellEnvironmentPolicy {
     pub inherit: ShellEnvironmentPolicyInherit,
 
     /// True to skip the check to exclude default environment variables that
-    /// contain "KEY" or "TOKEN" in their name.
+    /// contain "KEY", "SECRET" or "TOKEN" in their name.
     pub ignore_default_excludes: bool,
 
     /// Environment variable names to exclude from the environment.
@@ -225,3 +579,79 @@ pub enum Verbosity {
     Medium,
     High,
 }
+
+/// The `[redactions]` config section.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionsToml {
+    /// Apply the built-in patterns for AWS access keys, GitHub tokens,
+    /// `Bearer` tokens and private key blocks. Defaults to `true`.
+    pub builtins: Option<bool>,
+
+    /// Additional patterns, applied after the built-in ones.
+    #[serde(default)]
+    pub patterns: Vec<RedactionPatternToml>,
+}
+
+/// A named regex whose matches are replaced with `[REDACTED:<name>]`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionPatternToml {
+    pub name: String,
+    pub regex: String,
+}
+
+/// How much context the built-in `web_search` tool retrieves per search.
+/// See https://platform.openai.com/docs/guides/tools-web-search#search-context-size
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum WebSearchContextSize {
+    Low,
+    Medium,
+    High,
+}
+
+/// User-defined entry in the `[model_families]` table. The table key is the
+/// model slug, or a glob pattern such as `"ft:gpt-4.1:*"` that covers several
+/// slugs. Unset fields keep the value of the built-in family.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ModelFamilyToml {
+    /// Built-in family to start from, e.g. `"gpt-4.1"`. Defaults to the
+    /// family the model slug itself resolves to, if any.
+    pub family: Option<String>,
+
+    pub needs_special_apply_patch_instructions: Option<bool>,
+
+    pub supports_reasoning_summaries: Option<bool>,
+
+    /// Whether the model accepts `"minimal"` reasoning effort.
+    pub supports_minimal_reasoning_effort: Option<bool>,
+
+    pub supports_verbosity: Option<bool>,
+
+    /// Whether user messages may include images.
+    pub supports_image_input: Option<bool>,
+
+    pub uses_local_shell_tool: Option<bool>,
+
+    /// Flavor of the `apply_patch` tool: `"freeform"` or `"function"`.
+    pub apply_patch_tool_type: Option<ApplyPatchToolType>,
+
+    /// Size of the context window in tokens.
+    pub context_window: Option<u64>,
+
+    /// Maximum number of output tokens the model can generate.
+    pub max_output_tokens: Option<u64>,
+
+    /// Sequences that end the model's answer. Chat Completions only.
+    pub stop: Option<Vec<String>>,
+
+    /// Text the model's answer is forced to start with, sent as a trailing
+    /// assistant message. Chat Completions only.
+    pub assistant_prefix: Option<String>,
+
+    /// Run Responses API requests in background mode. Responses API only.
+    pub use_background_mode: Option<bool>,
+}
@@ -0,0 +1,272 @@
+//! Strict deserialization of `config.toml`. Unknown keys are errors unless
+//! `allow_unknown_config = true`, and every error names the offending key,
+//! plus its line and column when it comes from the file rather than from a
+//! `-c` override.
+
+use std::path::Path;
+
+use toml::Value as TomlValue;
+
+use crate::config::ConfigToml;
+
+/// Deserializes the merged configuration: `config.toml` at `config_path`
+/// with the `-c` overrides applied. The file is only read again to locate
+/// an error.
+pub(crate) fn deserialize_config_toml(
+    mut root: TomlValue,
+    config_path: &Path,
+) -> std::io::Result<ConfigToml> {
+    let allow_unknown = root
+        .get("allow_unknown_config")
+        .and_then(TomlValue::as_bool)
+        .unwrap_or(false);
+    loop {
+        // Deserialize the value's text rather than the value itself so that
+        // errors carry a location to derive the key path from.
+        let text = toml::to_string(&root)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let err = match toml::from_str::<ConfigToml>(&text) {
+            Ok(cfg) => return Ok(cfg),
+            Err(err) => err,
+        };
+        let key = error_key_path(&text, &err);
+        // Each pass drops one unknown key, so this ends once all are gone.
+        if allow_unknown
+            && let Some(key) = key.as_deref()
+            && unknown_field(&err).is_some_and(|field| key.last() == Some(&field))
+            && remove_key(&mut root, key)
+        {
+            tracing::warn!("ignoring unknown config key `{}`", key.join("."));
+            continue;
+        }
+        let message = describe_error(&err, key, config_path);
+        tracing::error!("{message}");
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            message,
+        ));
+    }
+}
+
+/// The key that `err` rejects as unknown, if it is such an error.
+fn unknown_field(err: &toml::de::Error) -> Option<String> {
+    err.message()
+        .split_once("unknown field `")
+        .and_then(|(_, rest)| rest.split_once('`'))
+        .map(|(field, _)| field.to_string())
+}
+
+/// Key path of the key or value `err` points at in `text`.
+fn error_key_path(text: &str, err: &toml::de::Error) -> Option<Vec<String>> {
+    // Profiles are deserialized on their own, so their errors name the
+    // profile and point at the whole `profiles` table.
+    if let Some(rest) = err.message().strip_prefix("invalid profile `")
+        && let Some((profile, _)) = rest.split_once('`')
+    {
+        let mut path = vec!["profiles".to_string(), profile.to_string()];
+        path.extend(unknown_field(err));
+        return Some(path);
+    }
+    key_path_at(text, err.span()?.start)
+}
+
+fn remove_key(root: &mut TomlValue, path: &[String]) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    let mut current = root;
+    for segment in parents {
+        match current.get_mut(segment.as_str()) {
+            Some(next) => current = next,
+            None => return false,
+        }
+    }
+    current
+        .as_table_mut()
+        .is_some_and(|table| table.remove(last).is_some())
+}
+
+fn describe_error(err: &toml::de::Error, key: Option<Vec<String>>, config_path: &Path) -> String {
+    // The same error from the file alone means the file is at fault, and
+    // its text says where.
+    if let Ok(contents) = std::fs::read_to_string(config_path)
+        && let Err(file_err) = toml::from_str::<ConfigToml>(&contents)
+        && file_err.message() == err.message()
+        && let Some(span) = file_err.span()
+    {
+        let (line, column) = line_and_column(&contents, span.start);
+        let location = format!("{}:{line}:{column}", config_path.display());
+        return match error_key_path(&contents, &file_err) {
+            Some(key) => format!("{location}: `{}`: {}", key.join("."), err.message()),
+            None => format!("{location}: {}", err.message()),
+        };
+    }
+    match key {
+        Some(key) => format!(
+            "invalid configuration at `{}`: {}",
+            key.join("."),
+            err.message()
+        ),
+        None => format!("invalid configuration: {}", err.message()),
+    }
+}
+
+/// One-based line and column of byte `offset` in `text`.
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Dotted key path of the key or value at byte `offset`, from the table
+/// header above it and the key on its line. Values spanning several lines
+/// are only recognized on their first line.
+fn key_path_at(text: &str, offset: usize) -> Option<Vec<String>> {
+    let mut table = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let line_end = line_start + line.len();
+        let trimmed = line.trim();
+        let header = trimmed
+            .strip_prefix('[')
+            .map(|rest| rest.trim_start_matches('['))
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(keys, _)| split_key(keys));
+        if offset < line_end {
+            if let Some(header) = header {
+                return Some(header);
+            }
+            let (key, _) = line.split_once('=')?;
+            table.extend(split_key(key));
+            return Some(table);
+        }
+        if let Some(header) = header {
+            table = header;
+        }
+        line_start = line_end;
+    }
+    None
+}
+
+/// Splits a dotted TOML key such as `projects."/tmp/a.b".trust_level` into
+/// its unquoted segments.
+fn split_key(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in key.trim().chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '.') => segments.push(std::mem::take(&mut current).trim().to_string()),
+            (None, c) => current.push(c),
+        }
+    }
+    segments.push(current.trim().to_string());
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn load(contents: &str) -> (TempDir, std::io::Result<ConfigToml>) {
+        let dir = TempDir::new().expect("tempdir");
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, contents).expect("write config.toml");
+        let root = toml::from_str::<TomlValue>(contents).expect("valid TOML");
+        let result = deserialize_config_toml(root, &path);
+        (dir, result)
+    }
+
+    fn error(contents: &str) -> String {
+        let (dir, result) = load(contents);
+        let message = result.expect_err("config should be rejected").to_string();
+        let prefix = format!("{}", dir.path().join("config.toml").display());
+        message.replace(&prefix, "config.toml")
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_location() {
+        let message = error("model = \"o3\"\napproval_polcy = \"never\"\n");
+        assert!(
+            message.starts_with(
+                "config.toml:2:1: `approval_polcy`: unknown field `approval_polcy`, expected one of"
+            ),
+            "{message}"
+        );
+
+        let message = error("[sandbox_workspace_write]\nnetwork_acess = true\n");
+        assert!(
+            message.starts_with(
+                "config.toml:2:1: `sandbox_workspace_write.network_acess`: unknown field `network_acess`"
+            ),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn invalid_values_name_their_key() {
+        let message = error("[tui]\nexpand_reasoning = \"yes\"\n");
+        assert!(message.starts_with("config.toml:2:"), "{message}");
+        assert!(message.contains("`tui.expand_reasoning`"), "{message}");
+    }
+
+    #[test]
+    fn errors_from_overrides_have_no_file_location() {
+        let dir = TempDir::new().expect("tempdir");
+        let mut root = toml::from_str::<TomlValue>("[tui]\n").expect("valid TOML");
+        if let Some(tui) = root.get_mut("tui").and_then(TomlValue::as_table_mut) {
+            tui.insert("expand_reasoning".into(), TomlValue::String("yes".into()));
+        }
+        let message = deserialize_config_toml(root, &dir.path().join("config.toml"))
+            .expect_err("override should be rejected")
+            .to_string();
+        assert!(
+            message.starts_with("invalid configuration at `tui.expand_reasoning`: "),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_can_be_allowed() {
+        let (_dir, result) = load(
+            r#"
+allow_unknown_config = true
+approval_polcy = "never"
+
+[tui]
+future_option = 1
+
+[profiles.fast]
+model = "o3"
+also_from_the_future = true
+"#,
+        );
+        let cfg = result.expect("unknown keys should be ignored");
+        assert_eq!(
+            cfg.profiles.get("fast").and_then(|p| p.model.as_deref()),
+            Some("o3")
+        );
+    }
+
+    #[test]
+    fn key_paths_follow_table_headers_and_quotes() {
+        let text = "a = 1\n[projects.\"/tmp/x.y\"]\ntrust_level = \"trusted\"\n";
+        let offset = text.find("trust_level").expect("key present");
+        assert_eq!(
+            key_path_at(text, offset),
+            Some(vec![
+                "projects".to_string(),
+                "/tmp/x.y".to_string(),
+                "trust_level".to_string()
+            ])
+        );
+        assert_eq!(key_path_at(text, 0), Some(vec!["a".to_string()]));
+        assert_eq!(line_and_column(text, offset), (3, 1));
+    }
+}
@@ -1,10 +1,13 @@
+use std::sync::Arc;
+
 use codex_protocol::models::ResponseItem;
 
 /// Transcript of conversation history
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ConversationHistory {
-    /// The oldest items are at the beginning of the vector.
-    items: Vec<ResponseItem>,
+    /// The oldest items are at the beginning of the vector. Items are shared
+    /// with the prompts built from them, so a turn does not copy the history.
+    items: Vec<Arc<ResponseItem>>,
 }
 
 impl ConversationHistory {
@@ -14,6 +17,14 @@ impl ConversationHistory {
 
     /// Returns a clone of the contents in the transcript.
     pub(crate) fn contents(&self) -> Vec<ResponseItem> {
+        self.items
+            .iter()
+            .map(|item| item.as_ref().clone())
+            .collect()
+    }
+
+    /// The items in the transcript, shared rather than copied.
+    pub(crate) fn shared_contents(&self) -> Vec<Arc<ResponseItem>> {
         self.items.clone()
     }
 
@@ -28,36 +39,27 @@ impl ConversationHistory {
                 continue;
             }
 
-            self.items.push(item.clone());
+            self.items.push(Arc::new(item.clone()));
         }
     }
 
-    pub(crate) fn keep_last_messages(&mut self, n: usize) {
-        if n == 0 {
-            self.items.clear();
-            return;
-        }
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
 
-        // Collect the last N message items (assistant/user), newest to oldest.
-        let mut kept: Vec<ResponseItem> = Vec::with_capacity(n);
-        for item in self.items.iter().rev() {
-            if let ResponseItem::Message { role, content, .. } = item {
-                kept.push(ResponseItem::Message {
-                    // we need to remove the id or the model will complain that messages are sent without
-                    // their reasonings
-                    id: None,
-                    role: role.clone(),
-                    content: content.clone(),
-                });
-                if kept.len() == n {
-                    break;
-                }
-            }
-        }
+    /// Items in the transcript, oldest first.
+    pub(crate) fn items(&self) -> &[Arc<ResponseItem>] {
+        &self.items
+    }
 
-        // Preserve chronological order (oldest to newest) within the kept slice.
-        kept.reverse();
-        self.items = kept;
+    /// Replaces the items in `range` with `replacement`, e.g. a summary of
+    /// the conversation they contain.
+    pub(crate) fn replace_range(
+        &mut self,
+        range: std::ops::Range<usize>,
+        replacement: ResponseItem,
+    ) {
+        self.items.splice(range, [Arc::new(replacement)]);
     }
 }
 
@@ -140,4 +142,29 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn replace_range_keeps_surrounding_items() {
+        let mut h = ConversationHistory::default();
+        let items = [
+            user_msg("instructions"),
+            user_msg("first"),
+            assistant_msg("one"),
+            user_msg("second"),
+            assistant_msg("two"),
+        ];
+        h.record_items(items.iter());
+
+        h.replace_range(1..3, user_msg("summary"));
+
+        assert_eq!(
+            h.contents(),
+            vec![
+                user_msg("instructions"),
+                user_msg("summary"),
+                user_msg("second"),
+                assistant_msg("two"),
+            ]
+        );
+    }
 }
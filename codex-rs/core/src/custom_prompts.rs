@@ -4,12 +4,12 @@ use std::path::Path;
 use std::path::PathBuf;
 use tokio::fs;
 
-/// Return the default prompts directory: `$CODEX_HOME/prompts`.
-/// If `CODEX_HOME` cannot be resolved, returns `None`.
+/// Return the default prompts directory: `prompts` in the config directory.
+/// If the config directory cannot be resolved, returns `None`.
 pub fn default_prompts_dir() -> Option<PathBuf> {
-    crate::config::find_codex_home()
+    crate::codex_dirs::CodexDirs::resolve()
         .ok()
-        .map(|home| home.join("prompts"))
+        .map(|dirs| dirs.config_dir().join("prompts"))
 }
 
 /// Discover prompt files in the given directory, returning entries sorted by name.
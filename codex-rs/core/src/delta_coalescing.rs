@@ -0,0 +1,338 @@
+//! Merges runs of text and reasoning deltas from the model into fewer,
+//! larger deltas. Providers often stream a token or two per event, and each
+//! delta becomes an event for every frontend; coalescing them within a short
+//! window keeps the output just as live while sending a fraction of the
+//! events.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::client_common::ResponseEvent;
+use crate::client_common::ResponseStream;
+use crate::client_common::spawn_response_task;
+use crate::config_types::StreamCoalescingToml;
+use crate::error::Result;
+
+/// How long and how far deltas are held back to be merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaCoalescing {
+    /// A run of deltas is sent at the latest this long after its first delta
+    /// arrived. Zero turns coalescing off.
+    pub window: Duration,
+    /// A run of deltas is sent as soon as it holds this many bytes.
+    pub max_bytes: usize,
+}
+
+impl DeltaCoalescing {
+    /// Every delta is forwarded as the model sent it.
+    pub fn disabled() -> Self {
+        Self {
+            window: Duration::ZERO,
+            max_bytes: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero() && self.max_bytes > 0
+    }
+}
+
+impl Default for DeltaCoalescing {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(16),
+            max_bytes: 4096,
+        }
+    }
+}
+
+impl From<&StreamCoalescingToml> for DeltaCoalescing {
+    fn from(toml: &StreamCoalescingToml) -> Self {
+        let default = Self::default();
+        Self {
+            window: toml
+                .window_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.window),
+            max_bytes: toml.max_bytes.unwrap_or(default.max_bytes),
+        }
+    }
+}
+
+/// Wraps `inner` so that consecutive deltas of the same kind are merged.
+/// Every other event, `OutputItemDone` included, flushes the pending run
+/// first, so events keep their order.
+pub(crate) fn coalesce_deltas(inner: ResponseStream, settings: DeltaCoalescing) -> ResponseStream {
+    if !settings.is_enabled() {
+        return inner;
+    }
+    let metrics = inner.metrics.clone();
+    let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+    spawn_response_task(tx.clone(), forward(inner, tx, settings));
+    ResponseStream::new(rx, metrics)
+}
+
+async fn forward(
+    mut inner: ResponseStream,
+    tx: mpsc::Sender<Result<ResponseEvent>>,
+    settings: DeltaCoalescing,
+) {
+    let mut buffer = DeltaBuffer::new(settings.max_bytes);
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let next = match deadline {
+            Some(at) => match tokio::time::timeout_at(at, inner.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    deadline = None;
+                    if let Some(event) = buffer.flush()
+                        && tx.send(Ok(event)).await.is_err()
+                    {
+                        return;
+                    }
+                    continue;
+                }
+            },
+            None => inner.next().await,
+        };
+        let ready: Vec<Result<ResponseEvent>> = match next {
+            Some(Ok(event)) => buffer.push(event).into_iter().map(Ok).collect(),
+            // Errors end the stream, so whatever is pending goes first.
+            Some(Err(e)) => buffer.flush().into_iter().map(Ok).chain([Err(e)]).collect(),
+            None => {
+                if let Some(event) = buffer.flush() {
+                    let _ = tx.send(Ok(event)).await;
+                }
+                return;
+            }
+        };
+        for event in ready {
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+        deadline = match (buffer.is_empty(), deadline) {
+            (true, _) => None,
+            (false, Some(at)) => Some(at),
+            (false, None) => Some(Instant::now() + settings.window),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeltaKind {
+    OutputText,
+    ReasoningSummary,
+    ReasoningContent,
+}
+
+impl DeltaKind {
+    fn of(event: &ResponseEvent) -> Option<(Self, &str)> {
+        match event {
+            ResponseEvent::OutputTextDelta(delta) => Some((Self::OutputText, delta)),
+            ResponseEvent::ReasoningSummaryDelta(delta) => Some((Self::ReasoningSummary, delta)),
+            ResponseEvent::ReasoningContentDelta(delta) => Some((Self::ReasoningContent, delta)),
+            _ => None,
+        }
+    }
+
+    fn event(self, delta: String) -> ResponseEvent {
+        match self {
+            Self::OutputText => ResponseEvent::OutputTextDelta(delta),
+            Self::ReasoningSummary => ResponseEvent::ReasoningSummaryDelta(delta),
+            Self::ReasoningContent => ResponseEvent::ReasoningContentDelta(delta),
+        }
+    }
+}
+
+/// The run of same-kind deltas that has not been sent yet.
+struct DeltaBuffer {
+    pending: Option<(DeltaKind, String)>,
+    max_bytes: usize,
+}
+
+impl DeltaBuffer {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            pending: None,
+            max_bytes,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    /// Takes in `event` and returns the events that are ready to be sent, in
+    /// order.
+    fn push(&mut self, event: ResponseEvent) -> Vec<ResponseEvent> {
+        let mut ready = Vec::new();
+        let Some((kind, delta)) = DeltaKind::of(&event) else {
+            ready.extend(self.flush());
+            ready.push(event);
+            return ready;
+        };
+        match &mut self.pending {
+            Some((pending_kind, pending)) if *pending_kind == kind => pending.push_str(delta),
+            _ => {
+                ready.extend(self.flush());
+                self.pending = Some((kind, delta.to_string()));
+            }
+        }
+        if let Some((_, pending)) = &self.pending
+            && pending.len() >= self.max_bytes
+        {
+            ready.extend(self.flush());
+        }
+        ready
+    }
+
+    fn flush(&mut self) -> Option<ResponseEvent> {
+        self.pending.take().map(|(kind, delta)| kind.event(delta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use codex_protocol::models::ContentItem;
+    use codex_protocol::models::ResponseItem;
+    use pretty_assertions::assert_eq;
+    use rand::Rng;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::client_common::StreamMetrics;
+
+    fn message(text: &str) -> ResponseEvent {
+        ResponseEvent::OutputItemDone(ResponseItem::Message {
+            id: None,
+            role: "assistant".to_string(),
+            content: vec![ContentItem::OutputText {
+                text: text.to_string(),
+            }],
+        })
+    }
+
+    /// Debug rendering of `events` with adjacent same-kind deltas joined,
+    /// which coalescing must leave unchanged.
+    fn normalized(events: &[ResponseEvent]) -> Vec<String> {
+        let mut buffer = DeltaBuffer::new(usize::MAX);
+        let mut out: Vec<ResponseEvent> = Vec::new();
+        for event in events {
+            out.extend(buffer.push(event.clone()));
+        }
+        out.extend(buffer.flush());
+        debug(&out)
+    }
+
+    /// `ResponseEvent` has no `PartialEq`, so tests compare its `Debug`
+    /// rendering.
+    fn debug(events: &[ResponseEvent]) -> Vec<String> {
+        events.iter().map(|event| format!("{event:?}")).collect()
+    }
+
+    fn random_events(rng: &mut StdRng) -> Vec<ResponseEvent> {
+        const WORDS: [&str; 6] = ["a", "bc", "déf", " ", "ghij\n", "✓"];
+        (0..rng.random_range(0..60))
+            .map(|i| {
+                let word = WORDS[rng.random_range(0..WORDS.len())].to_string();
+                match rng.random_range(0..8) {
+                    0..=2 => ResponseEvent::OutputTextDelta(word),
+                    3 => ResponseEvent::ReasoningSummaryDelta(word),
+                    4 => ResponseEvent::ReasoningContentDelta(word),
+                    5 => ResponseEvent::ReasoningSummaryPartAdded,
+                    6 => message(&format!("item {i}")),
+                    _ => ResponseEvent::WebSearchCallBegin {
+                        call_id: format!("call {i}"),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn coalesced_deltas_concatenate_to_the_original() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..500 {
+            let events = random_events(&mut rng);
+            let max_bytes = rng.random_range(1..32);
+            let mut buffer = DeltaBuffer::new(max_bytes);
+            let mut coalesced = Vec::new();
+            for event in &events {
+                coalesced.extend(buffer.push(event.clone()));
+                // Stands in for the window running out.
+                if rng.random_bool(0.2) {
+                    coalesced.extend(buffer.flush());
+                }
+            }
+            coalesced.extend(buffer.flush());
+
+            assert_eq!(normalized(&coalesced), normalized(&events));
+            assert!(coalesced.len() <= events.len());
+            // A run is sent once it reaches `max_bytes`, so it overshoots by
+            // less than one word.
+            for event in &coalesced {
+                if let Some((_, delta)) = DeltaKind::of(event) {
+                    assert!(delta.len() < max_bytes + 5, "{delta:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_full_buffer_is_sent_right_away() {
+        let mut buffer = DeltaBuffer::new(4);
+        assert!(
+            buffer
+                .push(ResponseEvent::OutputTextDelta("ab".to_string()))
+                .is_empty()
+        );
+        assert_eq!(
+            debug(&buffer.push(ResponseEvent::OutputTextDelta("cd".to_string()))),
+            debug(&[ResponseEvent::OutputTextDelta("abcd".to_string())])
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn deltas_are_merged_up_to_the_next_item() {
+        let (tx, rx) = mpsc::channel(16);
+        let inner =
+            ResponseStream::new(rx, Arc::new(StreamMetrics::new(std::time::Instant::now())));
+        for event in [
+            ResponseEvent::Created,
+            ResponseEvent::ReasoningSummaryDelta("Think".to_string()),
+            ResponseEvent::ReasoningSummaryDelta("ing".to_string()),
+            ResponseEvent::OutputTextDelta("Hello".to_string()),
+            ResponseEvent::OutputTextDelta(", world".to_string()),
+            message("Hello, world"),
+        ] {
+            tx.send(Ok(event)).await.ok();
+        }
+        drop(tx);
+
+        let settings = DeltaCoalescing {
+            window: Duration::from_secs(60),
+            max_bytes: 1024,
+        };
+        let events: Vec<ResponseEvent> = coalesce_deltas(inner, settings)
+            .filter_map(|event| async move { event.ok() })
+            .collect()
+            .await;
+        assert_eq!(
+            debug(&events),
+            debug(&[
+                ResponseEvent::Created,
+                ResponseEvent::ReasoningSummaryDelta("Thinking".to_string()),
+                ResponseEvent::OutputTextDelta("Hello, world".to_string()),
+                message("Hello, world"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! A small, supported surface for embedding Codex in another program.
+//!
+//! [`CodexBuilder`] loads `config.toml` the same way the CLI does, applies the
+//! settings given to it on top, and starts a session. The returned
+//! [`CodexHandle`] runs one turn per [`CodexHandle::submit`] and streams the
+//! events of that turn back to the caller.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use codex_login::AuthManager;
+use futures::Stream;
+use uuid::Uuid;
+
+use crate::CodexConversation;
+use crate::ConversationManager;
+use crate::ModelProviderInfo;
+use crate::NewConversation;
+use crate::cassette::Cassette;
+use crate::codex_dirs::CodexDirs;
+use crate::config::Config;
+use crate::config::ConfigOverrides;
+use crate::config::load_config_as_toml_with_cli_overrides;
+use crate::error::Result as CodexResult;
+use crate::protocol::AskForApproval;
+use crate::protocol::Event;
+use crate::protocol::EventMsg;
+use crate::protocol::InputItem;
+use crate::protocol::Op;
+use crate::protocol::ReviewDecision;
+use crate::protocol::SandboxPolicy;
+use crate::protocol::SessionConfiguredEvent;
+
+/// Configures and starts an embedded Codex session.
+///
+/// Settings that are not given keep the value from `config.toml`, or the
+/// default when it does not set them either.
+#[derive(Debug, Default)]
+pub struct CodexBuilder {
+    codex_home: Option<PathBuf>,
+    overrides: ConfigOverrides,
+    model_provider: Option<ModelProviderInfo>,
+    sandbox_policy: Option<SandboxPolicy>,
+    user_instructions: Option<String>,
+    cassette: Option<Cassette>,
+}
+
+impl CodexBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory holding `config.toml`, credentials and rollouts, like
+    /// `$CODEX_HOME`. Defaults to the directories described in
+    /// [`crate::codex_dirs`].
+    pub fn codex_home(mut self, codex_home: impl Into<PathBuf>) -> Self {
+        self.codex_home = Some(codex_home.into());
+        self
+    }
+
+    /// Working directory of the session. Defaults to the process's.
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.overrides.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.overrides.model = Some(model.into());
+        self
+    }
+
+    /// Uses `provider` instead of one of the `model_providers` from the
+    /// configuration.
+    pub fn model_provider(mut self, provider: ModelProviderInfo) -> Self {
+        self.model_provider = Some(provider);
+        self
+    }
+
+    pub fn sandbox_policy(mut self, sandbox_policy: SandboxPolicy) -> Self {
+        self.sandbox_policy = Some(sandbox_policy);
+        self
+    }
+
+    pub fn approval_policy(mut self, approval_policy: AskForApproval) -> Self {
+        self.overrides.approval_policy = Some(approval_policy);
+        self
+    }
+
+    /// Replaces the instructions from `$CODEX_HOME/AGENTS.md`. `AGENTS.md`
+    /// files in the working directory are still added.
+    pub fn user_instructions(mut self, user_instructions: impl Into<String>) -> Self {
+        self.user_instructions = Some(user_instructions.into());
+        self
+    }
+
+    /// Records model requests to, or answers them from, `cassette` instead
+    /// of the one named by `CODEX_RECORD` or `CODEX_REPLAY`.
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Loads the configuration and starts the session.
+    pub async fn build(self) -> CodexResult<CodexHandle> {
+        let Self {
+            codex_home,
+            overrides,
+            model_provider,
+            sandbox_policy,
+            user_instructions,
+            cassette,
+        } = self;
+
+        let dirs = match codex_home {
+            Some(codex_home) => CodexDirs::single(codex_home),
+            None => CodexDirs::resolve()?,
+        };
+        let cfg = load_config_as_toml_with_cli_overrides(dirs.config_dir(), Vec::new())?;
+        let mut config = Config::load_from_base_config_with_overrides(cfg, overrides, dirs)?;
+        if let Some(provider) = model_provider {
+            config.model_provider_id = provider.name.clone();
+            config.model_provider = provider;
+        }
+        if let Some(sandbox_policy) = sandbox_policy {
+            config.sandbox_policy = sandbox_policy;
+        }
+        if let Some(user_instructions) = user_instructions {
+            config.user_instructions = Some(user_instructions);
+        }
+        if let Some(cassette) = cassette {
+            config.cassette = Some(cassette);
+        }
+
+        let auth_manager = AuthManager::shared(
+            config.dirs.config_dir().to_path_buf(),
+            config.preferred_auth_method,
+        );
+        let NewConversation {
+            conversation_id,
+            conversation,
+            session_configured,
+        } = ConversationManager::new(auth_manager)
+            .new_conversation(config)
+            .await?;
+        Ok(CodexHandle {
+            conversation_id,
+            conversation,
+            session_configured,
+            patch_approvals: Mutex::new(HashSet::new()),
+        })
+    }
+}
+
+/// A running session started by [`CodexBuilder`].
+pub struct CodexHandle {
+    conversation_id: Uuid,
+    conversation: Arc<CodexConversation>,
+    session_configured: SessionConfiguredEvent,
+
+    /// Ids of the patch approvals requested so far; every other approval is
+    /// for a command.
+    patch_approvals: Mutex<HashSet<String>>,
+}
+
+impl CodexHandle {
+    pub fn conversation_id(&self) -> Uuid {
+        self.conversation_id
+    }
+
+    /// The model, rollout path and history details the session started with.
+    pub fn session_configured(&self) -> &SessionConfiguredEvent {
+        &self.session_configured
+    }
+
+    /// Sends `user_message` to the agent and returns the events it produces.
+    /// The stream ends after the turn completes or is aborted.
+    ///
+    /// Approval requests stop the turn until they are answered with
+    /// [`Self::approve`], so keep polling the stream while deciding.
+    pub async fn submit(
+        &self,
+        user_message: impl Into<String>,
+    ) -> CodexResult<impl Stream<Item = Event> + '_> {
+        self.conversation
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text {
+                    text: user_message.into(),
+                }],
+            })
+            .await?;
+
+        Ok(futures::stream::unfold(false, move |done| async move {
+            if done {
+                return None;
+            }
+            let event = self.conversation.next_event().await.ok()?;
+            if matches!(event.msg, EventMsg::ApplyPatchApprovalRequest(_)) {
+                self.patch_approvals
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(event.id.clone());
+            }
+            let done = matches!(
+                event.msg,
+                EventMsg::TaskComplete(_) | EventMsg::TurnAborted(_)
+            );
+            Some((event, done))
+        }))
+    }
+
+    /// Answers the approval request carried by the event with id `id`.
+    pub async fn approve(
+        &self,
+        id: impl Into<String>,
+        decision: ReviewDecision,
+    ) -> CodexResult<()> {
+        let id = id.into();
+        let is_patch = self
+            .patch_approvals
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id);
+        let op = if is_patch {
+            Op::PatchApproval {
+                id,
+                decision,
+                selection: None,
+                feedback: None,
+            }
+        } else {
+            Op::ExecApproval {
+                id,
+                decision,
+                feedback: None,
+            }
+        };
+        self.conversation.submit(op).await?;
+        Ok(())
+    }
+
+    /// Stops the session and waits for the agent to finish shutting down.
+    pub async fn shutdown(self) -> CodexResult<()> {
+        self.conversation.submit(Op::Shutdown).await?;
+        loop {
+            let event = self.conversation.next_event().await?;
+            if matches!(event.msg, EventMsg::ShutdownComplete) {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -1,26 +1,102 @@
 use serde::Deserialize;
 use serde::Serialize;
-use strum_macros::Display as DeriveDisplay;
 
+use crate::exec_env::EnvironmentSummary;
+use crate::git_info::GitHeadState;
+use crate::git_info::VersionControl;
+use crate::landlock::NetworkEnforcement;
+use crate::landlock::network_enforcement;
 use crate::protocol::AskForApproval;
 use crate::protocol::SandboxPolicy;
 use crate::shell::Shell;
 use codex_protocol::config_types::SandboxMode;
 use codex_protocol::models::ContentItem;
 use codex_protocol::models::ResponseItem;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
 
 /// wraps environment context message in a tag for the model to parse more easily.
 pub(crate) const ENVIRONMENT_CONTEXT_START: &str = "<environment_context>";
 pub(crate) const ENVIRONMENT_CONTEXT_END: &str = "</environment_context>";
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, DeriveDisplay)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
-#[strum(serialize_all = "kebab-case")]
 pub enum NetworkAccess {
     Restricted,
     Enabled,
+    /// Only the listed domains, through the sandbox network proxy.
+    Allowlist(Vec<String>),
+    /// The policy restricts the network but the sandbox cannot enforce it on
+    /// this system, so commands that use the network need approval.
+    Unenforced,
 }
+
+impl NetworkAccess {
+    /// The access sandboxed commands actually get on this system.
+    fn effective(self) -> Self {
+        if cfg!(target_os = "linux") {
+            self.under_linux_sandbox(network_enforcement())
+        } else {
+            self
+        }
+    }
+
+    /// The access commands actually get when the Linux sandbox restricts the
+    /// network with `enforcement`.
+    fn under_linux_sandbox(self, enforcement: NetworkEnforcement) -> Self {
+        match (self, enforcement) {
+            (
+                NetworkAccess::Restricted | NetworkAccess::Allowlist(_),
+                NetworkEnforcement::Unavailable,
+            ) => NetworkAccess::Unenforced,
+            // Without Landlock the proxy port cannot be allowed on its own.
+            (NetworkAccess::Allowlist(_), NetworkEnforcement::Seccomp) => NetworkAccess::Restricted,
+            (access, _) => access,
+        }
+    }
+}
+
+impl fmt::Display for NetworkAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkAccess::Restricted => f.write_str("restricted"),
+            NetworkAccess::Enabled => f.write_str("enabled"),
+            NetworkAccess::Allowlist(domains) => {
+                write!(f, "restricted (allowlist: {})", domains.join(", "))
+            }
+            NetworkAccess::Unenforced => f.write_str(
+                "enabled (not enforced: the sandbox cannot restrict network access on this system; commands that use the network require approval)",
+            ),
+        }
+    }
+}
+
+/// Operating system and CPU architecture of the machine Codex runs on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Platform {
+    /// OS name and version, e.g. "macos 14.5.0" or "linux".
+    pub os: String,
+    /// CPU architecture, e.g. "aarch64" or "x86_64".
+    pub arch: String,
+}
+
+impl Platform {
+    pub(crate) fn detect() -> Self {
+        let os = std::env::consts::OS;
+        let version = os_info::get().version().clone();
+        let os = if version == os_info::Version::Unknown {
+            os.to_string()
+        } else {
+            format!("{os} {version}")
+        };
+        Self {
+            os,
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename = "environment_context", rename_all = "snake_case")]
 pub(crate) struct EnvironmentContext {
@@ -28,7 +104,24 @@ pub(crate) struct EnvironmentContext {
     pub approval_policy: Option<AskForApproval>,
     pub sandbox_mode: Option<SandboxMode>,
     pub network_access: Option<NetworkAccess>,
+    /// Roots writable in addition to the cwd and the temp directories.
+    pub writable_roots: Option<Vec<PathBuf>>,
+    /// Per-session directory for temporary files, writable whatever the
+    /// sandbox policy and exported to commands as `CODEX_SCRATCH_DIR`.
+    pub scratch_dir: Option<PathBuf>,
+    pub os: Option<String>,
+    pub arch: Option<String>,
     pub shell: Option<Shell>,
+    /// How the user's shell profile is applied to commands, when
+    /// `[shell_environment] policy = "profile"`.
+    pub shell_profile: Option<String>,
+    pub env_inherit: Option<String>,
+    pub env_removed: Option<Vec<String>>,
+    /// `none`, or `git` with the state of the worktree, e.g. `git (dirty)`.
+    pub version_control: Option<String>,
+    pub git_branch: Option<String>,
+    pub git_commit: Option<String>,
+    pub is_dirty: Option<bool>,
 }
 
 impl EnvironmentContext {
@@ -36,8 +129,36 @@ impl EnvironmentContext {
         cwd: Option<PathBuf>,
         approval_policy: Option<AskForApproval>,
         sandbox_policy: Option<SandboxPolicy>,
+        platform: Option<Platform>,
         shell: Option<Shell>,
+        env: Option<EnvironmentSummary>,
+        git_state: Option<GitHeadState>,
+        version_control: Option<VersionControl>,
     ) -> Self {
+        let (os, arch) = match platform {
+            Some(Platform { os, arch }) => (Some(os), Some(arch)),
+            None => (None, None),
+        };
+        let (env_inherit, env_removed) = match env {
+            Some(EnvironmentSummary { inherit, removed }) => (
+                Some(inherit.to_string()),
+                (!removed.is_empty()).then_some(removed),
+            ),
+            None => (None, None),
+        };
+        let GitHeadState {
+            branch: git_branch,
+            commit: git_commit,
+            is_dirty,
+        } = git_state.unwrap_or_default();
+        let writable_roots = match &sandbox_policy {
+            Some(SandboxPolicy::WorkspaceWrite { writable_roots, .. })
+                if !writable_roots.is_empty() =>
+            {
+                Some(writable_roots.clone())
+            }
+            _ => None,
+        };
         Self {
             cwd,
             approval_policy,
@@ -50,16 +171,34 @@ impl EnvironmentContext {
             network_access: match sandbox_policy {
                 Some(SandboxPolicy::DangerFullAccess) => Some(NetworkAccess::Enabled),
                 Some(SandboxPolicy::ReadOnly) => Some(NetworkAccess::Restricted),
-                Some(SandboxPolicy::WorkspaceWrite { network_access, .. }) => {
+                Some(SandboxPolicy::WorkspaceWrite {
+                    network_access,
+                    allowed_domains,
+                    ..
+                }) => {
                     if network_access {
                         Some(NetworkAccess::Enabled)
+                    } else if !allowed_domains.is_empty() {
+                        Some(NetworkAccess::Allowlist(allowed_domains))
                     } else {
                         Some(NetworkAccess::Restricted)
                     }
                 }
                 None => None,
-            },
+            }
+            .map(NetworkAccess::effective),
+            writable_roots,
+            scratch_dir: None,
+            os,
+            arch,
             shell,
+            shell_profile: None,
+            env_inherit,
+            env_removed,
+            version_control: version_control.map(|vc| vc.to_string()),
+            git_branch,
+            git_commit,
+            is_dirty,
         }
     }
 }
@@ -75,7 +214,18 @@ impl EnvironmentContext {
     ///   <approval_policy>...</approval_policy>
     ///   <sandbox_mode>...</sandbox_mode>
     ///   <network_access>...</network_access>
+    ///   <writable_roots>...</writable_roots>
+    ///   <scratch_dir>...</scratch_dir>
+    ///   <os>...</os>
+    ///   <arch>...</arch>
     ///   <shell>...</shell>
+    ///   <shell_profile>...</shell_profile>
+    ///   <env_inherit>...</env_inherit>
+    ///   <env_removed>...</env_removed>
+    ///   <version_control>...</version_control>
+    ///   <git_branch>...</git_branch>
+    ///   <git_commit>...</git_commit>
+    ///   <is_dirty>...</is_dirty>
     /// </environment_context>
     /// ```
     pub fn serialize_to_xml(self) -> String {
@@ -96,11 +246,59 @@ impl EnvironmentContext {
                 "  <network_access>{network_access}</network_access>"
             ));
         }
+        if let Some(writable_roots) = self.writable_roots {
+            let roots: Vec<_> = writable_roots
+                .iter()
+                .map(|root| root.to_string_lossy())
+                .collect();
+            lines.push(format!(
+                "  <writable_roots>{}</writable_roots>",
+                roots.join(", ")
+            ));
+        }
+        if let Some(scratch_dir) = self.scratch_dir {
+            lines.push(format!(
+                "  <scratch_dir>{}</scratch_dir>",
+                scratch_dir.to_string_lossy()
+            ));
+        }
+        if let Some(os) = self.os {
+            lines.push(format!("  <os>{os}</os>"));
+        }
+        if let Some(arch) = self.arch {
+            lines.push(format!("  <arch>{arch}</arch>"));
+        }
         if let Some(shell) = self.shell
             && let Some(shell_name) = shell.name()
         {
             lines.push(format!("  <shell>{shell_name}</shell>"));
         }
+        if let Some(shell_profile) = self.shell_profile {
+            lines.push(format!("  <shell_profile>{shell_profile}</shell_profile>"));
+        }
+        if let Some(env_inherit) = self.env_inherit {
+            lines.push(format!("  <env_inherit>{env_inherit}</env_inherit>"));
+        }
+        if let Some(env_removed) = self.env_removed {
+            lines.push(format!(
+                "  <env_removed>{}</env_removed>",
+                env_removed.join(", ")
+            ));
+        }
+        if let Some(version_control) = self.version_control {
+            lines.push(format!(
+                "  <version_control>{version_control}</version_control>"
+            ));
+        }
+        if let Some(git_branch) = self.git_branch {
+            lines.push(format!("  <git_branch>{git_branch}</git_branch>"));
+        }
+        if let Some(git_commit) = self.git_commit {
+            lines.push(format!("  <git_commit>{git_commit}</git_commit>"));
+        }
+        if let Some(is_dirty) = self.is_dirty {
+            lines.push(format!("  <is_dirty>{is_dirty}</is_dirty>"));
+        }
         lines.push(ENVIRONMENT_CONTEXT_END.to_string());
         lines.join("\n")
     }
@@ -117,3 +315,409 @@ impl From<EnvironmentContext> for ResponseItem {
         }
     }
 }
+
+/// The `(tag, value)` pairs of a serialized environment context, or `None`
+/// when `text` is not one.
+fn parse_fields(text: &str) -> Option<Vec<(&str, &str)>> {
+    let body = text
+        .strip_prefix(ENVIRONMENT_CONTEXT_START)?
+        .strip_suffix(ENVIRONMENT_CONTEXT_END)?;
+    let fields = body
+        .lines()
+        .filter_map(|line| {
+            let (tag, rest) = line.trim().strip_prefix('<')?.split_once('>')?;
+            let value = rest.strip_suffix(&format!("</{tag}>"))?;
+            Some((tag, value))
+        })
+        .collect();
+    Some(fields)
+}
+
+fn environment_context_text(item: &ResponseItem) -> Option<&str> {
+    match item {
+        ResponseItem::Message { role, content, .. } if role == "user" => {
+            content.iter().find_map(|c| match c {
+                ContentItem::InputText { text } if text.starts_with(ENVIRONMENT_CONTEXT_START) => {
+                    Some(text.as_str())
+                }
+                _ => None,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// What the model has been told about its environment so far. Environment
+/// context messages after the first only list the fields that changed, so
+/// they are folded in order, each one overriding the fields it carries.
+pub(crate) fn recorded_environment(items: &[ResponseItem]) -> HashMap<String, String> {
+    let mut recorded = HashMap::new();
+    for fields in items
+        .iter()
+        .filter_map(environment_context_text)
+        .filter_map(parse_fields)
+    {
+        for (tag, value) in fields {
+            recorded.insert(tag.to_string(), value.to_string());
+        }
+    }
+    recorded
+}
+
+/// Reduces a freshly built environment context message to the fields that
+/// differ from `recorded`, or `None` when nothing changed. A field missing
+/// from the new context is left alone rather than reported as removed, in
+/// line with the partial messages sent when the turn context is overridden.
+pub(crate) fn environment_context_delta(
+    item: ResponseItem,
+    recorded: &HashMap<String, String>,
+) -> Option<ResponseItem> {
+    let Some(fields) = environment_context_text(&item).and_then(parse_fields) else {
+        return Some(item);
+    };
+    let changed: Vec<_> = fields
+        .into_iter()
+        .filter(|(tag, value)| recorded.get(*tag).map(String::as_str) != Some(*value))
+        .collect();
+    if changed.is_empty() {
+        return None;
+    }
+    let mut lines = vec![ENVIRONMENT_CONTEXT_START.to_string()];
+    lines.extend(
+        changed
+            .into_iter()
+            .map(|(tag, value)| format!("  <{tag}>{value}</{tag}>")),
+    );
+    lines.push(ENVIRONMENT_CONTEXT_END.to_string());
+    Some(ResponseItem::Message {
+        id: None,
+        role: "user".to_string(),
+        content: vec![ContentItem::InputText {
+            text: lines.join("\n"),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_types::ShellEnvironmentPolicyInherit;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn detect_platform_uses_build_target() {
+        let platform = Platform::detect();
+        assert!(platform.os.starts_with(std::env::consts::OS));
+        assert_eq!(platform.arch, std::env::consts::ARCH);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn serialize_linux_environment_context() {
+        let context = EnvironmentContext::new(
+            Some(PathBuf::from("/home/user/project")),
+            Some(AskForApproval::OnRequest),
+            Some(SandboxPolicy::new_workspace_write_policy()),
+            Some(Platform {
+                os: "linux 22.04".to_string(),
+                arch: "x86_64".to_string(),
+            }),
+            Some(Shell::Unknown),
+            None,
+            None,
+            None,
+        );
+
+        let expected = r#"<environment_context>
+  <cwd>/home/user/project</cwd>
+  <approval_policy>on-request</approval_policy>
+  <sandbox_mode>workspace-write</sandbox_mode>
+  <network_access>restricted</network_access>
+  <os>linux 22.04</os>
+  <arch>x86_64</arch>
+</environment_context>"#;
+        assert_eq!(context.serialize_to_xml(), expected);
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn serialize_macos_environment_context() {
+        let shell: Shell = serde_json::from_value(serde_json::json!({
+            "Zsh": { "shell_path": "/bin/zsh", "zshrc_path": "/Users/user/.zshrc" }
+        }))
+        .expect("valid zsh shell");
+        let context = EnvironmentContext::new(
+            Some(PathBuf::from("/Users/user/project")),
+            Some(AskForApproval::OnRequest),
+            Some(SandboxPolicy::new_workspace_write_policy()),
+            Some(Platform {
+                os: "macos 14.5.0".to_string(),
+                arch: "aarch64".to_string(),
+            }),
+            Some(shell),
+            None,
+            None,
+            None,
+        );
+
+        let expected = r#"<environment_context>
+  <cwd>/Users/user/project</cwd>
+  <approval_policy>on-request</approval_policy>
+  <sandbox_mode>workspace-write</sandbox_mode>
+  <network_access>restricted</network_access>
+  <os>macos 14.5.0</os>
+  <arch>aarch64</arch>
+  <shell>zsh</shell>
+</environment_context>"#;
+        assert_eq!(context.serialize_to_xml(), expected);
+    }
+
+    #[test]
+    fn scratch_dir_is_listed() {
+        let context = EnvironmentContext {
+            scratch_dir: Some(PathBuf::from("/cache/scratch/1234")),
+            ..EnvironmentContext::new(None, None, None, None, None, None, None, None)
+        };
+
+        assert_eq!(
+            context.serialize_to_xml(),
+            "<environment_context>\n  <scratch_dir>/cache/scratch/1234</scratch_dir>\n</environment_context>"
+        );
+    }
+
+    #[test]
+    fn shell_profile_is_listed() {
+        let context = EnvironmentContext {
+            shell_profile: Some("bash login environment (variables only)".to_string()),
+            ..EnvironmentContext::new(None, None, None, None, None, None, None, None)
+        };
+
+        assert_eq!(
+            context.serialize_to_xml(),
+            "<environment_context>\n  <shell_profile>bash login environment (variables only)</shell_profile>\n</environment_context>"
+        );
+    }
+
+    #[test]
+    fn network_allowlist_is_listed() {
+        let context = EnvironmentContext::new(
+            None,
+            None,
+            Some(SandboxPolicy::WorkspaceWrite {
+                writable_roots: vec![],
+                network_access: false,
+                allowed_domains: vec!["crates.io".to_string(), "github.com".to_string()],
+                exclude_tmpdir_env_var: false,
+                exclude_slash_tmp: false,
+            }),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // Kernels without Landlock network rules cannot reach the proxy.
+        let network_access =
+            NetworkAccess::Allowlist(vec!["crates.io".to_string(), "github.com".to_string()])
+                .effective();
+        let expected = format!(
+            r#"<environment_context>
+  <sandbox_mode>workspace-write</sandbox_mode>
+  <network_access>{network_access}</network_access>
+</environment_context>"#
+        );
+        assert_eq!(context.serialize_to_xml(), expected);
+    }
+
+    #[test]
+    fn network_access_reflects_linux_enforcement() {
+        let allowlist = NetworkAccess::Allowlist(vec!["crates.io".to_string()]);
+        assert_eq!(
+            allowlist
+                .clone()
+                .under_linux_sandbox(NetworkEnforcement::Landlock),
+            allowlist
+        );
+        assert_eq!(
+            allowlist
+                .clone()
+                .under_linux_sandbox(NetworkEnforcement::Seccomp),
+            NetworkAccess::Restricted
+        );
+        assert_eq!(
+            allowlist.under_linux_sandbox(NetworkEnforcement::Unavailable),
+            NetworkAccess::Unenforced
+        );
+        assert_eq!(
+            NetworkAccess::Restricted.under_linux_sandbox(NetworkEnforcement::Unavailable),
+            NetworkAccess::Unenforced
+        );
+        assert_eq!(
+            NetworkAccess::Enabled.under_linux_sandbox(NetworkEnforcement::Unavailable),
+            NetworkAccess::Enabled
+        );
+    }
+
+    #[test]
+    fn extra_writable_roots_are_listed() {
+        let context = EnvironmentContext::new(
+            None,
+            None,
+            Some(SandboxPolicy::WorkspaceWrite {
+                writable_roots: vec![
+                    PathBuf::from("/work/target"),
+                    PathBuf::from("/home/user/.cache/my-tool"),
+                ],
+                network_access: false,
+                allowed_domains: vec![],
+                exclude_tmpdir_env_var: false,
+                exclude_slash_tmp: false,
+            }),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let expected = r#"<environment_context>
+  <sandbox_mode>workspace-write</sandbox_mode>
+  <network_access>restricted</network_access>
+  <writable_roots>/work/target, /home/user/.cache/my-tool</writable_roots>
+</environment_context>"#;
+        assert_eq!(context.serialize_to_xml(), expected);
+    }
+
+    #[test]
+    fn removed_environment_variables_are_listed() {
+        let context = EnvironmentContext::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(EnvironmentSummary {
+                inherit: ShellEnvironmentPolicyInherit::All,
+                removed: vec!["GITHUB_TOKEN".to_string(), "NPM_TOKEN".to_string()],
+            }),
+            None,
+            None,
+        );
+
+        let expected = r#"<environment_context>
+  <env_inherit>all</env_inherit>
+  <env_removed>GITHUB_TOKEN, NPM_TOKEN</env_removed>
+</environment_context>"#;
+        assert_eq!(context.serialize_to_xml(), expected);
+    }
+
+    #[test]
+    fn version_control_is_listed() {
+        let context = EnvironmentContext::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(GitHeadState {
+                branch: Some("main".to_string()),
+                commit: None,
+                is_dirty: Some(true),
+            }),
+            Some(VersionControl::Git {
+                is_dirty: Some(true),
+            }),
+        );
+
+        let expected = r#"<environment_context>
+  <version_control>git (dirty)</version_control>
+  <git_branch>main</git_branch>
+  <is_dirty>true</is_dirty>
+</environment_context>"#;
+        assert_eq!(context.serialize_to_xml(), expected);
+
+        let outside_repo = EnvironmentContext::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(VersionControl::None),
+        );
+        assert_eq!(
+            outside_repo.serialize_to_xml(),
+            "<environment_context>\n  <version_control>none</version_control>\n</environment_context>"
+        );
+    }
+
+    fn context_with_cwd(cwd: &str) -> ResponseItem {
+        ResponseItem::from(EnvironmentContext::new(
+            Some(PathBuf::from(cwd)),
+            Some(AskForApproval::OnRequest),
+            Some(SandboxPolicy::ReadOnly),
+            None,
+            None,
+            None,
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn later_environment_contexts_override_earlier_fields() {
+        let items = vec![
+            context_with_cwd("/old"),
+            ResponseItem::from(EnvironmentContext::new(
+                None,
+                Some(AskForApproval::Never),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )),
+        ];
+
+        let recorded = recorded_environment(&items);
+        assert_eq!(recorded.get("cwd").map(String::as_str), Some("/old"));
+        assert_eq!(
+            recorded.get("approval_policy").map(String::as_str),
+            Some("never")
+        );
+        assert_eq!(
+            recorded.get("sandbox_mode").map(String::as_str),
+            Some("read-only")
+        );
+    }
+
+    #[test]
+    fn unchanged_environment_context_is_skipped() {
+        let recorded = recorded_environment(&[context_with_cwd("/repo")]);
+        assert_eq!(
+            environment_context_delta(context_with_cwd("/repo"), &recorded),
+            None
+        );
+    }
+
+    #[test]
+    fn changed_environment_context_only_lists_the_changes() {
+        let recorded = recorded_environment(&[context_with_cwd("/old")]);
+        let expected = ResponseItem::Message {
+            id: None,
+            role: "user".to_string(),
+            content: vec![ContentItem::InputText {
+                text: "<environment_context>\n  <cwd>/new</cwd>\n</environment_context>"
+                    .to_string(),
+            }],
+        };
+        assert_eq!(
+            environment_context_delta(context_with_cwd("/new"), &recorded),
+            Some(expected)
+        );
+    }
+}
@@ -1,11 +1,16 @@
+use crate::protocol::ErrorKind;
 use reqwest::StatusCode;
+use serde::Deserialize;
 use serde_json;
 use std::io;
+use std::path::PathBuf;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::task::JoinError;
 use uuid::Uuid;
 
+use crate::rate_limits::format_wait;
+
 pub type Result<T> = std::result::Result<T, CodexErr>;
 
 #[derive(Error, Debug)]
@@ -24,10 +29,6 @@ pub enum SandboxErr {
     #[error("seccomp backend error")]
     SeccompBackend(#[from] seccompiler::BackendError),
 
-    /// Command timed out
-    #[error("command timed out")]
-    Timeout,
-
     /// Command was killed by a signal
     #[error("command was killed by a signal")]
     Signal(i32),
@@ -42,11 +43,67 @@ pub enum CodexErr {
     /// Returned by ResponsesClient when the SSE stream disconnects or errors out **after** the HTTP
     /// handshake has succeeded but **before** it finished emitting `response.completed`.
     ///
-    /// The Session loop treats this as a transient error and will automatically retry the turn.
+    /// The Session loop retries the turn unless `retryable` is `false`, which is the case when
+    /// the provider reported a failure that another attempt would repeat.
     ///
     /// Optionally includes the requested delay before retrying the turn.
-    #[error("stream disconnected before completion: {0}")]
-    Stream(String, Option<Duration>),
+    #[error("stream disconnected before completion: {message}")]
+    StreamDisconnected {
+        message: String,
+        retryable: bool,
+        retry_after: Option<Duration>,
+    },
+
+    /// The prompt does not fit the model's context window. The Session loop compacts the
+    /// history and retries once before giving up.
+    #[error(
+        "the conversation no longer fits the model's context window; run /compact or start a new conversation"
+    )]
+    ContextWindowExceeded,
+
+    /// The account has no quota or credits left for the API.
+    #[error("{}", quota_exceeded_message(.plan_type.as_deref()))]
+    QuotaExceeded { plan_type: Option<String> },
+
+    /// The provider rejected the credentials, even after refreshing the token.
+    #[error("authentication failed; your credentials may have expired, run `codex login` again")]
+    AuthExpired,
+
+    /// The provider rejected the request for any other reason.
+    #[error("unexpected status {status}: {message}")]
+    ProviderError {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+
+    /// The instructions use a `{{name}}` placeholder that is not defined,
+    /// with `strict_prompt_vars` set.
+    #[error(
+        "undefined variable `{0}` in the instructions; define it under [prompt_vars] or write `{{{{{{{{` for a literal `{{{{`"
+    )]
+    UndefinedPromptVar(String),
+
+    /// The `AGENTS.md` files do not fit `project_doc_max_bytes`, with
+    /// `project_doc_strict` set. `path` is the first file that did not fit.
+    #[error(
+        "{} ({size} bytes) does not fit project_doc_max_bytes ({limit}); shorten it or raise the limit",
+        .path.display()
+    )]
+    ProjectDocTooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: usize,
+    },
+
+    /// The model answered a compaction request without a summary, so the
+    /// history was left as it was.
+    #[error("the model returned no summary, so the conversation was not compacted")]
+    EmptyCompactionSummary,
+
+    /// A request missing from the cassette being replayed.
+    #[error("{0}")]
+    CassetteMiss(String),
 
     #[error("no conversation with id: {0}")]
     ConversationNotFound(Uuid),
@@ -68,13 +125,15 @@ pub enum CodexErr {
     #[error("interrupted (Ctrl-C)")]
     Interrupted,
 
-    /// Unexpected HTTP status code.
-    #[error("unexpected status {0}: {1}")]
-    UnexpectedStatus(StatusCode, String),
-
     #[error("{0}")]
     UsageLimitReached(UsageLimitReachedError),
 
+    /// The provider answered 429 and the request was not, or no longer,
+    /// retried. Carries the time until the exhausted limit resets, when the
+    /// rate limit headers report it.
+    #[error("rate limit reached{}", .0.map(|wait| format!("; resets in {}", format_wait(wait))).unwrap_or_default())]
+    RateLimitReached(Option<Duration>),
+
     #[error(
         "To use Codex with your ChatGPT plan, upgrade to Plus: https://openai.com/chatgpt/pricing."
     )]
@@ -162,6 +221,15 @@ impl std::fmt::Display for UsageLimitReachedError {
     }
 }
 
+fn quota_exceeded_message(plan_type: Option<&str>) -> String {
+    match plan_type {
+        Some(plan_type) => format!(
+            "You've run out of quota on the {plan_type} plan. Check your plan and billing details."
+        ),
+        None => "You've run out of quota. Check your plan and billing details.".to_string(),
+    }
+}
+
 fn format_reset_duration(total_secs: u64) -> String {
     let days = total_secs / 86_400;
     let hours = (total_secs % 86_400) / 3_600;
@@ -212,7 +280,146 @@ impl std::fmt::Display for EnvVarError {
     }
 }
 
+/// The `error` object of a provider's error response body or SSE error
+/// event.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ProviderErrorDetails {
+    #[serde(rename = "type")]
+    pub(crate) kind: Option<String>,
+    pub(crate) code: Option<String>,
+    pub(crate) message: Option<String>,
+
+    // Present on "usage_limit_reached" and "usage_not_included" errors.
+    pub(crate) plan_type: Option<String>,
+    pub(crate) resets_in_seconds: Option<u64>,
+}
+
+impl ProviderErrorDetails {
+    /// Parses the `error` object out of an HTTP error response body.
+    pub(crate) fn from_body(body: &str) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: ProviderErrorDetails,
+        }
+        serde_json::from_str::<ErrorBody>(body)
+            .ok()
+            .map(|body| body.error)
+    }
+
+    fn is_context_window_exceeded(&self) -> bool {
+        self.code.as_deref() == Some("context_length_exceeded")
+            // Anthropic reports it only in the message.
+            || self
+                .message
+                .as_deref()
+                .is_some_and(|message| message.starts_with("prompt is too long"))
+    }
+
+    fn is_quota_exceeded(&self) -> bool {
+        self.code.as_deref() == Some("insufficient_quota")
+            || self.kind.as_deref() == Some("insufficient_quota")
+    }
+
+    /// Error for a request the provider rejected with `status`.
+    pub(crate) fn into_http_error(self, status: StatusCode) -> CodexErr {
+        if self.is_context_window_exceeded() {
+            CodexErr::ContextWindowExceeded
+        } else if self.is_quota_exceeded() {
+            CodexErr::QuotaExceeded {
+                plan_type: self.plan_type,
+            }
+        } else if status == StatusCode::UNAUTHORIZED {
+            CodexErr::AuthExpired
+        } else {
+            CodexErr::ProviderError {
+                status,
+                code: self.code,
+                message: self.message.unwrap_or_default(),
+            }
+        }
+    }
+
+    /// Error for a failure the provider reported in the middle of a
+    /// response stream.
+    pub(crate) fn into_stream_error(self) -> CodexErr {
+        if self.is_context_window_exceeded() {
+            CodexErr::ContextWindowExceeded
+        } else if self.is_quota_exceeded() {
+            CodexErr::QuotaExceeded {
+                plan_type: self.plan_type,
+            }
+        } else {
+            CodexErr::StreamDisconnected {
+                retryable: self.kind.as_deref() != Some("invalid_request_error"),
+                message: self.message.unwrap_or_default(),
+                retry_after: None,
+            }
+        }
+    }
+}
+
+/// Error for a request the provider rejected with `status`, classified from
+/// the response body when it is the usual JSON error object.
+pub(crate) fn http_error(status: StatusCode, body: String) -> CodexErr {
+    match ProviderErrorDetails::from_body(&body) {
+        Some(details) => details.into_http_error(status),
+        None if status == StatusCode::UNAUTHORIZED => CodexErr::AuthExpired,
+        None => CodexErr::ProviderError {
+            status,
+            code: None,
+            message: body,
+        },
+    }
+}
+
 impl CodexErr {
+    /// A stream that ended early for a transient reason, retried without a
+    /// provider-requested delay.
+    pub(crate) fn stream_disconnected(message: impl Into<String>) -> Self {
+        CodexErr::StreamDisconnected {
+            message: message.into(),
+            retryable: true,
+            retry_after: None,
+        }
+    }
+
+    /// Whether retrying the same request could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CodexErr::StreamDisconnected { retryable, .. } => *retryable,
+            CodexErr::ContextWindowExceeded
+            | CodexErr::QuotaExceeded { .. }
+            | CodexErr::AuthExpired
+            | CodexErr::ProviderError { .. }
+            | CodexErr::UsageLimitReached(_)
+            | CodexErr::UsageNotIncluded
+            | CodexErr::Interrupted
+            | CodexErr::UndefinedPromptVar(_)
+            | CodexErr::CassetteMiss(_)
+            | CodexErr::EmptyCompactionSummary
+            | CodexErr::EnvVar(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Category reported to clients with `EventMsg::Error`, for errors that
+    /// came from the model provider.
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self {
+            CodexErr::ContextWindowExceeded => Some(ErrorKind::ContextWindowExceeded),
+            CodexErr::QuotaExceeded { .. }
+            | CodexErr::UsageLimitReached(_)
+            | CodexErr::UsageNotIncluded => Some(ErrorKind::QuotaExceeded),
+            CodexErr::AuthExpired => Some(ErrorKind::AuthExpired),
+            CodexErr::StreamDisconnected { .. } => Some(ErrorKind::StreamDisconnected),
+            CodexErr::ProviderError { .. }
+            | CodexErr::RateLimitReached(_)
+            | CodexErr::RetryLimit(_)
+            | CodexErr::InternalServerError => Some(ErrorKind::ProviderError),
+            _ => None,
+        }
+    }
+
     /// Minimal shim so that existing `e.downcast_ref::<CodexErr>()` checks continue to compile
     /// after replacing `anyhow::Error` in the return signature. This mirrors the behavior of
     /// `anyhow::Error::downcast_ref` but works directly on our concrete enum.
@@ -224,8 +431,6 @@ impl CodexErr {
 pub fn get_error_message_ui(e: &CodexErr) -> String {
     match e {
         CodexErr::Sandbox(SandboxErr::Denied(_, _, stderr)) => stderr.to_string(),
-        // Timeouts are not sandbox errors from a UX perspective; present them plainly
-        CodexErr::Sandbox(SandboxErr::Timeout) => "error: command timed out".to_string(),
         _ => e.to_string(),
     }
 }
@@ -317,4 +522,87 @@ mod tests {
             "You've hit your usage limit. Try again in less than a minute."
         );
     }
+
+    #[test]
+    fn http_errors_are_classified_from_the_body() {
+        let context = r#"{"error":{"message":"This model's maximum context length is 128000 tokens.","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        assert!(matches!(
+            http_error(StatusCode::BAD_REQUEST, context.to_string()),
+            CodexErr::ContextWindowExceeded
+        ));
+
+        let anthropic_context = r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#;
+        assert!(matches!(
+            http_error(StatusCode::BAD_REQUEST, anthropic_context.to_string()),
+            CodexErr::ContextWindowExceeded
+        ));
+
+        let quota = r#"{"error":{"message":"You exceeded your current quota.","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert!(matches!(
+            http_error(StatusCode::TOO_MANY_REQUESTS, quota.to_string()),
+            CodexErr::QuotaExceeded { plan_type: None }
+        ));
+
+        let auth = r#"{"error":{"message":"Incorrect API key provided.","type":"invalid_request_error","code":"invalid_api_key"}}"#;
+        assert!(matches!(
+            http_error(StatusCode::UNAUTHORIZED, auth.to_string()),
+            CodexErr::AuthExpired
+        ));
+
+        let other = r#"{"error":{"message":"Unknown parameter: 'foo'.","type":"invalid_request_error","code":"unknown_parameter"}}"#;
+        match http_error(StatusCode::BAD_REQUEST, other.to_string()) {
+            CodexErr::ProviderError {
+                status,
+                code,
+                message,
+            } => {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                assert_eq!(code.as_deref(), Some("unknown_parameter"));
+                assert_eq!(message, "Unknown parameter: 'foo'.");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        match http_error(
+            StatusCode::BAD_GATEWAY,
+            "<html>bad gateway</html>".to_string(),
+        ) {
+            CodexErr::ProviderError { code, message, .. } => {
+                assert_eq!(code, None);
+                assert_eq!(message, "<html>bad gateway</html>");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn stream_errors_are_retryable_unless_the_request_is_invalid() {
+        let overloaded = ProviderErrorDetails {
+            kind: Some("overloaded_error".to_string()),
+            message: Some("Overloaded".to_string()),
+            ..Default::default()
+        };
+        assert!(overloaded.into_stream_error().is_retryable());
+
+        let invalid = ProviderErrorDetails {
+            kind: Some("invalid_request_error".to_string()),
+            message: Some("Invalid prompt".to_string()),
+            ..Default::default()
+        };
+        let err = invalid.into_stream_error();
+        assert!(!err.is_retryable());
+        assert_eq!(err.kind(), Some(ErrorKind::StreamDisconnected));
+    }
+
+    #[test]
+    fn rate_limit_reached_mentions_reset() {
+        assert_eq!(
+            CodexErr::RateLimitReached(Some(Duration::from_secs(75))).to_string(),
+            "rate limit reached; resets in 1m 15s"
+        );
+        assert_eq!(
+            CodexErr::RateLimitReached(None).to_string(),
+            "rate limit reached"
+        );
+    }
 }
@@ -5,15 +5,21 @@ use std::collections::HashMap;
 use std::io;
 use std::path::PathBuf;
 use std::process::ExitStatus;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 use std::time::Instant;
 
 use async_channel::Sender;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::process::Child;
+use tokio::sync::Notify;
 
+use crate::config_types::ExecToml;
 use crate::error::CodexErr;
 use crate::error::Result;
 use crate::error::SandboxErr;
@@ -22,18 +28,20 @@ use crate::protocol::Event;
 use crate::protocol::EventMsg;
 use crate::protocol::ExecCommandOutputDeltaEvent;
 use crate::protocol::ExecOutputStream;
+use crate::protocol::ExecTermination;
 use crate::protocol::SandboxPolicy;
 use crate::seatbelt::spawn_command_under_seatbelt;
 use crate::spawn::StdioPolicy;
 use crate::spawn::spawn_child_async;
+#[cfg(windows)]
+use crate::windows_sandbox::spawn_command_under_windows_sandbox;
 use serde_bytes::ByteBuf;
 
-const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+pub(crate) const DEFAULT_TIMEOUT_MS: u64 = 10_000;
 
 // Hardcode these since it does not seem worth including the libc crate just
 // for these.
 const SIGKILL_CODE: i32 = 9;
-const TIMEOUT_CODE: i32 = 64;
 const EXIT_CODE_SIGNAL_BASE: i32 = 128; // conventional shell: 128 + signal
 
 // I/O buffer sizing
@@ -52,6 +60,67 @@ pub struct ExecParams {
     pub env: HashMap<String, String>,
     pub with_escalated_permissions: Option<bool>,
     pub justification: Option<String>,
+    pub limits: ExecLimits,
+    /// Whether to run the command under a pseudo-terminal. `None` leaves the
+    /// decision to the session configuration.
+    pub use_pty: Option<bool>,
+    pub pty: ExecPty,
+    /// Written to the command's stdin, which is then closed. Without it the
+    /// command's stdin is `/dev/null`, or the pseudo-terminal's
+    /// [`ExecPty::stdin_response`].
+    pub stdin: Option<String>,
+}
+
+/// Resource limits applied to a command in addition to its timeout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecLimits {
+    /// Caps the address space of the command and everything it starts. Only
+    /// enforced on Linux.
+    pub max_memory_bytes: Option<u64>,
+    /// Kill the command once it has written this many bytes of stdout and
+    /// stderr combined.
+    pub max_captured_output_bytes: Option<usize>,
+}
+
+impl From<&ExecToml> for ExecLimits {
+    fn from(toml: &ExecToml) -> Self {
+        Self {
+            max_memory_bytes: toml.max_memory_mb.map(|mb| mb.saturating_mul(1024 * 1024)),
+            max_captured_output_bytes: toml.max_captured_output_bytes,
+        }
+    }
+}
+
+/// How a command run under a pseudo-terminal sees it. Supported on macOS and
+/// Linux; elsewhere the command falls back to pipes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecPty {
+    pub rows: u16,
+    pub cols: u16,
+    /// Fed to the command's stdin, which is then closed. Any read from stdin
+    /// therefore sees this response, or EOF right away when there is none.
+    pub stdin_response: Option<String>,
+}
+
+impl Default for ExecPty {
+    fn default() -> Self {
+        Self {
+            rows: 24,
+            cols: 80,
+            stdin_response: None,
+        }
+    }
+}
+
+impl From<&ExecToml> for ExecPty {
+    fn from(toml: &ExecToml) -> Self {
+        let default = Self::default();
+        Self {
+            rows: toml.pty_rows.unwrap_or(default.rows),
+            cols: toml.pty_cols.unwrap_or(default.cols),
+            stdin_response: toml.pty_stdin_response.clone(),
+        }
+    }
 }
 
 impl ExecParams {
@@ -69,6 +138,9 @@ pub enum SandboxType {
 
     /// Only available on Linux.
     LinuxSeccomp,
+
+    /// Only available on Windows.
+    WindowsAppContainer,
 }
 
 #[derive(Clone)]
@@ -79,7 +151,7 @@ pub struct StdoutStream {
 }
 
 pub async fn process_exec_tool_call(
-    params: ExecParams,
+    mut params: ExecParams,
     sandbox_type: SandboxType,
     sandbox_policy: &SandboxPolicy,
     codex_linux_sandbox_exe: &Option<PathBuf>,
@@ -92,23 +164,29 @@ pub async fn process_exec_tool_call(
         SandboxType::None => exec(params, sandbox_policy, stdout_stream.clone()).await,
         SandboxType::MacosSeatbelt => {
             let timeout = params.timeout_duration();
+            let (stdio_policy, command_io) = shell_tool_stdio(&mut params)?;
             let ExecParams {
-                command, cwd, env, ..
-            } = params;
-            let child = spawn_command_under_seatbelt(
                 command,
-                sandbox_policy,
                 cwd,
-                StdioPolicy::RedirectForShellTool,
                 env,
-            )
-            .await?;
-            consume_truncated_output(child, timeout, stdout_stream.clone()).await
+                limits,
+                ..
+            } = params;
+            let child =
+                spawn_command_under_seatbelt(command, sandbox_policy, cwd, stdio_policy, env)
+                    .await?;
+            consume_truncated_output(child, command_io, timeout, limits, stdout_stream.clone())
+                .await
         }
         SandboxType::LinuxSeccomp => {
             let timeout = params.timeout_duration();
+            let (stdio_policy, command_io) = shell_tool_stdio(&mut params)?;
             let ExecParams {
-                command, cwd, env, ..
+                command,
+                cwd,
+                env,
+                limits,
+                ..
             } = params;
 
             let codex_linux_sandbox_exe = codex_linux_sandbox_exe
@@ -119,13 +197,39 @@ pub async fn process_exec_tool_call(
                 command,
                 sandbox_policy,
                 cwd,
-                StdioPolicy::RedirectForShellTool,
+                stdio_policy,
                 env,
             )
             .await?;
 
-            consume_truncated_output(child, timeout, stdout_stream).await
+            consume_truncated_output(child, command_io, timeout, limits, stdout_stream).await
         }
+        #[cfg(windows)]
+        SandboxType::WindowsAppContainer => {
+            let timeout = params.timeout_duration();
+            let (stdio_policy, command_io) = shell_tool_stdio(&mut params)?;
+            let ExecParams {
+                command,
+                cwd,
+                env,
+                limits,
+                ..
+            } = params;
+            let child = spawn_command_under_windows_sandbox(
+                command,
+                sandbox_policy,
+                cwd,
+                stdio_policy,
+                env,
+            )
+            .await?;
+            consume_truncated_output(child, command_io, timeout, limits, stdout_stream).await
+        }
+        #[cfg(not(windows))]
+        SandboxType::WindowsAppContainer => Err(CodexErr::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the Windows sandbox is only available on Windows",
+        ))),
     };
     let duration = start.elapsed();
     match raw_output_result {
@@ -133,13 +237,23 @@ pub async fn process_exec_tool_call(
             let stdout = raw_output.stdout.from_utf8_lossy();
             let stderr = raw_output.stderr.from_utf8_lossy();
 
+            // A command Codex killed for exceeding a limit is reported with
+            // whatever output it produced, rather than as an error.
+            if let Some(terminated) = raw_output.terminated {
+                return Ok(ExecToolCallOutput {
+                    exit_code: EXIT_CODE_SIGNAL_BASE + SIGKILL_CODE,
+                    stdout,
+                    stderr,
+                    aggregated_output: raw_output.aggregated_output.from_utf8_lossy(),
+                    duration,
+                    full_output_path: None,
+                    terminated: Some(terminated),
+                });
+            }
+
             #[cfg(target_family = "unix")]
-            match raw_output.exit_status.signal() {
-                Some(TIMEOUT_CODE) => return Err(CodexErr::Sandbox(SandboxErr::Timeout)),
-                Some(signal) => {
-                    return Err(CodexErr::Sandbox(SandboxErr::Signal(signal)));
-                }
-                None => {}
+            if let Some(signal) = raw_output.exit_status.signal() {
+                return Err(CodexErr::Sandbox(SandboxErr::Signal(signal)));
             }
 
             let exit_code = raw_output.exit_status.code().unwrap_or(-1);
@@ -158,6 +272,8 @@ pub async fn process_exec_tool_call(
                 stderr,
                 aggregated_output: raw_output.aggregated_output.from_utf8_lossy(),
                 duration,
+                full_output_path: None,
+                terminated: None,
             })
         }
         Err(err) => {
@@ -198,6 +314,7 @@ struct RawExecToolCallOutput {
     pub stdout: StreamOutput<Vec<u8>>,
     pub stderr: StreamOutput<Vec<u8>>,
     pub aggregated_output: StreamOutput<Vec<u8>>,
+    pub terminated: Option<ExecTermination>,
 }
 
 impl StreamOutput<String> {
@@ -230,16 +347,27 @@ pub struct ExecToolCallOutput {
     pub stderr: StreamOutput<String>,
     pub aggregated_output: StreamOutput<String>,
     pub duration: Duration,
+    /// Where `aggregated_output` was written in full because it was too long
+    /// to show the model.
+    pub full_output_path: Option<PathBuf>,
+    /// Set when the command was killed for exceeding its timeout or output
+    /// limit; the output is then partial.
+    pub terminated: Option<ExecTermination>,
 }
 
 async fn exec(
-    params: ExecParams,
+    mut params: ExecParams,
     sandbox_policy: &SandboxPolicy,
     stdout_stream: Option<StdoutStream>,
 ) -> Result<RawExecToolCallOutput> {
     let timeout = params.timeout_duration();
+    let (stdio_policy, command_io) = shell_tool_stdio(&mut params)?;
     let ExecParams {
-        command, cwd, env, ..
+        command,
+        cwd,
+        env,
+        limits,
+        ..
     } = params;
 
     let (program, args) = command.split_first().ok_or_else(|| {
@@ -255,66 +383,231 @@ async fn exec(
         arg0,
         cwd,
         sandbox_policy,
-        StdioPolicy::RedirectForShellTool,
+        stdio_policy,
         env,
     )
     .await?;
-    consume_truncated_output(child, timeout, stdout_stream).await
+    consume_truncated_output(child, command_io, timeout, limits, stdout_stream).await
+}
+
+/// The parts of a running command's stdio that [`consume_truncated_output`]
+/// drives beyond the stdout and stderr pipes.
+struct CommandIo {
+    /// The pseudo-terminal the command writes its output to, if it runs
+    /// under one.
+    pty: Option<Box<dyn AsyncRead + Unpin + Send>>,
+    /// Written to the command's stdin before it is closed.
+    stdin: Option<String>,
+}
+
+/// Picks the stdio for a shell tool command, opening a pseudo-terminal when
+/// `params` asks for one.
+fn shell_tool_stdio(params: &mut ExecParams) -> io::Result<(StdioPolicy, CommandIo)> {
+    if params.use_pty != Some(true) {
+        return Ok(piped_stdio(params));
+    }
+
+    #[cfg(unix)]
+    {
+        let (master, terminal) = crate::pty::open_pty(params.pty.rows, params.pty.cols)?;
+        // Nobody is there to scroll through a pager, which a terminal would
+        // otherwise start for commands like `git diff`.
+        params.env.insert("PAGER".to_string(), "cat".to_string());
+        params
+            .env
+            .insert("GIT_PAGER".to_string(), "cat".to_string());
+        params
+            .env
+            .entry("TERM".to_string())
+            .or_insert_with(|| "xterm-256color".to_string());
+        let command_io = CommandIo {
+            pty: Some(Box::new(master)),
+            stdin: params
+                .stdin
+                .clone()
+                .or_else(|| params.pty.stdin_response.clone()),
+        };
+        Ok((StdioPolicy::RedirectToPty(terminal), command_io))
+    }
+
+    #[cfg(not(unix))]
+    {
+        tracing::warn!("pseudo-terminals are not supported on this platform; using pipes");
+        Ok(piped_stdio(params))
+    }
+}
+
+fn piped_stdio(params: &ExecParams) -> (StdioPolicy, CommandIo) {
+    let stdio_policy = if params.stdin.is_some() {
+        StdioPolicy::RedirectForShellToolWithInput
+    } else {
+        StdioPolicy::RedirectForShellTool
+    };
+    let command_io = CommandIo {
+        pty: None,
+        stdin: params.stdin.clone(),
+    };
+    (stdio_policy, command_io)
+}
+
+/// Stops a command started by [`spawn_child_async`] along with everything it
+/// started: shell tool commands lead their own process group, so the group is
+/// killed rather than just the child.
+fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: killpg has no memory-safety preconditions.
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    child.start_kill()
+}
+
+/// Applies `limits.max_memory_bytes` to a freshly spawned command. Limits are
+/// inherited, so this also covers the command a sandbox wrapper executes.
+#[cfg(target_os = "linux")]
+fn apply_memory_limit(child: &Child, limits: ExecLimits) {
+    let (Some(pid), Some(max_bytes)) = (child.id(), limits.max_memory_bytes) else {
+        return;
+    };
+    let limit = libc::rlimit {
+        rlim_cur: max_bytes,
+        rlim_max: max_bytes,
+    };
+    // SAFETY: `limit` is a valid rlimit and the old value is not requested.
+    let result = unsafe {
+        libc::prlimit(
+            pid as libc::pid_t,
+            libc::RLIMIT_AS,
+            &limit,
+            std::ptr::null_mut(),
+        )
+    };
+    if result != 0 {
+        tracing::warn!(
+            "failed to limit memory of command: {}",
+            io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_memory_limit(_child: &Child, _limits: ExecLimits) {}
+
+/// Shared between the readers of a command's stdout and stderr to enforce
+/// [`ExecLimits::max_captured_output_bytes`].
+struct CapturedOutputLimit {
+    max_bytes: usize,
+    captured: AtomicUsize,
+    exceeded: Notify,
+}
+
+impl CapturedOutputLimit {
+    fn record(&self, n: usize) {
+        if self.captured.fetch_add(n, Ordering::Relaxed) + n > self.max_bytes {
+            self.exceeded.notify_one();
+        }
+    }
+
+    async fn wait_until_exceeded(limit: Option<&Self>) {
+        match limit {
+            Some(limit) => limit.exceeded.notified().await,
+            None => std::future::pending().await,
+        }
+    }
 }
 
 /// Consumes the output of a child process, truncating it so it is suitable for
 /// use as the output of a `shell` tool call. Also enforces specified timeout.
 async fn consume_truncated_output(
     mut child: Child,
+    command_io: CommandIo,
     timeout: Duration,
+    limits: ExecLimits,
     stdout_stream: Option<StdoutStream>,
 ) -> Result<RawExecToolCallOutput> {
-    // Both stdout and stderr were configured with `Stdio::piped()`
-    // above, therefore `take()` should normally return `Some`.  If it doesn't
-    // we treat it as an exceptional I/O error
+    apply_memory_limit(&child, limits);
 
-    let stdout_reader = child.stdout.take().ok_or_else(|| {
-        CodexErr::Io(io::Error::other(
-            "stdout pipe was unexpectedly not available",
-        ))
-    })?;
-    let stderr_reader = child.stderr.take().ok_or_else(|| {
-        CodexErr::Io(io::Error::other(
-            "stderr pipe was unexpectedly not available",
-        ))
-    })?;
+    type OutputReader = Box<dyn AsyncRead + Unpin + Send>;
+    // Stdin is closed once the input (if any) is written, so reads past it
+    // see EOF instead of waiting for input.
+    if let Some(mut stdin) = child.stdin.take()
+        && let Some(input) = command_io.stdin
+    {
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
+    let (stdout_reader, stderr_reader): (OutputReader, OutputReader) = match command_io.pty {
+        Some(reader) => {
+            // The terminal merges stderr into stdout.
+            (reader, Box::new(tokio::io::empty()))
+        }
+        None => {
+            // Both stdout and stderr were configured with `Stdio::piped()`
+            // above, therefore `take()` should normally return `Some`.  If it doesn't
+            // we treat it as an exceptional I/O error
+
+            let stdout_reader = child.stdout.take().ok_or_else(|| {
+                CodexErr::Io(io::Error::other(
+                    "stdout pipe was unexpectedly not available",
+                ))
+            })?;
+            let stderr_reader = child.stderr.take().ok_or_else(|| {
+                CodexErr::Io(io::Error::other(
+                    "stderr pipe was unexpectedly not available",
+                ))
+            })?;
+            (Box::new(stdout_reader), Box::new(stderr_reader))
+        }
+    };
 
     let (agg_tx, agg_rx) = async_channel::unbounded::<Vec<u8>>();
+    let output_limit = limits.max_captured_output_bytes.map(|max_bytes| {
+        Arc::new(CapturedOutputLimit {
+            max_bytes,
+            captured: AtomicUsize::new(0),
+            exceeded: Notify::new(),
+        })
+    });
 
     let stdout_handle = tokio::spawn(read_capped(
         BufReader::new(stdout_reader),
         stdout_stream.clone(),
         false,
         Some(agg_tx.clone()),
+        output_limit.clone(),
     ));
     let stderr_handle = tokio::spawn(read_capped(
         BufReader::new(stderr_reader),
         stdout_stream.clone(),
         true,
         Some(agg_tx.clone()),
+        output_limit.clone(),
     ));
 
-    let exit_status = tokio::select! {
+    let killed_status = synthetic_exit_status(EXIT_CODE_SIGNAL_BASE + SIGKILL_CODE);
+    let (exit_status, terminated) = tokio::select! {
         result = tokio::time::timeout(timeout, child.wait()) => {
             match result {
-                Ok(Ok(exit_status)) => exit_status,
-                Ok(e) => e?,
+                Ok(Ok(exit_status)) => (exit_status, None),
+                Ok(e) => (e?, None),
                 Err(_) => {
-                    // timeout
-                    child.start_kill()?;
-                    // Debatable whether `child.wait().await` should be called here.
-                    synthetic_exit_status(EXIT_CODE_SIGNAL_BASE + TIMEOUT_CODE)
+                    kill_process_group(&mut child)?;
+                    let timeout_ms = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+                    (killed_status, Some(ExecTermination::TimedOut { timeout_ms }))
                 }
             }
         }
+        () = CapturedOutputLimit::wait_until_exceeded(output_limit.as_deref()) => {
+            kill_process_group(&mut child)?;
+            let max_bytes = limits.max_captured_output_bytes.unwrap_or_default() as u64;
+            (killed_status, Some(ExecTermination::OutputLimitExceeded { max_bytes }))
+        }
         _ = tokio::signal::ctrl_c() => {
-            child.start_kill()?;
-            synthetic_exit_status(EXIT_CODE_SIGNAL_BASE + SIGKILL_CODE)
+            kill_process_group(&mut child)?;
+            (killed_status, None)
         }
     };
 
@@ -337,6 +630,7 @@ async fn consume_truncated_output(
         stdout,
         stderr,
         aggregated_output,
+        terminated,
     })
 }
 
@@ -345,6 +639,7 @@ async fn read_capped<R: AsyncRead + Unpin + Send + 'static>(
     stream: Option<StdoutStream>,
     is_stderr: bool,
     aggregate_tx: Option<Sender<Vec<u8>>>,
+    output_limit: Option<Arc<CapturedOutputLimit>>,
 ) -> io::Result<StreamOutput<Vec<u8>>> {
     let mut buf = Vec::with_capacity(AGGREGATE_BUFFER_INITIAL_CAPACITY);
     let mut tmp = [0u8; READ_CHUNK_SIZE];
@@ -385,6 +680,9 @@ async fn read_capped<R: AsyncRead + Unpin + Send + 'static>(
         }
 
         append_all(&mut buf, &tmp[..n]);
+        if let Some(limit) = &output_limit {
+            limit.record(n);
+        }
         // Continue reading to EOF to avoid back-pressure
     }
 
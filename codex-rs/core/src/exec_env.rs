@@ -15,25 +15,85 @@ pub fn create_env(policy: &ShellEnvironmentPolicy) -> HashMap<String, String> {
     populate_env(std::env::vars(), policy)
 }
 
-fn populate_env<I>(vars: I, policy: &ShellEnvironmentPolicy) -> HashMap<String, String>
+/// Like [`create_env`], but starting from `vars`, such as the environment
+/// captured from the user's shell profile, instead of Codex's own.
+pub(crate) fn create_env_from(
+    vars: &HashMap<String, String>,
+    policy: &ShellEnvironmentPolicy,
+) -> HashMap<String, String> {
+    populate_env(vars.clone(), policy)
+}
+
+/// What the model is told about the environment its commands run with, so it
+/// does not assume a variable such as `GITHUB_TOKEN` exists after the policy
+/// stripped it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EnvironmentSummary {
+    pub inherit: ShellEnvironmentPolicyInherit,
+    /// Inherited variables that the policy removed, sorted by name.
+    pub removed: Vec<String>,
+}
+
+pub(crate) fn summarize_env(policy: &ShellEnvironmentPolicy) -> EnvironmentSummary {
+    summarize(std::env::vars(), policy)
+}
+
+fn summarize<I>(vars: I, policy: &ShellEnvironmentPolicy) -> EnvironmentSummary
 where
     I: IntoIterator<Item = (String, String)>,
 {
-    // Step 1 – determine the starting set of variables based on the
-    // `inherit` strategy.
-    let mut env_map: HashMap<String, String> = match policy.inherit {
+    let inherited = inherit_vars(vars, &policy.inherit);
+    let env_map = populate_env(inherited.clone(), policy);
+    let mut removed: Vec<String> = inherited
+        .into_keys()
+        .filter(|name| !env_map.contains_key(name))
+        .collect();
+    removed.sort();
+    EnvironmentSummary {
+        inherit: policy.inherit.clone(),
+        removed,
+    }
+}
+
+/// Windows treats environment variable names case-insensitively, so `Path`
+/// and `PATH` name the same variable there.
+fn same_var(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+fn inherit_vars<I>(vars: I, inherit: &ShellEnvironmentPolicyInherit) -> HashMap<String, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    match inherit {
         ShellEnvironmentPolicyInherit::All => vars.into_iter().collect(),
         ShellEnvironmentPolicyInherit::None => HashMap::new(),
         ShellEnvironmentPolicyInherit::Core => {
             const CORE_VARS: &[&str] = &[
                 "HOME", "LOGNAME", "PATH", "SHELL", "USER", "USERNAME", "TMPDIR", "TEMP", "TMP",
             ];
-            let allow: HashSet<&str> = CORE_VARS.iter().copied().collect();
-            vars.into_iter()
-                .filter(|(k, _)| allow.contains(k.as_str()))
-                .collect()
+            // Programs on Windows commonly fail to start without these.
+            const WINDOWS_CORE_VARS: &[&str] = &["COMSPEC", "PATHEXT", "SYSTEMROOT", "USERPROFILE"];
+            let is_core = |name: &str| {
+                CORE_VARS.iter().any(|core| same_var(core, name))
+                    || (cfg!(windows) && WINDOWS_CORE_VARS.iter().any(|core| same_var(core, name)))
+            };
+            vars.into_iter().filter(|(k, _)| is_core(k)).collect()
         }
-    };
+    }
+}
+
+fn populate_env<I>(vars: I, policy: &ShellEnvironmentPolicy) -> HashMap<String, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    // Step 1 – determine the starting set of variables based on the
+    // `inherit` strategy.
+    let mut env_map = inherit_vars(vars, &policy.inherit);
 
     // Internal helper – does `name` match **any** pattern in `patterns`?
     let matches_any = |name: &str, patterns: &[EnvironmentVariablePattern]| -> bool {
@@ -55,8 +115,10 @@ where
         env_map.retain(|k, _| !matches_any(k, &policy.exclude));
     }
 
-    // Step 4 – Apply user-provided overrides.
+    // Step 4 – Apply user-provided overrides, replacing an inherited variable
+    // whose name differs only in case on Windows.
     for (key, val) in &policy.r#set {
+        env_map.retain(|k, _| !same_var(k, key));
         env_map.insert(key.clone(), val.clone());
     }
 
@@ -191,4 +253,73 @@ mod tests {
         };
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn patterns_match_case_insensitively() {
+        let vars = make_vars(&[
+            ("Path", "/usr/bin"),
+            ("github_Token", "t"),
+            ("Aws_Secret_Access_Key", "s"),
+            ("my_build_dir", "/tmp/build"),
+        ]);
+
+        let policy = ShellEnvironmentPolicy {
+            inherit: ShellEnvironmentPolicyInherit::All,
+            exclude: vec![EnvironmentVariablePattern::new_case_insensitive("MY_*")],
+            ..Default::default()
+        };
+
+        let result = populate_env(vars, &policy);
+        let expected: HashMap<String, String> = hashmap! {
+            "Path".to_string() => "/usr/bin".to_string(),
+        };
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn summary_lists_removed_variables() {
+        let vars = make_vars(&[
+            ("PATH", "/usr/bin"),
+            ("NPM_TOKEN", "t"),
+            ("AWS_SECRET_ACCESS_KEY", "s"),
+        ]);
+
+        let policy = ShellEnvironmentPolicy {
+            inherit: ShellEnvironmentPolicyInherit::All,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            summarize(vars, &policy),
+            EnvironmentSummary {
+                inherit: ShellEnvironmentPolicyInherit::All,
+                removed: vec!["AWS_SECRET_ACCESS_KEY".to_string(), "NPM_TOKEN".to_string()],
+            }
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn core_inherit_and_set_ignore_case_on_windows() {
+        let vars = make_vars(&[
+            ("Path", "C:\\Windows"),
+            ("SystemRoot", "C:\\Windows"),
+            ("Editor", "notepad"),
+        ]);
+
+        let mut policy = ShellEnvironmentPolicy {
+            inherit: ShellEnvironmentPolicyInherit::Core,
+            ..Default::default()
+        };
+        policy
+            .r#set
+            .insert("PATH".to_string(), "C:\\tools".to_string());
+
+        let result = populate_env(vars, &policy);
+        let expected: HashMap<String, String> = hashmap! {
+            "PATH".to_string() => "C:\\tools".to_string(),
+            "SystemRoot".to_string() => "C:\\Windows".to_string(),
+        };
+        assert_eq!(result, expected);
+    }
 }
@@ -109,6 +109,23 @@ pub async fn git_diff_to_remote(cwd: &Path) -> Option<GitDiffToRemote> {
     })
 }
 
+/// Returns the merge base of `HEAD` and `base`, a ref or sha, and the diff of
+/// the working tree (including untracked files) against it. Returns `None`
+/// when `base` cannot be resolved.
+pub(crate) async fn git_diff_against_base(cwd: &Path, base: &str) -> Option<GitDiffToRemote> {
+    // Refuse anything git could mistake for an option.
+    if base.starts_with('-') {
+        return None;
+    }
+    let output = run_git_command_with_timeout(&["merge-base", "HEAD", base], cwd).await?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = GitSha::new(String::from_utf8(output.stdout).ok()?.trim());
+    let diff = diff_against_sha(cwd, &sha).await?;
+    Some(GitDiffToRemote { sha, diff })
+}
+
 /// Run a git command with a timeout to prevent blocking on large repositories
 async fn run_git_command_with_timeout(args: &[&str], cwd: &Path) -> Option<std::process::Output> {
     let result = timeout(
@@ -439,10 +456,178 @@ async fn diff_against_sha(cwd: &Path, sha: &GitSha) -> Option<String> {
     Some(diff)
 }
 
+/// Cheap snapshot of where `HEAD` points for the repository containing a
+/// directory. Used to seed the environment context so the model does not need
+/// to spend a tool call on `git status`/`git branch`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct GitHeadState {
+    /// Branch name when `HEAD` is a symbolic ref; `None` when detached.
+    pub branch: Option<String>,
+    /// Commit `HEAD` resolves to. `None` on an unborn branch.
+    pub commit: Option<String>,
+    /// Whether tracked files have uncommitted changes. `None` when the check
+    /// could not be completed (e.g. `git` is missing or timed out).
+    pub is_dirty: Option<bool>,
+}
+
+/// Whether the working directory is under version control, as reported in
+/// the environment context, e.g. `git (dirty)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VersionControl {
+    None,
+    Git { is_dirty: Option<bool> },
+}
+
+impl VersionControl {
+    pub(crate) fn detect(cwd: &Path, git_state: Option<&GitHeadState>) -> Self {
+        if !is_inside_git_repo(cwd) {
+            return VersionControl::None;
+        }
+        VersionControl::Git {
+            is_dirty: git_state.and_then(|state| state.is_dirty),
+        }
+    }
+}
+
+impl std::fmt::Display for VersionControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionControl::None => f.write_str("none"),
+            VersionControl::Git {
+                is_dirty: Some(true),
+            } => f.write_str("git (dirty)"),
+            VersionControl::Git {
+                is_dirty: Some(false),
+            } => f.write_str("git (clean)"),
+            VersionControl::Git { is_dirty: None } => f.write_str("git"),
+        }
+    }
+}
+
+/// Collect a [`GitHeadState`] for `cwd`. Branch and commit are read directly
+/// from the git directory; only the dirty check shells out to `git`.
+/// Returns `None` when `cwd` is not inside a repository or the repository
+/// metadata cannot be parsed.
+pub(crate) async fn collect_git_head_state(cwd: &Path) -> Option<GitHeadState> {
+    let (branch, commit) = read_git_head(cwd)?;
+
+    // Skip untracked files: scanning them is the expensive part of
+    // `git status` on large checkouts.
+    let is_dirty =
+        run_git_command_with_timeout(&["status", "--porcelain", "--untracked-files=no"], cwd)
+            .await
+            .filter(|output| output.status.success())
+            .map(|output| !output.stdout.is_empty());
+
+    Some(GitHeadState {
+        branch,
+        commit,
+        is_dirty,
+    })
+}
+
+/// Read `HEAD` for the repository containing `cwd` without invoking `git`.
+/// Returns `(branch, commit)`, or `None` if no repository was found or `HEAD`
+/// is malformed.
+fn read_git_head(cwd: &Path) -> Option<(Option<String>, Option<String>)> {
+    let git_dir = find_git_dir(cwd)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        let common_dir = resolve_git_common_dir(&git_dir);
+        let commit = resolve_git_ref(&git_dir, &common_dir, reference);
+        let branch = reference.strip_prefix("refs/heads/").map(str::to_string);
+        Some((branch, commit))
+    } else if is_hex_sha(head) {
+        // Detached HEAD.
+        Some((None, Some(head.to_string())))
+    } else {
+        None
+    }
+}
+
+/// Walk up from `cwd` looking for a `.git` directory, or a `.git` file that
+/// points at a separate git directory (linked worktrees, submodules,
+/// `--separate-git-dir`).
+fn find_git_dir(cwd: &Path) -> Option<PathBuf> {
+    let mut dir = cwd.to_path_buf();
+    loop {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let contents = std::fs::read_to_string(&dot_git).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            let target = if Path::new(target).is_absolute() {
+                PathBuf::from(target)
+            } else {
+                dir.join(target)
+            };
+            return target.is_dir().then_some(target);
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+/// Linked worktrees keep shared refs in the directory named by their
+/// `commondir` file; every other git directory is its own common dir.
+fn resolve_git_common_dir(git_dir: &Path) -> PathBuf {
+    match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(contents) => {
+            let common = contents.trim();
+            if Path::new(common).is_absolute() {
+                PathBuf::from(common)
+            } else {
+                git_dir.join(common)
+            }
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Resolve a fully-qualified ref (e.g. `refs/heads/main`) to a commit sha by
+/// checking loose refs first and then `packed-refs`.
+fn resolve_git_ref(git_dir: &Path, common_dir: &Path, reference: &str) -> Option<String> {
+    for dir in [git_dir, common_dir] {
+        if let Ok(contents) = std::fs::read_to_string(dir.join(reference)) {
+            let sha = contents.trim();
+            if is_hex_sha(sha) {
+                return Some(sha.to_string());
+            }
+        }
+    }
+
+    let packed = std::fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, name)| name.trim() == reference)
+        .map(|(sha, _)| sha.trim())
+        .filter(|sha| is_hex_sha(sha))
+        .map(str::to_string)
+}
+
+fn is_hex_sha(s: &str) -> bool {
+    // SHA-1 object names are 40 hex chars; SHA-256 repositories use 64.
+    matches!(s.len(), 40 | 64) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 /// Resolve the path that should be used for trust checks. Similar to
 /// `[utils::is_inside_git_repo]`, but resolves to the root of the main
 /// repository. Handles worktrees.
 pub fn resolve_root_git_project_for_trust(cwd: &Path) -> Option<PathBuf> {
+    resolve_git_common_dir(cwd)?.parent().map(Path::to_path_buf)
+}
+
+/// The `.git` directory of the main repository containing `cwd`, shared by
+/// all of its worktrees.
+pub(crate) fn resolve_git_common_dir(cwd: &Path) -> Option<PathBuf> {
     let base = if cwd.is_dir() { cwd } else { cwd.parent()? };
 
     // TODO: we should make this async, but it's primarily used deep in
@@ -467,8 +652,7 @@ pub fn resolve_root_git_project_for_trust(cwd: &Path) -> Option<PathBuf> {
     };
 
     // Normalize to handle macOS /var vs /private/var and resolve ".." segments.
-    let git_dir_path = std::fs::canonicalize(&git_dir_path_raw).unwrap_or(git_dir_path_raw);
-    git_dir_path.parent().map(Path::to_path_buf)
+    Some(std::fs::canonicalize(&git_dir_path_raw).unwrap_or(git_dir_path_raw))
 }
 
 #[cfg(test)]
@@ -736,6 +920,61 @@ mod tests {
         assert!(state.diff.contains("untracked.txt"));
     }
 
+    #[tokio::test]
+    async fn test_git_diff_against_base_uses_merge_base() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = create_test_git_repo(&temp_dir).await;
+        let base_sha = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to rev-parse HEAD");
+        let base_sha = String::from_utf8(base_sha.stdout)
+            .unwrap()
+            .trim()
+            .to_string();
+        Command::new("git")
+            .args(["tag", "base"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to tag base");
+
+        fs::write(repo_path.join("committed.txt"), "committed").unwrap();
+        Command::new("git")
+            .args(["add", "committed.txt"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to add file");
+        Command::new("git")
+            .args(["commit", "-m", "Second commit"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to commit");
+        fs::write(repo_path.join("test.txt"), "modified").unwrap();
+
+        let state = git_diff_against_base(&repo_path, "base")
+            .await
+            .expect("Should diff against base");
+        assert_eq!(state.sha, GitSha::new(&base_sha));
+        assert!(state.diff.contains("committed.txt"));
+        assert!(state.diff.contains("test.txt"));
+
+        assert!(
+            git_diff_against_base(&repo_path, "no-such-ref")
+                .await
+                .is_none()
+        );
+        assert!(
+            git_diff_against_base(&repo_path, "--output=x")
+                .await
+                .is_none()
+        );
+    }
+
     #[tokio::test]
     async fn test_get_git_working_tree_state_branch_fallback() {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -852,6 +1091,162 @@ mod tests {
         assert!(resolve_root_git_project_for_trust(&proj.join("nested")).is_none());
     }
 
+    #[tokio::test]
+    async fn collect_git_head_state_reports_branch_commit_and_dirty() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = create_test_git_repo(&temp_dir).await;
+
+        Command::new("git")
+            .args(["checkout", "-b", "feature-branch"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to create branch");
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to get HEAD");
+        let commit_hash = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+        let state = collect_git_head_state(&repo_path)
+            .await
+            .expect("Should collect head state from repo");
+        assert_eq!(
+            state,
+            GitHeadState {
+                branch: Some("feature-branch".to_string()),
+                commit: Some(commit_hash.clone()),
+                is_dirty: Some(false),
+            }
+        );
+
+        fs::write(repo_path.join("test.txt"), "modified").unwrap();
+        let state = collect_git_head_state(&repo_path)
+            .await
+            .expect("Should collect head state from repo");
+        assert_eq!(state.is_dirty, Some(true));
+        assert_eq!(
+            VersionControl::detect(&repo_path, Some(&state)).to_string(),
+            "git (dirty)"
+        );
+    }
+
+    #[test]
+    fn version_control_is_none_outside_a_repo() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        assert_eq!(
+            VersionControl::detect(temp_dir.path(), None),
+            VersionControl::None
+        );
+    }
+
+    #[tokio::test]
+    async fn read_git_head_detached_head() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = create_test_git_repo(&temp_dir).await;
+
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to get HEAD");
+        let commit_hash = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+        Command::new("git")
+            .args(["checkout", &commit_hash])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to checkout commit");
+
+        let nested = repo_path.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(read_git_head(&nested), Some((None, Some(commit_hash))));
+    }
+
+    #[tokio::test]
+    async fn read_git_head_linked_worktree() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = create_test_git_repo(&temp_dir).await;
+
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("Failed to get HEAD");
+        let commit_hash = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+        let wt_root = temp_dir.path().join("wt");
+        Command::new("git")
+            .args([
+                "worktree",
+                "add",
+                wt_root.to_str().unwrap(),
+                "-b",
+                "feature/x",
+            ])
+            .current_dir(&repo_path)
+            .output()
+            .await
+            .expect("git worktree add");
+
+        assert_eq!(
+            read_git_head(&wt_root),
+            Some((Some("feature/x".to_string()), Some(commit_hash)))
+        );
+    }
+
+    #[test]
+    fn read_git_head_separate_gitdir_with_packed_refs() {
+        let tmp = TempDir::new().expect("tempdir");
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+
+        // A bare-bones git directory living outside the checkout, with the
+        // branch only present in packed-refs.
+        let git_dir = tmp.path().join("store.git");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!("# pack-refs with: peeled fully-peeled sorted\n{sha} refs/heads/main\n"),
+        )
+        .unwrap();
+
+        let checkout = tmp.path().join("checkout");
+        fs::create_dir_all(&checkout).unwrap();
+        fs::write(
+            checkout.join(".git"),
+            format!("gitdir: {}\n", git_dir.display()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_git_head(&checkout),
+            Some((Some("main".to_string()), Some(sha.to_string())))
+        );
+    }
+
+    #[test]
+    fn read_git_head_returns_none_for_non_repo_and_corrupt_head() {
+        let tmp = TempDir::new().expect("tempdir");
+        assert_eq!(read_git_head(tmp.path()), None);
+
+        let proj = tmp.path().join("proj");
+        fs::create_dir_all(proj.join(".git")).unwrap();
+        fs::write(proj.join(".git/HEAD"), "garbage").unwrap();
+        assert_eq!(read_git_head(&proj), None);
+
+        // `.git` file pointing at a directory that does not exist.
+        let dangling = tmp.path().join("dangling");
+        fs::create_dir_all(&dangling).unwrap();
+        fs::write(dangling.join(".git"), "gitdir: nowhere").unwrap();
+        assert_eq!(read_git_head(&dangling), None);
+    }
+
     #[tokio::test]
     async fn test_get_git_working_tree_state_unpushed_commit() {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -0,0 +1,273 @@
+//! Checks on images attached to user input before they are sent to the model.
+
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use base64::Engine;
+
+use crate::model_family::ModelFamily;
+use crate::protocol::InputItem;
+
+/// Largest image the Responses and Chat Completions APIs accept.
+const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Image formats the model accepts, as (mime type, magic bytes) pairs. WebP is
+/// checked separately because its signature has a gap.
+const SUPPORTED_FORMATS: &[(&str, &[u8])] = &[
+    ("image/png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", b"\xff\xd8\xff"),
+    ("image/gif", b"GIF8"),
+];
+const WEBP_MIME: &str = "image/webp";
+
+/// Returns a message for the user if any image in `items` cannot be sent to
+/// `model_family`.
+pub(crate) fn validate_input_images(
+    items: &[InputItem],
+    model_family: &ModelFamily,
+) -> Result<(), String> {
+    let has_images = items
+        .iter()
+        .any(|item| matches!(item, InputItem::Image { .. } | InputItem::LocalImage { .. }));
+    if has_images && !model_family.supports_image_input {
+        return Err(format!(
+            "Model `{}` does not accept images. Remove the attached image or switch to a model that supports image input.",
+            model_family.slug
+        ));
+    }
+
+    for item in items {
+        match item {
+            InputItem::LocalImage { path } => validate_local_image(path)?,
+            InputItem::Image { image_url } => {
+                // Remote URLs are fetched and checked by the API.
+                if let Some((mime_type, data)) = parse_data_url(image_url) {
+                    if !is_supported_mime_type(mime_type) {
+                        return Err(format!(
+                            "Unsupported image format `{mime_type}`; use PNG, JPEG, GIF or WebP."
+                        ));
+                    }
+                    if base64_decoded_len(data) > MAX_IMAGE_BYTES {
+                        return Err(too_large("The pasted image"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn validate_local_image(path: &Path) -> Result<(), String> {
+    let display = path.display();
+    let mut file =
+        std::fs::File::open(path).map_err(|e| format!("Cannot read image {display}: {e}"))?;
+    let len = file
+        .metadata()
+        .map_err(|e| format!("Cannot read image {display}: {e}"))?
+        .len();
+    if len > MAX_IMAGE_BYTES {
+        return Err(too_large(&display.to_string()));
+    }
+
+    let mut header = [0u8; 12];
+    let read = file
+        .read(&mut header)
+        .map_err(|e| format!("Cannot read image {display}: {e}"))?;
+    if sniff_mime_type(&header[..read]).is_none() {
+        return Err(format!("{display} is not a PNG, JPEG, GIF or WebP image."));
+    }
+    Ok(())
+}
+
+/// An image read by the `view_image` tool.
+#[derive(Debug)]
+pub(crate) struct ViewedImage {
+    /// Canonical path of the file.
+    pub path: PathBuf,
+    pub bytes: Vec<u8>,
+    pub data_url: String,
+}
+
+/// Reads the image at `path` for the `view_image` tool. The file must be
+/// inside one of `roots`, after resolving symlinks, and pass the same format
+/// and size checks as an attached image.
+pub(crate) fn read_viewed_image(path: &Path, roots: &[PathBuf]) -> Result<ViewedImage, String> {
+    let display = path.display();
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("Cannot read image {display}: {e}"))?;
+    let inside_roots = roots.iter().any(|root| {
+        let root = root.canonicalize().unwrap_or_else(|_| root.clone());
+        path.starts_with(root)
+    });
+    if !inside_roots {
+        return Err(format!(
+            "{display} is outside the workspace and the writable roots."
+        ));
+    }
+
+    let file =
+        std::fs::File::open(&path).map_err(|e| format!("Cannot read image {display}: {e}"))?;
+    let mut bytes = Vec::new();
+    file.take(MAX_IMAGE_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Cannot read image {display}: {e}"))?;
+    if bytes.len() as u64 > MAX_IMAGE_BYTES {
+        return Err(too_large(&display.to_string()));
+    }
+    let Some(mime_type) = sniff_mime_type(&bytes) else {
+        return Err(format!("{display} is not a PNG, JPEG, GIF or WebP image."));
+    };
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    Ok(ViewedImage {
+        path,
+        data_url: format!("data:{mime_type};base64,{encoded}"),
+        bytes,
+    })
+}
+
+fn too_large(what: &str) -> String {
+    format!(
+        "{what} is larger than {} MB, the most the model accepts.",
+        MAX_IMAGE_BYTES / (1024 * 1024)
+    )
+}
+
+/// Splits a `data:<mime>;base64,<data>` URL into its mime type and payload.
+pub(crate) fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    Some((mime_type, data))
+}
+
+/// The mime type of the image whose first bytes are `header`, if it is in a
+/// supported format.
+fn sniff_mime_type(header: &[u8]) -> Option<&'static str> {
+    if header.len() >= 12 && &header[..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        return Some(WEBP_MIME);
+    }
+    SUPPORTED_FORMATS
+        .iter()
+        .find(|(_, magic)| header.starts_with(magic))
+        .map(|(mime_type, _)| *mime_type)
+}
+
+fn is_supported_mime_type(mime_type: &str) -> bool {
+    mime_type == WEBP_MIME
+        || SUPPORTED_FORMATS
+            .iter()
+            .any(|(supported, _)| *supported == mime_type)
+}
+
+fn base64_decoded_len(data: &str) -> u64 {
+    (data.len() as u64 / 4) * 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_family::find_family_for_model;
+    use std::io::Write;
+
+    fn family(slug: &str) -> ModelFamily {
+        find_family_for_model(slug).unwrap()
+    }
+
+    #[test]
+    fn text_only_families_reject_images() {
+        let items = vec![InputItem::Image {
+            image_url: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+        }];
+
+        assert_eq!(validate_input_images(&items, &family("gpt-5")), Ok(()));
+        assert_eq!(
+            validate_input_images(&items, &family("gpt-oss-20b")),
+            Err("Model `gpt-oss-20b` does not accept images. Remove the attached image or switch to a model that supports image input.".to_string())
+        );
+        // Text alone is fine for every model.
+        let text = vec![InputItem::Text {
+            text: "hi".to_string(),
+        }];
+        assert_eq!(validate_input_images(&text, &family("gpt-oss-20b")), Ok(()));
+    }
+
+    #[test]
+    fn local_images_must_be_in_a_supported_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("screenshot.png");
+        std::fs::File::create(&png)
+            .unwrap()
+            .write_all(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")
+            .unwrap();
+        let svg = dir.path().join("diagram.png");
+        std::fs::write(&svg, "<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+
+        let gpt5 = family("gpt-5");
+        assert_eq!(
+            validate_input_images(&[InputItem::LocalImage { path: png }], &gpt5),
+            Ok(())
+        );
+        assert_eq!(
+            validate_input_images(&[InputItem::LocalImage { path: svg.clone() }], &gpt5),
+            Err(format!(
+                "{} is not a PNG, JPEG, GIF or WebP image.",
+                svg.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn data_urls_must_use_a_supported_mime_type() {
+        let items = vec![InputItem::Image {
+            image_url: "data:image/svg+xml;base64,PHN2Zz4=".to_string(),
+        }];
+        assert_eq!(
+            validate_input_images(&items, &family("gpt-5")),
+            Err(
+                "Unsupported image format `image/svg+xml`; use PNG, JPEG, GIF or WebP.".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn viewed_images_must_be_inside_the_roots() {
+        let workspace = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let png_bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let inside = workspace.path().join("plot.png");
+        std::fs::write(&inside, png_bytes).unwrap();
+        let outside = elsewhere.path().join("secret.png");
+        std::fs::write(&outside, png_bytes).unwrap();
+        let roots = vec![workspace.path().to_path_buf()];
+
+        let image = read_viewed_image(&inside, &roots).unwrap();
+        assert_eq!(image.path, inside.canonicalize().unwrap());
+        assert_eq!(image.bytes, png_bytes);
+        assert!(image.data_url.starts_with("data:image/png;base64,"));
+
+        assert_eq!(
+            read_viewed_image(&outside, &roots).unwrap_err(),
+            format!(
+                "{} is outside the workspace and the writable roots.",
+                outside.display()
+            )
+        );
+        // `..` cannot be used to climb out of a root.
+        let escape = workspace
+            .path()
+            .join("..")
+            .join(elsewhere.path().file_name().unwrap())
+            .join("secret.png");
+        assert!(read_viewed_image(&escape, &roots).is_err());
+    }
+
+    #[test]
+    fn sniffs_supported_formats() {
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_mime_type(b"GIF89a"), Some("image/gif"));
+        assert_eq!(sniff_mime_type(b"BM"), None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::bash::try_parse_bash;
+use crate::bash::try_parse_word_only_commands_sequence;
+
+/// Whether `command` is likely to use the network: a network client, or a
+/// package manager or VCS subcommand that downloads or uploads. Used to ask
+/// for approval when the sandbox cannot block network access.
+pub(crate) fn is_network_command(command: &[String]) -> bool {
+    if let [bash, flag, script] = command
+        && bash == "bash"
+        && flag == "-lc"
+    {
+        return match try_parse_bash(script)
+            .and_then(|tree| try_parse_word_only_commands_sequence(&tree, script))
+        {
+            Some(commands) => commands.iter().any(|cmd| is_network_exec(cmd)),
+            // Scripts we cannot parse are checked word by word.
+            None => script
+                .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|' | '(' | ')'))
+                .any(|word| is_network_program(word)),
+        };
+    }
+
+    is_network_exec(command)
+}
+
+fn is_network_exec(command: &[String]) -> bool {
+    let Some(cmd0) = command.first() else {
+        return false;
+    };
+    if is_network_program(cmd0) {
+        return true;
+    }
+
+    let program = cmd0.rsplit('/').next().unwrap_or(cmd0);
+    let subcommand = command
+        .iter()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map(String::as_str);
+    match (program, subcommand) {
+        ("git", Some(sub)) => matches!(
+            sub,
+            "clone" | "fetch" | "pull" | "push" | "ls-remote" | "submodule"
+        ),
+        ("npm" | "pnpm" | "yarn" | "bun", Some(sub)) => matches!(
+            sub,
+            "install" | "i" | "add" | "ci" | "update" | "upgrade" | "publish"
+        ),
+        ("npx" | "pnpx" | "bunx", _) => true,
+        ("pip" | "pip3" | "uv" | "poetry", Some(sub)) => {
+            matches!(sub, "install" | "download" | "add" | "sync" | "lock")
+        }
+        ("cargo", Some(sub)) => matches!(
+            sub,
+            "install" | "fetch" | "update" | "publish" | "search" | "add"
+        ),
+        ("go", Some(sub)) => matches!(sub, "get" | "install" | "mod"),
+        ("apt" | "apt-get" | "brew" | "dnf" | "yum" | "apk", _) => true,
+        ("docker" | "podman", Some(sub)) => matches!(sub, "pull" | "push" | "login"),
+        _ => false,
+    }
+}
+
+fn is_network_program(word: &str) -> bool {
+    let program = word.rsplit('/').next().unwrap_or(word);
+    matches!(
+        program,
+        "curl"
+            | "wget"
+            | "ssh"
+            | "scp"
+            | "sftp"
+            | "ftp"
+            | "telnet"
+            | "nc"
+            | "ncat"
+            | "netcat"
+            | "socat"
+            | "ping"
+            | "dig"
+            | "nslookup"
+            | "host"
+            | "gh"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec_str(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn network_clients_are_flagged() {
+        assert!(is_network_command(&vec_str(&[
+            "curl",
+            "-I",
+            "https://example.com"
+        ])));
+        assert!(is_network_command(&vec_str(&[
+            "/usr/bin/wget",
+            "example.com"
+        ])));
+        assert!(is_network_command(&vec_str(&["git", "clone", "repo"])));
+        assert!(is_network_command(&vec_str(&["npm", "install"])));
+        assert!(is_network_command(&vec_str(&[
+            "bash",
+            "-lc",
+            "cd web && npm ci"
+        ])));
+        assert!(is_network_command(&vec_str(&[
+            "bash",
+            "-lc",
+            "curl -s $(cat url.txt) > out"
+        ])));
+    }
+
+    #[test]
+    fn local_commands_are_not_flagged() {
+        assert!(!is_network_command(&vec_str(&["git", "status"])));
+        assert!(!is_network_command(&vec_str(&["cargo", "test"])));
+        assert!(!is_network_command(&vec_str(&["ls", "-la"])));
+        assert!(!is_network_command(&vec_str(&[
+            "bash",
+            "-lc",
+            "grep -rn curl src"
+        ])));
+    }
+}
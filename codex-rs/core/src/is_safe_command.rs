@@ -1,26 +1,48 @@
+//! Commands known not to modify anything, which may run without approval.
+//! The built-in rules live in `safe_commands.toml`; users add their own under
+//! `[[approvals.safe_commands]]`.
+
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+
+use crate::bash::shell_script_arg;
 use crate::bash::try_parse_bash;
 use crate::bash::try_parse_word_only_commands_sequence;
+use crate::config_types::SafeCommandRule;
+
+#[derive(Deserialize)]
+struct SafeCommandRules {
+    command: Vec<SafeCommandRule>,
+}
+
+static BUILTIN_RULES: LazyLock<Vec<SafeCommandRule>> = LazyLock::new(|| {
+    #[expect(clippy::expect_used)]
+    let rules: SafeCommandRules =
+        toml::from_str(include_str!("safe_commands.toml")).expect("parse safe_commands.toml");
+    rules.command
+});
 
-pub fn is_known_safe_command(command: &[String]) -> bool {
-    if is_safe_to_call_with_exec(command) {
+/// Whether `command` is read-only under the built-in rules or `extra_rules`.
+pub(crate) fn is_known_safe_command(command: &[String], extra_rules: &[SafeCommandRule]) -> bool {
+    if is_safe_to_call_with_exec(command, extra_rules) {
         return true;
     }
 
-    // Support `bash -lc "..."` where the script consists solely of one or
-    // more "plain" commands (only bare words / quoted strings) combined with
-    // a conservative allow‑list of shell operators that themselves do not
-    // introduce side effects ( "&&", "||", ";", and "|" ). If every
-    // individual command in the script is itself a known‑safe command, then
-    // the composite expression is considered safe.
-    if let [bash, flag, script] = command
-        && bash == "bash"
-        && flag == "-lc"
+    // Support `bash -c "..."` (or `-lc`) where the script consists solely of
+    // one or more "plain" commands (only bare words / quoted strings) combined
+    // with a conservative allow‑list of shell operators that themselves do
+    // not introduce side effects ( "&&", "||", ";", and "|" ). Redirections
+    // and substitutions fail to parse as plain commands. If every individual
+    // command in the script is itself a known‑safe command, then the
+    // composite expression is considered safe.
+    if let Some(script) = shell_script_arg(command)
         && let Some(tree) = try_parse_bash(script)
         && let Some(all_commands) = try_parse_word_only_commands_sequence(&tree, script)
         && !all_commands.is_empty()
         && all_commands
             .iter()
-            .all(|cmd| is_safe_to_call_with_exec(cmd))
+            .all(|cmd| is_safe_to_call_with_exec(cmd, extra_rules))
     {
         return true;
     }
@@ -28,102 +50,84 @@ pub fn is_known_safe_command(command: &[String]) -> bool {
     false
 }
 
-fn is_safe_to_call_with_exec(command: &[String]) -> bool {
-    let cmd0 = command.first().map(String::as_str);
-
-    match cmd0 {
-        #[rustfmt::skip]
-        Some(
-            "cat" |
-            "cd" |
-            "echo" |
-            "false" |
-            "grep" |
-            "head" |
-            "ls" |
-            "nl" |
-            "pwd" |
-            "tail" |
-            "true" |
-            "wc" |
-            "which") => {
-            true
-        },
-
-        Some("find") => {
-            // Certain options to `find` can delete files, write to files, or
-            // execute arbitrary commands, so we cannot auto-approve the
-            // invocation of `find` in such cases.
-            #[rustfmt::skip]
-            const UNSAFE_FIND_OPTIONS: &[&str] = &[
-                // Options that can execute arbitrary commands.
-                "-exec", "-execdir", "-ok", "-okdir",
-                // Option that deletes matching files.
-                "-delete",
-                // Options that write pathnames to a file.
-                "-fls", "-fprint", "-fprint0", "-fprintf",
-            ];
-
-            !command
-                .iter()
-                .any(|arg| UNSAFE_FIND_OPTIONS.contains(&arg.as_str()))
-        }
+/// Whether a single argv, run without a shell, is read-only.
+pub(crate) fn is_safe_to_call_with_exec(
+    command: &[String],
+    extra_rules: &[SafeCommandRule],
+) -> bool {
+    let Some((program, args)) = command.split_first() else {
+        return false;
+    };
+    if program == "sed" {
+        return is_safe_sed(args);
+    }
+    BUILTIN_RULES
+        .iter()
+        .chain(extra_rules)
+        .any(|rule| rule_allows(rule, program, args))
+}
 
-        // Ripgrep
-        Some("rg") => {
-            const UNSAFE_RIPGREP_OPTIONS_WITH_ARGS: &[&str] = &[
-                // Takes an arbitrary command that is executed for each match.
-                "--pre",
-                // Takes a command that can be used to obtain the local hostname.
-                "--hostname-bin",
-            ];
-            const UNSAFE_RIPGREP_OPTIONS_WITHOUT_ARGS: &[&str] = &[
-                // Calls out to other decompression tools, so do not auto-approve
-                // out of an abundance of caution.
-                "--search-zip",
-                "-z",
-            ];
-
-            !command.iter().any(|arg| {
-                UNSAFE_RIPGREP_OPTIONS_WITHOUT_ARGS.contains(&arg.as_str())
-                    || UNSAFE_RIPGREP_OPTIONS_WITH_ARGS
-                        .iter()
-                        .any(|&opt| arg == opt || arg.starts_with(&format!("{opt}=")))
-            })
+fn rule_allows(rule: &SafeCommandRule, program: &str, args: &[String]) -> bool {
+    if rule.program != program {
+        return false;
+    }
+    let args = if rule.subcommands.is_empty() {
+        args
+    } else {
+        match args.split_first() {
+            Some((subcommand, rest)) if rule.subcommands.contains(subcommand) => rest,
+            _ => return false,
         }
+    };
+    if args
+        .iter()
+        .any(|arg| is_forbidden_flag(arg, &rule.forbidden_flags))
+    {
+        return false;
+    }
+    rule.max_operands
+        .is_none_or(|max| args.iter().filter(|arg| !arg.starts_with('-')).count() <= max)
+}
 
-        // Git
-        Some("git") => matches!(
-            command.get(1).map(String::as_str),
-            Some("branch" | "status" | "log" | "diff" | "show")
-        ),
-
-        // Rust
-        Some("cargo") if command.get(1).map(String::as_str) == Some("check") => true,
-
-        // Special-case `sed -n {N|M,N}p FILE`
-        Some("sed")
-            if {
-                command.len() == 4
-                    && command.get(1).map(String::as_str) == Some("-n")
-                    && is_valid_sed_n_arg(command.get(2).map(String::as_str))
-                    && command.get(3).map(String::is_empty) == Some(false)
-            } =>
-        {
-            true
+/// Whether `arg` passes one of `forbidden`: exactly, as `--flag=value`, for
+/// a long flag abbreviated the way getopt_long accepts (`--out=x` for
+/// `--output`), or, for a single-letter flag, inside a group such as `-ni` or
+/// with an attached value such as `-oout.txt`.
+fn is_forbidden_flag(arg: &str, forbidden: &[String]) -> bool {
+    forbidden.iter().any(|flag| {
+        if arg == flag {
+            return true;
+        }
+        if flag.starts_with("--") {
+            // Any unambiguous prefix selects the flag; an ambiguous one is an
+            // error, so treating every prefix as the flag is safe.
+            let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+            return name.len() > 2 && name.starts_with("--") && flag.starts_with(name);
+        }
+        match flag.strip_prefix('-') {
+            Some(letter) if letter.chars().count() == 1 => arg
+                .strip_prefix('-')
+                .is_some_and(|group| !group.starts_with('-') && group.contains(letter)),
+            _ => false,
         }
+    })
+}
 
-        // ── anything else ─────────────────────────────────────────────────
+/// `sed -n {N|M,N}p [FILE...]`; any other script may write files or run
+/// commands.
+fn is_safe_sed(args: &[String]) -> bool {
+    match args {
+        [flag, script, files @ ..] => {
+            flag == "-n"
+                && is_valid_sed_n_arg(Some(script.as_str()))
+                && files
+                    .iter()
+                    .all(|file| !file.is_empty() && !file.starts_with('-'))
+        }
         _ => false,
     }
 }
 
-// (bash parsing helpers implemented in crate::bash)
-
-/* ----------------------------------------------------------
-Example
----------------------------------------------------------- */
-
 /// Returns true if `arg` matches /^(\d+,)?\d+p$/
 fn is_valid_sed_n_arg(arg: Option<&str>) -> bool {
     // unwrap or bail
@@ -165,6 +169,150 @@ mod tests {
         args.iter().map(|s| s.to_string()).collect()
     }
 
+    fn is_safe_to_call_with_exec(command: &[String]) -> bool {
+        super::is_safe_to_call_with_exec(command, &[])
+    }
+
+    fn is_known_safe_command(command: &[String]) -> bool {
+        super::is_known_safe_command(command, &[])
+    }
+
+    #[test]
+    fn builtin_rules_parse() {
+        assert!(BUILTIN_RULES.iter().any(|rule| rule.program == "rg"));
+    }
+
+    /// Commands models commonly run to look around a repository.
+    const SAFE_CORPUS: &[&str] = &[
+        "rg -n 'fn main' src",
+        "rg TODO -g '*.rs' | sort | uniq -c",
+        "fd -e rs",
+        "fd --type f Cargo.toml",
+        "wc -l src/lib.rs",
+        "git log --oneline -20",
+        "git log -p --follow -- src/lib.rs",
+        "git diff HEAD~1 --stat",
+        "git show HEAD:README.md",
+        "git branch --show-current",
+        "git branch -vv",
+        "git rev-parse --abbrev-ref HEAD",
+        "git ls-files | wc -l",
+        "sed -n '1,40p' src/main.rs",
+        "sed -n 12p a.rs b.rs",
+        "cat Cargo.toml | head -n 20",
+        "ls -la && git status",
+        "find . -name '*.rs' | wc -l",
+        "du -sh target",
+        "tree -L 2",
+        "jq .version package.json",
+        "sort -u names.txt",
+        "cargo check",
+        "diff -u old.txt new.txt",
+    ];
+
+    /// Commands that write, delete or run something else and so must ask.
+    const ESCALATE_CORPUS: &[&str] = &[
+        "sed -i 's/a/b/' f.txt",
+        "sed -ni 1p f.txt",
+        "sed -n '1p;w out.txt' f.txt",
+        "sed 's/a/b/e' f.txt",
+        "perl -pi -e 's/a/b/' f.txt",
+        "rg foo > out.txt",
+        "cat < in.txt",
+        "echo $(rm -rf /)",
+        "cat `whoami`",
+        "ls; rm -rf target",
+        "rg --pre=./evil foo",
+        "rg -nz foo",
+        "fd -x rm",
+        "fd -e tmp --exec rm",
+        "find . -name '*.o' -delete",
+        "git log --output=log.txt",
+        "git diff --ext-diff",
+        "git grep -O vim TODO",
+        "git branch -D main",
+        "git branch feature",
+        "git -c core.pager=evil log",
+        "git push",
+        "git checkout -- .",
+        "sort -o sorted.txt data.txt",
+        "sort -rno sorted.txt data.txt",
+        "sort --out=/etc/x data.txt",
+        "sort --compress-prog=sh -u data.txt",
+        "date --se='2020-01-01'",
+        "git log --outp=log.txt",
+        "uniq in.txt out.txt",
+        "tree -o out.txt",
+        "date -s '2020-01-01'",
+        "xargs rm",
+        "tee out.txt",
+        "cargo build",
+        "bash -c 'rm -rf target'",
+    ];
+
+    #[test]
+    fn corpus_of_real_commands() {
+        for script in SAFE_CORPUS {
+            for flag in ["-c", "-lc"] {
+                assert!(
+                    is_known_safe_command(&vec_str(&["bash", flag, script])),
+                    "expected `bash {flag} {script:?}` to be safe"
+                );
+            }
+        }
+        for script in ESCALATE_CORPUS {
+            for flag in ["-c", "-lc"] {
+                assert!(
+                    !is_known_safe_command(&vec_str(&["bash", flag, script])),
+                    "expected `bash {flag} {script:?}` to need approval"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn forbidden_flags_match_groups_and_values() {
+        let forbidden = vec_str(&["-o", "--output"]);
+        assert!(is_forbidden_flag("-o", &forbidden));
+        assert!(is_forbidden_flag("-rno", &forbidden));
+        assert!(is_forbidden_flag("-oout.txt", &forbidden));
+        assert!(is_forbidden_flag("--output=out.txt", &forbidden));
+        assert!(!is_forbidden_flag("--output-indicator-new=+", &forbidden));
+        assert!(!is_forbidden_flag("--sort", &forbidden));
+        assert!(!is_forbidden_flag("out.txt", &forbidden));
+        assert!(!is_forbidden_flag("--", &forbidden));
+    }
+
+    #[test]
+    fn forbidden_long_flags_match_abbreviations() {
+        let forbidden = vec_str(&["--output", "--compress-program"]);
+        assert!(is_forbidden_flag("--out=/etc/x", &forbidden));
+        assert!(is_forbidden_flag("--o", &forbidden));
+        assert!(is_forbidden_flag("--compress-prog=sh", &forbidden));
+        assert!(is_forbidden_flag("--comp", &forbidden));
+        assert!(!is_forbidden_flag("--outputs", &forbidden));
+        assert!(!is_forbidden_flag("--oneline", &forbidden));
+    }
+
+    #[test]
+    fn extra_rules_extend_the_builtin_list() {
+        let rules = vec![SafeCommandRule {
+            program: "kubectl".to_string(),
+            subcommands: vec_str(&["get", "describe"]),
+            forbidden_flags: vec_str(&["--kubeconfig"]),
+            max_operands: None,
+        }];
+        let safe =
+            |script: &str| super::is_known_safe_command(&vec_str(&["bash", "-lc", script]), &rules);
+
+        assert!(safe("kubectl get pods -n default | grep api"));
+        assert!(!safe("kubectl delete pod api"));
+        assert!(!safe("kubectl get pods --kubeconfig=/tmp/evil"));
+        assert!(!is_known_safe_command(&vec_str(&[
+            "kubectl", "get", "pods"
+        ])));
+    }
+
     #[test]
     fn known_safe_examples() {
         assert!(is_safe_to_call_with_exec(&vec_str(&["ls"])));
@@ -4,8 +4,82 @@ use crate::spawn::spawn_child_async;
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use tokio::process::Child;
 
+/// How `codex-linux-sandbox` keeps sandboxed commands off the network on
+/// this kernel, from strongest to weakest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEnforcement {
+    /// Landlock network rules (ABI >= 4) for TCP, plus a seccomp filter for
+    /// the other socket types.
+    Landlock,
+    /// A seccomp filter denying every socket that is not AF_UNIX.
+    Seccomp,
+    /// Neither is available, so sandboxed commands keep network access.
+    Unavailable,
+}
+
+/// Detects the network enforcement available to the Linux sandbox. The
+/// result is computed once per process.
+pub fn network_enforcement() -> NetworkEnforcement {
+    static ENFORCEMENT: OnceLock<NetworkEnforcement> = OnceLock::new();
+    *ENFORCEMENT.get_or_init(detect_network_enforcement)
+}
+
+#[cfg(target_os = "linux")]
+fn detect_network_enforcement() -> NetworkEnforcement {
+    // Landlock only covers TCP, so the seccomp filter is needed either way.
+    if !seccomp_filter_available() {
+        NetworkEnforcement::Unavailable
+    } else if landlock_abi_version() >= 4 {
+        NetworkEnforcement::Landlock
+    } else {
+        NetworkEnforcement::Seccomp
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_network_enforcement() -> NetworkEnforcement {
+    NetworkEnforcement::Unavailable
+}
+
+/// Highest Landlock ABI supported by the running kernel, or 0 when Landlock
+/// is missing or disabled.
+#[cfg(target_os = "linux")]
+fn landlock_abi_version() -> i64 {
+    const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+    let version = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    version.max(0)
+}
+
+/// Whether seccomp filters that fail syscalls with an errno can be
+/// installed. Kernels older than 4.14 cannot answer the query and are
+/// treated as lacking support.
+#[cfg(target_os = "linux")]
+fn seccomp_filter_available() -> bool {
+    const SECCOMP_GET_ACTION_AVAIL: libc::c_uint = 2;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    let action = SECCOMP_RET_ERRNO;
+    let flags: libc::c_uint = 0;
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            SECCOMP_GET_ACTION_AVAIL,
+            flags,
+            &action as *const u32,
+        )
+    };
+    rc == 0
+}
+
 /// Spawn a shell tool command under the Linux Landlock+seccomp sandbox helper
 /// (codex-linux-sandbox).
 ///
@@ -5,19 +5,30 @@
 // the TUI or the tracing stack).
 #![deny(clippy::print_stdout, clippy::print_stderr)]
 
+mod anthropic_messages;
 mod apply_patch;
+mod approved_commands;
 mod bash;
+pub mod cassette;
 mod chat_completions;
 mod client;
 mod client_common;
 pub mod codex;
 mod codex_conversation;
+pub mod codex_dirs;
+pub mod command_tools;
+mod compact;
 pub use codex_conversation::CodexConversation;
 pub mod config;
 pub mod config_profile;
 pub mod config_types;
+mod config_validation;
 mod conversation_history;
 pub mod custom_prompts;
+pub mod delta_coalescing;
+mod embed;
+pub use embed::CodexBuilder;
+pub use embed::CodexHandle;
 mod environment_context;
 pub mod error;
 pub mod exec;
@@ -25,15 +36,22 @@ mod exec_command;
 pub mod exec_env;
 mod flags;
 pub mod git_info;
+mod image_input;
+mod is_network_command;
 mod is_safe_command;
 pub mod landlock;
 mod mcp_connection_manager;
+mod mcp_prompt;
 mod mcp_tool_call;
 mod message_history;
 mod model_provider_info;
+mod network_proxy;
 pub mod parse_command;
+mod patch_undo;
+pub use model_provider_info::BUILT_IN_GEMINI_MODEL_PROVIDER_ID;
 pub use model_provider_info::BUILT_IN_OSS_MODEL_PROVIDER_ID;
 pub use model_provider_info::ModelProviderInfo;
+pub use model_provider_info::ProviderKind;
 pub use model_provider_info::WireApi;
 pub use model_provider_info::built_in_model_providers;
 pub use model_provider_info::create_oss_provider_with_base_url;
@@ -41,26 +59,50 @@ mod conversation_manager;
 pub use conversation_manager::ConversationManager;
 pub use conversation_manager::NewConversation;
 pub mod model_family;
+pub mod model_pricing;
 mod openai_model_info;
 mod openai_tools;
+pub mod otel;
 pub mod plan_tool;
 pub mod project_doc;
-mod rollout;
+mod prompt_vars;
+#[cfg(unix)]
+mod pty;
+pub mod pull_request;
+mod rate_limits;
+pub mod redact;
+mod review;
+pub mod rollout;
 pub(crate) mod safety;
+mod sandbox_denial;
 pub mod seatbelt;
+pub mod session_export;
+pub mod session_index;
 pub mod shell;
 pub mod spawn;
 pub mod terminal;
+mod token_estimate;
 mod tool_apply_patch;
+mod tool_output_budget;
 pub mod turn_diff_tracker;
 pub mod user_agent;
 mod user_notification;
 pub mod util;
+#[cfg(windows)]
+pub mod windows_sandbox;
+mod wire_api_cache;
 pub use apply_patch::CODEX_APPLY_PATCH_ARG1;
 pub use safety::get_platform_sandbox;
+#[cfg(windows)]
+pub use windows_sandbox::CODEX_WINDOWS_SANDBOX_ARG1;
 // Re-export the protocol types from the standalone `codex-protocol` crate so existing
 // `codex_core::protocol::...` references continue to work across the workspace.
 pub use codex_protocol::protocol;
 // Re-export protocol config enums to ensure call sites can use the same types
 // as those in the protocol crate when constructing protocol messages.
 pub use codex_protocol::config_types as protocol_config_types;
+// Types that embedders of `CodexBuilder` need most often.
+pub use codex_protocol::models::ResponseItem;
+pub use codex_protocol::protocol::AskForApproval;
+pub use codex_protocol::protocol::SandboxPolicy;
+pub use codex_protocol::protocol::TokenUsage;
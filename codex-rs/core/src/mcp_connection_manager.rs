@@ -4,29 +4,60 @@
 //! configured server (keyed by the *server name*). It offers convenience
 //! helpers to query the available tools across *all* servers and returns them
 //! in a single aggregated map using the fully-qualified tool name
-//! `"<server><MCP_TOOL_NAME_DELIMITER><tool>"` as the key.
+//! `"<server><MCP_TOOL_NAME_DELIMITER><tool>"` as the key. Resources and
+//! prompts are fetched on demand from the servers that advertise them; prompts
+//! are qualified the same way as tools.
+//!
+//! Tool lists are cached; a server's tools are only listed again after it
+//! sends `notifications/tools/list_changed`, and only if it declared the
+//! `tools.listChanged` capability.
+//!
+//! A server's configuration can hide some of its tools and replace the
+//! `<server>__` prefix or whole tool names. Calls are made with the name the
+//! server gave the tool.
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ffi::OsString;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+use std::sync::RwLockWriteGuard;
 use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use codex_mcp_client::McpClient;
+use codex_mcp_client::OAuthCredentialStore;
+use codex_mcp_client::OAuthSession;
 use mcp_types::ClientCapabilities;
+use mcp_types::GetPromptResult;
 use mcp_types::Implementation;
+use mcp_types::ProgressNotificationParams;
+use mcp_types::Prompt;
+use mcp_types::ReadResourceResult;
+use mcp_types::Resource;
+use mcp_types::ServerCapabilities;
 use mcp_types::Tool;
 
 use serde_json::json;
 use sha1::Digest;
 use sha1::Sha1;
+use tokio::sync::mpsc;
 use tokio::task::JoinSet;
 use tracing::info;
 use tracing::warn;
 
 use crate::config_types::McpServerConfig;
+use crate::config_types::McpServerStartup;
+use crate::config_types::McpServerTransport;
+use crate::config_types::ShellEnvironmentPolicy;
+use crate::exec_env::create_env;
+use crate::protocol::McpServerTools;
 
 /// Delimiter used to separate the server name from the tool name in a fully
 /// qualified tool name.
@@ -36,21 +67,32 @@ use crate::config_types::McpServerConfig;
 const MCP_TOOL_NAME_DELIMITER: &str = "__";
 const MAX_TOOL_NAME_LENGTH: usize = 64;
 
+/// How long a server may take to start and list its tools, unless its
+/// configuration sets `startup_timeout_ms`.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Timeout for the `tools/list` request.
 const LIST_TOOLS_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Timeout for the `resources/list`, `resources/read`, `prompts/list` and
+/// `prompts/get` requests.
+const RESOURCES_AND_PROMPTS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper bound on the pages fetched from a single server by a list request, in
+/// case a misbehaving server keeps returning a cursor.
+const MAX_LIST_PAGES: usize = 20;
+
 /// Map that holds a startup error for every MCP server that could **not** be
 /// spawned successfully.
 pub type ClientStartErrors = HashMap<String, anyhow::Error>;
 
-fn qualify_tools(tools: Vec<ToolInfo>) -> HashMap<String, ToolInfo> {
-    let mut used_names = HashSet::new();
-    let mut qualified_tools = HashMap::new();
+/// Gives every tool a unique name that fits the model's limits. Also returns
+/// the servers with a tool whose name is taken by a server listed earlier.
+fn qualify_tools(tools: Vec<ToolInfo>) -> (HashMap<String, ToolInfo>, ClientStartErrors) {
+    let mut qualified_tools: HashMap<String, ToolInfo> = HashMap::new();
+    let mut clashes = ClientStartErrors::new();
     for tool in tools {
-        let mut qualified_name = format!(
-            "{}{}{}",
-            tool.server_name, MCP_TOOL_NAME_DELIMITER, tool.tool_name
-        );
+        let mut qualified_name = tool.exposed_name.clone();
         if qualified_name.len() > MAX_TOOL_NAME_LENGTH {
             let mut hasher = Sha1::new();
             hasher.update(qualified_name.as_bytes());
@@ -63,37 +105,194 @@ fn qualify_tools(tools: Vec<ToolInfo>) -> HashMap<String, ToolInfo> {
             qualified_name = format!("{}{}", &qualified_name[..prefix_len], sha1_str);
         }
 
-        if used_names.contains(&qualified_name) {
-            warn!("skipping duplicated tool {}", qualified_name);
+        if let Some(existing) = qualified_tools.get(&qualified_name) {
+            if existing.server_name == tool.server_name {
+                warn!("skipping duplicated tool {}", qualified_name);
+            } else if !clashes.contains_key(&tool.server_name) {
+                let error = anyhow!(
+                    "tool `{}` would be offered as `{qualified_name}`, like tool `{}` of MCP server `{}`; rename one of them with `tool_prefix` or `tool_names`",
+                    tool.tool_name,
+                    existing.tool_name,
+                    existing.server_name
+                );
+                clashes.insert(tool.server_name.clone(), error);
+            }
             continue;
         }
 
-        used_names.insert(qualified_name.clone());
         qualified_tools.insert(qualified_name, tool);
     }
 
-    qualified_tools
+    (qualified_tools, clashes)
 }
 
 struct ToolInfo {
     server_name: String,
     tool_name: String,
+    /// Name offered to the model, before [`qualify_tools`] shortens it.
+    exposed_name: String,
     tool: Tool,
 }
 
-/// A thin wrapper around a set of running [`McpClient`] instances.
+/// Which of a server's tools are offered to the model and under what names,
+/// from the server's configuration.
+struct ToolNaming {
+    enabled: Option<HashSet<String>>,
+    disabled: HashSet<String>,
+    prefix: Option<String>,
+    renames: HashMap<String, String>,
+}
+
+impl ToolNaming {
+    fn from_config(cfg: &McpServerConfig) -> Result<Self> {
+        if let Some(prefix) = &cfg.tool_prefix
+            && !prefix.is_empty()
+            && !is_valid_name(prefix)
+        {
+            return Err(anyhow!(
+                "invalid tool_prefix '{prefix}': must match pattern ^[a-zA-Z0-9_-]*$"
+            ));
+        }
+        if let Some(name) = cfg.tool_names.values().find(|name| !is_valid_name(name)) {
+            return Err(anyhow!(
+                "invalid name '{name}' in tool_names: must match pattern ^[a-zA-Z0-9_-]+$"
+            ));
+        }
+        Ok(Self {
+            enabled: cfg
+                .enabled_tools
+                .as_ref()
+                .map(|names| names.iter().cloned().collect()),
+            disabled: cfg.disabled_tools.iter().cloned().collect(),
+            prefix: cfg.tool_prefix.clone(),
+            renames: cfg.tool_names.clone(),
+        })
+    }
+
+    fn is_offered(&self, tool_name: &str) -> bool {
+        self.enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled.contains(tool_name))
+            && !self.disabled.contains(tool_name)
+    }
+
+    fn exposed_name(&self, server_name: &str, tool_name: &str) -> String {
+        if let Some(name) = self.renames.get(tool_name) {
+            return name.clone();
+        }
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}{tool_name}"),
+            None => format!("{server_name}{MCP_TOOL_NAME_DELIMITER}{tool_name}"),
+        }
+    }
+}
+
+/// A server that started successfully, with the tools it listed.
+struct RunningServer {
+    client: Arc<McpClient>,
+
+    /// Capabilities the server advertised in its reply to `initialize`.
+    capabilities: ServerCapabilities,
+
+    /// Cached result of `tools/list`. Only refreshed when the server declared
+    /// `tools.listChanged` and then sent the matching notification.
+    tools: Vec<Tool>,
+
+    naming: ToolNaming,
+}
+
+impl RunningServer {
+    fn announces_tool_changes(&self) -> bool {
+        self.capabilities
+            .tools
+            .as_ref()
+            .and_then(|tools| tools.list_changed)
+            .unwrap_or(false)
+    }
+}
+
 #[derive(Default)]
-pub(crate) struct McpConnectionManager {
-    /// Server-name -> client instance.
+struct Servers {
+    /// Server-name -> running server.
     ///
     /// The server name originates from the keys of the `mcp_servers` map in
     /// the user configuration.
-    clients: HashMap<String, std::sync::Arc<McpClient>>,
+    running: HashMap<String, RunningServer>,
 
     /// Fully qualified tool name -> tool instance.
     tools: HashMap<String, ToolInfo>,
 }
 
+impl Servers {
+    /// Names the tools of the running servers. A server with a tool whose
+    /// name is taken by a server earlier in name order is stopped and
+    /// returned as failed.
+    fn requalify_tools(&mut self) -> ClientStartErrors {
+        let mut errors = ClientStartErrors::new();
+        loop {
+            let mut server_names: Vec<&String> = self.running.keys().collect();
+            server_names.sort();
+            let all_tools = server_names
+                .into_iter()
+                .flat_map(|server_name| {
+                    let server = &self.running[server_name];
+                    server
+                        .tools
+                        .iter()
+                        .filter(|tool| server.naming.is_offered(&tool.name))
+                        .map(|tool| ToolInfo {
+                            server_name: server_name.clone(),
+                            tool_name: tool.name.clone(),
+                            exposed_name: server.naming.exposed_name(server_name, &tool.name),
+                            tool: tool.clone(),
+                        })
+                })
+                .collect();
+            let (tools, clashes) = qualify_tools(all_tools);
+            if clashes.is_empty() {
+                self.tools = tools;
+                break;
+            }
+            for (server_name, error) in clashes {
+                self.running.remove(&server_name);
+                errors.insert(server_name, error);
+            }
+        }
+        info!(
+            "aggregated {} tools from {} servers",
+            self.tools.len(),
+            self.running.len()
+        );
+        errors
+    }
+}
+
+/// A server configured with `startup = "lazy"` that has not been started yet.
+struct PendingServer {
+    server_name: String,
+    cfg: McpServerConfig,
+    naming: ToolNaming,
+    store: OAuthCredentialStore,
+    base_env: HashMap<String, String>,
+}
+
+/// A thin wrapper around a set of running [`McpClient`] instances.
+#[derive(Default)]
+pub(crate) struct McpConnectionManager {
+    servers: RwLock<Servers>,
+
+    /// Lazy servers, started by the next [`Self::refresh_tools`]. The lock
+    /// also keeps concurrent refreshes from starting a server twice.
+    deferred: tokio::sync::Mutex<Vec<PendingServer>>,
+}
+
+/// A prompt offered by one of the servers.
+#[derive(Debug, Clone)]
+pub(crate) struct PromptInfo {
+    pub server_name: String,
+    pub prompt: Prompt,
+}
+
 impl McpConnectionManager {
     /// Spawn a [`McpClient`] for each configured server.
     ///
@@ -101,23 +300,33 @@ impl McpConnectionManager {
     ///   are human-readable server identifiers and *values* are the spawn
     ///   instructions.
     ///
+    /// * `codex_home` – Where the OAuth tokens of HTTP servers are stored.
+    ///
+    /// Servers are started concurrently, each within its own startup timeout.
     /// Servers that fail to start are reported in `ClientStartErrors`: the
-    /// user should be informed about these errors.
+    /// user should be informed about these errors. Lazy servers are only
+    /// started by [`Self::refresh_tools`].
+    /// Stdio servers are spawned with the environment derived from
+    /// `env_policy`, plus the `env` entries from their own configuration.
     pub async fn new(
         mcp_servers: HashMap<String, McpServerConfig>,
+        codex_home: &Path,
+        env_policy: &ShellEnvironmentPolicy,
     ) -> Result<(Self, ClientStartErrors)> {
         // Early exit if no servers are configured.
         if mcp_servers.is_empty() {
             return Ok((Self::default(), ClientStartErrors::default()));
         }
 
-        // Launch all configured servers concurrently.
-        let mut join_set = JoinSet::new();
         let mut errors = ClientStartErrors::new();
+        let store = OAuthCredentialStore::new(codex_home);
+        let base_env = create_env(env_policy);
 
+        let mut eager = Vec::new();
+        let mut deferred = Vec::new();
         for (server_name, cfg) in mcp_servers {
             // Validate server name before spawning
-            if !is_valid_mcp_server_name(&server_name) {
+            if !is_valid_name(&server_name) {
                 let error = anyhow::anyhow!(
                     "invalid server name '{}': must match pattern ^[a-zA-Z0-9_-]+$",
                     server_name
@@ -125,157 +334,460 @@ impl McpConnectionManager {
                 errors.insert(server_name, error);
                 continue;
             }
-
-            join_set.spawn(async move {
-                let McpServerConfig { command, args, env } = cfg;
-                let client_res = McpClient::new_stdio_client(
-                    command.into(),
-                    args.into_iter().map(OsString::from).collect(),
-                    env,
-                )
-                .await;
-                match client_res {
-                    Ok(client) => {
-                        // Initialize the client.
-                        let params = mcp_types::InitializeRequestParams {
-                            capabilities: ClientCapabilities {
-                                experimental: None,
-                                roots: None,
-                                sampling: None,
-                                // https://modelcontextprotocol.io/specification/2025-06-18/client/elicitation#capabilities
-                                // indicates this should be an empty object.
-                                elicitation: Some(json!({})),
-                            },
-                            client_info: Implementation {
-                                name: "codex-mcp-client".to_owned(),
-                                version: env!("CARGO_PKG_VERSION").to_owned(),
-                                title: Some("Codex".into()),
-                            },
-                            protocol_version: mcp_types::MCP_SCHEMA_VERSION.to_owned(),
-                        };
-                        let initialize_notification_params = None;
-                        let timeout = Some(Duration::from_secs(10));
-                        match client
-                            .initialize(params, initialize_notification_params, timeout)
-                            .await
-                        {
-                            Ok(_response) => (server_name, Ok(client)),
-                            Err(e) => (server_name, Err(e)),
-                        }
-                    }
-                    Err(e) => (server_name, Err(e.into())),
-                }
-            });
-        }
-
-        let mut clients: HashMap<String, std::sync::Arc<McpClient>> =
-            HashMap::with_capacity(join_set.len());
-
-        while let Some(res) = join_set.join_next().await {
-            let (server_name, client_res) = res?; // JoinError propagation
-
-            match client_res {
-                Ok(client) => {
-                    clients.insert(server_name, std::sync::Arc::new(client));
-                }
+            let naming = match ToolNaming::from_config(&cfg) {
+                Ok(naming) => naming,
                 Err(e) => {
                     errors.insert(server_name, e);
+                    continue;
                 }
+            };
+
+            let startup = cfg.startup;
+            let pending = PendingServer {
+                server_name,
+                cfg,
+                naming,
+                store: store.clone(),
+                base_env: base_env.clone(),
+            };
+            match startup {
+                McpServerStartup::Eager => eager.push(pending),
+                McpServerStartup::Lazy => deferred.push(pending),
             }
         }
 
-        let all_tools = list_all_tools(&clients).await?;
+        let (running, start_errors) = start_servers(eager).await?;
+        errors.extend(start_errors);
 
-        let tools = qualify_tools(all_tools);
+        let mut servers = Servers {
+            running,
+            tools: HashMap::new(),
+        };
+        errors.extend(servers.requalify_tools());
+
+        Ok((
+            Self {
+                servers: RwLock::new(servers),
+                deferred: tokio::sync::Mutex::new(deferred),
+            },
+            errors,
+        ))
+    }
+
+    /// Starts the lazy servers that are still pending and lists the tools
+    /// again for servers that announced a change. Returns the servers that
+    /// failed to start or were stopped because their tool names clash; they
+    /// are not retried.
+    pub async fn refresh_tools(&self) -> ClientStartErrors {
+        let mut deferred = self.deferred.lock().await;
+        let (started, mut errors) = match start_servers(std::mem::take(&mut *deferred)).await {
+            Ok(res) => res,
+            Err(e) => {
+                warn!("failed to start lazy MCP servers: {e}");
+                return ClientStartErrors::new();
+            }
+        };
+
+        let changed: Vec<(String, Arc<McpClient>)> = self
+            .read()
+            .running
+            .iter()
+            .filter(|(_, server)| {
+                server.announces_tool_changes() && server.client.take_tools_list_changed()
+            })
+            .map(|(server_name, server)| (server_name.clone(), server.client.clone()))
+            .collect();
+        let mut join_set = JoinSet::new();
+        for (server_name, client) in changed {
+            join_set.spawn(async move {
+                let res = client
+                    .list_tools(None, Some(LIST_TOOLS_TIMEOUT))
+                    .await
+                    .map(|result| result.tools);
+                (server_name, res)
+            });
+        }
+        let relisted = collect_per_server(join_set, "tools").await;
 
-        Ok((Self { clients, tools }, errors))
+        if !started.is_empty() || !relisted.is_empty() {
+            let mut servers = self.write();
+            servers.running.extend(started);
+            for (server_name, tools) in relisted {
+                if let Some(server) = servers.running.get_mut(&server_name) {
+                    server.tools = tools;
+                }
+            }
+            errors.extend(servers.requalify_tools());
+        }
+        errors
     }
 
     /// Returns a single map that contains **all** tools. Each key is the
     /// fully-qualified name for the tool.
     pub fn list_all_tools(&self) -> HashMap<String, Tool> {
-        self.tools
+        self.read()
+            .tools
             .iter()
             .map(|(name, tool)| (name.clone(), tool.tool.clone()))
             .collect()
     }
 
-    /// Invoke the tool indicated by the (server, tool) pair.
+    /// Returns how the tools of each running server are offered to the model.
+    pub fn list_tools_by_server(&self) -> HashMap<String, McpServerTools> {
+        let servers = self.read();
+        let mut by_server: HashMap<String, McpServerTools> = servers
+            .running
+            .iter()
+            .map(|(server_name, server)| {
+                let mut hidden: Vec<String> = server
+                    .tools
+                    .iter()
+                    .filter(|tool| !server.naming.is_offered(&tool.name))
+                    .map(|tool| tool.name.clone())
+                    .collect();
+                hidden.sort();
+                let tools = McpServerTools {
+                    tools: BTreeMap::new(),
+                    hidden,
+                };
+                (server_name.clone(), tools)
+            })
+            .collect();
+        for (qualified_name, tool) in &servers.tools {
+            if let Some(entry) = by_server.get_mut(&tool.server_name) {
+                entry
+                    .tools
+                    .insert(qualified_name.clone(), tool.tool_name.clone());
+            }
+        }
+        by_server
+    }
+
+    /// Invoke the tool indicated by the (server, tool) pair. Progress the
+    /// server reports while the call runs is forwarded to `progress`.
     pub async fn call_tool(
         &self,
         server: &str,
         tool: &str,
         arguments: Option<serde_json::Value>,
         timeout: Option<Duration>,
+        progress: mpsc::UnboundedSender<ProgressNotificationParams>,
     ) -> Result<mcp_types::CallToolResult> {
-        let client = self
-            .clients
-            .get(server)
-            .ok_or_else(|| anyhow!("unknown MCP server '{server}'"))?
-            .clone();
-
-        client
-            .call_tool(tool.to_string(), arguments, timeout)
+        self.client(server)?
+            .call_tool_with_progress(tool.to_string(), arguments, timeout, progress)
             .await
             .with_context(|| format!("tool call failed for `{server}/{tool}`"))
     }
 
     pub fn parse_tool_name(&self, tool_name: &str) -> Option<(String, String)> {
-        self.tools
+        self.read()
+            .tools
             .get(tool_name)
             .map(|tool| (tool.server_name.clone(), tool.tool_name.clone()))
     }
+
+    /// Returns the resources of every server that offers any, keyed by server
+    /// name. Servers that fail to answer are logged and left out.
+    pub async fn list_all_resources(&self) -> HashMap<String, Vec<Resource>> {
+        let mut join_set = JoinSet::new();
+        for (server_name, client) in self.clients_with(|caps| caps.resources.is_some()) {
+            join_set.spawn(async move {
+                let res = async {
+                    let mut resources = Vec::new();
+                    let mut cursor = None;
+                    for _ in 0..MAX_LIST_PAGES {
+                        let params =
+                            cursor
+                                .take()
+                                .map(|cursor| mcp_types::ListResourcesRequestParams {
+                                    cursor: Some(cursor),
+                                });
+                        let page = client
+                            .list_resources(params, Some(RESOURCES_AND_PROMPTS_TIMEOUT))
+                            .await?;
+                        resources.extend(page.resources);
+                        cursor = page.next_cursor;
+                        if cursor.is_none() {
+                            break;
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(resources)
+                }
+                .await;
+                (server_name, res)
+            });
+        }
+        collect_per_server(join_set, "resources").await
+    }
+
+    /// Returns the prompts of every server that offers any, keyed by their
+    /// fully qualified name. Servers that fail to answer are logged and left
+    /// out.
+    pub async fn list_all_prompts(&self) -> HashMap<String, PromptInfo> {
+        let mut join_set = JoinSet::new();
+        for (server_name, client) in self.clients_with(|caps| caps.prompts.is_some()) {
+            join_set.spawn(async move {
+                let res = async {
+                    let mut prompts = Vec::new();
+                    let mut cursor = None;
+                    for _ in 0..MAX_LIST_PAGES {
+                        let params =
+                            cursor
+                                .take()
+                                .map(|cursor| mcp_types::ListPromptsRequestParams {
+                                    cursor: Some(cursor),
+                                });
+                        let page = client
+                            .list_prompts(params, Some(RESOURCES_AND_PROMPTS_TIMEOUT))
+                            .await?;
+                        prompts.extend(page.prompts);
+                        cursor = page.next_cursor;
+                        if cursor.is_none() {
+                            break;
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(prompts)
+                }
+                .await;
+                (server_name, res)
+            });
+        }
+
+        let mut qualified = HashMap::new();
+        let per_server = collect_per_server(join_set, "prompts").await;
+        for (server_name, prompts) in per_server {
+            for prompt in prompts {
+                let name = format!("{server_name}{MCP_TOOL_NAME_DELIMITER}{}", prompt.name);
+                if qualified.contains_key(&name) {
+                    warn!("skipping duplicated prompt {name}");
+                    continue;
+                }
+                qualified.insert(
+                    name,
+                    PromptInfo {
+                        server_name: server_name.clone(),
+                        prompt,
+                    },
+                );
+            }
+        }
+        qualified
+    }
+
+    /// Reads the resource at `uri` from `server`.
+    pub async fn read_resource(&self, server: &str, uri: &str) -> Result<ReadResourceResult> {
+        self.client(server)?
+            .read_resource(uri.to_string(), Some(RESOURCES_AND_PROMPTS_TIMEOUT))
+            .await
+            .with_context(|| format!("failed to read `{uri}` from `{server}`"))
+    }
+
+    /// Renders the prompt `name` offered by `server`.
+    pub async fn get_prompt(
+        &self,
+        server: &str,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let arguments = (!arguments.is_empty()).then(|| json!(arguments));
+        self.client(server)?
+            .get_prompt(
+                name.to_string(),
+                arguments,
+                Some(RESOURCES_AND_PROMPTS_TIMEOUT),
+            )
+            .await
+            .with_context(|| format!("failed to get prompt `{name}` from `{server}`"))
+    }
+
+    fn client(&self, server: &str) -> Result<Arc<McpClient>> {
+        self.read()
+            .running
+            .get(server)
+            .map(|server| server.client.clone())
+            .ok_or_else(|| anyhow!("unknown MCP server '{server}'"))
+    }
+
+    fn clients_with(
+        &self,
+        has_capability: impl Fn(&ServerCapabilities) -> bool,
+    ) -> Vec<(String, Arc<McpClient>)> {
+        self.read()
+            .running
+            .iter()
+            .filter(|(_, server)| has_capability(&server.capabilities))
+            .map(|(server_name, server)| (server_name.clone(), server.client.clone()))
+            .collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Servers> {
+        self.servers.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Servers> {
+        self.servers.write().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
-/// Query every server for its available tools and return a single map that
-/// contains **all** tools. Each key is the fully-qualified name for the tool.
-async fn list_all_tools(
-    clients: &HashMap<String, std::sync::Arc<McpClient>>,
-) -> Result<Vec<ToolInfo>> {
+/// Starts `pending` concurrently and lists their tools. A server that does not
+/// get through both within its startup timeout is reported as failed.
+async fn start_servers(
+    pending: Vec<PendingServer>,
+) -> Result<(HashMap<String, RunningServer>, ClientStartErrors)> {
     let mut join_set = JoinSet::new();
-
-    // Spawn one task per server so we can query them concurrently. This
-    // keeps the overall latency roughly at the slowest server instead of
-    // the cumulative latency.
-    for (server_name, client) in clients {
-        let server_name_cloned = server_name.clone();
-        let client_clone = client.clone();
+    for PendingServer {
+        server_name,
+        cfg,
+        naming,
+        store,
+        base_env,
+    } in pending
+    {
         join_set.spawn(async move {
-            let res = client_clone
-                .list_tools(None, Some(LIST_TOOLS_TIMEOUT))
-                .await;
-            (server_name_cloned, res)
+            let startup_timeout = cfg
+                .startup_timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_STARTUP_TIMEOUT);
+            let res = tokio::time::timeout(
+                startup_timeout,
+                start_server(&server_name, cfg, naming, store, base_env, startup_timeout),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow!(
+                    "timed out after {}ms; raise `startup_timeout_ms` if the server is slow to start",
+                    startup_timeout.as_millis()
+                ))
+            });
+            (server_name, res)
         });
     }
 
-    let mut aggregated: Vec<ToolInfo> = Vec::with_capacity(join_set.len());
-
-    while let Some(join_res) = join_set.join_next().await {
-        let (server_name, list_result) = join_res?;
-        let list_result = list_result?;
-
-        for tool in list_result.tools {
-            let tool_info = ToolInfo {
-                server_name: server_name.clone(),
-                tool_name: tool.name.clone(),
-                tool,
-            };
-            aggregated.push(tool_info);
+    let mut running = HashMap::with_capacity(join_set.len());
+    let mut errors = ClientStartErrors::new();
+    while let Some(res) = join_set.join_next().await {
+        let (server_name, server_res) = res?; // JoinError propagation
+        match server_res {
+            Ok(server) => {
+                running.insert(server_name, server);
+            }
+            Err(e) => {
+                errors.insert(server_name, e);
+            }
         }
     }
+    Ok((running, errors))
+}
 
-    info!(
-        "aggregated {} tools from {} servers",
-        aggregated.len(),
-        clients.len()
-    );
+async fn start_server(
+    server_name: &str,
+    cfg: McpServerConfig,
+    naming: ToolNaming,
+    store: OAuthCredentialStore,
+    base_env: HashMap<String, String>,
+    timeout: Duration,
+) -> Result<RunningServer> {
+    let (client, capabilities) = start_client(server_name, cfg, store, base_env, timeout).await?;
+    let tools = client
+        .list_tools(None, Some(timeout))
+        .await
+        .context("failed to list tools")?
+        .tools;
+    Ok(RunningServer {
+        client: Arc::new(client),
+        capabilities,
+        tools,
+        naming,
+    })
+}
 
-    Ok(aggregated)
+/// Spawns or connects to the server described by `cfg` and runs the
+/// `initialize` handshake.
+async fn start_client(
+    server_name: &str,
+    cfg: McpServerConfig,
+    store: OAuthCredentialStore,
+    mut base_env: HashMap<String, String>,
+    timeout: Duration,
+) -> Result<(McpClient, ServerCapabilities)> {
+    let (client, login_hint) = match cfg.transport {
+        McpServerTransport::Stdio { command, args, env } => {
+            base_env.extend(env.unwrap_or_default());
+            let client = McpClient::new_stdio_client_with_env(
+                command.into(),
+                args.into_iter().map(OsString::from).collect(),
+                base_env,
+            )
+            .await?;
+            (client, false)
+        }
+        McpServerTransport::StreamableHttp { url, .. } => {
+            let credentials = store.load(server_name, &url)?;
+            let login_hint = credentials.is_none();
+            let oauth = credentials
+                .map(|credentials| OAuthSession::new(server_name.to_string(), store, credentials));
+            (
+                McpClient::new_streamable_http_client(url, oauth),
+                login_hint,
+            )
+        }
+    };
+
+    let params = mcp_types::InitializeRequestParams {
+        capabilities: ClientCapabilities {
+            experimental: None,
+            roots: None,
+            sampling: None,
+            // https://modelcontextprotocol.io/specification/2025-06-18/client/elicitation#capabilities
+            // indicates this should be an empty object.
+            elicitation: Some(json!({})),
+        },
+        client_info: Implementation {
+            name: "codex-mcp-client".to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            title: Some("Codex".into()),
+        },
+        protocol_version: mcp_types::MCP_SCHEMA_VERSION.to_owned(),
+    };
+    let initialize_notification_params = None;
+    let initialized = client
+        .initialize(params, initialize_notification_params, Some(timeout))
+        .await;
+    match initialized {
+        Ok(response) => Ok((client, response.capabilities)),
+        Err(e) if login_hint => Err(e.context(format!(
+            "if `{server_name}` requires authentication, run `codex mcp login {server_name}`"
+        ))),
+        Err(e) => Err(e),
+    }
 }
 
-fn is_valid_mcp_server_name(server_name: &str) -> bool {
-    !server_name.is_empty()
-        && server_name
+/// Waits for the per-server list requests in `join_set`, logging and leaving
+/// out the servers that failed.
+async fn collect_per_server<T: 'static>(
+    mut join_set: JoinSet<(String, Result<Vec<T>>)>,
+    what: &str,
+) -> HashMap<String, Vec<T>> {
+    let mut per_server = HashMap::with_capacity(join_set.len());
+    while let Some(join_res) = join_set.join_next().await {
+        match join_res {
+            Ok((server_name, Ok(items))) => {
+                per_server.insert(server_name, items);
+            }
+            Ok((server_name, Err(e))) => {
+                warn!("failed to list {what} of MCP server `{server_name}`: {e:#}");
+            }
+            Err(e) => warn!("failed to list MCP {what}: {e}"),
+        }
+    }
+    per_server
+}
+
+/// Whether `name` is a valid server or tool name: OpenAI requires tool names
+/// to match `^[a-zA-Z0-9_-]+$`.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
             .chars()
             .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
 }
@@ -284,11 +796,50 @@ fn is_valid_mcp_server_name(server_name: &str) -> bool {
 mod tests {
     use super::*;
     use mcp_types::ToolInputSchema;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    /// Answers `initialize` and `tools/list` with a single `echo` tool, using
+    /// only shell builtins.
+    const STUB_SERVER: &str = r#"
+while IFS= read -r line; do
+  case "$line" in *'"id":'*) ;; *) continue ;; esac
+  id=${line#*\"id\":}
+  id=${id%%,*}
+  id=${id%%\}*}
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{}},"protocolVersion":"2025-06-18","serverInfo":{"name":"stub","version":"1"}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","inputSchema":{"type":"object"}}]}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    /// Reads requests forever without answering any of them.
+    const HANGING_SERVER: &str = "while read -r line; do :; done";
+
+    fn stub_config(script: &str, startup: McpServerStartup) -> McpServerConfig {
+        McpServerConfig {
+            transport: McpServerTransport::Stdio {
+                command: "/bin/sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: None,
+            },
+            startup,
+            startup_timeout_ms: Some(2_000),
+            enabled_tools: None,
+            disabled_tools: Vec::new(),
+            tool_prefix: None,
+            tool_names: HashMap::new(),
+        }
+    }
 
     fn create_test_tool(server_name: &str, tool_name: &str) -> ToolInfo {
         ToolInfo {
             server_name: server_name.to_string(),
             tool_name: tool_name.to_string(),
+            exposed_name: format!("{server_name}{MCP_TOOL_NAME_DELIMITER}{tool_name}"),
             tool: Tool {
                 annotations: None,
                 description: Some(format!("Test tool: {tool_name}")),
@@ -311,7 +862,7 @@ mod tests {
             create_test_tool("server1", "tool2"),
         ];
 
-        let qualified_tools = qualify_tools(tools);
+        let (qualified_tools, _) = qualify_tools(tools);
 
         assert_eq!(qualified_tools.len(), 2);
         assert!(qualified_tools.contains_key("server1__tool1"));
@@ -325,7 +876,7 @@ mod tests {
             create_test_tool("server1", "duplicate_tool"),
         ];
 
-        let qualified_tools = qualify_tools(tools);
+        let (qualified_tools, _) = qualify_tools(tools);
 
         // Only the first tool should remain, the second is skipped
         assert_eq!(qualified_tools.len(), 1);
@@ -347,7 +898,7 @@ mod tests {
             ),
         ];
 
-        let qualified_tools = qualify_tools(tools);
+        let (qualified_tools, _) = qualify_tools(tools);
 
         assert_eq!(qualified_tools.len(), 2);
 
@@ -366,4 +917,178 @@ mod tests {
             "my_server__yet_another_e1c3987bd9c50b826cbe1687966f79f0c602d19ca"
         );
     }
+
+    #[test]
+    fn test_qualify_tools_reports_clashes_between_servers() {
+        let mut second = create_test_tool("server2", "search");
+        second.exposed_name = "search".to_string();
+        let mut first = create_test_tool("server1", "find");
+        first.exposed_name = "search".to_string();
+
+        let (qualified_tools, clashes) = qualify_tools(vec![first, second]);
+
+        assert_eq!(qualified_tools.keys().collect::<Vec<_>>(), vec!["search"]);
+        assert_eq!(clashes.keys().collect::<Vec<_>>(), vec!["server2"]);
+        let message = clashes["server2"].to_string();
+        assert!(
+            message.contains("tool `find` of MCP server `server1`"),
+            "{message}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hanging_server_does_not_block_the_others() {
+        let codex_home = TempDir::new().unwrap();
+        let mut hanging = stub_config(HANGING_SERVER, McpServerStartup::Eager);
+        hanging.startup_timeout_ms = Some(200);
+        let servers = HashMap::from([
+            ("hanging".to_string(), hanging),
+            (
+                "stub".to_string(),
+                stub_config(STUB_SERVER, McpServerStartup::Eager),
+            ),
+        ]);
+
+        let started = Instant::now();
+        let (manager, errors) = McpConnectionManager::new(
+            servers,
+            codex_home.path(),
+            &ShellEnvironmentPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["hanging"]);
+        assert!(
+            errors["hanging"]
+                .to_string()
+                .contains("timed out after 200ms")
+        );
+        assert_eq!(
+            manager.list_all_tools().keys().collect::<Vec<_>>(),
+            vec!["stub__echo"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn lazy_server_starts_on_first_refresh() {
+        let codex_home = TempDir::new().unwrap();
+        let servers = HashMap::from([(
+            "lazy".to_string(),
+            stub_config(STUB_SERVER, McpServerStartup::Lazy),
+        )]);
+
+        let (manager, errors) = McpConnectionManager::new(
+            servers,
+            codex_home.path(),
+            &ShellEnvironmentPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert!(errors.is_empty());
+        assert!(manager.list_all_tools().is_empty());
+
+        assert!(manager.refresh_tools().await.is_empty());
+        assert_eq!(
+            manager.list_all_tools().keys().collect::<Vec<_>>(),
+            vec!["lazy__echo"]
+        );
+        assert_eq!(
+            manager.parse_tool_name("lazy__echo"),
+            Some(("lazy".to_string(), "echo".to_string()))
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn lazy_server_that_hangs_is_reported_once() {
+        let codex_home = TempDir::new().unwrap();
+        let mut hanging = stub_config(HANGING_SERVER, McpServerStartup::Lazy);
+        hanging.startup_timeout_ms = Some(200);
+        let servers = HashMap::from([("hanging".to_string(), hanging)]);
+
+        let (manager, errors) = McpConnectionManager::new(
+            servers,
+            codex_home.path(),
+            &ShellEnvironmentPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert!(errors.is_empty());
+
+        let errors = manager.refresh_tools().await;
+        assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["hanging"]);
+        assert!(manager.refresh_tools().await.is_empty());
+        assert!(manager.list_all_tools().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tools_are_filtered_and_renamed() {
+        let codex_home = TempDir::new().unwrap();
+        let mut renamed = stub_config(STUB_SERVER, McpServerStartup::Eager);
+        renamed.tool_names = HashMap::from([("echo".to_string(), "say".to_string())]);
+        let mut hidden = stub_config(STUB_SERVER, McpServerStartup::Eager);
+        hidden.disabled_tools = vec!["echo".to_string()];
+        let servers = HashMap::from([
+            ("renamed".to_string(), renamed),
+            ("hidden".to_string(), hidden),
+        ]);
+
+        let (manager, errors) = McpConnectionManager::new(
+            servers,
+            codex_home.path(),
+            &ShellEnvironmentPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            manager.list_all_tools().keys().collect::<Vec<_>>(),
+            vec!["say"]
+        );
+        assert_eq!(
+            manager.parse_tool_name("say"),
+            Some(("renamed".to_string(), "echo".to_string()))
+        );
+        assert_eq!(manager.parse_tool_name("hidden__echo"), None);
+        let by_server = manager.list_tools_by_server();
+        assert_eq!(by_server["hidden"].hidden, vec!["echo".to_string()]);
+        assert_eq!(
+            by_server["renamed"].tools,
+            BTreeMap::from([("say".to_string(), "echo".to_string())])
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn clashing_tool_names_stop_the_later_server() {
+        let codex_home = TempDir::new().unwrap();
+        let mut first = stub_config(STUB_SERVER, McpServerStartup::Eager);
+        first.tool_prefix = Some(String::new());
+        let servers = HashMap::from([("a".to_string(), first.clone()), ("b".to_string(), first)]);
+
+        let (manager, errors) = McpConnectionManager::new(
+            servers,
+            codex_home.path(),
+            &ShellEnvironmentPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["b"]);
+        assert!(errors["b"].to_string().contains("MCP server `a`"));
+        assert_eq!(
+            manager.list_all_tools().keys().collect::<Vec<_>>(),
+            vec!["echo"]
+        );
+        assert_eq!(
+            manager.list_tools_by_server().keys().collect::<Vec<_>>(),
+            vec!["a"]
+        );
+    }
 }
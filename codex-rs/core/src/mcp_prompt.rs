@@ -0,0 +1,498 @@
+//! Turns the result of an MCP `prompts/get` request into conversation input.
+//!
+//! Every message of the prompt but the last becomes a history item; the last
+//! one, which must come from the user, starts the task. Resources referenced
+//! by the prompt are read and inlined: text is capped at
+//! [`MAX_TEXT_RESOURCE_BYTES`], images are attached as images and any other
+//! binary content is saved to a file the model is told about.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use anyhow::bail;
+use base64::Engine;
+use codex_protocol::models::ContentItem;
+use codex_protocol::models::ResponseItem;
+use mcp_types::BlobResourceContents;
+use mcp_types::ContentBlock;
+use mcp_types::EmbeddedResourceResource;
+use mcp_types::GetPromptResult;
+use mcp_types::ReadResourceResultContents;
+use mcp_types::Role;
+use mcp_types::TextResourceContents;
+
+use crate::mcp_connection_manager::McpConnectionManager;
+use crate::mcp_connection_manager::PromptInfo;
+use crate::protocol::InputItem;
+
+/// Text resources longer than this are truncated before they reach the model.
+pub(crate) const MAX_TEXT_RESOURCE_BYTES: usize = 64 * 1024;
+
+/// Binary resources larger than this are neither attached nor saved.
+pub(crate) const MAX_BINARY_RESOURCE_BYTES: usize = 20 * 1024 * 1024;
+
+/// A rendered prompt, ready to be recorded and submitted.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RenderedPrompt {
+    /// The messages that precede the final user message.
+    pub history: Vec<ResponseItem>,
+    /// The final user message.
+    pub input: Vec<InputItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Image(String),
+}
+
+/// Finds the prompt called `name`: either its fully qualified name or, when
+/// only one server offers it, its bare name. Fails if an argument the prompt
+/// requires is missing from `arguments`.
+pub(crate) fn resolve_prompt(
+    mut prompts: HashMap<String, PromptInfo>,
+    name: &str,
+    arguments: &HashMap<String, String>,
+) -> Result<PromptInfo> {
+    let info = match prompts.remove(name) {
+        Some(info) => info,
+        None => {
+            let mut matches: Vec<PromptInfo> = prompts
+                .into_values()
+                .filter(|info| info.prompt.name == name)
+                .collect();
+            match matches.len() {
+                0 => bail!("unknown MCP prompt `{name}`"),
+                1 => matches.remove(0),
+                _ => {
+                    let mut servers: Vec<String> =
+                        matches.into_iter().map(|info| info.server_name).collect();
+                    servers.sort();
+                    bail!(
+                        "MCP prompt `{name}` is offered by several servers ({}); use its qualified name",
+                        servers.join(", ")
+                    )
+                }
+            }
+        }
+    };
+
+    let missing: Vec<&str> = info
+        .prompt
+        .arguments
+        .iter()
+        .flatten()
+        .filter(|argument| argument.required == Some(true))
+        .map(|argument| argument.name.as_str())
+        .filter(|argument| !arguments.contains_key(*argument))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "MCP prompt `{name}` requires the argument(s): {}",
+            missing.join(", ")
+        );
+    }
+    Ok(info)
+}
+
+/// Renders `result`, the prompt `name` of `server`. Resource links are read
+/// from `server`; binary resources are saved under `attachments_dir`.
+pub(crate) async fn render_prompt(
+    manager: &McpConnectionManager,
+    server: &str,
+    name: &str,
+    result: GetPromptResult,
+    attachments_dir: &Path,
+) -> Result<RenderedPrompt> {
+    let mut renderer = Renderer {
+        manager,
+        server,
+        attachments_dir,
+        saved: 0,
+    };
+    let mut messages: Vec<(Role, Vec<Part>)> = Vec::new();
+    for message in result.messages {
+        let parts = renderer.render_block(message.content).await?;
+        // Consecutive messages of the same role form a single turn.
+        match messages.last_mut() {
+            Some((role, existing)) if *role == message.role => existing.extend(parts),
+            _ => messages.push((message.role, parts)),
+        }
+    }
+    into_rendered_prompt(name, messages)
+}
+
+fn into_rendered_prompt(
+    name: &str,
+    mut messages: Vec<(Role, Vec<Part>)>,
+) -> Result<RenderedPrompt> {
+    let input = match messages.pop() {
+        Some((Role::User, parts)) => parts
+            .into_iter()
+            .map(|part| match part {
+                Part::Text(text) => InputItem::Text { text },
+                Part::Image(image_url) => InputItem::Image { image_url },
+            })
+            .collect(),
+        Some((Role::Assistant, _)) => {
+            bail!("MCP prompt `{name}` does not end with a user message")
+        }
+        None => bail!("MCP prompt `{name}` has no messages"),
+    };
+    let history = messages
+        .into_iter()
+        .map(|(role, parts)| {
+            let (role, content) = match role {
+                Role::User => (
+                    "user",
+                    parts
+                        .into_iter()
+                        .map(|part| match part {
+                            Part::Text(text) => ContentItem::InputText { text },
+                            Part::Image(image_url) => ContentItem::InputImage { image_url },
+                        })
+                        .collect(),
+                ),
+                // Assistant messages cannot carry images.
+                Role::Assistant => (
+                    "assistant",
+                    parts
+                        .into_iter()
+                        .map(|part| match part {
+                            Part::Text(text) => ContentItem::OutputText { text },
+                            Part::Image(_) => ContentItem::OutputText {
+                                text: "[image omitted]".to_string(),
+                            },
+                        })
+                        .collect(),
+                ),
+            };
+            ResponseItem::Message {
+                id: None,
+                role: role.to_string(),
+                content,
+            }
+        })
+        .collect();
+    Ok(RenderedPrompt { history, input })
+}
+
+struct Renderer<'a> {
+    manager: &'a McpConnectionManager,
+    server: &'a str,
+    attachments_dir: &'a Path,
+    /// Number of files saved so far, used to keep their names unique.
+    saved: usize,
+}
+
+impl Renderer<'_> {
+    async fn render_block(&mut self, block: ContentBlock) -> Result<Vec<Part>> {
+        let parts = match block {
+            ContentBlock::TextContent(text) => vec![Part::Text(text.text)],
+            ContentBlock::ImageContent(image) => vec![Part::Image(format!(
+                "data:{};base64,{}",
+                image.mime_type, image.data
+            ))],
+            ContentBlock::AudioContent(audio) => vec![Part::Text(format!(
+                "[audio content ({}) omitted]",
+                audio.mime_type
+            ))],
+            ContentBlock::ResourceLink(link) => {
+                let result = self.manager.read_resource(self.server, &link.uri).await?;
+                let mut parts = Vec::with_capacity(result.contents.len());
+                for contents in result.contents {
+                    parts.push(match contents {
+                        ReadResourceResultContents::TextResourceContents(text) => {
+                            render_text_resource(text)
+                        }
+                        ReadResourceResultContents::BlobResourceContents(blob) => {
+                            self.render_blob_resource(blob).await?
+                        }
+                    });
+                }
+                parts
+            }
+            ContentBlock::EmbeddedResource(embedded) => vec![match embedded.resource {
+                EmbeddedResourceResource::TextResourceContents(text) => render_text_resource(text),
+                EmbeddedResourceResource::BlobResourceContents(blob) => {
+                    self.render_blob_resource(blob).await?
+                }
+            }],
+        };
+        Ok(parts)
+    }
+
+    async fn render_blob_resource(&mut self, blob: BlobResourceContents) -> Result<Part> {
+        let mime_type = blob
+            .mime_type
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        // Base64 takes four characters for every three bytes.
+        if blob.blob.len() / 4 * 3 > MAX_BINARY_RESOURCE_BYTES {
+            return Ok(Part::Text(format!(
+                "[binary resource {} ({mime_type}) is larger than {MAX_BINARY_RESOURCE_BYTES} bytes and was not attached]",
+                blob.uri
+            )));
+        }
+        if mime_type.starts_with("image/") {
+            return Ok(Part::Image(format!(
+                "data:{mime_type};base64,{}",
+                blob.blob
+            )));
+        }
+
+        let bytes = base64::engine::general_purpose::STANDARD.decode(&blob.blob)?;
+        let path = self.attachment_path(&blob.uri);
+        tokio::fs::create_dir_all(self.attachments_dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+        Ok(Part::Text(format!(
+            "[binary resource {} ({mime_type}, {} bytes) saved to {}]",
+            blob.uri,
+            bytes.len(),
+            path.display()
+        )))
+    }
+
+    fn attachment_path(&mut self, uri: &str) -> PathBuf {
+        self.saved += 1;
+        let file_name: String = uri
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let file_name = file_name.trim_start_matches('.');
+        let file_name = if file_name.is_empty() {
+            "resource"
+        } else {
+            file_name
+        };
+        self.attachments_dir
+            .join(format!("{}-{file_name}", self.saved))
+    }
+}
+
+fn render_text_resource(contents: TextResourceContents) -> Part {
+    let TextResourceContents { text, uri, .. } = contents;
+    let text = if text.len() > MAX_TEXT_RESOURCE_BYTES {
+        let mut end = MAX_TEXT_RESOURCE_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!(
+            "{}\n[truncated: showing the first {end} of {} bytes]",
+            &text[..end],
+            text.len()
+        )
+    } else {
+        text
+    };
+    Part::Text(format!("Contents of {uri}:\n{text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn text(text: &str) -> Part {
+        Part::Text(text.to_string())
+    }
+
+    fn prompt_info(server_name: &str, name: &str, required: &[&str]) -> PromptInfo {
+        PromptInfo {
+            server_name: server_name.to_string(),
+            prompt: mcp_types::Prompt {
+                arguments: Some(
+                    required
+                        .iter()
+                        .map(|argument| mcp_types::PromptArgument {
+                            description: None,
+                            name: argument.to_string(),
+                            required: Some(true),
+                            title: None,
+                        })
+                        .collect(),
+                ),
+                description: None,
+                name: name.to_string(),
+                title: None,
+            },
+        }
+    }
+
+    #[test]
+    fn prompts_resolve_by_qualified_or_unique_bare_name() {
+        let prompts = HashMap::from([
+            ("git__review".to_string(), prompt_info("git", "review", &[])),
+            (
+                "git__summary".to_string(),
+                prompt_info("git", "summary", &[]),
+            ),
+            (
+                "docs__summary".to_string(),
+                prompt_info("docs", "summary", &[]),
+            ),
+        ]);
+        let no_args = HashMap::new();
+
+        let info = resolve_prompt(prompts.clone(), "review", &no_args).unwrap();
+        assert_eq!(info.server_name, "git");
+        let info = resolve_prompt(prompts.clone(), "docs__summary", &no_args).unwrap();
+        assert_eq!(info.server_name, "docs");
+        let err = resolve_prompt(prompts.clone(), "summary", &no_args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "MCP prompt `summary` is offered by several servers (docs, git); use its qualified name"
+        );
+        let err = resolve_prompt(prompts, "deploy", &no_args).unwrap_err();
+        assert_eq!(err.to_string(), "unknown MCP prompt `deploy`");
+    }
+
+    #[test]
+    fn required_prompt_arguments_must_be_given() {
+        let prompts = HashMap::from([(
+            "git__review".to_string(),
+            prompt_info("git", "review", &["branch", "base"]),
+        )]);
+
+        let err = resolve_prompt(
+            prompts.clone(),
+            "review",
+            &HashMap::from([("branch".to_string(), "main".to_string())]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "MCP prompt `review` requires the argument(s): base"
+        );
+
+        let arguments = HashMap::from([
+            ("branch".to_string(), "main".to_string()),
+            ("base".to_string(), "HEAD~1".to_string()),
+        ]);
+        assert!(resolve_prompt(prompts, "review", &arguments).is_ok());
+    }
+
+    #[test]
+    fn earlier_messages_become_history() {
+        let rendered = into_rendered_prompt(
+            "review",
+            vec![
+                (Role::User, vec![text("What changed?")]),
+                (Role::Assistant, vec![text("Let me look.")]),
+                (
+                    Role::User,
+                    vec![
+                        text("Review this"),
+                        Part::Image("data:image/png;base64,AA==".into()),
+                    ],
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            rendered,
+            RenderedPrompt {
+                history: vec![
+                    ResponseItem::Message {
+                        id: None,
+                        role: "user".to_string(),
+                        content: vec![ContentItem::InputText {
+                            text: "What changed?".to_string()
+                        }],
+                    },
+                    ResponseItem::Message {
+                        id: None,
+                        role: "assistant".to_string(),
+                        content: vec![ContentItem::OutputText {
+                            text: "Let me look.".to_string()
+                        }],
+                    },
+                ],
+                input: vec![
+                    InputItem::Text {
+                        text: "Review this".to_string()
+                    },
+                    InputItem::Image {
+                        image_url: "data:image/png;base64,AA==".to_string()
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn prompt_must_end_with_a_user_message() {
+        let err = into_rendered_prompt(
+            "review",
+            vec![
+                (Role::User, vec![text("hi")]),
+                (Role::Assistant, vec![text("hello")]),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "MCP prompt `review` does not end with a user message"
+        );
+    }
+
+    #[test]
+    fn long_text_resources_are_truncated() {
+        let Part::Text(rendered) = render_text_resource(TextResourceContents {
+            mime_type: None,
+            text: "é".repeat(MAX_TEXT_RESOURCE_BYTES),
+            uri: "file:///notes.md".to_string(),
+        }) else {
+            panic!("expected text");
+        };
+
+        assert!(rendered.starts_with("Contents of file:///notes.md:\n"));
+        assert!(rendered.ends_with(&format!(
+            "[truncated: showing the first {MAX_TEXT_RESOURCE_BYTES} of {} bytes]",
+            2 * MAX_TEXT_RESOURCE_BYTES
+        )));
+    }
+
+    #[tokio::test]
+    async fn binary_resources_are_saved_to_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = McpConnectionManager::default();
+        let mut renderer = Renderer {
+            manager: &manager,
+            server: "docs",
+            attachments_dir: dir.path(),
+            saved: 0,
+        };
+
+        let part = renderer
+            .render_blob_resource(BlobResourceContents {
+                blob: base64::engine::general_purpose::STANDARD.encode(b"%PDF-1.7"),
+                mime_type: Some("application/pdf".to_string()),
+                uri: "docs://specs/design doc.pdf".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let path = dir.path().join("1-design_doc.pdf");
+        assert_eq!(
+            part,
+            Part::Text(format!(
+                "[binary resource docs://specs/design doc.pdf (application/pdf, 8 bytes) saved to {}]",
+                path.display()
+            ))
+        );
+        assert_eq!(std::fs::read(path).unwrap(), b"%PDF-1.7");
+    }
+}
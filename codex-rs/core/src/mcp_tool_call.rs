@@ -1,19 +1,25 @@
 use std::time::Duration;
 use std::time::Instant;
 
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::Instrument;
 use tracing::error;
 
 use crate::codex::Session;
+use crate::otel;
 use crate::protocol::Event;
 use crate::protocol::EventMsg;
 use crate::protocol::McpInvocation;
 use crate::protocol::McpToolCallBeginEvent;
 use crate::protocol::McpToolCallEndEvent;
+use crate::protocol::McpToolCallProgressEvent;
 use codex_protocol::models::FunctionCallOutputPayload;
 use codex_protocol::models::ResponseInputItem;
 
 /// Handles the specified tool call dispatches the appropriate
-/// `McpToolCallBegin` and `McpToolCallEnd` events to the `Session`.
+/// `McpToolCallBegin`, `McpToolCallProgress` and `McpToolCallEnd` events to
+/// the `Session`.
 pub(crate) async fn handle_mcp_tool_call(
     sess: &Session,
     sub_id: &str,
@@ -43,10 +49,15 @@ pub(crate) async fn handle_mcp_tool_call(
         }
     };
 
+    let (event_arguments, arguments_truncated) = arguments_for_event(
+        arguments_value.as_ref(),
+        sess.mcp_event_max_argument_bytes(),
+    );
     let invocation = McpInvocation {
         server: server.clone(),
         tool: tool_name.clone(),
-        arguments: arguments_value.clone(),
+        arguments: event_arguments,
+        arguments_truncated,
     };
 
     let tool_call_begin_event = EventMsg::McpToolCallBegin(McpToolCallBeginEvent {
@@ -55,12 +66,31 @@ pub(crate) async fn handle_mcp_tool_call(
     });
     notify_mcp_tool_call_event(sess, sub_id, tool_call_begin_event).await;
 
+    let span =
+        otel::mcp_tool_call_span(&server, &tool_name, &arguments, sess.otel_include_content());
     let start = Instant::now();
-    // Perform the tool call.
-    let result = sess
-        .call_tool(&server, &tool_name, arguments_value.clone(), timeout)
-        .await
-        .map_err(|e| format!("tool call error: {e}"));
+    // Perform the tool call, passing on progress until it returns.
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let call = sess
+        .call_tool(&server, &tool_name, arguments_value, timeout, progress_tx)
+        .instrument(span.clone());
+    tokio::pin!(call);
+    let result = loop {
+        tokio::select! {
+            result = &mut call => break result,
+            Some(progress) = progress_rx.recv() => {
+                let event = EventMsg::McpToolCallProgress(McpToolCallProgressEvent {
+                    call_id: call_id.clone(),
+                    progress: progress.progress,
+                    total: progress.total,
+                    message: progress.message,
+                });
+                notify_mcp_tool_call_event(sess, sub_id, event).await;
+            }
+        }
+    };
+    let result = result.map_err(|e| format!("tool call error: {e}"));
+    otel::record_mcp_tool_call_result(&span, &result, start.elapsed(), sess.otel_include_content());
     let tool_call_end_event = EventMsg::McpToolCallEnd(McpToolCallEndEvent {
         call_id: call_id.clone(),
         invocation,
@@ -73,6 +103,24 @@ pub(crate) async fn handle_mcp_tool_call(
     ResponseInputItem::McpToolCallOutput { call_id, result }
 }
 
+/// The arguments to report in tool call events: `arguments` itself if its
+/// JSON fits in `max_bytes`, else the first `max_bytes` of the JSON as a
+/// string, and whether it was truncated.
+fn arguments_for_event(arguments: Option<&Value>, max_bytes: usize) -> (Option<Value>, bool) {
+    let Some(arguments) = arguments else {
+        return (None, false);
+    };
+    let json = arguments.to_string();
+    if json.len() <= max_bytes {
+        return (Some(arguments.clone()), false);
+    }
+    let mut end = max_bytes;
+    while !json.is_char_boundary(end) {
+        end -= 1;
+    }
+    (Some(Value::String(json[..end].to_string())), true)
+}
+
 async fn notify_mcp_tool_call_event(sess: &Session, sub_id: &str, event: EventMsg) {
     sess.send_event(Event {
         id: sub_id.to_string(),
@@ -80,3 +128,27 @@ async fn notify_mcp_tool_call_event(sess: &Session, sub_id: &str, event: EventMs
     })
     .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn large_arguments_are_truncated_for_events() {
+        let small = json!({ "path": "README.md" });
+        assert_eq!(
+            arguments_for_event(Some(&small), 64),
+            (Some(small.clone()), false)
+        );
+        assert_eq!(arguments_for_event(None, 0), (None, false));
+
+        let large = json!({ "text": "héllo wörld" });
+        // `{"text":"h` is 10 bytes; the 11th is inside `é`.
+        assert_eq!(
+            arguments_for_event(Some(&large), 11),
+            (Some(Value::String("{\"text\":\"h".to_string())), true)
+        );
+    }
+}
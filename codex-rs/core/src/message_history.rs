@@ -14,12 +14,17 @@
 //! the file descriptor is opened with the `O_APPEND` flag. POSIX guarantees
 //! that writes up to `PIPE_BUF` bytes are atomic in that case.
 
+use std::collections::HashSet;
 use std::fs::File;
 use std::fs::OpenOptions;
+use std::io::Read;
 use std::io::Result;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::io::Write;
 use std::path::PathBuf;
 
+use codex_protocol::message_history::HistoryMatch;
 use serde::Deserialize;
 use serde::Serialize;
 use std::time::Duration;
@@ -35,8 +40,8 @@ use std::os::unix::fs::OpenOptionsExt;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
-/// Filename that stores the message history inside `~/.codex`.
-const HISTORY_FILENAME: &str = "history.jsonl";
+/// Filename that stores the message history inside the state directory.
+pub(crate) const HISTORY_FILENAME: &str = "history.jsonl";
 
 const MAX_RETRIES: usize = 10;
 const RETRY_SLEEP: Duration = Duration::from_millis(100);
@@ -49,9 +54,7 @@ pub struct HistoryEntry {
 }
 
 fn history_filepath(config: &Config) -> PathBuf {
-    let mut path = config.codex_home.clone();
-    path.push(HISTORY_FILENAME);
-    path
+    config.dirs.state_dir().join(HISTORY_FILENAME)
 }
 
 /// Append a `text` entry associated with `session_id` to the history file. Uses
@@ -68,7 +71,10 @@ pub(crate) async fn append_entry(text: &str, session_id: &Uuid, config: &Config)
         }
     }
 
-    // TODO: check `text` for sensitive patterns
+    // Keep anything that looks like a secret out of the file.
+    if config.redactions.is_sensitive(text) {
+        return Ok(());
+    }
 
     // Resolve `~/.codex/history.jsonl` and ensure the parent directory exists.
     let path = history_filepath(config);
@@ -111,8 +117,12 @@ pub(crate) async fn append_entry(text: &str, session_id: &Uuid, config: &Config)
     // We use sync I/O with spawn_blocking() because we are using a
     // [`std::fs::File`] instead of a [`tokio::fs::File`] to leverage an
     // advisory file locking API that is not available in the async API.
+    let max_entries = config.history.max_entries;
     tokio::task::spawn_blocking(move || -> Result<()> {
         history_file.write_all(line.as_bytes())?;
+        if let Some(max_entries) = max_entries {
+            keep_newest_entries(&mut history_file, max_entries)?;
+        }
         history_file.flush()?;
         Ok(())
     })
@@ -121,6 +131,28 @@ pub(crate) async fn append_entry(text: &str, session_id: &Uuid, config: &Config)
     Ok(())
 }
 
+/// Drops the oldest lines of the locked history `file` so that at most
+/// `max_entries` remain. The file is truncated and rewritten in place so that
+/// its identifier stays the same.
+fn keep_newest_entries(file: &mut File, max_entries: usize) -> Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    if lines.len() <= max_entries {
+        return Ok(());
+    }
+
+    let mut kept = lines[lines.len() - max_entries..].join("\n");
+    if !kept.is_empty() {
+        kept.push('\n');
+    }
+    // The file is opened in append mode, so after truncating, the write
+    // starts at offset 0.
+    file.set_len(0)?;
+    file.write_all(kept.as_bytes())
+}
+
 /// Attempt to acquire an exclusive advisory lock on `file`, retrying up to 10
 /// times if the lock is currently held by another process. This prevents a
 /// potential indefinite wait while still giving other writers some time to
@@ -251,6 +283,79 @@ pub(crate) fn lookup(log_id: u64, offset: usize, config: &Config) -> Option<Hist
     None
 }
 
+/// Returns the history file's identifier and up to `limit` entries whose text
+/// contains `query`, ignoring case. Matches are newest first; an entry whose
+/// text repeats a newer match is skipped.
+///
+/// Like [`lookup`], this uses the sync advisory file locking API.
+#[cfg(unix)]
+pub(crate) fn search(query: &str, limit: usize, config: &Config) -> (u64, Vec<HistoryMatch>) {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = history_filepath(config);
+    let mut file: File = match OpenOptions::new().read(true).open(&path) {
+        Ok(f) => f,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(error = %e, "failed to open history file");
+            }
+            return (0, Vec::new());
+        }
+    };
+    let log_id = match file.metadata() {
+        Ok(metadata) => metadata.ino(),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to stat history file");
+            return (0, Vec::new());
+        }
+    };
+
+    if let Err(e) = acquire_shared_lock_with_retry(&file) {
+        tracing::warn!(error = %e, "failed to acquire shared lock on history file");
+        return (log_id, Vec::new());
+    }
+    let mut contents = String::new();
+    if let Err(e) = file.read_to_string(&mut contents) {
+        tracing::warn!(error = %e, "failed to read history file");
+        return (log_id, Vec::new());
+    }
+
+    (log_id, search_lines(&contents, query, limit))
+}
+
+/// Fallback stub for non-Unix systems: currently always returns no matches.
+#[cfg(not(unix))]
+pub(crate) fn search(query: &str, limit: usize, config: &Config) -> (u64, Vec<HistoryMatch>) {
+    let _ = (query, limit, config);
+    (0, Vec::new())
+}
+
+fn search_lines(contents: &str, query: &str, limit: usize) -> Vec<HistoryMatch> {
+    let query = query.to_lowercase();
+    let mut seen = HashSet::new();
+    let mut matches = Vec::new();
+    for (offset, line) in contents.lines().enumerate().rev() {
+        if matches.len() >= limit {
+            break;
+        }
+        let Ok(entry) = serde_json::from_str::<HistoryEntry>(line) else {
+            continue;
+        };
+        if !entry.text.to_lowercase().contains(&query) || !seen.insert(entry.text.clone()) {
+            continue;
+        }
+        matches.push(HistoryMatch {
+            offset,
+            entry: codex_protocol::message_history::HistoryEntry {
+                session_id: entry.session_id,
+                ts: entry.ts,
+                text: entry.text,
+            },
+        });
+    }
+    matches
+}
+
 /// Fallback stub for non-Unix systems: currently always returns `None`.
 #[cfg(not(unix))]
 pub(crate) fn lookup(log_id: u64, offset: usize, config: &Config) -> Option<HistoryEntry> {
@@ -299,3 +404,65 @@ async fn ensure_owner_only_permissions(_file: &File) -> Result<()> {
     // For now, on non-Unix, simply succeed.
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn line(text: &str) -> String {
+        serde_json::to_string(&HistoryEntry {
+            session_id: "s".to_string(),
+            ts: 0,
+            text: text.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn search_returns_newest_unique_matches_first() {
+        let contents = [
+            line("cargo build"),
+            line("git status"),
+            line("Cargo test"),
+            line("cargo build"),
+        ]
+        .join("\n");
+
+        let matches = search_lines(&contents, "cargo", 10);
+        let found: Vec<(usize, &str)> = matches
+            .iter()
+            .map(|m| (m.offset, m.entry.text.as_str()))
+            .collect();
+        assert_eq!(found, vec![(3, "cargo build"), (2, "Cargo test")]);
+
+        assert_eq!(search_lines(&contents, "", 1).len(), 1);
+    }
+
+    #[test]
+    fn only_the_newest_entries_are_kept() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(HISTORY_FILENAME);
+        std::fs::write(
+            &path,
+            format!("{}\n{}\n{}\n", line("a"), line("b"), line("c")),
+        )
+        .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .read(true)
+            .open(&path)
+            .unwrap();
+
+        keep_newest_entries(&mut file, 2).unwrap();
+        file.write_all(format!("{}\n", line("d")).as_bytes())
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let texts: Vec<String> = search_lines(&contents, "", 10)
+            .into_iter()
+            .map(|m| m.entry.text)
+            .collect();
+        assert_eq!(texts, vec!["d", "c", "b"]);
+    }
+}
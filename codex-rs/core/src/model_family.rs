@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+
+use wildmatch::WildMatch;
+
+use crate::config_types::ModelFamilyToml;
+use crate::openai_model_info::get_model_info;
 use crate::tool_apply_patch::ApplyPatchToolType;
 
 /// A model family is a group of models that share certain characteristics.
@@ -20,6 +26,10 @@ pub struct ModelFamily {
     // `summary` is optional).
     pub supports_reasoning_summaries: bool,
 
+    /// Whether the model accepts `"minimal"` reasoning effort. Other
+    /// reasoning models get `"low"` instead.
+    pub supports_minimal_reasoning_effort: bool,
+
     // This should be set to true when the model expects a tool named
     // "local_shell" to be provided. Its contract must be understood natively by
     // the model such that its description can be omitted.
@@ -29,6 +39,26 @@ pub struct ModelFamily {
     /// Present if the model performs better when `apply_patch` is provided as
     /// a tool call instead of just a bash command
     pub apply_patch_tool_type: Option<ApplyPatchToolType>,
+
+    /// Whether the Responses API accepts `text.verbosity` for this model.
+    pub supports_verbosity: bool,
+
+    /// Whether user messages may include images.
+    pub supports_image_input: bool,
+
+    /// Sequences that end the model's answer, sent as `stop` to Chat
+    /// Completions providers.
+    pub stop: Vec<String>,
+
+    /// Text the answer is forced to start with. Chat Completions providers
+    /// get it as a trailing assistant message, which servers such as
+    /// llama.cpp continue instead of starting a new answer.
+    pub assistant_prefix: Option<String>,
+
+    /// Whether Responses API requests run in background mode, so that a
+    /// long turn survives connections that proxies drop while the model is
+    /// thinking.
+    pub use_background_mode: bool,
 }
 
 macro_rules! model_family {
@@ -41,8 +71,14 @@ macro_rules! model_family {
             family: $family.to_string(),
             needs_special_apply_patch_instructions: false,
             supports_reasoning_summaries: false,
+            supports_minimal_reasoning_effort: false,
             uses_local_shell_tool: false,
             apply_patch_tool_type: None,
+            supports_verbosity: false,
+            supports_image_input: true,
+            stop: Vec::new(),
+            assistant_prefix: None,
+            use_background_mode: false,
         };
         // apply overrides
         $(
@@ -61,8 +97,14 @@ macro_rules! simple_model_family {
             family: $family.to_string(),
             needs_special_apply_patch_instructions: false,
             supports_reasoning_summaries: false,
+            supports_minimal_reasoning_effort: false,
             uses_local_shell_tool: false,
             apply_patch_tool_type: None,
+            supports_verbosity: false,
+            supports_image_input: true,
+            stop: Vec::new(),
+            assistant_prefix: None,
+            use_background_mode: false,
         })
     }};
 }
@@ -97,17 +139,222 @@ pub fn find_family_for_model(slug: &str) -> Option<ModelFamily> {
             needs_special_apply_patch_instructions: true,
         )
     } else if slug.starts_with("gpt-oss") {
-        model_family!(slug, "gpt-oss", apply_patch_tool_type: Some(ApplyPatchToolType::Function))
+        model_family!(
+            slug, "gpt-oss",
+            apply_patch_tool_type: Some(ApplyPatchToolType::Function),
+            supports_image_input: false,
+        )
     } else if slug.starts_with("gpt-4o") {
         simple_model_family!(slug, "gpt-4o")
     } else if slug.starts_with("gpt-3.5") {
-        simple_model_family!(slug, "gpt-3.5")
+        model_family!(slug, "gpt-3.5", supports_image_input: false)
     } else if slug.starts_with("gpt-5") {
         model_family!(
             slug, "gpt-5",
             supports_reasoning_summaries: true,
+            supports_minimal_reasoning_effort: true,
+            supports_verbosity: true,
         )
     } else {
         None
     }
 }
+
+/// Like [`find_family_for_model`], but consults the user-defined
+/// `[model_families]` table first. A matching entry is layered on top of the
+/// built-in family it names (or the one `slug` resolves to), so it can both
+/// override known models and describe models Codex has never heard of.
+pub fn find_family_for_model_with_overrides(
+    slug: &str,
+    model_families: &HashMap<String, ModelFamilyToml>,
+) -> Option<ModelFamily> {
+    let Some(overrides) = find_model_family_override(slug, model_families) else {
+        return find_family_for_model(slug);
+    };
+
+    let base = overrides
+        .family
+        .as_deref()
+        .and_then(find_family_for_model)
+        .or_else(|| find_family_for_model(slug));
+    let mut family = match base {
+        Some(base) => ModelFamily {
+            slug: slug.to_string(),
+            ..base
+        },
+        None => ModelFamily {
+            slug: slug.to_string(),
+            family: overrides.family.clone().unwrap_or_else(|| slug.to_string()),
+            needs_special_apply_patch_instructions: false,
+            supports_reasoning_summaries: false,
+            supports_minimal_reasoning_effort: false,
+            uses_local_shell_tool: false,
+            apply_patch_tool_type: None,
+            supports_verbosity: false,
+            supports_image_input: true,
+            stop: Vec::new(),
+            assistant_prefix: None,
+            use_background_mode: false,
+        },
+    };
+
+    if let Some(v) = overrides.needs_special_apply_patch_instructions {
+        family.needs_special_apply_patch_instructions = v;
+    }
+    if let Some(v) = overrides.supports_reasoning_summaries {
+        family.supports_reasoning_summaries = v;
+    }
+    if let Some(v) = overrides.supports_minimal_reasoning_effort {
+        family.supports_minimal_reasoning_effort = v;
+    }
+    if let Some(v) = overrides.supports_verbosity {
+        family.supports_verbosity = v;
+    }
+    if let Some(v) = overrides.supports_image_input {
+        family.supports_image_input = v;
+    }
+    if let Some(v) = overrides.uses_local_shell_tool {
+        family.uses_local_shell_tool = v;
+    }
+    if let Some(v) = &overrides.apply_patch_tool_type {
+        family.apply_patch_tool_type = Some(v.clone());
+    }
+    if let Some(v) = &overrides.stop {
+        family.stop = v.clone();
+    }
+    if let Some(v) = &overrides.assistant_prefix {
+        family.assistant_prefix = Some(v.clone()).filter(|prefix| !prefix.is_empty());
+    }
+    if let Some(v) = overrides.use_background_mode {
+        family.use_background_mode = v;
+    }
+    Some(family)
+}
+
+/// Returns the `[model_families]` entry that applies to `slug`. An exact key
+/// wins; otherwise the longest glob pattern that matches is used.
+pub(crate) fn find_model_family_override<'a>(
+    slug: &str,
+    model_families: &'a HashMap<String, ModelFamilyToml>,
+) -> Option<&'a ModelFamilyToml> {
+    if let Some(exact) = model_families.get(slug) {
+        return Some(exact);
+    }
+    model_families
+        .iter()
+        .filter(|(pattern, _)| WildMatch::new(pattern).matches(slug))
+        .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+        .map(|(_, family)| family)
+}
+
+/// Context window for `model_family`, preferring a `context_window` declared
+/// in `[model_families]` over the built-in model metadata.
+pub(crate) fn get_model_context_window(
+    model_family: &ModelFamily,
+    model_families: &HashMap<String, ModelFamilyToml>,
+) -> Option<u64> {
+    find_model_family_override(&model_family.slug, model_families)
+        .and_then(|overrides| overrides.context_window)
+        .or_else(|| get_model_info(model_family).map(|info| info.context_window))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn configured_family_wins_over_built_in() {
+        let model_families = HashMap::from([(
+            "gpt-4.1".to_string(),
+            ModelFamilyToml {
+                needs_special_apply_patch_instructions: Some(false),
+                apply_patch_tool_type: Some(ApplyPatchToolType::Function),
+                ..Default::default()
+            },
+        )]);
+
+        let family = find_family_for_model_with_overrides("gpt-4.1", &model_families)
+            .expect("gpt-4.1 is known");
+        assert_eq!(family.family, "gpt-4.1");
+        assert!(!family.needs_special_apply_patch_instructions);
+        assert_eq!(
+            family.apply_patch_tool_type,
+            Some(ApplyPatchToolType::Function)
+        );
+    }
+
+    #[test]
+    fn glob_pattern_extends_named_family() {
+        let model_families = HashMap::from([
+            (
+                "ft:*".to_string(),
+                ModelFamilyToml {
+                    supports_reasoning_summaries: Some(false),
+                    ..Default::default()
+                },
+            ),
+            (
+                "ft:gpt-4.1:acme:*".to_string(),
+                ModelFamilyToml {
+                    family: Some("gpt-4.1".to_string()),
+                    supports_reasoning_summaries: Some(true),
+                    context_window: Some(64_000),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let slug = "ft:gpt-4.1:acme::abc";
+        assert_eq!(find_family_for_model(slug), None);
+        let family =
+            find_family_for_model_with_overrides(slug, &model_families).expect("configured");
+        assert_eq!(family.slug, slug);
+        assert_eq!(family.family, "gpt-4.1");
+        assert!(family.needs_special_apply_patch_instructions);
+        assert!(family.supports_reasoning_summaries);
+        assert_eq!(
+            get_model_context_window(&family, &model_families),
+            Some(64_000)
+        );
+
+        let other = find_family_for_model_with_overrides("ft:o3:acme::xyz", &model_families)
+            .expect("configured");
+        assert_eq!(other.family, "ft:o3:acme::xyz");
+        assert!(!other.supports_reasoning_summaries);
+    }
+
+    #[test]
+    fn stop_and_assistant_prefix_come_from_config() {
+        let model_families = HashMap::from([(
+            "qwen*".to_string(),
+            ModelFamilyToml {
+                stop: Some(vec!["<|im_end|>".to_string()]),
+                assistant_prefix: Some("<think>\n".to_string()),
+                ..Default::default()
+            },
+        )]);
+
+        let family = find_family_for_model_with_overrides("qwen3-coder", &model_families)
+            .expect("configured");
+        assert_eq!(family.stop, vec!["<|im_end|>".to_string()]);
+        assert_eq!(family.assistant_prefix.as_deref(), Some("<think>\n"));
+
+        let built_in = find_family_for_model("gpt-4.1").expect("gpt-4.1 is known");
+        assert!(built_in.stop.is_empty());
+        assert_eq!(built_in.assistant_prefix, None);
+    }
+
+    #[test]
+    fn unconfigured_slug_uses_built_in_table() {
+        let model_families = HashMap::new();
+        assert_eq!(
+            find_family_for_model_with_overrides("o3", &model_families),
+            find_family_for_model("o3")
+        );
+        assert_eq!(
+            find_family_for_model_with_overrides("unknown-model", &model_families),
+            None
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Estimated cost of model responses, from the token counts the provider
+//! reports and a table of per-token prices.
+//!
+//! Prices come from the `[model_pricing]` config table, keyed by model slug
+//! or glob pattern like `[model_families]`, and then from the built-in table
+//! below. A model found in neither has no price: frontends show its tokens
+//! only rather than a wrong dollar figure.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+use wildmatch::WildMatch;
+
+use crate::protocol::TokenUsage;
+
+const TOKENS_PER_PRICE_UNIT: f64 = 1_000_000.0;
+
+/// Prices of a model in US dollars per million tokens.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPricing {
+    pub input: f64,
+
+    /// Price of input tokens read from the prompt cache. Defaults to `input`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+
+    /// Also applies to reasoning tokens, which are billed as output.
+    pub output: f64,
+}
+
+impl ModelPricing {
+    const fn new(input: f64, cached_input: f64, output: f64) -> Self {
+        Self {
+            input,
+            cached_input: Some(cached_input),
+            output,
+        }
+    }
+
+    /// Cost of `usage` in US dollars.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached_input = self.cached_input.unwrap_or(self.input);
+        (usage.non_cached_input() as f64 * self.input
+            + usage.cached_input() as f64 * cached_input
+            + usage.output_tokens as f64 * self.output)
+            / TOKENS_PER_PRICE_UNIT
+    }
+}
+
+/// List prices of the models Codex knows, as input, cached input and output
+/// dollars per million tokens. Dated snapshots of these slugs, such as
+/// `gpt-4.1-2025-04-14`, use the same prices.
+const BUILT_IN_PRICING: &[(&str, ModelPricing)] = &[
+    // https://platform.openai.com/docs/pricing
+    ("gpt-5", ModelPricing::new(1.25, 0.125, 10.0)),
+    ("gpt-5-mini", ModelPricing::new(0.25, 0.025, 2.0)),
+    ("gpt-5-nano", ModelPricing::new(0.05, 0.005, 0.4)),
+    ("gpt-5-codex", ModelPricing::new(1.25, 0.125, 10.0)),
+    ("gpt-4.1", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("gpt-4.1-mini", ModelPricing::new(0.4, 0.1, 1.6)),
+    ("gpt-4.1-nano", ModelPricing::new(0.1, 0.025, 0.4)),
+    ("gpt-4o", ModelPricing::new(2.5, 1.25, 10.0)),
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.075, 0.6)),
+    ("o3", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("o3-mini", ModelPricing::new(1.1, 0.55, 4.4)),
+    ("o4-mini", ModelPricing::new(1.1, 0.275, 4.4)),
+    ("codex-mini-latest", ModelPricing::new(1.5, 0.375, 6.0)),
+    // https://docs.anthropic.com/en/docs/about-claude/pricing
+    ("claude-opus-4-1", ModelPricing::new(15.0, 1.5, 75.0)),
+    ("claude-opus-4", ModelPricing::new(15.0, 1.5, 75.0)),
+    ("claude-sonnet-4-5", ModelPricing::new(3.0, 0.3, 15.0)),
+    ("claude-sonnet-4", ModelPricing::new(3.0, 0.3, 15.0)),
+    ("claude-3-5-haiku", ModelPricing::new(0.8, 0.08, 4.0)),
+];
+
+/// Prices for `slug`: an exact entry in `model_pricing`, else the most
+/// specific glob pattern there that matches, else the built-in table.
+pub fn find_model_pricing(
+    slug: &str,
+    model_pricing: &HashMap<String, ModelPricing>,
+) -> Option<ModelPricing> {
+    if let Some(exact) = model_pricing.get(slug) {
+        return Some(*exact);
+    }
+    let configured = model_pricing
+        .iter()
+        .filter(|(pattern, _)| WildMatch::new(pattern).matches(slug))
+        .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+        .map(|(_, pricing)| *pricing);
+    configured.or_else(|| {
+        BUILT_IN_PRICING
+            .iter()
+            .find(|(model, _)| {
+                slug.strip_prefix(model)
+                    .is_some_and(|rest| rest.is_empty() || is_snapshot_suffix(rest))
+            })
+            .map(|(_, pricing)| *pricing)
+    })
+}
+
+/// `-2025-04-14` or `-20250514`: a dated snapshot of the same model, as
+/// opposed to a different model sharing its prefix, like `gpt-4.1-mini`.
+fn is_snapshot_suffix(rest: &str) -> bool {
+    let Some(date) = rest.strip_prefix('-') else {
+        return false;
+    };
+    let digits = date.chars().filter(char::is_ascii_digit).count();
+    digits == 8 && date.chars().all(|c| c.is_ascii_digit() || c == '-')
+}
+
+/// Formats an estimated cost for display, e.g. `$0.42`.
+pub fn format_usd(cost: f64) -> String {
+    if cost > 0.0 && cost < 0.01 {
+        "<$0.01".to_string()
+    } else {
+        format!("${cost:.2}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn usage(input: u64, cached: u64, output: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens: input,
+            cached_input_tokens: Some(cached),
+            output_tokens: output,
+            reasoning_output_tokens: None,
+            total_tokens: input + output,
+        }
+    }
+
+    #[test]
+    fn cached_input_is_priced_at_the_discounted_rate() {
+        let pricing = ModelPricing::new(2.0, 0.5, 8.0);
+        // 600k uncached input, 400k cached input, 100k output.
+        let cost = pricing.cost(&usage(1_000_000, 400_000, 100_000));
+        assert!((cost - (1.2 + 0.2 + 0.8)).abs() < 1e-9, "{cost}");
+
+        let no_discount = ModelPricing {
+            cached_input: None,
+            ..pricing
+        };
+        let cost = no_discount.cost(&usage(1_000_000, 400_000, 0));
+        assert!((cost - 2.0).abs() < 1e-9, "{cost}");
+    }
+
+    #[test]
+    fn pricing_lookup() {
+        let configured = HashMap::from([
+            ("gpt-5".to_string(), ModelPricing::new(1.0, 0.1, 8.0)),
+            (
+                "ft:gpt-4.1:*".to_string(),
+                ModelPricing::new(3.0, 0.75, 12.0),
+            ),
+        ]);
+        let cases = [
+            ("gpt-5", Some(ModelPricing::new(1.0, 0.1, 8.0))),
+            (
+                "ft:gpt-4.1:acme::abc123",
+                Some(ModelPricing::new(3.0, 0.75, 12.0)),
+            ),
+            ("gpt-4.1", Some(ModelPricing::new(2.0, 0.5, 8.0))),
+            ("gpt-4.1-2025-04-14", Some(ModelPricing::new(2.0, 0.5, 8.0))),
+            ("gpt-4.1-mini", Some(ModelPricing::new(0.4, 0.1, 1.6))),
+            (
+                "claude-sonnet-4-20250514",
+                Some(ModelPricing::new(3.0, 0.3, 15.0)),
+            ),
+            ("gpt-4.1-turbo", None),
+            ("llama3.2", None),
+        ];
+        for (slug, expected) in cases {
+            assert_eq!(find_model_pricing(slug, &configured), expected, "{slug}");
+        }
+    }
+
+    #[test]
+    fn usd_formatting() {
+        assert_eq!(format_usd(0.0), "$0.00");
+        assert_eq!(format_usd(0.004), "<$0.01");
+        assert_eq!(format_usd(1.234), "$1.23");
+    }
+}
@@ -7,6 +7,7 @@
 
 use codex_login::AuthMode;
 use codex_login::CodexAuth;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
@@ -14,9 +15,13 @@ use std::env::VarError;
 use std::time::Duration;
 
 use crate::error::EnvVarError;
+use crate::openai_tools::strip_unsupported_schema_keywords;
+use crate::util::backoff_with_base;
 const DEFAULT_STREAM_IDLE_TIMEOUT_MS: u64 = 300_000;
 const DEFAULT_STREAM_MAX_RETRIES: u64 = 5;
 const DEFAULT_REQUEST_MAX_RETRIES: u64 = 4;
+const DEFAULT_REQUEST_RETRY_BASE_DELAY_MS: u64 = 200;
+const DEFAULT_REQUEST_RETRY_MAX_DELAY_MS: u64 = 30_000;
 /// Hard cap for user-configured `stream_max_retries`.
 const MAX_STREAM_MAX_RETRIES: u64 = 100;
 /// Hard cap for user-configured `request_max_retries`.
@@ -25,22 +30,101 @@ const MAX_REQUEST_MAX_RETRIES: u64 = 100;
 /// Wire protocol that the provider speaks. Most third-party services only
 /// implement the classic OpenAI Chat Completions JSON schema, whereas OpenAI
 /// itself (and a handful of others) additionally expose the more modern
-/// *Responses* API. The two protocols use different request/response shapes
-/// and *cannot* be auto-detected at runtime, therefore each provider entry
-/// must declare which one it expects.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// *Responses* API. The two protocols use different request/response shapes.
+/// A provider entry that does not declare one is probed: the first request
+/// tries Responses and falls back to Chat Completions if the server has no
+/// such endpoint. The outcome is cached per provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum WireApi {
     /// The Responses API exposed by OpenAI at `/v1/responses`.
     Responses,
 
     /// Regular Chat Completions compatible with `/v1/chat/completions`.
-    #[default]
     Chat,
+
+    /// The Anthropic Messages API exposed at `/v1/messages`.
+    Anthropic,
+}
+
+/// Kind of server behind a provider, for behaviour beyond the wire protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI or a service that mirrors its API.
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+
+    /// A local Ollama server. Codex checks that the model has been pulled
+    /// before starting a session and offers to pull it if not.
+    Ollama,
+
+    /// Google's OpenAI-compatible Gemini endpoint, which only implements Chat
+    /// Completions and a subset of JSON Schema for tool parameters.
+    Gemini,
+}
+
+/// Fields of the Responses API that have no Chat Completions counterpart.
+const RESPONSES_ONLY_FIELDS: &[&str] = &[
+    "store",
+    "include",
+    "reasoning",
+    "text",
+    "prompt_cache_key",
+    "previous_response_id",
+];
+
+/// What a kind of provider accepts beyond the standard OpenAI request shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProviderCapabilities {
+    /// Top-level request fields the provider rejects, even as `null`.
+    pub unsupported_request_fields: &'static [&'static str],
+    /// JSON Schema keywords the provider rejects in tool parameters.
+    pub unsupported_schema_keywords: &'static [&'static str],
+    /// `format` values accepted on tool parameters, or `None` for any.
+    pub string_formats: Option<&'static [&'static str]>,
+    /// Whether the provider only implements Chat Completions.
+    pub chat_completions_only: bool,
+    /// Whether a stored response's event stream can be reopened with
+    /// `GET /responses/{id}?stream=true&starting_after=N` after the
+    /// connection drops.
+    pub resumable_streams: bool,
+}
+
+impl ProviderKind {
+    pub(crate) fn capabilities(self) -> ProviderCapabilities {
+        match self {
+            ProviderKind::OpenAi => ProviderCapabilities {
+                unsupported_request_fields: &[],
+                unsupported_schema_keywords: &[],
+                string_formats: None,
+                chat_completions_only: false,
+                resumable_streams: true,
+            },
+            // Ollama has no server-side storage or reasoning controls and
+            // older versions reject the fields.
+            ProviderKind::Ollama => ProviderCapabilities {
+                unsupported_request_fields: &["store", "include", "reasoning"],
+                unsupported_schema_keywords: &[],
+                string_formats: None,
+                chat_completions_only: false,
+                resumable_streams: false,
+            },
+            ProviderKind::Gemini => ProviderCapabilities {
+                unsupported_request_fields: &[],
+                unsupported_schema_keywords: &["default", "additionalProperties", "$schema"],
+                string_formats: Some(&["enum", "date-time"]),
+                chat_completions_only: true,
+                resumable_streams: false,
+            },
+        }
+    }
 }
 
 /// Serializable representation of a provider definition.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct ModelProviderInfo {
     /// Friendly display name.
     pub name: String,
@@ -53,9 +137,10 @@ pub struct ModelProviderInfo {
     /// variable and set it.
     pub env_key_instructions: Option<String>,
 
-    /// Which wire protocol this provider expects.
-    #[serde(default)]
-    pub wire_api: WireApi,
+    /// Which wire protocol this provider expects. When unset, it is probed
+    /// on the first request; see [`Self::probes_wire_api`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wire_api: Option<WireApi>,
 
     /// Optional query parameters to append to the base URL.
     pub query_params: Option<HashMap<String, String>>,
@@ -73,6 +158,18 @@ pub struct ModelProviderInfo {
     /// Maximum number of times to retry a failed HTTP request to this provider.
     pub request_max_retries: Option<u64>,
 
+    /// Delay (in milliseconds) before the first request retry. Subsequent
+    /// retries back off exponentially from this value.
+    pub request_retry_base_delay_ms: Option<u64>,
+
+    /// Upper bound (in milliseconds) for the computed backoff between request
+    /// retries. A `Retry-After` header sent by the server takes precedence.
+    pub request_retry_max_delay_ms: Option<u64>,
+
+    /// HTTP status codes that should be retried. Defaults to 429 and all 5xx
+    /// responses.
+    pub request_retry_on_status: Option<Vec<u16>>,
+
     /// Number of times to retry reconnecting a dropped streaming response before failing.
     pub stream_max_retries: Option<u64>,
 
@@ -80,16 +177,54 @@ pub struct ModelProviderInfo {
     /// the connection as lost.
     pub stream_idle_timeout_ms: Option<u64>,
 
+    /// Sampling temperature to send with every request (0.0 to 2.0). When
+    /// unset, the provider's default applies.
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling probability mass to send with every request (0.0 to
+    /// 1.0). When unset, the provider's default applies.
+    pub top_p: Option<f64>,
+
+    /// Seed for best-effort deterministic sampling. Only sent with Chat
+    /// Completions requests; the other APIs have no such parameter.
+    pub seed: Option<i64>,
+
+    /// Whether Chat Completions requests ask for a final stream chunk with
+    /// the token usage (`stream_options.include_usage`). Defaults to `true`;
+    /// turn it off for servers that reject the option.
+    pub stream_include_usage: Option<bool>,
+
+    /// Azure OpenAI `api-version`, sent as a query parameter on every request.
+    pub api_version: Option<String>,
+
+    /// Azure OpenAI deployment to route requests to. The deployment name goes
+    /// in the URL path (`{base_url}/deployments/{deployment}/...`) and the
+    /// `model` field is left out of the request body.
+    pub deployment: Option<String>,
+
+    /// Header that carries the API key as-is instead of the default
+    /// `Authorization: Bearer` scheme, e.g. `"api-key"` for Azure OpenAI.
+    pub auth_header: Option<String>,
+
+    /// Kind of server behind this provider (default: `openai`).
+    #[serde(default)]
+    pub kind: ProviderKind,
+
     /// Whether this provider requires some form of standard authentication (API key, ChatGPT token).
     #[serde(default)]
     pub requires_openai_auth: bool,
+
+    /// Key stored for this provider with `codex login --api-key --provider`,
+    /// filled in when the configuration is loaded.
+    #[serde(skip)]
+    pub stored_api_key: Option<String>,
 }
 
 impl ModelProviderInfo {
     /// Construct a `POST` RequestBuilder for the given URL using the provided
     /// reqwest Client applying:
     ///   • provider-specific headers (static + env based)
-    ///   • Bearer auth header when an API key is available.
+    ///   • Bearer auth header (or `auth_header`) when an API key is available.
     ///   • Auth token for OAuth.
     ///
     /// If the provider declares an `env_key` but the variable is missing/empty, returns an [`Err`] identical to the
@@ -99,44 +234,240 @@ impl ModelProviderInfo {
         client: &'a reqwest::Client,
         auth: &Option<CodexAuth>,
     ) -> crate::error::Result<reqwest::RequestBuilder> {
-        let effective_auth = match self.api_key() {
-            Ok(Some(key)) => Some(CodexAuth::from_api_key(&key)),
-            Ok(None) => auth.clone(),
+        let effective_auth = self.effective_auth(auth)?;
+        let url = self.get_full_url(&effective_auth);
+        self.authorize(client.post(url), &effective_auth).await
+    }
+
+    /// Construct a `GET` RequestBuilder that streams the events of the stored
+    /// Responses API response `response_id` again, starting after the event
+    /// with sequence number `starting_after`. Headers and auth are applied as
+    /// in [`ModelProviderInfo::create_request_builder`].
+    pub(crate) async fn create_resume_request_builder<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        auth: &Option<CodexAuth>,
+        response_id: &str,
+        starting_after: u64,
+    ) -> crate::error::Result<reqwest::RequestBuilder> {
+        let effective_auth = self.effective_auth(auth)?;
+        let url = self.get_response_url(
+            &effective_auth,
+            response_id,
+            "",
+            &format!("stream=true&starting_after={starting_after}"),
+        );
+        self.authorize(client.get(url), &effective_auth).await
+    }
+
+    /// Construct a `GET` RequestBuilder that fetches the stored Responses API
+    /// response `response_id`, e.g. to check on a background response.
+    pub(crate) async fn create_retrieve_request_builder<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        auth: &Option<CodexAuth>,
+        response_id: &str,
+    ) -> crate::error::Result<reqwest::RequestBuilder> {
+        let effective_auth = self.effective_auth(auth)?;
+        let url = self.get_response_url(&effective_auth, response_id, "", "");
+        self.authorize(client.get(url), &effective_auth).await
+    }
+
+    /// Construct a `POST` RequestBuilder that cancels the background
+    /// Responses API response `response_id`.
+    pub(crate) async fn create_cancel_request_builder<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        auth: &Option<CodexAuth>,
+        response_id: &str,
+    ) -> crate::error::Result<reqwest::RequestBuilder> {
+        let effective_auth = self.effective_auth(auth)?;
+        let url = self.get_response_url(&effective_auth, response_id, "/cancel", "");
+        self.authorize(client.post(url), &effective_auth).await
+    }
+
+    /// Checks `api_key` by listing the provider's models, a cheap request that
+    /// fails without a valid key, e.g. before `codex login` stores the key.
+    pub async fn check_api_key(&self, api_key: &str) -> crate::error::Result<()> {
+        let auth = Some(CodexAuth::from_api_key(api_key));
+        let url = format!(
+            "{}/models{}",
+            self.get_base_url(&auth),
+            self.get_query_string()
+        );
+        let response = self
+            .authorize(reqwest::Client::new().get(url), &auth)
+            .await?
+            .send()
+            .await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(crate::error::CodexErr::ProviderError {
+            status,
+            code: None,
+            message: response.text().await.unwrap_or_default(),
+        })
+    }
+
+    /// URL of the stored response `response_id`, followed by `path` and the
+    /// extra query parameters `query`.
+    fn get_response_url(
+        &self,
+        auth: &Option<CodexAuth>,
+        response_id: &str,
+        path: &str,
+        query: &str,
+    ) -> String {
+        let query_string = self.get_query_string();
+        let query = match (query_string.is_empty(), query.is_empty()) {
+            (_, true) => query_string,
+            (true, false) => format!("?{query}"),
+            (false, false) => format!("{query_string}&{query}"),
+        };
+        format!(
+            "{}/responses/{response_id}{path}{query}",
+            self.get_base_url(auth)
+        )
+    }
+
+    /// The auth to send: the provider's own API key when it has one,
+    /// otherwise `auth`.
+    fn effective_auth(&self, auth: &Option<CodexAuth>) -> crate::error::Result<Option<CodexAuth>> {
+        match self.api_key() {
+            Ok(Some(key)) => Ok(Some(CodexAuth::from_api_key(&key))),
+            Ok(None) => Ok(auth.clone()),
             Err(err) => {
                 if auth.is_some() {
-                    auth.clone()
+                    Ok(auth.clone())
                 } else {
-                    return Err(err);
+                    Err(err)
                 }
             }
-        };
-
-        let url = self.get_full_url(&effective_auth);
-
-        let mut builder = client.post(url);
+        }
+    }
 
+    async fn authorize(
+        &self,
+        mut builder: reqwest::RequestBuilder,
+        effective_auth: &Option<CodexAuth>,
+    ) -> crate::error::Result<reqwest::RequestBuilder> {
         if let Some(auth) = effective_auth.as_ref() {
-            builder = builder.bearer_auth(auth.get_token().await?);
+            let token = auth.get_token().await?;
+            builder = match (&self.auth_header, self.wire_api()) {
+                (Some(header), _) => builder.header(header, token),
+                // Anthropic authenticates API keys via `x-api-key` rather than
+                // a bearer token.
+                (None, WireApi::Anthropic) => builder.header("x-api-key", token),
+                (None, WireApi::Responses | WireApi::Chat) => builder.bearer_auth(token),
+            };
         }
 
         Ok(self.apply_http_headers(builder))
     }
 
     fn get_query_string(&self) -> String {
-        self.query_params
-            .as_ref()
-            .map_or_else(String::new, |params| {
-                let full_params = params
-                    .iter()
-                    .map(|(k, v)| format!("{k}={v}"))
-                    .collect::<Vec<_>>()
-                    .join("&");
-                format!("?{full_params}")
-            })
+        let mut params: Vec<String> = self
+            .query_params
+            .iter()
+            .flatten()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect();
+        if let Some(api_version) = &self.api_version {
+            params.push(format!("api-version={api_version}"));
+        }
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+
+    /// Adjusts a request body built for the standard OpenAI endpoints to this
+    /// provider. Deployment-routed providers take the model from the URL, so
+    /// the `model` field is dropped. Fields and tool schema keywords that the
+    /// provider's [`ProviderCapabilities`] rule out are left out.
+    pub(crate) fn shape_request_body(&self, payload: &mut serde_json::Value) {
+        let Some(obj) = payload.as_object_mut() else {
+            return;
+        };
+        if self.deployment.is_some() {
+            obj.remove("model");
+        }
+        let capabilities = self.kind.capabilities();
+        for field in capabilities.unsupported_request_fields {
+            obj.remove(*field);
+        }
+        if capabilities.chat_completions_only {
+            for field in RESPONSES_ONLY_FIELDS {
+                obj.remove(*field);
+            }
+        }
+        if let Some(tools) = obj.get_mut("tools").and_then(|t| t.as_array_mut()) {
+            for tool in tools {
+                // Chat Completions nests the function under `function`.
+                let pointer = if tool.get("function").is_some() {
+                    "/function/parameters"
+                } else {
+                    "/parameters"
+                };
+                if let Some(parameters) = tool.pointer_mut(pointer) {
+                    strip_unsupported_schema_keywords(parameters, &capabilities);
+                }
+            }
+        }
+    }
+
+    /// The wire API requests are sent with until probing says otherwise:
+    /// the configured one, else Chat Completions for providers that only
+    /// implement it, else Responses.
+    pub fn wire_api(&self) -> WireApi {
+        match self.wire_api {
+            Some(wire_api) => wire_api,
+            None if self.kind.capabilities().chat_completions_only => WireApi::Chat,
+            None => WireApi::Responses,
+        }
+    }
+
+    /// Whether the wire API is found out by trying Responses and falling back
+    /// to Chat Completions. Only custom servers are probed: an explicit
+    /// `wire_api` always wins, and the default OpenAI endpoint speaks
+    /// Responses.
+    pub fn probes_wire_api(&self) -> bool {
+        self.wire_api.is_none()
+            && self.base_url.is_some()
+            && !self.kind.capabilities().chat_completions_only
+    }
+
+    /// Checks that the wire API is one this kind of provider implements.
+    pub fn validate_wire_api(&self) -> Result<(), String> {
+        if self.kind.capabilities().chat_completions_only && self.wire_api() != WireApi::Chat {
+            return Err(format!(
+                "kind = \"{}\" only supports wire_api = \"chat\"",
+                serde_json::to_value(self.kind)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_default()
+            ));
+        }
+        Ok(())
     }
 
     pub(crate) fn get_full_url(&self, auth: &Option<CodexAuth>) -> String {
-        let default_base_url = if matches!(
+        let base_url = self.get_base_url(auth);
+        let query_string = self.get_query_string();
+        match self.wire_api() {
+            WireApi::Responses => format!("{base_url}/responses{query_string}"),
+            WireApi::Chat => format!("{base_url}/chat/completions{query_string}"),
+            WireApi::Anthropic => format!("{base_url}/messages{query_string}"),
+        }
+    }
+
+    fn get_base_url(&self, auth: &Option<CodexAuth>) -> String {
+        let default_base_url = if self.wire_api() == WireApi::Anthropic {
+            "https://api.anthropic.com/v1"
+        } else if matches!(
             auth,
             Some(CodexAuth {
                 mode: AuthMode::ChatGPT,
@@ -147,16 +478,14 @@ impl ModelProviderInfo {
         } else {
             "https://api.openai.com/v1"
         };
-        let query_string = self.get_query_string();
-        let base_url = self
+        let mut base_url = self
             .base_url
             .clone()
             .unwrap_or(default_base_url.to_string());
-
-        match self.wire_api {
-            WireApi::Responses => format!("{base_url}/responses{query_string}"),
-            WireApi::Chat => format!("{base_url}/chat/completions{query_string}"),
+        if let Some(deployment) = &self.deployment {
+            base_url = format!("{base_url}/deployments/{deployment}");
         }
+        base_url
     }
 
     /// Apply provider-specific HTTP headers (both static and environment-based)
@@ -181,10 +510,15 @@ impl ModelProviderInfo {
         builder
     }
 
-    /// If `env_key` is Some, returns the API key for this provider if present
-    /// (and non-empty) in the environment. If `env_key` is required but
-    /// cannot be found, returns an error.
+    /// The API key for this provider, in order of precedence:
+    /// 1. the key stored for it with `codex login --api-key --provider <id>`;
+    /// 2. the `env_key` variable, which is an error to leave unset or empty;
+    /// 3. none, and requests use the login from `auth.json` or the
+    ///    `OPENAI_API_KEY` variable (see [`CodexAuth::from_codex_home`]).
     pub fn api_key(&self) -> crate::error::Result<Option<String>> {
+        if let Some(key) = &self.stored_api_key {
+            return Ok(Some(key.clone()));
+        }
         match &self.env_key {
             Some(env_key) => {
                 let env_value = std::env::var(env_key);
@@ -214,6 +548,36 @@ impl ModelProviderInfo {
             .min(MAX_REQUEST_MAX_RETRIES)
     }
 
+    /// Whether a request that failed with `status` should be retried.
+    pub(crate) fn should_retry_status(&self, status: StatusCode) -> bool {
+        match &self.request_retry_on_status {
+            Some(statuses) => statuses.contains(&status.as_u16()),
+            None => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        }
+    }
+
+    /// Delay before retry number `attempt` (1-based): jittered exponential
+    /// backoff bounded by `request_retry_max_delay_ms`, unless the server
+    /// asked for a specific delay via `Retry-After`.
+    pub(crate) fn request_retry_delay(
+        &self,
+        attempt: u64,
+        retry_after: Option<Duration>,
+    ) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let base = Duration::from_millis(
+            self.request_retry_base_delay_ms
+                .unwrap_or(DEFAULT_REQUEST_RETRY_BASE_DELAY_MS),
+        );
+        let max = Duration::from_millis(
+            self.request_retry_max_delay_ms
+                .unwrap_or(DEFAULT_REQUEST_RETRY_MAX_DELAY_MS),
+        );
+        backoff_with_base(attempt, base, max)
+    }
+
     /// Effective maximum number of stream reconnection attempts for this provider.
     pub fn stream_max_retries(&self) -> u64 {
         self.stream_max_retries
@@ -221,6 +585,30 @@ impl ModelProviderInfo {
             .min(MAX_STREAM_MAX_RETRIES)
     }
 
+    /// Checks that the configured sampling parameters are within the ranges
+    /// accepted by the APIs, so a typo fails at startup instead of turning
+    /// every request into a 400.
+    pub fn validate_sampling_params(&self) -> Result<(), String> {
+        if let Some(temperature) = self.temperature
+            && !(0.0..=2.0).contains(&temperature)
+        {
+            return Err(format!(
+                "temperature must be between 0.0 and 2.0, got {temperature}"
+            ));
+        }
+        if let Some(top_p) = self.top_p
+            && !(0.0..=1.0).contains(&top_p)
+        {
+            return Err(format!("top_p must be between 0.0 and 1.0, got {top_p}"));
+        }
+        Ok(())
+    }
+
+    /// Whether Chat Completions streams are asked to report token usage.
+    pub(crate) fn stream_include_usage(&self) -> bool {
+        self.stream_include_usage.unwrap_or(true)
+    }
+
     /// Effective idle timeout for streaming responses.
     pub fn stream_idle_timeout(&self) -> Duration {
         self.stream_idle_timeout_ms
@@ -233,14 +621,18 @@ const DEFAULT_OLLAMA_PORT: u32 = 11434;
 
 pub const BUILT_IN_OSS_MODEL_PROVIDER_ID: &str = "oss";
 
+pub const BUILT_IN_GEMINI_MODEL_PROVIDER_ID: &str = "gemini";
+
 /// Built-in default provider list.
 pub fn built_in_model_providers() -> HashMap<String, ModelProviderInfo> {
     use ModelProviderInfo as P;
 
     // We do not want to be in the business of adjucating which third-party
     // providers are bundled with Codex CLI, so we only include the OpenAI and
-    // open source ("oss") providers by default. Users are encouraged to add to
-    // `model_providers` in config.toml to add their own providers.
+    // open source ("oss") providers by default, plus Gemini, whose
+    // OpenAI-compatible endpoint needs request fixups a user entry could not
+    // express. Users are encouraged to add to `model_providers` in
+    // config.toml to add their own providers.
     [
         (
             "openai",
@@ -256,7 +648,7 @@ pub fn built_in_model_providers() -> HashMap<String, ModelProviderInfo> {
                     .filter(|v| !v.trim().is_empty()),
                 env_key: None,
                 env_key_instructions: None,
-                wire_api: WireApi::Responses,
+                wire_api: Some(WireApi::Responses),
                 query_params: None,
                 http_headers: Some(
                     [("version".to_string(), env!("CARGO_PKG_VERSION").to_string())]
@@ -276,12 +668,25 @@ pub fn built_in_model_providers() -> HashMap<String, ModelProviderInfo> {
                 ),
                 // Use global defaults for retry/timeout unless overridden in config.toml.
                 request_max_retries: None,
+                request_retry_base_delay_ms: None,
+                request_retry_max_delay_ms: None,
+                request_retry_on_status: None,
                 stream_max_retries: None,
                 stream_idle_timeout_ms: None,
+                temperature: None,
+                top_p: None,
+                seed: None,
+                stream_include_usage: None,
+                api_version: None,
+                deployment: None,
+                auth_header: None,
+                kind: ProviderKind::OpenAi,
                 requires_openai_auth: true,
+                stored_api_key: None,
             },
         ),
         (BUILT_IN_OSS_MODEL_PROVIDER_ID, create_oss_provider()),
+        (BUILT_IN_GEMINI_MODEL_PROVIDER_ID, create_gemini_provider()),
     ]
     .into_iter()
     .map(|(k, v)| (k.to_string(), v))
@@ -315,14 +720,58 @@ pub fn create_oss_provider_with_base_url(base_url: &str) -> ModelProviderInfo {
         base_url: Some(base_url.into()),
         env_key: None,
         env_key_instructions: None,
-        wire_api: WireApi::Chat,
+        wire_api: Some(WireApi::Chat),
         query_params: None,
         http_headers: None,
         env_http_headers: None,
         request_max_retries: None,
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
         stream_max_retries: None,
         stream_idle_timeout_ms: None,
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::Ollama,
         requires_openai_auth: false,
+        stored_api_key: None,
+    }
+}
+
+/// Gemini through Google's OpenAI-compatible Chat Completions endpoint.
+pub fn create_gemini_provider() -> ModelProviderInfo {
+    ModelProviderInfo {
+        name: "Gemini".into(),
+        base_url: Some("https://generativelanguage.googleapis.com/v1beta/openai".into()),
+        env_key: Some("GEMINI_API_KEY".into()),
+        env_key_instructions: Some(
+            "Create an API key at https://aistudio.google.com/apikey".into(),
+        ),
+        wire_api: Some(WireApi::Chat),
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: None,
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
+        stream_max_retries: None,
+        stream_idle_timeout_ms: None,
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::Gemini,
+        requires_openai_auth: false,
+        stored_api_key: None,
     }
 }
 
@@ -342,14 +791,26 @@ base_url = "http://localhost:11434/v1"
             base_url: Some("http://localhost:11434/v1".into()),
             env_key: None,
             env_key_instructions: None,
-            wire_api: WireApi::Chat,
+            wire_api: None,
             query_params: None,
             http_headers: None,
             env_http_headers: None,
             request_max_retries: None,
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: None,
             stream_idle_timeout_ms: None,
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let provider: ModelProviderInfo = toml::from_str(azure_provider_toml).unwrap();
@@ -369,16 +830,28 @@ query_params = { api-version = "2025-04-01-preview" }
             base_url: Some("https://xxxxx.openai.azure.com/openai".into()),
             env_key: Some("AZURE_OPENAI_API_KEY".into()),
             env_key_instructions: None,
-            wire_api: WireApi::Chat,
+            wire_api: None,
             query_params: Some(maplit::hashmap! {
                 "api-version".to_string() => "2025-04-01-preview".to_string(),
             }),
             http_headers: None,
             env_http_headers: None,
             request_max_retries: None,
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: None,
             stream_idle_timeout_ms: None,
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let provider: ModelProviderInfo = toml::from_str(azure_provider_toml).unwrap();
@@ -399,7 +872,7 @@ env_http_headers = { "X-Example-Env-Header" = "EXAMPLE_ENV_VAR" }
             base_url: Some("https://example.com".into()),
             env_key: Some("API_KEY".into()),
             env_key_instructions: None,
-            wire_api: WireApi::Chat,
+            wire_api: None,
             query_params: None,
             http_headers: Some(maplit::hashmap! {
                 "X-Example-Header".to_string() => "example-value".to_string(),
@@ -408,12 +881,356 @@ env_http_headers = { "X-Example-Env-Header" = "EXAMPLE_ENV_VAR" }
                 "X-Example-Env-Header".to_string() => "EXAMPLE_ENV_VAR".to_string(),
             }),
             request_max_retries: None,
+            request_retry_base_delay_ms: None,
+            request_retry_max_delay_ms: None,
+            request_retry_on_status: None,
             stream_max_retries: None,
             stream_idle_timeout_ms: None,
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stream_include_usage: None,
+            api_version: None,
+            deployment: None,
+            auth_header: None,
+            kind: ProviderKind::OpenAi,
             requires_openai_auth: false,
+            stored_api_key: None,
         };
 
         let provider: ModelProviderInfo = toml::from_str(azure_provider_toml).unwrap();
         assert_eq!(expected_provider, provider);
     }
+
+    #[test]
+    fn test_only_custom_servers_without_wire_api_are_probed() {
+        let custom: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Local"
+base_url = "http://localhost:8000/v1"
+            "#,
+        )
+        .unwrap();
+        assert!(custom.probes_wire_api());
+        assert_eq!(custom.wire_api(), WireApi::Responses);
+
+        let explicit = ModelProviderInfo {
+            wire_api: Some(WireApi::Chat),
+            ..custom.clone()
+        };
+        assert!(!explicit.probes_wire_api());
+        assert_eq!(explicit.wire_api(), WireApi::Chat);
+
+        let default_endpoint = ModelProviderInfo {
+            base_url: None,
+            ..custom
+        };
+        assert!(!default_endpoint.probes_wire_api());
+    }
+
+    #[test]
+    fn test_deserialize_retry_policy() {
+        let provider_toml = r#"
+name = "Example"
+base_url = "https://example.com"
+request_max_retries = 6
+request_retry_base_delay_ms = 500
+request_retry_max_delay_ms = 10000
+request_retry_on_status = [429, 503]
+        "#;
+        let provider: ModelProviderInfo = toml::from_str(provider_toml).unwrap();
+        assert_eq!(provider.request_max_retries(), 6);
+        assert_eq!(provider.request_retry_on_status, Some(vec![429, 503]));
+
+        assert!(provider.should_retry_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(provider.should_retry_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!provider.should_retry_status(StatusCode::INTERNAL_SERVER_ERROR));
+
+        let first = provider.request_retry_delay(1, None);
+        assert!(first >= Duration::from_millis(450) && first <= Duration::from_millis(550));
+        let capped = provider.request_retry_delay(10, None);
+        assert!(capped <= Duration::from_millis(11_000));
+        assert_eq!(
+            provider.request_retry_delay(1, Some(Duration::from_secs(12))),
+            Duration::from_secs(12)
+        );
+    }
+
+    #[test]
+    fn test_default_retry_statuses() {
+        let provider = create_oss_provider_with_base_url("http://localhost:11434/v1");
+        assert!(provider.should_retry_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(provider.should_retry_status(StatusCode::BAD_GATEWAY));
+        assert!(!provider.should_retry_status(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_deserialize_anthropic_provider_toml() {
+        let anthropic_provider_toml = r#"
+name = "Anthropic"
+env_key = "ANTHROPIC_API_KEY"
+wire_api = "anthropic"
+        "#;
+        let provider: ModelProviderInfo = toml::from_str(anthropic_provider_toml).unwrap();
+        assert_eq!(provider.wire_api, Some(WireApi::Anthropic));
+        assert_eq!(
+            provider.get_full_url(&None),
+            "https://api.anthropic.com/v1/messages"
+        );
+    }
+
+    #[test]
+    fn test_azure_deployment_routing() {
+        let provider_toml = r#"
+name = "Azure"
+base_url = "https://foo.openai.azure.com/openai"
+env_key = "AZURE_OPENAI_API_KEY"
+wire_api = "chat"
+api_version = "2025-04-01-preview"
+deployment = "gpt-4o-prod"
+auth_header = "api-key"
+        "#;
+        let provider: ModelProviderInfo = toml::from_str(provider_toml).unwrap();
+        assert_eq!(
+            provider.get_full_url(&None),
+            "https://foo.openai.azure.com/openai/deployments/gpt-4o-prod/chat/completions?api-version=2025-04-01-preview"
+        );
+
+        let mut payload = serde_json::json!({"model": "gpt-4o", "stream": true});
+        provider.shape_request_body(&mut payload);
+        assert_eq!(payload, serde_json::json!({"stream": true}));
+    }
+
+    #[test]
+    fn test_stored_response_urls_keep_query_params() {
+        let provider_toml = r#"
+name = "Proxy"
+base_url = "https://proxy.example.com/v1"
+query_params = { team = "infra" }
+        "#;
+        let provider: ModelProviderInfo = toml::from_str(provider_toml).unwrap();
+        assert_eq!(
+            provider.get_response_url(&None, "resp_1", "/cancel", ""),
+            "https://proxy.example.com/v1/responses/resp_1/cancel?team=infra"
+        );
+        assert_eq!(
+            provider.get_response_url(&None, "resp_1", "", "stream=true&starting_after=7"),
+            "https://proxy.example.com/v1/responses/resp_1?team=infra&stream=true&starting_after=7"
+        );
+
+        let provider = create_oss_provider_with_base_url("http://localhost:11434/v1");
+        assert_eq!(
+            provider.get_response_url(&None, "resp_1", "", ""),
+            "http://localhost:11434/v1/responses/resp_1"
+        );
+    }
+
+    #[test]
+    fn test_ollama_requests_omit_unsupported_fields() {
+        let provider = create_oss_provider_with_base_url("http://localhost:11434/v1");
+        assert_eq!(provider.kind, ProviderKind::Ollama);
+
+        let mut payload = serde_json::json!({
+            "model": "gpt-oss:20b",
+            "store": false,
+            "include": [],
+            "reasoning": null,
+            "stream": true,
+        });
+        provider.shape_request_body(&mut payload);
+        assert_eq!(
+            payload,
+            serde_json::json!({"model": "gpt-oss:20b", "stream": true})
+        );
+
+        let provider: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Remote Ollama"
+base_url = "http://gpu-box:11434/v1"
+kind = "ollama"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(provider.kind, ProviderKind::Ollama);
+    }
+
+    #[test]
+    fn test_gemini_requests_drop_unsupported_fields_and_schema_keywords() {
+        let provider = create_gemini_provider();
+        assert_eq!(provider.validate_wire_api(), Ok(()));
+
+        let mut payload = serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "store": false,
+            "stream": true,
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "fetch",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "url": {"type": "string", "format": "uri"},
+                            "since": {"type": "string", "format": "date-time"},
+                            "retries": {
+                                "type": "number",
+                                "description": "Retry count",
+                                "default": 3
+                            },
+                            "headers": {
+                                "type": "array",
+                                "items": {"type": "string", "default": "Accept: */*"}
+                            }
+                        },
+                        "additionalProperties": false
+                    }
+                }
+            }]
+        });
+        provider.shape_request_body(&mut payload);
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "model": "gemini-2.5-pro",
+                "stream": true,
+                "tools": [{
+                    "type": "function",
+                    "function": {
+                        "name": "fetch",
+                        "parameters": {
+                            "type": "object",
+                            "properties": {
+                                "url": {"type": "string", "description": "(format: uri)"},
+                                "since": {"type": "string", "format": "date-time"},
+                                "retries": {
+                                    "type": "number",
+                                    "description": "Retry count (default: 3)"
+                                },
+                                "headers": {
+                                    "type": "array",
+                                    "items": {
+                                        "type": "string",
+                                        "description": "(default: \"Accept: */*\")"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }]
+            })
+        );
+
+        let provider: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Gemini"
+kind = "gemini"
+wire_api = "responses"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            provider.validate_wire_api(),
+            Err("kind = \"gemini\" only supports wire_api = \"chat\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_sampling_params() {
+        let provider_toml = r#"
+name = "Local"
+base_url = "http://localhost:8000/v1"
+temperature = 0.0
+top_p = 0.95
+seed = 42
+        "#;
+        let provider: ModelProviderInfo = toml::from_str(provider_toml).unwrap();
+        assert_eq!(provider.temperature, Some(0.0));
+        assert_eq!(provider.top_p, Some(0.95));
+        assert_eq!(provider.seed, Some(42));
+        assert_eq!(provider.validate_sampling_params(), Ok(()));
+    }
+
+    #[test]
+    fn test_stored_api_key_takes_precedence_over_env_key() {
+        // `PATH` is always set, so it stands in for a provider's key variable.
+        let mut provider: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Example"
+env_key = "PATH"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(provider.api_key().unwrap(), std::env::var("PATH").ok());
+
+        provider.stored_api_key = Some("stored-key".to_string());
+        assert_eq!(provider.api_key().unwrap(), Some("stored-key".to_string()));
+
+        // A stored key also stands in for a missing variable.
+        provider.env_key = Some("CODEX_TEST_UNSET_PROVIDER_KEY".to_string());
+        assert_eq!(provider.api_key().unwrap(), Some("stored-key".to_string()));
+        provider.stored_api_key = None;
+        assert!(provider.api_key().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_api_key_lists_models() {
+        use wiremock::Mock;
+        use wiremock::MockServer;
+        use wiremock::ResponseTemplate;
+        use wiremock::matchers::header;
+        use wiremock::matchers::method;
+        use wiremock::matchers::path;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(header("authorization", "Bearer good-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"data": []}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("invalid key"))
+            .mount(&server)
+            .await;
+
+        let provider = create_oss_provider_with_base_url(&format!("{}/v1", server.uri()));
+        provider.check_api_key("good-key").await.unwrap();
+        let err = provider.check_api_key("bad-key").await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                crate::error::CodexErr::ProviderError { status, .. }
+                    if status == reqwest::StatusCode::UNAUTHORIZED
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_sampling_params_are_rejected() {
+        let provider: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Local"
+temperature = 2.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            provider.validate_sampling_params(),
+            Err("temperature must be between 0.0 and 2.0, got 2.5".to_string())
+        );
+
+        let provider: ModelProviderInfo = toml::from_str(
+            r#"
+name = "Local"
+top_p = -0.1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            provider.validate_sampling_params(),
+            Err("top_p must be between 0.0 and 1.0, got -0.1".to_string())
+        );
+    }
 }
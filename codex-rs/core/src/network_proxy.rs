@@ -0,0 +1,475 @@
+//! Local HTTP proxy that lets sandboxed commands reach only an allowlist of
+//! domains.
+//!
+//! When `SandboxPolicy::WorkspaceWrite` lists `allowed_domains`, sandboxed
+//! commands get `HTTP_PROXY`/`HTTPS_PROXY` pointing at this proxy, and the
+//! sandbox makes it the only way out: Seatbelt only permits connections to
+//! its loopback address, and the Linux sandbox runs the command in a network
+//! namespace of its own where that address leads to the proxy's UNIX socket.
+//! The proxy tunnels `CONNECT host:port` requests and forwards absolute-URI
+//! (plain HTTP) requests when `host` is allowed, and answers `403 Forbidden`
+//! otherwise. Tools that ignore the proxy variables cannot reach the network
+//! at all.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::debug;
+use tracing::warn;
+
+use crate::spawn::CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR;
+use crate::spawn::CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR;
+
+/// Upper bound on the size of a request line plus headers.
+const MAX_REQUEST_HEAD_BYTES: usize = 16 * 1024;
+
+/// Variables read by curl, git, cargo, npm, pip and most HTTP libraries.
+const PROXY_ENV_VARS: [&str; 6] = [
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "ALL_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "all_proxy",
+];
+
+pub(crate) struct NetworkProxy {
+    addr: SocketAddr,
+    allowed_domains: Vec<String>,
+    tasks: Vec<JoinHandle<()>>,
+    /// Directory of the UNIX socket the proxy also listens on, which the
+    /// Linux sandbox bridges to from the command's network namespace.
+    #[cfg(unix)]
+    socket_dir: tempfile::TempDir,
+}
+
+impl NetworkProxy {
+    /// Binds to an ephemeral port on the loopback interface, and on UNIX to a
+    /// socket in a new private directory, and serves connections in the
+    /// background until dropped. Must be called from within a Tokio runtime.
+    pub(crate) fn start(allowed_domains: Vec<String>) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let addr = listener.local_addr()?;
+
+        let allowlist = Arc::new(allowed_domains.clone());
+        let mut tasks = Vec::new();
+        let tcp_allowlist = Arc::clone(&allowlist);
+        tasks.push(tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => serve_connection(stream, &tcp_allowlist),
+                    Err(e) => warn!("network proxy failed to accept a connection: {e}"),
+                }
+            }
+        }));
+
+        #[cfg(unix)]
+        let socket_dir = {
+            let socket_dir = tempfile::Builder::new().prefix("codex-proxy-").tempdir()?;
+            let listener = tokio::net::UnixListener::bind(socket_dir.path().join(SOCKET_NAME))?;
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => serve_connection(stream, &allowlist),
+                        Err(e) => warn!("network proxy failed to accept a connection: {e}"),
+                    }
+                }
+            }));
+            socket_dir
+        };
+
+        Ok(Self {
+            addr,
+            allowed_domains,
+            tasks,
+            #[cfg(unix)]
+            socket_dir,
+        })
+    }
+
+    pub(crate) fn allowed_domains(&self) -> &[String] {
+        &self.allowed_domains
+    }
+
+    /// Environment variables that route proxy-aware tools through this proxy
+    /// and tell the sandbox which port to allow.
+    pub(crate) fn env_vars(&self) -> Vec<(String, String)> {
+        let url = format!("http://{}", self.addr);
+        let mut vars: Vec<(String, String)> = PROXY_ENV_VARS
+            .iter()
+            .map(|key| (key.to_string(), url.clone()))
+            .collect();
+        // An inherited NO_PROXY would make tools bypass the proxy, which the
+        // sandbox blocks anyway.
+        vars.push(("NO_PROXY".to_string(), String::new()));
+        vars.push(("no_proxy".to_string(), String::new()));
+        vars.push((
+            CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR.to_string(),
+            self.addr.to_string(),
+        ));
+        #[cfg(unix)]
+        vars.push((
+            CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR.to_string(),
+            self.socket_dir
+                .path()
+                .join(SOCKET_NAME)
+                .to_string_lossy()
+                .into_owned(),
+        ));
+        vars
+    }
+}
+
+impl Drop for NetworkProxy {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(unix)]
+const SOCKET_NAME: &str = "proxy.sock";
+
+fn serve_connection<S>(stream: S, allowlist: &Arc<Vec<String>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let allowlist = Arc::clone(allowlist);
+    tokio::spawn(async move {
+        if let Err(e) = handle_connection(stream, &allowlist).await {
+            debug!("network proxy connection failed: {e}");
+        }
+    });
+}
+
+/// Port of the network proxy a sandboxed command was started with, if any.
+pub(crate) fn network_proxy_port(env: &HashMap<String, String>) -> Option<u16> {
+    env.get(CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR)?
+        .parse::<SocketAddr>()
+        .ok()
+        .map(|addr| addr.port())
+}
+
+/// Whether `host` is one of `allowed_domains` or a subdomain of one. A
+/// leading `*.` on an entry is ignored: `*.example.com` and `example.com`
+/// both allow `example.com` and `api.example.com`.
+pub(crate) fn is_host_allowed(host: &str, allowed_domains: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    allowed_domains.iter().any(|domain| {
+        let domain = domain
+            .trim_start_matches("*.")
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        !domain.is_empty()
+            && (host == domain
+                || host
+                    .strip_suffix(&domain)
+                    .is_some_and(|prefix| prefix.ends_with('.')))
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct ProxyRequest {
+    host: String,
+    port: u16,
+    /// For plain HTTP, the request head rewritten to origin form to send
+    /// upstream. `None` for `CONNECT`.
+    forward_head: Option<Vec<u8>>,
+}
+
+async fn handle_connection<S>(mut client: S, allowed_domains: &[String]) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Some((head, rest)) = read_request_head(&mut client).await? else {
+        return Ok(());
+    };
+    let Some(request) = parse_request(&head) else {
+        return respond(&mut client, "400 Bad Request", "malformed proxy request").await;
+    };
+    if !is_host_allowed(&request.host, allowed_domains) {
+        let message = format!("{} is not in the sandbox network allowlist", request.host);
+        return respond(&mut client, "403 Forbidden", &message).await;
+    }
+
+    let mut upstream = match TcpStream::connect((request.host.as_str(), request.port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            let message = format!("failed to connect to {}: {e}", request.host);
+            return respond(&mut client, "502 Bad Gateway", &message).await;
+        }
+    };
+    match &request.forward_head {
+        Some(forward_head) => upstream.write_all(forward_head).await?,
+        None => {
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?
+        }
+    }
+    upstream.write_all(&rest).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Reads until the blank line that ends the request head. Returns the head
+/// and whatever the client already sent after it, or `None` if the client
+/// closed the connection before sending anything.
+async fn read_request_head<S>(stream: &mut S) -> std::io::Result<Option<(Vec<u8>, Vec<u8>)>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return if buf.is_empty() {
+                Ok(None)
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "connection closed in request head",
+                ))
+            };
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok(Some((buf, rest)));
+        }
+        if buf.len() > MAX_REQUEST_HEAD_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+    }
+}
+
+fn parse_request(head: &[u8]) -> Option<ProxyRequest> {
+    let head = std::str::from_utf8(head).ok()?;
+    let (request_line, headers) = head.split_once("\r\n")?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let version = parts.next()?;
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_authority(target, 443)?;
+        return Some(ProxyRequest {
+            host,
+            port,
+            forward_head: None,
+        });
+    }
+
+    // Plain HTTP requests to a proxy use the absolute form,
+    // `GET http://host/path HTTP/1.1`.
+    let scheme_end = "http://".len();
+    if !target.get(..scheme_end)?.eq_ignore_ascii_case("http://") {
+        return None;
+    }
+    let after_scheme = &target[scheme_end..];
+    let path_start = after_scheme.find(['/', '?']).unwrap_or(after_scheme.len());
+    let (authority, path) = after_scheme.split_at(path_start);
+    let (host, port) = split_authority(authority, 80)?;
+    let path = match path {
+        "" => "/".to_string(),
+        path if path.starts_with('?') => format!("/{path}"),
+        path => path.to_string(),
+    };
+
+    let mut forward_head = format!("{method} {path} {version}\r\n");
+    for line in headers.split("\r\n") {
+        if line.to_ascii_lowercase().starts_with("proxy-") {
+            continue;
+        }
+        forward_head.push_str(line);
+        forward_head.push_str("\r\n");
+    }
+    // `headers` ends with the empty line, so the head is already terminated.
+    forward_head.truncate(forward_head.len() - 2);
+
+    Some(ProxyRequest {
+        host,
+        port,
+        forward_head: Some(forward_head.into_bytes()),
+    })
+}
+
+/// Splits `host[:port]`, including bracketed IPv6 literals, dropping any
+/// `user@` prefix.
+fn split_authority(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None if after.is_empty() => default_port,
+            None => return None,
+        };
+        (host, port)
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, default_port),
+        }
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+async fn respond<S>(stream: &mut S, status: &str, message: &str) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let body = format!("{message}\n");
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn domains(list: &[&str]) -> Vec<String> {
+        list.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn allows_listed_domains_and_their_subdomains() {
+        let allowed = domains(&["crates.io", "*.github.com", "Registry.internal."]);
+        assert!(is_host_allowed("crates.io", &allowed));
+        assert!(is_host_allowed("static.crates.io", &allowed));
+        assert!(is_host_allowed("github.com", &allowed));
+        assert!(is_host_allowed("api.GitHub.com", &allowed));
+        assert!(is_host_allowed("registry.internal", &allowed));
+        assert!(!is_host_allowed("evilcrates.io", &allowed));
+        assert!(!is_host_allowed("crates.io.evil.com", &allowed));
+        assert!(!is_host_allowed("example.com", &allowed));
+    }
+
+    #[test]
+    fn parses_connect_request() {
+        let request =
+            parse_request(b"CONNECT crates.io:443 HTTP/1.1\r\nHost: crates.io:443\r\n\r\n");
+        assert_eq!(
+            request,
+            Some(ProxyRequest {
+                host: "crates.io".to_string(),
+                port: 443,
+                forward_head: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rewrites_absolute_uri_to_origin_form() {
+        let request = parse_request(
+            b"GET http://example.com:8080/index.html?q=1 HTTP/1.1\r\nHost: example.com:8080\r\nProxy-Connection: keep-alive\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.host, "example.com");
+        assert_eq!(request.port, 8080);
+        assert_eq!(
+            String::from_utf8(request.forward_head.unwrap()).unwrap(),
+            "GET /index.html?q=1 HTTP/1.1\r\nHost: example.com:8080\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn rejects_origin_form_requests() {
+        assert_eq!(parse_request(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn splits_ipv6_authority() {
+        assert_eq!(
+            split_authority("[::1]:8443", 443),
+            Some(("::1".to_string(), 8443))
+        );
+        assert_eq!(
+            split_authority("[::1]", 443),
+            Some(("::1".to_string(), 443))
+        );
+        assert_eq!(split_authority(":443", 443), None);
+    }
+
+    #[tokio::test]
+    async fn tunnels_allowed_hosts_and_blocks_others() {
+        let upstream = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let upstream_port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            stream.write_all(b"hello from upstream").await.unwrap();
+        });
+
+        let proxy = NetworkProxy::start(domains(&["127.0.0.1"])).unwrap();
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(format!("CONNECT 127.0.0.1:{upstream_port} HTTP/1.1\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 Connection Established\r\n\r\nhello from upstream"
+        );
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        assert!(response.ends_with("example.com is not in the sandbox network allowlist\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_the_unix_socket_too() {
+        let proxy = NetworkProxy::start(domains(&["crates.io"])).unwrap();
+        let env: HashMap<String, String> = proxy.env_vars().into_iter().collect();
+        let mut client =
+            tokio::net::UnixStream::connect(&env[CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR])
+                .await
+                .unwrap();
+        client
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+
+    #[tokio::test]
+    async fn env_vars_point_at_proxy() {
+        let proxy = NetworkProxy::start(domains(&["crates.io"])).unwrap();
+        let env: HashMap<String, String> = proxy.env_vars().into_iter().collect();
+        assert_eq!(env["HTTPS_PROXY"], format!("http://{}", proxy.addr));
+        assert_eq!(network_proxy_port(&env), Some(proxy.addr.port()));
+    }
+}
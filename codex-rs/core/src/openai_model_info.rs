@@ -1,10 +1,7 @@
 use crate::model_family::ModelFamily;
 
-/// Metadata about a model, particularly OpenAI models.
-/// We may want to consider including details like the pricing for
-/// input tokens, output tokens, etc., though users will need to be able to
-/// override this in config.toml, as this information can get out of date.
-/// Though this would help present more accurate pricing information in the UI.
+/// Metadata about a model, particularly OpenAI models. Prices are kept in
+/// [`crate::model_pricing`], where config.toml can override them.
 #[derive(Debug)]
 pub(crate) struct ModelInfo {
     /// Size of the context window in tokens.
@@ -5,7 +5,9 @@ use serde_json::json;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 
+use crate::config_types::WebSearchContextSize;
 use crate::model_family::ModelFamily;
+use crate::model_provider_info::ProviderCapabilities;
 use crate::plan_tool::PLAN_TOOL;
 use crate::protocol::AskForApproval;
 use crate::protocol::SandboxPolicy;
@@ -47,10 +49,13 @@ pub(crate) enum OpenAiTool {
     Function(ResponsesApiTool),
     #[serde(rename = "local_shell")]
     LocalShell {},
-    // TODO: Understand why we get an error on web_search although the API docs say it's supported.
-    // https://platform.openai.com/docs/guides/tools-web-search?api-mode=responses#:~:text=%7B%20type%3A%20%22web_search%22%20%7D%2C
-    #[serde(rename = "web_search_preview")]
-    WebSearch {},
+    /// Built-in web search tool. Results stream back as `web_search_call`
+    /// output items.
+    #[serde(rename = "web_search")]
+    WebSearch {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        search_context_size: Option<WebSearchContextSize>,
+    },
     #[serde(rename = "custom")]
     Freeform(FreeformTool),
 }
@@ -69,6 +74,7 @@ pub(crate) struct ToolsConfig {
     pub plan_tool: bool,
     pub apply_patch_tool_type: Option<ApplyPatchToolType>,
     pub web_search_request: bool,
+    pub web_search_context_size: Option<WebSearchContextSize>,
     pub include_view_image_tool: bool,
 }
 
@@ -79,6 +85,7 @@ pub(crate) struct ToolsConfigParams<'a> {
     pub(crate) include_plan_tool: bool,
     pub(crate) include_apply_patch_tool: bool,
     pub(crate) include_web_search_request: bool,
+    pub(crate) web_search_context_size: Option<WebSearchContextSize>,
     pub(crate) use_streamable_shell_tool: bool,
     pub(crate) include_view_image_tool: bool,
 }
@@ -92,6 +99,7 @@ impl ToolsConfig {
             include_plan_tool,
             include_apply_patch_tool,
             include_web_search_request,
+            web_search_context_size,
             use_streamable_shell_tool,
             include_view_image_tool,
         } = params;
@@ -125,7 +133,8 @@ impl ToolsConfig {
             plan_tool: *include_plan_tool,
             apply_patch_tool_type,
             web_search_request: *include_web_search_request,
-            include_view_image_tool: *include_view_image_tool,
+            web_search_context_size: *web_search_context_size,
+            include_view_image_tool: *include_view_image_tool && model_family.supports_image_input,
         }
     }
 }
@@ -187,6 +196,12 @@ fn create_shell_tool() -> OpenAiTool {
             description: Some("The timeout for the command in milliseconds".to_string()),
         },
     );
+    properties.insert(
+        "tty".to_string(),
+        JsonSchema::Boolean {
+            description: Some("Run the command in a pseudo-terminal, for tools that only produce colored or complete output when attached to a terminal. The command still cannot read interactive input.".to_string()),
+        },
+    );
 
     OpenAiTool::Function(ResponsesApiTool {
         name: "shell".to_string(),
@@ -221,6 +236,12 @@ fn create_shell_tool_for_sandbox(sandbox_policy: &SandboxPolicy) -> OpenAiTool {
             description: Some("The timeout for the command in milliseconds".to_string()),
         },
     );
+    properties.insert(
+        "tty".to_string(),
+        JsonSchema::Boolean {
+            description: Some("Run the command in a pseudo-terminal, for tools that only produce colored or complete output when attached to a terminal. The command still cannot read interactive input.".to_string()),
+        },
+    );
 
     if matches!(sandbox_policy, SandboxPolicy::WorkspaceWrite { .. }) {
         properties.insert(
@@ -240,6 +261,7 @@ fn create_shell_tool_for_sandbox(sandbox_policy: &SandboxPolicy) -> OpenAiTool {
     let description = match sandbox_policy {
         SandboxPolicy::WorkspaceWrite {
             network_access,
+            allowed_domains,
             ..
         } => {
             format!(
@@ -257,10 +279,15 @@ The shell tool is used to execute shell commands.
 - When invoking a command that will require escalated privileges:
   - Provide the with_escalated_permissions parameter with the boolean value true
   - Include a short, 1 sentence explanation for why we need to run with_escalated_permissions in the justification parameter."#,
-                if !network_access {
-                    "\n  - Commands that require network access\n"
+                if *network_access {
+                    String::new()
+                } else if allowed_domains.is_empty() {
+                    "\n  - Commands that require network access\n".to_string()
                 } else {
-                    ""
+                    format!(
+                        "\n  - Commands that require network access to hosts other than {}\n",
+                        allowed_domains.join(", ")
+                    )
                 }
             )
         }
@@ -305,14 +332,17 @@ fn create_view_image_tool() -> OpenAiTool {
     properties.insert(
         "path".to_string(),
         JsonSchema::String {
-            description: Some("Local filesystem path to an image file".to_string()),
+            description: Some(
+                "Path to a PNG, JPEG, GIF or WebP file in the workspace or a writable root"
+                    .to_string(),
+            ),
         },
     );
 
     OpenAiTool::Function(ResponsesApiTool {
         name: "view_image".to_string(),
         description:
-            "Attach a local image (by filesystem path) to the conversation context for this turn."
+            "Look at a local image, such as a screenshot or a plot. The image is attached to the conversation after this call."
                 .to_string(),
         strict: false,
         parameters: JsonSchema::Object {
@@ -374,6 +404,33 @@ pub(crate) fn create_tools_json_for_chat_completions_api(
     Ok(tools_json)
 }
 
+/// Returns JSON values that are compatible with tool use in the Anthropic
+/// Messages API:
+/// https://docs.anthropic.com/en/docs/agents-and-tools/tool-use/overview
+///
+/// Only function tools are supported; built-in Responses API tools such as
+/// `local_shell`, `web_search` and freeform tools are dropped.
+pub(crate) fn create_tools_json_for_anthropic_api(
+    tools: &Vec<OpenAiTool>,
+) -> crate::error::Result<Vec<serde_json::Value>> {
+    let responses_api_tools_json = create_tools_json_for_responses_api(tools)?;
+    let tools_json = responses_api_tools_json
+        .into_iter()
+        .filter_map(|tool| {
+            if tool.get("type") != Some(&serde_json::Value::String("function".to_string())) {
+                return None;
+            }
+
+            Some(json!({
+                "name": tool.get("name")?,
+                "description": tool.get("description")?,
+                "input_schema": tool.get("parameters")?,
+            }))
+        })
+        .collect::<Vec<serde_json::Value>>();
+    Ok(tools_json)
+}
+
 pub(crate) fn mcp_tool_to_openai_tool(
     fully_qualified_name: String,
     tool: mcp_types::Tool,
@@ -416,7 +473,7 @@ pub(crate) fn mcp_tool_to_openai_tool(
 ///   and otherwise defaults to "string".
 /// - Fills required child fields (e.g. array items, object properties) with
 ///   permissive defaults when absent.
-fn sanitize_json_schema(value: &mut JsonValue) {
+pub(crate) fn sanitize_json_schema(value: &mut JsonValue) {
     match value {
         JsonValue::Bool(_) => {
             // JSON Schema boolean form: true/false. Coerce to an accept-all string.
@@ -523,6 +580,63 @@ fn sanitize_json_schema(value: &mut JsonValue) {
     }
 }
 
+/// Removes the JSON Schema keywords a provider rejects from a tool's
+/// parameter schema. A removed `default`, or a string `format` the provider
+/// does not support, is kept as a note in the description so the model still
+/// sees it.
+pub(crate) fn strip_unsupported_schema_keywords(
+    schema: &mut JsonValue,
+    capabilities: &ProviderCapabilities,
+) {
+    let map = match schema {
+        JsonValue::Array(arr) => {
+            for v in arr.iter_mut() {
+                strip_unsupported_schema_keywords(v, capabilities);
+            }
+            return;
+        }
+        JsonValue::Object(map) => map,
+        _ => return,
+    };
+    if let Some(props) = map.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        for v in props.values_mut() {
+            strip_unsupported_schema_keywords(v, capabilities);
+        }
+    }
+    for nested in ["items", "oneOf", "anyOf", "allOf", "prefixItems"] {
+        if let Some(v) = map.get_mut(nested) {
+            strip_unsupported_schema_keywords(v, capabilities);
+        }
+    }
+
+    let mut notes = Vec::new();
+    if capabilities
+        .unsupported_schema_keywords
+        .contains(&"default")
+        && let Some(default) = map.remove("default")
+    {
+        notes.push(format!("default: {default}"));
+    }
+    if let Some(formats) = capabilities.string_formats
+        && let Some(format) = map.get("format").and_then(|f| f.as_str())
+        && !formats.contains(&format)
+    {
+        notes.push(format!("format: {format}"));
+        map.remove("format");
+    }
+    for keyword in capabilities.unsupported_schema_keywords {
+        map.remove(*keyword);
+    }
+    if !notes.is_empty() {
+        let notes = notes.join(", ");
+        let description = match map.get("description").and_then(|d| d.as_str()) {
+            Some(description) => format!("{description} ({notes})"),
+            None => format!("({notes})"),
+        };
+        map.insert("description".to_string(), JsonValue::String(description));
+    }
+}
+
 /// Returns a list of OpenAiTools based on the provided config and MCP tools.
 /// Note that the keys of mcp_tools should be fully qualified names. See
 /// [`McpConnectionManager`] for more details.
@@ -568,7 +682,9 @@ pub(crate) fn get_openai_tools(
     }
 
     if config.web_search_request {
-        tools.push(OpenAiTool::WebSearch {});
+        tools.push(OpenAiTool::WebSearch {
+            search_context_size: config.web_search_context_size,
+        });
     }
 
     // Include the view_image tool so the agent can attach images to context.
@@ -609,7 +725,7 @@ mod tests {
             .map(|tool| match tool {
                 OpenAiTool::Function(ResponsesApiTool { name, .. }) => name,
                 OpenAiTool::LocalShell {} => "local_shell",
-                OpenAiTool::WebSearch {} => "web_search",
+                OpenAiTool::WebSearch { .. } => "web_search",
                 OpenAiTool::Freeform(FreeformTool { name, .. }) => name,
             })
             .collect::<Vec<_>>();
@@ -638,6 +754,7 @@ mod tests {
             include_plan_tool: true,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -659,6 +776,7 @@ mod tests {
             include_plan_tool: true,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -670,6 +788,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn view_image_requires_image_input() {
+        let model_family =
+            find_family_for_model("gpt-oss-20b").expect("gpt-oss should be a valid model family");
+        let config = ToolsConfig::new(&ToolsConfigParams {
+            model_family: &model_family,
+            approval_policy: AskForApproval::Never,
+            sandbox_policy: SandboxPolicy::ReadOnly,
+            include_plan_tool: false,
+            include_apply_patch_tool: false,
+            include_web_search_request: false,
+            web_search_context_size: None,
+            use_streamable_shell_tool: false,
+            include_view_image_tool: true,
+        });
+        let tools = get_openai_tools(&config, Some(HashMap::new()));
+
+        assert_eq_tool_names(&tools, &["shell", "apply_patch"]);
+    }
+
     #[test]
     fn test_get_openai_tools_mcp_tools() {
         let model_family = find_family_for_model("o3").expect("o3 should be a valid model family");
@@ -680,6 +818,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -785,6 +924,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: false,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -862,6 +1002,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -924,6 +1065,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -981,6 +1123,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -1038,6 +1181,7 @@ mod tests {
             include_plan_tool: false,
             include_apply_patch_tool: false,
             include_web_search_request: true,
+            web_search_context_size: None,
             use_streamable_shell_tool: false,
             include_view_image_tool: true,
         });
@@ -1081,4 +1225,76 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn anthropic_tools_json_uses_input_schema() {
+        let tools = vec![
+            create_shell_tool(),
+            OpenAiTool::WebSearch {
+                search_context_size: None,
+            },
+            OpenAiTool::LocalShell {},
+        ];
+
+        let tools_json = create_tools_json_for_anthropic_api(&tools).unwrap();
+
+        assert_eq!(
+            tools_json,
+            vec![serde_json::json!({
+                "name": "shell",
+                "description": "Runs a shell command and returns its output",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {
+                        "command": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "The command to execute",
+                        },
+                        "timeout_ms": {
+                            "type": "number",
+                            "description": "The timeout for the command in milliseconds",
+                        },
+                        "tty": {
+                            "type": "boolean",
+                            "description": "Run the command in a pseudo-terminal, for tools that only produce colored or complete output when attached to a terminal. The command still cannot read interactive input.",
+                        },
+                        "workdir": {
+                            "type": "string",
+                            "description": "The working directory to execute the command in",
+                        },
+                    },
+                    "required": ["command"],
+                    "additionalProperties": false,
+                },
+            })]
+        );
+    }
+
+    #[test]
+    fn web_search_tool_serializes_as_built_in_tool() {
+        let tools = vec![
+            OpenAiTool::WebSearch {
+                search_context_size: None,
+            },
+            OpenAiTool::WebSearch {
+                search_context_size: Some(WebSearchContextSize::High),
+            },
+        ];
+
+        let tools_json = create_tools_json_for_responses_api(&tools).unwrap();
+
+        assert_eq!(
+            tools_json,
+            vec![
+                serde_json::json!({ "type": "web_search" }),
+                serde_json::json!({ "type": "web_search", "search_context_size": "high" }),
+            ]
+        );
+        assert!(
+            create_tools_json_for_chat_completions_api(&tools)
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
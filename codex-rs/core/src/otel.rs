@@ -0,0 +1,233 @@
+//! Tracing spans for turns, model requests, commands and MCP tool calls.
+//!
+//! The spans are emitted under the [`TARGET`] target, so they cost next to
+//! nothing unless a subscriber enables it and do not show up in the regular
+//! log files. With the `otel` feature, [`layer`] builds a subscriber layer
+//! that exports them to the OTLP collector configured under `[otel]`.
+//!
+//! Prompt, command and output text is only attached to spans when
+//! `otel.include_content` is set; otherwise commands are identified by a hash
+//! of their argv.
+
+use std::time::Duration;
+
+use tracing::Span;
+use tracing::field::Empty;
+use tracing::info_span;
+use uuid::Uuid;
+
+use crate::exec::ExecToolCallOutput;
+use crate::protocol::TokenUsage;
+
+/// Target of every span in this module.
+pub const TARGET: &str = "codex_otel";
+
+pub(crate) fn turn_span(session_id: Uuid, turn_index: u64, model: &str, provider: &str) -> Span {
+    info_span!(
+        target: TARGET,
+        "turn",
+        session_id = %session_id,
+        turn_index,
+        model,
+        provider,
+        input_tokens = Empty,
+        cached_input_tokens = Empty,
+        output_tokens = Empty,
+        reasoning_output_tokens = Empty,
+        total_tokens = Empty,
+        output = Empty,
+    )
+}
+
+pub(crate) fn record_token_usage(span: &Span, usage: &TokenUsage) {
+    span.record("input_tokens", usage.input_tokens);
+    if let Some(cached_input_tokens) = usage.cached_input_tokens {
+        span.record("cached_input_tokens", cached_input_tokens);
+    }
+    span.record("output_tokens", usage.output_tokens);
+    if let Some(reasoning_output_tokens) = usage.reasoning_output_tokens {
+        span.record("reasoning_output_tokens", reasoning_output_tokens);
+    }
+    span.record("total_tokens", usage.total_tokens);
+}
+
+/// One attempt at sending a request to the model provider. `attempt` starts
+/// at 1.
+pub(crate) fn http_request_span(attempt: u64) -> Span {
+    info_span!(
+        target: TARGET,
+        "http_request",
+        retry_count = attempt.saturating_sub(1),
+        status = Empty,
+        error = Empty,
+    )
+}
+
+pub(crate) fn record_http_result(span: &Span, result: &reqwest::Result<reqwest::Response>) {
+    match result {
+        Ok(response) => span.record("status", response.status().as_u16()),
+        Err(e) => span.record("error", tracing::field::display(e)),
+    };
+}
+
+pub(crate) fn exec_span(command: &[String], include_content: bool) -> Span {
+    let span = info_span!(
+        target: TARGET,
+        "exec",
+        argv_hash = %format!("{:016x}", argv_hash(command)),
+        command = Empty,
+        exit_code = Empty,
+        duration_ms = Empty,
+        timed_out = Empty,
+        output = Empty,
+    );
+    if include_content {
+        span.record("command", tracing::field::debug(command));
+    }
+    span
+}
+
+pub(crate) fn record_exec_result(span: &Span, output: &ExecToolCallOutput, include_content: bool) {
+    span.record("exit_code", output.exit_code);
+    span.record("duration_ms", duration_ms(output.duration));
+    span.record("timed_out", output.terminated.is_some());
+    if include_content {
+        span.record("output", output.aggregated_output.text.as_str());
+    }
+}
+
+pub(crate) fn mcp_tool_call_span(
+    server: &str,
+    tool: &str,
+    arguments: &str,
+    include_content: bool,
+) -> Span {
+    let span = info_span!(
+        target: TARGET,
+        "mcp_tool_call",
+        server,
+        tool,
+        arguments = Empty,
+        is_error = Empty,
+        duration_ms = Empty,
+        output = Empty,
+    );
+    if include_content {
+        span.record("arguments", arguments);
+    }
+    span
+}
+
+pub(crate) fn record_mcp_tool_call_result(
+    span: &Span,
+    result: &Result<mcp_types::CallToolResult, String>,
+    duration: Duration,
+    include_content: bool,
+) {
+    span.record("duration_ms", duration_ms(duration));
+    let is_error = match result {
+        Ok(result) => result.is_error.unwrap_or(false),
+        Err(_) => true,
+    };
+    span.record("is_error", is_error);
+    if include_content {
+        let output = match result {
+            Ok(result) => serde_json::to_string(&result.content).unwrap_or_default(),
+            Err(e) => e.clone(),
+        };
+        span.record("output", output.as_str());
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// 64-bit FNV-1a over the NUL-separated argv, so the same command hashes the
+/// same across runs and versions without revealing its text.
+fn argv_hash(command: &[String]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = OFFSET_BASIS;
+    for (i, arg) in command.iter().enumerate() {
+        if i > 0 {
+            // The NUL separator: XOR with zero leaves the hash unchanged.
+            hash = hash.wrapping_mul(PRIME);
+        }
+        for byte in arg.bytes() {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+#[cfg(feature = "otel")]
+pub use export::OtelGuard;
+#[cfg(feature = "otel")]
+pub use export::layer;
+
+#[cfg(feature = "otel")]
+mod export {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_otlp::WithHttpConfig;
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing::Subscriber;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::filter::Targets;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::TARGET;
+    use crate::config_types::OtelConfig;
+
+    /// Flushes buffered spans to the collector when dropped. Keep it alive for
+    /// as long as the process should export spans.
+    pub struct OtelGuard(SdkTracerProvider);
+
+    impl Drop for OtelGuard {
+        fn drop(&mut self) {
+            if let Err(e) = self.0.shutdown() {
+                tracing::warn!("failed to flush OpenTelemetry spans: {e}");
+            }
+        }
+    }
+
+    /// Builds a layer exporting the spans of this module to `config.endpoint`,
+    /// or returns `None` when no endpoint is configured.
+    pub fn layer<S>(config: &OtelConfig) -> std::io::Result<Option<(impl Layer<S>, OtelGuard)>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let Some(endpoint) = config.endpoint.as_deref() else {
+            return Ok(None);
+        };
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .with_headers(config.headers.clone())
+            .build()
+            .map_err(std::io::Error::other)?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("codex").build())
+            .build();
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("codex"))
+            .with_filter(Targets::new().with_target(TARGET, tracing::Level::TRACE));
+        Ok(Some((layer, OtelGuard(provider))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argv_hash_separates_arguments() {
+        let joined = argv_hash(&["echo".to_string(), "ab".to_string()]);
+        let split = argv_hash(&["echo".to_string(), "a".to_string(), "b".to_string()]);
+        assert_ne!(joined, split);
+        assert_eq!(joined, argv_hash(&["echo".to_string(), "ab".to_string()]));
+    }
+}
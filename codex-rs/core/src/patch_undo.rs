@@ -0,0 +1,287 @@
+//! Undo for the patches applied during a session.
+//!
+//! Before a patch is applied, the current contents of every file it touches
+//! are captured. Once it applies cleanly, they are written to the session's
+//! `undo` directory together with a hash of what the patch left behind, one
+//! numbered file per patch, so the stack survives a crash. Undoing restores
+//! the most recent patch's files, provided nothing has changed them since.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+use sha1::Digest;
+use sha1::Sha1;
+
+use crate::protocol::FileChange;
+
+/// Contents of the files a patch touches, read before it is applied.
+#[derive(Debug)]
+pub(crate) struct PatchSnapshot {
+    files: Vec<(PathBuf, Option<String>)>,
+}
+
+impl PatchSnapshot {
+    /// Reads every path in `changes`, including move destinations. A missing
+    /// file is recorded as `None`; so is one that cannot be read as text,
+    /// which the patch could not have applied to anyway.
+    pub(crate) fn capture(changes: &HashMap<PathBuf, FileChange>) -> Self {
+        let mut paths = Vec::new();
+        for (path, change) in changes {
+            paths.push(path.clone());
+            if let FileChange::Update {
+                move_path: Some(dest),
+                ..
+            } = change
+            {
+                paths.push(dest.clone());
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let before = std::fs::read_to_string(&path).ok();
+                (path, before)
+            })
+            .collect();
+        Self { files }
+    }
+}
+
+/// A file as a patch found and left it.
+#[derive(Debug, Serialize, Deserialize)]
+struct UndoFile {
+    path: PathBuf,
+    /// Contents before the patch; `None` if the patch created the file.
+    before: Option<String>,
+    /// Hash of the contents after the patch; `None` if the patch deleted the
+    /// file or moved it away.
+    after_sha1: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UndoEntry {
+    call_id: String,
+    files: Vec<UndoFile>,
+}
+
+/// Files restored by [`PatchUndoStack::undo_last`].
+#[derive(Debug, PartialEq)]
+pub(crate) struct UndoneFiles {
+    pub(crate) call_id: String,
+    pub(crate) restored: Vec<PathBuf>,
+    pub(crate) removed: Vec<PathBuf>,
+}
+
+pub(crate) struct PatchUndoStack {
+    dir: PathBuf,
+}
+
+impl PatchUndoStack {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Records a patch that applied cleanly, hashing what it wrote.
+    pub(crate) fn push(&self, call_id: &str, snapshot: PatchSnapshot) -> io::Result<()> {
+        let files = snapshot
+            .files
+            .into_iter()
+            .map(|(path, before)| {
+                let after_sha1 = read_sha1(&path)?;
+                Ok(UndoFile {
+                    path,
+                    before,
+                    after_sha1,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let entry = UndoEntry {
+            call_id: call_id.to_string(),
+            files,
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        let next = self.entries()?.last().map_or(1, |(n, _)| n + 1);
+        let json = serde_json::to_vec(&entry).map_err(io::Error::other)?;
+        // Write then rename so a crash never leaves a truncated entry.
+        let tmp = self.dir.join(format!("{next:06}.json.tmp"));
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, self.dir.join(format!("{next:06}.json")))
+    }
+
+    /// Restores the files of the most recent patch and drops it from the
+    /// stack. Refuses, leaving everything as is, when any of them has changed
+    /// since the patch was applied.
+    pub(crate) fn undo_last(&self) -> Result<UndoneFiles, String> {
+        let entries = self
+            .entries()
+            .map_err(|e| format!("failed to read the undo history: {e}"))?;
+        let Some((_, entry_path)) = entries.last() else {
+            return Err("no patch to undo".to_string());
+        };
+        let entry: UndoEntry = std::fs::read(entry_path)
+            .map_err(io::Error::other)
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(io::Error::other))
+            .map_err(|e| format!("failed to read {}: {e}", entry_path.display()))?;
+
+        for file in &entry.files {
+            let current = read_sha1(&file.path)
+                .map_err(|e| format!("failed to read {}: {e}", file.path.display()))?;
+            if current != file.after_sha1 {
+                return Err(format!(
+                    "cannot undo the last patch: {} has changed since it was applied",
+                    file.path.display()
+                ));
+            }
+        }
+
+        let mut restored = Vec::new();
+        let mut removed = Vec::new();
+        for file in &entry.files {
+            let result = match &file.before {
+                Some(contents) => {
+                    if let Some(parent) = file.path.parent() {
+                        std::fs::create_dir_all(parent).ok();
+                    }
+                    std::fs::write(&file.path, contents).map(|()| restored.push(file.path.clone()))
+                }
+                None if file.after_sha1.is_some() => {
+                    std::fs::remove_file(&file.path).map(|()| removed.push(file.path.clone()))
+                }
+                None => Ok(()),
+            };
+            result.map_err(|e| format!("failed to restore {}: {e}", file.path.display()))?;
+        }
+        std::fs::remove_file(entry_path)
+            .map_err(|e| format!("failed to update the undo history: {e}"))?;
+        Ok(UndoneFiles {
+            call_id: entry.call_id,
+            restored,
+            removed,
+        })
+    }
+
+    /// Recorded patches, oldest first.
+    fn entries(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let read_dir = match std::fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Some(n) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                entries.push((n, path));
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+fn read_sha1(path: &Path) -> io::Result<Option<String>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(format!("{:x}", Sha1::digest(&bytes)))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    fn update(move_path: Option<PathBuf>) -> FileChange {
+        FileChange::Update {
+            unified_diff: String::new(),
+            move_path,
+        }
+    }
+
+    #[test]
+    fn undoes_patches_in_reverse_order() {
+        let dir = tempdir().unwrap();
+        let stack = PatchUndoStack::new(dir.path().join("undo"));
+        let edited = dir.path().join("edited.txt");
+        let created = dir.path().join("created.txt");
+        let moved_from = dir.path().join("old.txt");
+        let moved_to = dir.path().join("sub/new.txt");
+        std::fs::write(&edited, "v1\n").unwrap();
+        std::fs::write(&moved_from, "moved\n").unwrap();
+
+        // First patch: edit a file and create another.
+        let changes = HashMap::from([
+            (edited.clone(), update(None)),
+            (
+                created.clone(),
+                FileChange::Add {
+                    content: "new\n".to_string(),
+                },
+            ),
+        ]);
+        let snapshot = PatchSnapshot::capture(&changes);
+        std::fs::write(&edited, "v2\n").unwrap();
+        std::fs::write(&created, "new\n").unwrap();
+        stack.push("call-1", snapshot).unwrap();
+
+        // Second patch: edit again and move a file.
+        let changes = HashMap::from([
+            (edited.clone(), update(None)),
+            (moved_from.clone(), update(Some(moved_to.clone()))),
+        ]);
+        let snapshot = PatchSnapshot::capture(&changes);
+        std::fs::write(&edited, "v3\n").unwrap();
+        std::fs::create_dir_all(moved_to.parent().unwrap()).unwrap();
+        std::fs::rename(&moved_from, &moved_to).unwrap();
+        stack.push("call-2", snapshot).unwrap();
+
+        let undone = stack.undo_last().unwrap();
+        assert_eq!(undone.call_id, "call-2");
+        assert_eq!(undone.removed, vec![moved_to.clone()]);
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "v2\n");
+        assert_eq!(std::fs::read_to_string(&moved_from).unwrap(), "moved\n");
+        assert!(!moved_to.exists());
+
+        let undone = stack.undo_last().unwrap();
+        assert_eq!(undone.call_id, "call-1");
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "v1\n");
+        assert!(!created.exists());
+
+        assert_eq!(stack.undo_last(), Err("no patch to undo".to_string()));
+    }
+
+    #[test]
+    fn refuses_when_a_file_changed_since() {
+        let dir = tempdir().unwrap();
+        let stack = PatchUndoStack::new(dir.path().join("undo"));
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "before\n").unwrap();
+
+        let snapshot = PatchSnapshot::capture(&HashMap::from([(path.clone(), update(None))]));
+        std::fs::write(&path, "patched\n").unwrap();
+        stack.push("call-1", snapshot).unwrap();
+        std::fs::write(&path, "edited by hand\n").unwrap();
+
+        let err = stack.undo_last().unwrap_err();
+        assert!(err.contains("has changed since it was applied"), "{err}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "edited by hand\n");
+
+        // The entry is kept, so undo works once the file is put back.
+        std::fs::write(&path, "patched\n").unwrap();
+        assert_eq!(stack.undo_last().unwrap().restored, vec![path.clone()]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "before\n");
+    }
+}
@@ -71,6 +71,7 @@ pub(crate) async fn handle_update_plan(
 ) -> ResponseInputItem {
     match parse_update_plan_arguments(arguments, &call_id) {
         Ok(args) => {
+            session.set_plan(args.clone());
             let output = ResponseInputItem::FunctionCallOutput {
                 call_id,
                 output: FunctionCallOutputPayload {
@@ -90,6 +91,25 @@ pub(crate) async fn handle_update_plan(
     }
 }
 
+/// Renders `plan` as a markdown checklist.
+pub(crate) fn plan_checklist(plan: &UpdatePlanArgs) -> String {
+    let mut out = String::from("Current plan:");
+    if let Some(explanation) = plan.explanation.as_deref()
+        && !explanation.is_empty()
+    {
+        out.push_str(&format!("\n{explanation}"));
+    }
+    for PlanItemArg { step, status } in &plan.plan {
+        let mark = match status {
+            StepStatus::Completed => "[x]",
+            StepStatus::InProgress => "[~]",
+            StepStatus::Pending => "[ ]",
+        };
+        out.push_str(&format!("\n- {mark} {step}"));
+    }
+    out
+}
+
 fn parse_update_plan_arguments(
     arguments: String,
     call_id: &str,
@@ -9,10 +9,20 @@
 //!     root is found, only the current working directory is considered.
 //! 2.  Collect every `AGENTS.md` found from the repository root down to the
 //!     current working directory (inclusive) and concatenate their contents in
-//!     that order.
-//! 3.  We do **not** walk past the Git root.
+//!     that order, each preceded by a header naming the file relative to the
+//!     repository root.
+//! 3.  We do **not** walk past the Git root, and symlinked `AGENTS.md` files
+//!     are ignored so that nothing outside the repository can be pulled in.
+//!
+//! The contents share a budget of `project_doc_max_bytes`; the file that
+//! exhausts it is truncated at the last paragraph break that fits and any
+//! files after it are dropped. A marker in the text tells the model what was
+//! left out, and [`UserInstructions::truncated`] tells the user.
 
 use crate::config::Config;
+use crate::protocol::TruncatedProjectDoc;
+use std::borrow::Cow;
+use std::path::Path;
 use std::path::PathBuf;
 use tokio::io::AsyncReadExt;
 use tracing::error;
@@ -24,50 +34,102 @@ const CANDIDATE_FILENAMES: &[&str] = &["AGENTS.md"];
 /// be concatenated with the following separator.
 const PROJECT_DOC_SEPARATOR: &str = "\n\n--- project-doc ---\n\n";
 
+/// The user instructions for a session along with the project docs that were
+/// merged into them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct UserInstructions {
+    pub(crate) text: Option<String>,
+    pub(crate) project_docs: Vec<PathBuf>,
+    /// Docs that did not fit the budget, in whole or in part.
+    pub(crate) truncated: Vec<TruncatedProjectDoc>,
+}
+
+/// Project docs read from disk, concatenated with per-file headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectDocs {
+    pub contents: String,
+    /// The files that contributed to `contents`, root first.
+    pub paths: Vec<PathBuf>,
+    /// The files that were cut short or left out to stay within the budget.
+    pub truncated: Vec<TruncatedProjectDoc>,
+}
+
 /// Combines `Config::instructions` and `AGENTS.md` (if present) into a single
 /// string of instructions.
-pub(crate) async fn get_user_instructions(config: &Config) -> Option<String> {
-    match read_project_docs(config).await {
-        Ok(Some(project_doc)) => match &config.user_instructions {
-            Some(original_instructions) => Some(format!(
-                "{original_instructions}{PROJECT_DOC_SEPARATOR}{project_doc}"
-            )),
-            None => Some(project_doc),
+pub(crate) async fn get_user_instructions(config: &Config) -> UserInstructions {
+    load_user_instructions(
+        &config.cwd,
+        config.project_doc_max_bytes,
+        config.user_instructions.as_deref(),
+    )
+    .await
+}
+
+/// Like [`get_user_instructions`], but for an arbitrary working directory so
+/// the instructions can be re-evaluated when the session cwd changes.
+pub(crate) async fn load_user_instructions(
+    cwd: &Path,
+    max_bytes: usize,
+    user_instructions: Option<&str>,
+) -> UserInstructions {
+    match read_project_docs_in(cwd, max_bytes).await {
+        Ok(Some(ProjectDocs {
+            contents,
+            paths,
+            truncated,
+        })) => UserInstructions {
+            text: Some(match user_instructions {
+                Some(original_instructions) => {
+                    format!("{original_instructions}{PROJECT_DOC_SEPARATOR}{contents}")
+                }
+                None => contents,
+            }),
+            project_docs: paths,
+            truncated,
+        },
+        Ok(None) => UserInstructions {
+            text: user_instructions.map(ToOwned::to_owned),
+            ..Default::default()
         },
-        Ok(None) => config.user_instructions.clone(),
         Err(e) => {
             error!("error trying to find project doc: {e:#}");
-            config.user_instructions.clone()
+            UserInstructions {
+                text: user_instructions.map(ToOwned::to_owned),
+                ..Default::default()
+            }
         }
     }
 }
 
 /// Attempt to locate and load the project documentation.
 ///
-/// On success returns `Ok(Some(contents))` where `contents` is the
+/// On success returns `Ok(Some(docs))` where `docs.contents` is the
 /// concatenation of all discovered docs. If no documentation file is found the
 /// function returns `Ok(None)`. Unexpected I/O failures bubble up as `Err` so
 /// callers can decide how to handle them.
-pub async fn read_project_docs(config: &Config) -> std::io::Result<Option<String>> {
-    let max_total = config.project_doc_max_bytes;
+pub async fn read_project_docs(config: &Config) -> std::io::Result<Option<ProjectDocs>> {
+    read_project_docs_in(&config.cwd, config.project_doc_max_bytes).await
+}
 
+async fn read_project_docs_in(
+    cwd: &Path,
+    max_total: usize,
+) -> std::io::Result<Option<ProjectDocs>> {
     if max_total == 0 {
         return Ok(None);
     }
 
-    let paths = discover_project_doc_paths(config)?;
+    let (root, paths) = discover_project_doc_paths_in(cwd)?;
     if paths.is_empty() {
         return Ok(None);
     }
 
     let mut remaining: u64 = max_total as u64;
     let mut parts: Vec<String> = Vec::new();
+    let mut loaded: Vec<PathBuf> = Vec::new();
+    let mut truncated: Vec<TruncatedProjectDoc> = Vec::new();
 
     for p in paths {
-        if remaining == 0 {
-            break;
-        }
-
         let file = match tokio::fs::File::open(&p).await {
             Ok(f) => f,
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
@@ -75,39 +137,140 @@ pub async fn read_project_docs(config: &Config) -> std::io::Result<Option<String
         };
 
         let size = file.metadata().await?.len();
+        let origin = p.strip_prefix(&root).unwrap_or(&p).display().to_string();
+        if remaining == 0 {
+            if size > 0 {
+                tracing::warn!(
+                    "Project doc `{}` does not fit the budget - skipping.",
+                    p.display()
+                );
+                parts.push(format!(
+                    "--- {origin} (omitted) ---\n\n{}",
+                    omission_marker(size, size, &origin, max_total)
+                ));
+                truncated.push(TruncatedProjectDoc {
+                    path: p,
+                    size_bytes: size,
+                    omitted_bytes: size,
+                });
+            }
+            continue;
+        }
+
         let mut reader = tokio::io::BufReader::new(file).take(remaining);
         let mut data: Vec<u8> = Vec::new();
         reader.read_to_end(&mut data).await?;
 
-        if size > remaining {
-            tracing::warn!(
-                "Project doc `{}` exceeds remaining budget ({} bytes) - truncating.",
-                p.display(),
-                remaining,
-            );
+        if size <= remaining {
+            let text = String::from_utf8_lossy(&data);
+            if !text.trim().is_empty() {
+                parts.push(format!("--- {origin} ---\n\n{text}"));
+                remaining -= data.len() as u64;
+                loaded.push(p);
+            }
+            continue;
         }
 
-        let text = String::from_utf8_lossy(&data).to_string();
-        if !text.trim().is_empty() {
-            parts.push(text);
-            remaining = remaining.saturating_sub(data.len() as u64);
+        tracing::warn!(
+            "Project doc `{}` exceeds remaining budget ({} bytes) - truncating.",
+            p.display(),
+            remaining,
+        );
+        let kept = truncate_at_paragraph(&data);
+        let omitted = size.saturating_sub(kept.len() as u64);
+        let marker = omission_marker(omitted, size, &origin, max_total);
+        if kept.trim().is_empty() {
+            parts.push(format!("--- {origin} (omitted) ---\n\n{marker}"));
+        } else {
+            parts.push(format!(
+                "--- {origin} (truncated) ---\n\n{kept}\n\n{marker}"
+            ));
+            loaded.push(p.clone());
         }
+        truncated.push(TruncatedProjectDoc {
+            path: p,
+            size_bytes: size,
+            omitted_bytes: omitted,
+        });
+        remaining = 0;
     }
 
     if parts.is_empty() {
         Ok(None)
     } else {
-        Ok(Some(parts.join("\n\n")))
+        Ok(Some(ProjectDocs {
+            contents: parts.join("\n\n"),
+            paths: loaded,
+            truncated,
+        }))
     }
 }
 
+/// The longest prefix of `data` that ends at a paragraph break, or failing
+/// that a line break, or failing that a character boundary. A multi-byte
+/// character cut off by the end of `data` is dropped rather than replaced.
+fn truncate_at_paragraph(data: &[u8]) -> Cow<'_, str> {
+    let text = String::from_utf8_lossy(without_partial_char(data));
+    let end = match text.rfind("\n\n") {
+        Some(end) if end > 0 => end,
+        _ => match text.rfind('\n') {
+            Some(end) if end > 0 => end,
+            _ => text.len(),
+        },
+    };
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(text[..end].trim_end()),
+        Cow::Owned(mut text) => {
+            text.truncate(end);
+            text.truncate(text.trim_end().len());
+            Cow::Owned(text)
+        }
+    }
+}
+
+/// `data` without the leading bytes of a UTF-8 sequence that runs past its end.
+fn without_partial_char(data: &[u8]) -> &[u8] {
+    // The last character starts at most three continuation bytes back.
+    for back in 1..=data.len().min(4) {
+        let byte = data[data.len() - back];
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        let width = match byte {
+            0b1100_0000..=0b1101_1111 => 2,
+            0b1110_0000..=0b1110_1111 => 3,
+            0b1111_0000..=0b1111_0111 => 4,
+            _ => 1,
+        };
+        return if width > back {
+            &data[..data.len() - back]
+        } else {
+            data
+        };
+    }
+    data
+}
+
+/// Tells the model that part of a doc is missing, so it does not take what it
+/// has for the whole of it.
+fn omission_marker(omitted: u64, size: u64, origin: &str, max_total: usize) -> String {
+    format!(
+        "[... {omitted} of {size} bytes of {origin} omitted: the project docs exceed project_doc_max_bytes ({max_total}) ...]"
+    )
+}
+
 /// Discover the list of AGENTS.md files using the same search rules as
 /// `read_project_docs`, but return the file paths instead of concatenated
 /// contents. The list is ordered from repository root to the current working
-/// directory (inclusive). Symlinks are allowed. When `project_doc_max_bytes`
-/// is zero, returns an empty list.
+/// directory (inclusive). Symlinked files are skipped.
 pub fn discover_project_doc_paths(config: &Config) -> std::io::Result<Vec<PathBuf>> {
-    let mut dir = config.cwd.clone();
+    discover_project_doc_paths_in(&config.cwd).map(|(_, paths)| paths)
+}
+
+/// Returns the directory the search started from (the Git root, or `cwd` when
+/// there is none) along with the docs found beneath it.
+fn discover_project_doc_paths_in(cwd: &Path) -> std::io::Result<(PathBuf, Vec<PathBuf>)> {
+    let mut dir = cwd.to_path_buf();
     if let Ok(canon) = dir.canonicalize() {
         dir = canon;
     }
@@ -133,7 +296,7 @@ pub fn discover_project_doc_paths(config: &Config) -> std::io::Result<Vec<PathBu
         cursor = parent.to_path_buf();
     }
 
-    let search_dirs: Vec<PathBuf> = if let Some(root) = git_root {
+    let (root, search_dirs) = if let Some(root) = git_root {
         let mut dirs: Vec<PathBuf> = Vec::new();
         let mut saw_root = false;
         for p in chain.iter().rev() {
@@ -146,9 +309,9 @@ pub fn discover_project_doc_paths(config: &Config) -> std::io::Result<Vec<PathBu
             }
             dirs.push(p.clone());
         }
-        dirs
+        (root, dirs)
     } else {
-        vec![config.cwd.clone()]
+        (dir.clone(), vec![dir])
     };
 
     let mut found: Vec<PathBuf> = Vec::new();
@@ -156,21 +319,20 @@ pub fn discover_project_doc_paths(config: &Config) -> std::io::Result<Vec<PathBu
         for name in CANDIDATE_FILENAMES {
             let candidate = d.join(name);
             match std::fs::symlink_metadata(&candidate) {
-                Ok(md) => {
-                    let ft = md.file_type();
-                    // Allow regular files and symlinks; opening will later fail for dangling links.
-                    if ft.is_file() || ft.is_symlink() {
-                        found.push(candidate);
-                        break;
-                    }
+                // A symlink could point anywhere, including outside the
+                // repository, so only regular files are considered.
+                Ok(md) if md.file_type().is_file() => {
+                    found.push(candidate);
+                    break;
                 }
+                Ok(_) => continue,
                 Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
                 Err(e) => return Err(e),
             }
         }
     }
 
-    Ok(found)
+    Ok((root, found))
 }
 
 #[cfg(test)]
@@ -209,10 +371,10 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, 4096, None)).await;
         assert!(
-            res.is_none(),
+            res.text.is_none(),
             "Expected None when AGENTS.md is absent and no system instructions provided"
         );
-        assert!(res.is_none(), "Expected None when AGENTS.md is absent");
+        assert!(res.project_docs.is_empty(), "Expected no docs to be loaded");
     }
 
     /// Small file within the byte-limit is returned unmodified.
@@ -223,10 +385,11 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, 4096, None))
             .await
+            .text
             .expect("doc expected");
 
         assert_eq!(
-            res, "hello world",
+            res, "--- AGENTS.md ---\n\nhello world",
             "The document should be returned verbatim when it is smaller than the limit and there are no existing instructions"
         );
     }
@@ -242,10 +405,69 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, LIMIT, None))
             .await
+            .text
             .expect("doc expected");
 
-        assert_eq!(res.len(), LIMIT, "doc should be truncated to LIMIT bytes");
-        assert_eq!(res, huge[..LIMIT]);
+        assert_eq!(
+            res,
+            format!(
+                "--- AGENTS.md (truncated) ---\n\n{}\n\n[... 1024 of 2048 bytes of AGENTS.md omitted: the project docs exceed project_doc_max_bytes (1024) ...]",
+                &huge[..LIMIT]
+            ),
+            "doc should be truncated to LIMIT bytes"
+        );
+    }
+
+    /// The cut lands on the last paragraph break that fits, and the session
+    /// is told which file lost how much.
+    #[tokio::test]
+    async fn truncation_prefers_paragraph_boundary() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let doc = "first paragraph\nstill first\n\nsecond paragraph\n\nthird paragraph";
+        fs::write(tmp.path().join("AGENTS.md"), doc).unwrap();
+
+        let res = get_user_instructions(&make_config(&tmp, 50, None)).await;
+        assert_eq!(
+            res.text.as_deref(),
+            Some(
+                "--- AGENTS.md (truncated) ---\n\nfirst paragraph\nstill first\n\nsecond paragraph\n\n[... 17 of 62 bytes of AGENTS.md omitted: the project docs exceed project_doc_max_bytes (50) ...]"
+            )
+        );
+        assert_eq!(
+            res.truncated,
+            vec![TruncatedProjectDoc {
+                path: tmp.path().canonicalize().unwrap().join("AGENTS.md"),
+                size_bytes: 62,
+                omitted_bytes: 17,
+            }]
+        );
+    }
+
+    #[test]
+    fn truncation_never_splits_a_multi_byte_char() {
+        // "é" is two bytes, "日" three and "🦀" four; cut each one part way.
+        for (text, limit, expected) in [
+            ("éé", 3, "é"),
+            ("a日", 3, "a"),
+            ("a日", 4, "a日"),
+            ("🦀🦀", 5, "🦀"),
+            ("🦀🦀", 7, "🦀"),
+            ("🦀", 3, ""),
+        ] {
+            assert_eq!(
+                truncate_at_paragraph(&text.as_bytes()[..limit]),
+                expected,
+                "{text:?} cut at {limit} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn truncation_falls_back_to_line_then_char_boundary() {
+        assert_eq!(truncate_at_paragraph("ä line\nnext ä".as_bytes()), "ä line");
+        assert_eq!(truncate_at_paragraph("ää ä".as_bytes()), "ää ä");
+        // Invalid bytes elsewhere are still replaced, not dropped.
+        assert_eq!(truncate_at_paragraph(b"a\xffb\n\nc"), "a\u{fffd}b");
     }
 
     /// When `cwd` is nested inside a repo, the search should locate AGENTS.md
@@ -272,8 +494,11 @@ mod tests {
         let mut cfg = make_config(&repo, 4096, None);
         cfg.cwd = nested;
 
-        let res = get_user_instructions(&cfg).await.expect("doc expected");
-        assert_eq!(res, "root level doc");
+        let res = get_user_instructions(&cfg)
+            .await
+            .text
+            .expect("doc expected");
+        assert_eq!(res, "--- AGENTS.md ---\n\nroot level doc");
     }
 
     /// Explicitly setting the byte-limit to zero disables project docs.
@@ -284,7 +509,7 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, 0, None)).await;
         assert!(
-            res.is_none(),
+            res.text.is_none(),
             "With limit 0 the function should return None"
         );
     }
@@ -300,9 +525,13 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, 4096, Some(INSTRUCTIONS)))
             .await
+            .text
             .expect("should produce a combined instruction string");
 
-        let expected = format!("{INSTRUCTIONS}{PROJECT_DOC_SEPARATOR}{}", "proj doc");
+        let expected = format!(
+            "{INSTRUCTIONS}{PROJECT_DOC_SEPARATOR}{}",
+            "--- AGENTS.md ---\n\nproj doc"
+        );
 
         assert_eq!(res, expected);
     }
@@ -317,7 +546,7 @@ mod tests {
 
         let res = get_user_instructions(&make_config(&tmp, 4096, Some(INSTRUCTIONS))).await;
 
-        assert_eq!(res, Some(INSTRUCTIONS.to_string()));
+        assert_eq!(res.text, Some(INSTRUCTIONS.to_string()));
     }
 
     /// When both the repository root and the working directory contain
@@ -344,7 +573,95 @@ mod tests {
         let mut cfg = make_config(&repo, 4096, None);
         cfg.cwd = nested;
 
-        let res = get_user_instructions(&cfg).await.expect("doc expected");
-        assert_eq!(res, "root doc\n\ncrate doc");
+        let res = get_user_instructions(&cfg).await;
+        assert_eq!(
+            res.text.as_deref(),
+            Some(
+                "--- AGENTS.md ---\n\nroot doc\n\n--- workspace/crate_a/AGENTS.md ---\n\ncrate doc"
+            )
+        );
+        let root = repo.path().canonicalize().unwrap();
+        assert_eq!(
+            res.project_docs,
+            vec![
+                root.join("AGENTS.md"),
+                root.join("workspace/crate_a/AGENTS.md")
+            ]
+        );
+    }
+
+    /// The byte budget is shared: once the root doc has used most of it, the
+    /// nested doc is truncated to whatever is left.
+    #[tokio::test]
+    async fn budget_is_shared_across_docs() {
+        let repo = tempfile::tempdir().expect("tempdir");
+        std::fs::write(repo.path().join(".git"), "gitdir: nowhere\n").unwrap();
+        fs::write(repo.path().join("AGENTS.md"), "0123456789").unwrap();
+        let nested = repo.path().join("pkg");
+        std::fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("AGENTS.md"), "abcdefghij").unwrap();
+
+        let mut cfg = make_config(&repo, 14, None);
+        cfg.cwd = nested;
+
+        let res = get_user_instructions(&cfg)
+            .await
+            .text
+            .expect("doc expected");
+        assert_eq!(
+            res,
+            "--- AGENTS.md ---\n\n0123456789\n\n--- pkg/AGENTS.md (truncated) ---\n\nabcd\n\n[... 6 of 10 bytes of pkg/AGENTS.md omitted: the project docs exceed project_doc_max_bytes (14) ...]"
+        );
+    }
+
+    /// A doc that comes after the budget ran out is named, not silently lost.
+    #[tokio::test]
+    async fn docs_past_the_budget_are_reported() {
+        let repo = tempfile::tempdir().expect("tempdir");
+        std::fs::write(repo.path().join(".git"), "gitdir: nowhere\n").unwrap();
+        fs::write(repo.path().join("AGENTS.md"), "0123456789").unwrap();
+        let nested = repo.path().join("pkg");
+        std::fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("AGENTS.md"), "abcdefghij").unwrap();
+
+        let mut cfg = make_config(&repo, 10, None);
+        cfg.cwd = nested;
+
+        let res = get_user_instructions(&cfg).await;
+        assert_eq!(
+            res.text.as_deref(),
+            Some(
+                "--- AGENTS.md ---\n\n0123456789\n\n--- pkg/AGENTS.md (omitted) ---\n\n[... 10 of 10 bytes of pkg/AGENTS.md omitted: the project docs exceed project_doc_max_bytes (10) ...]"
+            )
+        );
+        let root = repo.path().canonicalize().unwrap();
+        assert_eq!(res.project_docs, vec![root.join("AGENTS.md")]);
+        assert_eq!(
+            res.truncated,
+            vec![TruncatedProjectDoc {
+                path: root.join("pkg/AGENTS.md"),
+                size_bytes: 10,
+                omitted_bytes: 10,
+            }]
+        );
+    }
+
+    /// Symlinked docs are ignored because they may point outside the repo.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_docs_are_ignored() {
+        let outside = tempfile::tempdir().expect("tempdir");
+        fs::write(outside.path().join("secrets.md"), "do not load").unwrap();
+
+        let repo = tempfile::tempdir().expect("tempdir");
+        std::fs::write(repo.path().join(".git"), "gitdir: nowhere\n").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secrets.md"),
+            repo.path().join("AGENTS.md"),
+        )
+        .unwrap();
+
+        let res = get_user_instructions(&make_config(&repo, 4096, None)).await;
+        assert_eq!(res, UserInstructions::default());
     }
 }
@@ -0,0 +1,183 @@
+//! `{{name}}` placeholders in the base and user instructions.
+//!
+//! The built-in variables are `cwd`, `model`, `os` and `date`; the
+//! `[prompt_vars]` config table adds more. A placeholder whose name is not
+//! defined is left as written, or fails the turn with `strict_prompt_vars`.
+//! `{{{{` stands for a literal `{{`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::Local;
+
+use crate::error::CodexErr;
+use crate::error::Result;
+
+/// Variables that are always defined and cannot be set in `[prompt_vars]`.
+pub(crate) const BUILT_IN_PROMPT_VARS: &[&str] = &["cwd", "model", "os", "date"];
+
+/// The values substituted into the instructions of a turn.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PromptVars {
+    values: HashMap<String, String>,
+    strict: bool,
+}
+
+impl PromptVars {
+    pub(crate) fn new(
+        cwd: &Path,
+        model: &str,
+        user_vars: &HashMap<String, String>,
+        strict: bool,
+    ) -> Self {
+        let mut values = user_vars.clone();
+        values.insert("cwd".to_string(), cwd.display().to_string());
+        values.insert("model".to_string(), model.to_string());
+        values.insert("os".to_string(), std::env::consts::OS.to_string());
+        values.insert(
+            "date".to_string(),
+            Local::now().format("%Y-%m-%d").to_string(),
+        );
+        Self { values, strict }
+    }
+
+    /// Replaces the placeholders in `template`. Text between braces that is
+    /// not a variable name, such as JSON, is left alone.
+    pub(crate) fn render<'a>(&self, template: &'a str) -> Result<Cow<'a, str>> {
+        if !template.contains("{{") {
+            return Ok(Cow::Borrowed(template));
+        }
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            if let Some(after_escape) = after.strip_prefix("{{") {
+                out.push_str("{{");
+                rest = after_escape;
+                continue;
+            }
+            let name = after
+                .find("}}")
+                .map(|end| (after[..end].trim(), &after[end + 2..]))
+                .filter(|(name, _)| is_var_name(name));
+            let Some((name, after_var)) = name else {
+                out.push_str("{{");
+                rest = after;
+                continue;
+            };
+            match self.values.get(name) {
+                Some(value) => out.push_str(value),
+                None if self.strict => {
+                    return Err(CodexErr::UndefinedPromptVar(name.to_string()));
+                }
+                None => out.push_str(&rest[start..rest.len() - after_var.len()]),
+            }
+            rest = after_var;
+        }
+        out.push_str(rest);
+        Ok(Cow::Owned(out))
+    }
+}
+
+/// Checks the names in the `[prompt_vars]` config table.
+pub(crate) fn validate_prompt_vars(
+    vars: &HashMap<String, String>,
+) -> std::result::Result<(), String> {
+    for name in vars.keys() {
+        if BUILT_IN_PROMPT_VARS.contains(&name.as_str()) {
+            return Err(format!("prompt_vars.{name} is a built-in variable"));
+        }
+        if !is_var_name(name) {
+            return Err(format!(
+                "invalid name in prompt_vars: {name:?} (use letters, digits, `_` and `-`)"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn vars(strict: bool) -> PromptVars {
+        let user_vars = HashMap::from([
+            ("project".to_string(), "codex".to_string()),
+            ("toolchain".to_string(), "cargo".to_string()),
+        ]);
+        PromptVars::new(Path::new("/work/codex"), "gpt-5", &user_vars, strict)
+    }
+
+    #[test]
+    fn substitution_matrix() {
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        let os = std::env::consts::OS;
+        let cases: Vec<(&str, String)> = vec![
+            ("no placeholders", "no placeholders".to_string()),
+            ("{{cwd}}", "/work/codex".to_string()),
+            ("{{ model }} on {{os}}", format!("gpt-5 on {os}")),
+            ("Today is {{date}}.", format!("Today is {date}.")),
+            (
+                "Build {{project}} with {{toolchain}}.",
+                "Build codex with cargo.".to_string(),
+            ),
+            ("{{project}}{{project}}", "codexcodex".to_string()),
+            // Unknown variables are left intact.
+            (
+                "{{unknown}} and {{ unknown }}",
+                "{{unknown}} and {{ unknown }}".to_string(),
+            ),
+            // `{{{{` is a literal `{{`.
+            ("{{{{project}}", "{{project}}".to_string()),
+            ("{{{{{{{{", "{{{{".to_string()),
+            ("{{{{{{project}}", "{{codex".to_string()),
+            // Not variable names.
+            ("{{}}", "{{}}".to_string()),
+            ("{{ a b }}", "{{ a b }}".to_string()),
+            (r#"{{"key": 1}}"#, r#"{{"key": 1}}"#.to_string()),
+            ("{{{project}}}", "{{{project}}}".to_string()),
+            ("unclosed {{project", "unclosed {{project".to_string()),
+            ("}}", "}}".to_string()),
+            ("ünïcode {{project}} ✓", "ünïcode codex ✓".to_string()),
+        ];
+        let vars = vars(false);
+        for (template, expected) in cases {
+            assert_eq!(vars.render(template).unwrap(), expected, "{template:?}");
+        }
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_variables() {
+        let vars = vars(true);
+        assert_eq!(vars.render("{{project}}").unwrap(), "codex");
+        assert_eq!(vars.render("{{{{unknown}}").unwrap(), "{{unknown}}");
+        assert_eq!(vars.render("{{ not a var }}").unwrap(), "{{ not a var }}");
+        let err = vars.render("{{project}} {{unknown}}").unwrap_err();
+        assert!(
+            matches!(&err, CodexErr::UndefinedPromptVar(name) if name == "unknown"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn config_names_are_validated() {
+        let table = |name: &str| HashMap::from([(name.to_string(), "x".to_string())]);
+        assert_eq!(validate_prompt_vars(&table("project_name")), Ok(()));
+        assert_eq!(validate_prompt_vars(&table("tool-chain")), Ok(()));
+        assert_eq!(
+            validate_prompt_vars(&table("cwd")),
+            Err("prompt_vars.cwd is a built-in variable".to_string())
+        );
+        assert!(validate_prompt_vars(&table("two words")).is_err());
+    }
+}
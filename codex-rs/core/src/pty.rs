@@ -0,0 +1,63 @@
+//! Pseudo-terminals for shell tool commands that should believe they are
+//! writing to a terminal.
+
+use std::io;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use tokio::io::AsyncRead;
+use tokio::io::ReadBuf;
+
+/// The controlling side of a pseudo-terminal, from which everything the
+/// command writes to the terminal is read.
+pub(crate) struct PtyMaster(tokio::fs::File);
+
+impl AsyncRead for PtyMaster {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match Pin::new(&mut self.0).poll_read(cx, buf) {
+            // Linux reports EIO rather than EOF once every process holding the
+            // terminal has exited.
+            Poll::Ready(Err(err)) if err.raw_os_error() == Some(libc::EIO) => Poll::Ready(Ok(())),
+            other => other,
+        }
+    }
+}
+
+/// Opens a pseudo-terminal of the given size. The returned [`OwnedFd`] is the
+/// terminal itself, to be handed to the command; see
+/// [`crate::spawn::StdioPolicy::RedirectToPty`].
+pub(crate) fn open_pty(rows: u16, cols: u16) -> io::Result<(PtyMaster, OwnedFd)> {
+    let mut master: libc::c_int = -1;
+    let mut slave: libc::c_int = -1;
+    let mut size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: all pointers are valid for the duration of the call, and a null
+    // name and termios are allowed.
+    let result = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty succeeded, so both descriptors are open and owned by us.
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    let master = tokio::fs::File::from_std(std::fs::File::from(master));
+    Ok((PtyMaster(master), slave))
+}
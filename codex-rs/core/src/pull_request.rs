@@ -0,0 +1,316 @@
+//! The `create_pull_request` tool from `[tools.pull_request]`, which commits
+//! pending changes, pushes a branch and opens a pull request with `gh` (or a
+//! merge request with `glab`).
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::config_types::PullRequestToolToml;
+use crate::openai_tools::JsonSchema;
+use crate::openai_tools::OpenAiTool;
+use crate::openai_tools::ResponsesApiTool;
+use crate::protocol_config_types::PullRequestProvider;
+
+pub const PULL_REQUEST_TOOL_NAME: &str = "create_pull_request";
+
+/// A validated `[tools.pull_request]` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PullRequestTool {
+    pub provider: PullRequestProvider,
+    pub remote: String,
+    base: Option<String>,
+    token_env: Option<String>,
+    pub requires_approval: bool,
+    pub dry_run: bool,
+    pub timeout_ms: Option<u64>,
+}
+
+/// The arguments of a `create_pull_request` call.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PullRequestArgs {
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    pub head: String,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+impl PullRequestTool {
+    /// Validates the `[tools.pull_request]` table.
+    pub fn from_config(toml: &PullRequestToolToml) -> Result<Self, String> {
+        let remote = toml.remote.clone().unwrap_or_else(|| "origin".to_string());
+        check_ref_name(&remote).map_err(|e| format!("invalid tools.pull_request.remote: {e}"))?;
+        if let Some(base) = &toml.base {
+            check_ref_name(base).map_err(|e| format!("invalid tools.pull_request.base: {e}"))?;
+        }
+        Ok(Self {
+            provider: toml.provider,
+            remote,
+            base: toml.base.clone(),
+            token_env: toml.token_env.clone(),
+            requires_approval: toml.requires_approval.unwrap_or(true),
+            dry_run: toml.dry_run,
+            timeout_ms: toml.timeout_ms,
+        })
+    }
+
+    pub(crate) fn openai_tool(&self) -> OpenAiTool {
+        let noun = self.provider.request_noun();
+        let string = |description: &str| JsonSchema::String {
+            description: Some(description.to_string()),
+        };
+        let properties = BTreeMap::from([
+            (
+                "title".to_string(),
+                string(&format!("Title of the {noun}.")),
+            ),
+            ("body".to_string(), string("Description, in Markdown.")),
+            (
+                "head".to_string(),
+                string(
+                    "Branch to push the work to. It is created from the current commit if it does not exist.",
+                ),
+            ),
+            (
+                "base".to_string(),
+                string("Branch to merge into. Defaults to the repository's default branch."),
+            ),
+            (
+                "draft".to_string(),
+                JsonSchema::Boolean {
+                    description: Some(format!("Open the {noun} as a draft.")),
+                },
+            ),
+        ]);
+        OpenAiTool::Function(ResponsesApiTool {
+            name: PULL_REQUEST_TOOL_NAME.to_string(),
+            description: format!(
+                "Opens a {noun} for the work in the workspace: switches to the `head` branch, commits any uncommitted changes with the title as the message, pushes the branch and opens the {noun}. Returns its URL. Use this rather than running git push or the provider's CLI yourself."
+            ),
+            strict: false,
+            parameters: JsonSchema::Object {
+                properties,
+                required: Some(vec!["title".to_string(), "head".to_string()]),
+                additional_properties: Some(false),
+            },
+        })
+    }
+
+    /// Parses and checks the arguments of a call. The error is meant for the
+    /// model.
+    pub(crate) fn parse_args(&self, arguments: &str) -> Result<PullRequestArgs, String> {
+        let mut args: PullRequestArgs = serde_json::from_str(arguments)
+            .map_err(|e| format!("failed to parse function arguments: {e}"))?;
+        if args.title.trim().is_empty() {
+            return Err("title must not be empty".to_string());
+        }
+        check_ref_name(&args.head).map_err(|e| format!("invalid head branch: {e}"))?;
+        if args.base.is_none() {
+            args.base = self.base.clone();
+        }
+        if let Some(base) = &args.base {
+            check_ref_name(base).map_err(|e| format!("invalid base branch: {e}"))?;
+            if *base == args.head {
+                return Err(format!("head and base are both `{base}`"));
+            }
+        }
+        Ok(args)
+    }
+
+    /// The `sh` script a call runs. Every step stops the script if it fails.
+    pub(crate) fn script(&self, args: &PullRequestArgs) -> Result<String, String> {
+        let head = quote(&args.head)?;
+        let remote = quote(&self.remote)?;
+        let mut create = match self.provider {
+            PullRequestProvider::Github => vec![
+                "gh pr create".to_string(),
+                format!("--head {head}"),
+                format!("--title {}", quote(&args.title)?),
+                format!("--body {}", quote(&args.body)?),
+            ],
+            PullRequestProvider::Gitlab => vec![
+                "glab mr create --yes".to_string(),
+                format!("--source-branch {head}"),
+                format!("--title {}", quote(&args.title)?),
+                format!("--description {}", quote(&args.body)?),
+            ],
+        };
+        if let Some(base) = &args.base {
+            let flag = match self.provider {
+                PullRequestProvider::Github => "--base",
+                PullRequestProvider::Gitlab => "--target-branch",
+            };
+            create.push(format!("{flag} {}", quote(base)?));
+        }
+        if args.draft {
+            create.push("--draft".to_string());
+        }
+        Ok(format!(
+            "set -e
+if [ \"$(git rev-parse --abbrev-ref HEAD)\" != {head} ]; then
+  git switch {head} 2>/dev/null || git switch -c {head}
+fi
+if [ -n \"$(git status --porcelain)\" ]; then
+  git add -A
+  git commit -q -m {title}
+fi
+git push -u {remote} {head}
+{create}
+",
+            title = quote(&args.title)?,
+            create = create.join(" "),
+        ))
+    }
+
+    /// Variables for the script: the token, taken from Codex's own
+    /// environment because the shell environment policy usually strips
+    /// tokens, and settings that keep git and the CLI from prompting.
+    pub(crate) fn env(&self) -> HashMap<String, String> {
+        let defaults: &[&str] = match self.provider {
+            PullRequestProvider::Github => &["GH_TOKEN", "GITHUB_TOKEN"],
+            PullRequestProvider::Gitlab => &["GITLAB_TOKEN"],
+        };
+        let mut env = HashMap::from([
+            ("GIT_TERMINAL_PROMPT".to_string(), "0".to_string()),
+            ("GH_PROMPT_DISABLED".to_string(), "1".to_string()),
+            ("GH_NO_UPDATE_NOTIFIER".to_string(), "1".to_string()),
+        ]);
+        let token = match &self.token_env {
+            Some(var) => std::env::var(var).ok(),
+            None => defaults.iter().find_map(|var| std::env::var(var).ok()),
+        };
+        if let Some(token) = token.filter(|token| !token.is_empty()) {
+            env.insert(defaults[0].to_string(), token);
+        }
+        env
+    }
+}
+
+/// The URL of the new pull request in what the CLI printed: the last URL on
+/// its own, since both CLIs print it after any progress messages.
+pub(crate) fn find_pull_request_url(stdout: &str) -> Option<String> {
+    stdout
+        .split_whitespace()
+        .rev()
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(ToString::to_string)
+}
+
+/// Rejects names git would refuse, and any that could be read as an option.
+fn check_ref_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("the name is empty".to_string());
+    }
+    let bad = name.starts_with('-')
+        || name.starts_with('/')
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.ends_with(".lock")
+        || name.contains("..")
+        || name.contains("@{")
+        || name.contains("//")
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+    if bad {
+        Err(format!("`{name}` is not a valid branch name"))
+    } else {
+        Ok(())
+    }
+}
+
+fn quote(arg: &str) -> Result<String, String> {
+    shlex::try_quote(arg)
+        .map(|quoted| quoted.into_owned())
+        .map_err(|_| "arguments must not contain NUL characters".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn tool(provider: PullRequestProvider, base: Option<&str>) -> PullRequestTool {
+        PullRequestTool::from_config(&PullRequestToolToml {
+            provider,
+            base: base.map(ToString::to_string),
+            ..Default::default()
+        })
+        .expect("valid config")
+    }
+
+    #[test]
+    fn github_script_commits_pushes_and_creates() {
+        let tool = tool(PullRequestProvider::Github, Some("main"));
+        let args = tool
+            .parse_args(r#"{"title": "Fix the parser", "body": "Handles nested lists.", "head": "fix/parser"}"#)
+            .expect("valid arguments");
+        assert_eq!(args.base.as_deref(), Some("main"));
+        assert_eq!(
+            tool.script(&args).expect("script"),
+            r#"set -e
+if [ "$(git rev-parse --abbrev-ref HEAD)" != fix/parser ]; then
+  git switch fix/parser 2>/dev/null || git switch -c fix/parser
+fi
+if [ -n "$(git status --porcelain)" ]; then
+  git add -A
+  git commit -q -m 'Fix the parser'
+fi
+git push -u origin fix/parser
+gh pr create --head fix/parser --title 'Fix the parser' --body 'Handles nested lists.' --base main
+"#
+        );
+    }
+
+    #[test]
+    fn gitlab_script_uses_glab() {
+        let tool = tool(PullRequestProvider::Gitlab, None);
+        let args = tool
+            .parse_args(r#"{"title": "T", "head": "feature", "base": "develop", "draft": true}"#)
+            .expect("valid arguments");
+        let script = tool.script(&args).expect("script");
+        assert!(
+            script.ends_with(
+                "glab mr create --yes --source-branch feature --title T --description '' --target-branch develop --draft\n"
+            ),
+            "{script}"
+        );
+    }
+
+    #[test]
+    fn rejects_branches_that_could_be_options_or_invalid() {
+        let tool = tool(PullRequestProvider::Github, None);
+        for head in ["--force", "a b", "a..b", "x.lock", ""] {
+            let arguments = serde_json::json!({"title": "T", "head": head}).to_string();
+            assert!(tool.parse_args(&arguments).is_err(), "{head:?}");
+        }
+        assert_eq!(
+            tool.parse_args(r#"{"title": "T", "head": "main", "base": "main"}"#),
+            Err("head and base are both `main`".to_string())
+        );
+        assert!(
+            PullRequestTool::from_config(&PullRequestToolToml {
+                remote: Some("-x".to_string()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn finds_the_last_url_in_the_output() {
+        assert_eq!(
+            find_pull_request_url(
+                "Creating merge request for feature into main in group/project\n\n!12 Title (feature)\n https://gitlab.com/group/project/-/merge_requests/12\n"
+            ),
+            Some("https://gitlab.com/group/project/-/merge_requests/12".to_string())
+        );
+        assert_eq!(find_pull_request_url("nothing here\n"), None);
+    }
+}
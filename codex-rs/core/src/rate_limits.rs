@@ -0,0 +1,244 @@
+//! Parsing of the rate limit and quota headers sent with model responses.
+
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use reqwest::header::HeaderMap;
+
+use crate::protocol::RateLimitSnapshot;
+use crate::protocol::RateLimitWindow;
+
+/// Reads the `x-ratelimit-*` headers of the OpenAI API and the
+/// `x-codex-*` usage headers sent for ChatGPT plans. Returns `None` when the
+/// response carries none of them.
+pub(crate) fn parse_rate_limits(
+    headers: &HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<RateLimitSnapshot> {
+    let snapshot = RateLimitSnapshot {
+        requests: parse_api_window(headers, "requests", now),
+        tokens: parse_api_window(headers, "tokens", now),
+        plan: parse_plan_window(headers, "primary", now),
+        weekly: parse_plan_window(headers, "secondary", now),
+    };
+    (!snapshot.is_empty()).then_some(snapshot)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+}
+
+fn parse_api_window(
+    headers: &HeaderMap,
+    kind: &str,
+    now: DateTime<Utc>,
+) -> Option<RateLimitWindow> {
+    let limit = header(headers, &format!("x-ratelimit-limit-{kind}"))?
+        .parse::<u64>()
+        .ok()?;
+    let remaining = header(headers, &format!("x-ratelimit-remaining-{kind}"))?
+        .parse::<u64>()
+        .ok()?;
+    let used_percent = if limit == 0 {
+        100.0
+    } else {
+        limit.saturating_sub(remaining) as f64 * 100.0 / limit as f64
+    };
+    let window_resets_at = header(headers, &format!("x-ratelimit-reset-{kind}"))
+        .and_then(parse_reset_duration)
+        .map(|delay| resets_at(now, delay));
+    Some(RateLimitWindow {
+        used_percent,
+        window_resets_at,
+        limit: Some(limit),
+        remaining: Some(remaining),
+    })
+}
+
+fn parse_plan_window(
+    headers: &HeaderMap,
+    kind: &str,
+    now: DateTime<Utc>,
+) -> Option<RateLimitWindow> {
+    let used_percent = header(headers, &format!("x-codex-{kind}-used-percent"))?
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| percent.is_finite())?;
+    let window_resets_at = header(headers, &format!("x-codex-{kind}-reset-after-seconds"))
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|secs| resets_at(now, Duration::from_secs(secs)));
+    Some(RateLimitWindow {
+        used_percent: used_percent.clamp(0.0, 100.0),
+        window_resets_at,
+        limit: None,
+        remaining: None,
+    })
+}
+
+fn resets_at(now: DateTime<Utc>, delay: Duration) -> u64 {
+    let now = u64::try_from(now.timestamp()).unwrap_or(0);
+    // Round partial seconds up so the window has reset by then.
+    now.saturating_add(delay.as_millis().div_ceil(1_000) as u64)
+}
+
+/// Parses the reset durations of the OpenAI API, e.g. `"20ms"`, `"1s"`,
+/// `"6m0s"` or `"1h2m3.5s"`.
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0_f64;
+    let mut rest = value;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds_per_unit = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3_600.0,
+            "d" => 86_400.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        total += number * seconds_per_unit;
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+/// When the limit behind a 429 lifts: the latest reset among the exhausted
+/// windows, or among all windows when none is reported as exhausted.
+pub(crate) fn limit_resets_at(snapshot: &RateLimitSnapshot) -> Option<u64> {
+    let exhausted = snapshot
+        .windows()
+        .filter(|(_, window)| window.remaining == Some(0) || window.used_percent >= 100.0)
+        .filter_map(|(_, window)| window.window_resets_at)
+        .max();
+    exhausted.or_else(|| {
+        snapshot
+            .windows()
+            .filter_map(|(_, window)| window.window_resets_at)
+            .max()
+    })
+}
+
+/// Time from `now` until the Unix time `resets_at`, zero if it has passed.
+pub(crate) fn until(resets_at: u64, now: DateTime<Utc>) -> Duration {
+    let now = u64::try_from(now.timestamp()).unwrap_or(0);
+    Duration::from_secs(resets_at.saturating_sub(now))
+}
+
+/// Formats a wait such as `45s`, `3m 5s`, `2h 10m` or `3d 4h`.
+pub(crate) fn format_wait(wait: Duration) -> String {
+    let secs = wait.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m {}s", secs / 60, secs % 60),
+        3_600..86_400 => format!("{}h {}m", secs / 3_600, secs % 3_600 / 60),
+        _ => format!("{}d {}h", secs / 86_400, secs % 86_400 / 3_600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn parses_api_and_plan_windows() {
+        let now = DateTime::from_timestamp(1_000, 0).expect("valid timestamp");
+        let headers = headers(&[
+            ("x-ratelimit-limit-requests", "500"),
+            ("x-ratelimit-remaining-requests", "499"),
+            ("x-ratelimit-reset-requests", "120ms"),
+            ("x-ratelimit-limit-tokens", "30000"),
+            ("x-ratelimit-remaining-tokens", "0"),
+            ("x-ratelimit-reset-tokens", "6m0s"),
+            ("x-codex-secondary-used-percent", "42.5"),
+            ("x-codex-secondary-reset-after-seconds", "86400"),
+        ]);
+        assert_eq!(
+            parse_rate_limits(&headers, now),
+            Some(RateLimitSnapshot {
+                requests: Some(RateLimitWindow {
+                    used_percent: 0.2,
+                    window_resets_at: Some(1_001),
+                    limit: Some(500),
+                    remaining: Some(499),
+                }),
+                tokens: Some(RateLimitWindow {
+                    used_percent: 100.0,
+                    window_resets_at: Some(1_360),
+                    limit: Some(30_000),
+                    remaining: Some(0),
+                }),
+                plan: None,
+                weekly: Some(RateLimitWindow {
+                    used_percent: 42.5,
+                    window_resets_at: Some(87_400),
+                    limit: None,
+                    remaining: None,
+                }),
+            })
+        );
+        assert_eq!(parse_rate_limits(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn parses_reset_durations() {
+        assert_eq!(
+            parse_reset_duration("20ms"),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_reset_duration("1h2m3.5s"),
+            Some(Duration::from_millis(3_723_500))
+        );
+        assert_eq!(parse_reset_duration(""), None);
+        assert_eq!(parse_reset_duration("soon"), None);
+        assert_eq!(parse_reset_duration("5x"), None);
+    }
+
+    #[test]
+    fn limit_resets_when_the_exhausted_window_does() {
+        let snapshot = RateLimitSnapshot {
+            requests: Some(RateLimitWindow {
+                used_percent: 10.0,
+                window_resets_at: Some(2_000),
+                limit: Some(10),
+                remaining: Some(9),
+            }),
+            tokens: Some(RateLimitWindow {
+                used_percent: 100.0,
+                window_resets_at: Some(1_500),
+                limit: Some(10),
+                remaining: Some(0),
+            }),
+            plan: None,
+            weekly: None,
+        };
+        assert_eq!(limit_resets_at(&snapshot), Some(1_500));
+        assert_eq!(format_wait(Duration::from_secs(185)), "3m 5s");
+        assert_eq!(format_wait(Duration::from_secs(7_800)), "2h 10m");
+    }
+}
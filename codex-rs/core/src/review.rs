@@ -0,0 +1,135 @@
+//! Review turns: the model reads the diff of the working tree against a base
+//! ref and reports its findings in a JSON block at the end of its final
+//! message.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::git_info::git_diff_against_base;
+use crate::protocol::InputItem;
+use crate::protocol::ReviewFinding;
+
+/// Base instructions for review turns.
+pub(crate) const REVIEW_PROMPT: &str = include_str!("review_prompt.md");
+
+/// Builds the input of a review turn: a short request naming the base and
+/// the diff itself.
+pub(crate) async fn review_input(cwd: &Path, base: &str) -> Result<Vec<InputItem>, String> {
+    let Some(state) = git_diff_against_base(cwd, base).await else {
+        return Err(format!(
+            "cannot review against `{base}`: not a git ref with a common ancestor of HEAD"
+        ));
+    };
+    if state.diff.trim().is_empty() {
+        return Err(format!("nothing to review: no changes against `{base}`"));
+    }
+    Ok(vec![
+        InputItem::Text {
+            text: format!(
+                "Review the changes in the working tree against `{base}` (merge base {}).",
+                state.sha.0
+            ),
+        },
+        InputItem::Text {
+            text: format!("```diff\n{}\n```", state.diff.trim_end()),
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct ReviewOutput {
+    findings: Vec<ReviewFinding>,
+}
+
+/// Parses the findings from the last fenced `json` block of `message`.
+pub(crate) fn parse_review_findings(message: &str) -> Result<Vec<ReviewFinding>, String> {
+    const FENCE: &str = "```json";
+    let Some(start) = message.rfind(FENCE) else {
+        return Err("review did not end with a JSON findings block".to_string());
+    };
+    let block = &message[start + FENCE.len()..];
+    let block = block.find("```").map_or(block, |end| &block[..end]);
+    let output: ReviewOutput = serde_json::from_str(block.trim())
+        .map_err(|e| format!("failed to parse review findings: {e}"))?;
+    for finding in &output.findings {
+        if finding.line_end < finding.line_start {
+            return Err(format!(
+                "failed to parse review findings: {}:{}-{} ends before it starts",
+                finding.file, finding.line_start, finding.line_end
+            ));
+        }
+    }
+    Ok(output.findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ReviewSeverity;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_last_json_block() {
+        let message = r#"The example in the prompt:
+
+```json
+{"findings": []}
+```
+
+Two problems:
+
+```json
+{
+  "findings": [
+    {
+      "file": "src/lib.rs",
+      "line_start": 3,
+      "line_end": 4,
+      "severity": "blocking",
+      "comment": "Unwraps user input."
+    },
+    {
+      "file": "README.md",
+      "line_start": 10,
+      "line_end": 10,
+      "severity": "nit",
+      "comment": "Typo."
+    }
+  ]
+}
+```
+"#;
+        assert_eq!(
+            parse_review_findings(message),
+            Ok(vec![
+                ReviewFinding {
+                    file: "src/lib.rs".to_string(),
+                    line_start: 3,
+                    line_end: 4,
+                    severity: ReviewSeverity::Blocking,
+                    comment: "Unwraps user input.".to_string(),
+                },
+                ReviewFinding {
+                    file: "README.md".to_string(),
+                    line_start: 10,
+                    line_end: 10,
+                    severity: ReviewSeverity::Nit,
+                    comment: "Typo.".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_blocks() {
+        assert!(parse_review_findings("Looks good to me.").is_err());
+        assert!(
+            parse_review_findings("```json\n{\"findings\": [{\"file\": \"a\"}]}\n```").is_err()
+        );
+        let backwards = r#"```json
+{"findings": [{"file": "a", "line_start": 5, "line_end": 2, "severity": "nit", "comment": "x"}]}
+```"#;
+        assert!(parse_review_findings(backwards).is_err());
+    }
+}
@@ -1,13 +1,21 @@
 //! Persist Codex session rollouts (.jsonl) so sessions can be replayed or inspected later.
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::fs::{self};
+use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Error as IoError;
 use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use base64::Engine;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use sha1::Digest;
+use sha1::Sha1;
 use time::OffsetDateTime;
 use time::format_description::FormatItem;
 use time::macros::format_description;
@@ -15,22 +23,56 @@ use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::{self};
 use tokio::sync::oneshot;
+use tokio::time::Instant;
 use tracing::info;
 use tracing::warn;
 use uuid::Uuid;
 
+use crate::client_common::USER_INSTRUCTIONS_START;
 use crate::config::Config;
+use crate::config_types::RolloutToml;
+use crate::environment_context::ENVIRONMENT_CONTEXT_START;
 use crate::git_info::GitInfo;
 use crate::git_info::collect_git_info;
+use crate::image_input::parse_data_url;
+use crate::protocol::TokenUsage;
+use crate::session_index::SessionIndexEntry;
+use crate::session_index::SessionIndexWriter;
+use crate::session_index::summarize_rollout;
+use codex_protocol::models::ContentItem;
 use codex_protocol::models::ResponseItem;
 
-const SESSIONS_SUBDIR: &str = "sessions";
+pub(crate) const SESSIONS_SUBDIR: &str = "sessions";
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+/// Directory, next to the rollout files, that holds the images attached to
+/// user messages. Rollouts refer to them instead of embedding the base64 data.
+const IMAGES_SUBDIR: &str = "images";
+
+/// Prefix of the `image_url` recorded for an image stored in
+/// [`IMAGES_SUBDIR`], e.g. `codex-image:3f2a….png`.
+const IMAGE_REF_PREFIX: &str = "codex-image:";
+
+/// Prefix of the `image_url` recorded for an image the model looked at with
+/// the `view_image` tool. The file is referred to in place, by its SHA-1 and
+/// path, e.g. `codex-image-file:3f2a…:/work/plot.png`.
+const IMAGE_FILE_REF_PREFIX: &str = "codex-image-file:";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SessionMeta {
     pub id: Uuid,
     pub timestamp: String,
     pub instructions: Option<String>,
+    /// Model the session was started with. Absent in older rollouts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Id of the model provider the session was started with. Absent in older
+    /// rollouts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_provider: Option<String>,
+    /// Working directory the session was started in. Absent in older
+    /// rollouts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
 }
 
 #[derive(Serialize)]
@@ -44,6 +86,38 @@ struct SessionMetaWithGit {
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct SessionStateSnapshot {}
 
+/// Files the user let a single patch write outside the sandbox's writable
+/// roots. Recorded for auditing; resuming a session does not restore it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WriteExemption {
+    pub call_id: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Lines recorded for post-mortem inspection only. They are tagged with a
+/// `record_type` and skipped when the rollout is replayed.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "record_type", rename_all = "snake_case")]
+enum AuditRecord {
+    WriteExemption(WriteExemption),
+    /// The session's scratch directory, exported as `CODEX_SCRATCH_DIR`.
+    ScratchDir {
+        path: PathBuf,
+    },
+    Usage(UsageRecord),
+}
+
+/// Tokens used by one completed model response and their estimated cost,
+/// summed up by `codex export`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct UsageRecord {
+    pub model: String,
+    pub token_usage: TokenUsage,
+    /// `None` when the prices of `model` are not known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct SavedSession {
     pub session: SessionMeta,
@@ -54,8 +128,44 @@ pub struct SavedSession {
     pub session_id: Uuid,
 }
 
+/// How often the rollout file is synced to disk. Every entry is written as
+/// one line and handed to the OS as soon as it is recorded, which survives a
+/// crash of Codex; syncing makes it survive a crash of the machine too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutFsync {
+    /// Sync once this many entries have been written since the last sync.
+    pub every_entries: usize,
+    /// Sync once the oldest unsynced entry is this old.
+    pub interval: Duration,
+}
+
+impl Default for RolloutFsync {
+    fn default() -> Self {
+        Self {
+            every_entries: 32,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl From<&RolloutToml> for RolloutFsync {
+    fn from(toml: &RolloutToml) -> Self {
+        let default = Self::default();
+        Self {
+            every_entries: toml
+                .fsync_every_entries
+                .unwrap_or(default.every_entries)
+                .max(1),
+            interval: toml
+                .fsync_interval_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.interval),
+        }
+    }
+}
+
 /// Records all [`ResponseItem`]s for a session and flushes them to disk after
-/// every update.
+/// every update, syncing them as often as [`RolloutFsync`] says.
 ///
 /// Rollouts are recorded as JSONL and can be inspected with tools such as:
 ///
@@ -66,12 +176,22 @@ pub struct SavedSession {
 #[derive(Clone)]
 pub(crate) struct RolloutRecorder {
     tx: Sender<RolloutCmd>,
+    path: PathBuf,
 }
 
 enum RolloutCmd {
     AddItems(Vec<ResponseItem>),
     UpdateState(SessionStateSnapshot),
-    Shutdown { ack: oneshot::Sender<()> },
+    AddAuditRecord(AuditRecord),
+    /// The image with SHA-1 `digest` was read from `path`, so it can be
+    /// recorded as a reference to the file rather than a copy.
+    ViewedImage {
+        digest: String,
+        path: PathBuf,
+    },
+    Shutdown {
+        ack: oneshot::Sender<()>,
+    },
 }
 
 impl RolloutRecorder {
@@ -85,6 +205,7 @@ impl RolloutRecorder {
     ) -> std::io::Result<Self> {
         let LogFileInfo {
             file,
+            path,
             session_id,
             timestamp,
         } = create_log_file(config, uuid)?;
@@ -104,21 +225,32 @@ impl RolloutRecorder {
         // perform *blocking* I/O on the caller's thread.
         let (tx, rx) = mpsc::channel::<RolloutCmd>(256);
 
+        let meta = SessionMeta {
+            timestamp,
+            id: session_id,
+            instructions,
+            model: Some(config.model.clone()),
+            model_provider: Some(config.model_provider_id.clone()),
+            cwd: Some(cwd.clone()),
+        };
+        let index = SessionIndexWriter::new(
+            config.dirs.state_dir(),
+            SessionIndexEntry::new(path.clone(), &meta),
+        );
+
         // Spawn a Tokio task that owns the file handle and performs async
         // writes. Using `tokio::fs::File` keeps everything on the async I/O
         // driver instead of blocking the runtime.
         tokio::task::spawn(rollout_writer(
-            tokio::fs::File::from_std(file),
+            JsonlWriter::new(tokio::fs::File::from_std(file), config.rollout_fsync, 0),
+            images_dir(&path),
             rx,
-            Some(SessionMeta {
-                timestamp,
-                id: session_id,
-                instructions,
-            }),
+            Some(meta),
             cwd,
+            index,
         ));
 
-        Ok(Self { tx })
+        Ok(Self { tx, path })
     }
 
     pub(crate) async fn record_items(&self, items: &[ResponseItem]) -> std::io::Result<()> {
@@ -157,77 +289,103 @@ impl RolloutRecorder {
             .map_err(|e| IoError::other(format!("failed to queue rollout state: {e}")))
     }
 
+    pub(crate) async fn record_write_exemption(
+        &self,
+        exemption: WriteExemption,
+    ) -> std::io::Result<()> {
+        self.record_audit(AuditRecord::WriteExemption(exemption))
+            .await
+    }
+
+    pub(crate) async fn record_scratch_dir(&self, path: PathBuf) -> std::io::Result<()> {
+        self.record_audit(AuditRecord::ScratchDir { path }).await
+    }
+
+    pub(crate) async fn record_usage(&self, usage: UsageRecord) -> std::io::Result<()> {
+        self.record_audit(AuditRecord::Usage(usage)).await
+    }
+
+    /// Notes that `bytes` were read from `path` by the `view_image` tool. The
+    /// message that carries them is then recorded with the path and a hash of
+    /// the contents instead of the image itself.
+    pub(crate) async fn record_viewed_image(
+        &self,
+        path: PathBuf,
+        bytes: &[u8],
+    ) -> std::io::Result<()> {
+        let digest = format!("{:x}", Sha1::digest(bytes));
+        self.tx
+            .send(RolloutCmd::ViewedImage { digest, path })
+            .await
+            .map_err(|e| IoError::other(format!("failed to queue viewed image: {e}")))
+    }
+
+    async fn record_audit(&self, record: AuditRecord) -> std::io::Result<()> {
+        self.tx
+            .send(RolloutCmd::AddAuditRecord(record))
+            .await
+            .map_err(|e| IoError::other(format!("failed to queue rollout record: {e}")))
+    }
+
+    /// Continue recording to the rollout at `path`, keeping its entry in the
+    /// session index of `state_dir` up to date.
     pub async fn resume(
         path: &Path,
+        state_dir: &Path,
         cwd: std::path::PathBuf,
+        fsync: RolloutFsync,
     ) -> std::io::Result<(Self, SavedSession)> {
         info!("Resuming rollout from {path:?}");
-        let text = tokio::fs::read_to_string(path).await?;
-        let mut lines = text.lines();
-        let meta_line = lines
-            .next()
-            .ok_or_else(|| IoError::other("empty session file"))?;
-        let session: SessionMeta = serde_json::from_str(meta_line)
-            .map_err(|e| IoError::other(format!("failed to parse session meta: {e}")))?;
-        let mut items = Vec::new();
-        let mut state = SessionStateSnapshot::default();
-
-        for line in lines {
-            if line.trim().is_empty() {
-                continue;
-            }
-            let v: Value = match serde_json::from_str(line) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            if v.get("record_type")
-                .and_then(|rt| rt.as_str())
-                .map(|s| s == "state")
-                .unwrap_or(false)
-            {
-                if let Ok(s) = serde_json::from_value::<SessionStateSnapshot>(v.clone()) {
-                    state = s
-                }
-                continue;
-            }
-            match serde_json::from_value::<ResponseItem>(v.clone()) {
-                Ok(item) => match item {
-                    ResponseItem::Message { .. }
-                    | ResponseItem::LocalShellCall { .. }
-                    | ResponseItem::FunctionCall { .. }
-                    | ResponseItem::FunctionCallOutput { .. }
-                    | ResponseItem::CustomToolCall { .. }
-                    | ResponseItem::CustomToolCallOutput { .. }
-                    | ResponseItem::Reasoning { .. } => items.push(item),
-                    ResponseItem::WebSearchCall { .. } | ResponseItem::Other => {}
-                },
-                Err(e) => {
-                    warn!("failed to parse item: {v:?}, error: {e}");
-                }
-            }
-        }
-
-        let saved = SavedSession {
-            session: session.clone(),
-            items: items.clone(),
-            state: state.clone(),
-            session_id: session.id,
-        };
+        // New entries must not be appended to a line cut short by a crash.
+        recover_rollout(path)?;
+        let saved = load_rollout(path).await?;
+        let index = SessionIndexWriter::new(state_dir, summarize_rollout(path)?);
 
         let file = std::fs::OpenOptions::new()
             .append(true)
             .read(true)
             .open(path)?;
+        let len = file.metadata()?.len();
 
         let (tx, rx) = mpsc::channel::<RolloutCmd>(256);
         tokio::task::spawn(rollout_writer(
-            tokio::fs::File::from_std(file),
+            JsonlWriter::new(tokio::fs::File::from_std(file), fsync, len),
+            images_dir(path),
             rx,
             None,
             cwd,
+            index,
         ));
         info!("Resumed rollout successfully from {path:?}");
-        Ok((Self { tx }, saved))
+        Ok((
+            Self {
+                tx,
+                path: path.to_path_buf(),
+            },
+            saved,
+        ))
+    }
+
+    /// Start a new rollout, with a fresh session id, that begins with the
+    /// history of the rollout at `path`. The original file is left untouched.
+    pub async fn fork(config: &Config, path: &Path) -> std::io::Result<(Self, SavedSession)> {
+        info!("Forking rollout from {path:?}");
+        let saved = load_rollout(path).await?;
+        let session_id = Uuid::new_v4();
+        let recorder = Self::new(config, session_id, saved.session.instructions.clone()).await?;
+        recorder.record_items(&saved.items).await?;
+        Ok((
+            recorder,
+            SavedSession {
+                session_id,
+                ..saved
+            },
+        ))
+    }
+
+    /// The JSONL file the session is recorded to.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
     }
 
     pub async fn shutdown(&self) -> std::io::Result<()> {
@@ -246,10 +404,371 @@ impl RolloutRecorder {
     }
 }
 
+/// What [`recover_rollout`] kept of a rollout file.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RolloutRecovery {
+    /// Complete entries, the session meta included.
+    pub entries: usize,
+    /// Bytes cut off the end of the file.
+    pub truncated_bytes: u64,
+}
+
+/// Cuts off whatever follows the last complete entry of the rollout at
+/// `path`: a line left unfinished by a crash, bytes that are not UTF-8, or
+/// the zeros some filesystems leave behind after a power loss. Entries are
+/// complete when they are valid JSON followed by a newline.
+pub(crate) fn recover_rollout(path: &Path) -> std::io::Result<RolloutRecovery> {
+    let bytes = fs::read(path)?;
+    let mut entries = 0;
+    let mut complete_len = 0;
+    let mut start = 0;
+    while let Some(newline) = bytes[start..].iter().position(|b| *b == b'\n') {
+        let end = start + newline + 1;
+        if serde_json::from_slice::<serde::de::IgnoredAny>(&bytes[start..end - 1]).is_ok() {
+            entries += 1;
+            complete_len = end;
+        }
+        start = end;
+    }
+
+    let truncated_bytes = (bytes.len() - complete_len) as u64;
+    if truncated_bytes > 0 {
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.set_len(complete_len as u64)?;
+        file.sync_all()?;
+        warn!(
+            "recovered rollout {path:?}: salvaged {entries} entries, dropped {truncated_bytes} trailing bytes"
+        );
+    }
+    Ok(RolloutRecovery {
+        entries,
+        truncated_bytes,
+    })
+}
+
+/// Parse a rollout file written by [`RolloutRecorder`]. Lines that cannot be
+/// parsed, such as an entry cut short by a crash, are skipped.
+async fn load_rollout(path: &Path) -> std::io::Result<SavedSession> {
+    let bytes = tokio::fs::read(path).await?;
+    let images_dir = images_dir(path);
+    let mut lines = bytes.split(|b| *b == b'\n');
+    let meta_line = lines
+        .next()
+        .filter(|line| !line.is_empty())
+        .ok_or_else(|| IoError::other("empty session file"))?;
+    let session: SessionMeta = serde_json::from_slice(meta_line)
+        .map_err(|e| IoError::other(format!("failed to parse session meta: {e}")))?;
+    let mut items = Vec::new();
+    let mut state = SessionStateSnapshot::default();
+
+    for line in lines {
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let v: Value = match serde_json::from_slice(line) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("skipping unreadable rollout line in {path:?}: {e}");
+                continue;
+            }
+        };
+        match v.get("record_type").and_then(|rt| rt.as_str()) {
+            Some("state") => {
+                if let Ok(s) = serde_json::from_value::<SessionStateSnapshot>(v.clone()) {
+                    state = s
+                }
+                continue;
+            }
+            // Audit records, such as write exemptions, are not replayed.
+            Some(_) => continue,
+            None => {}
+        }
+        match serde_json::from_value::<ResponseItem>(v.clone()) {
+            Ok(item) => match item {
+                ResponseItem::Message { id, role, content } => {
+                    let content = load_images(content, &images_dir).await;
+                    items.push(ResponseItem::Message { id, role, content });
+                }
+                ResponseItem::LocalShellCall { .. }
+                | ResponseItem::FunctionCall { .. }
+                | ResponseItem::FunctionCallOutput { .. }
+                | ResponseItem::CustomToolCall { .. }
+                | ResponseItem::CustomToolCallOutput { .. }
+                | ResponseItem::Reasoning { .. } => items.push(item),
+                ResponseItem::WebSearchCall { .. } | ResponseItem::Other => {}
+            },
+            Err(e) => {
+                warn!("failed to parse item: {v:?}, error: {e}");
+            }
+        }
+    }
+
+    Ok(SavedSession {
+        session_id: session.id,
+        session,
+        items,
+        state,
+    })
+}
+
+fn images_dir(rollout_path: &Path) -> PathBuf {
+    rollout_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(IMAGES_SUBDIR)
+}
+
+/// Replaces the data URLs of images in `content` with references to copies
+/// saved in `images_dir`, so the rollout does not carry megabytes of base64.
+/// Images in `viewed_images`, keyed by SHA-1, refer to their files instead.
+/// Images that cannot be saved stay inline.
+async fn store_images(
+    content: Vec<ContentItem>,
+    images_dir: &Path,
+    viewed_images: &HashMap<String, PathBuf>,
+) -> Vec<ContentItem> {
+    let mut stored = Vec::with_capacity(content.len());
+    for item in content {
+        let item = match item {
+            ContentItem::InputImage { image_url } => {
+                match store_image(&image_url, images_dir, viewed_images).await {
+                    Ok(Some(image_ref)) => ContentItem::InputImage {
+                        image_url: image_ref,
+                    },
+                    Ok(None) => ContentItem::InputImage { image_url },
+                    Err(e) => {
+                        warn!("failed to store image for rollout: {e}");
+                        ContentItem::InputImage { image_url }
+                    }
+                }
+            }
+            other => other,
+        };
+        stored.push(item);
+    }
+    stored
+}
+
+/// Saves the image of a data URL under a name derived from its contents and
+/// returns the reference to record. Returns `None` for URLs that are not data
+/// URLs.
+async fn store_image(
+    image_url: &str,
+    images_dir: &Path,
+    viewed_images: &HashMap<String, PathBuf>,
+) -> std::io::Result<Option<String>> {
+    let Some((mime_type, data)) = parse_data_url(image_url) else {
+        return Ok(None);
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(IoError::other)?;
+    let extension = mime_guess::get_mime_extensions_str(mime_type)
+        .and_then(|extensions| extensions.first())
+        .unwrap_or(&"bin");
+    let digest = format!("{:x}", Sha1::digest(&bytes));
+    if let Some(path) = viewed_images.get(&digest) {
+        return Ok(Some(format!(
+            "{IMAGE_FILE_REF_PREFIX}{digest}:{}",
+            path.display()
+        )));
+    }
+    let file_name = format!("{digest}.{extension}");
+
+    let path = images_dir.join(&file_name);
+    if !tokio::fs::try_exists(&path).await? {
+        tokio::fs::create_dir_all(images_dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+    }
+    Ok(Some(format!("{IMAGE_REF_PREFIX}{file_name}")))
+}
+
+/// Inverse of [`store_images`]: turns image references back into data URLs.
+/// An image whose file is gone, or was changed after the model viewed it, is
+/// replaced by a note saying so.
+async fn load_images(content: Vec<ContentItem>, images_dir: &Path) -> Vec<ContentItem> {
+    let mut loaded = Vec::with_capacity(content.len());
+    for item in content {
+        let item = match item {
+            ContentItem::InputImage { image_url } => {
+                if let Some(file_ref) = image_url.strip_prefix(IMAGE_FILE_REF_PREFIX) {
+                    loaded.push(load_viewed_image(file_ref).await);
+                    continue;
+                }
+                match image_url.strip_prefix(IMAGE_REF_PREFIX) {
+                    Some(file_name) => {
+                        let path = images_dir.join(file_name);
+                        match tokio::fs::read(&path).await {
+                            Ok(bytes) => {
+                                let mime_type = mime_guess::from_path(&path)
+                                    .first_or_octet_stream()
+                                    .essence_str()
+                                    .to_owned();
+                                let encoded =
+                                    base64::engine::general_purpose::STANDARD.encode(bytes);
+                                ContentItem::InputImage {
+                                    image_url: format!("data:{mime_type};base64,{encoded}"),
+                                }
+                            }
+                            Err(e) => {
+                                warn!("failed to load image {}: {e}", path.display());
+                                ContentItem::InputText {
+                                    text: format!("[image {file_name} is no longer available]"),
+                                }
+                            }
+                        }
+                    }
+                    None => ContentItem::InputImage { image_url },
+                }
+            }
+            other => other,
+        };
+        loaded.push(item);
+    }
+    loaded
+}
+
+/// Loads an image recorded as `<sha1>:<path>` by [`store_image`].
+async fn load_viewed_image(file_ref: &str) -> ContentItem {
+    let Some((digest, path)) = file_ref.split_once(':') else {
+        return ContentItem::InputText {
+            text: format!("[image {file_ref} is no longer available]"),
+        };
+    };
+    let bytes = match tokio::fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("failed to load viewed image {path}: {e}");
+            return ContentItem::InputText {
+                text: format!("[image {path} is no longer available]"),
+            };
+        }
+    };
+    if format!("{:x}", Sha1::digest(&bytes)) != digest {
+        return ContentItem::InputText {
+            text: format!("[image {path} has changed since it was viewed]"),
+        };
+    }
+    let mime_type = mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_owned();
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    ContentItem::InputImage {
+        image_url: format!("data:{mime_type};base64,{encoded}"),
+    }
+}
+
+/// Read only the [`SessionMeta`] header of a rollout file.
+pub fn read_session_meta(path: &Path) -> std::io::Result<SessionMeta> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut meta_line = String::new();
+    reader.read_line(&mut meta_line)?;
+    if meta_line.trim().is_empty() {
+        return Err(IoError::other("empty session file"));
+    }
+    serde_json::from_str(&meta_line)
+        .map_err(|e| IoError::other(format!("failed to parse session meta: {e}")))
+}
+
+/// Find the rollout file recorded for `session_id` under the `sessions`
+/// directory of `state_dir`.
+pub fn find_rollout_by_id(state_dir: &Path, session_id: Uuid) -> std::io::Result<Option<PathBuf>> {
+    let suffix = format!("-{session_id}.jsonl");
+    Ok(rollout_files(state_dir)?.into_iter().find(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(&suffix))
+    }))
+}
+
+/// All rollout files under `<state_dir>/sessions`, most recent first.
+pub(crate) fn rollout_files(state_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![state_dir.join(SESSIONS_SUBDIR)];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("rollout-") && name.ends_with(".jsonl"))
+            {
+                files.push(path);
+            }
+        }
+    }
+    // File names start with the creation timestamp, so they sort by age.
+    files.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+    Ok(files)
+}
+
+/// Whether `text` is one of the messages Codex itself adds at the start of a
+/// session rather than something the user typed.
+pub(crate) fn is_session_prefix(text: &str) -> bool {
+    text.starts_with(USER_INSTRUCTIONS_START) || text.starts_with(ENVIRONMENT_CONTEXT_START)
+}
+
+/// The part of a recorded history that can be sent back to the model when a
+/// session is resumed.
+///
+/// - Calls whose output was never recorded (the session ended while a tool
+///   was running) and outputs without a matching call are dropped, since the
+///   API rejects both.
+pub(crate) fn replayable_items(items: Vec<ResponseItem>) -> Vec<ResponseItem> {
+    use std::collections::HashSet;
+
+    let mut calls = HashSet::new();
+    let mut outputs = HashSet::new();
+    for item in &items {
+        match item {
+            ResponseItem::FunctionCall { call_id, .. }
+            | ResponseItem::CustomToolCall { call_id, .. } => {
+                calls.insert(call_id.clone());
+            }
+            ResponseItem::LocalShellCall { id, call_id, .. } => {
+                if let Some(call_id) = call_id.as_ref().or(id.as_ref()) {
+                    calls.insert(call_id.clone());
+                }
+            }
+            ResponseItem::FunctionCallOutput { call_id, .. }
+            | ResponseItem::CustomToolCallOutput { call_id, .. } => {
+                outputs.insert(call_id.clone());
+            }
+            _ => {}
+        }
+    }
+
+    items
+        .into_iter()
+        .filter(|item| match item {
+            ResponseItem::FunctionCall { call_id, .. }
+            | ResponseItem::CustomToolCall { call_id, .. } => outputs.contains(call_id),
+            ResponseItem::LocalShellCall { id, call_id, .. } => call_id
+                .as_ref()
+                .or(id.as_ref())
+                .is_some_and(|call_id| outputs.contains(call_id)),
+            ResponseItem::FunctionCallOutput { call_id, .. }
+            | ResponseItem::CustomToolCallOutput { call_id, .. } => calls.contains(call_id),
+            _ => true,
+        })
+        .collect()
+}
+
 struct LogFileInfo {
     /// Opened file handle to the rollout file.
     file: File,
 
+    /// Path of the rollout file.
+    path: PathBuf,
+
     /// Session ID (also embedded in filename).
     session_id: Uuid,
 
@@ -258,11 +777,10 @@ struct LogFileInfo {
 }
 
 fn create_log_file(config: &Config, session_id: Uuid) -> std::io::Result<LogFileInfo> {
-    // Resolve ~/.codex/sessions/YYYY/MM/DD and create it if missing.
+    // Resolve <state dir>/sessions/YYYY/MM/DD and create it if missing.
     let timestamp = OffsetDateTime::now_local()
         .map_err(|e| IoError::other(format!("failed to get local time: {e}")))?;
-    let mut dir = config.codex_home.clone();
-    dir.push(SESSIONS_SUBDIR);
+    let mut dir = config.dirs.state_dir().join(SESSIONS_SUBDIR);
     dir.push(timestamp.year().to_string());
     dir.push(format!("{:02}", u8::from(timestamp.month())));
     dir.push(format!("{:02}", timestamp.day()));
@@ -286,18 +804,21 @@ fn create_log_file(config: &Config, session_id: Uuid) -> std::io::Result<LogFile
 
     Ok(LogFileInfo {
         file,
+        path,
         session_id,
         timestamp,
     })
 }
 
 async fn rollout_writer(
-    file: tokio::fs::File,
+    mut writer: JsonlWriter,
+    images_dir: PathBuf,
     mut rx: mpsc::Receiver<RolloutCmd>,
     mut meta: Option<SessionMeta>,
     cwd: std::path::PathBuf,
+    mut index: SessionIndexWriter,
 ) -> std::io::Result<()> {
-    let mut writer = JsonlWriter { file };
+    let mut viewed_images = HashMap::new();
 
     // If we have a meta, collect git info asynchronously and write meta first
     if let Some(session_meta) = meta.take() {
@@ -309,16 +830,38 @@ async fn rollout_writer(
 
         // Write the SessionMeta as the first item in the file
         writer.write_line(&session_meta_with_git).await?;
+        index.save(writer.len).await;
     }
 
-    // Process rollout commands
-    while let Some(cmd) = rx.recv().await {
+    // Process rollout commands, syncing when unsynced entries have waited
+    // long enough.
+    loop {
+        let cmd = match writer.sync_deadline() {
+            Some(deadline) => match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(cmd) => cmd,
+                Err(_) => {
+                    writer.sync().await?;
+                    continue;
+                }
+            },
+            None => rx.recv().await,
+        };
+        let Some(cmd) = cmd else {
+            break;
+        };
         match cmd {
             RolloutCmd::AddItems(items) => {
+                let turns = index.entry.turns;
                 for item in items {
+                    index.entry.record_item(&item);
                     match item {
-                        ResponseItem::Message { .. }
-                        | ResponseItem::LocalShellCall { .. }
+                        ResponseItem::Message { id, role, content } => {
+                            let content = store_images(content, &images_dir, &viewed_images).await;
+                            writer
+                                .write_line(&ResponseItem::Message { id, role, content })
+                                .await?;
+                        }
+                        ResponseItem::LocalShellCall { .. }
                         | ResponseItem::FunctionCall { .. }
                         | ResponseItem::FunctionCallOutput { .. }
                         | ResponseItem::CustomToolCall { .. }
@@ -329,6 +872,9 @@ async fn rollout_writer(
                         ResponseItem::WebSearchCall { .. } | ResponseItem::Other => {}
                     }
                 }
+                if index.entry.turns != turns {
+                    index.save(writer.len).await;
+                }
             }
             RolloutCmd::UpdateState(state) => {
                 #[derive(Serialize)]
@@ -344,25 +890,442 @@ async fn rollout_writer(
                     })
                     .await?;
             }
+            RolloutCmd::AddAuditRecord(record) => {
+                writer.write_line(&record).await?;
+                if let AuditRecord::Usage(usage) = &record {
+                    index.entry.record_usage(usage);
+                    index.save(writer.len).await;
+                }
+            }
+            RolloutCmd::ViewedImage { digest, path } => {
+                viewed_images.insert(digest, path);
+            }
             RolloutCmd::Shutdown { ack } => {
+                writer.sync().await?;
+                index.save(writer.len).await;
                 let _ = ack.send(());
             }
         }
     }
 
-    Ok(())
+    writer.sync().await
 }
 
+/// Writes one JSON entry per line. An entry never contains a raw newline, so
+/// a crash can only leave the last line incomplete; see [`recover_rollout`].
 struct JsonlWriter {
     file: tokio::fs::File,
+    fsync: RolloutFsync,
+    /// Entries written since the last sync.
+    unsynced: usize,
+    /// When the oldest of them was written.
+    unsynced_since: Option<Instant>,
+    /// Bytes in the file, which the session index records.
+    len: u64,
 }
 
 impl JsonlWriter {
+    fn new(file: tokio::fs::File, fsync: RolloutFsync, len: u64) -> Self {
+        Self {
+            file,
+            fsync,
+            unsynced: 0,
+            unsynced_since: None,
+            len,
+        }
+    }
+
     async fn write_line(&mut self, item: &impl serde::Serialize) -> std::io::Result<()> {
         let mut json = serde_json::to_string(item)?;
         json.push('\n');
-        let _ = self.file.write_all(json.as_bytes()).await;
+        self.file.write_all(json.as_bytes()).await?;
         self.file.flush().await?;
+        self.len += json.len() as u64;
+        self.unsynced += 1;
+        self.unsynced_since.get_or_insert_with(Instant::now);
+        if self.unsynced >= self.fsync.every_entries {
+            self.sync().await?;
+        }
         Ok(())
     }
+
+    /// When the entries written since the last sync must be synced.
+    fn sync_deadline(&self) -> Option<Instant> {
+        self.unsynced_since.map(|since| since + self.fsync.interval)
+    }
+
+    async fn sync(&mut self) -> std::io::Result<()> {
+        if self.unsynced > 0 {
+            self.file.sync_data().await?;
+        }
+        self.unsynced = 0;
+        self.unsynced_since = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_index::list_sessions;
+    use codex_protocol::models::FunctionCallOutputPayload;
+    use codex_protocol::models::LocalShellAction;
+    use codex_protocol::models::LocalShellExecAction;
+    use codex_protocol::models::LocalShellStatus;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn user_message(text: &str) -> ResponseItem {
+        ResponseItem::Message {
+            id: None,
+            role: "user".to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    fn function_call(call_id: &str) -> ResponseItem {
+        ResponseItem::FunctionCall {
+            id: None,
+            name: "shell".to_string(),
+            arguments: "{}".to_string(),
+            call_id: call_id.to_string(),
+        }
+    }
+
+    fn function_call_output(call_id: &str) -> ResponseItem {
+        ResponseItem::FunctionCallOutput {
+            call_id: call_id.to_string(),
+            output: FunctionCallOutputPayload {
+                content: "ok".to_string(),
+                success: Some(true),
+            },
+        }
+    }
+
+    #[test]
+    fn replayable_items_drop_unmatched_calls() {
+        let local_shell_call = ResponseItem::LocalShellCall {
+            id: Some("shell-1".to_string()),
+            call_id: None,
+            status: LocalShellStatus::Completed,
+            action: LocalShellAction::Exec(LocalShellExecAction {
+                command: vec!["ls".to_string()],
+                timeout_ms: None,
+                working_directory: None,
+                env: None,
+                user: None,
+            }),
+        };
+        let items = vec![
+            user_message("<environment_context>\n  <cwd>/old</cwd>\n</environment_context>"),
+            user_message("hi"),
+            function_call("call-1"),
+            function_call_output("call-1"),
+            local_shell_call.clone(),
+            function_call_output("shell-1"),
+            function_call_output("call-orphan"),
+            function_call("call-dangling"),
+        ];
+
+        assert_eq!(
+            replayable_items(items),
+            vec![
+                user_message("<environment_context>\n  <cwd>/old</cwd>\n</environment_context>"),
+                user_message("hi"),
+                function_call("call-1"),
+                function_call_output("call-1"),
+                local_shell_call,
+                function_call_output("shell-1"),
+            ]
+        );
+    }
+
+    fn write_rollout(codex_home: &Path, day: &str, file_stem: &str, id: Uuid, first_message: &str) {
+        let dir = codex_home.join(SESSIONS_SUBDIR).join(day);
+        fs::create_dir_all(&dir).unwrap();
+        let meta = serde_json::json!({
+            "id": id,
+            "timestamp": "2025-08-01T10:00:00.000Z",
+            "instructions": null,
+        });
+        let instructions = serde_json::to_string(&user_message(
+            "<user_instructions>\n\nbe nice\n\n</user_instructions>",
+        ))
+        .unwrap();
+        let message = serde_json::to_string(&user_message(first_message)).unwrap();
+        fs::write(
+            dir.join(format!("rollout-{file_stem}-{id}.jsonl")),
+            format!("{meta}\n{instructions}\n{message}\n"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn rollouts_are_listed_newest_first_and_found_by_id() {
+        let codex_home = TempDir::new().unwrap();
+        let older = Uuid::new_v4();
+        let newer = Uuid::new_v4();
+        write_rollout(
+            codex_home.path(),
+            "2025/08/01",
+            "2025-08-01T10-00-00",
+            older,
+            "first task",
+        );
+        write_rollout(
+            codex_home.path(),
+            "2025/08/02",
+            "2025-08-02T09-00-00",
+            newer,
+            "second task",
+        );
+
+        let summaries = list_sessions(codex_home.path()).unwrap();
+        let listed = summaries
+            .iter()
+            .map(|s| (s.id, s.first_user_message.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            vec![(newer, Some("second task")), (older, Some("first task"))]
+        );
+
+        let found = find_rollout_by_id(codex_home.path(), older)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, summaries[1].path);
+        assert_eq!(
+            find_rollout_by_id(codex_home.path(), Uuid::new_v4()).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn audit_records_are_not_replayed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rollout.jsonl");
+        let meta = serde_json::to_string(&SessionMeta::default()).unwrap();
+        let exemption = serde_json::to_string(&AuditRecord::WriteExemption(WriteExemption {
+            call_id: "call-1".to_string(),
+            paths: vec![PathBuf::from("/outside/notes.txt")],
+        }))
+        .unwrap();
+        assert_eq!(
+            exemption,
+            r#"{"record_type":"write_exemption","call_id":"call-1","paths":["/outside/notes.txt"]}"#
+        );
+        let scratch = serde_json::to_string(&AuditRecord::ScratchDir {
+            path: PathBuf::from("/cache/scratch/1"),
+        })
+        .unwrap();
+        let message = serde_json::to_string(&user_message("hi")).unwrap();
+        std::fs::write(
+            &path,
+            format!("{meta}\n{exemption}\n{scratch}\n{message}\n"),
+        )
+        .unwrap();
+
+        let saved = load_rollout(&path).await.unwrap();
+        assert_eq!(saved.items, vec![user_message("hi")]);
+    }
+
+    /// A rollout with the session meta and two messages.
+    fn complete_rollout() -> Vec<u8> {
+        let meta = serde_json::to_string(&SessionMeta::default()).unwrap();
+        let hi = serde_json::to_string(&user_message("hi")).unwrap();
+        let there = serde_json::to_string(&user_message("there")).unwrap();
+        format!("{meta}\n{hi}\n{there}\n").into_bytes()
+    }
+
+    #[tokio::test]
+    async fn recovery_cuts_off_a_corrupt_tail() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rollout.jsonl");
+        let tails: [&[u8]; 5] = [
+            b"",
+            br#"{"type":"message","role":"user","content":[{"type":"input_te"#,
+            b"{\"type\":\"message\",\"text\":\"\xff\xfe\"}\n",
+            b"\0\0\0\0\0\0\0\0",
+            b"\n\n\0\0\0\0\n",
+        ];
+        for tail in tails {
+            let mut contents = complete_rollout();
+            contents.extend_from_slice(tail);
+            std::fs::write(&path, &contents).unwrap();
+
+            assert_eq!(
+                recover_rollout(&path).unwrap(),
+                RolloutRecovery {
+                    entries: 3,
+                    truncated_bytes: tail.len() as u64,
+                },
+                "{tail:?}"
+            );
+            assert_eq!(std::fs::read(&path).unwrap(), complete_rollout());
+            let saved = load_rollout(&path).await.unwrap();
+            assert_eq!(saved.items, vec![user_message("hi"), user_message("there")]);
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_lines_are_skipped_when_loading() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rollout.jsonl");
+        let mut contents = complete_rollout();
+        contents.extend_from_slice(b"\xff\xfe\0\n");
+        contents.extend_from_slice(
+            serde_json::to_string(&user_message("again"))
+                .unwrap()
+                .as_bytes(),
+        );
+        contents.extend_from_slice(b"\n{\"type\":\"mess");
+        std::fs::write(&path, &contents).unwrap();
+
+        let saved = load_rollout(&path).await.unwrap();
+        assert_eq!(
+            saved.items,
+            vec![
+                user_message("hi"),
+                user_message("there"),
+                user_message("again")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resumed_rollout_continues_after_the_last_complete_entry() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rollout.jsonl");
+        let mut contents = complete_rollout();
+        contents.extend_from_slice(br#"{"type":"message","role":"assis"#);
+        std::fs::write(&path, &contents).unwrap();
+
+        let fsync = RolloutFsync {
+            every_entries: 1,
+            interval: Duration::from_secs(1),
+        };
+        fs::create_dir(dir.path().join(SESSIONS_SUBDIR)).unwrap();
+        let (recorder, saved) =
+            RolloutRecorder::resume(&path, dir.path(), dir.path().to_path_buf(), fsync)
+                .await
+                .unwrap();
+        assert_eq!(saved.items, vec![user_message("hi"), user_message("there")]);
+        recorder
+            .record_items(&[user_message("after the crash")])
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let saved = load_rollout(&path).await.unwrap();
+        assert_eq!(
+            saved.items,
+            vec![
+                user_message("hi"),
+                user_message("there"),
+                user_message("after the crash")
+            ]
+        );
+        assert_eq!(
+            recover_rollout(&path).unwrap(),
+            RolloutRecovery {
+                entries: 4,
+                truncated_bytes: 0,
+            }
+        );
+
+        // The session index follows the resumed rollout.
+        let index =
+            fs::read_to_string(dir.path().join(SESSIONS_SUBDIR).join("index.jsonl")).unwrap();
+        let entry: SessionIndexEntry = serde_json::from_str(index.lines().last().unwrap()).unwrap();
+        assert_eq!(entry.turns, 3);
+        assert_eq!(entry.last_user_message.as_deref(), Some("after the crash"));
+        assert_eq!(entry.rollout_len, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn fsync_settings_come_from_config() {
+        assert_eq!(
+            RolloutFsync::from(&RolloutToml {
+                fsync_every_entries: Some(0),
+                fsync_interval_ms: Some(250),
+            }),
+            RolloutFsync {
+                every_entries: 1,
+                interval: Duration::from_millis(250),
+            }
+        );
+        assert_eq!(
+            RolloutFsync::from(&RolloutToml::default()),
+            RolloutFsync::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn images_are_stored_next_to_the_rollout() {
+        let dir = TempDir::new().unwrap();
+        let images_dir = dir.path().join(IMAGES_SUBDIR);
+        let data_url = "data:image/png;base64,iVBORw0KGgo=".to_string();
+        let content = vec![
+            ContentItem::InputText {
+                text: "what is this?".to_string(),
+            },
+            ContentItem::InputImage {
+                image_url: data_url.clone(),
+            },
+        ];
+
+        let stored = store_images(content.clone(), &images_dir, &HashMap::new()).await;
+        let ContentItem::InputImage { image_url } = &stored[1] else {
+            panic!("expected an image");
+        };
+        let file_name = image_url.strip_prefix(IMAGE_REF_PREFIX).unwrap();
+        assert!(file_name.ends_with(".png"));
+        assert_eq!(
+            std::fs::read(images_dir.join(file_name)).unwrap(),
+            b"\x89PNG\r\n\x1a\n"
+        );
+
+        assert_eq!(load_images(stored.clone(), &images_dir).await, content);
+
+        std::fs::remove_dir_all(&images_dir).unwrap();
+        assert_eq!(
+            load_images(stored, &images_dir).await[1],
+            ContentItem::InputText {
+                text: format!("[image {file_name} is no longer available]"),
+            }
+        );
+    }
+    #[tokio::test]
+    async fn viewed_images_refer_to_their_file() {
+        let dir = TempDir::new().unwrap();
+        let images_dir = dir.path().join(IMAGES_SUBDIR);
+        let plot = dir.path().join("plot.png");
+        std::fs::write(&plot, b"\x89PNG\r\n\x1a\n").unwrap();
+        let data_url = "data:image/png;base64,iVBORw0KGgo=".to_string();
+        let digest = format!("{:x}", Sha1::digest(b"\x89PNG\r\n\x1a\n"));
+        let viewed_images = HashMap::from([(digest.clone(), plot.clone())]);
+        let content = vec![ContentItem::InputImage {
+            image_url: data_url.clone(),
+        }];
+
+        let stored = store_images(content.clone(), &images_dir, &viewed_images).await;
+        assert_eq!(
+            stored,
+            vec![ContentItem::InputImage {
+                image_url: format!("{IMAGE_FILE_REF_PREFIX}{digest}:{}", plot.display()),
+            }]
+        );
+        assert!(!images_dir.exists());
+        assert_eq!(load_images(stored.clone(), &images_dir).await, content);
+
+        std::fs::write(&plot, b"GIF89a").unwrap();
+        assert_eq!(
+            load_images(stored, &images_dir).await,
+            vec![ContentItem::InputText {
+                text: format!("[image {} has changed since it was viewed]", plot.display()),
+            }]
+        );
+    }
 }
@@ -1,4 +1,3 @@
-use std::collections::HashSet;
 use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
@@ -6,15 +5,19 @@ use std::path::PathBuf;
 use codex_apply_patch::ApplyPatchAction;
 use codex_apply_patch::ApplyPatchFileChange;
 
+use crate::approved_commands::ApprovedCommands;
 use crate::exec::SandboxType;
-use crate::is_safe_command::is_known_safe_command;
+use crate::is_network_command::is_network_command;
+use crate::landlock::NetworkEnforcement;
+use crate::landlock::network_enforcement;
 use crate::protocol::AskForApproval;
+use crate::protocol::EscalationReason;
 use crate::protocol::SandboxPolicy;
 
 #[derive(Debug, PartialEq)]
 pub enum SafetyCheck {
     AutoApprove { sandbox_type: SandboxType },
-    AskUser,
+    AskUser { reason: EscalationReason },
     Reject { reason: String },
 }
 
@@ -30,6 +33,23 @@ pub fn assess_patch_safety(
         };
     }
 
+    // Sensitive locations stay off limits, approval or not, unless the
+    // sandbox is disabled altogether.
+    if !matches!(sandbox_policy, SandboxPolicy::DangerFullAccess) {
+        let home = dirs::home_dir();
+        if let Some(path) = paths_outside_writable_roots(action, sandbox_policy, cwd)
+            .into_iter()
+            .find(|path| is_sensitive_path(path, home.as_deref()))
+        {
+            return SafetyCheck::Reject {
+                reason: format!(
+                    "{} is in a protected location; writing there requires full access",
+                    path.display()
+                ),
+            };
+        }
+    }
+
     match policy {
         AskForApproval::OnFailure | AskForApproval::Never | AskForApproval::OnRequest => {
             // Continue to see if this can be auto-approved.
@@ -37,7 +57,9 @@ pub fn assess_patch_safety(
         // TODO(ragona): I'm not sure this is actually correct? I believe in this case
         // we want to continue to the writable paths check before asking the user.
         AskForApproval::UnlessTrusted => {
-            return SafetyCheck::AskUser;
+            return SafetyCheck::AskUser {
+                reason: EscalationReason::UnrecognizedCommand,
+            };
         }
     }
 
@@ -45,15 +67,16 @@ pub fn assess_patch_safety(
     // is possible that paths in the patch are hard links to files outside the
     // writable roots, so we should still run `apply_patch` in a sandbox in that
     // case.
-    if is_write_patch_constrained_to_writable_paths(action, sandbox_policy, cwd)
-        || policy == AskForApproval::OnFailure
-    {
+    let outside = paths_outside_writable_roots(action, sandbox_policy, cwd);
+    if outside.is_empty() {
         // Only auto‑approve when we can actually enforce a sandbox. Otherwise
         // fall back to asking the user because the patch may touch arbitrary
         // paths outside the project.
         match get_platform_sandbox() {
             Some(sandbox_type) => SafetyCheck::AutoApprove { sandbox_type },
-            None => SafetyCheck::AskUser,
+            None => SafetyCheck::AskUser {
+                reason: EscalationReason::NoSandbox,
+            },
         }
     } else if policy == AskForApproval::Never {
         SafetyCheck::Reject {
@@ -61,43 +84,89 @@ pub fn assess_patch_safety(
                 .to_string(),
         }
     } else {
-        SafetyCheck::AskUser
+        SafetyCheck::AskUser {
+            reason: EscalationReason::WritesOutsideWorkspace { paths: outside },
+        }
     }
 }
 
 /// For a command to be run _without_ a sandbox, one of the following must be
 /// true:
 ///
-/// - the user has explicitly approved the command
+/// - the user has explicitly approved the command, or a prefix of it, for
+///   the session or in `[approvals] trusted_commands`
 /// - the command is on the "known safe" list
 /// - `DangerFullAccess` was specified and `UnlessTrusted` was not
 pub fn assess_command_safety(
     command: &[String],
     approval_policy: AskForApproval,
     sandbox_policy: &SandboxPolicy,
-    approved: &HashSet<Vec<String>>,
+    approved: &ApprovedCommands,
     with_escalated_permissions: bool,
 ) -> SafetyCheck {
     // A command is "trusted" because either:
     // - it belongs to a set of commands we consider "safe" by default, or
-    // - the user has explicitly approved the command for this session
+    // - the user has explicitly approved the command for this session or
+    //   trusts it in config.toml
     //
     // Currently, whether a command is "trusted" is a simple boolean, but we
     // should include more metadata on this command test to indicate whether it
     // should be run inside a sandbox or not. (This could be something the user
     // defines as part of `execpolicy`.)
     //
-    // For example, when `is_known_safe_command(command)` returns `true`, it
+    // For example, when `approved.is_known_safe(command)` returns `true`, it
     // would probably be fine to run the command in a sandbox, but when
-    // `approved.contains(command)` is `true`, the user may have approved it for
+    // `approved.is_approved(..)` is `true`, the user may have approved it for
     // the session _because_ they know it needs to run outside a sandbox.
-    if is_known_safe_command(command) || approved.contains(command) {
+    if approved.is_known_safe(command) || approved.is_approved(command, sandbox_policy) {
         return SafetyCheck::AutoApprove {
             sandbox_type: SandboxType::None,
         };
     }
 
-    assess_safety_for_untrusted_command(approval_policy, sandbox_policy, with_escalated_permissions)
+    let check = assess_safety_for_untrusted_command(
+        approval_policy,
+        sandbox_policy,
+        with_escalated_permissions,
+    );
+    if matches!(
+        check,
+        SafetyCheck::AutoApprove {
+            sandbox_type: SandboxType::LinuxSeccomp
+        }
+    ) && !sandbox_policy.has_full_network_access()
+    {
+        return require_approval_for_unenforced_network(
+            check,
+            command,
+            approval_policy,
+            network_enforcement(),
+        );
+    }
+    check
+}
+
+/// The Linux sandbox cannot restrict the network when `enforcement` is
+/// unavailable, so commands that look like they use it are not
+/// auto-approved.
+fn require_approval_for_unenforced_network(
+    check: SafetyCheck,
+    command: &[String],
+    approval_policy: AskForApproval,
+    enforcement: NetworkEnforcement,
+) -> SafetyCheck {
+    if enforcement != NetworkEnforcement::Unavailable || !is_network_command(command) {
+        return check;
+    }
+    match approval_policy {
+        AskForApproval::Never => SafetyCheck::Reject {
+            reason: "the sandbox cannot restrict network access on this system; rejected by user approval settings"
+                .to_string(),
+        },
+        _ => SafetyCheck::AskUser {
+            reason: EscalationReason::NetworkNeeded,
+        },
+    }
 }
 
 pub(crate) fn assess_safety_for_untrusted_command(
@@ -113,7 +182,9 @@ pub(crate) fn assess_safety_for_untrusted_command(
             // Even though the user may have opted into DangerFullAccess,
             // they also requested that we ask for approval for untrusted
             // commands.
-            SafetyCheck::AskUser
+            SafetyCheck::AskUser {
+                reason: EscalationReason::UnrecognizedCommand,
+            }
         }
         (OnFailure, DangerFullAccess)
         | (Never, DangerFullAccess)
@@ -122,13 +193,17 @@ pub(crate) fn assess_safety_for_untrusted_command(
         },
         (OnRequest, ReadOnly) | (OnRequest, WorkspaceWrite { .. }) => {
             if with_escalated_permissions {
-                SafetyCheck::AskUser
+                SafetyCheck::AskUser {
+                    reason: EscalationReason::EscalationRequested,
+                }
             } else {
                 match get_platform_sandbox() {
                     Some(sandbox_type) => SafetyCheck::AutoApprove { sandbox_type },
                     // Fall back to asking since the command is untrusted and
                     // we do not have a sandbox available
-                    None => SafetyCheck::AskUser,
+                    None => SafetyCheck::AskUser {
+                        reason: EscalationReason::NoSandbox,
+                    },
                 }
             }
         }
@@ -144,7 +219,9 @@ pub(crate) fn assess_safety_for_untrusted_command(
                         // user has requested to only ask for approval on
                         // failure, we will ask the user because no sandbox is
                         // available.
-                        SafetyCheck::AskUser
+                        SafetyCheck::AskUser {
+                            reason: EscalationReason::NoSandbox,
+                        }
                     } else {
                         // We are in non-interactive mode and lack approval, so
                         // all we can do is reject the command.
@@ -164,83 +241,227 @@ pub fn get_platform_sandbox() -> Option<SandboxType> {
         Some(SandboxType::MacosSeatbelt)
     } else if cfg!(target_os = "linux") {
         Some(SandboxType::LinuxSeccomp)
+    } else if windows_sandbox_available() {
+        Some(SandboxType::WindowsAppContainer)
     } else {
         None
     }
 }
 
-fn is_write_patch_constrained_to_writable_paths(
+/// The approval policy as it is actually enforced. Without a platform sandbox,
+/// commands cannot be confined, so `on-request` and `on-failure` behave like
+/// `untrusted`: anything not known to be safe needs the user's approval.
+pub(crate) fn effective_approval_policy(
+    approval_policy: AskForApproval,
+    sandbox_policy: &SandboxPolicy,
+    sandbox_type: Option<SandboxType>,
+) -> AskForApproval {
+    match approval_policy {
+        AskForApproval::OnRequest | AskForApproval::OnFailure
+            if sandbox_type.is_none()
+                && !matches!(sandbox_policy, SandboxPolicy::DangerFullAccess) =>
+        {
+            AskForApproval::UnlessTrusted
+        }
+        other => other,
+    }
+}
+
+#[cfg(windows)]
+fn windows_sandbox_available() -> bool {
+    crate::windows_sandbox::is_available()
+}
+
+#[cfg(not(windows))]
+fn windows_sandbox_available() -> bool {
+    false
+}
+
+/// Locations under the home directory that patches may only write to when
+/// the sandbox is disabled or a writable root covers them, even if the user
+/// approves the patch.
+const SENSITIVE_HOME_PATHS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".aws",
+    ".azure",
+    ".kube",
+    ".docker",
+    ".netrc",
+    ".config/gcloud",
+];
+
+/// System locations that get the same protection as
+/// [`SENSITIVE_HOME_PATHS`].
+const SENSITIVE_SYSTEM_PATHS: &[&str] = &["/etc", "/boot", "/usr", "/bin", "/sbin", "/System"];
+
+fn is_sensitive_path(path: &Path, home: Option<&Path>) -> bool {
+    SENSITIVE_SYSTEM_PATHS
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+        || home.is_some_and(|home| {
+            SENSITIVE_HOME_PATHS
+                .iter()
+                .any(|prefix| path.starts_with(home.join(prefix)))
+        })
+}
+
+/// The files `action` writes, creates or removes that are not inside one of
+/// the sandbox policy's writable roots, as absolute, normalized, sorted paths.
+/// The destination of a move counts as a separate file.
+pub(crate) fn paths_outside_writable_roots(
     action: &ApplyPatchAction,
     sandbox_policy: &SandboxPolicy,
     cwd: &Path,
-) -> bool {
-    // Early‑exit if there are no declared writable roots.
-    let writable_roots = match sandbox_policy {
-        SandboxPolicy::ReadOnly => {
-            return false;
-        }
-        SandboxPolicy::DangerFullAccess => {
-            return true;
-        }
-        SandboxPolicy::WorkspaceWrite { .. } => sandbox_policy.get_writable_roots_with_cwd(cwd),
-    };
+) -> Vec<PathBuf> {
+    if matches!(sandbox_policy, SandboxPolicy::DangerFullAccess) {
+        return Vec::new();
+    }
+    let writable_roots = sandbox_policy.get_writable_roots_with_cwd(cwd);
+    let mut outside: Vec<PathBuf> = patch_targets(action, cwd)
+        .into_iter()
+        .map(|target| target.path)
+        .filter(|path| {
+            !writable_roots
+                .iter()
+                .any(|writable_root| writable_root.is_path_writable(path))
+        })
+        .collect();
+    outside.sort();
+    outside.dedup();
+    outside
+}
 
-    // Normalize a path by removing `.` and resolving `..` without touching the
-    // filesystem (works even if the file does not exist).
-    fn normalize(path: &Path) -> Option<PathBuf> {
-        let mut out = PathBuf::new();
-        for comp in path.components() {
-            match comp {
-                Component::ParentDir => {
-                    out.pop();
-                }
-                Component::CurDir => { /* skip */ }
-                other => out.push(other.as_os_str()),
+/// The paths the sandbox must additionally allow writes under so that
+/// `action` can write the approved `paths`. Files that are only modified are
+/// exempted on their own, but creating or removing a file needs its parent
+/// directory, or the closest ancestor that exists if `apply_patch` is going
+/// to create the parent.
+pub(crate) fn write_exemption_roots(
+    action: &ApplyPatchAction,
+    paths: &[PathBuf],
+    cwd: &Path,
+) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = patch_targets(action, cwd)
+        .into_iter()
+        .filter(|target| paths.contains(&target.path))
+        .filter_map(|target| {
+            if !target.creates_or_removes {
+                return Some(target.path);
             }
-        }
-        Some(out)
+            target
+                .path
+                .ancestors()
+                .skip(1)
+                .find(|ancestor| ancestor.is_dir())
+                .map(Path::to_path_buf)
+        })
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// `sandbox_policy` with `roots` added to its writable roots, for running a
+/// single approved patch. A read-only policy becomes a workspace-write policy
+/// without network or temp directory access, so the working directory is
+/// writable for that one operation as well.
+pub(crate) fn sandbox_policy_with_write_exemptions(
+    sandbox_policy: &SandboxPolicy,
+    roots: &[PathBuf],
+) -> SandboxPolicy {
+    match sandbox_policy {
+        SandboxPolicy::DangerFullAccess => SandboxPolicy::DangerFullAccess,
+        SandboxPolicy::ReadOnly => SandboxPolicy::WorkspaceWrite {
+            writable_roots: roots.to_vec(),
+            network_access: false,
+            allowed_domains: Vec::new(),
+            exclude_tmpdir_env_var: true,
+            exclude_slash_tmp: true,
+        },
+        SandboxPolicy::WorkspaceWrite {
+            writable_roots,
+            network_access,
+            allowed_domains,
+            exclude_tmpdir_env_var,
+            exclude_slash_tmp,
+        } => SandboxPolicy::WorkspaceWrite {
+            writable_roots: writable_roots.iter().chain(roots).cloned().collect(),
+            network_access: *network_access,
+            allowed_domains: allowed_domains.clone(),
+            exclude_tmpdir_env_var: *exclude_tmpdir_env_var,
+            exclude_slash_tmp: *exclude_slash_tmp,
+        },
     }
+}
 
-    // Determine whether `path` is inside **any** writable root. Both `path`
-    // and roots are converted to absolute, normalized forms before the
-    // prefix check.
-    let is_path_writable = |p: &PathBuf| {
-        let abs = if p.is_absolute() {
-            p.clone()
-        } else {
-            cwd.join(p)
-        };
-        let abs = match normalize(&abs) {
-            Some(v) => v,
-            None => return false,
-        };
+/// `sandbox_policy` widened for the `create_pull_request` tool, which commits
+/// to `git_dir` and pushes to the remote: `git_dir` becomes writable and the
+/// network is allowed. Everything else stays as the policy has it.
+pub(crate) fn sandbox_policy_for_publishing(
+    sandbox_policy: &SandboxPolicy,
+    git_dir: &Path,
+) -> SandboxPolicy {
+    match sandbox_policy_with_write_exemptions(sandbox_policy, &[git_dir.to_path_buf()]) {
+        SandboxPolicy::WorkspaceWrite {
+            writable_roots,
+            exclude_tmpdir_env_var,
+            exclude_slash_tmp,
+            ..
+        } => SandboxPolicy::WorkspaceWrite {
+            writable_roots,
+            network_access: true,
+            allowed_domains: Vec::new(),
+            exclude_tmpdir_env_var,
+            exclude_slash_tmp,
+        },
+        policy => policy,
+    }
+}
 
-        writable_roots
-            .iter()
-            .any(|writable_root| writable_root.is_path_writable(&abs))
-    };
+struct PatchTarget {
+    /// Absolute and normalized.
+    path: PathBuf,
+    /// Whether the file is created or removed rather than modified in place.
+    creates_or_removes: bool,
+}
 
+fn patch_targets(action: &ApplyPatchAction, cwd: &Path) -> Vec<PatchTarget> {
+    let target = |path: &Path, creates_or_removes: bool| PatchTarget {
+        path: normalize(&cwd.join(path)),
+        creates_or_removes,
+    };
+    let mut targets = Vec::new();
     for (path, change) in action.changes() {
         match change {
-            ApplyPatchFileChange::Add { .. } | ApplyPatchFileChange::Delete => {
-                if !is_path_writable(path) {
-                    return false;
-                }
+            ApplyPatchFileChange::Add { .. } | ApplyPatchFileChange::Delete { .. } => {
+                targets.push(target(path, true));
             }
             ApplyPatchFileChange::Update { move_path, .. } => {
-                if !is_path_writable(path) {
-                    return false;
-                }
-                if let Some(dest) = move_path
-                    && !is_path_writable(dest)
-                {
-                    return false;
+                targets.push(target(path, move_path.is_some()));
+                if let Some(dest) = move_path {
+                    targets.push(target(dest, true));
                 }
             }
         }
     }
+    targets
+}
 
-    true
+/// Normalize a path by removing `.` and resolving `..` without touching the
+/// filesystem (works even if the file does not exist).
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => { /* skip */ }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
 }
 
 #[cfg(test)]
@@ -267,34 +488,156 @@ mod tests {
         let policy_workspace_only = SandboxPolicy::WorkspaceWrite {
             writable_roots: vec![],
             network_access: false,
+            allowed_domains: vec![],
             exclude_tmpdir_env_var: true,
             exclude_slash_tmp: true,
         };
 
-        assert!(is_write_patch_constrained_to_writable_paths(
-            &add_inside,
-            &policy_workspace_only,
-            &cwd,
-        ));
+        assert!(paths_outside_writable_roots(&add_inside, &policy_workspace_only, &cwd).is_empty());
 
-        assert!(!is_write_patch_constrained_to_writable_paths(
-            &add_outside,
-            &policy_workspace_only,
-            &cwd,
-        ));
+        assert_eq!(
+            paths_outside_writable_roots(&add_outside, &policy_workspace_only, &cwd),
+            vec![parent.join("outside.txt")]
+        );
 
         // With the parent dir explicitly added as a writable root, the
         // outside write should be permitted.
         let policy_with_parent = SandboxPolicy::WorkspaceWrite {
             writable_roots: vec![parent.clone()],
             network_access: false,
+            allowed_domains: vec![],
             exclude_tmpdir_env_var: true,
             exclude_slash_tmp: true,
         };
-        assert!(is_write_patch_constrained_to_writable_paths(
-            &add_outside,
-            &policy_with_parent,
+        assert!(paths_outside_writable_roots(&add_outside, &policy_with_parent, &cwd).is_empty());
+    }
+
+    #[test]
+    fn only_files_outside_writable_roots_are_listed() {
+        let tmp = TempDir::new().unwrap();
+        let cwd = tmp.path().join("workspace");
+        std::fs::create_dir(&cwd).unwrap();
+        let outside = tmp.path().join("outside.txt");
+        std::fs::write(&outside, "old\n").unwrap();
+        let patch = format!(
+            "*** Begin Patch\n*** Add File: inner.txt\n+hi\n*** Update File: {}\n@@\n-old\n+new\n*** Add File: ../notes/new.txt\n+hi\n*** End Patch",
+            outside.display()
+        );
+        let action = match codex_apply_patch::maybe_parse_apply_patch_verified(
+            &["apply_patch".to_string(), patch],
             &cwd,
+        ) {
+            codex_apply_patch::MaybeApplyPatchVerified::Body(action) => action,
+            other => panic!("expected a patch, got {other:?}"),
+        };
+        let policy = SandboxPolicy::WorkspaceWrite {
+            writable_roots: vec![],
+            network_access: false,
+            allowed_domains: vec![],
+            exclude_tmpdir_env_var: true,
+            exclude_slash_tmp: true,
+        };
+
+        let paths = paths_outside_writable_roots(&action, &policy, &cwd);
+        assert_eq!(
+            paths,
+            vec![tmp.path().join("notes/new.txt"), outside.clone()]
+        );
+
+        // The modified file is exempted on its own; the new file's missing
+        // parent means its closest existing ancestor is.
+        assert_eq!(
+            write_exemption_roots(&action, &paths, &cwd),
+            vec![tmp.path().to_path_buf(), outside.clone()]
+        );
+        assert_eq!(
+            write_exemption_roots(&action, std::slice::from_ref(&outside), &cwd),
+            vec![outside.clone()]
+        );
+
+        let exempted = sandbox_policy_with_write_exemptions(&policy, &[outside.clone()]);
+        assert_eq!(
+            paths_outside_writable_roots(&action, &exempted, &cwd),
+            vec![tmp.path().join("notes/new.txt")]
+        );
+    }
+
+    #[test]
+    fn publishing_allows_the_git_dir_and_the_network() {
+        let git_dir = PathBuf::from("/repo/.git");
+        let allowlisted = SandboxPolicy::WorkspaceWrite {
+            writable_roots: vec![PathBuf::from("/extra")],
+            network_access: false,
+            allowed_domains: vec!["example.com".to_string()],
+            exclude_tmpdir_env_var: false,
+            exclude_slash_tmp: true,
+        };
+        assert_eq!(
+            sandbox_policy_for_publishing(&allowlisted, &git_dir),
+            SandboxPolicy::WorkspaceWrite {
+                writable_roots: vec![PathBuf::from("/extra"), git_dir.clone()],
+                network_access: true,
+                allowed_domains: Vec::new(),
+                exclude_tmpdir_env_var: false,
+                exclude_slash_tmp: true,
+            }
+        );
+        assert_eq!(
+            sandbox_policy_for_publishing(&SandboxPolicy::ReadOnly, &git_dir),
+            SandboxPolicy::WorkspaceWrite {
+                writable_roots: vec![git_dir.clone()],
+                network_access: true,
+                allowed_domains: Vec::new(),
+                exclude_tmpdir_env_var: true,
+                exclude_slash_tmp: true,
+            }
+        );
+        assert_eq!(
+            sandbox_policy_for_publishing(&SandboxPolicy::DangerFullAccess, &git_dir),
+            SandboxPolicy::DangerFullAccess
+        );
+    }
+
+    #[test]
+    fn patches_to_sensitive_paths_need_full_access() {
+        let tmp = TempDir::new().unwrap();
+        let cwd = tmp.path().to_path_buf();
+        let action = ApplyPatchAction::new_add_for_test(Path::new("/etc/hosts"), "".to_string());
+
+        for approval_policy in [AskForApproval::UnlessTrusted, AskForApproval::OnRequest] {
+            assert_eq!(
+                assess_patch_safety(&action, approval_policy, &SandboxPolicy::ReadOnly, &cwd),
+                SafetyCheck::Reject {
+                    reason:
+                        "/etc/hosts is in a protected location; writing there requires full access"
+                            .to_string()
+                }
+            );
+        }
+        assert_eq!(
+            assess_patch_safety(
+                &action,
+                AskForApproval::UnlessTrusted,
+                &SandboxPolicy::DangerFullAccess,
+                &cwd
+            ),
+            SafetyCheck::AskUser {
+                reason: EscalationReason::UnrecognizedCommand
+            }
+        );
+
+        let home = Path::new("/home/user");
+        assert!(is_sensitive_path(
+            Path::new("/home/user/.ssh/config"),
+            Some(home)
+        ));
+        assert!(!is_sensitive_path(
+            Path::new("/home/user/.sshrc"),
+            Some(home)
+        ));
+        assert!(!is_sensitive_path(
+            Path::new("/home/user/src/etc"),
+            Some(home)
         ));
     }
 
@@ -304,7 +647,7 @@ mod tests {
         let command = vec!["git commit".to_string()];
         let approval_policy = AskForApproval::OnRequest;
         let sandbox_policy = SandboxPolicy::ReadOnly;
-        let approved: HashSet<Vec<String>> = HashSet::new();
+        let approved = ApprovedCommands::default();
         let request_escalated_privileges = true;
 
         let safety_check = assess_command_safety(
@@ -315,7 +658,12 @@ mod tests {
             request_escalated_privileges,
         );
 
-        assert_eq!(safety_check, SafetyCheck::AskUser);
+        assert_eq!(
+            safety_check,
+            SafetyCheck::AskUser {
+                reason: EscalationReason::EscalationRequested
+            }
+        );
     }
 
     #[test]
@@ -323,7 +671,7 @@ mod tests {
         let command = vec!["git".to_string(), "commit".to_string()];
         let approval_policy = AskForApproval::OnRequest;
         let sandbox_policy = SandboxPolicy::ReadOnly;
-        let approved: HashSet<Vec<String>> = HashSet::new();
+        let approved = ApprovedCommands::default();
         let request_escalated_privileges = false;
 
         let safety_check = assess_command_safety(
@@ -336,8 +684,91 @@ mod tests {
 
         let expected = match get_platform_sandbox() {
             Some(sandbox_type) => SafetyCheck::AutoApprove { sandbox_type },
-            None => SafetyCheck::AskUser,
+            None => SafetyCheck::AskUser {
+                reason: EscalationReason::NoSandbox,
+            },
         };
         assert_eq!(safety_check, expected);
     }
+
+    #[test]
+    fn test_effective_approval_policy_without_sandbox() {
+        let read_only = SandboxPolicy::ReadOnly;
+        assert_eq!(
+            effective_approval_policy(AskForApproval::OnRequest, &read_only, None),
+            AskForApproval::UnlessTrusted
+        );
+        assert_eq!(
+            effective_approval_policy(AskForApproval::OnFailure, &read_only, None),
+            AskForApproval::UnlessTrusted
+        );
+        assert_eq!(
+            effective_approval_policy(AskForApproval::Never, &read_only, None),
+            AskForApproval::Never
+        );
+        assert_eq!(
+            effective_approval_policy(
+                AskForApproval::OnRequest,
+                &read_only,
+                Some(SandboxType::WindowsAppContainer)
+            ),
+            AskForApproval::OnRequest
+        );
+        assert_eq!(
+            effective_approval_policy(
+                AskForApproval::OnRequest,
+                &SandboxPolicy::DangerFullAccess,
+                None
+            ),
+            AskForApproval::OnRequest
+        );
+    }
+
+    #[test]
+    fn network_commands_need_approval_without_network_enforcement() {
+        let curl = vec!["curl".to_string(), "https://example.com".to_string()];
+        let ls = vec!["ls".to_string()];
+        let sandboxed = || SafetyCheck::AutoApprove {
+            sandbox_type: SandboxType::LinuxSeccomp,
+        };
+
+        assert_eq!(
+            require_approval_for_unenforced_network(
+                sandboxed(),
+                &curl,
+                AskForApproval::OnRequest,
+                NetworkEnforcement::Unavailable,
+            ),
+            SafetyCheck::AskUser {
+                reason: EscalationReason::NetworkNeeded
+            }
+        );
+        assert!(matches!(
+            require_approval_for_unenforced_network(
+                sandboxed(),
+                &curl,
+                AskForApproval::Never,
+                NetworkEnforcement::Unavailable,
+            ),
+            SafetyCheck::Reject { .. }
+        ));
+        assert_eq!(
+            require_approval_for_unenforced_network(
+                sandboxed(),
+                &ls,
+                AskForApproval::OnRequest,
+                NetworkEnforcement::Unavailable,
+            ),
+            sandboxed()
+        );
+        assert_eq!(
+            require_approval_for_unenforced_network(
+                sandboxed(),
+                &curl,
+                AskForApproval::OnRequest,
+                NetworkEnforcement::Seccomp,
+            ),
+            sandboxed()
+        );
+    }
 }
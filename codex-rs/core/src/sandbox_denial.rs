@@ -0,0 +1,256 @@
+//! Works out what the sandbox blocked when a sandboxed command fails, from
+//! the error messages the command printed, so the approval prompt and the
+//! model can see which path or system call was denied.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::SandboxErr;
+use crate::protocol::EscalationReason;
+
+/// Error messages that mean the sandbox may have blocked an operation, with
+/// the errno they correspond to. `getaddrinfo` has no errno; its error code
+/// stands in for one.
+const DENIAL_MESSAGES: &[(&str, &str)] = &[
+    ("Operation not permitted", "EPERM"),
+    ("Permission denied", "EACCES"),
+    ("Read-only file system", "EROFS"),
+    ("Network is unreachable", "ENETUNREACH"),
+    ("Temporary failure in name resolution", "EAI_AGAIN"),
+];
+
+/// What coreutils-style tools say before the path they failed on, and the
+/// system call behind it.
+const OPERATIONS: &[(&str, &str)] = &[
+    ("cannot create directory", "mkdir"),
+    ("cannot create", "open"),
+    ("cannot touch", "open"),
+    ("cannot open", "open"),
+    ("cannot remove", "unlink"),
+    ("cannot move", "rename"),
+    ("cannot overwrite", "open"),
+    ("failed to create file via template", "open"),
+    ("failed to create directory via template", "mkdir"),
+];
+
+/// Temp directories a command may fall back to, besides `std::env::temp_dir`.
+const TEMP_DIRS: &[&str] = &["/tmp", "/var/tmp", "/private/tmp"];
+
+/// System calls that tools name on their own between colons, as in
+/// `ping: socket: Operation not permitted`.
+const SYSCALLS: &[&str] = &[
+    "bind",
+    "chmod",
+    "chown",
+    "connect",
+    "execve",
+    "getaddrinfo",
+    "mkdir",
+    "open",
+    "openat",
+    "rename",
+    "sendto",
+    "socket",
+    "unlink",
+];
+
+/// The escalation reason for a command that failed with `error` in the
+/// sandbox. Relative paths in the command's output are resolved against
+/// `cwd`.
+pub(crate) fn sandbox_denial_reason(error: &SandboxErr, cwd: &Path) -> EscalationReason {
+    let (path, syscall, errno) = match error {
+        SandboxErr::Denied(_, stdout, stderr) => stderr
+            .lines()
+            .chain(stdout.lines())
+            .find_map(|line| parse_denial(line, cwd))
+            .unwrap_or_default(),
+        _ => Default::default(),
+    };
+    EscalationReason::SandboxDenied {
+        path,
+        syscall,
+        errno,
+    }
+}
+
+type Denial = (Option<PathBuf>, Option<String>, Option<String>);
+
+/// Parses one line of error output, such as
+/// `touch: cannot touch '/etc/motd': Read-only file system`.
+fn parse_denial(line: &str, cwd: &Path) -> Option<Denial> {
+    let (message, errno) = DENIAL_MESSAGES
+        .iter()
+        .find(|(message, _)| line.contains(message))?;
+    let segments: Vec<&str> = line
+        .split(": ")
+        .map(str::trim)
+        .filter(|segment| !segment.contains(message))
+        .collect();
+
+    let path = segments
+        .iter()
+        .find_map(|segment| path_in_segment(segment))
+        .map(|path| cwd.join(path));
+    let syscall = segments.iter().find_map(|segment| {
+        OPERATIONS
+            .iter()
+            .find(|(operation, _)| segment.starts_with(operation))
+            .map(|(_, syscall)| *syscall)
+            .or_else(|| SYSCALLS.iter().copied().find(|syscall| segment == syscall))
+    });
+    Some((path, syscall.map(str::to_string), Some(errno.to_string())))
+}
+
+/// A quoted string in `segment`, or `segment` itself if it looks like a path.
+fn path_in_segment(segment: &str) -> Option<&str> {
+    const QUOTES: &[(char, char)] = &[('\'', '\''), ('"', '"'), ('‘', '’'), ('`', '\'')];
+    for (open, close) in QUOTES {
+        if let Some((_, rest)) = segment.split_once(*open)
+            && let Some((quoted, _)) = rest.split_once(*close)
+            && !quoted.is_empty()
+        {
+            return Some(quoted);
+        }
+    }
+    let looks_like_path = ["/", "./", "../"]
+        .iter()
+        .any(|prefix| segment.starts_with(prefix))
+        && !segment.contains(char::is_whitespace);
+    looks_like_path.then_some(segment)
+}
+
+/// Whether `path` is in a temp directory, which the read-only sandbox only
+/// makes writable with `sandbox.read_only_allow_tmp`.
+fn is_temp_path(path: &Path) -> bool {
+    let temp_dir = std::env::temp_dir();
+    TEMP_DIRS
+        .iter()
+        .map(Path::new)
+        .chain([temp_dir.as_path()])
+        .any(|dir| path.starts_with(dir))
+}
+
+/// A note for the model on why a command needed approval and how it might
+/// get by without, appended to the output it sees.
+pub(crate) fn escalation_note(reason: &EscalationReason) -> String {
+    const STAY_INSIDE: &str =
+        " If you can, use a command that only writes inside the workspace or the writable roots.";
+    const STAY_OFFLINE: &str = " If you can, use a command that does not need the network.";
+    const USE_SCRATCH: &str = " The temp directory is not writable in this sandbox. If you can, have the command put its temporary files in `$CODEX_SCRATCH_DIR` (for example with `TMPDIR=\"$CODEX_SCRATCH_DIR\"`); otherwise the user can set `sandbox.read_only_allow_tmp = true` in config.toml.";
+    let suggestion = match reason {
+        EscalationReason::WritesOutsideWorkspace { .. } => STAY_INSIDE,
+        EscalationReason::NetworkNeeded => STAY_OFFLINE,
+        EscalationReason::SandboxDenied {
+            path,
+            syscall,
+            errno,
+        } => {
+            let network = matches!(
+                syscall.as_deref(),
+                Some("socket" | "connect" | "bind" | "sendto" | "getaddrinfo")
+            ) || matches!(errno.as_deref(), Some("ENETUNREACH" | "EAI_AGAIN"));
+            if network {
+                STAY_OFFLINE
+            } else if path.as_deref().is_some_and(is_temp_path) {
+                USE_SCRATCH
+            } else if path.is_some() {
+                STAY_INSIDE
+            } else {
+                ""
+            }
+        }
+        _ => "",
+    };
+    format!("Approval was needed because {reason}.{suggestion}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn denied(stderr: &str) -> SandboxErr {
+        SandboxErr::Denied(1, String::new(), stderr.to_string())
+    }
+
+    fn reason(path: Option<&str>, syscall: Option<&str>, errno: Option<&str>) -> EscalationReason {
+        EscalationReason::SandboxDenied {
+            path: path.map(PathBuf::from),
+            syscall: syscall.map(str::to_string),
+            errno: errno.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reads_the_blocked_path_and_syscall_from_error_output() {
+        let cwd = Path::new("/repo");
+        let cases = [
+            (
+                "touch: cannot touch '/etc/motd': Read-only file system",
+                reason(Some("/etc/motd"), Some("open"), Some("EROFS")),
+            ),
+            (
+                "mkdir: cannot create directory ‘/opt/tool’: Permission denied",
+                reason(Some("/opt/tool"), Some("mkdir"), Some("EACCES")),
+            ),
+            (
+                "bash: ../outside.txt: Operation not permitted",
+                reason(Some("/repo/../outside.txt"), None, Some("EPERM")),
+            ),
+            (
+                "PermissionError: [Errno 13] Permission denied: 'build/out.log'",
+                reason(Some("/repo/build/out.log"), None, Some("EACCES")),
+            ),
+            (
+                "ping: socket: Operation not permitted",
+                reason(None, Some("socket"), Some("EPERM")),
+            ),
+            (
+                "sort: cannot create temporary file in '/tmp': Permission denied",
+                reason(Some("/tmp"), Some("open"), Some("EACCES")),
+            ),
+            (
+                "mktemp: failed to create file via template ‘/tmp/tmp.XXXXXXXXXX’: Read-only file system",
+                reason(Some("/tmp/tmp.XXXXXXXXXX"), Some("open"), Some("EROFS")),
+            ),
+            ("error: could not compile", reason(None, None, None)),
+        ];
+        for (stderr, expected) in cases {
+            assert_eq!(
+                sandbox_denial_reason(&denied(stderr), cwd),
+                expected,
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn first_denial_in_the_output_wins() {
+        let stderr = "warning: something\nrm: cannot remove '/usr/lib/x': Read-only file system\nrm: cannot remove '/usr/lib/y': Read-only file system";
+        assert_eq!(
+            sandbox_denial_reason(&denied(stderr), Path::new("/repo")),
+            reason(Some("/usr/lib/x"), Some("unlink"), Some("EROFS"))
+        );
+        assert_eq!(
+            sandbox_denial_reason(&SandboxErr::Signal(31), Path::new("/repo")),
+            reason(None, None, None)
+        );
+    }
+
+    #[test]
+    fn note_suggests_a_narrower_command() {
+        assert_eq!(
+            escalation_note(&reason(Some("/etc/motd"), Some("open"), Some("EROFS"))),
+            "Approval was needed because the sandbox blocked open on /etc/motd (EROFS). If you can, use a command that only writes inside the workspace or the writable roots."
+        );
+        assert!(
+            escalation_note(&reason(Some("/tmp"), Some("open"), Some("EACCES"))).ends_with(
+                "otherwise the user can set `sandbox.read_only_allow_tmp = true` in config.toml."
+            )
+        );
+        assert_eq!(
+            escalation_note(&EscalationReason::UnrecognizedCommand),
+            "Approval was needed because the command is not on the list of trusted commands."
+        );
+    }
+}
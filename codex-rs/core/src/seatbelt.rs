@@ -3,9 +3,12 @@ use std::path::Path;
 use std::path::PathBuf;
 use tokio::process::Child;
 
+use crate::network_proxy::network_proxy_port;
 use crate::protocol::SandboxPolicy;
+use crate::protocol::WritableRoot;
 use crate::spawn::CODEX_SANDBOX_ENV_VAR;
 use crate::spawn::StdioPolicy;
+use crate::spawn::extra_writable_dirs;
 use crate::spawn::spawn_child_async;
 
 const MACOS_SEATBELT_BASE_POLICY: &str = include_str!("seatbelt_base_policy.sbpl");
@@ -23,7 +26,15 @@ pub async fn spawn_command_under_seatbelt(
     stdio_policy: StdioPolicy,
     mut env: HashMap<String, String>,
 ) -> std::io::Result<Child> {
-    let args = create_seatbelt_command_args(command, sandbox_policy, &cwd);
+    let network_proxy_port = network_proxy_port(&env);
+    let extra_writable_dirs = extra_writable_dirs(&env);
+    let args = create_seatbelt_command_args(
+        command,
+        sandbox_policy,
+        &cwd,
+        network_proxy_port,
+        &extra_writable_dirs,
+    );
     let arg0 = None;
     env.insert(CODEX_SANDBOX_ENV_VAR.to_string(), "seatbelt".to_string());
     spawn_child_async(
@@ -42,6 +53,8 @@ fn create_seatbelt_command_args(
     command: Vec<String>,
     sandbox_policy: &SandboxPolicy,
     cwd: &Path,
+    network_proxy_port: Option<u16>,
+    extra_writable_dirs: &[PathBuf],
 ) -> Vec<String> {
     let (file_write_policy, extra_cli_args) = {
         if sandbox_policy.has_full_disk_write_access() {
@@ -51,7 +64,11 @@ fn create_seatbelt_command_args(
                 Vec::<String>::new(),
             )
         } else {
-            let writable_roots = sandbox_policy.get_writable_roots_with_cwd(cwd);
+            let mut writable_roots = sandbox_policy.get_writable_roots_with_cwd(cwd);
+            writable_roots.extend(extra_writable_dirs.iter().map(|dir| WritableRoot {
+                root: dir.clone(),
+                read_only_subpaths: Vec::new(),
+            }));
 
             let mut writable_folder_policies: Vec<String> = Vec::new();
             let mut cli_args: Vec<String> = Vec::new();
@@ -104,9 +121,14 @@ fn create_seatbelt_command_args(
 
     // TODO(mbolin): apply_patch calls must also honor the SandboxPolicy.
     let network_policy = if sandbox_policy.has_full_network_access() {
-        "(allow network-outbound)\n(allow network-inbound)\n(allow system-socket)"
+        "(allow network-outbound)\n(allow network-inbound)\n(allow system-socket)".to_string()
+    } else if let Some(port) = network_proxy_port
+        && sandbox_policy.network_allowlist().is_some()
+    {
+        // Only the local proxy that enforces the domain allowlist.
+        format!("(allow network-outbound (remote ip \"localhost:{port}\"))")
     } else {
-        ""
+        String::new()
     };
 
     let full_policy = format!(
@@ -155,6 +177,7 @@ mod tests {
         let policy = SandboxPolicy::WorkspaceWrite {
             writable_roots: vec![root_with_git.clone(), root_without_git.clone()],
             network_access: false,
+            allowed_domains: vec![],
             exclude_tmpdir_env_var: true,
             exclude_slash_tmp: true,
         };
@@ -163,6 +186,8 @@ mod tests {
             vec!["/bin/echo".to_string(), "hello".to_string()],
             &policy,
             &cwd,
+            None,
+            &[],
         );
 
         // Build the expected policy text using a raw string for readability.
@@ -207,6 +232,65 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn network_allowlist_only_allows_proxy_port() {
+        let tmp = TempDir::new().expect("tempdir");
+        let policy = SandboxPolicy::WorkspaceWrite {
+            writable_roots: vec![],
+            network_access: false,
+            allowed_domains: vec!["crates.io".to_string()],
+            exclude_tmpdir_env_var: true,
+            exclude_slash_tmp: true,
+        };
+
+        let args = create_seatbelt_command_args(
+            vec!["true".to_string()],
+            &policy,
+            tmp.path(),
+            Some(41234),
+            &[],
+        );
+        assert!(
+            args[1].ends_with("\n(allow network-outbound (remote ip \"localhost:41234\"))"),
+            "unexpected policy: {}",
+            args[1]
+        );
+
+        let args =
+            create_seatbelt_command_args(vec!["true".to_string()], &policy, tmp.path(), None, &[]);
+        assert!(!args[1].contains("network-outbound"));
+    }
+
+    #[test]
+    fn scratch_dir_is_writable_under_read_only_policy() {
+        let tmp = TempDir::new().expect("tempdir");
+        let scratch = tmp.path().join("scratch");
+        std::fs::create_dir(&scratch).expect("create scratch dir");
+        let canonical_scratch = scratch.canonicalize().expect("canonicalize scratch dir");
+
+        let args = create_seatbelt_command_args(
+            vec!["true".to_string()],
+            &SandboxPolicy::ReadOnly,
+            tmp.path(),
+            None,
+            std::slice::from_ref(&scratch),
+        );
+        assert!(args[1].contains("(allow file-write*\n(subpath (param \"WRITABLE_ROOT_0\"))"));
+        assert!(args.contains(&format!(
+            "-DWRITABLE_ROOT_0={}",
+            canonical_scratch.to_string_lossy()
+        )));
+
+        let args = create_seatbelt_command_args(
+            vec!["true".to_string()],
+            &SandboxPolicy::ReadOnly,
+            tmp.path(),
+            None,
+            &[],
+        );
+        assert!(!args[1].contains("file-write*"));
+    }
+
     #[test]
     fn create_seatbelt_args_for_cwd_as_git_repo() {
         if cfg!(target_os = "windows") {
@@ -230,6 +314,7 @@ mod tests {
         let policy = SandboxPolicy::WorkspaceWrite {
             writable_roots: vec![],
             network_access: false,
+            allowed_domains: vec![],
             exclude_tmpdir_env_var: false,
             exclude_slash_tmp: false,
         };
@@ -238,6 +323,8 @@ mod tests {
             vec!["/bin/echo".to_string(), "hello".to_string()],
             &policy,
             root_with_git.as_path(),
+            None,
+            &[],
         );
 
         let tmpdir_env_var = std::env::var("TMPDIR")
@@ -0,0 +1,523 @@
+//! An index of the recorded sessions, `sessions/index.jsonl` under the state
+//! directory, so that listing them does not mean reading every rollout.
+//!
+//! The rollout writer appends an entry for its session when the session
+//! starts, after each message from the user or response's usage, and at
+//! shutdown. Each entry notes how long the rollout was when it was written;
+//! [`list_sessions`] reads again only the rollouts whose length differs, such
+//! as those of sessions that crashed or were recorded before the index
+//! existed, and then rewrites the index with one entry per session.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use codex_protocol::models::ContentItem;
+use codex_protocol::models::ResponseItem;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::environment_context::ENVIRONMENT_CONTEXT_START;
+use crate::protocol::TokenUsage;
+use crate::rollout::SESSIONS_SUBDIR;
+use crate::rollout::SessionMeta;
+use crate::rollout::UsageRecord;
+use crate::rollout::is_session_prefix;
+use crate::rollout::rollout_files;
+
+const INDEX_FILE: &str = "index.jsonl";
+
+/// Longest user message kept in an entry, in characters.
+const MESSAGE_SNIPPET_CHARS: usize = 200;
+
+/// What the index knows about one recorded session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionIndexEntry {
+    pub id: Uuid,
+    pub path: PathBuf,
+    /// When the session started.
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Messages the user sent.
+    #[serde(default)]
+    pub turns: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_user_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_user_message: Option<String>,
+    #[serde(default)]
+    pub token_usage: TokenUsage,
+    /// `None` when a response came from a model without known prices.
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    /// Length of the rollout when the entry was written.
+    pub rollout_len: u64,
+    /// Why the rollout could not be read in full, if it could not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+impl SessionIndexEntry {
+    pub(crate) fn new(path: PathBuf, meta: &SessionMeta) -> Self {
+        Self {
+            id: meta.id,
+            path,
+            timestamp: meta.timestamp.clone(),
+            cwd: meta.cwd.clone(),
+            model: meta.model.clone(),
+            turns: 0,
+            first_user_message: None,
+            last_user_message: None,
+            token_usage: TokenUsage::default(),
+            cost_usd: Some(0.0),
+            rollout_len: 0,
+            problem: None,
+        }
+    }
+
+    /// Counts `item` if it is a message from the user. The environment
+    /// context Codex sends first supplies the working directory of rollouts
+    /// recorded before the session meta had it.
+    pub(crate) fn record_item(&mut self, item: &ResponseItem) {
+        let ResponseItem::Message { role, content, .. } = item else {
+            return;
+        };
+        if role != "user" {
+            return;
+        }
+        let text = content
+            .iter()
+            .filter_map(|c| match c {
+                ContentItem::InputText { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if is_session_prefix(&text) {
+            if self.cwd.is_none() && text.starts_with(ENVIRONMENT_CONTEXT_START) {
+                self.cwd = text
+                    .split_once("<cwd>")
+                    .and_then(|(_, rest)| rest.split_once("</cwd>"))
+                    .map(|(cwd, _)| PathBuf::from(cwd));
+            }
+            return;
+        }
+        self.turns += 1;
+        let text = text.trim();
+        if !text.is_empty() {
+            let snippet = snippet(text);
+            self.first_user_message
+                .get_or_insert_with(|| snippet.clone());
+            self.last_user_message = Some(snippet);
+        }
+    }
+
+    pub(crate) fn record_usage(&mut self, usage: &UsageRecord) {
+        self.token_usage = self.token_usage.add_usage(&usage.token_usage);
+        self.cost_usd = self.cost_usd.zip(usage.cost_usd).map(|(a, b)| a + b);
+    }
+}
+
+fn snippet(text: &str) -> String {
+    match text.char_indices().nth(MESSAGE_SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Keeps the index entry of the session being recorded up to date.
+pub(crate) struct SessionIndexWriter {
+    index_path: PathBuf,
+    pub entry: SessionIndexEntry,
+}
+
+impl SessionIndexWriter {
+    pub(crate) fn new(state_dir: &Path, entry: SessionIndexEntry) -> Self {
+        Self {
+            index_path: index_path(state_dir),
+            entry,
+        }
+    }
+
+    /// Appends the entry, noting that the rollout is now `rollout_len` bytes
+    /// long. The index is only a cache, so failing to update it is logged
+    /// rather than failing the session.
+    pub(crate) async fn save(&mut self, rollout_len: u64) {
+        self.entry.rollout_len = rollout_len;
+        if let Err(e) = append_entry(&self.index_path, &self.entry).await {
+            warn!("failed to update session index {:?}: {e}", self.index_path);
+        }
+    }
+}
+
+async fn append_entry(index_path: &Path, entry: &SessionIndexEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(index_path)
+        .await?;
+    file.write_all(line.as_bytes()).await
+}
+
+fn index_path(state_dir: &Path) -> PathBuf {
+    state_dir.join(SESSIONS_SUBDIR).join(INDEX_FILE)
+}
+
+/// Reads the rollout at `path` in full. A rollout whose meta cannot be read
+/// is still summarized, from its file name, and both that and unreadable
+/// entries are noted in [`SessionIndexEntry::problem`].
+pub(crate) fn summarize_rollout(path: &Path) -> std::io::Result<SessionIndexEntry> {
+    let bytes = fs::read(path)?;
+    let mut lines = bytes.split(|b| *b == b'\n');
+    let meta = lines
+        .next()
+        .and_then(|line| serde_json::from_slice::<SessionMeta>(line).ok());
+    let Some(meta) = meta else {
+        return Ok(SessionIndexEntry {
+            rollout_len: bytes.len() as u64,
+            problem: Some("unreadable session header".to_string()),
+            ..SessionIndexEntry::new(path.to_path_buf(), &meta_from_file_name(path))
+        });
+    };
+
+    let mut entry = SessionIndexEntry::new(path.to_path_buf(), &meta);
+    entry.rollout_len = bytes.len() as u64;
+    let mut unreadable = 0;
+    for line in lines {
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let Ok(value) = serde_json::from_slice::<Value>(line) else {
+            unreadable += 1;
+            continue;
+        };
+        match value.get("record_type").and_then(Value::as_str) {
+            Some("usage") => {
+                if let Ok(usage) = serde_json::from_value::<UsageRecord>(value) {
+                    entry.record_usage(&usage);
+                }
+            }
+            Some(_) => {}
+            None => {
+                if let Ok(item) = serde_json::from_value::<ResponseItem>(value) {
+                    entry.record_item(&item);
+                }
+            }
+        }
+    }
+    entry.problem = match unreadable {
+        0 if !bytes.ends_with(b"\n") => Some("last entry is incomplete".to_string()),
+        0 => None,
+        1 => Some("1 unreadable entry".to_string()),
+        n => Some(format!("{n} unreadable entries")),
+    };
+    Ok(entry)
+}
+
+/// The id and start time in a rollout's file name,
+/// `rollout-2025-05-07T17-24-21-<uuid>.jsonl`, for rollouts whose meta
+/// cannot be read.
+fn meta_from_file_name(path: &Path) -> SessionMeta {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let rest = stem.strip_prefix("rollout-").unwrap_or(stem);
+    let id = rest
+        .get(rest.len().saturating_sub(36)..)
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .unwrap_or_default();
+    let timestamp = match (rest.get(..10), rest.get(11..19)) {
+        (Some(date), Some(time)) => format!("{date}T{}", time.replace('-', ":")),
+        _ => String::new(),
+    };
+    SessionMeta {
+        id,
+        timestamp,
+        ..SessionMeta::default()
+    }
+}
+
+/// The index entries by rollout path, the last one winning, and how many
+/// lines the index has.
+fn read_index(index_path: &Path) -> std::io::Result<(HashMap<PathBuf, SessionIndexEntry>, usize)> {
+    let contents = match fs::read_to_string(index_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((HashMap::new(), 0)),
+        Err(e) => return Err(e),
+    };
+    let mut entries = HashMap::new();
+    let mut lines = 0;
+    for line in contents.lines() {
+        lines += 1;
+        // A line cut short by a crash is dropped; its rollout is read again.
+        if let Ok(entry) = serde_json::from_str::<SessionIndexEntry>(line) {
+            entries.insert(entry.path.clone(), entry);
+        }
+    }
+    Ok((entries, lines))
+}
+
+/// Replaces the index with `entries`, one line each.
+fn write_index(index_path: &Path, entries: &[SessionIndexEntry]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    let tmp = index_path.with_extension(format!("jsonl.{}.tmp", std::process::id()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, index_path)
+}
+
+/// All recorded sessions, most recent first. Rollouts that changed since
+/// their entry was written, or that have none, are read again, and the index
+/// is rewritten when anything changed.
+pub fn list_sessions(state_dir: &Path) -> std::io::Result<Vec<SessionIndexEntry>> {
+    let index_path = index_path(state_dir);
+    let (mut indexed, index_lines) = read_index(&index_path)?;
+    let mut changed = false;
+    let mut sessions = Vec::new();
+    for path in rollout_files(state_dir)? {
+        let len = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        match indexed.remove(&path) {
+            Some(entry) if entry.rollout_len == len => sessions.push(entry),
+            _ => {
+                changed = true;
+                sessions.push(
+                    summarize_rollout(&path).unwrap_or_else(|e| SessionIndexEntry {
+                        rollout_len: len,
+                        problem: Some(format!("unreadable: {e}")),
+                        ..SessionIndexEntry::new(path.clone(), &meta_from_file_name(&path))
+                    }),
+                );
+            }
+        }
+    }
+    // Entries left over are for rollouts that no longer exist. Superseded
+    // entries pile up as sessions run, so the index is also compacted once
+    // most of its lines are.
+    changed |= !indexed.is_empty() || index_lines > 2 * sessions.len();
+    if changed && let Err(e) = write_index(&index_path, &sessions) {
+        warn!("failed to rewrite session index {index_path:?}: {e}");
+    }
+    Ok(sessions)
+}
+
+/// Deletes the rollouts at `paths` and their index entries. Stored images
+/// are shared by the sessions of a day and are left alone.
+pub fn delete_sessions(state_dir: &Path, paths: &[PathBuf]) -> std::io::Result<()> {
+    for path in paths {
+        match fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    list_sessions(state_dir).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn user_message(text: &str) -> String {
+        serde_json::to_string(&ResponseItem::Message {
+            id: None,
+            role: "user".to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+        })
+        .unwrap()
+    }
+
+    /// Writes a rollout started at `stem` with `lines` after the meta.
+    fn write_rollout(state_dir: &Path, stem: &str, id: Uuid, lines: &[String]) -> PathBuf {
+        let dir = state_dir.join(SESSIONS_SUBDIR).join("2025/08/01");
+        fs::create_dir_all(&dir).unwrap();
+        let meta = serde_json::json!({
+            "id": id,
+            "timestamp": "2025-08-01T10:00:00.000Z",
+            "instructions": null,
+            "model": "o3",
+        });
+        let path = dir.join(format!("rollout-{stem}-{id}.jsonl"));
+        let mut contents = format!("{meta}\n");
+        for line in lines {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn rollouts_are_summarized_and_indexed() {
+        let state = TempDir::new().unwrap();
+        let id = Uuid::new_v4();
+        let usage = serde_json::json!({
+            "record_type": "usage",
+            "model": "o3",
+            "token_usage": {"input_tokens": 10, "output_tokens": 5, "total_tokens": 15},
+            "cost_usd": 0.5,
+        });
+        let path = write_rollout(
+            state.path(),
+            "2025-08-01T10-00-00",
+            id,
+            &[
+                user_message(
+                    "<environment_context>\n  <cwd>/work/app</cwd>\n</environment_context>",
+                ),
+                user_message("fix the build"),
+                usage.to_string(),
+                user_message("now add a test"),
+            ],
+        );
+
+        let sessions = list_sessions(state.path()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.id, id);
+        assert_eq!(session.path, path);
+        assert_eq!(session.cwd, Some(PathBuf::from("/work/app")));
+        assert_eq!(session.model.as_deref(), Some("o3"));
+        assert_eq!(session.turns, 2);
+        assert_eq!(session.first_user_message.as_deref(), Some("fix the build"));
+        assert_eq!(session.last_user_message.as_deref(), Some("now add a test"));
+        assert_eq!(session.token_usage.total_tokens, 15);
+        assert_eq!(session.cost_usd, Some(0.5));
+        assert_eq!(session.problem, None);
+
+        let (indexed, lines) = read_index(&index_path(state.path())).unwrap();
+        assert_eq!(lines, 1);
+        assert_eq!(indexed.get(&path), Some(session));
+    }
+
+    #[test]
+    fn only_rollouts_that_changed_are_read_again() {
+        let state = TempDir::new().unwrap();
+        let unchanged = write_rollout(
+            state.path(),
+            "2025-08-01T10-00-00",
+            Uuid::new_v4(),
+            &[user_message("first")],
+        );
+        let grown = write_rollout(
+            state.path(),
+            "2025-08-01T11-00-00",
+            Uuid::new_v4(),
+            &[user_message("second")],
+        );
+        list_sessions(state.path()).unwrap();
+
+        // An index entry whose rollout is unchanged is trusted as it is.
+        let mut entries = read_index(&index_path(state.path())).unwrap().0;
+        let mut stale = entries.remove(&unchanged).unwrap();
+        stale.last_user_message = Some("from the index".to_string());
+        let grown_entry = entries.remove(&grown).unwrap();
+        write_index(&index_path(state.path()), &[grown_entry, stale]).unwrap();
+        let mut contents = fs::read_to_string(&grown).unwrap();
+        contents.push_str(&user_message("more"));
+        contents.push('\n');
+        fs::write(&grown, contents).unwrap();
+
+        let sessions = list_sessions(state.path()).unwrap();
+        let last_messages = sessions
+            .iter()
+            .map(|s| (s.turns, s.last_user_message.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            last_messages,
+            vec![(2, Some("more")), (1, Some("from the index"))]
+        );
+    }
+
+    #[test]
+    fn corrupt_rollouts_are_listed_with_their_problem() {
+        let state = TempDir::new().unwrap();
+        let partial_id = Uuid::new_v4();
+        let partial = write_rollout(
+            state.path(),
+            "2025-08-01T10-00-00",
+            partial_id,
+            &[user_message("hi"), "{\"type\":\"mess".to_string()],
+        );
+        let headless_id = Uuid::new_v4();
+        let dir = partial.parent().unwrap();
+        fs::write(
+            dir.join(format!("rollout-2025-08-01T09-30-15-{headless_id}.jsonl")),
+            b"\0\0\0\0",
+        )
+        .unwrap();
+
+        let sessions = list_sessions(state.path()).unwrap();
+        let listed = sessions
+            .iter()
+            .map(|s| (s.id, s.timestamp.as_str(), s.problem.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            vec![
+                (
+                    partial_id,
+                    "2025-08-01T10:00:00.000Z",
+                    Some("1 unreadable entry")
+                ),
+                (
+                    headless_id,
+                    "2025-08-01T09:30:15",
+                    Some("unreadable session header")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn deleted_sessions_leave_the_index() {
+        let state = TempDir::new().unwrap();
+        let kept = write_rollout(
+            state.path(),
+            "2025-08-01T10-00-00",
+            Uuid::new_v4(),
+            &[user_message("keep")],
+        );
+        let deleted = write_rollout(
+            state.path(),
+            "2025-08-01T11-00-00",
+            Uuid::new_v4(),
+            &[user_message("delete")],
+        );
+        list_sessions(state.path()).unwrap();
+
+        delete_sessions(state.path(), std::slice::from_ref(&deleted)).unwrap();
+        assert!(!deleted.exists());
+        let (indexed, lines) = read_index(&index_path(state.path())).unwrap();
+        assert_eq!(lines, 1);
+        assert!(indexed.contains_key(&kept));
+    }
+
+    #[test]
+    fn missing_sessions_dir_lists_nothing() {
+        let state = TempDir::new().unwrap();
+        assert!(list_sessions(state.path()).unwrap().is_empty());
+    }
+}
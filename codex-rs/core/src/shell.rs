@@ -1,7 +1,18 @@
 use serde::Deserialize;
 use serde::Serialize;
 use shlex;
+use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest the user's shell may take to print its environment at session
+/// start before Codex gives up on the snapshot.
+const PROFILE_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Printed before the environment so that anything the rc file writes to
+/// stdout is not mistaken for a variable.
+const PROFILE_ENV_MARKER: &str = "__CODEX_PROFILE_ENV__";
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct ZshShell {
@@ -9,6 +20,12 @@ pub struct ZshShell {
     zshrc_path: String,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BashShell {
+    shell_path: String,
+    bashrc_path: String,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct PowerShellConfig {
     exe: String, // Executable name or path, e.g. "pwsh" or "powershell.exe".
@@ -18,6 +35,7 @@ pub struct PowerShellConfig {
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Shell {
     Zsh(ZshShell),
+    Bash(BashShell),
     PowerShell(PowerShellConfig),
     Unknown,
 }
@@ -43,6 +61,16 @@ impl Shell {
                 }
                 Some(result)
             }
+            Shell::Bash(bash) => {
+                let joined = strip_bash_lc(&command)
+                    .or_else(|| shlex::try_join(command.iter().map(|s| s.as_str())).ok())?;
+                let script = if Path::new(&bash.bashrc_path).exists() {
+                    format!("source {} && ({joined})", bash.bashrc_path)
+                } else {
+                    joined
+                };
+                Some(vec![bash.shell_path.clone(), "-lc".to_string(), script])
+            }
             Shell::PowerShell(ps) => {
                 // If model generated a bash command, prefer a detected bash fallback
                 if let Some(script) = strip_bash_lc(&command) {
@@ -97,10 +125,111 @@ impl Shell {
             Shell::Zsh(zsh) => std::path::Path::new(&zsh.shell_path)
                 .file_name()
                 .map(|s| s.to_string_lossy().to_string()),
+            Shell::Bash(bash) => std::path::Path::new(&bash.shell_path)
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string()),
             Shell::PowerShell(ps) => Some(ps.exe.clone()),
             Shell::Unknown => None,
         }
     }
+
+    /// Runs the user's login shell once, with its rc file, and returns the
+    /// environment it ends up with. Commands can then start from this
+    /// snapshot instead of paying for the profile on every call. `None` if
+    /// the shell is not supported or fails or times out.
+    pub(crate) async fn profile_env(&self, cwd: &Path) -> Option<HashMap<String, String>> {
+        let script = format!("echo {PROFILE_ENV_MARKER}; env");
+        let command = match self {
+            // Wrapped like a `bash -lc` command from the model: the script runs
+            // in zsh after the rc file.
+            Shell::Zsh(_) => self.format_default_shell_invocation(vec![
+                "bash".to_string(),
+                "-lc".to_string(),
+                script,
+            ])?,
+            // `-i` because the default `.bashrc` of Debian and Ubuntu returns
+            // early in non-interactive shells, before lines such as nvm's.
+            Shell::Bash(bash) => {
+                let script = if Path::new(&bash.bashrc_path).exists() {
+                    format!("source {}; {script}", bash.bashrc_path)
+                } else {
+                    script
+                };
+                vec![bash.shell_path.clone(), "-lic".to_string(), script]
+            }
+            Shell::PowerShell(_) | Shell::Unknown => return None,
+        };
+        let (program, args) = command.split_first()?;
+        let output = tokio::process::Command::new(program)
+            .args(args)
+            .current_dir(cwd)
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = match tokio::time::timeout(PROFILE_SNAPSHOT_TIMEOUT, output).await {
+            Ok(Ok(output)) if output.status.success() => output,
+            Ok(Ok(output)) => {
+                tracing::warn!("shell profile exited with {}", output.status);
+                return None;
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("failed to run shell profile: {e}");
+                return None;
+            }
+            Err(_) => {
+                tracing::warn!("shell profile did not finish within {PROFILE_SNAPSHOT_TIMEOUT:?}");
+                return None;
+            }
+        };
+        parse_env_output(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// The user's shell, from its path, with the rc file in `home` that
+/// [`Shell::format_default_shell_invocation`] sources.
+fn shell_from_path(shell_path: &str, home: &str) -> Shell {
+    if shell_path.ends_with("/zsh") {
+        Shell::Zsh(ZshShell {
+            shell_path: shell_path.to_string(),
+            zshrc_path: format!("{home}/.zshrc"),
+        })
+    } else if shell_path.ends_with("/bash") {
+        Shell::Bash(BashShell {
+            shell_path: shell_path.to_string(),
+            bashrc_path: format!("{home}/.bashrc"),
+        })
+    } else {
+        Shell::Unknown
+    }
+}
+
+/// Parses the output of `echo MARKER; env`. A line that does not start with
+/// `NAME=` continues the value of the previous variable.
+fn parse_env_output(stdout: &str) -> Option<HashMap<String, String>> {
+    let (_, env) = stdout.rsplit_once(&format!("{PROFILE_ENV_MARKER}\n"))?;
+    let mut vars: Vec<(String, String)> = Vec::new();
+    for line in env.lines() {
+        let assignment = line
+            .split_once('=')
+            .filter(|(name, _)| is_env_var_name(name));
+        match (assignment, vars.last_mut()) {
+            (Some((name, value)), _) => vars.push((name.to_string(), value.to_string())),
+            (None, Some((_, value))) => {
+                value.push('\n');
+                value.push_str(line);
+            }
+            (None, None) => {}
+        }
+    }
+    Some(vars.into_iter().collect())
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 fn strip_bash_lc(command: &Vec<String>) -> Option<String> {
@@ -134,18 +263,12 @@ pub async fn default_user_shell() -> Shell {
                 return Shell::Unknown;
             }
             let stdout = String::from_utf8_lossy(&o.stdout);
-            for line in stdout.lines() {
-                if let Some(shell_path) = line.strip_prefix("UserShell: ")
-                    && shell_path.ends_with("/zsh")
-                {
-                    return Shell::Zsh(ZshShell {
-                        shell_path: shell_path.to_string(),
-                        zshrc_path: format!("{home}/.zshrc"),
-                    });
-                }
-            }
-
-            Shell::Unknown
+            stdout
+                .lines()
+                .find_map(|line| line.strip_prefix("UserShell: "))
+                .map_or(Shell::Unknown, |shell_path| {
+                    shell_from_path(shell_path, &home)
+                })
         }
         _ => Shell::Unknown,
     }
@@ -153,7 +276,36 @@ pub async fn default_user_shell() -> Shell {
 
 #[cfg(all(not(target_os = "macos"), not(target_os = "windows")))]
 pub async fn default_user_shell() -> Shell {
-    Shell::Unknown
+    // `$SHELL` first, as it reflects a `chsh` that the passwd entry of a
+    // directory-service account may not.
+    let passwd = std::fs::read_to_string("/etc/passwd").ok();
+    let entry = passwd
+        .as_deref()
+        .and_then(|passwd| passwd_entry(passwd, &whoami::username()));
+    let shell_path = std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .or_else(|| entry.map(|(_, shell)| shell.to_string()));
+    let home = std::env::var("HOME")
+        .ok()
+        .or_else(|| entry.map(|(home, _)| home.to_string()));
+    match (shell_path, home) {
+        (Some(shell_path), Some(home)) => shell_from_path(&shell_path, &home),
+        _ => Shell::Unknown,
+    }
+}
+
+/// The home directory and login shell of `user` in the contents of
+/// `/etc/passwd`.
+#[cfg(all(not(target_os = "macos"), not(target_os = "windows")))]
+fn passwd_entry<'a>(passwd: &'a str, user: &str) -> Option<(&'a str, &'a str)> {
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.as_slice() {
+            [name, _, _, _, _, home, shell] if *name == user => Some((*home, *shell)),
+            _ => None,
+        }
+    })
 }
 
 #[cfg(target_os = "windows")]
@@ -271,7 +423,9 @@ mod tests {
             use std::collections::HashMap;
             use std::path::PathBuf;
 
+            use crate::exec::ExecLimits;
             use crate::exec::ExecParams;
+            use crate::exec::ExecPty;
             use crate::exec::SandboxType;
             use crate::exec::process_exec_tool_call;
             use crate::protocol::SandboxPolicy;
@@ -317,6 +471,10 @@ mod tests {
                     )]),
                     with_escalated_permissions: None,
                     justification: None,
+                    limits: ExecLimits::default(),
+                    use_pty: None,
+                    pty: ExecPty::default(),
+                    stdin: None,
                 },
                 SandboxType::None,
                 &SandboxPolicy::DangerFullAccess,
@@ -430,3 +588,78 @@ mod tests_windows {
         }
     }
 }
+
+#[cfg(test)]
+#[cfg(unix)]
+mod profile_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn env_output_after_the_marker_is_parsed() {
+        let stdout = format!(
+            "Welcome back!\nPATH=/nope\n{PROFILE_ENV_MARKER}\nPATH=/home/me/.nvm/bin:/usr/bin\nGREETING=hello\nworld\nEMPTY=\n"
+        );
+        assert_eq!(
+            parse_env_output(&stdout),
+            Some(HashMap::from([
+                ("PATH".to_string(), "/home/me/.nvm/bin:/usr/bin".to_string()),
+                ("GREETING".to_string(), "hello\nworld".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]))
+        );
+        assert_eq!(parse_env_output("no marker"), None);
+    }
+
+    #[test]
+    fn shells_are_recognized_by_path() {
+        assert_eq!(
+            shell_from_path("/usr/bin/bash", "/home/me"),
+            Shell::Bash(BashShell {
+                shell_path: "/usr/bin/bash".to_string(),
+                bashrc_path: "/home/me/.bashrc".to_string(),
+            })
+        );
+        assert_eq!(
+            shell_from_path("/bin/zsh", "/home/me").name(),
+            Some("zsh".to_string())
+        );
+        assert_eq!(shell_from_path("/usr/bin/fish", "/home/me"), Shell::Unknown);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn passwd_entries_are_looked_up_by_name() {
+        let passwd =
+            "root:x:0:0:root:/root:/bin/bash\nme:x:1000:1000:Me,,,:/home/me:/usr/bin/zsh\n";
+        assert_eq!(
+            passwd_entry(passwd, "me"),
+            Some(("/home/me", "/usr/bin/zsh"))
+        );
+        assert_eq!(passwd_entry(passwd, "nobody"), None);
+    }
+
+    #[tokio::test]
+    async fn bash_profile_env_includes_rc_exports() {
+        if !Path::new("/bin/bash").exists() {
+            return;
+        }
+        let home = tempfile::tempdir().unwrap();
+        let bashrc = home.path().join(".bashrc");
+        std::fs::write(
+            &bashrc,
+            "echo 'sourcing rc'\nexport CODEX_PROFILE_TEST=from-rc\n",
+        )
+        .unwrap();
+        let shell = Shell::Bash(BashShell {
+            shell_path: "/bin/bash".to_string(),
+            bashrc_path: bashrc.to_string_lossy().to_string(),
+        });
+
+        let env = shell.profile_env(home.path()).await.unwrap();
+        assert_eq!(
+            env.get("CODEX_PROFILE_TEST").map(String::as_str),
+            Some("from-rc")
+        );
+    }
+}
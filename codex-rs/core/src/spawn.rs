@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
 use std::path::PathBuf;
 use std::process::Stdio;
 use tokio::process::Child;
@@ -22,9 +24,47 @@ pub const CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR: &str = "CODEX_SANDBOX_NETWORK_
 /// accommodate sandboxing configuration and other sandboxing mechanisms.
 pub const CODEX_SANDBOX_ENV_VAR: &str = "CODEX_SANDBOX";
 
-#[derive(Debug, Clone, Copy)]
+/// Set to the `host:port` of the local network proxy when the sandbox policy
+/// only allows network access to an allowlist of domains. The sandbox permits
+/// outbound connections to that address and nothing else.
+pub const CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR: &str = "CODEX_SANDBOX_NETWORK_PROXY";
+
+/// Set along with [`CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR`] to a UNIX socket the
+/// proxy also listens on. The Linux sandbox forwards connections to the
+/// proxy's address inside the command's network namespace to it.
+pub const CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR: &str = "CODEX_SANDBOX_NETWORK_PROXY_SOCKET";
+
+/// Set to the session's scratch directory. Sandboxes keep it writable whatever
+/// the policy, so commands always have somewhere to put temporary files.
+pub const CODEX_SCRATCH_DIR_ENV_VAR: &str = "CODEX_SCRATCH_DIR";
+
+/// Set to the temp directory when the sandbox policy is read-only and
+/// `sandbox.read_only_allow_tmp` is on. Sandboxes keep it writable.
+pub const CODEX_SANDBOX_TMPDIR_ENV_VAR: &str = "CODEX_SANDBOX_TMPDIR";
+
+/// The directories exported in `env` that sandboxes keep writable on top of
+/// the policy's writable roots.
+pub(crate) fn extra_writable_dirs(env: &HashMap<String, String>) -> Vec<PathBuf> {
+    [CODEX_SCRATCH_DIR_ENV_VAR, CODEX_SANDBOX_TMPDIR_ENV_VAR]
+        .into_iter()
+        .filter_map(|var| env.get(var))
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+#[derive(Debug)]
 pub enum StdioPolicy {
     RedirectForShellTool,
+    /// Like [`StdioPolicy::RedirectForShellTool`], but stdin is a pipe for
+    /// the caller to write the command's input to.
+    RedirectForShellToolWithInput,
+    /// Like [`StdioPolicy::RedirectForShellTool`], but stdout and stderr are
+    /// the given pseudo-terminal, which also becomes the command's controlling
+    /// terminal. Stdin is a pipe so that reading from it never blocks on
+    /// input that will not come.
+    #[cfg(unix)]
+    RedirectToPty(OwnedFd),
     Inherit,
 }
 
@@ -86,14 +126,43 @@ pub(crate) async fn spawn_child_async(
     }
 
     match stdio_policy {
-        StdioPolicy::RedirectForShellTool => {
-            // Do not create a file descriptor for stdin because otherwise some
-            // commands may hang forever waiting for input. For example, ripgrep has
-            // a heuristic where it may try to read from stdin as explained here:
-            // https://github.com/BurntSushi/ripgrep/blob/e2362d4d5185d02fa857bf381e7bd52e66fafc73/crates/core/flags/hiargs.rs#L1101-L1103
-            cmd.stdin(Stdio::null());
+        StdioPolicy::RedirectForShellTool | StdioPolicy::RedirectForShellToolWithInput => {
+            if matches!(stdio_policy, StdioPolicy::RedirectForShellToolWithInput) {
+                cmd.stdin(Stdio::piped());
+            } else {
+                // Do not create a file descriptor for stdin because otherwise some
+                // commands may hang forever waiting for input. For example, ripgrep has
+                // a heuristic where it may try to read from stdin as explained here:
+                // https://github.com/BurntSushi/ripgrep/blob/e2362d4d5185d02fa857bf381e7bd52e66fafc73/crates/core/flags/hiargs.rs#L1101-L1103
+                cmd.stdin(Stdio::null());
+            }
 
             cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+            // Lead a new process group so that a command that times out can be
+            // killed together with everything it started.
+            #[cfg(unix)]
+            cmd.process_group(0);
+        }
+        #[cfg(unix)]
+        StdioPolicy::RedirectToPty(terminal) => {
+            cmd.stdin(Stdio::piped());
+            cmd.stdout(Stdio::from(terminal.try_clone()?))
+                .stderr(Stdio::from(terminal));
+
+            // A new session leads its own process group, like the one above,
+            // and takes the terminal (by now its stdout) as its controlling
+            // terminal so that `/dev/tty` refers to it.
+            unsafe {
+                cmd.pre_exec(|| {
+                    if libc::setsid() == -1
+                        || libc::ioctl(libc::STDOUT_FILENO, libc::TIOCSCTTY as _, 0) == -1
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
         }
         StdioPolicy::Inherit => {
             // Inherit stdin, stdout, and stderr from the parent process.
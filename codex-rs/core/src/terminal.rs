@@ -1,11 +1,3 @@
-use std::sync::OnceLock;
-
-static TERMINAL: OnceLock<String> = OnceLock::new();
-
-pub fn user_agent() -> String {
-    TERMINAL.get_or_init(detect_terminal).to_string()
-}
-
 /// Sanitize a header value to be used in a User-Agent string.
 ///
 /// This function replaces any characters that are not allowed in a User-Agent string with an underscore.
@@ -17,56 +9,201 @@ fn is_valid_header_value_char(c: char) -> bool {
     c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || c == '/'
 }
 
-fn sanitize_header_value(value: String) -> String {
+pub(crate) fn sanitize_header_value(value: String) -> String {
     value.replace(|c| !is_valid_header_value_char(c), "_")
 }
 
-fn detect_terminal() -> String {
-    sanitize_header_value(
-        if let Ok(tp) = std::env::var("TERM_PROGRAM")
-            && !tp.trim().is_empty()
-        {
-            let ver = std::env::var("TERM_PROGRAM_VERSION").ok();
-            match ver {
-                Some(v) if !v.trim().is_empty() => format!("{tp}/{v}"),
-                _ => tp,
-            }
-        } else if let Ok(v) = std::env::var("WEZTERM_VERSION") {
-            if !v.trim().is_empty() {
-                format!("WezTerm/{v}")
-            } else {
-                "WezTerm".to_string()
-            }
-        } else if std::env::var("KITTY_WINDOW_ID").is_ok()
-            || std::env::var("TERM")
-                .map(|t| t.contains("kitty"))
-                .unwrap_or(false)
-        {
-            "kitty".to_string()
-        } else if std::env::var("ALACRITTY_SOCKET").is_ok()
-            || std::env::var("TERM")
-                .map(|t| t == "alacritty")
-                .unwrap_or(false)
-        {
-            "Alacritty".to_string()
-        } else if let Ok(v) = std::env::var("KONSOLE_VERSION") {
-            if !v.trim().is_empty() {
-                format!("Konsole/{v}")
-            } else {
-                "Konsole".to_string()
-            }
-        } else if std::env::var("GNOME_TERMINAL_SCREEN").is_ok() {
-            return "gnome-terminal".to_string();
-        } else if let Ok(v) = std::env::var("VTE_VERSION") {
-            if !v.trim().is_empty() {
-                format!("VTE/{v}")
-            } else {
-                "VTE".to_string()
-            }
-        } else if std::env::var("WT_SESSION").is_ok() {
-            return "WindowsTerminal".to_string();
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// The terminal for the User-Agent, e.g. `iTerm.app/3.5.0`. Inside tmux or
+/// screen the multiplexer comes first, followed by the outer terminal when
+/// it can still be told, e.g. `tmux/3.4 WezTerm/20240203`.
+pub(crate) fn detect_terminal() -> String {
+    match (detect_multiplexer(), detect_outer_terminal()) {
+        (Some(multiplexer), Some(terminal)) => format!("{multiplexer} {terminal}"),
+        (Some(multiplexer), None) => multiplexer,
+        (None, Some(terminal)) => terminal,
+        (None, None) => {
+            sanitize_header_value(std::env::var("TERM").unwrap_or_else(|_| "unknown".to_string()))
+        }
+    }
+}
+
+fn detect_multiplexer() -> Option<String> {
+    if non_empty_env("TMUX").is_some() {
+        // tmux 3.2+ sets TERM_PROGRAM itself, hiding the outer terminal's.
+        let version = (non_empty_env("TERM_PROGRAM").as_deref() == Some("tmux"))
+            .then(|| non_empty_env("TERM_PROGRAM_VERSION"))
+            .flatten();
+        Some(sanitize_header_value(match version {
+            Some(v) => format!("tmux/{v}"),
+            None => "tmux".to_string(),
+        }))
+    } else if non_empty_env("STY").is_some() {
+        Some("screen".to_string())
+    } else {
+        None
+    }
+}
+
+fn detect_outer_terminal() -> Option<String> {
+    let terminal = if let Some(tp) = non_empty_env("TERM_PROGRAM").filter(|tp| tp != "tmux") {
+        match non_empty_env("TERM_PROGRAM_VERSION") {
+            Some(v) => format!("{tp}/{v}"),
+            None => tp,
+        }
+    } else if let Ok(v) = std::env::var("WEZTERM_VERSION") {
+        if !v.trim().is_empty() {
+            format!("WezTerm/{v}")
         } else {
-            std::env::var("TERM").unwrap_or_else(|_| "unknown".to_string())
-        },
-    )
+            "WezTerm".to_string()
+        }
+    } else if std::env::var("KITTY_WINDOW_ID").is_ok()
+        || std::env::var("TERM")
+            .map(|t| t.contains("kitty"))
+            .unwrap_or(false)
+    {
+        "kitty".to_string()
+    } else if std::env::var("ALACRITTY_SOCKET").is_ok()
+        || std::env::var("TERM")
+            .map(|t| t == "alacritty")
+            .unwrap_or(false)
+    {
+        "Alacritty".to_string()
+    } else if let Ok(v) = std::env::var("KONSOLE_VERSION") {
+        if !v.trim().is_empty() {
+            format!("Konsole/{v}")
+        } else {
+            "Konsole".to_string()
+        }
+    } else if std::env::var("GNOME_TERMINAL_SCREEN").is_ok() {
+        return Some("gnome-terminal".to_string());
+    } else if let Ok(v) = std::env::var("VTE_VERSION") {
+        if !v.trim().is_empty() {
+            format!("VTE/{v}")
+        } else {
+            "VTE".to_string()
+        }
+    } else if std::env::var("WT_SESSION").is_ok() {
+        return Some("WindowsTerminal".to_string());
+    } else {
+        return None;
+    };
+    Some(sanitize_header_value(terminal))
+}
+
+/// Every variable consulted by [`detect_terminal`] or the User-Agent suffix.
+#[cfg(test)]
+const USER_AGENT_ENV_VARS: &[&str] = &[
+    "TERM",
+    "TERM_PROGRAM",
+    "TERM_PROGRAM_VERSION",
+    "TMUX",
+    "STY",
+    "WEZTERM_VERSION",
+    "KITTY_WINDOW_ID",
+    "ALACRITTY_SOCKET",
+    "KONSOLE_VERSION",
+    "GNOME_TERMINAL_SCREEN",
+    "VTE_VERSION",
+    "WT_SESSION",
+    crate::user_agent::CODEX_USER_AGENT_SUFFIX_ENV_VAR,
+];
+
+/// Runs `f` with only `vars` set among the variables the User-Agent reads,
+/// restoring them afterwards. Tests that mutate the environment serialize on
+/// a lock so they do not observe each other's values.
+#[cfg(test)]
+pub(crate) fn with_user_agent_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    use std::sync::Mutex;
+    use std::sync::PoisonError;
+
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+    let _guard = ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let saved = USER_AGENT_ENV_VARS
+        .iter()
+        .map(|name| (*name, std::env::var_os(name)))
+        .collect::<Vec<_>>();
+    // SAFETY: the lock keeps these tests from touching the environment
+    // concurrently.
+    unsafe {
+        for name in USER_AGENT_ENV_VARS {
+            std::env::remove_var(name);
+        }
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+    }
+    let result = f();
+    unsafe {
+        for (name, value) in saved {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn detects_term_program_with_version() {
+        let terminal = with_user_agent_env(
+            &[
+                ("TERM_PROGRAM", "iTerm.app"),
+                ("TERM_PROGRAM_VERSION", "3.5.0"),
+            ],
+            detect_terminal,
+        );
+        assert_eq!(terminal, "iTerm.app/3.5.0");
+    }
+
+    #[test]
+    fn tmux_hides_the_outer_terminal_when_it_sets_term_program() {
+        let terminal = with_user_agent_env(
+            &[
+                ("TMUX", "/tmp/tmux-501/default,1234,0"),
+                ("TERM_PROGRAM", "tmux"),
+                ("TERM_PROGRAM_VERSION", "3.4"),
+                ("TERM", "tmux-256color"),
+            ],
+            detect_terminal,
+        );
+        assert_eq!(terminal, "tmux/3.4");
+    }
+
+    #[test]
+    fn multiplexers_look_through_to_the_outer_terminal() {
+        let tmux = with_user_agent_env(
+            &[
+                ("TMUX", "/tmp/tmux-501/default,1234,0"),
+                ("TERM_PROGRAM", "WezTerm"),
+                ("TERM_PROGRAM_VERSION", "20240203"),
+            ],
+            detect_terminal,
+        );
+        assert_eq!(tmux, "tmux WezTerm/20240203");
+
+        let screen = with_user_agent_env(
+            &[("STY", "1234.pts-0.host"), ("TERM", "screen")],
+            detect_terminal,
+        );
+        assert_eq!(screen, "screen");
+    }
+
+    #[test]
+    fn falls_back_to_term() {
+        assert_eq!(
+            with_user_agent_env(&[("TERM", "xterm 256")], detect_terminal),
+            "xterm_256"
+        );
+        assert_eq!(with_user_agent_env(&[], detect_terminal), "unknown");
+    }
 }
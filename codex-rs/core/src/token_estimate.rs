@@ -0,0 +1,221 @@
+//! Offline count of the number of tokens a prompt will consume.
+//!
+//! Text is encoded with the BPE ranks of the tiktoken encodings
+//! (`cl100k_base` / `o200k_base`) bundled by `tiktoken-rs`, so counts match
+//! what the model sees. Only the fixed per-item overhead and images are
+//! approximated, which is sufficient for warning the user before a turn
+//! would overflow the context window.
+
+use std::sync::LazyLock;
+
+use crate::model_family::ModelFamily;
+use codex_protocol::models::ContentItem;
+use codex_protocol::models::LocalShellAction;
+use codex_protocol::models::ReasoningItemContent;
+use codex_protocol::models::ReasoningItemReasoningSummary;
+use codex_protocol::models::ResponseItem;
+use tiktoken_rs::CoreBPE;
+use tracing::warn;
+
+/// Fixed overhead the API adds for every input item (role, separators, …).
+const TOKENS_PER_ITEM: usize = 4;
+
+/// Rough cost of an image input. The real cost depends on the resolution and
+/// detail level; this matches a single low-detail tile.
+const TOKENS_PER_IMAGE: usize = 85;
+
+/// Bytes per token assumed should the bundled ranks fail to load.
+const BYTES_PER_TOKEN: usize = 4;
+
+static CL100K_BASE: LazyLock<Option<CoreBPE>> =
+    LazyLock::new(|| load_encoding("cl100k_base", tiktoken_rs::cl100k_base));
+static O200K_BASE: LazyLock<Option<CoreBPE>> =
+    LazyLock::new(|| load_encoding("o200k_base", tiktoken_rs::o200k_base));
+
+fn load_encoding(name: &str, load: fn() -> anyhow::Result<CoreBPE>) -> Option<CoreBPE> {
+    load()
+        .inspect_err(|e| warn!("failed to load the {name} encoding: {e}"))
+        .ok()
+}
+
+/// The tiktoken encoding used by a model family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Cl100kBase,
+    O200kBase,
+}
+
+impl Encoding {
+    pub(crate) fn for_model_family(model: &ModelFamily) -> Self {
+        let family = model.family.as_str();
+        let is_legacy = family.starts_with("gpt-3.5")
+            || (family.starts_with("gpt-4")
+                && !family.starts_with("gpt-4o")
+                && !family.starts_with("gpt-4.1"));
+        if is_legacy {
+            Encoding::Cl100kBase
+        } else {
+            Encoding::O200kBase
+        }
+    }
+
+    fn bpe(self) -> Option<&'static CoreBPE> {
+        match self {
+            Encoding::Cl100kBase => CL100K_BASE.as_ref(),
+            Encoding::O200kBase => O200K_BASE.as_ref(),
+        }
+    }
+}
+
+/// Counts the tokens `text` encodes to. Special tokens such as
+/// `<|endoftext|>` are counted as the plain text the API treats them as.
+pub(crate) fn estimate_text_tokens(text: &str, encoding: Encoding) -> usize {
+    match encoding.bpe() {
+        Some(bpe) => bpe.encode_ordinary(text).len(),
+        None => text.len().div_ceil(BYTES_PER_TOKEN),
+    }
+}
+
+/// Estimates the number of tokens a single input item contributes to the
+/// prompt.
+pub(crate) fn estimate_response_item_tokens(item: &ResponseItem, encoding: Encoding) -> usize {
+    let text = |s: &str| estimate_text_tokens(s, encoding);
+    let content_tokens = match item {
+        ResponseItem::Message { role, content, .. } => {
+            text(role)
+                + content
+                    .iter()
+                    .map(|c| match c {
+                        ContentItem::InputText { text: t }
+                        | ContentItem::OutputText { text: t } => text(t),
+                        ContentItem::InputImage { .. } => TOKENS_PER_IMAGE,
+                    })
+                    .sum::<usize>()
+        }
+        ResponseItem::Reasoning {
+            summary, content, ..
+        } => {
+            let summary_tokens: usize = summary
+                .iter()
+                .map(|s| match s {
+                    ReasoningItemReasoningSummary::SummaryText { text: t } => text(t),
+                })
+                .sum();
+            let content_tokens: usize = content
+                .iter()
+                .flatten()
+                .map(|c| match c {
+                    ReasoningItemContent::ReasoningText { text: t }
+                    | ReasoningItemContent::Text { text: t } => text(t),
+                })
+                .sum();
+            summary_tokens + content_tokens
+        }
+        ResponseItem::LocalShellCall { action, .. } => {
+            let LocalShellAction::Exec(exec) = action;
+            exec.command.iter().map(|arg| text(arg)).sum::<usize>()
+                + exec.working_directory.as_deref().map_or(0, text)
+        }
+        ResponseItem::FunctionCall {
+            name,
+            arguments,
+            call_id,
+            ..
+        } => text(name) + text(arguments) + text(call_id),
+        ResponseItem::FunctionCallOutput { call_id, output } => {
+            text(call_id) + text(&output.content)
+        }
+        ResponseItem::CustomToolCall {
+            call_id,
+            name,
+            input,
+            ..
+        } => text(call_id) + text(name) + text(input),
+        ResponseItem::CustomToolCallOutput { call_id, output } => text(call_id) + text(output),
+        ResponseItem::WebSearchCall { .. } => 0,
+        ResponseItem::Other => return 0,
+    };
+    TOKENS_PER_ITEM + content_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codex_protocol::models::FunctionCallOutputPayload;
+    use pretty_assertions::assert_eq;
+
+    fn o200k(text: &str) -> usize {
+        estimate_text_tokens(text, Encoding::O200kBase)
+    }
+
+    #[test]
+    fn empty_text_has_no_tokens() {
+        assert_eq!(o200k(""), 0);
+    }
+
+    #[test]
+    fn plain_english_is_encoded_with_the_model_encoding() {
+        // Both cl100k_base and o200k_base encode this sentence as 10 tokens.
+        let sentence = "The quick brown fox jumps over the lazy dog.";
+        assert_eq!(o200k(sentence), 10);
+        assert_eq!(estimate_text_tokens(sentence, Encoding::Cl100kBase), 10);
+    }
+
+    #[test]
+    fn source_code_is_encoded_with_the_model_encoding() {
+        // "fn", " main", "()", " {}"
+        assert_eq!(o200k("fn main() {}"), 4);
+    }
+
+    #[test]
+    fn digits_are_grouped_in_threes() {
+        assert_eq!(o200k("1234567"), 3);
+    }
+
+    #[test]
+    fn special_tokens_are_plain_text() {
+        assert!(o200k("<|endoftext|>") > 1);
+    }
+
+    #[test]
+    fn legacy_models_use_cl100k() {
+        let family = |slug: &str| crate::model_family::find_family_for_model(slug).unwrap();
+        assert_eq!(
+            Encoding::for_model_family(&family("gpt-3.5-turbo")),
+            Encoding::Cl100kBase
+        );
+        assert_eq!(
+            Encoding::for_model_family(&family("gpt-4o")),
+            Encoding::O200kBase
+        );
+        assert_eq!(
+            Encoding::for_model_family(&family("o3")),
+            Encoding::O200kBase
+        );
+    }
+
+    #[test]
+    fn function_call_items_include_name_and_arguments() {
+        let call = ResponseItem::FunctionCall {
+            id: None,
+            name: "shell".to_string(),
+            arguments: r#"{"command":["ls","-la"]}"#.to_string(),
+            call_id: "call_1".to_string(),
+        };
+        let output = ResponseItem::FunctionCallOutput {
+            call_id: "call_1".to_string(),
+            output: FunctionCallOutputPayload {
+                content: "README.md".to_string(),
+                success: Some(true),
+            },
+        };
+        let call_tokens = estimate_response_item_tokens(&call, Encoding::O200kBase);
+        let output_tokens = estimate_response_item_tokens(&output, Encoding::O200kBase);
+        assert!(call_tokens > TOKENS_PER_ITEM + o200k("shell"));
+        assert!(output_tokens > TOKENS_PER_ITEM);
+        assert_eq!(
+            estimate_response_item_tokens(&ResponseItem::Other, Encoding::O200kBase),
+            0
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Caps how much tool output a single turn adds to the conversation, so a few
+//! large results (e.g. `cat` of a lockfile) cannot fill the context window.
+
+/// Budget used when the model's context window is unknown.
+const DEFAULT_BUDGET_BYTES: usize = 128 * 1024;
+
+/// How much of each tool output the model still sees once the budget is
+/// spent.
+const EXHAUSTED_EXCERPT_BYTES: usize = 1024;
+
+/// Default budget for a model with `context_window` tokens: about a quarter
+/// of the window, at roughly four bytes per token.
+pub(crate) fn default_tool_output_budget(context_window: Option<u64>) -> usize {
+    context_window.map_or(DEFAULT_BUDGET_BYTES, |tokens| {
+        usize::try_from(tokens).unwrap_or(usize::MAX)
+    })
+}
+
+/// Tracks the bytes of tool output added during one turn.
+#[derive(Debug)]
+pub(crate) struct ToolOutputBudget {
+    limit: usize,
+    used: usize,
+    exhausted: bool,
+}
+
+impl ToolOutputBudget {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: 0,
+            exhausted: false,
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Charges `output` against the budget, truncating it when it does not
+    /// fit. Returns true the first time the budget is exceeded.
+    pub(crate) fn apply(&mut self, output: &mut String) -> bool {
+        let remaining = self.limit.saturating_sub(self.used);
+        if output.len() <= remaining {
+            self.used += output.len();
+            return false;
+        }
+        let first = !self.exhausted;
+        self.exhausted = true;
+
+        let keep = take_bytes_at_char_boundary(output, remaining.max(EXHAUSTED_EXCERPT_BYTES));
+        self.used += keep.len();
+        if keep.len() == output.len() {
+            return first;
+        }
+        let omitted = output.len() - keep.len();
+        let total = output.len();
+        *output = format!(
+            "{keep}\n[... omitted {omitted} of {total} bytes: the tool output budget for this turn ({} bytes) is exhausted. Use targeted commands such as `rg -n <pattern>` or `sed -n '<start>,<end>p' <file>` to read only what you need ...]",
+            self.limit
+        );
+        first
+    }
+}
+
+fn take_bytes_at_char_boundary(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn outputs_within_budget_are_unchanged() {
+        let mut budget = ToolOutputBudget::new(10);
+        let mut output = "0123456789".to_string();
+        assert!(!budget.apply(&mut output));
+        assert_eq!(output, "0123456789");
+    }
+
+    #[test]
+    fn outputs_past_the_budget_are_truncated() {
+        let mut budget = ToolOutputBudget::new(2000);
+        let mut first = "a".repeat(1500);
+        assert!(!budget.apply(&mut first));
+
+        // The rest of the budget is larger than the excerpt, so it is kept.
+        let mut second = "b".repeat(3000);
+        assert!(budget.apply(&mut second));
+        assert!(second.starts_with(&"b".repeat(1024)));
+        assert!(!second.starts_with(&"b".repeat(1025)));
+        assert!(second.contains("omitted 1976 of 3000 bytes"));
+        assert!(second.contains("budget for this turn (2000 bytes) is exhausted"));
+
+        // Only the first overflow is reported.
+        let mut third = "c".repeat(3000);
+        assert!(!budget.apply(&mut third));
+        assert!(third.contains("omitted 1976 of 3000 bytes"));
+
+        // Small outputs still fit into the excerpt.
+        let mut small = "ok".to_string();
+        assert!(!budget.apply(&mut small));
+        assert_eq!(small, "ok");
+    }
+
+    #[test]
+    fn truncation_keeps_char_boundaries() {
+        let mut budget = ToolOutputBudget::new(0);
+        let mut output = "é".repeat(1000);
+        assert!(budget.apply(&mut output));
+        assert!(output.starts_with(&"é".repeat(512)));
+    }
+
+    #[test]
+    fn default_budget_scales_with_the_context_window() {
+        assert_eq!(default_tool_output_budget(Some(272_000)), 272_000);
+        assert_eq!(default_tool_output_budget(None), 128 * 1024);
+    }
+}
@@ -1,25 +1,72 @@
+use std::sync::OnceLock;
+
+use crate::terminal::detect_terminal;
+use crate::terminal::sanitize_header_value;
+
 const DEFAULT_ORIGINATOR: &str = "codex_cli_rs";
 
-pub fn get_codex_user_agent(originator: Option<&str>) -> String {
+/// Environment variable whose value is appended to the User-Agent, e.g. to
+/// tell a fleet of machines apart. Takes precedence over `user_agent_suffix`
+/// in `config.toml`.
+pub const CODEX_USER_AGENT_SUFFIX_ENV_VAR: &str = "CODEX_USER_AGENT_SUFFIX";
+
+/// `(<os> <version>; <arch>) <terminal>`, which does not change while the
+/// process runs and is costly to detect on some platforms.
+static PLATFORM: OnceLock<String> = OnceLock::new();
+
+/// `<originator>/<version> (<os> <version>; <arch>) <terminal> [<suffix>]`.
+/// `suffix` comes from the config and is overridden by
+/// [`CODEX_USER_AGENT_SUFFIX_ENV_VAR`].
+pub fn get_codex_user_agent(originator: Option<&str>, suffix: Option<&str>) -> String {
     let build_version = env!("CARGO_PKG_VERSION");
+    let mut user_agent = format!(
+        "{}/{build_version} {}",
+        originator.unwrap_or(DEFAULT_ORIGINATOR),
+        PLATFORM.get_or_init(detect_platform)
+    );
+    let suffix = std::env::var(CODEX_USER_AGENT_SUFFIX_ENV_VAR)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or_else(|| suffix.map(str::to_string));
+    if let Some(suffix) = suffix.as_deref().map(sanitize_suffix)
+        && !suffix.is_empty()
+    {
+        user_agent.push(' ');
+        user_agent.push_str(&suffix);
+    }
+    user_agent
+}
+
+fn detect_platform() -> String {
     let os_info = os_info::get();
     format!(
-        "{}/{build_version} ({} {}; {}) {}",
-        originator.unwrap_or(DEFAULT_ORIGINATOR),
+        "({} {}; {}) {}",
         os_info.os_type(),
         os_info.version(),
         os_info.architecture().unwrap_or("unknown"),
-        crate::terminal::user_agent()
+        detect_terminal()
     )
 }
 
+/// Keeps the suffix's space-separated product tokens, sanitizing each one so
+/// the header stays valid.
+fn sanitize_suffix(suffix: &str) -> String {
+    suffix
+        .split_whitespace()
+        .map(|token| sanitize_header_value(token.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::terminal::with_user_agent_env;
+    use pretty_assertions::assert_eq;
 
     #[test]
     fn test_get_codex_user_agent() {
-        let user_agent = get_codex_user_agent(None);
+        let user_agent = get_codex_user_agent(None, None);
         assert!(user_agent.starts_with("codex_cli_rs/"));
     }
 
@@ -27,11 +74,33 @@ mod tests {
     #[cfg(target_os = "macos")]
     fn test_macos() {
         use regex_lite::Regex;
-        let user_agent = get_codex_user_agent(None);
+        let user_agent = get_codex_user_agent(None, None);
+        // The terminal is two tokens inside tmux or screen.
         let re = Regex::new(
-            r"^codex_cli_rs/\d+\.\d+\.\d+ \(Mac OS \d+\.\d+\.\d+; (x86_64|arm64)\) (\S+)$",
+            r"^codex_cli_rs/\d+\.\d+\.\d+ \(Mac OS \d+\.\d+\.\d+; (x86_64|arm64)\) (\S+)( \S+)?$",
         )
         .unwrap();
         assert!(re.is_match(&user_agent));
     }
+
+    #[test]
+    fn appends_the_suffix_with_the_env_var_taking_precedence() {
+        let from_config = with_user_agent_env(&[], || {
+            get_codex_user_agent(Some("codex_exec"), Some("fleet/ci runner#7"))
+        });
+        assert!(from_config.starts_with("codex_exec/"));
+        assert!(from_config.ends_with(" fleet/ci runner_7"), "{from_config}");
+
+        let from_env =
+            with_user_agent_env(&[(CODEX_USER_AGENT_SUFFIX_ENV_VAR, "fleet/build")], || {
+                get_codex_user_agent(None, Some("fleet/ci"))
+            });
+        assert!(from_env.ends_with(" fleet/build"), "{from_env}");
+
+        let blank = with_user_agent_env(&[(CODEX_USER_AGENT_SUFFIX_ENV_VAR, " ")], || {
+            get_codex_user_agent(None, Some("fleet/ci"))
+        });
+        assert!(blank.ends_with(" fleet/ci"), "{blank}");
+        assert_eq!(sanitize_suffix("  a b  "), "a b");
+    }
 }
@@ -1,4 +1,18 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
 use serde::Serialize;
+use tracing::error;
+use tracing::warn;
+use uuid::Uuid;
+
+/// How long a notifier may run before it is killed, so that a hook that hangs
+/// does not pile up processes over a long session.
+const NOTIFIER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest message shown by the built-in desktop notification.
+const DESKTOP_MESSAGE_MAX_CHARS: usize = 200;
 
 /// User can configure a program that will receive notifications. Each
 /// notification is serialized as JSON and passed as an argument to the
@@ -9,6 +23,8 @@ pub(crate) enum UserNotification {
     #[serde(rename_all = "kebab-case")]
     AgentTurnComplete {
         turn_id: String,
+        session_id: Uuid,
+        cwd: PathBuf,
 
         /// Messages that the user sent to the agent to initiate the turn.
         input_messages: Vec<String>,
@@ -16,6 +32,161 @@ pub(crate) enum UserNotification {
         /// The last message sent by the assistant in the turn.
         last_assistant_message: Option<String>,
     },
+
+    /// The agent is waiting for the user to approve a command or a patch.
+    #[serde(rename_all = "kebab-case")]
+    ApprovalRequested {
+        turn_id: String,
+        session_id: Uuid,
+        cwd: PathBuf,
+
+        /// What needs approval, e.g. "run `cargo test`".
+        summary: String,
+
+        /// Why the agent asked, if it said.
+        reason: Option<String>,
+    },
+}
+
+impl UserNotification {
+    /// Text for the built-in desktop notification.
+    fn desktop_message(&self) -> String {
+        let message = match self {
+            UserNotification::AgentTurnComplete {
+                last_assistant_message,
+                ..
+            } => last_assistant_message
+                .clone()
+                .unwrap_or_else(|| "Turn complete".to_string()),
+            UserNotification::ApprovalRequested { summary, .. } => {
+                format!("Approval needed to {summary}")
+            }
+        };
+        let mut chars = message.chars();
+        let mut truncated: String = chars.by_ref().take(DESKTOP_MESSAGE_MAX_CHARS).collect();
+        if chars.next().is_some() {
+            truncated.push('…');
+        }
+        truncated
+    }
+}
+
+/// Delivers [`UserNotification`]s to the `notify` command from the config or,
+/// when none is set and `notifications` is enabled, as desktop notifications.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UserNotifier {
+    command: Option<Vec<String>>,
+    desktop_fallback: bool,
+    notify_on_approval: bool,
+}
+
+impl UserNotifier {
+    pub(crate) fn new(
+        command: Option<Vec<String>>,
+        desktop_fallback: bool,
+        notify_on_approval: bool,
+    ) -> Self {
+        Self {
+            command: command.filter(|command| !command.is_empty()),
+            desktop_fallback,
+            notify_on_approval,
+        }
+    }
+
+    /// Spawns the notifier for `notification`, if any. The notifier runs in
+    /// the background; failures are logged but otherwise ignored so that
+    /// notification issues do not interfere with the main workflow.
+    pub(crate) fn notify(&self, notification: &UserNotification) {
+        if matches!(notification, UserNotification::ApprovalRequested { .. })
+            && !self.notify_on_approval
+        {
+            return;
+        }
+
+        let argv = match &self.command {
+            Some(command) => {
+                let Ok(json) = serde_json::to_string(notification) else {
+                    error!("failed to serialise notification payload");
+                    return;
+                };
+                let mut argv = command.clone();
+                argv.push(json);
+                argv
+            }
+            None if self.desktop_fallback => {
+                match desktop_notification_argv("Codex", &notification.desktop_message()) {
+                    Some(argv) => argv,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        spawn_with_timeout(argv);
+    }
+}
+
+/// Command that shows a desktop notification on platforms with a standard
+/// tool for it.
+fn desktop_notification_argv(title: &str, message: &str) -> Option<Vec<String>> {
+    if cfg!(target_os = "macos") {
+        // Pass the text as arguments so it needs no AppleScript escaping.
+        Some(
+            [
+                "osascript",
+                "-e",
+                "on run argv",
+                "-e",
+                "display notification (item 2 of argv) with title (item 1 of argv)",
+                "-e",
+                "end run",
+                title,
+                message,
+            ]
+            .map(String::from)
+            .to_vec(),
+        )
+    } else if cfg!(target_os = "linux") {
+        Some(["notify-send", title, message].map(String::from).to_vec())
+    } else {
+        None
+    }
+}
+
+/// Fire-and-forget: the child is detached from the terminal and killed if it
+/// outlives [`NOTIFIER_TIMEOUT`].
+fn spawn_with_timeout(argv: Vec<String>) {
+    let Some((program, args)) = argv.split_first() else {
+        return;
+    };
+    let mut child = match tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("failed to spawn notifier '{program}': {e}");
+            return;
+        }
+    };
+
+    let program = program.clone();
+    tokio::spawn(async move {
+        match tokio::time::timeout(NOTIFIER_TIMEOUT, child.wait()).await {
+            Ok(Ok(status)) if !status.success() => {
+                warn!("notifier '{program}' exited with {status}");
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!("failed to wait for notifier '{program}': {e}"),
+            Err(_) => {
+                warn!("notifier '{program}' timed out; killing it");
+                let _ = child.kill().await;
+            }
+        }
+    });
 }
 
 #[cfg(test)]
@@ -26,6 +197,8 @@ mod tests {
     fn test_user_notification() {
         let notification = UserNotification::AgentTurnComplete {
             turn_id: "12345".to_string(),
+            session_id: Uuid::nil(),
+            cwd: PathBuf::from("/home/me/project"),
             input_messages: vec!["Rename `foo` to `bar` and update the callsites.".to_string()],
             last_assistant_message: Some(
                 "Rename complete and verified `cargo build` succeeds.".to_string(),
@@ -34,7 +207,42 @@ mod tests {
         let serialized = serde_json::to_string(&notification).unwrap();
         assert_eq!(
             serialized,
-            r#"{"type":"agent-turn-complete","turn-id":"12345","input-messages":["Rename `foo` to `bar` and update the callsites."],"last-assistant-message":"Rename complete and verified `cargo build` succeeds."}"#
+            r#"{"type":"agent-turn-complete","turn-id":"12345","session-id":"00000000-0000-0000-0000-000000000000","cwd":"/home/me/project","input-messages":["Rename `foo` to `bar` and update the callsites."],"last-assistant-message":"Rename complete and verified `cargo build` succeeds."}"#
+        );
+    }
+
+    #[test]
+    fn approval_notification_payload() {
+        let notification = UserNotification::ApprovalRequested {
+            turn_id: "7".to_string(),
+            session_id: Uuid::nil(),
+            cwd: PathBuf::from("/home/me/project"),
+            summary: "run `cargo test`".to_string(),
+            reason: None,
+        };
+        let serialized = serde_json::to_string(&notification).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"type":"approval-requested","turn-id":"7","session-id":"00000000-0000-0000-0000-000000000000","cwd":"/home/me/project","summary":"run `cargo test`","reason":null}"#
+        );
+        assert_eq!(
+            notification.desktop_message(),
+            "Approval needed to run `cargo test`"
+        );
+    }
+
+    #[test]
+    fn desktop_message_is_truncated() {
+        let notification = UserNotification::AgentTurnComplete {
+            turn_id: "1".to_string(),
+            session_id: Uuid::nil(),
+            cwd: PathBuf::from("/"),
+            input_messages: Vec::new(),
+            last_assistant_message: Some("é".repeat(DESKTOP_MESSAGE_MAX_CHARS + 1)),
+        };
+        assert_eq!(
+            notification.desktop_message(),
+            format!("{}…", "é".repeat(DESKTOP_MESSAGE_MAX_CHARS))
         );
     }
 }
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::path::Path;
 use std::time::Duration;
 
@@ -13,6 +14,35 @@ pub(crate) fn backoff(attempt: u64) -> Duration {
     Duration::from_millis((base as f64 * jitter) as u64)
 }
 
+/// Like [`backoff`], but starting from `base` and never exceeding `max`
+/// (before jitter is applied).
+pub(crate) fn backoff_with_base(attempt: u64, base: Duration, max: Duration) -> Duration {
+    let exp = BACKOFF_FACTOR.powi(attempt.saturating_sub(1).min(32) as i32);
+    let delay = (base.as_millis() as f64 * exp).min(max.as_millis() as f64);
+    let jitter = rand::rng().random_range(0.9..1.1);
+    Duration::from_millis((delay * jitter) as u64)
+}
+
+/// Extracts the delay requested by the server from `retry-after-ms` or
+/// `Retry-After` (either delta-seconds or an HTTP date).
+pub(crate) fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.trim().parse::<f64>().ok())
+        && ms >= 0.0
+    {
+        return Some(Duration::from_millis(ms as u64));
+    }
+
+    let value = header(reqwest::header::RETRY_AFTER.as_str())?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs >= 0.0).then(|| Duration::from_millis((secs * 1_000.0) as u64));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
 /// Return `true` if the project folder specified by the `Config` is inside a
 /// Git repository.
 ///
@@ -42,3 +72,122 @@ pub fn is_inside_git_repo(base_dir: &Path) -> bool {
 
     false
 }
+
+/// Removes what a command writes for the benefit of a terminal rather than a
+/// reader: ANSI escape sequences (colors, cursor movement, window titles) and
+/// lines that were redrawn with a carriage return, of which only the final
+/// contents are kept.
+pub(crate) fn strip_terminal_escapes(s: &str) -> Cow<'_, str> {
+    if !s.contains(['\x1b', '\r']) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut text = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameter and intermediate bytes up to a final byte.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC and other strings, terminated by BEL or `ESC \`.
+            Some(']' | 'P' | 'X' | '^' | '_') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Character set selection, e.g. `ESC ( B`.
+            Some('(' | ')' | '*' | '+') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| {
+            line.trim_end_matches('\r')
+                .rsplit('\r')
+                .next()
+                .unwrap_or_default()
+        })
+        .collect();
+    Cow::Owned(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn retry_after_prefers_milliseconds_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        headers.insert("retry-after", HeaderValue::from_static("10"));
+        assert_eq!(
+            parse_retry_after(&headers),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("12"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(12)));
+
+        // Dates in the past mean "retry now".
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert("retry-after", HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn strip_terminal_escapes_keeps_only_visible_text() {
+        assert!(matches!(
+            strip_terminal_escapes("plain\n"),
+            Cow::Borrowed("plain\n")
+        ));
+        assert_eq!(
+            strip_terminal_escapes("\x1b[1;31merror\x1b[0m: failed\r\n"),
+            "error: failed\n"
+        );
+        assert_eq!(
+            strip_terminal_escapes("\x1b]0;title\x07 10%\r 50%\r100%\r\ndone"),
+            "100%\ndone"
+        );
+        assert_eq!(strip_terminal_escapes("\x1b(Bx\x1b]8;;url\x1b\\y"), "xy");
+    }
+
+    #[test]
+    fn backoff_with_base_is_capped() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+        let first = backoff_with_base(1, base, max);
+        assert!(first >= Duration::from_millis(90) && first <= Duration::from_millis(110));
+        let late = backoff_with_base(20, base, max);
+        assert!(late <= Duration::from_millis(1100));
+    }
+}
@@ -0,0 +1,884 @@
+//! Sandbox for Windows.
+//!
+//! Commands are run inside an AppContainer: a low-privilege token that can
+//! only open files whose DACL explicitly grants the container access, and that
+//! has no network access unless the `internetClient` capability is added.
+//! Codex re-invokes its own executable with [`CODEX_WINDOWS_SANDBOX_ARG1`] so
+//! that the container setup happens in a short-lived helper process, mirroring
+//! how `codex-linux-sandbox` is used on Linux.
+//!
+//! The container is granted read/write access to the writable roots of the
+//! [`SandboxPolicy`] and explicitly denied write access to their read-only
+//! subpaths (e.g. `.git`); a working directory that is not writable gets a
+//! read grant instead. The grants are inheritable ACEs on those roots only,
+//! so they persist after the command exits and are written once, by the first
+//! command that needs them. Each distinct set of grants has a persistent
+//! profile of its own, named after a hash of the set, so a profile never
+//! holds access that the policy it runs under does not allow. Outside the
+//! granted roots the container can only read what Windows lets every app
+//! container read, such as the system directories.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use sha1::Digest;
+use sha1::Sha1;
+use tokio::process::Child;
+
+use crate::protocol::SandboxPolicy;
+use crate::spawn::CODEX_SANDBOX_ENV_VAR;
+use crate::spawn::CODEX_SCRATCH_DIR_ENV_VAR;
+use crate::spawn::StdioPolicy;
+use crate::spawn::spawn_child_async;
+
+/// Special argv[1] that makes the Codex executable act as the Windows sandbox
+/// helper: `codex --codex-run-as-windows-sandbox <policy-json> <cwd> -- <cmd>...`.
+pub const CODEX_WINDOWS_SANDBOX_ARG1: &str = "--codex-run-as-windows-sandbox";
+
+/// Prefix of the AppContainer profiles, one per set of grants.
+const APP_CONTAINER_NAME_PREFIX: &str = "openai.codex.sandbox";
+const APP_CONTAINER_DISPLAY_NAME: &str = "Codex sandbox";
+
+pub async fn spawn_command_under_windows_sandbox(
+    command: Vec<String>,
+    sandbox_policy: &SandboxPolicy,
+    cwd: PathBuf,
+    stdio_policy: StdioPolicy,
+    mut env: HashMap<String, String>,
+) -> io::Result<Child> {
+    let helper = std::env::current_exe()?;
+    let policy_json = serde_json::to_string(sandbox_policy).map_err(io::Error::other)?;
+    let mut args = vec![
+        CODEX_WINDOWS_SANDBOX_ARG1.to_string(),
+        policy_json,
+        cwd.to_string_lossy().to_string(),
+        "--".to_string(),
+    ];
+    args.extend(command);
+    env.insert(
+        CODEX_SANDBOX_ENV_VAR.to_string(),
+        "appcontainer".to_string(),
+    );
+    spawn_child_async(helper, args, None, cwd, sandbox_policy, stdio_policy, env).await
+}
+
+/// Whether commands can be sandboxed on this machine. AppContainers need
+/// Windows 8 or later and may be disabled by policy, so this is probed once by
+/// creating (or looking up) the profile that has no grants.
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(
+        || match ffi::AppContainerSid::create_or_open(&container_name(&[])) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!("Windows sandbox unavailable: {e}");
+                false
+            }
+        },
+    )
+}
+
+/// Entry point for the helper process. Never returns.
+pub fn run_main() -> ! {
+    let exit_code = match run_helper(std::env::args().skip(2).collect()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("codex windows sandbox: {e}");
+            1
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+fn run_helper(args: Vec<String>) -> io::Result<i32> {
+    let [policy_json, cwd, separator, command @ ..] = args.as_slice() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("usage: {CODEX_WINDOWS_SANDBOX_ARG1} <policy-json> <cwd> -- <command>..."),
+        ));
+    };
+    if separator != "--" || command.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected `--` followed by the command to run",
+        ));
+    }
+    let policy: SandboxPolicy = serde_json::from_str(policy_json).map_err(io::Error::other)?;
+    let cwd = PathBuf::from(cwd);
+    let scratch_dir = std::env::var_os(CODEX_SCRATCH_DIR_ENV_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    let plan = plan_for_policy(&policy, &cwd, scratch_dir.as_deref());
+
+    let container = ffi::AppContainerSid::create_or_open(&container_name(&plan.acl_rules))?;
+    for rule in &plan.acl_rules {
+        ffi::ensure_acl_rule(&container, rule)?;
+    }
+    if let Some(scratch_dir) = &plan.scratch_dir {
+        ffi::ensure_acl_rule(
+            &container,
+            &AclRule {
+                path: scratch_dir.clone(),
+                access: PathAccess::ReadWrite,
+            },
+        )?;
+    }
+    ffi::run_in_app_container(&container, plan.allow_network, &command_line(command), &cwd)
+}
+
+/// The name of the profile that holds exactly `rules`. The session's scratch
+/// directory is left out, so that sessions in the same workspace share a
+/// profile.
+fn container_name(rules: &[AclRule]) -> String {
+    let mut hasher = Sha1::new();
+    for rule in rules {
+        hasher.update(format!("{:?}", rule.access).as_bytes());
+        hasher.update(rule.path.as_os_str().as_encoded_bytes());
+        hasher.update([0]);
+    }
+    format!("{APP_CONTAINER_NAME_PREFIX}.{:x}", hasher.finalize())
+}
+
+/// Access the container is given to a path (and everything beneath it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PathAccess {
+    ReadExecute,
+    ReadWrite,
+    DenyWrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AclRule {
+    pub(crate) path: PathBuf,
+    pub(crate) access: PathAccess,
+}
+
+/// What the helper sets up before launching the command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SandboxPlan {
+    /// Applied in order; deny rules follow the grants they carve out of.
+    pub(crate) acl_rules: Vec<AclRule>,
+    /// Granted read/write access on top of `acl_rules`.
+    pub(crate) scratch_dir: Option<PathBuf>,
+    /// Whether the container gets the `internetClient` capability. The
+    /// container cannot be restricted to individual domains, so a policy with
+    /// an allowlist gets no network at all.
+    pub(crate) allow_network: bool,
+}
+
+/// `scratch_dir` is the session's scratch directory, which stays writable
+/// whatever the policy.
+pub(crate) fn plan_for_policy(
+    policy: &SandboxPolicy,
+    cwd: &Path,
+    scratch_dir: Option<&Path>,
+) -> SandboxPlan {
+    let mut grants = Vec::new();
+    let mut denies = Vec::new();
+    if policy.has_full_disk_write_access() {
+        grants.push(AclRule {
+            path: cwd.to_path_buf(),
+            access: PathAccess::ReadWrite,
+        });
+    } else {
+        let writable_roots = policy.get_writable_roots_with_cwd(cwd);
+        // A read-only subpath that is a writable root of its own, such as a
+        // `.git` opened up for publishing, stays writable.
+        let is_writable_root =
+            |path: &Path| writable_roots.iter().any(|writable| writable.root == path);
+        for writable_root in &writable_roots {
+            for subpath in &writable_root.read_only_subpaths {
+                if is_writable_root(subpath) {
+                    continue;
+                }
+                denies.push(AclRule {
+                    path: subpath.clone(),
+                    access: PathAccess::DenyWrite,
+                });
+            }
+            grants.push(AclRule {
+                path: writable_root.root.clone(),
+                access: PathAccess::ReadWrite,
+            });
+        }
+    }
+    // The container cannot read anything it has not been granted, so a
+    // working directory that is not writable is granted read access, once at
+    // its root. Nothing above it, such as the home directory, is touched.
+    if policy.has_full_disk_read_access() && !grants.iter().any(|rule| cwd.starts_with(&rule.path))
+    {
+        grants.push(AclRule {
+            path: cwd.to_path_buf(),
+            access: PathAccess::ReadExecute,
+        });
+    }
+    grants.extend(denies);
+
+    SandboxPlan {
+        acl_rules: grants,
+        scratch_dir: if policy.has_full_disk_write_access() {
+            None
+        } else {
+            scratch_dir.map(Path::to_path_buf)
+        },
+        allow_network: policy.has_full_network_access(),
+    }
+}
+
+/// Joins `args` into a command line that `CommandLineToArgvW` (and the MSVC
+/// runtime) split back into the same arguments.
+fn command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote_windows_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_windows_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\u{b}', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are escaped, as is the quote.
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // Backslashes before the closing quote must be escaped too.
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn to_wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(std::iter::once(0)).collect()
+}
+
+mod ffi {
+    use std::ffi::c_void;
+    use std::io;
+    use std::path::Path;
+    use std::ptr;
+
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::Foundation::ERROR_ALREADY_EXISTS;
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::Foundation::LocalFree;
+    use windows_sys::Win32::Foundation::WAIT_OBJECT_0;
+    use windows_sys::Win32::Security::ACCESS_ALLOWED_ACE;
+    use windows_sys::Win32::Security::ACCESS_ALLOWED_ACE_TYPE;
+    use windows_sys::Win32::Security::ACCESS_DENIED_ACE_TYPE;
+    use windows_sys::Win32::Security::ACL;
+    use windows_sys::Win32::Security::Authorization::ACCESS_MODE;
+    use windows_sys::Win32::Security::Authorization::DENY_ACCESS;
+    use windows_sys::Win32::Security::Authorization::EXPLICIT_ACCESS_W;
+    use windows_sys::Win32::Security::Authorization::GRANT_ACCESS;
+    use windows_sys::Win32::Security::Authorization::GetNamedSecurityInfoW;
+    use windows_sys::Win32::Security::Authorization::NO_MULTIPLE_TRUSTEE;
+    use windows_sys::Win32::Security::Authorization::SE_FILE_OBJECT;
+    use windows_sys::Win32::Security::Authorization::SetEntriesInAclW;
+    use windows_sys::Win32::Security::Authorization::SetNamedSecurityInfoW;
+    use windows_sys::Win32::Security::Authorization::TRUSTEE_IS_SID;
+    use windows_sys::Win32::Security::Authorization::TRUSTEE_IS_UNKNOWN;
+    use windows_sys::Win32::Security::Authorization::TRUSTEE_W;
+    use windows_sys::Win32::Security::CreateWellKnownSid;
+    use windows_sys::Win32::Security::DACL_SECURITY_INFORMATION;
+    use windows_sys::Win32::Security::EqualSid;
+    use windows_sys::Win32::Security::FreeSid;
+    use windows_sys::Win32::Security::GetAce;
+    use windows_sys::Win32::Security::INHERITED_ACE;
+    use windows_sys::Win32::Security::Isolation::CreateAppContainerProfile;
+    use windows_sys::Win32::Security::Isolation::DeriveAppContainerSidFromAppContainerName;
+    use windows_sys::Win32::Security::PSECURITY_DESCRIPTOR;
+    use windows_sys::Win32::Security::PSID;
+    use windows_sys::Win32::Security::SECURITY_CAPABILITIES;
+    use windows_sys::Win32::Security::SID_AND_ATTRIBUTES;
+    use windows_sys::Win32::Security::SUB_CONTAINERS_AND_OBJECTS_INHERIT;
+    use windows_sys::Win32::Security::WinCapabilityInternetClientSid;
+    use windows_sys::Win32::Storage::FileSystem::DELETE;
+    use windows_sys::Win32::Storage::FileSystem::FILE_APPEND_DATA;
+    use windows_sys::Win32::Storage::FileSystem::FILE_DELETE_CHILD;
+    use windows_sys::Win32::Storage::FileSystem::FILE_GENERIC_EXECUTE;
+    use windows_sys::Win32::Storage::FileSystem::FILE_GENERIC_READ;
+    use windows_sys::Win32::Storage::FileSystem::FILE_GENERIC_WRITE;
+    use windows_sys::Win32::Storage::FileSystem::FILE_WRITE_ATTRIBUTES;
+    use windows_sys::Win32::Storage::FileSystem::FILE_WRITE_DATA;
+    use windows_sys::Win32::Storage::FileSystem::FILE_WRITE_EA;
+    use windows_sys::Win32::System::Console::GetStdHandle;
+    use windows_sys::Win32::System::Console::STD_ERROR_HANDLE;
+    use windows_sys::Win32::System::Console::STD_INPUT_HANDLE;
+    use windows_sys::Win32::System::Console::STD_OUTPUT_HANDLE;
+    use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+    use windows_sys::Win32::System::JobObjects::CreateJobObjectW;
+    use windows_sys::Win32::System::JobObjects::JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    use windows_sys::Win32::System::JobObjects::JOBOBJECT_EXTENDED_LIMIT_INFORMATION;
+    use windows_sys::Win32::System::JobObjects::JobObjectExtendedLimitInformation;
+    use windows_sys::Win32::System::JobObjects::SetInformationJobObject;
+    use windows_sys::Win32::System::Threading::CREATE_SUSPENDED;
+    use windows_sys::Win32::System::Threading::CreateProcessW;
+    use windows_sys::Win32::System::Threading::DeleteProcThreadAttributeList;
+    use windows_sys::Win32::System::Threading::EXTENDED_STARTUPINFO_PRESENT;
+    use windows_sys::Win32::System::Threading::GetExitCodeProcess;
+    use windows_sys::Win32::System::Threading::INFINITE;
+    use windows_sys::Win32::System::Threading::InitializeProcThreadAttributeList;
+    use windows_sys::Win32::System::Threading::LPPROC_THREAD_ATTRIBUTE_LIST;
+    use windows_sys::Win32::System::Threading::PROC_THREAD_ATTRIBUTE_SECURITY_CAPABILITIES;
+    use windows_sys::Win32::System::Threading::PROCESS_INFORMATION;
+    use windows_sys::Win32::System::Threading::ResumeThread;
+    use windows_sys::Win32::System::Threading::STARTF_USESTDHANDLES;
+    use windows_sys::Win32::System::Threading::STARTUPINFOEXW;
+    use windows_sys::Win32::System::Threading::UpdateProcThreadAttribute;
+    use windows_sys::Win32::System::Threading::WaitForSingleObject;
+
+    use super::APP_CONTAINER_DISPLAY_NAME;
+    use super::AclRule;
+    use super::PathAccess;
+    use super::to_wide;
+
+    const SE_GROUP_ENABLED: u32 = 0x4;
+    const SECURITY_MAX_SID_SIZE: usize = 68;
+    const READ_EXECUTE: u32 = FILE_GENERIC_READ | FILE_GENERIC_EXECUTE;
+
+    const HRESULT_ALREADY_EXISTS: i32 = (0x8007_0000_u32 | ERROR_ALREADY_EXISTS) as i32;
+
+    /// The SID of an AppContainer profile, freed on drop. The profile itself
+    /// is kept, along with the grants made to it.
+    pub(super) struct AppContainerSid(PSID);
+
+    impl AppContainerSid {
+        pub(super) fn create_or_open(name: &str) -> io::Result<Self> {
+            let name = to_wide(name.as_ref());
+            let display_name = to_wide(APP_CONTAINER_DISPLAY_NAME.as_ref());
+            let mut sid: PSID = ptr::null_mut();
+            // SAFETY: the strings are NUL-terminated and outlive the call; on
+            // success `sid` receives a SID that we free with `FreeSid`.
+            let hr = unsafe {
+                CreateAppContainerProfile(
+                    name.as_ptr(),
+                    display_name.as_ptr(),
+                    display_name.as_ptr(),
+                    ptr::null(),
+                    0,
+                    &mut sid,
+                )
+            };
+            if hr == HRESULT_ALREADY_EXISTS {
+                // SAFETY: as above.
+                let hr =
+                    unsafe { DeriveAppContainerSidFromAppContainerName(name.as_ptr(), &mut sid) };
+                if hr < 0 {
+                    return Err(io::Error::from_raw_os_error(hr));
+                }
+            } else if hr < 0 {
+                return Err(io::Error::from_raw_os_error(hr));
+            }
+            Ok(Self(sid))
+        }
+    }
+
+    impl Drop for AppContainerSid {
+        fn drop(&mut self) {
+            // SAFETY: the SID was allocated by the AppContainer APIs above.
+            unsafe { FreeSid(self.0) };
+        }
+    }
+
+    /// The permission mask and ACE type used for each [`PathAccess`].
+    pub(super) fn access_mask(access: PathAccess) -> (u32, ACCESS_MODE) {
+        match access {
+            PathAccess::ReadExecute => (READ_EXECUTE, GRANT_ACCESS),
+            PathAccess::ReadWrite => (
+                FILE_GENERIC_READ | FILE_GENERIC_WRITE | FILE_GENERIC_EXECUTE | DELETE,
+                GRANT_ACCESS,
+            ),
+            // Deny only the rights that modify data: denying all of
+            // FILE_GENERIC_WRITE would also deny SYNCHRONIZE and READ_CONTROL,
+            // which reads need.
+            PathAccess::DenyWrite => (
+                FILE_WRITE_DATA
+                    | FILE_APPEND_DATA
+                    | FILE_WRITE_EA
+                    | FILE_WRITE_ATTRIBUTES
+                    | FILE_DELETE_CHILD
+                    | DELETE,
+                DENY_ACCESS,
+            ),
+        }
+    }
+
+    /// Adds an inheritable ACE for the container to the DACL of `rule.path`,
+    /// unless the path does not exist or already has that ACE, which saves
+    /// propagating it through the whole tree again.
+    pub(super) fn ensure_acl_rule(container: &AppContainerSid, rule: &AclRule) -> io::Result<()> {
+        if !rule.path.exists() {
+            return Ok(());
+        }
+        let (permissions, mode) = access_mask(rule.access);
+        let ace_type = if mode == DENY_ACCESS {
+            ACCESS_DENIED_ACE_TYPE
+        } else {
+            ACCESS_ALLOWED_ACE_TYPE
+        };
+        if has_explicit_ace(&rule.path, container.0, permissions, ace_type)? {
+            return Ok(());
+        }
+        set_acl_entry(&rule.path, container.0, permissions, mode)
+    }
+
+    /// Whether the DACL of `path` has an ACE of its own (not an inherited
+    /// one) of `ace_type` naming `sid` for at least `permissions`. Allow and
+    /// deny ACEs share the layout of `ACCESS_ALLOWED_ACE`.
+    fn has_explicit_ace(
+        path: &Path,
+        sid: PSID,
+        permissions: u32,
+        ace_type: u32,
+    ) -> io::Result<bool> {
+        let path = to_wide(path.as_os_str());
+        let mut dacl: *mut ACL = ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        // SAFETY: `path` is NUL-terminated; the descriptor owns `dacl`, whose
+        // ACEs are only read before it is released with LocalFree.
+        unsafe {
+            let status = GetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut dacl,
+                ptr::null_mut(),
+                &mut descriptor,
+            );
+            if status != ERROR_SUCCESS {
+                return Err(io::Error::from_raw_os_error(status as i32));
+            }
+            let mut found = false;
+            let ace_count = if dacl.is_null() { 0 } else { (*dacl).AceCount };
+            for index in 0..u32::from(ace_count) {
+                let mut ace: *mut c_void = ptr::null_mut();
+                if GetAce(dacl, index, &mut ace) == 0 {
+                    continue;
+                }
+                let ace = ace as *const ACCESS_ALLOWED_ACE;
+                if u32::from((*ace).Header.AceType) == ace_type
+                    && u32::from((*ace).Header.AceFlags) & INHERITED_ACE == 0
+                    && (*ace).Mask & permissions == permissions
+                    && EqualSid(&(*ace).SidStart as *const u32 as PSID, sid) != 0
+                {
+                    found = true;
+                    break;
+                }
+            }
+            LocalFree(descriptor);
+            Ok(found)
+        }
+    }
+
+    /// Merges an inheritable ACE for `sid` into the DACL of `path`, which
+    /// Windows then propagates to everything beneath it.
+    fn set_acl_entry(
+        path: &Path,
+        sid: PSID,
+        permissions: u32,
+        mode: ACCESS_MODE,
+    ) -> io::Result<()> {
+        let path = to_wide(path.as_os_str());
+        let entry = EXPLICIT_ACCESS_W {
+            grfAccessPermissions: permissions,
+            grfAccessMode: mode,
+            grfInheritance: SUB_CONTAINERS_AND_OBJECTS_INHERIT,
+            Trustee: TRUSTEE_W {
+                pMultipleTrustee: ptr::null_mut(),
+                MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+                TrusteeForm: TRUSTEE_IS_SID,
+                TrusteeType: TRUSTEE_IS_UNKNOWN,
+                ptstrName: sid as *mut u16,
+            },
+        };
+        let mut old_dacl: *mut ACL = ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        let mut new_dacl: *mut ACL = ptr::null_mut();
+        // SAFETY: `path` is NUL-terminated; the descriptor returned by
+        // GetNamedSecurityInfoW owns `old_dacl` and both it and `new_dacl` are
+        // released with LocalFree below.
+        unsafe {
+            let status = GetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut old_dacl,
+                ptr::null_mut(),
+                &mut descriptor,
+            );
+            if status != ERROR_SUCCESS {
+                return Err(io::Error::from_raw_os_error(status as i32));
+            }
+            let status = SetEntriesInAclW(1, &entry, old_dacl, &mut new_dacl);
+            if status != ERROR_SUCCESS {
+                LocalFree(descriptor);
+                return Err(io::Error::from_raw_os_error(status as i32));
+            }
+            let status = SetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                new_dacl,
+                ptr::null(),
+            );
+            LocalFree(new_dacl as *mut c_void);
+            LocalFree(descriptor);
+            if status != ERROR_SUCCESS {
+                return Err(io::Error::from_raw_os_error(status as i32));
+            }
+        }
+        Ok(())
+    }
+
+    /// Owns a handle and closes it on drop.
+    struct OwnedHandle(HANDLE);
+
+    impl Drop for OwnedHandle {
+        fn drop(&mut self) {
+            // SAFETY: the handle was returned by a successful Win32 call.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// Runs `command_line` inside the container with this process's stdio and
+    /// waits for it. The child is placed in a
+    /// kill-on-close job so that neither it nor its descendants outlive the
+    /// helper.
+    pub(super) fn run_in_app_container(
+        container: &AppContainerSid,
+        allow_network: bool,
+        command_line: &str,
+        cwd: &Path,
+    ) -> io::Result<i32> {
+        let mut internet_client_sid = [0u8; SECURITY_MAX_SID_SIZE];
+        let mut capabilities = Vec::new();
+        if allow_network {
+            let mut size = SECURITY_MAX_SID_SIZE as u32;
+            // SAFETY: the buffer is SECURITY_MAX_SID_SIZE bytes, as required.
+            let ok = unsafe {
+                CreateWellKnownSid(
+                    WinCapabilityInternetClientSid,
+                    ptr::null_mut(),
+                    internet_client_sid.as_mut_ptr() as PSID,
+                    &mut size,
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            capabilities.push(SID_AND_ATTRIBUTES {
+                Sid: internet_client_sid.as_mut_ptr() as PSID,
+                Attributes: SE_GROUP_ENABLED,
+            });
+        }
+        let mut security_capabilities = SECURITY_CAPABILITIES {
+            AppContainerSid: container.0,
+            Capabilities: capabilities.as_mut_ptr(),
+            CapabilityCount: capabilities.len() as u32,
+            Reserved: 0,
+        };
+
+        let mut command_line = to_wide(command_line.as_ref());
+        let cwd = to_wide(cwd.as_os_str());
+
+        let mut attribute_list_size = 0;
+        // SAFETY: the first call only reports the size of the list; the
+        // second initializes a buffer of that size, which is deleted below.
+        unsafe {
+            InitializeProcThreadAttributeList(ptr::null_mut(), 1, 0, &mut attribute_list_size);
+        }
+        let mut attribute_list_buf = vec![0u8; attribute_list_size];
+        let attribute_list = attribute_list_buf.as_mut_ptr() as LPPROC_THREAD_ATTRIBUTE_LIST;
+        // SAFETY: see above.
+        if unsafe {
+            InitializeProcThreadAttributeList(attribute_list, 1, 0, &mut attribute_list_size)
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let result = spawn_and_wait(
+            attribute_list,
+            &mut security_capabilities,
+            &mut command_line,
+            &cwd,
+        );
+        // SAFETY: the list was initialized above and is no longer in use.
+        unsafe { DeleteProcThreadAttributeList(attribute_list) };
+        result
+    }
+
+    fn spawn_and_wait(
+        attribute_list: LPPROC_THREAD_ATTRIBUTE_LIST,
+        security_capabilities: &mut SECURITY_CAPABILITIES,
+        command_line: &mut [u16],
+        cwd: &[u16],
+    ) -> io::Result<i32> {
+        // SAFETY: every pointer handed to the Win32 calls below refers to a
+        // value that outlives the call, and handles are closed on every path.
+        unsafe {
+            if UpdateProcThreadAttribute(
+                attribute_list,
+                0,
+                PROC_THREAD_ATTRIBUTE_SECURITY_CAPABILITIES as usize,
+                security_capabilities as *mut SECURITY_CAPABILITIES as *const c_void,
+                std::mem::size_of::<SECURITY_CAPABILITIES>(),
+                ptr::null_mut(),
+                ptr::null(),
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            let job = CreateJobObjectW(ptr::null(), ptr::null());
+            if job.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let job = OwnedHandle(job);
+            let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            if SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &limits as *const JOBOBJECT_EXTENDED_LIMIT_INFORMATION as *const c_void,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut startup_info: STARTUPINFOEXW = std::mem::zeroed();
+            startup_info.StartupInfo.cb = std::mem::size_of::<STARTUPINFOEXW>() as u32;
+            startup_info.StartupInfo.dwFlags = STARTF_USESTDHANDLES;
+            startup_info.StartupInfo.hStdInput = GetStdHandle(STD_INPUT_HANDLE);
+            startup_info.StartupInfo.hStdOutput = GetStdHandle(STD_OUTPUT_HANDLE);
+            startup_info.StartupInfo.hStdError = GetStdHandle(STD_ERROR_HANDLE);
+            startup_info.lpAttributeList = attribute_list;
+
+            let mut process_info: PROCESS_INFORMATION = std::mem::zeroed();
+            if CreateProcessW(
+                ptr::null(),
+                command_line.as_mut_ptr(),
+                ptr::null(),
+                ptr::null(),
+                1,
+                EXTENDED_STARTUPINFO_PRESENT | CREATE_SUSPENDED,
+                ptr::null(),
+                cwd.as_ptr(),
+                &startup_info.StartupInfo,
+                &mut process_info,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            let process = OwnedHandle(process_info.hProcess);
+            let thread = OwnedHandle(process_info.hThread);
+
+            // Assign the job before the child runs any code so that it cannot
+            // escape it by spawning descendants early.
+            if AssignProcessToJobObject(job.0, process.0) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            ResumeThread(thread.0);
+            if WaitForSingleObject(process.0, INFINITE) != WAIT_OBJECT_0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut exit_code = 0u32;
+            if GetExitCodeProcess(process.0, &mut exit_code) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(exit_code as i32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn workspace_write(writable_roots: Vec<PathBuf>, network_access: bool) -> SandboxPolicy {
+        SandboxPolicy::WorkspaceWrite {
+            writable_roots,
+            network_access,
+            allowed_domains: Vec::new(),
+            exclude_tmpdir_env_var: true,
+            exclude_slash_tmp: true,
+        }
+    }
+
+    #[test]
+    fn workspace_write_grants_roots_and_denies_git() {
+        let cwd = TempDir::new().unwrap();
+        let extra = TempDir::new().unwrap();
+        std::fs::create_dir(cwd.path().join(".git")).unwrap();
+
+        let plan = plan_for_policy(
+            &workspace_write(vec![extra.path().to_path_buf()], false),
+            cwd.path(),
+            None,
+        );
+
+        assert_eq!(
+            plan,
+            SandboxPlan {
+                acl_rules: vec![
+                    AclRule {
+                        path: extra.path().to_path_buf(),
+                        access: PathAccess::ReadWrite,
+                    },
+                    AclRule {
+                        path: cwd.path().to_path_buf(),
+                        access: PathAccess::ReadWrite,
+                    },
+                    AclRule {
+                        path: cwd.path().join(".git"),
+                        access: PathAccess::DenyWrite,
+                    },
+                ],
+                scratch_dir: None,
+                allow_network: false,
+            }
+        );
+    }
+
+    #[test]
+    fn git_dir_listed_as_a_writable_root_is_not_denied() {
+        let cwd = TempDir::new().unwrap();
+        let git_dir = cwd.path().join(".git");
+        std::fs::create_dir(&git_dir).unwrap();
+
+        let plan = plan_for_policy(
+            &workspace_write(vec![git_dir.clone()], true),
+            cwd.path(),
+            None,
+        );
+
+        assert_eq!(
+            plan.acl_rules,
+            vec![
+                AclRule {
+                    path: git_dir,
+                    access: PathAccess::ReadWrite,
+                },
+                AclRule {
+                    path: cwd.path().to_path_buf(),
+                    access: PathAccess::ReadWrite,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_only_grants_read_access_to_cwd_only() {
+        let cwd = TempDir::new().unwrap();
+        let scratch = TempDir::new().unwrap();
+
+        let plan = plan_for_policy(
+            &SandboxPolicy::new_read_only_policy(),
+            cwd.path(),
+            Some(scratch.path()),
+        );
+
+        assert_eq!(
+            plan,
+            SandboxPlan {
+                acl_rules: vec![AclRule {
+                    path: cwd.path().to_path_buf(),
+                    access: PathAccess::ReadExecute,
+                }],
+                scratch_dir: Some(scratch.path().to_path_buf()),
+                allow_network: false,
+            }
+        );
+    }
+
+    #[test]
+    fn profiles_are_named_after_their_grants() {
+        let cwd = TempDir::new().unwrap();
+        let read_only = plan_for_policy(&SandboxPolicy::new_read_only_policy(), cwd.path(), None);
+        let writable = plan_for_policy(&workspace_write(Vec::new(), false), cwd.path(), None);
+
+        let name = container_name(&read_only.acl_rules);
+        assert!(name.starts_with(APP_CONTAINER_NAME_PREFIX));
+        // Profile names are limited to 64 characters.
+        assert!(name.len() <= 64);
+        assert_eq!(name, container_name(&read_only.acl_rules));
+        assert_ne!(name, container_name(&writable.acl_rules));
+        assert_ne!(name, container_name(&[]));
+    }
+
+    #[test]
+    fn network_requires_unrestricted_network_access() {
+        let cwd = TempDir::new().unwrap();
+        assert!(
+            plan_for_policy(&workspace_write(Vec::new(), true), cwd.path(), None).allow_network
+        );
+
+        let allowlisted = SandboxPolicy::WorkspaceWrite {
+            writable_roots: Vec::new(),
+            network_access: false,
+            allowed_domains: vec!["example.com".to_string()],
+            exclude_tmpdir_env_var: true,
+            exclude_slash_tmp: true,
+        };
+        assert!(!plan_for_policy(&allowlisted, cwd.path(), None).allow_network);
+    }
+
+    #[test]
+    fn deny_write_keeps_read_rights() {
+        let (mask, mode) = ffi::access_mask(PathAccess::DenyWrite);
+        assert_eq!(
+            mode,
+            windows_sys::Win32::Security::Authorization::DENY_ACCESS
+        );
+        assert_eq!(
+            mask & windows_sys::Win32::Storage::FileSystem::FILE_GENERIC_READ,
+            0
+        );
+    }
+
+    #[test]
+    fn quotes_arguments_for_command_line_to_argv() {
+        assert_eq!(quote_windows_arg("plain"), "plain");
+        assert_eq!(quote_windows_arg(""), "\"\"");
+        assert_eq!(quote_windows_arg("with space"), "\"with space\"");
+        assert_eq!(quote_windows_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(
+            quote_windows_arg(r"C:\dir with space\"),
+            r#""C:\dir with space\\""#
+        );
+        assert_eq!(
+            command_line(&["cmd".to_string(), "/c".to_string(), "echo a b".to_string()]),
+            r#"cmd /c "echo a b""#
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Remembers which wire API the servers of providers without a `wire_api`
+//! speak, so that only the first session with a provider pays for the probe.
+//! Entries live in `wire_api.json` under the cache directory and are probed
+//! again once they are older than [`WIRE_API_CACHE_TTL`].
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::model_provider_info::ModelProviderInfo;
+use crate::model_provider_info::WireApi;
+
+const WIRE_API_CACHE_FILENAME: &str = "wire_api.json";
+
+/// How long a probed wire API is trusted; servers do get upgraded.
+pub(crate) const WIRE_API_CACHE_TTL: Duration = Duration::days(7);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// Keyed by [`cache_key`].
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct CacheEntry {
+    wire_api: WireApi,
+    // ISO-8601 timestamp (RFC3339)
+    probed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct WireApiCache {
+    path: PathBuf,
+}
+
+impl WireApiCache {
+    pub(crate) fn new(cache_dir: &Path) -> Self {
+        Self {
+            path: cache_dir.join(WIRE_API_CACHE_FILENAME),
+        }
+    }
+
+    /// The wire API last probed for `provider`, unless it has expired.
+    pub(crate) fn get(&self, provider: &ModelProviderInfo) -> Option<WireApi> {
+        self.get_at(&cache_key(provider), Utc::now())
+    }
+
+    fn get_at(&self, key: &str, now: DateTime<Utc>) -> Option<WireApi> {
+        let entry = *self.read().entries.get(key)?;
+        (now - entry.probed_at < WIRE_API_CACHE_TTL).then_some(entry.wire_api)
+    }
+
+    /// Records the outcome of a probe. Failures are only logged: the next
+    /// session probes again.
+    pub(crate) fn put(&self, provider: &ModelProviderInfo, wire_api: WireApi) {
+        self.put_at(&cache_key(provider), wire_api, Utc::now());
+    }
+
+    fn put_at(&self, key: &str, wire_api: WireApi, now: DateTime<Utc>) {
+        let mut file = self.read();
+        file.entries
+            .retain(|_, entry| now - entry.probed_at < WIRE_API_CACHE_TTL);
+        file.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                wire_api,
+                probed_at: now,
+            },
+        );
+        let result = serde_json::to_string_pretty(&file)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&self.path, json)
+            });
+        if let Err(e) = result {
+            warn!("failed to write {}: {e}", self.path.display());
+        }
+    }
+
+    /// A missing or unreadable file is an empty cache.
+    fn read(&self) -> CacheFile {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Providers are told apart by name and base URL: two entries pointing at
+/// the same server may still be routed or authenticated differently.
+fn cache_key(provider: &ModelProviderInfo) -> String {
+    format!(
+        "{} {}",
+        provider.name,
+        provider.base_url.as_deref().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn probed_wire_api_is_remembered_until_it_expires() {
+        let dir = TempDir::new().unwrap();
+        let cache = WireApiCache::new(dir.path());
+        let now = Utc::now();
+        assert_eq!(cache.get_at("http://localhost:8000/v1", now), None);
+
+        cache.put_at("http://localhost:8000/v1", WireApi::Chat, now);
+        cache.put_at("https://llm.example.com/v1", WireApi::Responses, now);
+        assert_eq!(
+            cache.get_at("http://localhost:8000/v1", now),
+            Some(WireApi::Chat)
+        );
+        assert_eq!(
+            cache.get_at("https://llm.example.com/v1", now + Duration::days(6)),
+            Some(WireApi::Responses)
+        );
+        assert_eq!(
+            cache.get_at("https://llm.example.com/v1", now + WIRE_API_CACHE_TTL),
+            None
+        );
+
+        // Writing drops expired entries.
+        let later = now + WIRE_API_CACHE_TTL;
+        cache.put_at("http://localhost:8000/v1", WireApi::Responses, later);
+        assert_eq!(cache.read().entries.len(), 1);
+    }
+
+    #[test]
+    fn unreadable_cache_is_empty() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(WIRE_API_CACHE_FILENAME), "not json").unwrap();
+        let cache = WireApiCache::new(dir.path());
+        let provider = crate::built_in_model_providers()["openai"].clone();
+        assert_eq!(cache.get(&provider), None);
+        cache.put(&provider, WireApi::Chat);
+        assert_eq!(cache.get(&provider), Some(WireApi::Chat));
+    }
+
+    #[test]
+    fn providers_on_the_same_server_are_cached_apart() {
+        let dir = TempDir::new().unwrap();
+        let cache = WireApiCache::new(dir.path());
+        let local = ModelProviderInfo {
+            name: "Local".to_string(),
+            base_url: Some("http://localhost:8000/v1".to_string()),
+            ..crate::built_in_model_providers()["openai"].clone()
+        };
+        let proxied = ModelProviderInfo {
+            name: "Proxied".to_string(),
+            ..local.clone()
+        };
+        cache.put(&local, WireApi::Chat);
+        assert_eq!(cache.get(&local), Some(WireApi::Chat));
+        assert_eq!(cache.get(&proxied), None);
+    }
+}
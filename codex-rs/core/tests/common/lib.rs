@@ -124,3 +124,59 @@ where
         }
     }
 }
+
+/// Builds an SSE stream body from a list of JSON events. An event with only a
+/// `type` field gets no `data:` line, as with [`load_sse_fixture`].
+pub fn sse(events: Vec<serde_json::Value>) -> String {
+    use std::fmt::Write as _;
+    let mut out = String::new();
+    for ev in events {
+        let kind = ev
+            .get("type")
+            .and_then(|v| v.as_str())
+            .expect("event missing type");
+        writeln!(&mut out, "event: {kind}").expect("write to String");
+        if !ev.as_object().map(|o| o.len() == 1).unwrap_or(false) {
+            write!(&mut out, "data: {ev}\n\n").expect("write to String");
+        } else {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// SSE event for a completed response with a specific id.
+pub fn ev_completed(id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "response.completed",
+        "response": {
+            "id": id,
+            "usage": {"input_tokens":0,"input_tokens_details":null,"output_tokens":0,"output_tokens_details":null,"total_tokens":0}
+        }
+    })
+}
+
+pub fn sse_response(body: String) -> wiremock::ResponseTemplate {
+    wiremock::ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(body, "text/event-stream")
+}
+
+/// Answers exactly one `POST /v1/responses` request matching `matcher` with
+/// the SSE stream `body`.
+pub async fn mount_sse_once<M>(server: &wiremock::MockServer, matcher: M, body: String)
+where
+    M: wiremock::Match + Send + Sync + 'static,
+{
+    use wiremock::Mock;
+    use wiremock::matchers::method;
+    use wiremock::matchers::path;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .and(matcher)
+        .respond_with(sse_response(body))
+        .expect(1)
+        .mount(server)
+        .await;
+}
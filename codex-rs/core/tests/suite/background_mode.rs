@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use codex_core::CodexConversation;
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::ProviderKind;
+use codex_core::WireApi;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::load_sse_fixture_with_id;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use core_test_support::wait_for_event_with_timeout;
+use serde_json::json;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::body_partial_json;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+use wiremock::matchers::query_param;
+
+fn provider(server: &MockServer) -> ModelProviderInfo {
+    ModelProviderInfo {
+        name: "mock-openai".into(),
+        base_url: Some(format!("{}/v1", server.uri())),
+        env_key: Some("PATH".into()),
+        env_key_instructions: None,
+        wire_api: Some(WireApi::Responses),
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(0),
+        request_retry_base_delay_ms: Some(10),
+        request_retry_max_delay_ms: Some(100),
+        request_retry_on_status: None,
+        stream_max_retries: Some(1),
+        stream_idle_timeout_ms: Some(2_000),
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
+        requires_openai_auth: false,
+        stored_api_key: None,
+    }
+}
+
+async fn start_turn(server: &MockServer, home: &TempDir) -> Arc<CodexConversation> {
+    let mut config = load_default_config_for_test(home);
+    config.model_provider = provider(server);
+    config.model_family.use_background_mode = true;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    codex
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quiet_background_response_is_checked_on_and_resumed() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    // The connection drops right after the response was created.
+    let created = load_sse_fixture_with_id_from_str(
+        r#"[{"type": "response.created", "sequence_number": 0, "response": {"id": "__ID__"}}]"#,
+        "resp_bg",
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .and(body_partial_json(
+            json!({"background": true, "store": true}),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(created, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let completed = load_sse_fixture_with_id_from_str(
+        r#"[{"type": "response.completed", "sequence_number": 1, "response": {"id": "__ID__"}}]"#,
+        "resp_bg",
+    );
+    Mock::given(method("GET"))
+        .and(path("/v1/responses/resp_bg"))
+        .and(query_param("stream", "true"))
+        .and(query_param("starting_after", "0"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(completed, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/responses/resp_bg"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"id": "resp_bg", "status": "in_progress"})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let home = TempDir::new().unwrap();
+    let codex = start_turn(&server, &home).await;
+
+    let progress = wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::BackgroundResponseProgress(_)),
+        Duration::from_secs(5),
+    )
+    .await;
+    let EventMsg::BackgroundResponseProgress(progress) = progress else {
+        unreachable!();
+    };
+    assert_eq!(progress.status, "in_progress");
+
+    wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::TaskComplete(_)),
+        Duration::from_secs(5),
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rejected_background_mode_falls_back_to_a_plain_request() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .and(body_partial_json(json!({"background": true})))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": {
+                "type": "invalid_request_error",
+                "message": "Unknown parameter: 'background'.",
+                "param": "background"
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let completed = load_sse_fixture_with_id("tests/fixtures/completed_template.json", "resp_1");
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(completed, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let home = TempDir::new().unwrap();
+    let codex = start_turn(&server, &home).await;
+
+    wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::TaskComplete(_)),
+        Duration::from_secs(5),
+    )
+    .await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let retried = requests[1].body_json::<serde_json::Value>().unwrap();
+    assert_eq!(retried.get("background"), None);
+}
@@ -0,0 +1,138 @@
+#![expect(clippy::unwrap_used)]
+
+use codex_core::CodexConversation;
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::cassette::Cassette;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::wait_for_event;
+use std::sync::Arc;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+const API_KEY: &str = "sk-cassette-test-secret";
+
+fn sse_reply(text: &str) -> String {
+    let message = serde_json::json!({
+        "type": "response.output_item.done",
+        "item": {
+            "type": "message",
+            "role": "assistant",
+            "id": "m1",
+            "content": [{"type": "output_text", "text": text}]
+        }
+    });
+    let completed = serde_json::json!({
+        "type": "response.completed",
+        "response": {
+            "id": "r1",
+            "usage": {"input_tokens":0,"input_tokens_details":null,"output_tokens":0,"output_tokens_details":null,"total_tokens":0}
+        }
+    });
+    format!(
+        "event: response.output_item.done\ndata: {message}\n\n\
+         event: response.completed\ndata: {completed}\n\n"
+    )
+}
+
+async fn start_conversation(
+    base_url: String,
+    home: &TempDir,
+    cassette: Cassette,
+) -> Arc<CodexConversation> {
+    let mut config = load_default_config_for_test(home);
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(base_url),
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    config.cassette = Some(cassette);
+    ConversationManager::with_auth(CodexAuth::from_api_key(API_KEY))
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation
+}
+
+async fn submit_text(codex: &CodexConversation, text: &str) {
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: text.to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+}
+
+/// A turn recorded against a server replays without one, and a turn that was
+/// not recorded fails with the field that differs.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn recorded_turn_replays_without_a_server() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let dir = TempDir::new().unwrap();
+    let cassette_path = dir.path().join("turn.json");
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse_reply("recorded reply"), "text/event-stream"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let base_url = format!("{}/v1", server.uri());
+
+    let home = TempDir::new().unwrap();
+    let codex = start_conversation(base_url.clone(), &home, Cassette::record(&cassette_path)).await;
+    submit_text(&codex, "hello").await;
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    drop(server);
+
+    let contents = std::fs::read_to_string(&cassette_path).unwrap();
+    assert!(contents.contains("recorded reply"), "{contents}");
+    assert!(!contents.contains(API_KEY), "{contents}");
+
+    let codex =
+        start_conversation(base_url, &home, Cassette::replay(&cassette_path).unwrap()).await;
+    submit_text(&codex, "hello").await;
+    let EventMsg::AgentMessage(message) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::AgentMessage(_))).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(message.message, "recorded reply");
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    submit_text(&codex, "goodbye").await;
+    let EventMsg::Error(error) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::Error(_))).await
+    else {
+        unreachable!()
+    };
+    assert!(
+        error.message.contains("no response recorded")
+            && error.message.contains("differs at `body.input["),
+        "{}",
+        error.message
+    );
+}
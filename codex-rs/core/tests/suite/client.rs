@@ -1,6 +1,8 @@
+use codex_core::BUILT_IN_GEMINI_MODEL_PROVIDER_ID;
 use codex_core::ConversationManager;
 use codex_core::ModelProviderInfo;
 use codex_core::NewConversation;
+use codex_core::ProviderKind;
 use codex_core::WireApi;
 use codex_core::built_in_model_providers;
 use codex_core::protocol::EventMsg;
@@ -17,6 +19,7 @@ use tempfile::TempDir;
 use wiremock::Mock;
 use wiremock::MockServer;
 use wiremock::ResponseTemplate;
+use wiremock::matchers::header;
 use wiremock::matchers::header_regex;
 use wiremock::matchers::method;
 use wiremock::matchers::path;
@@ -631,16 +634,28 @@ async fn azure_overrides_assign_properties_used_for_responses_url() {
             "2025-04-01-preview".to_string(),
         )])),
         env_key_instructions: None,
-        wire_api: WireApi::Responses,
+        wire_api: Some(WireApi::Responses),
         http_headers: Some(std::collections::HashMap::from([(
             "Custom-Header".to_string(),
             "Value".to_string(),
         )])),
         env_http_headers: None,
         request_max_retries: None,
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
         stream_max_retries: None,
         stream_idle_timeout_ms: None,
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
         requires_openai_auth: false,
+        stored_api_key: None,
     };
 
     // Init session
@@ -707,16 +722,28 @@ async fn env_var_overrides_loaded_auth() {
             "2025-04-01-preview".to_string(),
         )])),
         env_key_instructions: None,
-        wire_api: WireApi::Responses,
+        wire_api: Some(WireApi::Responses),
         http_headers: Some(std::collections::HashMap::from([(
             "Custom-Header".to_string(),
             "Value".to_string(),
         )])),
         env_http_headers: None,
         request_max_retries: None,
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
         stream_max_retries: None,
         stream_idle_timeout_ms: None,
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
         requires_openai_auth: false,
+        stored_api_key: None,
     };
 
     // Init session
@@ -894,3 +921,599 @@ async fn history_dedupes_streamed_and_final_messages_across_turns() {
         "request 3 tail mismatch",
     );
 }
+
+/// With response storage disabled the server keeps nothing, so follow-up
+/// requests must replay history without any ids that refer to server state.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn disabled_response_storage_sends_no_server_side_ids() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    let sse_raw = r##"[
+        {"type":"response.output_item.done", "item":{
+            "type":"reasoning", "id":"rs_replayable",
+            "summary":[{"type":"summary_text","text":"thinking"}],
+            "encrypted_content":"opaque"
+        }},
+        {"type":"response.output_item.done", "item":{
+            "type":"reasoning", "id":"rs_server_only", "summary":[]
+        }},
+        {"type":"response.output_item.done", "item":{
+            "type":"message", "id":"msg_1", "role":"assistant",
+            "content":[{"type":"output_text","text":"Done."}]
+        }},
+        {"type":"response.completed", "response": {"id": "__ID__"}}
+    ]"##;
+    let sse = core_test_support::load_sse_fixture_with_id_from_str(sse_raw, "resp1");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = model_provider;
+    config.disable_response_storage = true;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    for text in ["U1", "U2"] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected one request per turn");
+
+    for request in &requests {
+        let body = request.body_json::<serde_json::Value>().unwrap();
+        assert_eq!(body["store"], json!(false));
+        assert_eq!(body["include"], json!(["reasoning.encrypted_content"]));
+        assert!(body.get("previous_response_id").is_none());
+        for item in body["input"].as_array().unwrap() {
+            assert!(
+                item.get("id").is_none_or(serde_json::Value::is_null),
+                "input item carries a server-side id: {item}"
+            );
+        }
+    }
+
+    // The second turn replays the first in full: the reasoning that can be
+    // restored from its encrypted content, then the assistant reply.
+    let input = requests[1].body_json::<serde_json::Value>().unwrap()["input"]
+        .as_array()
+        .cloned()
+        .unwrap();
+    let tail = &input[input.len() - 4..];
+    assert_eq!(
+        serde_json::Value::Array(tail.to_vec()),
+        json!([
+            {
+                "type": "message",
+                "id": null,
+                "role": "user",
+                "content": [{"type":"input_text","text":"U1"}]
+            },
+            {
+                "type": "reasoning",
+                "summary": [{"type":"summary_text","text":"thinking"}],
+                "content": null,
+                "encrypted_content": "opaque"
+            },
+            {
+                "type": "message",
+                "id": null,
+                "role": "assistant",
+                "content": [{"type":"output_text","text":"Done."}]
+            },
+            {
+                "type": "message",
+                "id": null,
+                "role": "user",
+                "content": [{"type":"input_text","text":"U2"}]
+            }
+        ])
+    );
+}
+
+/// Sends one turn through an Azure-style provider routed to `deployment` and
+/// returns the request the mock server received at `endpoint`.
+async fn send_azure_deployment_request(
+    wire_api: WireApi,
+    endpoint: &str,
+    sse: String,
+) -> wiremock::Request {
+    let existing_env_var_with_random_value = if cfg!(windows) { "USERNAME" } else { "USER" };
+    let api_key = std::env::var(existing_env_var_with_random_value).unwrap();
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(endpoint))
+        .and(query_param("api-version", "2025-04-01-preview"))
+        .and(header("api-key", api_key.as_str()))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "azure".to_string(),
+        base_url: Some(format!("{}/openai", server.uri())),
+        // Reuse the existing environment variable to avoid using unsafe code
+        env_key: Some(existing_env_var_with_random_value.to_string()),
+        env_key_instructions: None,
+        wire_api: Some(wire_api),
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(0),
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
+        stream_max_retries: Some(0),
+        stream_idle_timeout_ms: None,
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: Some("2025-04-01-preview".to_string()),
+        deployment: Some("codex-prod".to_string()),
+        auth_header: Some("api-key".to_string()),
+        kind: ProviderKind::OpenAi,
+        requires_openai_auth: false,
+        stored_api_key: None,
+    };
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = provider;
+
+    let conversation_manager = ConversationManager::with_auth(create_dummy_codex_auth());
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let mut requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    requests.remove(0)
+}
+
+fn assert_azure_deployment_request_shape(request: &wiremock::Request) {
+    assert_eq!(request.url.query(), Some("api-version=2025-04-01-preview"));
+    assert!(
+        !request.headers.contains_key("authorization"),
+        "api-key auth must not also send a bearer token"
+    );
+    let body = request.body_json::<serde_json::Value>().unwrap();
+    assert!(
+        body.get("model").is_none(),
+        "deployment routing must not send `model`: {body}"
+    );
+    assert_eq!(body["stream"], json!(true));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn azure_deployment_routes_responses_requests() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let request = send_azure_deployment_request(
+        WireApi::Responses,
+        "/openai/deployments/codex-prod/responses",
+        sse_completed("resp1"),
+    )
+    .await;
+    assert_azure_deployment_request_shape(&request);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn azure_deployment_routes_chat_completions_requests() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let sse = concat!(
+        "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n"
+    );
+    let request = send_azure_deployment_request(
+        WireApi::Chat,
+        "/openai/deployments/codex-prod/chat/completions",
+        sse.to_string(),
+    )
+    .await;
+    assert_azure_deployment_request_shape(&request);
+    let body = request.body_json::<serde_json::Value>().unwrap();
+    assert!(body["messages"].is_array());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn gemini_requests_omit_unsupported_fields_and_schema_keywords() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let existing_env_var_with_random_value = if cfg!(windows) { "USERNAME" } else { "USER" };
+    let api_key = std::env::var(existing_env_var_with_random_value).unwrap();
+
+    let server = MockServer::start().await;
+    let sse = concat!(
+        "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n"
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1beta/openai/chat/completions"))
+        .and(header(
+            "authorization",
+            format!("Bearer {api_key}").as_str(),
+        ))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1beta/openai", server.uri())),
+        // Reuse the existing environment variable to avoid using unsafe code
+        env_key: Some(existing_env_var_with_random_value.to_string()),
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        ..built_in_model_providers()[BUILT_IN_GEMINI_MODEL_PROVIDER_ID].clone()
+    };
+    assert_eq!(provider.kind, ProviderKind::Gemini);
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model = "gemini-2.5-pro".to_string();
+    config.model_provider = provider;
+
+    let conversation_manager = ConversationManager::with_auth(create_dummy_codex_auth());
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_json::<serde_json::Value>().unwrap();
+    for field in ["store", "include", "reasoning"] {
+        assert!(body.get(field).is_none(), "`{field}` was sent: {body}");
+    }
+    let tools = body["tools"].as_array().unwrap();
+    assert!(
+        tools.iter().any(|tool| tool["function"]["name"] == "shell"),
+        "{tools:?}"
+    );
+    let tools = serde_json::to_string(tools).unwrap();
+    assert!(
+        !tools.contains("additionalProperties"),
+        "tool schemas kept `additionalProperties`: {tools}"
+    );
+}
+
+/// A server without a Responses endpoint is detected on the first turn,
+/// talked to over Chat Completions from then on, and remembered for the next
+/// session with the same provider.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unset_wire_api_falls_back_to_chat_completions() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(ResponseTemplate::new(404).set_body_string("404 page not found"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let sse = concat!(
+        "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n"
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let codex_home = TempDir::new().unwrap();
+    let new_conversation = || {
+        let mut config = load_default_config_for_test(&codex_home);
+        config.model_provider = ModelProviderInfo {
+            base_url: Some(format!("{}/v1", server.uri())),
+            wire_api: None,
+            request_max_retries: Some(0),
+            stream_max_retries: Some(0),
+            ..built_in_model_providers()["openai"].clone()
+        };
+        async move {
+            ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"))
+                .new_conversation(config)
+                .await
+                .unwrap()
+                .conversation
+        }
+    };
+    let hello = || Op::UserInput {
+        items: vec![InputItem::Text {
+            text: "hello".into(),
+        }],
+    };
+
+    let codex = new_conversation().await;
+    codex.submit(hello()).await.unwrap();
+    let EventMsg::BackgroundEvent(event) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::BackgroundEvent(_))).await
+    else {
+        unreachable!()
+    };
+    assert!(
+        event.message.contains("Chat Completions"),
+        "{}",
+        event.message
+    );
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    codex.submit(hello()).await.unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    // A new session reads the choice from the cache instead of probing.
+    let codex = new_conversation().await;
+    codex.submit(hello()).await.unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+}
+
+/// Raw chain-of-thought streamed as `reasoning_content` (DeepSeek style) is
+/// shown when `show_raw_agent_reasoning` is set, and is left out of the
+/// messages sent on the next turn.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chat_reasoning_content_is_shown_but_not_sent_back() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    let sse = concat!(
+        "data: {\"choices\":[{\"delta\":{\"reasoning_content\":\"The user greets; \"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"reasoning_content\":\"greet back.\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n"
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "deepseek".to_string(),
+        base_url: Some(format!("{}/v1", server.uri())),
+        wire_api: Some(WireApi::Chat),
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        requires_openai_auth: false,
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = provider;
+    config.show_raw_agent_reasoning = true;
+
+    let conversation_manager = ConversationManager::with_auth(create_dummy_codex_auth());
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    for text in ["hello", "again"] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+        if text == "hello" {
+            let raw = wait_for_event(&codex, |ev| {
+                matches!(ev, EventMsg::AgentReasoningRawContent(_))
+            })
+            .await;
+            let EventMsg::AgentReasoningRawContent(raw) = raw else {
+                unreachable!();
+            };
+            assert_eq!(raw.text, "The user greets; greet back.");
+        }
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let body = requests[1].body_json::<serde_json::Value>().unwrap();
+    let messages = serde_json::to_string(&body["messages"]).unwrap();
+    assert!(messages.contains("\"hi\""), "{messages}");
+    assert!(!messages.contains("greet back"), "{messages}");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chat_stop_sequences_and_assistant_prefix() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    // The server echoes the prefix before continuing the answer.
+    let sse = concat!(
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Answer:\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\" 42\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n"
+    );
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "llama.cpp".to_string(),
+        base_url: Some(format!("{}/v1", server.uri())),
+        wire_api: Some(WireApi::Chat),
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        requires_openai_auth: false,
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = provider;
+    config.show_raw_agent_reasoning = true;
+    config.model_family.stop = vec!["</answer>".to_string()];
+    config.model_family.assistant_prefix = Some("Answer:".to_string());
+
+    let conversation_manager = ConversationManager::with_auth(create_dummy_codex_auth());
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    for text in ["question", "another"] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+        let mut deltas = String::new();
+        let message = loop {
+            match wait_for_event(&codex, |ev| {
+                matches!(
+                    ev,
+                    EventMsg::AgentMessageDelta(_) | EventMsg::AgentMessage(_)
+                )
+            })
+            .await
+            {
+                EventMsg::AgentMessageDelta(delta) => deltas.push_str(&delta.delta),
+                EventMsg::AgentMessage(message) => break message.message,
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(deltas, " 42");
+        assert_eq!(message, " 42");
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let body = requests[1].body_json::<serde_json::Value>().unwrap();
+    assert_eq!(body["stop"], json!(["</answer>"]));
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(
+        messages.last(),
+        Some(&json!({"role": "assistant", "content": "Answer:"}))
+    );
+    // The history holds the answer without the prefix.
+    let history = serde_json::to_string(messages).unwrap();
+    assert!(history.contains("\" 42\""), "{history}");
+}
@@ -1,16 +1,22 @@
 #![expect(clippy::unwrap_used)]
 
+use codex_core::CodexConversation;
 use codex_core::ConversationManager;
 use codex_core::ModelProviderInfo;
 use codex_core::built_in_model_providers;
+use codex_core::protocol::ContextUsageEvent;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::InputItem;
 use codex_core::protocol::Op;
 use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
 use codex_login::CodexAuth;
+use core_test_support::ev_completed;
 use core_test_support::load_default_config_for_test;
+use core_test_support::mount_sse_once;
+use core_test_support::sse;
 use core_test_support::wait_for_event;
 use serde_json::Value;
+use std::time::Duration;
 use tempfile::TempDir;
 use wiremock::Mock;
 use wiremock::MockServer;
@@ -22,29 +28,13 @@ use pretty_assertions::assert_eq;
 
 // --- Test helpers -----------------------------------------------------------
 
-/// Build an SSE stream body from a list of JSON events.
-fn sse(events: Vec<Value>) -> String {
-    use std::fmt::Write as _;
-    let mut out = String::new();
-    for ev in events {
-        let kind = ev.get("type").and_then(|v| v.as_str()).unwrap();
-        writeln!(&mut out, "event: {kind}").unwrap();
-        if !ev.as_object().map(|o| o.len() == 1).unwrap_or(false) {
-            write!(&mut out, "data: {ev}\n\n").unwrap();
-        } else {
-            out.push('\n');
-        }
-    }
-    out
-}
-
-/// Convenience: SSE event for a completed response with a specific id.
-fn ev_completed(id: &str) -> Value {
+/// Like `ev_completed`, but reporting `input_tokens` of prompt usage.
+fn ev_completed_with_input_tokens(id: &str, input_tokens: u64) -> Value {
     serde_json::json!({
         "type": "response.completed",
         "response": {
             "id": id,
-            "usage": {"input_tokens":0,"input_tokens_details":null,"output_tokens":0,"output_tokens_details":null,"total_tokens":0}
+            "usage": {"input_tokens":input_tokens,"input_tokens_details":null,"output_tokens":0,"output_tokens_details":null,"total_tokens":input_tokens}
         }
     })
 }
@@ -62,29 +52,30 @@ fn ev_assistant_message(id: &str, text: &str) -> Value {
     })
 }
 
-fn sse_response(body: String) -> ResponseTemplate {
-    ResponseTemplate::new(200)
-        .insert_header("content-type", "text/event-stream")
-        .set_body_raw(body, "text/event-stream")
-}
-
-async fn mount_sse_once<M>(server: &MockServer, matcher: M, body: String)
-where
-    M: wiremock::Match + Send + Sync + 'static,
-{
-    Mock::given(method("POST"))
-        .and(path("/v1/responses"))
-        .and(matcher)
-        .respond_with(sse_response(body))
-        .expect(1)
-        .mount(server)
-        .await;
-}
-
 const FIRST_REPLY: &str = "FIRST_REPLY";
 const SUMMARY_TEXT: &str = "SUMMARY_ONLY_CONTEXT";
 const SUMMARIZE_TRIGGER: &str = "Start Summarization";
 const THIRD_USER_MSG: &str = "next turn";
+const SUMMARY_START: &str = "<conversation_summary>";
+
+/// (role, text) of every message in a request's input.
+fn input_messages(body: &Value) -> Vec<(String, String)> {
+    body["input"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|item| item["type"].as_str() == Some("message"))
+        .map(|item| {
+            (
+                item["role"].as_str().unwrap_or_default().to_string(),
+                item["content"][0]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        })
+        .collect()
+}
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn summarize_context_three_requests_and_instructions() {
@@ -161,6 +152,15 @@ async fn summarize_context_three_requests_and_instructions() {
 
     // 2) Summarize – second hit with summarization instructions.
     codex.submit(Op::Compact).await.unwrap();
+    let compacted = wait_for_event(&codex, |ev| {
+        matches!(ev, EventMsg::ConversationCompacted(_))
+    })
+    .await;
+    let EventMsg::ConversationCompacted(compacted) = compacted else {
+        unreachable!()
+    };
+    assert!(!compacted.automatic);
+    assert_eq!(compacted.summarized_items, 2);
     wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
 
     // 3) Next user input – third hit; history should include only the summary.
@@ -215,24 +215,18 @@ async fn summarize_context_three_requests_and_instructions() {
         "expected summary + new user message in third request"
     );
 
-    // Collect all (role, text) message tuples.
-    let mut messages: Vec<(String, String)> = Vec::new();
-    for item in input3 {
-        if item["type"].as_str() == Some("message") {
-            let role = item["role"].as_str().unwrap_or_default().to_string();
-            let text = item["content"][0]["text"]
-                .as_str()
-                .unwrap_or_default()
-                .to_string();
-            messages.push((role, text));
-        }
-    }
+    let messages = input_messages(&body3);
 
-    // Exactly one assistant message should remain after compaction and the new user message is present.
-    let assistant_count = messages.iter().filter(|(r, _)| r == "assistant").count();
-    assert_eq!(
-        assistant_count, 1,
-        "exactly one assistant message should remain after compaction"
+    // The conversation was replaced by the summary, sent as a user message.
+    assert!(
+        !messages.iter().any(|(r, _)| r == "assistant"),
+        "no assistant message should remain after compaction"
+    );
+    assert!(
+        messages
+            .iter()
+            .any(|(r, t)| r == "user" && t.starts_with(SUMMARY_START) && t.contains(SUMMARY_TEXT)),
+        "third request should include the conversation summary"
     );
     assert!(
         messages
@@ -249,3 +243,533 @@ async fn summarize_context_three_requests_and_instructions() {
         "third request should not include the summarize trigger"
     );
 }
+
+/// A summarization request answered without a summary leaves the history
+/// as it was and reports the failure.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn empty_summary_keeps_history() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    let first_matcher = |req: &wiremock::Request| {
+        let body = std::str::from_utf8(&req.body).unwrap_or("");
+        body.contains("\"text\":\"hello world\"")
+            && !body.contains(&format!("\"text\":\"{SUMMARIZE_TRIGGER}\""))
+            && !body.contains(&format!("\"text\":\"{THIRD_USER_MSG}\""))
+    };
+    mount_sse_once(
+        &server,
+        first_matcher,
+        sse(vec![
+            ev_assistant_message("m1", FIRST_REPLY),
+            ev_completed("r1"),
+        ]),
+    )
+    .await;
+    let summarize_matcher = |req: &wiremock::Request| {
+        let body = std::str::from_utf8(&req.body).unwrap_or("");
+        body.contains(&format!("\"text\":\"{SUMMARIZE_TRIGGER}\""))
+    };
+    mount_sse_once(&server, summarize_matcher, sse(vec![ev_completed("r2")])).await;
+    let third_matcher = |req: &wiremock::Request| {
+        let body = std::str::from_utf8(&req.body).unwrap_or("");
+        body.contains(&format!("\"text\":\"{THIRD_USER_MSG}\""))
+    };
+    mount_sse_once(&server, third_matcher, sse(vec![ev_completed("r3")])).await;
+
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello world".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    codex.submit(Op::Compact).await.unwrap();
+    let EventMsg::Error(error) = wait_for_event(&codex, |ev| {
+        matches!(ev, EventMsg::Error(_) | EventMsg::ConversationCompacted(_))
+    })
+    .await
+    else {
+        panic!("compaction without a summary should fail");
+    };
+    assert_eq!(
+        error.message,
+        "failed to compact the conversation: the model returned no summary, so the conversation was not compacted"
+    );
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: THIRD_USER_MSG.into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+    let messages = input_messages(&requests[2].body_json::<Value>().unwrap());
+    assert!(
+        messages
+            .iter()
+            .any(|(r, t)| r == "user" && t == "hello world"),
+        "the original history should be kept: {messages:?}"
+    );
+    assert!(
+        messages
+            .iter()
+            .any(|(r, t)| r == "assistant" && t == FIRST_REPLY),
+        "the original history should be kept: {messages:?}"
+    );
+}
+
+const SECOND_USER_MSG: &str = "second question";
+
+/// Once the provider reports a prompt close to the context window, the next
+/// request is preceded by a summarization of the older turns.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn compacts_automatically_near_context_window() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    // The first reply reports a prompt of 950 tokens out of 1000.
+    let sse1 = sse(vec![
+        ev_assistant_message("m1", FIRST_REPLY),
+        ev_completed_with_input_tokens("r1", 950),
+    ]);
+    let sse2 = sse(vec![
+        ev_assistant_message("m2", SUMMARY_TEXT),
+        ev_completed("r2"),
+    ]);
+    let sse3 = sse(vec![ev_completed("r3")]);
+
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains("\"text\":\"hello world\"") && !body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse1,
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse2,
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SECOND_USER_MSG) && !body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse3,
+    )
+    .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.model_context_window = Some(1_000);
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    for text in ["hello world", SECOND_USER_MSG] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3, "expected exactly three requests");
+
+    // Only the first turn is summarized; the new user message is kept.
+    let summarized = input_messages(&requests[1].body_json::<Value>().unwrap());
+    assert!(summarized.iter().any(|(_, t)| t == FIRST_REPLY));
+    assert!(!summarized.iter().any(|(_, t)| t == SECOND_USER_MSG));
+
+    let messages = input_messages(&requests[2].body_json::<Value>().unwrap());
+    assert!(
+        !messages
+            .iter()
+            .any(|(_, t)| t == "hello world" || t == FIRST_REPLY),
+        "summarized turn should be gone from {messages:#?}"
+    );
+    assert!(
+        messages
+            .iter()
+            .any(|(r, t)| r == "user" && t.starts_with(SUMMARY_START) && t.contains(SUMMARY_TEXT))
+    );
+    assert_eq!(
+        messages.last(),
+        Some(&("user".to_string(), SECOND_USER_MSG.to_string()))
+    );
+}
+
+/// When automatic compaction fails, the error is reported and the user's
+/// message is still sent, with the history left as it was.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn failed_automatic_compaction_still_sends_the_turn() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    // The first reply reports a prompt of 950 tokens out of 1000, and the
+    // summarization request is answered without a summary.
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains("\"text\":\"hello world\"")
+                && !body.contains(SECOND_USER_MSG)
+                && !body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse(vec![
+            ev_assistant_message("m1", FIRST_REPLY),
+            ev_completed_with_input_tokens("r1", 950),
+        ]),
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse(vec![ev_completed("r2")]),
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SECOND_USER_MSG) && !body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse(vec![ev_completed("r3")]),
+    )
+    .await;
+
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    config.model_context_window = Some(1_000);
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello world".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: SECOND_USER_MSG.into(),
+            }],
+        })
+        .await
+        .unwrap();
+    let EventMsg::Error(error) = wait_for_event(&codex, |ev| {
+        matches!(ev, EventMsg::Error(_) | EventMsg::TaskComplete(_))
+    })
+    .await
+    else {
+        panic!("the failed compaction should be reported");
+    };
+    assert_eq!(
+        error.message,
+        "failed to compact the conversation: the model returned no summary, so the conversation was not compacted"
+    );
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+    let messages = input_messages(&requests[2].body_json::<Value>().unwrap());
+    assert!(
+        messages
+            .iter()
+            .any(|(r, t)| r == "user" && t == "hello world"),
+        "the original history should be kept: {messages:?}"
+    );
+    assert_eq!(
+        messages.last(),
+        Some(&("user".to_string(), SECOND_USER_MSG.to_string()))
+    );
+}
+
+/// A request the provider rejects for overflowing the context window is sent
+/// again after the older turns are summarized.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn compacts_and_retries_when_context_window_is_exceeded() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    let sse1 = sse(vec![
+        ev_assistant_message("m1", FIRST_REPLY),
+        ev_completed("r1"),
+    ]);
+    let sse2 = sse(vec![
+        ev_assistant_message("m2", SUMMARY_TEXT),
+        ev_completed("r2"),
+    ]);
+    let sse3 = sse(vec![ev_completed("r3")]);
+
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains("\"text\":\"hello world\"") && !body.contains(SECOND_USER_MSG)
+        },
+        sse1,
+    )
+    .await;
+    let second_turn = |req: &wiremock::Request| {
+        let body = std::str::from_utf8(&req.body).unwrap_or("");
+        body.contains(SECOND_USER_MSG) && !body.contains(SUMMARIZE_TRIGGER)
+    };
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .and(second_turn)
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": {
+                "message": "Your input exceeds the context window of this model.",
+                "type": "invalid_request_error",
+                "code": "context_length_exceeded"
+            }
+        })))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SUMMARIZE_TRIGGER)
+        },
+        sse2,
+    )
+    .await;
+    mount_sse_once(&server, second_turn, sse3).await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.model_context_window = Some(1_000);
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    for text in ["hello world", SECOND_USER_MSG] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+        let event = wait_for_event(&codex, |ev| {
+            matches!(ev, EventMsg::TaskComplete(_) | EventMsg::Error(_))
+        })
+        .await;
+        assert!(
+            matches!(event, EventMsg::TaskComplete(_)),
+            "unexpected event: {event:?}"
+        );
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 4, "expected exactly four requests");
+
+    let messages = input_messages(&requests[3].body_json::<Value>().unwrap());
+    assert!(
+        !messages
+            .iter()
+            .any(|(_, t)| t == "hello world" || t == FIRST_REPLY),
+        "summarized turn should be gone from {messages:#?}"
+    );
+    assert_eq!(
+        messages.last(),
+        Some(&("user".to_string(), SECOND_USER_MSG.to_string()))
+    );
+}
+
+/// Context usage, overhead included, until the task completes, along with the
+/// thresholds that were warned about.
+async fn context_events_until_complete(
+    codex: &CodexConversation,
+) -> (Option<ContextUsageEvent>, Vec<u8>) {
+    let mut usage = None;
+    let mut warnings = Vec::new();
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), codex.next_event())
+            .await
+            .expect("timeout waiting for TaskComplete")
+            .unwrap();
+        match event.msg {
+            EventMsg::ContextUsage(ev) => usage = Some(ev),
+            EventMsg::ContextWindowWarning(ev) => warnings.push(ev.threshold_percent),
+            EventMsg::TaskComplete(_) => return (usage, warnings),
+            _ => {}
+        }
+    }
+}
+
+/// The provider's reported prompt size drives the context-left figure after
+/// each turn, and crossing a threshold warns once.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn warns_when_context_window_fills_past_a_threshold() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            !body.contains(SECOND_USER_MSG)
+        },
+        sse(vec![
+            ev_assistant_message("m1", FIRST_REPLY),
+            ev_completed_with_input_tokens("r1", 800),
+        ]),
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(SECOND_USER_MSG)
+        },
+        sse(vec![
+            ev_assistant_message("m2", "SECOND_REPLY"),
+            ev_completed_with_input_tokens("r2", 8_000),
+        ]),
+    )
+    .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.model_context_window = Some(10_000);
+    config.context_warning_thresholds = vec![0.75, 0.9];
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello world".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    let (usage, warnings) = context_events_until_complete(&codex).await;
+    let usage = usage.expect("context usage after the first turn");
+    // 800 reported tokens plus the reply recorded since.
+    assert!(usage.tokens_in_context > 800 && usage.tokens_in_context < 850);
+    assert_eq!(usage.context_window, 10_000);
+    assert_eq!(usage.percent_left, 91);
+    assert!(usage.breakdown.instructions_tokens > 0);
+    assert!(usage.breakdown.tool_schema_tokens > 0);
+    assert!(usage.breakdown.environment_tokens > 0);
+    assert_eq!(warnings, Vec::<u8>::new());
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: SECOND_USER_MSG.into(),
+            }],
+        })
+        .await
+        .unwrap();
+    let (usage, warnings) = context_events_until_complete(&codex).await;
+    assert_eq!(usage.map(|usage| usage.percent_left), Some(19));
+    assert_eq!(warnings, vec![75]);
+}
@@ -0,0 +1,81 @@
+#![expect(clippy::unwrap_used)]
+
+use codex_core::AskForApproval;
+use codex_core::CodexBuilder;
+use codex_core::ModelProviderInfo;
+use codex_core::SandboxPolicy;
+use codex_core::built_in_model_providers;
+use codex_core::protocol::EventMsg;
+use core_test_support::load_sse_fixture_with_id;
+use futures::StreamExt;
+use serde_json::Value;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn builder_runs_a_turn_with_the_given_settings() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    load_sse_fixture_with_id("tests/fixtures/completed_template.json", "resp"),
+                    "text/event-stream",
+                ),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let codex_home = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    let codex = CodexBuilder::new()
+        .codex_home(codex_home.path())
+        .cwd(cwd.path())
+        .model("gpt-5")
+        .model_provider(ModelProviderInfo {
+            base_url: Some(format!("{}/v1", server.uri())),
+            // Any variable that is set satisfies the API key lookup.
+            env_key: Some("PATH".into()),
+            requires_openai_auth: false,
+            ..built_in_model_providers()["openai"].clone()
+        })
+        .sandbox_policy(SandboxPolicy::new_read_only_policy())
+        .approval_policy(AskForApproval::Never)
+        .user_instructions("Be brief.")
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(codex.session_configured().model, "gpt-5");
+
+    let events: Vec<_> = codex.submit("hello").await.unwrap().collect().await;
+    assert!(matches!(
+        events.last().map(|event| &event.msg),
+        Some(EventMsg::TaskComplete(_))
+    ));
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests[0].body_json::<Value>().unwrap();
+    assert_eq!(body["model"], "gpt-5");
+    let texts: Vec<&str> = body["input"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|item| item["content"][0]["text"].as_str())
+        .collect();
+    assert!(texts.iter().any(|text| text.contains("Be brief.")));
+    assert!(
+        texts
+            .iter()
+            .any(|text| text.contains("<approval_policy>never</approval_policy>"))
+    );
+    assert_eq!(texts.last(), Some(&"hello"));
+
+    codex.shutdown().await.unwrap();
+}
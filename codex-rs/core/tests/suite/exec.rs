@@ -2,7 +2,9 @@
 
 use std::collections::HashMap;
 
+use codex_core::exec::ExecLimits;
 use codex_core::exec::ExecParams;
+use codex_core::exec::ExecPty;
 use codex_core::exec::ExecToolCallOutput;
 use codex_core::exec::SandboxType;
 use codex_core::exec::process_exec_tool_call;
@@ -35,6 +37,10 @@ async fn run_test_cmd(tmp: TempDir, cmd: Vec<&str>) -> Result<ExecToolCallOutput
         env: HashMap::new(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let policy = SandboxPolicy::new_read_only_policy();
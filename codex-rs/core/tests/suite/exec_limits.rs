@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use codex_core::exec::ExecLimits;
+use codex_core::exec::ExecParams;
+use codex_core::exec::ExecPty;
+use codex_core::exec::SandboxType;
+use codex_core::exec::process_exec_tool_call;
+use codex_core::protocol::ExecTermination;
+use codex_core::protocol::SandboxPolicy;
+
+fn params(script: &str, timeout_ms: u64, limits: ExecLimits) -> ExecParams {
+    ExecParams {
+        command: vec!["/bin/sh".to_string(), "-c".to_string(), script.to_string()],
+        cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        timeout_ms: Some(timeout_ms),
+        env: HashMap::new(),
+        with_escalated_permissions: None,
+        justification: None,
+        limits,
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
+    }
+}
+
+/// A command that outlives its timeout is reported with the output it
+/// produced, and the processes it started in the background are killed too:
+/// otherwise they would keep its stdout open and the call would not return.
+#[tokio::test]
+async fn timeout_kills_process_group_and_keeps_partial_output() {
+    let policy = SandboxPolicy::DangerFullAccess;
+    let start = Instant::now();
+
+    let output = process_exec_tool_call(
+        params(
+            "echo started; (sleep 30; echo never) & sleep 30",
+            500,
+            ExecLimits::default(),
+        ),
+        SandboxType::None,
+        &policy,
+        &None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        output.terminated,
+        Some(ExecTermination::TimedOut { timeout_ms: 500 })
+    );
+    assert_ne!(output.exit_code, 0);
+    assert_eq!(output.aggregated_output.text, "started\n");
+}
+
+#[tokio::test]
+async fn command_is_killed_once_it_exceeds_the_output_limit() {
+    let policy = SandboxPolicy::DangerFullAccess;
+    let limits = ExecLimits {
+        max_captured_output_bytes: Some(64 * 1024),
+        ..ExecLimits::default()
+    };
+
+    let output = process_exec_tool_call(
+        params("yes", 10_000, limits),
+        SandboxType::None,
+        &policy,
+        &None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        output.terminated,
+        Some(ExecTermination::OutputLimitExceeded {
+            max_bytes: 64 * 1024
+        })
+    );
+    assert!(output.aggregated_output.text.starts_with("y\ny\n"));
+}
+
+#[tokio::test]
+async fn commands_within_limits_are_not_terminated() {
+    let policy = SandboxPolicy::DangerFullAccess;
+    let limits = ExecLimits {
+        max_memory_bytes: Some(1 << 30),
+        max_captured_output_bytes: Some(1024),
+    };
+
+    let output = process_exec_tool_call(
+        params("echo ok", 5_000, limits),
+        SandboxType::None,
+        &policy,
+        &None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(output.terminated, None);
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(output.aggregated_output.text, "ok\n");
+}
@@ -0,0 +1,103 @@
+#![cfg(unix)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use codex_core::exec::ExecLimits;
+use codex_core::exec::ExecParams;
+use codex_core::exec::ExecPty;
+use codex_core::exec::SandboxType;
+use codex_core::exec::process_exec_tool_call;
+use codex_core::protocol::SandboxPolicy;
+
+fn pty_params(script: &str, pty: ExecPty) -> ExecParams {
+    ExecParams {
+        command: vec!["/bin/sh".to_string(), "-c".to_string(), script.to_string()],
+        cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        timeout_ms: Some(5_000),
+        env: HashMap::new(),
+        with_escalated_permissions: None,
+        justification: None,
+        limits: ExecLimits::default(),
+        use_pty: Some(true),
+        pty,
+        stdin: None,
+    }
+}
+
+#[tokio::test]
+async fn command_sees_a_terminal_and_stdin_at_eof() {
+    let policy = SandboxPolicy::DangerFullAccess;
+    let script = "[ -t 1 ] && echo tty; [ -t 0 ] || echo stdin-not-tty; \
+                  read line; echo \"read=$?\"; echo err 1>&2; stty size < /dev/tty";
+
+    let output = process_exec_tool_call(
+        pty_params(
+            script,
+            ExecPty {
+                rows: 40,
+                cols: 120,
+                stdin_response: None,
+            },
+        ),
+        SandboxType::None,
+        &policy,
+        &None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(output.exit_code, 0);
+    // The terminal turns newlines into CRLF and merges stderr into stdout.
+    assert_eq!(
+        output.aggregated_output.text,
+        "tty\r\nstdin-not-tty\r\nread=1\r\nerr\r\n40 120\r\n"
+    );
+    assert_eq!(output.stderr.text, "");
+}
+
+#[tokio::test]
+async fn stdin_response_is_fed_to_the_command() {
+    let policy = SandboxPolicy::DangerFullAccess;
+
+    let output = process_exec_tool_call(
+        pty_params(
+            "read answer; echo \"answer=$answer\"; read more; echo \"read=$?\"",
+            ExecPty {
+                stdin_response: Some("y\n".to_string()),
+                ..ExecPty::default()
+            },
+        ),
+        SandboxType::None,
+        &policy,
+        &None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(output.aggregated_output.text, "answer=y\r\nread=1\r\n");
+}
+
+#[tokio::test]
+async fn stdin_is_written_to_a_command_without_a_terminal() {
+    let policy = SandboxPolicy::DangerFullAccess;
+    let params = ExecParams {
+        use_pty: Some(false),
+        stdin: Some("{\"filter\":\"unit\"}".to_string()),
+        ..pty_params(
+            "[ -t 1 ] || echo no-tty; cat; echo; read more; echo \"read=$?\"",
+            ExecPty::default(),
+        )
+    };
+
+    let output = process_exec_tool_call(params, SandboxType::None, &policy, &None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        output.aggregated_output.text,
+        "no-tty\n{\"filter\":\"unit\"}\nread=1\n"
+    );
+}
@@ -4,7 +4,9 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 use async_channel::Receiver;
+use codex_core::exec::ExecLimits;
 use codex_core::exec::ExecParams;
+use codex_core::exec::ExecPty;
 use codex_core::exec::SandboxType;
 use codex_core::exec::StdoutStream;
 use codex_core::exec::process_exec_tool_call;
@@ -53,6 +55,10 @@ async fn test_exec_stdout_stream_events_echo() {
         env: HashMap::new(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let policy = SandboxPolicy::new_read_only_policy();
@@ -103,6 +109,10 @@ async fn test_exec_stderr_stream_events_echo() {
         env: HashMap::new(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let policy = SandboxPolicy::new_read_only_policy();
@@ -156,6 +166,10 @@ async fn test_aggregated_output_interleaves_in_order() {
         env: HashMap::new(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let policy = SandboxPolicy::new_read_only_policy();
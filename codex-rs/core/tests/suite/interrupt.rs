@@ -0,0 +1,162 @@
+#![expect(clippy::unwrap_used)]
+
+use std::time::Duration;
+use std::time::Instant;
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::protocol::AskForApproval;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::SandboxPolicy;
+use codex_core::protocol::TurnAbortReason;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::ev_completed;
+use core_test_support::load_default_config_for_test;
+use core_test_support::mount_sse_once;
+use core_test_support::sse;
+use core_test_support::wait_for_event;
+use core_test_support::wait_for_event_with_timeout;
+use serde_json::Value;
+use tempfile::TempDir;
+use wiremock::MockServer;
+
+const PARTIAL_REPLY: &str = "Checking the tests first.";
+const FOLLOW_UP: &str = "never mind, just summarize";
+
+/// Interrupting a turn while a command runs kills the command and keeps what
+/// the model already said in the history sent with the next turn.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn interrupted_turn_keeps_partial_output_in_history() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    // The model answers part of the way, then runs a command that would take
+    // far longer than the test is willing to wait.
+    let first = sse(vec![
+        serde_json::json!({
+            "type": "response.output_item.done",
+            "item": {
+                "type": "message",
+                "role": "assistant",
+                "id": "m1",
+                "content": [{"type": "output_text", "text": PARTIAL_REPLY}]
+            }
+        }),
+        serde_json::json!({
+            "type": "response.output_item.done",
+            "item": {
+                "type": "function_call",
+                "id": "fc1",
+                "call_id": "call-sleep",
+                "name": "shell",
+                "arguments": "{\"command\":[\"sleep\",\"60\"]}"
+            }
+        }),
+        ev_completed("r1"),
+    ]);
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains("\"text\":\"run the tests\"") && !body.contains(FOLLOW_UP)
+        },
+        first,
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            let body = std::str::from_utf8(&req.body).unwrap_or("");
+            body.contains(FOLLOW_UP)
+        },
+        sse(vec![ev_completed("r2")]),
+    )
+    .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.approval_policy = AskForApproval::Never;
+    config.sandbox_policy = SandboxPolicy::DangerFullAccess;
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    let started = Instant::now();
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "run the tests".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::ExecCommandBegin(_))).await;
+
+    codex.submit(Op::Interrupt).await.unwrap();
+    let aborted = wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::TurnAborted(_)),
+        Duration::from_secs(10),
+    )
+    .await;
+    let EventMsg::TurnAborted(aborted) = aborted else {
+        unreachable!()
+    };
+    assert_eq!(aborted.reason, TurnAbortReason::Interrupted);
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: FOLLOW_UP.into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    // The `sleep 60` child must have been killed rather than awaited.
+    assert!(started.elapsed() < Duration::from_secs(30));
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected exactly two requests");
+    let body = requests[1].body_json::<Value>().unwrap();
+    let input = body["input"].as_array().unwrap();
+
+    let has_partial_reply = input.iter().any(|item| {
+        item["type"] == "message"
+            && item["role"] == "assistant"
+            && item["content"][0]["text"] == PARTIAL_REPLY
+    });
+    assert!(has_partial_reply, "partial reply missing from {input:#?}");
+
+    let has_call = input
+        .iter()
+        .any(|item| item["type"] == "function_call" && item["call_id"] == "call-sleep");
+    assert!(has_call, "interrupted call missing from {input:#?}");
+
+    // The call never produced output, so the model is told it was aborted.
+    let has_aborted_output = input.iter().any(|item| {
+        item["type"] == "custom_tool_call_output"
+            && item["call_id"] == "call-sleep"
+            && item["output"] == "aborted"
+    });
+    assert!(has_aborted_output, "aborted output missing from {input:#?}");
+}
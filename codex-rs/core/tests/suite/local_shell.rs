@@ -0,0 +1,124 @@
+#![expect(clippy::unwrap_used)]
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::protocol::AskForApproval;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::SandboxPolicy;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::ev_completed;
+use core_test_support::load_default_config_for_test;
+use core_test_support::mount_sse_once;
+use core_test_support::sse;
+use core_test_support::wait_for_event;
+use serde_json::Value;
+use serde_json::json;
+use tempfile::TempDir;
+use wiremock::MockServer;
+
+/// A model family that uses the `local_shell` tool is offered only that
+/// shell tool, and its `local_shell_call` items run with the variables they
+/// set, with the output sent back for the same call id.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn local_shell_call_runs_and_reports_output() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    let call = json!({
+        "type": "response.output_item.done",
+        "item": {
+            "type": "local_shell_call",
+            "call_id": "call-local",
+            "status": "completed",
+            "action": {
+                "type": "exec",
+                "command": ["sh", "-c", "echo \"greeting: $GREETING\""],
+                "timeout_ms": 10_000,
+                "working_directory": null,
+                "env": {"GREETING": "hello"},
+                "user": null
+            }
+        }
+    });
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            !std::str::from_utf8(&req.body)
+                .unwrap_or("")
+                .contains("function_call_output")
+        },
+        sse(vec![call, ev_completed("r1")]),
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            std::str::from_utf8(&req.body)
+                .unwrap_or("")
+                .contains("function_call_output")
+        },
+        sse(vec![ev_completed("r2")]),
+    )
+    .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.model_family.uses_local_shell_tool = true;
+    config.approval_policy = AskForApproval::Never;
+    config.sandbox_policy = SandboxPolicy::DangerFullAccess;
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "say hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected exactly two requests");
+
+    let first = requests[0].body_json::<Value>().unwrap();
+    let tools = first["tools"].as_array().unwrap();
+    assert!(tools.iter().any(|tool| tool["type"] == "local_shell"));
+    assert!(
+        !tools.iter().any(|tool| tool["name"] == "shell"),
+        "both shell tools were offered: {tools:?}"
+    );
+
+    let second = requests[1].body_json::<Value>().unwrap();
+    let input = second["input"].as_array().unwrap();
+    assert!(
+        input
+            .iter()
+            .any(|item| item["type"] == "local_shell_call" && item["call_id"] == "call-local")
+    );
+    let output = input
+        .iter()
+        .find(|item| item["type"] == "function_call_output" && item["call_id"] == "call-local")
+        .map(|item| item["output"].as_str().unwrap().to_string())
+        .unwrap();
+    assert!(output.contains("greeting: hello"), "{output}");
+}
@@ -1,12 +1,28 @@
 // Aggregates all former standalone integration tests as modules.
 
+mod background_mode;
+mod cassette;
 mod cli_stream;
 mod client;
 mod compact;
+mod embed;
 mod exec;
+mod exec_limits;
+mod exec_pty;
 mod exec_stream_events;
+mod interrupt;
 mod live_cli;
+mod local_shell;
+mod parallel_tool_calls;
+mod project_docs;
 mod prompt_caching;
+mod pull_request;
+mod queued_input;
+mod request_retry;
+mod resume;
+mod scratch_dir;
 mod seatbelt;
+mod status;
 mod stream_error_allows_next_turn;
 mod stream_no_completed;
+mod usage;
@@ -0,0 +1,165 @@
+#![expect(clippy::unwrap_used)]
+
+use std::time::Duration;
+use std::time::Instant;
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::protocol::AskForApproval;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::SandboxPolicy;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::ev_completed;
+use core_test_support::load_default_config_for_test;
+use core_test_support::mount_sse_once;
+use core_test_support::sse;
+use core_test_support::wait_for_event_with_timeout;
+use serde_json::Value;
+use serde_json::json;
+use tempfile::TempDir;
+use wiremock::MockServer;
+
+/// How long each slow call blocks before its timeout ends it.
+const SLOW_CALL_MS: u64 = 2_000;
+
+fn ev_shell_call(call_id: &str, command: &[&str], timeout_ms: Option<u64>) -> Value {
+    let mut arguments = json!({ "command": command });
+    if let Some(timeout_ms) = timeout_ms {
+        arguments["timeout_ms"] = json!(timeout_ms);
+    }
+    json!({
+        "type": "response.output_item.done",
+        "item": {
+            "type": "function_call",
+            "call_id": call_id,
+            "name": "shell",
+            "arguments": arguments.to_string(),
+        }
+    })
+}
+
+/// Read-only calls from one response run concurrently, a call that may write
+/// waits for the ones before it, and every output is sent back in the order
+/// the calls were emitted.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn read_only_calls_run_concurrently_and_keep_emission_order() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    // Two slow reads that only end when their timeout fires, a fast read, a
+    // command that is not known to be read-only, and another fast read.
+    let slow = ["tail", "-f", "/dev/null"];
+    let first = sse(vec![
+        ev_shell_call("call-slow-1", &slow, Some(SLOW_CALL_MS)),
+        ev_shell_call("call-slow-2", &slow, Some(SLOW_CALL_MS)),
+        ev_shell_call("call-fast-1", &["echo", "fast one"], None),
+        ev_shell_call("call-serial", &["sh", "-c", "echo serial"], None),
+        ev_shell_call("call-fast-2", &["echo", "fast two"], None),
+        ev_completed("r1"),
+    ]);
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            !std::str::from_utf8(&req.body)
+                .unwrap_or("")
+                .contains("function_call_output")
+        },
+        first,
+    )
+    .await;
+    mount_sse_once(
+        &server,
+        |req: &wiremock::Request| {
+            std::str::from_utf8(&req.body)
+                .unwrap_or("")
+                .contains("function_call_output")
+        },
+        sse(vec![ev_completed("r2")]),
+    )
+    .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = model_provider;
+    config.approval_policy = AskForApproval::Never;
+    config.sandbox_policy = SandboxPolicy::DangerFullAccess;
+    assert_eq!(config.max_parallel_tool_calls, 4);
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    let started = Instant::now();
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "look around".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::TaskComplete(_)),
+        Duration::from_secs(30),
+    )
+    .await;
+
+    // Run one after another, the slow calls alone would take twice as long.
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed < Duration::from_millis(SLOW_CALL_MS * 2),
+        "slow calls did not overlap: {elapsed:?}"
+    );
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected exactly two requests");
+    let body = requests[1].body_json::<Value>().unwrap();
+    let output_order: Vec<&str> = body["input"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|item| item["type"] == "function_call_output")
+        .map(|item| item["call_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        output_order,
+        vec![
+            "call-slow-1",
+            "call-slow-2",
+            "call-fast-1",
+            "call-serial",
+            "call-fast-2"
+        ]
+    );
+
+    // Each output stays with its own call.
+    let output_for = |call_id: &str| {
+        body["input"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["type"] == "function_call_output" && item["call_id"] == call_id)
+            .map(|item| item["output"].as_str().unwrap().to_string())
+            .unwrap()
+    };
+    assert!(output_for("call-fast-1").contains("fast one"));
+    assert!(output_for("call-serial").contains("serial"));
+    assert!(output_for("call-fast-2").contains("fast two"));
+}
@@ -0,0 +1,216 @@
+#![allow(clippy::unwrap_used)]
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::error::CodexErr;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::TruncatedProjectDoc;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::load_sse_fixture_with_id;
+use core_test_support::wait_for_event;
+use serde_json::Value;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn sse_completed(id: &str) -> String {
+    load_sse_fixture_with_id("tests/fixtures/completed_template.json", id)
+}
+
+fn user_instructions(input: &[Value]) -> Vec<String> {
+    input
+        .iter()
+        .filter(|item| item["type"] == "message")
+        .filter_map(|item| item["content"][0]["text"].as_str())
+        .filter(|text| text.starts_with("<user_instructions>"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Nested AGENTS.md files are merged root first, reported in the
+/// SessionConfigured event, and re-read when the session cwd moves.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn project_docs_are_layered_and_follow_the_cwd() {
+    let server = MockServer::start().await;
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse_completed("resp"), "text/event-stream");
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let repo = TempDir::new().unwrap();
+    let root = repo.path().canonicalize().unwrap();
+    std::fs::write(root.join(".git"), "gitdir: nowhere\n").unwrap();
+    std::fs::write(root.join("AGENTS.md"), "root rules").unwrap();
+    let pkg = root.join("pkg");
+    std::fs::create_dir_all(&pkg).unwrap();
+    std::fs::write(pkg.join("AGENTS.md"), "pkg rules").unwrap();
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = root.clone();
+    config.user_instructions = None;
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let new_conversation = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation");
+    assert_eq!(
+        new_conversation.session_configured.project_docs,
+        vec![root.join("AGENTS.md")]
+    );
+    let codex = new_conversation.conversation;
+
+    for (turn, cwd) in [(1, None), (2, Some(pkg.clone()))] {
+        if cwd.is_some() {
+            codex
+                .submit(Op::OverrideTurnContext {
+                    cwd,
+                    approval_policy: None,
+                    sandbox_policy: None,
+                    model: None,
+                    effort: None,
+                    summary: None,
+                })
+                .await
+                .unwrap();
+        }
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text {
+                    text: format!("hello {turn}"),
+                }],
+            })
+            .await
+            .unwrap();
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests[1].body_json::<Value>().unwrap();
+    assert_eq!(
+        user_instructions(body["input"].as_array().unwrap()),
+        vec![
+            "<user_instructions>\n\n--- AGENTS.md ---\n\nroot rules\n\n</user_instructions>",
+            "<user_instructions>\n\n--- AGENTS.md ---\n\nroot rules\n\n--- pkg/AGENTS.md ---\n\npkg rules\n\n</user_instructions>",
+        ]
+    );
+}
+
+/// An AGENTS.md over the budget is cut at a paragraph break, the model is told
+/// what is missing inside the instructions, and the user gets a warning.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn oversized_project_doc_is_truncated_with_a_warning() {
+    let server = MockServer::start().await;
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse_completed("resp"), "text/event-stream");
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let repo = TempDir::new().unwrap();
+    let root = repo.path().canonicalize().unwrap();
+    std::fs::write(root.join("AGENTS.md"), "keep this\n\ndrop this").unwrap();
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = root.clone();
+    config.user_instructions = None;
+    config.project_doc_max_bytes = 15;
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    let EventMsg::ProjectDocTruncated(warning) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::ProjectDocTruncated(_))).await
+    else {
+        unreachable!();
+    };
+    assert_eq!(warning.limit_bytes, 15);
+    assert_eq!(
+        warning.files,
+        vec![TruncatedProjectDoc {
+            path: root.join("AGENTS.md"),
+            size_bytes: 20,
+            omitted_bytes: 11,
+        }]
+    );
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".to_string(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests[0].body_json::<Value>().unwrap();
+    assert_eq!(
+        user_instructions(body["input"].as_array().unwrap()),
+        vec![
+            "<user_instructions>\n\n--- AGENTS.md (truncated) ---\n\nkeep this\n\n[... 11 of 20 bytes of AGENTS.md omitted: the project docs exceed project_doc_max_bytes (15) ...]\n\n</user_instructions>",
+        ]
+    );
+}
+
+/// With `project_doc_strict` the session refuses to start and names the file.
+#[tokio::test]
+async fn strict_project_doc_limit_refuses_to_start() {
+    let repo = TempDir::new().unwrap();
+    let root = repo.path().canonicalize().unwrap();
+    std::fs::write(root.join("AGENTS.md"), "x".repeat(64)).unwrap();
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = root.clone();
+    config.project_doc_max_bytes = 32;
+    config.project_doc_strict = true;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let Err(err) = conversation_manager.new_conversation(config).await else {
+        panic!("session should not start");
+    };
+    assert!(
+        matches!(
+            &err,
+            CodexErr::ProjectDocTooLarge { path, size: 64, limit: 32 }
+                if *path == root.join("AGENTS.md")
+        ),
+        "{err:?}"
+    );
+    assert!(err.to_string().contains("AGENTS.md (64 bytes)"), "{err}");
+}
@@ -28,6 +28,16 @@ fn sse_completed(id: &str) -> String {
     load_sse_fixture_with_id("tests/fixtures/completed_template.json", id)
 }
 
+/// Mirrors the OS description Codex reports in the environment context.
+fn expected_os() -> String {
+    let version = os_info::get().version().clone();
+    if version == os_info::Version::Unknown {
+        std::env::consts::OS.to_string()
+    } else {
+        format!("{} {version}", std::env::consts::OS)
+    }
+}
+
 fn assert_tool_names(body: &serde_json::Value, expected_names: &[&str]) {
     assert_eq!(
         body["tools"]
@@ -108,6 +118,7 @@ async fn codex_mini_latest_tools() {
 
     let expected_instructions = [
         include_str!("../../prompt.md"),
+        include_str!("../../plan_tool_instructions.md"),
         include_str!("../../../apply-patch/apply_patch_tool_instructions.md"),
     ]
     .join("\n");
@@ -188,7 +199,11 @@ async fn prompt_tools_are_consistent_across_requests() {
     let requests = server.received_requests().await.unwrap();
     assert_eq!(requests.len(), 2, "expected two POST requests");
 
-    let expected_instructions: &str = include_str!("../../prompt.md");
+    let expected_instructions = [
+        include_str!("../../prompt.md"),
+        include_str!("../../plan_tool_instructions.md"),
+    ]
+    .join("\n");
     // our internal implementation is responsible for keeping tools in sync
     // with the OpenAI schema, so we just verify the tool presence here
     let expected_tools_names: &[&str] = &["shell", "update_plan", "apply_patch", "view_image"];
@@ -237,14 +252,19 @@ async fn prefixes_context_and_instructions_once_and_consistently_across_requests
     config.cwd = cwd.path().to_path_buf();
     config.model_provider = model_provider;
     config.user_instructions = Some("be consistent and helpful".to_string());
+    // Keep the reported environment independent of the variables set here.
+    config.shell_environment_policy.ignore_default_excludes = true;
+    let scratch_root = config.dirs.cache_dir().join("scratch");
 
     let conversation_manager =
         ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
-    let codex = conversation_manager
+    let new_conversation = conversation_manager
         .new_conversation(config)
         .await
-        .expect("create new conversation")
-        .conversation;
+        .expect("create new conversation");
+    let codex = new_conversation.conversation;
+    let scratch_dir = scratch_root.join(new_conversation.conversation_id.to_string());
+    assert!(scratch_dir.is_dir(), "scratch directory is created");
 
     codex
         .submit(Op::UserInput {
@@ -277,8 +297,15 @@ async fn prefixes_context_and_instructions_once_and_consistently_across_requests
   <approval_policy>on-request</approval_policy>
   <sandbox_mode>read-only</sandbox_mode>
   <network_access>restricted</network_access>
-{}</environment_context>"#,
+  <scratch_dir>{}</scratch_dir>
+  <os>{}</os>
+  <arch>{}</arch>
+{}  <env_inherit>all</env_inherit>
+</environment_context>"#,
         cwd.path().to_string_lossy(),
+        scratch_dir.to_string_lossy(),
+        expected_os(),
+        std::env::consts::ARCH,
         match shell.name() {
             Some(name) => format!("  <shell>{name}</shell>\n"),
             None => String::new(),
@@ -387,6 +414,7 @@ async fn overrides_turn_context_but_keeps_cached_prefix_and_key_constant() {
             sandbox_policy: Some(SandboxPolicy::WorkspaceWrite {
                 writable_roots: vec![writable.path().to_path_buf()],
                 network_access: true,
+                allowed_domains: vec![],
                 exclude_tmpdir_env_var: true,
                 exclude_slash_tmp: true,
             }),
@@ -431,11 +459,15 @@ async fn overrides_turn_context_but_keeps_cached_prefix_and_key_constant() {
     // After overriding the turn context, the environment context should be emitted again
     // reflecting the new approval policy and sandbox settings. Omit cwd because it did
     // not change.
-    let expected_env_text_2 = r#"<environment_context>
+    let expected_env_text_2 = format!(
+        r#"<environment_context>
   <approval_policy>never</approval_policy>
   <sandbox_mode>workspace-write</sandbox_mode>
   <network_access>enabled</network_access>
-</environment_context>"#;
+  <writable_roots>{}</writable_roots>
+</environment_context>"#,
+        writable.path().to_string_lossy()
+    );
     let expected_env_msg_2 = serde_json::json!({
         "type": "message",
         "id": serde_json::Value::Null,
@@ -452,6 +484,112 @@ async fn overrides_turn_context_but_keeps_cached_prefix_and_key_constant() {
     assert_eq!(body2["input"], expected_body2);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn overriding_model_family_rebuilds_tools() {
+    use pretty_assertions::assert_eq;
+
+    let server = MockServer::start().await;
+
+    let sse = sse_completed("resp");
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse, "text/event-stream");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let cwd = TempDir::new().unwrap();
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = cwd.path().to_path_buf();
+    config.model_provider = model_provider;
+    config.include_apply_patch_tool = false;
+    config.model = "codex-mini-latest".to_string();
+    config.model_family = find_family_for_model("codex-mini-latest").unwrap();
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello 1".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    // Switch from a local_shell family to one that uses the function shell.
+    codex
+        .submit(Op::OverrideTurnContext {
+            cwd: None,
+            approval_policy: None,
+            sandbox_policy: None,
+            model: Some("gpt-5".to_string()),
+            effort: Some(ReasoningEffort::Low),
+            summary: None,
+        })
+        .await
+        .unwrap();
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello 2".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected two POST requests");
+
+    // `local_shell` has no name, so identify tools by name or type.
+    let tool_ids = |body: &serde_json::Value| -> Vec<String> {
+        body["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| {
+                t["name"]
+                    .as_str()
+                    .or_else(|| t["type"].as_str())
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+    let body1 = requests[0].body_json::<serde_json::Value>().unwrap();
+    let body2 = requests[1].body_json::<serde_json::Value>().unwrap();
+    assert!(tool_ids(&body1).contains(&"local_shell".to_string()));
+    let tools2 = tool_ids(&body2);
+    assert!(!tools2.contains(&"local_shell".to_string()));
+    assert!(tools2.contains(&"shell".to_string()));
+    assert_eq!(body2["model"], "gpt-5");
+    assert_eq!(body2["reasoning"]["effort"], "low");
+
+    // The conversation so far is still sent as the prefix of the new request.
+    let input1 = body1["input"].as_array().unwrap();
+    let input2 = body2["input"].as_array().unwrap();
+    assert_eq!(&input2[..input1.len()], input1.as_slice());
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn per_turn_overrides_keep_cached_prefix_and_key_constant() {
     use pretty_assertions::assert_eq;
@@ -515,12 +653,14 @@ async fn per_turn_overrides_keep_cached_prefix_and_key_constant() {
             sandbox_policy: SandboxPolicy::WorkspaceWrite {
                 writable_roots: vec![writable.path().to_path_buf()],
                 network_access: true,
+                allowed_domains: vec![],
                 exclude_tmpdir_env_var: true,
                 exclude_slash_tmp: true,
             },
             model: "o3".to_string(),
-            effort: ReasoningEffort::High,
+            effort: Some(ReasoningEffort::High),
             summary: ReasoningSummary::Detailed,
+            final_output_json_schema: None,
         })
         .await
         .unwrap();
@@ -556,3 +696,304 @@ async fn per_turn_overrides_keep_cached_prefix_and_key_constant() {
     );
     assert_eq!(body2["input"], expected_body2);
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn user_turn_effort_applies_to_that_turn_only() {
+    use pretty_assertions::assert_eq;
+
+    let server = MockServer::start().await;
+
+    let sse = sse_completed("resp");
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse, "text/event-stream");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let cwd = TempDir::new().unwrap();
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = cwd.path().to_path_buf();
+    config.model_provider = model_provider;
+    config.model_reasoning_effort = ReasoningEffort::Low;
+    let (model, approval_policy, sandbox_policy) = (
+        config.model.clone(),
+        config.approval_policy,
+        config.sandbox_policy.clone(),
+    );
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    for effort in [Some(ReasoningEffort::High), None] {
+        codex
+            .submit(Op::UserTurn {
+                items: vec![InputItem::Text {
+                    text: "hello".into(),
+                }],
+                cwd: cwd.path().to_path_buf(),
+                approval_policy,
+                sandbox_policy: sandbox_policy.clone(),
+                model: model.clone(),
+                effort,
+                summary: ReasoningSummary::Auto,
+                final_output_json_schema: None,
+            })
+            .await
+            .unwrap();
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let efforts: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|request| {
+            request.body_json::<serde_json::Value>().unwrap()["reasoning"]["effort"].clone()
+        })
+        .collect();
+    assert_eq!(efforts, vec!["high", "low", "low"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn hide_environment_context_omits_context_message() {
+    use pretty_assertions::assert_eq;
+
+    let server = MockServer::start().await;
+
+    let sse = sse_completed("resp");
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse, "text/event-stream");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let cwd = TempDir::new().unwrap();
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = cwd.path().to_path_buf();
+    config.model_provider = model_provider;
+    config.user_instructions = Some("be consistent and helpful".to_string());
+    config.hide_environment_context = true;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello 1".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    // Changing the turn context must not inject a context message either.
+    codex
+        .submit(Op::OverrideTurnContext {
+            cwd: None,
+            approval_policy: Some(AskForApproval::Never),
+            sandbox_policy: None,
+            model: None,
+            effort: None,
+            summary: None,
+        })
+        .await
+        .unwrap();
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello 2".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2, "expected two POST requests");
+
+    let expected_ui_msg = serde_json::json!({
+        "type": "message",
+        "id": serde_json::Value::Null,
+        "role": "user",
+        "content": [ { "type": "input_text", "text": "<user_instructions>\n\nbe consistent and helpful\n\n</user_instructions>" } ]
+    });
+    let expected_user_message_1 = serde_json::json!({
+        "type": "message",
+        "id": serde_json::Value::Null,
+        "role": "user",
+        "content": [ { "type": "input_text", "text": "hello 1" } ]
+    });
+    let body0 = requests[0].body_json::<serde_json::Value>().unwrap();
+    assert_eq!(
+        body0["input"],
+        serde_json::json!([expected_ui_msg, expected_user_message_1])
+    );
+
+    let body1 = requests[1].body_json::<serde_json::Value>().unwrap();
+    let input1 = body1["input"].as_array().unwrap();
+    assert!(
+        input1
+            .iter()
+            .all(|item| !item.to_string().contains("<environment_context>")),
+        "environment context should not be sent: {input1:?}"
+    );
+}
+
+/// Runs a single turn for `config` and returns the `prompt_cache_key` the
+/// request was sent with.
+async fn prompt_cache_key_for_turn(
+    server: &MockServer,
+    config: codex_core::config::Config,
+) -> String {
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests
+        .last()
+        .unwrap()
+        .body_json::<serde_json::Value>()
+        .unwrap();
+    body["prompt_cache_key"].as_str().unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn resumed_sessions_reuse_prompt_cache_key() {
+    use pretty_assertions::assert_eq;
+
+    let server = MockServer::start().await;
+
+    let sse = sse_completed("resp");
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse, "text/event-stream");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    // Rollout of a previous session whose id must survive the resume.
+    let session_id = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+    let rollout_dir = TempDir::new().unwrap();
+    let rollout_path = rollout_dir.path().join("rollout.jsonl");
+    let meta = serde_json::json!({
+        "id": session_id,
+        "timestamp": "2025-01-01T00:00:00.000Z",
+        "instructions": null,
+    });
+    std::fs::write(&rollout_path, format!("{meta}\n")).unwrap();
+
+    let cwd = TempDir::new().unwrap();
+    let mut keys = Vec::new();
+    for _ in 0..2 {
+        let codex_home = TempDir::new().unwrap();
+        let mut config = load_default_config_for_test(&codex_home);
+        config.cwd = cwd.path().to_path_buf();
+        config.model_provider = model_provider.clone();
+        config.experimental_resume = Some(rollout_path.clone());
+        keys.push(prompt_cache_key_for_turn(&server, config).await);
+    }
+
+    assert_eq!(keys, vec![session_id.to_string(), session_id.to_string()]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn configured_prompt_cache_key_is_sent() {
+    use pretty_assertions::assert_eq;
+
+    let server = MockServer::start().await;
+
+    let sse = sse_completed("resp");
+    let template = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse, "text/event-stream");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(template)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+
+    let cwd = TempDir::new().unwrap();
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = cwd.path().to_path_buf();
+    config.model_provider = model_provider;
+    config.prompt_cache_key = Some("pinned-key".to_string());
+
+    assert_eq!(
+        prompt_cache_key_for_turn(&server, config).await,
+        "pinned-key"
+    );
+}
@@ -0,0 +1,285 @@
+#![cfg(unix)]
+#![expect(clippy::unwrap_used)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::config::Config;
+use codex_core::config_types::PullRequestToolToml;
+use codex_core::protocol::AskForApproval;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::PullRequestEvent;
+use codex_core::protocol::SandboxPolicy;
+use codex_core::pull_request::PullRequestTool;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::ev_completed;
+use core_test_support::load_default_config_for_test;
+use core_test_support::sse;
+use core_test_support::wait_for_event;
+use serde_json::Value;
+use serde_json::json;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?}: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// A repository with one commit on `main`, a bare `origin` next to it, an
+/// uncommitted file, and a fake `gh` that records its arguments and prints
+/// a pull request URL.
+struct Fixture {
+    root: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let root = TempDir::new().unwrap();
+        let repo = root.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        git(root.path(), &["init", "-q", "--bare", "origin.git"]);
+        git(&repo, &["init", "-q", "-b", "main"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        git(&repo, &["add", "README.md"]);
+        git(
+            &repo,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "initial",
+            ],
+        );
+        git(&repo, &["remote", "add", "origin", "../origin.git"]);
+        std::fs::write(repo.join("feature.txt"), "new work\n").unwrap();
+
+        let bin = root.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        let gh = bin.join("gh");
+        std::fs::write(
+            &gh,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > {}\necho https://github.com/acme/widgets/pull/7\n",
+                root.path().join("gh-args").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&gh, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Self { root }
+    }
+
+    fn repo(&self) -> std::path::PathBuf {
+        self.root.path().join("repo")
+    }
+
+    fn config(&self, server: &MockServer, home: &TempDir, dry_run: bool) -> Config {
+        let mut config = load_default_config_for_test(home);
+        config.cwd = self.repo();
+        config.model_provider = ModelProviderInfo {
+            base_url: Some(format!("{}/v1", server.uri())),
+            ..built_in_model_providers()["openai"].clone()
+        };
+        config.approval_policy = AskForApproval::Never;
+        config.sandbox_policy = SandboxPolicy::DangerFullAccess;
+        config.pull_request_tool = Some(
+            PullRequestTool::from_config(&PullRequestToolToml {
+                base: Some("main".to_string()),
+                requires_approval: Some(false),
+                dry_run,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let path = std::env::var("PATH").unwrap_or_default();
+        let set = &mut config.shell_environment_policy.r#set;
+        set.insert(
+            "PATH".to_string(),
+            format!("{}:{path}", self.root.path().join("bin").display()),
+        );
+        set.insert("GIT_CONFIG_GLOBAL".to_string(), "/dev/null".to_string());
+        for var in ["GIT_AUTHOR", "GIT_COMMITTER"] {
+            set.insert(format!("{var}_NAME"), "Test".to_string());
+            set.insert(format!("{var}_EMAIL"), "test@example.com".to_string());
+        }
+        config
+    }
+}
+
+/// Mounts a model that calls `create_pull_request` once and then finishes.
+async fn start_server() -> MockServer {
+    let server = MockServer::start().await;
+    let call = json!({
+        "type": "response.output_item.done",
+        "item": {
+            "type": "function_call",
+            "call_id": "call-pr",
+            "name": "create_pull_request",
+            "arguments": json!({
+                "title": "Add feature",
+                "body": "Adds feature.txt.",
+                "head": "feature",
+            })
+            .to_string(),
+        }
+    });
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse(vec![call, ev_completed("r1")]), "text/event-stream"),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse(vec![ev_completed("r2")]), "text/event-stream"),
+        )
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Runs one turn and returns the pull request event and the tool output the
+/// model got.
+async fn run_turn(server: &MockServer, config: Config) -> (PullRequestEvent, String) {
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "open a PR".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    let EventMsg::PullRequest(event) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::PullRequest(_))).await
+    else {
+        unreachable!();
+    };
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests[1].body_json::<Value>().unwrap();
+    let output = body["input"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["type"] == "function_call_output" && item["call_id"] == "call-pr")
+        .map(|item| item["output"].as_str().unwrap().to_string())
+        .unwrap();
+    (event, output)
+}
+
+/// The tool commits the pending change on a new branch, pushes it and
+/// reports the URL `gh` printed.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn creates_branch_commits_pushes_and_reports_url() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let fixture = Fixture::new();
+    let server = start_server().await;
+    let home = TempDir::new().unwrap();
+    let (event, output) = run_turn(&server, fixture.config(&server, &home, false)).await;
+
+    let url = "https://github.com/acme/widgets/pull/7";
+    assert_eq!(event.url.as_deref(), Some(url));
+    assert_eq!(event.head, "feature");
+    assert_eq!(event.base.as_deref(), Some("main"));
+    assert_eq!(output, format!("Opened pull request {url}"));
+
+    let origin = fixture.root.path().join("origin.git");
+    assert_eq!(
+        git(&origin, &["log", "-1", "--format=%s", "feature"]).trim(),
+        "Add feature"
+    );
+    assert_eq!(
+        git(&fixture.repo(), &["status", "--porcelain"]).trim(),
+        "",
+        "the pending change was committed"
+    );
+    let gh_args = std::fs::read_to_string(fixture.root.path().join("gh-args")).unwrap();
+    assert_eq!(
+        gh_args.lines().collect::<Vec<_>>(),
+        vec![
+            "pr",
+            "create",
+            "--head",
+            "feature",
+            "--title",
+            "Add feature",
+            "--body",
+            "Adds feature.txt.",
+            "--base",
+            "main",
+        ]
+    );
+}
+
+/// In a dry run the model and the UI get the script, and nothing changes.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dry_run_reports_the_script_without_running_it() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let fixture = Fixture::new();
+    let server = start_server().await;
+    let home = TempDir::new().unwrap();
+    let (event, output) = run_turn(&server, fixture.config(&server, &home, true)).await;
+
+    assert_eq!(event.url, None);
+    let script = event.dry_run_script.unwrap();
+    assert!(script.contains("git push -u origin feature"), "{script}");
+    assert!(
+        output.starts_with("Dry run: nothing was committed or pushed"),
+        "{output}"
+    );
+    assert!(output.ends_with(&script), "{output}");
+
+    assert_eq!(
+        git(&fixture.repo(), &["status", "--porcelain"]).trim(),
+        "?? feature.txt"
+    );
+    assert!(!fixture.root.path().join("gh-args").exists());
+}
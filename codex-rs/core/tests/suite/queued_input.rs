@@ -0,0 +1,247 @@
+#![expect(clippy::unwrap_used)]
+
+use std::time::Duration;
+
+use codex_core::CodexConversation;
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::built_in_model_providers;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::protocol::QueuedInputEvent;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::wait_for_event;
+use serde_json::Value;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn sse_completed(id: &str) -> String {
+    let completed = serde_json::json!({
+        "type": "response.completed",
+        "response": {
+            "id": id,
+            "usage": {"input_tokens":0,"input_tokens_details":null,"output_tokens":0,"output_tokens_details":null,"total_tokens":0}
+        }
+    });
+    format!("event: response.completed\ndata: {completed}\n\n")
+}
+
+/// Answers requests whose body contains `text` after `delay`. Later turns
+/// resend earlier input, so their replies need a higher priority (a lower
+/// number).
+async fn mount_reply(server: &MockServer, text: &'static str, delay: Duration, priority: u8) {
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .and(move |req: &wiremock::Request| {
+            std::str::from_utf8(&req.body).unwrap_or("").contains(text)
+        })
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse_completed(text), "text/event-stream")
+                .set_delay(delay),
+        )
+        .with_priority(priority)
+        .mount(server)
+        .await;
+}
+
+async fn start_conversation(
+    server: &MockServer,
+    home: &TempDir,
+    keep_queued_input_on_interrupt: bool,
+) -> std::sync::Arc<CodexConversation> {
+    let mut config = load_default_config_for_test(home);
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    config.keep_queued_input_on_interrupt = keep_queued_input_on_interrupt;
+    ConversationManager::with_auth(CodexAuth::from_api_key("dummy"))
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation
+}
+
+fn text(text: &str) -> Vec<InputItem> {
+    vec![InputItem::Text {
+        text: text.to_string(),
+    }]
+}
+
+async fn next_queue_update(codex: &CodexConversation) -> QueuedInputEvent {
+    let EventMsg::QueuedInputUpdated(update) =
+        wait_for_event(codex, |ev| matches!(ev, EventMsg::QueuedInputUpdated(_))).await
+    else {
+        unreachable!()
+    };
+    update
+}
+
+/// Texts of the last user message sent to the model.
+async fn last_user_message(server: &MockServer) -> Vec<String> {
+    let requests = server.received_requests().await.unwrap();
+    let body = requests.last().unwrap().body_json::<Value>().unwrap();
+    let message = body["input"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|item| item["type"] == "message" && item["role"] == "user")
+        .unwrap()
+        .clone();
+    message["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|content| content["text"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Input queued during a turn waits for it and is then sent, in order, as the
+/// input of a single new turn.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn queued_input_is_sent_together_after_the_turn() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    mount_reply(&server, "SECOND_MESSAGE", Duration::ZERO, 1).await;
+    mount_reply(&server, "FIRST_MESSAGE", Duration::from_millis(500), 2).await;
+    let home = TempDir::new().unwrap();
+    let codex = start_conversation(&server, &home, false).await;
+
+    codex
+        .submit(Op::UserInput {
+            items: text("FIRST_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskStarted(_))).await;
+
+    let second_id = codex
+        .submit(Op::QueueUserInput {
+            items: text("SECOND_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    assert_eq!(next_queue_update(&codex).await.queue.len(), 1);
+    let dropped_id = codex
+        .submit(Op::QueueUserInput {
+            items: text("DROPPED_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    next_queue_update(&codex).await;
+    codex
+        .submit(Op::QueueUserInput {
+            items: text("THIRD_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    next_queue_update(&codex).await;
+
+    codex
+        .submit(Op::RemoveQueuedInput { id: dropped_id })
+        .await
+        .unwrap();
+    let update = next_queue_update(&codex).await;
+    assert_eq!(update.queue.len(), 2);
+    assert_eq!(update.queue[0].id, second_id);
+
+    // The first turn completes before the queued input starts the next one.
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    let update = next_queue_update(&codex).await;
+    assert!(update.queue.is_empty());
+    assert_eq!(update.started.len(), 2);
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        last_user_message(&server).await,
+        vec!["SECOND_MESSAGE", "THIRD_MESSAGE"]
+    );
+}
+
+async fn interrupt_with_queued_input(keep_queued_input_on_interrupt: bool) {
+    let server = MockServer::start().await;
+    mount_reply(&server, "NEXT_MESSAGE", Duration::ZERO, 1).await;
+    mount_reply(&server, "FIRST_MESSAGE", Duration::from_secs(30), 2).await;
+    let home = TempDir::new().unwrap();
+    let codex = start_conversation(&server, &home, keep_queued_input_on_interrupt).await;
+
+    codex
+        .submit(Op::UserInput {
+            items: text("FIRST_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskStarted(_))).await;
+    codex
+        .submit(Op::QueueUserInput {
+            items: text("QUEUED_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    next_queue_update(&codex).await;
+
+    codex.submit(Op::Interrupt).await.unwrap();
+    if keep_queued_input_on_interrupt {
+        codex.submit(Op::ListQueuedInput).await.unwrap();
+        assert_eq!(next_queue_update(&codex).await.queue.len(), 1);
+    } else {
+        // The queue was cleared, and the frontend got the entries back.
+        let update = next_queue_update(&codex).await;
+        assert_eq!(update.cleared.len(), 1);
+        assert!(update.queue.is_empty());
+    }
+
+    codex
+        .submit(Op::UserInput {
+            items: text("NEXT_MESSAGE"),
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    let expected = if keep_queued_input_on_interrupt {
+        vec!["QUEUED_MESSAGE", "NEXT_MESSAGE"]
+    } else {
+        vec!["NEXT_MESSAGE"]
+    };
+    assert_eq!(last_user_message(&server).await, expected);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn interrupt_clears_queued_input() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+    interrupt_with_queued_input(false).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn interrupt_keeps_queued_input_when_configured() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+    interrupt_with_queued_input(true).await;
+}
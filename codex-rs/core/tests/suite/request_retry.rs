@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::ProviderKind;
+use codex_core::WireApi;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::load_sse_fixture_with_id;
+use core_test_support::wait_for_event_with_timeout;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn sse_completed(id: &str) -> String {
+    load_sse_fixture_with_id("tests/fixtures/completed_template.json", id)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rate_limited_request_is_retried_and_reported() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+
+    let rate_limited = ResponseTemplate::new(429)
+        .insert_header("retry-after-ms", "10")
+        .insert_header("content-type", "application/json")
+        .set_body_string(
+            serde_json::json!({
+                "error": {"type": "rate_limit_exceeded", "message": "slow down"}
+            })
+            .to_string(),
+        );
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(rate_limited)
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ok = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(sse_completed("resp_ok"), "text/event-stream");
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(ok)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "mock-openai".into(),
+        base_url: Some(format!("{}/v1", server.uri())),
+        env_key: Some("PATH".into()),
+        env_key_instructions: None,
+        wire_api: Some(WireApi::Responses),
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(2),
+        request_retry_base_delay_ms: Some(10),
+        request_retry_max_delay_ms: Some(100),
+        request_retry_on_status: Some(vec![429]),
+        stream_max_retries: Some(0),
+        stream_idle_timeout_ms: Some(2_000),
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
+        requires_openai_auth: false,
+        stored_api_key: None,
+    };
+
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = provider;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "hello".into(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let retry_notice = wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::StreamError(_)),
+        Duration::from_secs(5),
+    )
+    .await;
+    let EventMsg::StreamError(retry_notice) = retry_notice else {
+        unreachable!();
+    };
+    assert!(
+        retry_notice
+            .message
+            .starts_with("429 Too Many Requests; retrying 1/2 in"),
+        "unexpected retry notice: {}",
+        retry_notice.message
+    );
+
+    wait_for_event_with_timeout(
+        &codex,
+        |ev| matches!(ev, EventMsg::TaskComplete(_)),
+        Duration::from_secs(5),
+    )
+    .await;
+}
@@ -0,0 +1,225 @@
+#![expect(clippy::unwrap_used)]
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::NewConversation;
+use codex_core::built_in_model_providers;
+use codex_core::config::Config;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::rollout::find_rollout_by_id;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::load_sse_fixture_with_id;
+use core_test_support::wait_for_event;
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+use tempfile::TempDir;
+use uuid::Uuid;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+/// Recorded session whose last shell call never produced output.
+const ROLLOUT: &str = include_str!("../fixtures/rollout_with_dangling_call.jsonl");
+const RECORDED_SESSION_ID: &str = "0f8c2d52-6a49-4f1b-9d3e-2c1a7b5e4f60";
+
+fn sse_completed(id: &str) -> String {
+    load_sse_fixture_with_id("tests/fixtures/completed_template.json", id)
+}
+
+/// Starts a conversation from `config`, sends one user turn and returns the
+/// new conversation id together with the `input` sent to the model.
+async fn run_one_turn(server: &MockServer, config: Config) -> (Uuid, Vec<Value>) {
+    let conversation_manager = ConversationManager::with_auth(CodexAuth::from_api_key("dummy"));
+    let NewConversation {
+        conversation_id,
+        conversation: codex,
+        ..
+    } = conversation_manager.new_conversation(config).await.unwrap();
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "continue".into(),
+            }],
+        })
+        .await
+        .unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+
+    let requests = server.received_requests().await.unwrap();
+    let body = requests.last().unwrap().body_json::<Value>().unwrap();
+    let input = body["input"].as_array().unwrap().clone();
+    (conversation_id, input)
+}
+
+async fn start_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse_completed("resp"), "text/event-stream"),
+        )
+        .mount(&server)
+        .await;
+    server
+}
+
+fn config_for(server: &MockServer, codex_home: &TempDir, cwd: &TempDir) -> Config {
+    let mut config = load_default_config_for_test(codex_home);
+    config.cwd = cwd.path().to_path_buf();
+    config.model_provider = ModelProviderInfo {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..built_in_model_providers()["openai"].clone()
+    };
+    config
+}
+
+fn texts_with_prefix(input: &[Value], prefix: &str) -> Vec<String> {
+    input
+        .iter()
+        .filter(|item| item["type"] == "message")
+        .filter_map(|item| item["content"][0]["text"].as_str())
+        .filter(|text| text.starts_with(prefix))
+        .map(str::to_string)
+        .collect()
+}
+
+fn call_ids(input: &[Value]) -> Vec<String> {
+    input
+        .iter()
+        .filter_map(|item| item["call_id"].as_str())
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn resume_replays_history_for_current_cwd() {
+    let server = start_server().await;
+    let codex_home = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    let rollout_dir = TempDir::new().unwrap();
+    let rollout_path = rollout_dir.path().join("rollout.jsonl");
+    std::fs::write(&rollout_path, ROLLOUT).unwrap();
+
+    let mut config = config_for(&server, &codex_home, &cwd);
+    config.experimental_resume = Some(rollout_path.clone());
+    let (conversation_id, input) = run_one_turn(&server, config).await;
+
+    assert_eq!(conversation_id.to_string(), RECORDED_SESSION_ID);
+
+    // The completed call is replayed; the one without output is not.
+    assert_eq!(call_ids(&input), vec!["call-done", "call-done"]);
+
+    // The recorded user instructions are sent exactly once.
+    assert_eq!(
+        texts_with_prefix(&input, "<user_instructions>"),
+        vec!["<user_instructions>\n\nPrefer small commits.\n\n</user_instructions>"]
+    );
+
+    // The recorded environment context is replayed, followed by the fields
+    // that changed since, including the new cwd.
+    let environment_contexts = texts_with_prefix(&input, "<environment_context>");
+    assert_eq!(environment_contexts.len(), 2, "{environment_contexts:?}");
+    assert_eq!(
+        environment_contexts[0],
+        "<environment_context>\n  <cwd>/recorded/cwd</cwd>\n</environment_context>"
+    );
+    assert!(
+        environment_contexts[1].contains(&format!("<cwd>{}</cwd>", cwd.path().display())),
+        "{environment_contexts:?}"
+    );
+
+    let last = input.last().unwrap();
+    assert_eq!(last["content"][0]["text"], "continue");
+
+    // The resumed turn is appended to the original rollout.
+    let contents = std::fs::read_to_string(&rollout_path).unwrap();
+    assert!(contents.starts_with(ROLLOUT));
+    assert!(contents.len() > ROLLOUT.len());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn resume_only_reports_environment_changes() {
+    let server = start_server().await;
+    let codex_home = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    let rollout_dir = TempDir::new().unwrap();
+    let rollout_path = rollout_dir.path().join("rollout.jsonl");
+    std::fs::write(&rollout_path, ROLLOUT).unwrap();
+
+    let mut config = config_for(&server, &codex_home, &cwd);
+    config.experimental_resume = Some(rollout_path.clone());
+    let (_, first) = run_one_turn(&server, config.clone()).await;
+    let first_contexts = texts_with_prefix(&first, "<environment_context>");
+
+    // Resuming again from the same cwd repeats neither the instructions nor
+    // the environment context.
+    let (_, second) = run_one_turn(&server, config).await;
+    assert_eq!(
+        texts_with_prefix(&second, "<environment_context>"),
+        first_contexts
+    );
+    assert_eq!(texts_with_prefix(&second, "<user_instructions>").len(), 1);
+
+    // Moving to another cwd only reports the cwd.
+    let other_cwd = TempDir::new().unwrap();
+    let mut config = config_for(&server, &codex_home, &other_cwd);
+    config.experimental_resume = Some(rollout_path);
+    let (_, third) = run_one_turn(&server, config).await;
+    let third_contexts = texts_with_prefix(&third, "<environment_context>");
+    assert_eq!(third_contexts.len(), first_contexts.len() + 1);
+    assert_eq!(
+        third_contexts.last().unwrap(),
+        &format!(
+            "<environment_context>\n  <cwd>{}</cwd>\n</environment_context>",
+            other_cwd.path().display()
+        )
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn fork_records_history_under_new_session_id() {
+    let server = start_server().await;
+    let codex_home = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    let rollout_dir = TempDir::new().unwrap();
+    let rollout_path = rollout_dir.path().join("rollout.jsonl");
+    std::fs::write(&rollout_path, ROLLOUT).unwrap();
+
+    let mut config = config_for(&server, &codex_home, &cwd);
+    config.experimental_resume = Some(rollout_path.clone());
+    config.experimental_fork = true;
+    let (conversation_id, input) = run_one_turn(&server, config).await;
+
+    assert_ne!(conversation_id.to_string(), RECORDED_SESSION_ID);
+    assert_eq!(call_ids(&input), vec!["call-done", "call-done"]);
+    assert_eq!(
+        texts_with_prefix(&input, "list the files"),
+        vec!["list the files"]
+    );
+
+    // The original transcript is untouched.
+    assert_eq!(std::fs::read_to_string(&rollout_path).unwrap(), ROLLOUT);
+
+    // The fork has its own rollout that starts with the copied history.
+    let fork_path = find_rollout_by_id(codex_home.path(), conversation_id)
+        .unwrap()
+        .unwrap();
+    let fork = std::fs::read_to_string(fork_path).unwrap();
+    let mut lines = fork.lines();
+    let meta: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(meta["id"], conversation_id.to_string());
+    assert_eq!(meta["instructions"], "Prefer small commits.");
+    assert!(fork.contains("list the files"));
+    assert!(
+        fork.contains("call-dangling"),
+        "fork copies the raw history"
+    );
+}
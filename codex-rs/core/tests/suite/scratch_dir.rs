@@ -0,0 +1,42 @@
+#![allow(clippy::unwrap_used)]
+
+use codex_core::ConversationManager;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::Op;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::wait_for_event;
+use tempfile::TempDir;
+
+/// Starts a session, shuts it down and reports whether its scratch directory
+/// was still there afterwards.
+async fn scratch_dir_survives_shutdown(retain_scratch_dir: bool) -> bool {
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.retain_scratch_dir = retain_scratch_dir;
+    let scratch_root = config.dirs.cache_dir().join("scratch");
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let new_conversation = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation");
+    let scratch_dir = scratch_root.join(new_conversation.conversation_id.to_string());
+    assert!(scratch_dir.is_dir(), "scratch directory is created");
+
+    let codex = new_conversation.conversation;
+    codex.submit(Op::Shutdown).await.unwrap();
+    wait_for_event(&codex, |ev| matches!(ev, EventMsg::ShutdownComplete)).await;
+    scratch_dir.exists()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn scratch_dir_is_removed_on_shutdown() {
+    assert!(!scratch_dir_survives_shutdown(false).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn scratch_dir_can_be_retained() {
+    assert!(scratch_dir_survives_shutdown(true).await);
+}
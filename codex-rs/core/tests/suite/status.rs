@@ -0,0 +1,76 @@
+#![allow(clippy::unwrap_used)]
+
+use codex_core::ConversationManager;
+use codex_core::protocol::AskForApproval;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::Op;
+use codex_core::protocol::SandboxPolicy;
+use codex_login::AuthMode;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::wait_for_event;
+use pretty_assertions::assert_eq;
+use tempfile::TempDir;
+
+/// The status reflects the session's instruction files, auth and rollout,
+/// and follows `Op::OverrideTurnContext`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn status_reports_the_current_turn_context() {
+    let repo = TempDir::new().unwrap();
+    let root = repo.path().canonicalize().unwrap();
+    std::fs::write(root.join(".git"), "gitdir: nowhere\n").unwrap();
+    std::fs::write(root.join("AGENTS.md"), "root rules").unwrap();
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.cwd = root.clone();
+    config.approval_policy = AskForApproval::OnRequest;
+    config.sandbox_policy = SandboxPolicy::ReadOnly;
+    let model = config.model.clone();
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .expect("create new conversation")
+        .conversation;
+
+    codex.submit(Op::GetStatus).await.unwrap();
+    let EventMsg::StatusResponse(status) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::StatusResponse(_))).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(status.model.slug, model);
+    assert_eq!(status.provider.id, "openai");
+    assert_eq!(status.approval_policy, AskForApproval::OnRequest);
+    assert_eq!(status.sandbox_policy, SandboxPolicy::ReadOnly);
+    assert!(status.writable_roots.is_empty());
+    assert!(!status.network_access);
+    assert_eq!(status.cwd, root);
+    assert_eq!(status.instruction_files, vec![root.join("AGENTS.md")]);
+    assert!(status.mcp_servers.is_empty());
+    assert_eq!(status.auth_mode, Some(AuthMode::ApiKey));
+    assert!(status.rollout_path.unwrap().is_file());
+
+    codex
+        .submit(Op::OverrideTurnContext {
+            cwd: None,
+            approval_policy: Some(AskForApproval::Never),
+            sandbox_policy: Some(SandboxPolicy::new_workspace_write_policy()),
+            model: None,
+            effort: None,
+            summary: None,
+        })
+        .await
+        .unwrap();
+    codex.submit(Op::GetStatus).await.unwrap();
+    let EventMsg::StatusResponse(status) =
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::StatusResponse(_))).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(status.approval_policy, AskForApproval::Never);
+    assert!(status.writable_roots.contains(&root));
+}
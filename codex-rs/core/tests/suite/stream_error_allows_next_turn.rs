@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use codex_core::ConversationManager;
 use codex_core::ModelProviderInfo;
+use codex_core::ProviderKind;
 use codex_core::WireApi;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::InputItem;
@@ -73,14 +74,26 @@ async fn continue_after_stream_error() {
         base_url: Some(format!("{}/v1", server.uri())),
         env_key: Some("PATH".into()),
         env_key_instructions: None,
-        wire_api: WireApi::Responses,
+        wire_api: Some(WireApi::Responses),
         query_params: None,
         http_headers: None,
         env_http_headers: None,
         request_max_retries: Some(1),
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
         stream_max_retries: Some(1),
         stream_idle_timeout_ms: Some(2_000),
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
         requires_openai_auth: false,
+        stored_api_key: None,
     };
 
     let home = TempDir::new().unwrap();
@@ -79,15 +79,27 @@ async fn retries_on_early_close() {
         // provider is not set.
         env_key: Some("PATH".into()),
         env_key_instructions: None,
-        wire_api: codex_core::WireApi::Responses,
+        wire_api: Some(codex_core::WireApi::Responses),
         query_params: None,
         http_headers: None,
         env_http_headers: None,
         // exercise retry path: first attempt yields incomplete stream, so allow 1 retry
         request_max_retries: Some(0),
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
         stream_max_retries: Some(1),
         stream_idle_timeout_ms: Some(2000),
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: codex_core::ProviderKind::OpenAi,
         requires_openai_auth: false,
+        stored_api_key: None,
     };
 
     let codex_home = TempDir::new().unwrap();
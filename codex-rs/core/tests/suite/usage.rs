@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use codex_core::ConversationManager;
+use codex_core::ModelProviderInfo;
+use codex_core::ProviderKind;
+use codex_core::WireApi;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_login::CodexAuth;
+use core_test_support::load_default_config_for_test;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use core_test_support::wait_for_event;
+use core_test_support::wait_for_event_with_timeout;
+use tempfile::TempDir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+const SSE_WITH_USAGE: &str = r#"[
+  {
+    "type": "response.completed",
+    "response": {
+      "id": "__ID__",
+      "usage": {
+        "input_tokens": 100,
+        "input_tokens_details": {"cached_tokens": 40},
+        "output_tokens": 10,
+        "output_tokens_details": {"reasoning_tokens": 4},
+        "total_tokens": 110
+      },
+      "output": []
+    }
+  }
+]"#;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn usage_is_reported_while_streaming_and_summed_per_session() {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    let ok = ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(
+            load_sse_fixture_with_id_from_str(SSE_WITH_USAGE, "resp"),
+            "text/event-stream",
+        );
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(ok)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "mock-openai".into(),
+        base_url: Some(format!("{}/v1", server.uri())),
+        env_key: Some("PATH".into()),
+        env_key_instructions: None,
+        wire_api: Some(WireApi::Responses),
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(0),
+        request_retry_base_delay_ms: None,
+        request_retry_max_delay_ms: None,
+        request_retry_on_status: None,
+        stream_max_retries: Some(0),
+        stream_idle_timeout_ms: Some(2_000),
+        temperature: None,
+        top_p: None,
+        seed: None,
+        stream_include_usage: None,
+        api_version: None,
+        deployment: None,
+        auth_header: None,
+        kind: ProviderKind::OpenAi,
+        requires_openai_auth: false,
+        stored_api_key: None,
+    };
+
+    let home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&home);
+    config.model_provider = provider;
+
+    let conversation_manager =
+        ConversationManager::with_auth(CodexAuth::from_api_key("Test API Key"));
+    let codex = conversation_manager
+        .new_conversation(config)
+        .await
+        .unwrap()
+        .conversation;
+
+    for text in ["hello", "again"] {
+        codex
+            .submit(Op::UserInput {
+                items: vec![InputItem::Text { text: text.into() }],
+            })
+            .await
+            .unwrap();
+
+        let update = wait_for_event_with_timeout(
+            &codex,
+            |ev| matches!(ev, EventMsg::UsageUpdate(_)),
+            Duration::from_secs(5),
+        )
+        .await;
+        let EventMsg::UsageUpdate(update) = update else {
+            unreachable!();
+        };
+        assert_eq!(update.last_token_usage.input_tokens, 100);
+        assert_eq!(update.last_token_usage.cached_input_tokens, Some(40));
+
+        wait_for_event(&codex, |ev| matches!(ev, EventMsg::TaskComplete(_))).await;
+    }
+
+    codex.submit(Op::GetUsage).await.unwrap();
+    let totals = wait_for_event(&codex, |ev| matches!(ev, EventMsg::UsageUpdate(_))).await;
+    let EventMsg::UsageUpdate(totals) = totals else {
+        unreachable!();
+    };
+    let total = totals.total_token_usage;
+    assert_eq!(total.input_tokens, 200);
+    assert_eq!(total.cached_input_tokens, Some(80));
+    assert_eq!(total.output_tokens, 20);
+    assert_eq!(total.reasoning_output_tokens, Some(8));
+    assert_eq!(total.total_tokens, 220);
+    assert_eq!(totals.last_token_usage.total_tokens, 110);
+}
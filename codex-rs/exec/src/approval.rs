@@ -0,0 +1,172 @@
+//! Answers approval requests on behalf of the user, who is not there to
+//! answer them, as `--on-approval` says.
+
+use std::path::PathBuf;
+
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::Op;
+use codex_core::protocol::ReviewDecision;
+use serde_json::Value;
+use serde_json::json;
+use shlex::try_join;
+
+use crate::cli::OnApproval;
+
+/// A command or patch that needed approval during the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ApprovalAction {
+    Exec {
+        call_id: String,
+        command: Vec<String>,
+    },
+    Patch {
+        call_id: String,
+        files: Vec<PathBuf>,
+    },
+}
+
+impl ApprovalAction {
+    /// The action an approval request is about, if `msg` is one.
+    pub(crate) fn from_event(msg: &EventMsg) -> Option<Self> {
+        match msg {
+            EventMsg::ExecApprovalRequest(ev) => Some(ApprovalAction::Exec {
+                call_id: ev.call_id.clone(),
+                command: ev.command.clone(),
+            }),
+            EventMsg::ApplyPatchApprovalRequest(ev) => {
+                let mut files = ev.changes.keys().cloned().collect::<Vec<_>>();
+                files.sort();
+                Some(ApprovalAction::Patch {
+                    call_id: ev.call_id.clone(),
+                    files,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            ApprovalAction::Exec { command, .. } => {
+                let command = try_join(command.iter().map(String::as_str))
+                    .unwrap_or_else(|_| command.join(" "));
+                format!("run `{command}`")
+            }
+            ApprovalAction::Patch { files, .. } => {
+                let files = files
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("apply a patch to {files}")
+            }
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            ApprovalAction::Exec { call_id, command } => json!({
+                "kind": "exec",
+                "call_id": call_id,
+                "command": command,
+            }),
+            ApprovalAction::Patch { call_id, files } => json!({
+                "kind": "patch",
+                "call_id": call_id,
+                "files": files,
+            }),
+        }
+    }
+}
+
+/// The answer to the approval request `id` for `action`. Failing aborts the
+/// turn, which ends the run.
+pub(crate) fn approval_response(
+    on_approval: OnApproval,
+    id: String,
+    action: &ApprovalAction,
+) -> Op {
+    let (decision, feedback) = match on_approval {
+        OnApproval::Fail => (ReviewDecision::Abort, None),
+        OnApproval::Skip => (ReviewDecision::Denied, Some(skipped_feedback(action))),
+        OnApproval::AutoDeny => (ReviewDecision::Denied, None),
+    };
+    match action {
+        ApprovalAction::Exec { .. } => Op::ExecApproval {
+            id,
+            decision,
+            feedback,
+        },
+        ApprovalAction::Patch { .. } => Op::PatchApproval {
+            id,
+            decision,
+            selection: None,
+            feedback,
+        },
+    }
+}
+
+/// Tool result for a skipped action. It is JSON so the model can tell a skip
+/// from a failure of the action itself.
+fn skipped_feedback(action: &ApprovalAction) -> String {
+    json!({
+        "status": "skipped",
+        "reason": "approval_required",
+        "action": action.to_json(),
+        "message": "This run is unattended and skips anything that needs approval. Do not retry this action; carry on without it and say what was left undone.",
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_answers_with_a_structured_denial() {
+        let action = ApprovalAction::Patch {
+            call_id: "call_1".to_string(),
+            files: vec![PathBuf::from("/repo/test.md")],
+        };
+        let Op::PatchApproval {
+            id,
+            decision,
+            feedback: Some(feedback),
+            ..
+        } = approval_response(OnApproval::Skip, "7".to_string(), &action)
+        else {
+            panic!("expected a patch denial with feedback");
+        };
+        assert_eq!(id, "7");
+        assert_eq!(decision, ReviewDecision::Denied);
+        let feedback: Value = serde_json::from_str(&feedback).expect("feedback is JSON");
+        assert_eq!(feedback["status"], "skipped");
+        assert_eq!(feedback["reason"], "approval_required");
+        assert_eq!(feedback["action"], action.to_json());
+    }
+
+    #[test]
+    fn fail_aborts_and_auto_deny_denies_plainly() {
+        let action = ApprovalAction::Exec {
+            call_id: "call_1".to_string(),
+            command: vec!["touch".to_string(), "a b".to_string()],
+        };
+        assert_eq!(action.describe(), "run `touch 'a b'`");
+        assert!(matches!(
+            approval_response(OnApproval::Fail, "1".to_string(), &action),
+            Op::ExecApproval {
+                decision: ReviewDecision::Abort,
+                feedback: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            approval_response(OnApproval::AutoDeny, "1".to_string(), &action),
+            Op::ExecApproval {
+                decision: ReviewDecision::Denied,
+                feedback: None,
+                ..
+            }
+        ));
+    }
+}
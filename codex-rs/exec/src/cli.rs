@@ -1,5 +1,8 @@
+use clap::Args;
 use clap::Parser;
+use clap::Subcommand;
 use clap::ValueEnum;
+use codex_common::ApprovalModeCliArg;
 use codex_common::CliConfigOverrides;
 use std::path::PathBuf;
 
@@ -22,6 +25,16 @@ pub struct Cli {
     #[arg(long = "sandbox", short = 's', value_enum)]
     pub sandbox_mode: Option<codex_common::SandboxModeCliArg>,
 
+    /// When a command or patch needs approval. Defaults to `never`: failures
+    /// go straight back to the model.
+    #[arg(long = "ask-for-approval", short = 'a', value_enum)]
+    pub approval_policy: Option<ApprovalModeCliArg>,
+
+    /// What to do when a command or patch needs approval, since nobody is
+    /// there to give it.
+    #[arg(long = "on-approval", value_enum, default_value_t = OnApproval::Fail)]
+    pub on_approval: OnApproval,
+
     /// Configuration profile from config.toml to specify default options.
     #[arg(long = "profile", short = 'p')]
     pub config_profile: Option<String>,
@@ -63,10 +76,62 @@ pub struct Cli {
     #[arg(long = "output-last-message")]
     pub last_message_file: Option<PathBuf>,
 
+    /// Path to a JSON schema the final message must match. The validated
+    /// JSON is written to `--output-last-message`, or to stdout.
+    #[arg(long = "output-schema", value_name = "FILE")]
+    pub output_schema: Option<PathBuf>,
+
     /// Initial instructions for the agent. If not provided as an argument (or
     /// if `-` is used), instructions are read from stdin.
     #[arg(value_name = "PROMPT")]
     pub prompt: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Review the changes in the working tree against a base ref without
+    /// modifying it. Exits with code 2 when there are blocking findings.
+    Review(ReviewArgs),
+
+    /// Start a session without sending a prompt and print its status as
+    /// JSON: model, provider, sandbox, instruction files, MCP servers and
+    /// more.
+    Status,
+}
+
+#[derive(Debug, Args)]
+pub struct ReviewArgs {
+    /// Git ref to review against, e.g. `main`. The diff starts at the merge
+    /// base of this ref and `HEAD`.
+    #[arg(long, value_name = "REF")]
+    pub base: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+pub enum OnApproval {
+    /// Stop the run and exit with code 9, listing the action that needed
+    /// approval.
+    #[default]
+    Fail,
+    /// Leave the action out and tell the model, in a structured tool result,
+    /// that it was skipped because it needs approval.
+    Skip,
+    /// Deny the action as a user would and let the model carry on.
+    AutoDeny,
+}
+
+impl OnApproval {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            OnApproval::Fail => "fail",
+            OnApproval::Skip => "skip",
+            OnApproval::AutoDeny => "auto-deny",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
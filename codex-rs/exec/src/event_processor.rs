@@ -1,20 +1,115 @@
 use std::path::Path;
 
 use codex_core::config::Config;
+use codex_core::protocol::ErrorKind;
 use codex_core::protocol::Event;
 
+use crate::approval::ApprovalAction;
+use crate::cli::OnApproval;
+
 pub(crate) enum CodexStatus {
     Running,
     InitiateShutdown,
     Shutdown,
 }
 
+/// How the task run by `codex exec` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TaskOutcome {
+    Completed,
+    /// The agent reported an error while working on the task, with its
+    /// category when the model provider caused it.
+    Failed(Option<ErrorKind>),
+    /// The task was interrupted (Ctrl-C) before it completed.
+    Interrupted,
+    /// A review completed and reported at least one blocking finding.
+    Blocked,
+    /// The final message still did not match `--output-schema` after the
+    /// corrective turns.
+    InvalidOutput,
+    /// A command or patch needed approval under `--on-approval fail`.
+    ApprovalRequired,
+}
+
+impl TaskOutcome {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            TaskOutcome::Completed => "completed",
+            TaskOutcome::Failed(_) => "failed",
+            TaskOutcome::Interrupted => "interrupted",
+            TaskOutcome::Blocked => "blocked",
+            TaskOutcome::InvalidOutput => "invalid_output",
+            TaskOutcome::ApprovalRequired => "approval_required",
+        }
+    }
+
+    /// Process exit code for this outcome. Interruption follows the shell
+    /// convention for SIGINT; provider errors get a code of their own so
+    /// scripts can tell them apart.
+    pub(crate) fn exit_code(self) -> i32 {
+        match self {
+            TaskOutcome::Completed => 0,
+            TaskOutcome::Failed(None) => 1,
+            TaskOutcome::Failed(Some(ErrorKind::ContextWindowExceeded)) => 3,
+            TaskOutcome::Failed(Some(ErrorKind::QuotaExceeded)) => 4,
+            TaskOutcome::Failed(Some(ErrorKind::AuthExpired)) => 5,
+            TaskOutcome::Failed(Some(ErrorKind::StreamDisconnected)) => 6,
+            TaskOutcome::Failed(Some(ErrorKind::ProviderError)) => 7,
+            TaskOutcome::Interrupted => 130,
+            TaskOutcome::Blocked => 2,
+            TaskOutcome::InvalidOutput => 8,
+            TaskOutcome::ApprovalRequired => 9,
+        }
+    }
+
+    /// What to do about a failure caused by the model provider or an answer
+    /// that does not match the output schema.
+    pub(crate) fn hint(self) -> Option<&'static str> {
+        let kind = match self {
+            TaskOutcome::Failed(Some(kind)) => kind,
+            TaskOutcome::InvalidOutput => {
+                return Some(
+                    "The final message did not match --output-schema, even after asking the model to correct it. Simplify the schema or make the prompt more specific.",
+                );
+            }
+            TaskOutcome::ApprovalRequired => {
+                return Some(
+                    "Nobody can approve actions in `codex exec`. Pass --on-approval skip or auto-deny to let the model carry on without them, or relax --ask-for-approval or --sandbox.",
+                );
+            }
+            _ => return None,
+        };
+        Some(match kind {
+            ErrorKind::ContextWindowExceeded => {
+                "The task no longer fits the model's context window, even after compacting the conversation. Split it into smaller tasks or use a model with a larger context window."
+            }
+            ErrorKind::QuotaExceeded => {
+                "Your account is out of quota. Check your plan and billing details, or try again once the limit resets."
+            }
+            ErrorKind::AuthExpired => {
+                "Your credentials were rejected. Run `codex login` again or check your API key."
+            }
+            ErrorKind::StreamDisconnected => {
+                "The connection to the model provider kept dropping. Check your network and try again."
+            }
+            ErrorKind::ProviderError => "The model provider rejected the request.",
+        })
+    }
+}
+
 pub(crate) trait EventProcessor {
     /// Print summary of effective configuration and user prompt.
     fn print_config_summary(&mut self, config: &Config, prompt: &str);
 
     /// Handle a single event emitted by the agent.
     fn process_event(&mut self, event: Event) -> CodexStatus;
+
+    /// Called after an approval request was answered as `--on-approval`
+    /// says.
+    fn process_approval(&mut self, action: &ApprovalAction, on_approval: OnApproval);
+
+    /// Called once after the last event has been processed.
+    fn print_final_output(&mut self, _outcome: TaskOutcome) {}
 }
 
 pub(crate) fn handle_last_message(last_agent_message: Option<&str>, output_file: &Path) {
@@ -1,29 +1,46 @@
 use codex_common::elapsed::format_duration;
 use codex_common::elapsed::format_elapsed;
 use codex_core::config::Config;
+use codex_core::model_pricing::format_usd;
+use codex_core::plan_tool::PlanItemArg;
+use codex_core::plan_tool::StepStatus;
 use codex_core::plan_tool::UpdatePlanArgs;
 use codex_core::protocol::AgentMessageDeltaEvent;
 use codex_core::protocol::AgentMessageEvent;
 use codex_core::protocol::AgentReasoningDeltaEvent;
 use codex_core::protocol::AgentReasoningRawContentDeltaEvent;
 use codex_core::protocol::AgentReasoningRawContentEvent;
+use codex_core::protocol::AuthExpiredEvent;
 use codex_core::protocol::BackgroundEventEvent;
+use codex_core::protocol::BackgroundResponseProgressEvent;
+use codex_core::protocol::ContextWindowWarningEvent;
+use codex_core::protocol::ConversationCompactedEvent;
 use codex_core::protocol::ErrorEvent;
 use codex_core::protocol::Event;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::ExecCommandBeginEvent;
 use codex_core::protocol::ExecCommandEndEvent;
 use codex_core::protocol::FileChange;
+use codex_core::protocol::GitRepoWarningEvent;
+use codex_core::protocol::GitRepoWarningKind;
 use codex_core::protocol::McpInvocation;
+use codex_core::protocol::McpStartupWarningEvent;
 use codex_core::protocol::McpToolCallBeginEvent;
 use codex_core::protocol::McpToolCallEndEvent;
 use codex_core::protocol::PatchApplyBeginEvent;
 use codex_core::protocol::PatchApplyEndEvent;
+use codex_core::protocol::PatchMatchStrategy;
+use codex_core::protocol::ProjectDocTruncatedEvent;
+use codex_core::protocol::PullRequestEvent;
+use codex_core::protocol::ReviewFindingsEvent;
+use codex_core::protocol::ReviewSeverity;
 use codex_core::protocol::SessionConfiguredEvent;
 use codex_core::protocol::StreamErrorEvent;
 use codex_core::protocol::TaskCompleteEvent;
+use codex_core::protocol::ToolOutputBudgetExceededEvent;
 use codex_core::protocol::TurnAbortReason;
 use codex_core::protocol::TurnDiffEvent;
+use codex_core::protocol::UsageUpdateEvent;
 use codex_core::protocol::WebSearchBeginEvent;
 use codex_core::protocol::WebSearchEndEvent;
 use owo_colors::OwoColorize;
@@ -32,10 +49,14 @@ use shlex::try_join;
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 use std::time::Instant;
 
+use crate::approval::ApprovalAction;
+use crate::cli::OnApproval;
 use crate::event_processor::CodexStatus;
 use crate::event_processor::EventProcessor;
+use crate::event_processor::TaskOutcome;
 use crate::event_processor::handle_last_message;
 use codex_common::create_config_summary_entries;
 
@@ -65,6 +86,13 @@ pub(crate) struct EventProcessorWithHumanOutput {
     reasoning_started: bool,
     raw_reasoning_started: bool,
     last_message_path: Option<PathBuf>,
+    /// Costs from the latest usage update, printed with the token count of
+    /// the response it follows.
+    last_cost_usd: Option<f64>,
+    total_cost_usd: Option<f64>,
+    /// Actions that needed approval, for the final summary.
+    approvals: Vec<String>,
+    on_approval: Option<OnApproval>,
 }
 
 impl EventProcessorWithHumanOutput {
@@ -93,6 +121,10 @@ impl EventProcessorWithHumanOutput {
                 reasoning_started: false,
                 raw_reasoning_started: false,
                 last_message_path,
+                last_cost_usd: None,
+                total_cost_usd: None,
+                approvals: Vec::new(),
+                on_approval: None,
             }
         } else {
             Self {
@@ -111,6 +143,10 @@ impl EventProcessorWithHumanOutput {
                 reasoning_started: false,
                 raw_reasoning_started: false,
                 last_message_path,
+                last_cost_usd: None,
+                total_cost_usd: None,
+                approvals: Vec::new(),
+                on_approval: None,
             }
         }
     }
@@ -136,6 +172,58 @@ macro_rules! ts_println {
     }};
 }
 
+impl EventProcessorWithHumanOutput {
+    /// Prints the findings as a table: severity, location and comment, with
+    /// continuation lines of a comment indented under its first line.
+    fn print_review_findings(&self, ev: &ReviewFindingsEvent) {
+        let blocking = ev.blocking_count();
+        let summary = format!(
+            "review against {}: {} finding{}, {blocking} blocking",
+            ev.base,
+            ev.findings.len(),
+            if ev.findings.len() == 1 { "" } else { "s" },
+        );
+        let summary_style = if blocking > 0 { self.red } else { self.green };
+        ts_println!(self, "{}", summary.style(summary_style));
+
+        let locations: Vec<String> = ev
+            .findings
+            .iter()
+            .map(|finding| {
+                if finding.line_start == finding.line_end {
+                    format!("{}:{}", finding.file, finding.line_start)
+                } else {
+                    format!(
+                        "{}:{}-{}",
+                        finding.file, finding.line_start, finding.line_end
+                    )
+                }
+            })
+            .collect();
+        let severity_width = "blocking".len();
+        let location_width = locations.iter().map(String::len).max().unwrap_or(0);
+        let indent = " ".repeat(severity_width + location_width + 4);
+        for (finding, location) in ev.findings.iter().zip(&locations) {
+            let severity_style = match finding.severity {
+                ReviewSeverity::Blocking => self.red,
+                ReviewSeverity::Warning => self.magenta,
+                ReviewSeverity::Nit => self.dimmed,
+            };
+            let severity = finding.severity.to_string();
+            let severity = format!("{severity:<severity_width$}");
+            let mut comment_lines = finding.comment.lines();
+            println!(
+                "{}  {location:<location_width$}  {}",
+                severity.style(severity_style),
+                comment_lines.next().unwrap_or_default(),
+            );
+            for line in comment_lines {
+                println!("{indent}{line}");
+            }
+        }
+    }
+}
+
 impl EventProcessor for EventProcessorWithHumanOutput {
     /// Print a concise summary of the effective configuration that will be used
     /// for the session. This mirrors the information shown in the TUI welcome
@@ -170,7 +258,8 @@ impl EventProcessor for EventProcessorWithHumanOutput {
     fn process_event(&mut self, event: Event) -> CodexStatus {
         let Event { id: _, msg } = event;
         match msg {
-            EventMsg::Error(ErrorEvent { message }) => {
+            EventMsg::Error(ErrorEvent { message, .. })
+            | EventMsg::AuthExpired(AuthExpiredEvent { message }) => {
                 let prefix = "ERROR:".style(self.red);
                 ts_println!(self, "{prefix} {message}");
             }
@@ -180,6 +269,14 @@ impl EventProcessor for EventProcessorWithHumanOutput {
             EventMsg::StreamError(StreamErrorEvent { message }) => {
                 ts_println!(self, "{}", message.style(self.dimmed));
             }
+            EventMsg::BackgroundResponseProgress(BackgroundResponseProgressEvent {
+                status,
+                elapsed_seconds,
+            }) => {
+                let elapsed = format_duration(Duration::from_secs(elapsed_seconds));
+                let message = format!("model response {status} for {elapsed}");
+                ts_println!(self, "{}", message.style(self.dimmed));
+            }
             EventMsg::TaskStarted(_) => {
                 // Ignore.
             }
@@ -190,7 +287,14 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                 return CodexStatus::InitiateShutdown;
             }
             EventMsg::TokenCount(token_usage) => {
-                ts_println!(self, "tokens used: {}", token_usage.blended_total());
+                let mut line = format!("tokens used: {}", token_usage.blended_total());
+                if let Some(cost) = self.last_cost_usd {
+                    line.push_str(&format!(", cost: {}", format_usd(cost)));
+                    if let Some(total) = self.total_cost_usd {
+                        line.push_str(&format!(" (session: {})", format_usd(total)));
+                    }
+                }
+                ts_println!(self, "{line}");
             }
             EventMsg::AgentMessageDelta(AgentMessageDeltaEvent { delta }) => {
                 if !self.answer_started {
@@ -292,6 +396,7 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                 aggregated_output,
                 duration,
                 exit_code,
+                terminated,
                 ..
             }) => {
                 let exec_command = self.call_id_to_command.remove(&call_id);
@@ -310,8 +415,12 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                     .take(MAX_OUTPUT_LINES_FOR_EXEC_TOOL_CALL)
                     .collect::<Vec<_>>()
                     .join("\n");
-                match exit_code {
-                    0 => {
+                match (exit_code, terminated) {
+                    (_, Some(terminated)) => {
+                        let title = format!("{call} {terminated}{duration}:");
+                        ts_println!(self, "{}", title.style(self.red));
+                    }
+                    (0, None) => {
                         let title = format!("{call} succeeded{duration}:");
                         ts_println!(self, "{}", title.style(self.green));
                     }
@@ -404,13 +513,16 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                                 println!("{}", line.style(self.green));
                             }
                         }
-                        FileChange::Delete => {
+                        FileChange::Delete { content } => {
                             let header = format!(
                                 "{} {}",
                                 format_file_change(change),
                                 path.to_string_lossy()
                             );
                             println!("{}", header.style(self.magenta));
+                            for line in content.lines() {
+                                println!("{}", line.style(self.red));
+                            }
                         }
                         FileChange::Update {
                             unified_diff,
@@ -451,7 +563,7 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                 stdout,
                 stderr,
                 success,
-                ..
+                match_strategies,
             }) => {
                 let patch_begin = self.call_id_to_patch.remove(&call_id);
 
@@ -480,11 +592,24 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                 for line in output.lines() {
                     println!("{}", line.style(self.dimmed));
                 }
+                let mut fuzzy_paths: Vec<_> = match_strategies
+                    .into_iter()
+                    .filter(|(_, strategy)| *strategy == PatchMatchStrategy::Fuzzy)
+                    .map(|(path, _)| path)
+                    .collect();
+                fuzzy_paths.sort();
+                for path in fuzzy_paths {
+                    println!(
+                        "{}",
+                        format!("context matched fuzzily: {}", path.display()).style(self.magenta)
+                    );
+                }
             }
             EventMsg::TurnDiff(TurnDiffEvent { unified_diff }) => {
                 ts_println!(self, "{}", "turn diff:".style(self.magenta));
                 println!("{unified_diff}");
             }
+            EventMsg::ReviewFindings(ev) => self.print_review_findings(&ev),
             EventMsg::ExecApprovalRequest(_) => {
                 // Should we exit?
             }
@@ -512,6 +637,8 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                     model,
                     history_log_id: _,
                     history_entry_count: _,
+                    project_docs,
+                    rollout_path,
                 } = session_configured_event;
 
                 ts_println!(
@@ -522,35 +649,218 @@ impl EventProcessor for EventProcessorWithHumanOutput {
                 );
 
                 ts_println!(self, "model: {}", model);
+                if !project_docs.is_empty() {
+                    let project_docs: Vec<String> = project_docs
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect();
+                    ts_println!(self, "project docs: {}", project_docs.join(", "));
+                }
+                if let Some(rollout_path) = rollout_path {
+                    ts_println!(self, "rollout: {}", rollout_path.display());
+                }
                 println!();
             }
             EventMsg::PlanUpdate(plan_update_event) => {
                 let UpdatePlanArgs { explanation, plan } = plan_update_event;
-                ts_println!(self, "explanation: {explanation:?}");
-                ts_println!(self, "plan: {plan:?}");
+                ts_println!(self, "{}", "plan".style(self.magenta));
+                if let Some(explanation) = explanation {
+                    println!("{}", explanation.style(self.italic));
+                }
+                for PlanItemArg { step, status } in plan {
+                    match status {
+                        StepStatus::Completed => {
+                            println!("  {} {}", "✔".style(self.green), step.style(self.dimmed));
+                        }
+                        StepStatus::InProgress => {
+                            println!("  □ {}", step.style(self.cyan));
+                        }
+                        StepStatus::Pending => {
+                            println!("  {} {}", "□".style(self.dimmed), step.style(self.dimmed));
+                        }
+                    }
+                }
             }
             EventMsg::GetHistoryEntryResponse(_) => {
                 // Currently ignored in exec output.
             }
+            EventMsg::SearchHistoryResponse(_) => {
+                // Currently ignored in exec output.
+            }
             EventMsg::McpListToolsResponse(_) => {
                 // Currently ignored in exec output.
             }
-            EventMsg::ListCustomPromptsResponse(_) => {
+            EventMsg::StatusResponse(_) => {
+                // Printed by `codex exec status` itself.
+            }
+            EventMsg::QueuedInputUpdated(_) => {
+                // Currently ignored in exec output.
+            }
+            EventMsg::McpListResourcesResponse(_) => {
+                // Currently ignored in exec output.
+            }
+            EventMsg::McpListPromptsResponse(_) => {
                 // Currently ignored in exec output.
             }
-            EventMsg::TurnAborted(abort_reason) => match abort_reason.reason {
-                TurnAbortReason::Interrupted => {
-                    ts_println!(self, "task interrupted");
+            EventMsg::McpStartupWarning(McpStartupWarningEvent { failures }) => {
+                for (server_name, message) in failures {
+                    let prefix = "WARNING:".style(self.magenta);
+                    ts_println!(
+                        self,
+                        "{prefix} MCP server `{server_name}` failed to start: {message}"
+                    );
                 }
-                TurnAbortReason::Replaced => {
-                    ts_println!(self, "task aborted: replaced by a new task");
+            }
+            EventMsg::GitRepoWarning(GitRepoWarningEvent {
+                cwd,
+                kind,
+                confirmation_required,
+            }) => {
+                let prefix = "WARNING:".style(self.magenta);
+                let problem = match kind {
+                    GitRepoWarningKind::NotARepo => "is not a git repository",
+                    GitRepoWarningKind::Dirty => "has uncommitted changes",
+                };
+                ts_println!(self, "{prefix} {} {problem}", cwd.display());
+                if confirmation_required {
+                    ts_println!(
+                        self,
+                        "the first command that can write files will ask for approval"
+                    );
                 }
-            },
+            }
+            EventMsg::ProjectDocTruncated(ProjectDocTruncatedEvent { limit_bytes, files }) => {
+                let prefix = "WARNING:".style(self.magenta);
+                for file in files {
+                    ts_println!(
+                        self,
+                        "{prefix} {} exceeds project_doc_max_bytes ({limit_bytes}): {} of {} bytes omitted",
+                        file.path.display(),
+                        file.omitted_bytes,
+                        file.size_bytes
+                    );
+                }
+            }
+            EventMsg::PullRequest(PullRequestEvent {
+                provider,
+                title,
+                url,
+                dry_run_script,
+                ..
+            }) => {
+                let noun = provider.request_noun();
+                match url {
+                    Some(url) => {
+                        ts_println!(self, "{} {noun} {url}", "opened".style(self.green));
+                    }
+                    None => {
+                        ts_println!(
+                            self,
+                            "{} would open {noun} \"{title}\" by running:\n{}",
+                            "dry run".style(self.magenta),
+                            dry_run_script.unwrap_or_default().trim_end()
+                        );
+                    }
+                }
+            }
+            EventMsg::ListCustomPromptsResponse(_) => {
+                // Currently ignored in exec output.
+            }
+            EventMsg::ToolOutputBudgetExceeded(ToolOutputBudgetExceededEvent { budget_bytes }) => {
+                let prefix = "WARNING:".style(self.magenta);
+                ts_println!(
+                    self,
+                    "{prefix} tool output exceeded this turn's budget of {budget_bytes} bytes; further output is truncated"
+                );
+            }
+            EventMsg::TurnAborted(abort_reason) => {
+                match abort_reason.reason {
+                    TurnAbortReason::Interrupted => {
+                        ts_println!(self, "task interrupted");
+                    }
+                    TurnAbortReason::Replaced => {
+                        ts_println!(self, "task aborted: replaced by a new task");
+                    }
+                }
+                // An aborted task never sends `TaskComplete`.
+                return CodexStatus::InitiateShutdown;
+            }
+            EventMsg::ConversationCompacted(ConversationCompactedEvent {
+                summarized_items,
+                tokens_before,
+                tokens_after,
+                automatic,
+            }) => {
+                let trigger = if automatic { " (automatic)" } else { "" };
+                ts_println!(
+                    self,
+                    "{}{trigger}: {summarized_items} items, ~{tokens_before} -> ~{tokens_after} tokens",
+                    "conversation compacted".style(self.dimmed)
+                );
+            }
             EventMsg::ShutdownComplete => return CodexStatus::Shutdown,
             EventMsg::ConversationHistory(_) => {}
+            EventMsg::PromptTokenEstimate(_) | EventMsg::ContextUsage(_) => {}
+            EventMsg::ContextWindowWarning(ContextWindowWarningEvent {
+                threshold_percent,
+                percent_left,
+            }) => {
+                let prefix = "WARNING:".style(self.magenta);
+                ts_println!(
+                    self,
+                    "{prefix} context window is over {threshold_percent}% full ({percent_left}% left)"
+                );
+            }
+            EventMsg::UsageUpdate(UsageUpdateEvent {
+                last_cost_usd,
+                total_cost_usd,
+                ..
+            }) => {
+                self.last_cost_usd = last_cost_usd;
+                self.total_cost_usd = total_cost_usd;
+            }
+            EventMsg::RateLimits(_) => {}
+            EventMsg::TurnMetrics(_) => {}
+            EventMsg::PatchUndone(_) => {}
+            EventMsg::FunctionCallArgumentsDelta(_) => {}
+            EventMsg::McpToolCallProgress(_) => {}
         }
         CodexStatus::Running
     }
+
+    fn process_approval(&mut self, action: &ApprovalAction, on_approval: OnApproval) {
+        let (style, answer) = match on_approval {
+            OnApproval::Fail => (self.red, "stopping"),
+            OnApproval::Skip => (self.magenta, "skipped"),
+            OnApproval::AutoDeny => (self.magenta, "denied"),
+        };
+        let description = action.describe();
+        ts_println!(
+            self,
+            "{} {description}; {answer} (--on-approval {})",
+            "approval required:".style(style),
+            on_approval.as_str()
+        );
+        self.approvals.push(description);
+        self.on_approval = Some(on_approval);
+    }
+
+    fn print_final_output(&mut self, outcome: TaskOutcome) {
+        if let Some(on_approval) = self.on_approval {
+            let count = self.approvals.len();
+            eprintln!(
+                "{count} action{} needed approval (--on-approval {}):",
+                if count == 1 { "" } else { "s" },
+                on_approval.as_str()
+            );
+            for description in &self.approvals {
+                eprintln!("  - {description}");
+            }
+        }
+        if let Some(hint) = outcome.hint() {
+            eprintln!("{}", hint.style(self.red));
+        }
+    }
 }
 
 fn escape_command(command: &[String]) -> String {
@@ -560,7 +870,7 @@ fn escape_command(command: &[String]) -> String {
 fn format_file_change(change: &FileChange) -> &'static str {
     match change {
         FileChange::Add { .. } => "A",
-        FileChange::Delete => "D",
+        FileChange::Delete { .. } => "D",
         FileChange::Update {
             move_path: Some(_), ..
         } => "R",
@@ -574,12 +884,15 @@ fn format_mcp_invocation(invocation: &McpInvocation) -> String {
     // Build fully-qualified tool name: server.tool
     let fq_tool_name = format!("{}.{}", invocation.server, invocation.tool);
 
-    // Format arguments as compact JSON so they fit on one line.
-    let args_str = invocation
-        .arguments
-        .as_ref()
-        .map(|v: &serde_json::Value| serde_json::to_string(v).unwrap_or_else(|_| v.to_string()))
-        .unwrap_or_default();
+    // Format arguments as compact JSON so they fit on one line. Truncated
+    // arguments are already the start of their JSON.
+    let args_str = match &invocation.arguments {
+        Some(serde_json::Value::String(prefix)) if invocation.arguments_truncated => {
+            format!("{prefix}…")
+        }
+        Some(v) => serde_json::to_string(v).unwrap_or_else(|_| v.to_string()),
+        None => String::new(),
+    };
 
     if args_str.is_empty() {
         format!("{fq_tool_name}()")
@@ -5,59 +5,145 @@ use codex_core::config::Config;
 use codex_core::protocol::Event;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::TaskCompleteEvent;
+use codex_core::protocol::TurnMetrics;
+use codex_core::protocol::UsageUpdateEvent;
+use serde_json::Value;
 use serde_json::json;
 
+use crate::approval::ApprovalAction;
+use crate::cli::OnApproval;
 use crate::event_processor::CodexStatus;
 use crate::event_processor::EventProcessor;
+use crate::event_processor::TaskOutcome;
 use crate::event_processor::handle_last_message;
 use codex_common::create_config_summary_entries;
 
+/// Version of the `--json` output format. Bump it whenever a line changes in
+/// a way that is not backwards compatible. Event payloads follow the serde
+/// representation of `EventMsg`, so adding new event types or fields does not
+/// require a bump.
+pub(crate) const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Prints one JSON object per line:
+///
+/// - `{"type":"exec_started","schema_version":1,"config":{..},"prompt":".."}`
+/// - `{"type":"event","id":"..","msg":{"type":"<event>",..}}` for every event
+/// - `{"type":"review_finding","base":"..","finding":{..}}` for every finding
+///   of a `ReviewFindings` event, after the event itself
+/// - `{"type":"approval","on_approval":"..","action":{..}}` for every
+///   approval request, after the event itself
+/// - `{"type":"exec_finished","status":"..","exit_code":N,"on_approval":"..","approvals":[..],"last_agent_message":..,"turn_metrics":[..],"token_usage":{..},"cost_usd":..}`
 pub(crate) struct EventProcessorWithJsonOutput {
     last_message_path: Option<PathBuf>,
+    last_agent_message: Option<String>,
+    /// Latency of every model response of the task, in order.
+    turn_metrics: Vec<TurnMetrics>,
+    /// Session totals from the latest usage update.
+    usage: Option<UsageUpdateEvent>,
+    on_approval: OnApproval,
+    /// Actions that needed approval, in order.
+    approvals: Vec<Value>,
 }
 
 impl EventProcessorWithJsonOutput {
-    pub fn new(last_message_path: Option<PathBuf>) -> Self {
-        Self { last_message_path }
+    pub fn new(last_message_path: Option<PathBuf>, on_approval: OnApproval) -> Self {
+        Self {
+            last_message_path,
+            last_agent_message: None,
+            turn_metrics: Vec::new(),
+            usage: None,
+            on_approval,
+            approvals: Vec::new(),
+        }
     }
 }
 
+fn print_line(value: &Value) {
+    println!("{value}");
+}
+
 impl EventProcessor for EventProcessorWithJsonOutput {
     fn print_config_summary(&mut self, config: &Config, prompt: &str) {
         let entries = create_config_summary_entries(config)
             .into_iter()
             .map(|(key, value)| (key.to_string(), value))
             .collect::<HashMap<String, String>>();
-        #[expect(clippy::expect_used)]
-        let config_json =
-            serde_json::to_string(&entries).expect("Failed to serialize config summary to JSON");
-        println!("{config_json}");
-
-        let prompt_json = json!({
+        print_line(&json!({
+            "type": "exec_started",
+            "schema_version": JSON_SCHEMA_VERSION,
+            "config": entries,
             "prompt": prompt,
-        });
-        println!("{prompt_json}");
+        }));
     }
 
     fn process_event(&mut self, event: Event) -> CodexStatus {
-        match event.msg {
-            EventMsg::AgentMessageDelta(_) | EventMsg::AgentReasoningDelta(_) => {
-                // Suppress streaming events in JSON mode.
-                CodexStatus::Running
+        match serde_json::to_value(&event.msg) {
+            Ok(msg) => print_line(&json!({
+                "type": "event",
+                "id": event.id,
+                "msg": msg,
+            })),
+            Err(e) => eprintln!("Failed to serialize event {}: {e}", event.id),
+        }
+
+        if let EventMsg::ReviewFindings(ev) = &event.msg {
+            for finding in &ev.findings {
+                print_line(&json!({
+                    "type": "review_finding",
+                    "base": ev.base,
+                    "finding": finding,
+                }));
             }
+        }
+
+        match event.msg {
             EventMsg::TaskComplete(TaskCompleteEvent { last_agent_message }) => {
                 if let Some(output_file) = self.last_message_path.as_deref() {
                     handle_last_message(last_agent_message.as_deref(), output_file);
                 }
+                self.last_agent_message = last_agent_message;
                 CodexStatus::InitiateShutdown
             }
-            EventMsg::ShutdownComplete => CodexStatus::Shutdown,
-            _ => {
-                if let Ok(line) = serde_json::to_string(&event) {
-                    println!("{line}");
-                }
+            EventMsg::TurnMetrics(metrics) => {
+                self.turn_metrics.push(metrics);
+                CodexStatus::Running
+            }
+            EventMsg::UsageUpdate(usage) => {
+                self.usage = Some(usage);
                 CodexStatus::Running
             }
+            // An aborted task never sends `TaskComplete`.
+            EventMsg::TurnAborted(_) => CodexStatus::InitiateShutdown,
+            EventMsg::ShutdownComplete => CodexStatus::Shutdown,
+            _ => CodexStatus::Running,
         }
     }
+
+    fn process_approval(&mut self, action: &ApprovalAction, on_approval: OnApproval) {
+        let action = action.to_json();
+        print_line(&json!({
+            "type": "approval",
+            "on_approval": on_approval.as_str(),
+            "action": action,
+        }));
+        self.approvals.push(action);
+    }
+
+    fn print_final_output(&mut self, outcome: TaskOutcome) {
+        print_line(&json!({
+            "type": "exec_finished",
+            "status": outcome.as_str(),
+            "exit_code": outcome.exit_code(),
+            "error_kind": match outcome {
+                TaskOutcome::Failed(kind) => kind,
+                _ => None,
+            },
+            "on_approval": self.on_approval.as_str(),
+            "approvals": self.approvals,
+            "last_agent_message": self.last_agent_message,
+            "turn_metrics": self.turn_metrics,
+            "token_usage": self.usage.as_ref().map(|usage| &usage.total_token_usage),
+            "cost_usd": self.usage.as_ref().and_then(|usage| usage.total_cost_usd),
+        }));
+    }
 }
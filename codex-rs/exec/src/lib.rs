@@ -1,19 +1,30 @@
+mod approval;
 mod cli;
 mod event_processor;
 mod event_processor_with_human_output;
 mod event_processor_with_json_output;
+mod output_schema;
 
 use std::io::IsTerminal;
 use std::io::Read;
 use std::path::PathBuf;
 
+use approval::ApprovalAction;
+use approval::approval_response;
 pub use cli::Cli;
+use cli::Command;
+use cli::OnApproval;
+use cli::ReviewArgs;
 use codex_core::BUILT_IN_OSS_MODEL_PROVIDER_ID;
+use codex_core::CodexConversation;
 use codex_core::ConversationManager;
 use codex_core::NewConversation;
+use codex_core::WireApi;
 use codex_core::config::Config;
 use codex_core::config::ConfigOverrides;
+use codex_core::delta_coalescing::DeltaCoalescing;
 use codex_core::protocol::AskForApproval;
+use codex_core::protocol::ErrorKind;
 use codex_core::protocol::Event;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::InputItem;
@@ -29,9 +40,16 @@ use tracing::debug;
 use tracing::error;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
 
 use crate::event_processor::CodexStatus;
 use crate::event_processor::EventProcessor;
+use crate::event_processor::TaskOutcome;
+use crate::output_schema::MAX_OUTPUT_SCHEMA_RETRIES;
+use crate::output_schema::load_output_schema;
+use crate::output_schema::parse_final_message;
+use crate::output_schema::retry_message;
+use crate::output_schema::schema_instructions;
 
 pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> anyhow::Result<()> {
     let Cli {
@@ -45,45 +63,38 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         skip_git_repo_check,
         color,
         last_message_file,
+        output_schema: output_schema_path,
         json: json_mode,
         sandbox_mode: sandbox_mode_cli_arg,
+        approval_policy: approval_policy_cli_arg,
+        on_approval,
         prompt,
+        command,
         config_overrides,
     } = cli;
 
-    // Determine the prompt based on CLI arg and/or stdin.
-    let prompt = match prompt {
-        Some(p) if p != "-" => p,
-        // Either `-` was passed or no positional arg.
-        maybe_dash => {
-            // When no arg (None) **and** stdin is a TTY, bail out early – unless the
-            // user explicitly forced reading via `-`.
-            let force_stdin = matches!(maybe_dash.as_deref(), Some("-"));
-
-            if std::io::stdin().is_terminal() && !force_stdin {
-                eprintln!(
-                    "No prompt provided. Either specify one as an argument or pipe the prompt into stdin."
-                );
-                std::process::exit(1);
-            }
+    let status_only = matches!(command, Some(Command::Status));
+    let (prompt, review_base) = match command {
+        Some(Command::Review(ReviewArgs { base })) => {
+            (format!("review changes against {base}"), Some(base))
+        }
+        Some(Command::Status) => (String::new(), None),
+        None => (read_prompt(prompt), None),
+    };
 
-            // Ensure the user knows we are waiting on stdin, as they may
-            // have gotten into this state by mistake. If so, and they are not
-            // writing to stdin, Codex will hang indefinitely, so this should
-            // help them debug in that case.
-            if !force_stdin {
-                eprintln!("Reading prompt from stdin...");
-            }
-            let mut buffer = String::new();
-            if let Err(e) = std::io::stdin().read_to_string(&mut buffer) {
-                eprintln!("Failed to read prompt from stdin: {e}");
-                std::process::exit(1);
-            } else if buffer.trim().is_empty() {
-                eprintln!("No prompt provided via stdin.");
+    let output_schema = match output_schema_path {
+        Some(_) if review_base.is_some() => {
+            eprintln!("--output-schema cannot be used with `review`.");
+            std::process::exit(1);
+        }
+        Some(path) => match load_output_schema(&path) {
+            Ok(schema) => Some(schema),
+            Err(e) => {
+                eprintln!("{e:#}");
                 std::process::exit(1);
             }
-            buffer
-        }
+        },
+        None => None,
     };
 
     let (stdout_with_ansi, stderr_with_ansi) = match color {
@@ -95,20 +106,6 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         ),
     };
 
-    // TODO(mbolin): Take a more thoughtful approach to logging.
-    let default_level = "error";
-    let _ = tracing_subscriber::fmt()
-        // Fallback to the `default_level` log filter if the environment
-        // variable is not set _or_ contains an invalid value
-        .with_env_filter(
-            EnvFilter::try_from_default_env()
-                .or_else(|_| EnvFilter::try_new(default_level))
-                .unwrap_or_else(|_| EnvFilter::new(default_level)),
-        )
-        .with_ansi(stderr_with_ansi)
-        .with_writer(std::io::stderr)
-        .try_init();
-
     let sandbox_mode = if full_auto {
         Some(SandboxMode::WorkspaceWrite)
     } else if dangerously_bypass_approvals_and_sandbox {
@@ -139,8 +136,9 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         model,
         config_profile,
         // This CLI is intended to be headless and has no affordances for asking
-        // the user for approval.
-        approval_policy: Some(AskForApproval::Never),
+        // the user for approval. Unless `-a` asks for another policy, nothing
+        // needs approval; otherwise `--on-approval` answers the requests.
+        approval_policy: Some(approval_policy_cli_arg.map_or(AskForApproval::Never, Into::into)),
         sandbox_mode,
         cwd: cwd.map(|p| p.canonicalize().unwrap_or(p)),
         model_provider,
@@ -152,6 +150,7 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         disable_response_storage: oss.then_some(true),
         show_raw_agent_reasoning: oss.then_some(true),
         tools_web_search_request: None,
+        skip_git_repo_check: skip_git_repo_check.then_some(true),
     };
     // Parse `-c` overrides.
     let cli_kv_overrides = match config_overrides.parse_overrides() {
@@ -162,9 +161,39 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         }
     };
 
-    let config = Config::load_with_cli_overrides(cli_kv_overrides, overrides)?;
+    let mut config = Config::load_with_cli_overrides(cli_kv_overrides, overrides)?;
+    if json_mode {
+        // Consumers of the JSON output see the deltas exactly as the model
+        // sent them.
+        config.stream_coalescing = DeltaCoalescing::disabled();
+    }
+
+    // TODO(mbolin): Take a more thoughtful approach to logging.
+    let default_level = "error";
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(stderr_with_ansi)
+        .with_writer(std::io::stderr)
+        // Fallback to the `default_level` log filter if the environment
+        // variable is not set _or_ contains an invalid value
+        .with_filter(
+            EnvFilter::try_from_default_env()
+                .or_else(|_| EnvFilter::try_new(default_level))
+                .unwrap_or_else(|_| EnvFilter::new(default_level)),
+        );
+    #[cfg(feature = "otel")]
+    let (otel_layer, _otel_guard) = codex_core::otel::layer(&config.otel)?.unzip();
+    #[cfg(not(feature = "otel"))]
+    let otel_layer: Option<tracing_subscriber::layer::Identity> = None;
+    let _ = tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .try_init();
+
     let mut event_processor: Box<dyn EventProcessor> = if json_mode {
-        Box::new(EventProcessorWithJsonOutput::new(last_message_file.clone()))
+        Box::new(EventProcessorWithJsonOutput::new(
+            last_message_file.clone(),
+            on_approval,
+        ))
     } else {
         Box::new(EventProcessorWithHumanOutput::create_with_ansi(
             stdout_with_ansi,
@@ -173,23 +202,64 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         ))
     };
 
-    if oss {
-        codex_ollama::ensure_oss_ready(&config)
-            .await
-            .map_err(|e| anyhow::anyhow!("OSS setup failed: {e}"))?;
-    }
+    // exec runs unattended, so only pull when `--oss` asked for the model.
+    codex_ollama::ensure_model_ready(
+        &config,
+        |_| oss,
+        &mut codex_ollama::CliProgressReporter::new(),
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Ollama setup failed: {e}"))?;
 
     // Print the effective configuration and prompt so users can see what Codex
     // is using.
-    event_processor.print_config_summary(&config, &prompt);
+    if !status_only {
+        event_processor.print_config_summary(&config, &prompt);
+    }
 
-    if !skip_git_repo_check && !is_inside_git_repo(&config.cwd.to_path_buf()) {
-        eprintln!("Not inside a trusted directory and --skip-git-repo-check was not specified.");
+    if !status_only && !config.skip_git_repo_check && !is_inside_git_repo(&config.cwd.to_path_buf())
+    {
+        eprintln!(
+            "Not inside a git repository. Pass --skip-git-repo-check or set skip_git_repo_check = true to run here anyway."
+        );
         std::process::exit(1);
     }
 
+    // Ask for structured output where the provider supports it; otherwise
+    // spell the schema out in the prompt. Either way the final message is
+    // validated locally below.
+    let structured_output = config.model_provider.wire_api() == WireApi::Responses;
+    let turn_op = {
+        let output_schema = output_schema.clone();
+        let cwd = config.cwd.clone();
+        let approval_policy = config.approval_policy;
+        let sandbox_policy = config.sandbox_policy.clone();
+        let model = config.model.clone();
+        let summary = config.model_reasoning_summary;
+        move |text: String| match &output_schema {
+            Some(schema) if structured_output => Op::UserTurn {
+                items: vec![InputItem::Text { text }],
+                cwd: cwd.clone(),
+                approval_policy,
+                sandbox_policy: sandbox_policy.clone(),
+                model: model.clone(),
+                effort: None,
+                summary,
+                final_output_json_schema: Some(schema.clone()),
+            },
+            Some(schema) => Op::UserInput {
+                items: vec![InputItem::Text {
+                    text: format!("{text}\n\n{}", schema_instructions(schema)),
+                }],
+            },
+            None => Op::UserInput {
+                items: vec![InputItem::Text { text }],
+            },
+        }
+    };
+
     let conversation_manager = ConversationManager::new(AuthManager::shared(
-        config.codex_home.clone(),
+        config.dirs.config_dir().to_path_buf(),
         config.preferred_auth_method,
     ));
     let NewConversation {
@@ -198,21 +268,27 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         session_configured,
     } = conversation_manager.new_conversation(config).await?;
     info!("Codex initialized with event: {session_configured:?}");
+    if status_only {
+        return print_status(&conversation).await;
+    }
 
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
     {
         let conversation = conversation.clone();
         tokio::spawn(async move {
+            let mut interrupted = false;
             loop {
                 tokio::select! {
                     _ = tokio::signal::ctrl_c() => {
                         tracing::debug!("Keyboard interrupt");
-                        // Immediately notify Codex to abort any in‑flight task.
+                        if interrupted {
+                            // Second Ctrl-C: stop waiting for the task to wind down.
+                            break;
+                        }
+                        interrupted = true;
+                        // Immediately notify Codex to abort any in‑flight task. Keep
+                        // forwarding events so the resulting `TurnAborted` is reported.
                         conversation.submit(Op::Interrupt).await.ok();
-
-                        // Exit the inner loop and return to the main input prompt. The codex
-                        // will emit a `TurnInterrupted` (Error) event which is drained later.
-                        break;
                     }
                     res = conversation.next_event() => match res {
                         Ok(event) => {
@@ -260,24 +336,163 @@ pub async fn run_main(cli: Cli, codex_linux_sandbox_exe: Option<PathBuf>) -> any
         }
     }
 
-    // Send the prompt.
-    let items: Vec<InputItem> = vec![InputItem::Text { text: prompt }];
-    let initial_prompt_task_id = conversation.submit(Op::UserInput { items }).await?;
-    info!("Sent prompt with event ID: {initial_prompt_task_id}");
+    // Send the prompt, or start the review.
+    let op = match review_base {
+        Some(base) => Op::Review { base },
+        None => turn_op(prompt),
+    };
+    let mut task_id = conversation.submit(op).await?;
+    info!("Sent prompt with event ID: {task_id}");
 
     // Run the loop until the task is complete.
-    while let Some(event) = rx.recv().await {
+    let mut outcome = TaskOutcome::Completed;
+    let mut blocking_findings = false;
+    let mut shutdown_complete = false;
+    let mut schema_retries = 0;
+    let mut validated_output = None;
+    while let Some(mut event) = rx.recv().await {
+        match &event.msg {
+            EventMsg::Error(ev) if event.id == task_id => {
+                outcome = TaskOutcome::Failed(ev.kind);
+            }
+            // Nobody can log in again in the middle of a run, so give up on
+            // the turn rather than wait.
+            EventMsg::AuthExpired(_) if event.id == task_id => {
+                outcome = TaskOutcome::Failed(Some(ErrorKind::AuthExpired));
+                conversation.submit(Op::Interrupt).await?;
+            }
+            EventMsg::TurnAborted(_) if outcome == TaskOutcome::Completed => {
+                outcome = TaskOutcome::Interrupted;
+            }
+            EventMsg::ReviewFindings(ev) => blocking_findings = ev.blocking_count() > 0,
+            _ => {}
+        }
+        if let Some(schema) = &output_schema
+            && outcome == TaskOutcome::Completed
+            && let EventMsg::TaskComplete(TaskCompleteEvent { last_agent_message }) = &mut event.msg
+        {
+            match parse_final_message(last_agent_message.as_deref(), schema) {
+                Ok(value) => {
+                    let json = value.to_string();
+                    validated_output = Some(json.clone());
+                    *last_agent_message = Some(json);
+                }
+                Err(e) if schema_retries < MAX_OUTPUT_SCHEMA_RETRIES => {
+                    schema_retries += 1;
+                    eprintln!(
+                        "The final message did not match the output schema ({e}); asking for a corrected one."
+                    );
+                    task_id = conversation.submit(turn_op(retry_message(&e))).await?;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("The final message did not match the output schema: {e}");
+                    outcome = TaskOutcome::InvalidOutput;
+                    *last_agent_message = None;
+                }
+            }
+        }
+        let approval =
+            ApprovalAction::from_event(&event.msg).map(|action| (event.id.clone(), action));
         let shutdown: CodexStatus = event_processor.process_event(event);
+        if let Some((id, action)) = approval {
+            event_processor.process_approval(&action, on_approval);
+            if on_approval == OnApproval::Fail && outcome == TaskOutcome::Completed {
+                outcome = TaskOutcome::ApprovalRequired;
+            }
+            conversation
+                .submit(approval_response(on_approval, id, &action))
+                .await?;
+        }
         match shutdown {
             CodexStatus::Running => continue,
             CodexStatus::InitiateShutdown => {
                 conversation.submit(Op::Shutdown).await?;
             }
             CodexStatus::Shutdown => {
+                shutdown_complete = true;
                 break;
             }
         }
     }
+    // The event stream ended without a clean shutdown, e.g. after a second
+    // Ctrl-C.
+    if !shutdown_complete && outcome == TaskOutcome::Completed {
+        outcome = TaskOutcome::Interrupted;
+    }
+    if blocking_findings && outcome == TaskOutcome::Completed {
+        outcome = TaskOutcome::Blocked;
+    }
+
+    // Without `--output-last-message`, the validated JSON goes to stdout. With
+    // `--json` it is already part of the `exec_finished` line.
+    if let Some(json) = validated_output
+        && last_message_file.is_none()
+        && !json_mode
+    {
+        println!("{json}");
+    }
 
+    event_processor.print_final_output(outcome);
+    if outcome != TaskOutcome::Completed {
+        std::process::exit(outcome.exit_code());
+    }
     Ok(())
 }
+
+/// Prints the session's status as JSON, then shuts the session down.
+async fn print_status(conversation: &CodexConversation) -> anyhow::Result<()> {
+    let status_id = conversation.submit(Op::GetStatus).await?;
+    loop {
+        let event = conversation.next_event().await?;
+        if event.id == status_id
+            && let EventMsg::StatusResponse(status) = event.msg
+        {
+            println!("{}", serde_json::to_string_pretty(&status)?);
+            break;
+        }
+    }
+    conversation.submit(Op::Shutdown).await?;
+    while !matches!(
+        conversation.next_event().await?.msg,
+        EventMsg::ShutdownComplete
+    ) {}
+    Ok(())
+}
+
+/// Determines the prompt from the CLI arg and/or stdin.
+fn read_prompt(prompt: Option<String>) -> String {
+    match prompt {
+        Some(p) if p != "-" => p,
+        // Either `-` was passed or no positional arg.
+        maybe_dash => {
+            // When no arg (None) **and** stdin is a TTY, bail out early – unless the
+            // user explicitly forced reading via `-`.
+            let force_stdin = matches!(maybe_dash.as_deref(), Some("-"));
+
+            if std::io::stdin().is_terminal() && !force_stdin {
+                eprintln!(
+                    "No prompt provided. Either specify one as an argument or pipe the prompt into stdin."
+                );
+                std::process::exit(1);
+            }
+
+            // Ensure the user knows we are waiting on stdin, as they may
+            // have gotten into this state by mistake. If so, and they are not
+            // writing to stdin, Codex will hang indefinitely, so this should
+            // help them debug in that case.
+            if !force_stdin {
+                eprintln!("Reading prompt from stdin...");
+            }
+            let mut buffer = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut buffer) {
+                eprintln!("Failed to read prompt from stdin: {e}");
+                std::process::exit(1);
+            } else if buffer.trim().is_empty() {
+                eprintln!("No prompt provided via stdin.");
+                std::process::exit(1);
+            }
+            buffer
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Support for `codex exec --output-schema`: loading the schema, asking for
+//! structured output when the provider cannot enforce it, and validating the
+//! final message locally.
+//!
+//! Validation covers the subset of JSON Schema accepted by strict structured
+//! outputs: `type`, `enum`, `const`, `properties`, `required`,
+//! `additionalProperties`, `items`, `anyOf`/`oneOf`/`allOf`, local `$ref`s
+//! and the length/size/range bounds. Other keywords are ignored.
+
+use std::path::Path;
+
+use anyhow::Context;
+use serde_json::Value;
+
+/// How many corrective turns to run when the final message does not match
+/// the schema before giving up.
+pub(crate) const MAX_OUTPUT_SCHEMA_RETRIES: usize = 2;
+
+pub(crate) fn load_output_schema(path: &Path) -> anyhow::Result<Value> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read output schema {}", path.display()))?;
+    let schema: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("output schema {} is not valid JSON", path.display()))?;
+    if !schema.is_object() {
+        anyhow::bail!("output schema {} must be a JSON object", path.display());
+    }
+    Ok(schema)
+}
+
+/// Instructions appended to the prompt for providers without structured
+/// output support.
+pub(crate) fn schema_instructions(schema: &Value) -> String {
+    format!(
+        "Your final message must be a single JSON value, without code fences or any other text, that matches this JSON schema:\n{schema}"
+    )
+}
+
+/// Message for the corrective turn sent after validation failed with `error`.
+pub(crate) fn retry_message(error: &str) -> String {
+    format!(
+        "Your last message did not match the required output schema: {error}. Reply again with only a JSON value that matches the schema."
+    )
+}
+
+/// Parses the final message as JSON, tolerating a surrounding code fence, and
+/// validates it against `schema`.
+pub(crate) fn parse_final_message(message: Option<&str>, schema: &Value) -> Result<Value, String> {
+    let Some(message) = message else {
+        return Err("the task ended without a final message".to_string());
+    };
+    let value: Value = serde_json::from_str(strip_code_fence(message))
+        .map_err(|e| format!("the final message is not valid JSON ({e})"))?;
+    validate(&value, schema, schema, "$")?;
+    Ok(value)
+}
+
+fn strip_code_fence(message: &str) -> &str {
+    let trimmed = message.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    // Drop the info string (e.g. `json`) on the opening fence line.
+    let rest = rest.split_once('\n').map_or("", |(_, body)| body);
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+fn validate(value: &Value, schema: &Value, root: &Value, at: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{at} is not allowed")),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .ok_or_else(|| format!("unsupported $ref {reference} in the output schema"))?;
+        validate(value, target, root, at)?;
+    }
+
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all_of {
+            validate(value, sub, root, at)?;
+        }
+    }
+    if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array)
+        && !any_of
+            .iter()
+            .any(|sub| validate(value, sub, root, at).is_ok())
+    {
+        return Err(format!("{at} does not match any of the allowed schemas"));
+    }
+    if let Some(one_of) = schema.get("oneOf").and_then(Value::as_array) {
+        let matches = one_of
+            .iter()
+            .filter(|sub| validate(value, sub, root, at).is_ok())
+            .count();
+        if matches != 1 {
+            return Err(format!(
+                "{at} must match exactly one of the allowed schemas, matched {matches}"
+            ));
+        }
+    }
+
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        return Err(format!("{at} must be {expected}"));
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let allowed = allowed
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!("{at} must be one of {allowed}"));
+    }
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(ty) => vec![ty.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|ty| has_type(value, ty)) {
+            return Err(format!("{at} must be of type {}", types.join(" or ")));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        return Err(format!("{at} is missing required property `{key}`"));
+                    }
+                }
+            }
+            for (key, item) in object {
+                let item_at = format!("{at}.{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(item_schema) => validate(item, item_schema, root, &item_at)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{at} has unexpected property `{key}`"));
+                        }
+                        Some(extra) => validate(item, extra, root, &item_at)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bounds(items.len(), schema, "minItems", "maxItems", "items", at)?;
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate(item, item_schema, root, &format!("{at}[{i}]"))?;
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count();
+            check_bounds(length, schema, "minLength", "maxLength", "characters", at)?;
+        }
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                check_range(number, schema, at)?;
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn check_bounds(
+    len: usize,
+    schema: &serde_json::Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    unit: &str,
+    at: &str,
+) -> Result<(), String> {
+    if let Some(min) = schema.get(min_key).and_then(Value::as_u64)
+        && (len as u64) < min
+    {
+        return Err(format!("{at} must have at least {min} {unit}"));
+    }
+    if let Some(max) = schema.get(max_key).and_then(Value::as_u64)
+        && (len as u64) > max
+    {
+        return Err(format!("{at} must have at most {max} {unit}"));
+    }
+    Ok(())
+}
+
+fn check_range(
+    number: f64,
+    schema: &serde_json::Map<String, Value>,
+    at: &str,
+) -> Result<(), String> {
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum")
+        && number < min
+    {
+        return Err(format!("{at} must be at least {min}"));
+    }
+    if let Some(max) = bound("maximum")
+        && number > max
+    {
+        return Err(format!("{at} must be at most {max}"));
+    }
+    if let Some(min) = bound("exclusiveMinimum")
+        && number <= min
+    {
+        return Err(format!("{at} must be greater than {min}"));
+    }
+    if let Some(max) = bound("exclusiveMaximum")
+        && number >= max
+    {
+        return Err(format!("{at} must be less than {max}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "status": {"type": "string", "enum": ["ok", "failed"]},
+                "files": {"type": "array", "items": {"$ref": "#/$defs/file"}, "maxItems": 2},
+                "score": {"anyOf": [{"type": "integer", "minimum": 0}, {"type": "null"}]}
+            },
+            "required": ["status", "files", "score"],
+            "additionalProperties": false,
+            "$defs": {
+                "file": {
+                    "type": "object",
+                    "properties": {"path": {"type": "string", "minLength": 1}},
+                    "required": ["path"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn accepts_matching_message() {
+        let message = r#"{"status": "ok", "files": [{"path": "a.rs"}], "score": 3}"#;
+        assert_eq!(
+            parse_final_message(Some(message), &schema()),
+            Ok(json!({"status": "ok", "files": [{"path": "a.rs"}], "score": 3}))
+        );
+    }
+
+    #[test]
+    fn accepts_message_in_code_fence() {
+        let message = "```json\n{\"status\": \"failed\", \"files\": [], \"score\": null}\n```";
+        assert!(parse_final_message(Some(message), &schema()).is_ok());
+    }
+
+    #[test]
+    fn reports_where_validation_failed() {
+        let check = |message: &str| parse_final_message(Some(message), &schema()).unwrap_err();
+        assert_eq!(
+            check(r#"{"status": "ok", "files": []}"#),
+            "$ is missing required property `score`"
+        );
+        assert_eq!(
+            check(r#"{"status": "done", "files": [], "score": 1}"#),
+            r#"$.status must be one of "ok", "failed""#
+        );
+        assert_eq!(
+            check(r#"{"status": "ok", "files": [{"path": ""}], "score": 1}"#),
+            "$.files[0].path must have at least 1 characters"
+        );
+        assert_eq!(
+            check(r#"{"status": "ok", "files": [], "score": -1}"#),
+            "$.score does not match any of the allowed schemas"
+        );
+        assert_eq!(
+            check(r#"{"status": "ok", "files": [], "score": 1, "extra": true}"#),
+            "$ has unexpected property `extra`"
+        );
+        assert!(check("Done!").starts_with("the final message is not valid JSON"));
+        assert_eq!(
+            parse_final_message(None, &schema()).unwrap_err(),
+            "the task ended without a final message"
+        );
+    }
+}
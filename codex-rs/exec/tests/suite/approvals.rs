@@ -0,0 +1,220 @@
+#![cfg(not(target_os = "windows"))]
+#![allow(clippy::expect_used, clippy::unwrap_used)]
+
+use assert_cmd::prelude::*;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use serde_json::Value;
+use serde_json::json;
+use std::process::Command;
+use std::process::Output;
+use tempfile::TempDir;
+use tempfile::tempdir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::Request;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+struct Run {
+    output: Output,
+    lines: Vec<Value>,
+    requests: Vec<Request>,
+    cwd: TempDir,
+}
+
+impl Run {
+    fn approval_lines(&self) -> Vec<&Value> {
+        self.lines
+            .iter()
+            .filter(|line| line["type"] == "approval")
+            .collect()
+    }
+
+    fn finished(&self) -> &Value {
+        let last = self.lines.last().expect("final line");
+        assert_eq!(last["type"], "exec_finished");
+        last
+    }
+
+    /// The tool result the model got for the patch, if it was asked again.
+    fn patch_output(&self) -> Option<String> {
+        let body: Value = self.requests.get(1)?.body_json().ok()?;
+        body["input"]
+            .as_array()?
+            .iter()
+            .find(|item| item["type"] == "custom_tool_call_output")
+            .map(|item| item["output"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+/// Runs `codex exec --json -a untrusted --on-approval <on_approval>` with a
+/// model that proposes a patch, which needs approval under `untrusted`, and
+/// then finishes.
+async fn run_patch_needing_approval(on_approval: &str) -> anyhow::Result<Run> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    load_sse_fixture_with_id_from_str(
+                        include_str!("../fixtures/sse_apply_patch_add.json"),
+                        "call_patch",
+                    ),
+                    "text/event-stream",
+                ),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    let done = json!([
+        {
+            "type": "response.output_item.done",
+            "item": {
+                "type": "message",
+                "role": "assistant",
+                "content": [{"type": "output_text", "text": "could not add test.md"}]
+            }
+        },
+        {
+            "type": "response.completed",
+            "response": {"id": "__ID__", "output": []}
+        }
+    ]);
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    load_sse_fixture_with_id_from_str(&done.to_string(), "resp2"),
+                    "text/event-stream",
+                ),
+        )
+        .mount(&server)
+        .await;
+
+    let cwd = tempdir()?;
+    let output = Command::cargo_bin("codex-exec")?
+        .current_dir(cwd.path())
+        .env("CODEX_HOME", cwd.path())
+        .env("OPENAI_API_KEY", "dummy")
+        .env("OPENAI_BASE_URL", format!("{}/v1", server.uri()))
+        .arg("--skip-git-repo-check")
+        .arg("-s")
+        .arg("danger-full-access")
+        .arg("-a")
+        .arg("untrusted")
+        .arg("--on-approval")
+        .arg(on_approval)
+        .arg("--json")
+        .arg("add test.md")
+        .output()?;
+    let lines = String::from_utf8(output.stdout.clone())?
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap_or_else(|e| panic!("{e}: {line}")))
+        .collect();
+    let requests = server.received_requests().await.expect("requests");
+    Ok(Run {
+        output,
+        lines,
+        requests,
+        cwd,
+    })
+}
+
+/// `--on-approval fail` stops at the patch, exits with code 9 and names it.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn fail_stops_the_run_at_the_patch() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let run = run_patch_needing_approval("fail").await?;
+    assert_eq!(run.output.status.code(), Some(9), "{:?}", run.output);
+    assert!(!run.cwd.path().join("test.md").exists());
+    assert_eq!(run.requests.len(), 1);
+
+    let approvals = run.approval_lines();
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0]["on_approval"], "fail");
+    assert_eq!(approvals[0]["action"]["kind"], "patch");
+
+    let finished = run.finished();
+    assert_eq!(finished["status"], "approval_required");
+    assert_eq!(finished["exit_code"], 9);
+    assert_eq!(finished["on_approval"], "fail");
+    assert_eq!(finished["approvals"][0]["call_id"], "call_patch");
+
+    let stderr = String::from_utf8(run.output.stderr.clone())?;
+    assert!(
+        stderr.contains("1 action needed approval (--on-approval fail):"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("apply a patch to"), "{stderr}");
+    Ok(())
+}
+
+/// `--on-approval skip` leaves the patch out and tells the model why in a
+/// structured tool result.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn skip_returns_a_structured_denial() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let run = run_patch_needing_approval("skip").await?;
+    assert!(run.output.status.success(), "{:?}", run.output);
+    assert!(!run.cwd.path().join("test.md").exists());
+    assert_eq!(run.requests.len(), 2);
+
+    let output = run.patch_output().expect("patch output sent to the model");
+    let denial: Value = serde_json::from_str(&output)?;
+    assert_eq!(denial["status"], "skipped");
+    assert_eq!(denial["reason"], "approval_required");
+    assert_eq!(denial["action"]["kind"], "patch");
+
+    let finished = run.finished();
+    assert_eq!(finished["status"], "completed");
+    assert_eq!(finished["on_approval"], "skip");
+    assert_eq!(finished["approvals"].as_array().map(Vec::len), Some(1));
+    assert_eq!(run.approval_lines()[0]["on_approval"], "skip");
+    Ok(())
+}
+
+/// `--on-approval auto-deny` rejects the patch as a user would and the model
+/// carries on.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn auto_deny_rejects_the_patch_and_continues() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let run = run_patch_needing_approval("auto-deny").await?;
+    assert!(run.output.status.success(), "{:?}", run.output);
+    assert!(!run.cwd.path().join("test.md").exists());
+    assert_eq!(run.requests.len(), 2);
+    assert_eq!(
+        run.patch_output().as_deref(),
+        Some("patch rejected by user")
+    );
+
+    let finished = run.finished();
+    assert_eq!(finished["status"], "completed");
+    assert_eq!(finished["on_approval"], "auto-deny");
+    assert_eq!(finished["last_agent_message"], "could not add test.md");
+    assert_eq!(run.approval_lines()[0]["on_approval"], "auto-deny");
+    Ok(())
+}
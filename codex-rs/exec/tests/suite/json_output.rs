@@ -0,0 +1,103 @@
+#![cfg(not(target_os = "windows"))]
+#![allow(clippy::expect_used, clippy::unwrap_used)]
+
+use assert_cmd::prelude::*;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use serde_json::Value;
+use std::process::Command;
+use tempfile::tempdir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+const SSE_ASSISTANT_MESSAGE: &str = r#"[
+  {
+    "type": "response.output_item.done",
+    "item": {
+      "type": "message",
+      "role": "assistant",
+      "content": [{"type": "output_text", "text": "all done"}]
+    }
+  },
+  {
+    "type": "response.completed",
+    "response": {"id": "__ID__", "output": []}
+  }
+]"#;
+
+/// `--json` prints a header line, one line per event and a final summary line,
+/// all of which must parse as JSON.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn json_mode_emits_versioned_jsonl() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    load_sse_fixture_with_id_from_str(SSE_ASSISTANT_MESSAGE, "resp1"),
+                    "text/event-stream",
+                ),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let cwd = tempdir()?;
+    let output = Command::cargo_bin("codex-exec")?
+        .current_dir(cwd.path())
+        .env("CODEX_HOME", cwd.path())
+        .env("OPENAI_API_KEY", "dummy")
+        .env("OPENAI_BASE_URL", format!("{}/v1", server.uri()))
+        .arg("--skip-git-repo-check")
+        .arg("-s")
+        .arg("danger-full-access")
+        .arg("--json")
+        .arg("say hi")
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines = stdout
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap_or_else(|e| panic!("{e}: {line}")))
+        .collect::<Vec<_>>();
+
+    let first = lines.first().expect("header line");
+    assert_eq!(first["type"], "exec_started");
+    assert_eq!(first["schema_version"], 1);
+    assert_eq!(first["prompt"], "say hi");
+
+    let event_types = lines
+        .iter()
+        .filter(|line| line["type"] == "event")
+        .map(|line| line["msg"]["type"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(event_types.contains(&"task_started"), "{event_types:?}");
+    assert!(event_types.contains(&"agent_message"), "{event_types:?}");
+    assert!(event_types.contains(&"task_complete"), "{event_types:?}");
+    assert!(event_types.contains(&"turn_metrics"), "{event_types:?}");
+
+    let last = lines.last().expect("final line");
+    assert_eq!(last["type"], "exec_finished");
+    assert_eq!(last["status"], "completed");
+    assert_eq!(last["exit_code"], 0);
+    assert_eq!(last["last_agent_message"], "all done");
+    let turn_metrics = last["turn_metrics"].as_array().expect("turn_metrics array");
+    assert_eq!(turn_metrics.len(), 1, "{turn_metrics:?}");
+    assert_eq!(turn_metrics[0]["retries"], 0);
+    assert!(turn_metrics[0]["stream_duration_ms"].is_u64());
+    assert!(turn_metrics[0]["time_to_first_byte_ms"].is_u64());
+    Ok(())
+}
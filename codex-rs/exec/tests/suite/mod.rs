@@ -1,4 +1,8 @@
 // Aggregates all former standalone integration tests as modules.
 mod apply_patch;
+mod approvals;
 mod common;
+mod json_output;
+mod output_schema;
+mod review;
 mod sandbox;
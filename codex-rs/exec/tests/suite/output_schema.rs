@@ -0,0 +1,167 @@
+#![cfg(not(target_os = "windows"))]
+#![allow(clippy::expect_used, clippy::unwrap_used)]
+
+use assert_cmd::prelude::*;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use serde_json::Value;
+use serde_json::json;
+use std::process::Command;
+use tempfile::tempdir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn assistant_message_sse(text: &str, id: &str) -> String {
+    let events = json!([
+        {
+            "type": "response.output_item.done",
+            "item": {
+                "type": "message",
+                "role": "assistant",
+                "content": [{"type": "output_text", "text": text}]
+            }
+        },
+        {
+            "type": "response.completed",
+            "response": {"id": "__ID__", "output": []}
+        }
+    ]);
+    load_sse_fixture_with_id_from_str(&events.to_string(), id)
+}
+
+/// `--output-schema` sends the schema as a strict `text.format`, asks again
+/// when the final message does not match it and writes the validated JSON to
+/// `--output-last-message`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn output_schema_retries_until_the_message_matches() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    assistant_message_sse(r#"{"status": "done"}"#, "resp1"),
+                    "text/event-stream",
+                ),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    assistant_message_sse(r#"{"status": "ok"}"#, "resp2"),
+                    "text/event-stream",
+                ),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let cwd = tempdir()?;
+    let schema = json!({
+        "type": "object",
+        "properties": {"status": {"type": "string", "enum": ["ok", "failed"]}},
+        "required": ["status"],
+        "additionalProperties": false
+    });
+    let schema_path = cwd.path().join("schema.json");
+    std::fs::write(&schema_path, schema.to_string())?;
+    let last_message_path = cwd.path().join("last_message.json");
+
+    let output = Command::cargo_bin("codex-exec")?
+        .current_dir(cwd.path())
+        .env("CODEX_HOME", cwd.path())
+        .env("OPENAI_API_KEY", "dummy")
+        .env("OPENAI_BASE_URL", format!("{}/v1", server.uri()))
+        .arg("--skip-git-repo-check")
+        .arg("--output-schema")
+        .arg(&schema_path)
+        .arg("--output-last-message")
+        .arg(&last_message_path)
+        .arg("report the status")
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+
+    let written: Value = serde_json::from_str(&std::fs::read_to_string(&last_message_path)?)?;
+    assert_eq!(written, json!({"status": "ok"}));
+
+    let requests = server.received_requests().await.expect("requests");
+    assert_eq!(requests.len(), 2);
+    for request in &requests {
+        let body: Value = request.body_json()?;
+        assert_eq!(body["text"]["format"]["type"], "json_schema");
+        assert_eq!(body["text"]["format"]["strict"], true);
+        assert_eq!(body["text"]["format"]["schema"], schema);
+    }
+    let retry: Value = requests[1].body_json()?;
+    assert!(
+        retry["input"]
+            .to_string()
+            .contains("did not match the required output schema: $.status must be one of")
+    );
+    Ok(())
+}
+
+/// The run fails with its own exit code when the message never matches.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn output_schema_gives_up_after_the_retries() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    assistant_message_sse("All done!", "resp1"),
+                    "text/event-stream",
+                ),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let cwd = tempdir()?;
+    let schema_path = cwd.path().join("schema.json");
+    std::fs::write(&schema_path, r#"{"type": "object"}"#)?;
+
+    let output = Command::cargo_bin("codex-exec")?
+        .current_dir(cwd.path())
+        .env("CODEX_HOME", cwd.path())
+        .env("OPENAI_API_KEY", "dummy")
+        .env("OPENAI_BASE_URL", format!("{}/v1", server.uri()))
+        .arg("--skip-git-repo-check")
+        .arg("--json")
+        .arg("--output-schema")
+        .arg(&schema_path)
+        .arg("report the status")
+        .output()?;
+    assert_eq!(output.status.code(), Some(8), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let last: Value = serde_json::from_str(stdout.lines().last().expect("summary line"))?;
+    assert_eq!(last["type"], "exec_finished");
+    assert_eq!(last["status"], "invalid_output");
+    Ok(())
+}
@@ -0,0 +1,125 @@
+#![cfg(not(target_os = "windows"))]
+#![allow(clippy::expect_used, clippy::unwrap_used)]
+
+use std::path::Path;
+
+use assert_cmd::prelude::*;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use core_test_support::load_sse_fixture_with_id_from_str;
+use serde_json::Value;
+use serde_json::json;
+use std::process::Command;
+use tempfile::tempdir;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn git(cwd: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .args([
+            "-c",
+            "user.name=Test User",
+            "-c",
+            "user.email=test@example.com",
+        ])
+        .args(args)
+        .current_dir(cwd)
+        .status()
+        .expect("git should run");
+    assert!(status.success(), "git {args:?} failed");
+}
+
+/// `codex exec review` sends the diff against the base with the review
+/// instructions, prints each finding as a JSON line and exits with code 2
+/// when one of them is blocking.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn review_reports_blocking_findings() -> anyhow::Result<()> {
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return Ok(());
+    }
+
+    let answer = r#"One problem.
+
+```json
+{"findings": [{"file": "lib.rs", "line_start": 1, "line_end": 1, "severity": "blocking", "comment": "Panics on empty input."}]}
+```"#;
+    let sse = json!([
+        {
+            "type": "response.output_item.done",
+            "item": {
+                "type": "message",
+                "role": "assistant",
+                "content": [{"type": "output_text", "text": answer}]
+            }
+        },
+        {
+            "type": "response.completed",
+            "response": {"id": "__ID__", "output": []}
+        }
+    ]);
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(
+                    load_sse_fixture_with_id_from_str(&sse.to_string(), "resp1"),
+                    "text/event-stream",
+                ),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let home = tempdir()?;
+    let repo = tempdir()?;
+    git(repo.path(), &["init", "-q"]);
+    std::fs::write(repo.path().join("lib.rs"), "fn old() {}\n")?;
+    git(repo.path(), &["add", "."]);
+    git(repo.path(), &["commit", "-q", "-m", "initial"]);
+    std::fs::write(repo.path().join("lib.rs"), "fn new() { todo!() }\n")?;
+
+    let output = Command::cargo_bin("codex-exec")?
+        .current_dir(repo.path())
+        .env("CODEX_HOME", home.path())
+        .env("OPENAI_API_KEY", "dummy")
+        .env("OPENAI_BASE_URL", format!("{}/v1", server.uri()))
+        .arg("--json")
+        .arg("review")
+        .arg("--base")
+        .arg("HEAD")
+        .output()?;
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines = stdout
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap_or_else(|e| panic!("{e}: {line}")))
+        .collect::<Vec<_>>();
+    let finding = lines
+        .iter()
+        .find(|line| line["type"] == "review_finding")
+        .expect("review_finding line");
+    assert_eq!(finding["base"], "HEAD");
+    assert_eq!(finding["finding"]["file"], "lib.rs");
+    assert_eq!(finding["finding"]["severity"], "blocking");
+    let last = lines.last().expect("summary line");
+    assert_eq!(last["type"], "exec_finished");
+    assert_eq!(last["status"], "blocked");
+    assert_eq!(last["exit_code"], 2);
+
+    let requests = server.received_requests().await.expect("requests");
+    let body: Value = requests[0].body_json()?;
+    let instructions = body["instructions"].as_str().unwrap_or_default();
+    assert!(instructions.starts_with("You are a code reviewer."));
+    assert!(body.to_string().contains("+fn new() { todo!() }"));
+    Ok(())
+}
@@ -1,5 +1,6 @@
 #![cfg(unix)]
 use codex_core::protocol::SandboxPolicy;
+use codex_core::spawn::CODEX_SANDBOX_TMPDIR_ENV_VAR;
 use codex_core::spawn::StdioPolicy;
 use std::collections::HashMap;
 use std::future::Future;
@@ -56,6 +57,7 @@ async fn python_multiprocessing_lock_works_under_sandbox() {
     let policy = SandboxPolicy::WorkspaceWrite {
         writable_roots,
         network_access: false,
+        allowed_domains: vec![],
         exclude_tmpdir_env_var: false,
         exclude_slash_tmp: false,
     };
@@ -92,6 +94,59 @@ if __name__ == '__main__':
     assert!(status.success(), "python exited with {status:?}");
 }
 
+/// Runs `sort` on a file too large for its buffer, so that it spills to
+/// `$TMPDIR`, under the read-only policy. Codex exports
+/// `CODEX_SANDBOX_TMPDIR` when `sandbox.read_only_allow_tmp` is set.
+#[expect(clippy::expect_used)]
+async fn sort_spilling_to_tmp(allow_tmp: bool) -> std::process::Output {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let spill_dir = tmp.path().join("spill");
+    std::fs::create_dir(&spill_dir).expect("create spill dir");
+    let large_file = tmp.path().join("large_file");
+    let lines: String = (0..200_000u32)
+        .map(|i| format!("{}\n", i.wrapping_mul(2_654_435_761)))
+        .collect();
+    std::fs::write(&large_file, lines).expect("write large file");
+
+    let spill_dir = spill_dir.to_string_lossy().into_owned();
+    let mut env = HashMap::from([("TMPDIR".to_string(), spill_dir.clone())]);
+    if allow_tmp {
+        env.insert(CODEX_SANDBOX_TMPDIR_ENV_VAR.to_string(), spill_dir);
+    }
+    let child = spawn_command_under_sandbox(
+        vec![
+            "sort".to_string(),
+            "-S".to_string(),
+            "1K".to_string(),
+            "-o".to_string(),
+            "/dev/null".to_string(),
+            large_file.to_string_lossy().into_owned(),
+        ],
+        &SandboxPolicy::ReadOnly,
+        std::env::current_dir().expect("should be able to get current dir"),
+        StdioPolicy::RedirectForShellTool,
+        env,
+    )
+    .await
+    .expect("should be able to spawn sort under sandbox");
+    child
+        .wait_with_output()
+        .await
+        .expect("should wait for sort")
+}
+
+#[tokio::test]
+async fn read_only_sort_cannot_spill_to_tmp_by_default() {
+    let output = sort_spilling_to_tmp(false).await;
+    assert!(!output.status.success(), "sort succeeded: {output:?}");
+}
+
+#[tokio::test]
+async fn read_only_sort_spills_to_tmp_when_allowed() {
+    let output = sort_spilling_to_tmp(true).await;
+    assert!(output.status.success(), "sort failed: {output:?}");
+}
+
 fn unix_sock_body() {
     unsafe {
         let mut fds = [0i32; 2];
@@ -240,14 +240,105 @@ pub fn run(
     let mut raw_matches: Vec<(u32, String)> = global_heap.into_iter().map(|r| r.0).collect();
     sort_matches(&mut raw_matches);
 
-    // Transform into `FileMatch`, optionally computing indices.
+    Ok(FileSearchResults {
+        matches: to_file_matches(&pattern, raw_matches, compute_indices),
+        total_match_count,
+    })
+}
+
+/// The files under a directory, found by the same walk as [`run`] (which
+/// honours `.gitignore` and skips hidden files) and kept in memory so that
+/// searches do not walk the tree again.
+pub struct FileIndex {
+    paths: Vec<String>,
+    truncated: bool,
+}
+
+impl FileIndex {
+    /// Walks `search_directory`, keeping at most `max_files` paths relative
+    /// to it. Stops early, with what it found so far, if `cancel_flag` is set.
+    pub fn build(search_directory: &Path, max_files: usize, cancel_flag: &AtomicBool) -> Self {
+        let mut paths = Vec::new();
+        let mut truncated = false;
+        for entry in WalkBuilder::new(search_directory).build().flatten() {
+            if cancel_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                continue;
+            }
+            let Some(path) = entry
+                .path()
+                .strip_prefix(search_directory)
+                .ok()
+                .and_then(Path::to_str)
+            else {
+                continue;
+            };
+            if paths.len() == max_files {
+                truncated = true;
+                break;
+            }
+            paths.push(path.to_string());
+        }
+        Self { paths, truncated }
+    }
+
+    /// Number of indexed files.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Whether the walk found more than `max_files` files, so that searches
+    /// of the index miss some.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Same as [`run`], over the indexed paths.
+    pub fn search(
+        &self,
+        pattern_text: &str,
+        limit: NonZero<usize>,
+        compute_indices: bool,
+    ) -> FileSearchResults {
+        let pattern = create_pattern(pattern_text);
+        let mut best_list = BestMatchesList::new(
+            limit.get(),
+            pattern.clone(),
+            Matcher::new(nucleo_matcher::Config::DEFAULT),
+        );
+        for path in &self.paths {
+            best_list.insert(path);
+        }
+        let mut raw_matches: Vec<(u32, String)> =
+            best_list.binary_heap.into_iter().map(|r| r.0).collect();
+        sort_matches(&mut raw_matches);
+        FileSearchResults {
+            matches: to_file_matches(&pattern, raw_matches, compute_indices),
+            total_match_count: best_list.num_matches,
+        }
+    }
+}
+
+/// Transforms sorted `(score, path)` pairs into `FileMatch`es, optionally
+/// computing the matched indices.
+fn to_file_matches(
+    pattern: &Pattern,
+    raw_matches: Vec<(u32, String)>,
+    compute_indices: bool,
+) -> Vec<FileMatch> {
     let mut matcher = if compute_indices {
         Some(Matcher::new(nucleo_matcher::Config::DEFAULT))
     } else {
         None
     };
 
-    let matches: Vec<FileMatch> = raw_matches
+    raw_matches
         .into_iter()
         .map(|(score, path)| {
             let indices = if compute_indices {
@@ -271,12 +362,7 @@ pub fn run(
                 indices,
             }
         })
-        .collect();
-
-    Ok(FileSearchResults {
-        matches,
-        total_match_count,
-    })
+        .collect()
 }
 
 /// Sort matches in-place by descending score, then ascending path.
@@ -378,6 +464,33 @@ mod tests {
         assert_eq!(score, None);
     }
 
+    #[test]
+    fn file_index_skips_ignored_files_and_caps_its_size() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src/client")).expect("mkdir");
+        std::fs::create_dir_all(root.join("target")).expect("mkdir");
+        for file in ["src/client/mod.rs", "src/lib.rs", "target/out.rs"] {
+            std::fs::write(root.join(file), "").expect("write");
+        }
+        // Outside a git repository `.gitignore` files are not consulted.
+        std::fs::write(root.join(".ignore"), "target/\n").expect("write");
+
+        let index = FileIndex::build(root, 100, &AtomicBool::new(false));
+        assert_eq!(index.len(), 2);
+        assert!(!index.is_truncated());
+        let limit = NonZero::new(8).expect("non-zero");
+        let results = index.search("src/cl", limit, true);
+        assert_eq!(results.total_match_count, 1);
+        assert_eq!(results.matches[0].path, "src/client/mod.rs");
+        assert!(results.matches[0].indices.is_some());
+        assert_eq!(index.search("out", limit, false).total_match_count, 0);
+
+        let capped = FileIndex::build(root, 1, &AtomicBool::new(false));
+        assert_eq!(capped.len(), 1);
+        assert!(capped.is_truncated());
+    }
+
     #[test]
     fn tie_breakers_sort_by_path_when_scores_equal() {
         let mut matches = vec![
@@ -5,13 +5,19 @@ use std::path::PathBuf;
 use codex_core::error::CodexErr;
 use codex_core::error::Result;
 use codex_core::error::SandboxErr;
+use codex_core::landlock::NetworkEnforcement;
+use codex_core::landlock::network_enforcement;
 use codex_core::protocol::SandboxPolicy;
+use codex_core::spawn::CODEX_SANDBOX_TMPDIR_ENV_VAR;
+use codex_core::spawn::CODEX_SCRATCH_DIR_ENV_VAR;
 
 use landlock::ABI;
 use landlock::Access;
 use landlock::AccessFs;
+use landlock::AccessNet;
 use landlock::CompatLevel;
 use landlock::Compatible;
+use landlock::NetPort;
 use landlock::Ruleset;
 use landlock::RulesetAttr;
 use landlock::RulesetCreatedAttr;
@@ -27,20 +33,35 @@ use seccompiler::apply_filter;
 
 /// Apply sandbox policies inside this thread so only the child inherits
 /// them, not the entire CLI process.
+///
+/// `proxy_port` is the port of the network proxy when the process is already
+/// confined to a network namespace where it leads to the proxy, see
+/// [`crate::netns`]. The port is then left open for TCP connections.
 pub(crate) fn apply_sandbox_policy_to_current_thread(
     sandbox_policy: &SandboxPolicy,
     cwd: &Path,
+    proxy_port: Option<u16>,
 ) -> Result<()> {
     if !sandbox_policy.has_full_network_access() {
-        install_network_seccomp_filter_on_current_thread()?;
+        install_network_rules_on_current_thread(proxy_port)?;
     }
 
     if !sandbox_policy.has_full_disk_write_access() {
-        let writable_roots = sandbox_policy
+        let mut writable_roots: Vec<PathBuf> = sandbox_policy
             .get_writable_roots_with_cwd(cwd)
             .into_iter()
             .map(|writable_root| writable_root.root)
             .collect();
+        // The session's scratch directory, and the temp directory when a
+        // read-only policy allows it, as exported by Codex when it spawned
+        // this helper.
+        writable_roots.extend(
+            [CODEX_SCRATCH_DIR_ENV_VAR, CODEX_SANDBOX_TMPDIR_ENV_VAR]
+                .into_iter()
+                .filter_map(std::env::var_os)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
         install_filesystem_landlock_rules_on_current_thread(writable_roots)?;
     }
 
@@ -50,6 +71,47 @@ pub(crate) fn apply_sandbox_policy_to_current_thread(
     Ok(())
 }
 
+/// Restricts network access with the strongest mechanism the kernel offers:
+///
+/// - Landlock network rules (ABI >= 4) for TCP, only allowing connections to
+///   `proxy_port`, if any, plus a seccomp filter blocking UDP and raw sockets;
+/// - otherwise a seccomp filter blocking every socket but AF_UNIX ones, so
+///   the proxy is unreachable too;
+/// - nothing when seccomp filters are unavailable. Codex reports the network
+///   as enabled but not enforced in that case and asks before running network
+///   commands.
+fn install_network_rules_on_current_thread(proxy_port: Option<u16>) -> Result<()> {
+    match network_enforcement() {
+        NetworkEnforcement::Landlock => {
+            if install_landlock_network_rules_on_current_thread(proxy_port)? {
+                install_non_tcp_socket_seccomp_filter_on_current_thread()?;
+            } else {
+                install_network_seccomp_filter_on_current_thread()?;
+            }
+        }
+        NetworkEnforcement::Seccomp => install_network_seccomp_filter_on_current_thread()?,
+        NetworkEnforcement::Unavailable => {}
+    }
+    Ok(())
+}
+
+/// Denies TCP binds and connections with Landlock, except connections to
+/// `proxy_port`. Landlock only filters by port, not by address, so the
+/// caller must make sure the port leads nowhere but the proxy. Returns
+/// whether the rules are fully enforced.
+fn install_landlock_network_rules_on_current_thread(proxy_port: Option<u16>) -> Result<bool> {
+    let abi = ABI::V4;
+    let mut ruleset = Ruleset::default()
+        .set_compatibility(CompatLevel::BestEffort)
+        .handle_access(AccessNet::from_all(abi))?
+        .create()?;
+    if let Some(port) = proxy_port {
+        ruleset = ruleset.add_rule(NetPort::new(port, AccessNet::ConnectTcp))?;
+    }
+    let status = ruleset.set_no_new_privs(true).restrict_self()?;
+    Ok(status.ruleset == landlock::RulesetStatus::FullyEnforced)
+}
+
 /// Installs Landlock file-system rules on the current thread allowing read
 /// access to the entire file-system while restricting write access to
 /// `/dev/null` and the provided list of `writable_roots`.
@@ -82,6 +144,34 @@ fn install_filesystem_landlock_rules_on_current_thread(writable_roots: Vec<PathB
     Ok(())
 }
 
+/// Installs a seccomp filter that blocks creating datagram and raw sockets
+/// other than AF_UNIX ones, leaving TCP to the Landlock network rules.
+fn install_non_tcp_socket_seccomp_filter_on_current_thread() -> std::result::Result<(), SandboxErr>
+{
+    let mut socket_rules = Vec::new();
+    for sock_type in [libc::SOCK_DGRAM, libc::SOCK_RAW] {
+        socket_rules.push(SeccompRule::new(vec![
+            SeccompCondition::new(
+                0, // domain
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::Ne,
+                libc::AF_UNIX as u64,
+            )?,
+            SeccompCondition::new(
+                1, // type, without the SOCK_NONBLOCK/SOCK_CLOEXEC flags
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::MaskedEq(0xf),
+                sock_type as u64,
+            )?,
+        ])?);
+    }
+
+    let mut rules: BTreeMap<i64, Vec<SeccompRule>> = BTreeMap::new();
+    rules.insert(libc::SYS_socket, socket_rules);
+    rules.insert(libc::SYS_ptrace, vec![]);
+    apply_seccomp_rules(rules)
+}
+
 /// Installs a seccomp filter that blocks outbound network access except for
 /// AF_UNIX domain sockets.
 fn install_network_seccomp_filter_on_current_thread() -> std::result::Result<(), SandboxErr> {
@@ -124,6 +214,14 @@ fn install_network_seccomp_filter_on_current_thread() -> std::result::Result<(),
     rules.insert(libc::SYS_socket, vec![unix_only_rule.clone()]);
     rules.insert(libc::SYS_socketpair, vec![unix_only_rule]); // always deny (Unix can use socketpair but fine, keep open?)
 
+    apply_seccomp_rules(rules)
+}
+
+/// Applies `rules` to the current thread, returning EPERM for matching
+/// syscalls and allowing everything else.
+fn apply_seccomp_rules(
+    rules: BTreeMap<i64, Vec<SeccompRule>>,
+) -> std::result::Result<(), SandboxErr> {
     let filter = SeccompFilter::new(
         rules,
         SeccompAction::Allow,                     // default – allow
@@ -1,8 +1,15 @@
 use clap::Parser;
+use codex_core::landlock::NetworkEnforcement;
+use codex_core::landlock::network_enforcement;
+use codex_core::protocol::SandboxPolicy;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR;
+use codex_core::spawn::CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR;
 use std::ffi::CString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use crate::landlock::apply_sandbox_policy_to_current_thread;
+use crate::netns::isolate_network_with_proxy;
 
 #[derive(Debug, Parser)]
 pub struct LandlockCommand {
@@ -24,7 +31,10 @@ pub fn run_main() -> ! {
         command,
     } = LandlockCommand::parse();
 
-    if let Err(e) = apply_sandbox_policy_to_current_thread(&sandbox_policy, &sandbox_policy_cwd) {
+    let proxy_port = isolate_network_for_allowlist(&sandbox_policy);
+    if let Err(e) =
+        apply_sandbox_policy_to_current_thread(&sandbox_policy, &sandbox_policy_cwd, proxy_port)
+    {
         panic!("error running landlock: {e:?}");
     }
 
@@ -52,3 +62,35 @@ pub fn run_main() -> ! {
     let err = std::io::Error::last_os_error();
     panic!("Failed to execvp {}: {err}", command[0].as_str());
 }
+
+/// When the policy only allows an allowlist of domains, moves the command
+/// into a network namespace where the proxy Codex started for it, as
+/// exported in the environment, is the only thing it can reach, and returns
+/// the proxy's port. Returns `None`, leaving the network blocked entirely,
+/// when that is not possible: the namespace alone would not stop UDP, and
+/// without Landlock network rules the sandbox cannot allow just the proxy.
+fn isolate_network_for_allowlist(sandbox_policy: &SandboxPolicy) -> Option<u16> {
+    sandbox_policy.network_allowlist()?;
+    let proxy_addr = std::env::var(CODEX_SANDBOX_NETWORK_PROXY_ENV_VAR)
+        .ok()?
+        .parse::<SocketAddr>()
+        .ok()?;
+    let proxy_socket = PathBuf::from(std::env::var_os(
+        CODEX_SANDBOX_NETWORK_PROXY_SOCKET_ENV_VAR,
+    )?);
+    if network_enforcement() != NetworkEnforcement::Landlock {
+        eprintln!(
+            "codex-linux-sandbox: the kernel lacks Landlock network rules, so network access is blocked entirely"
+        );
+        return None;
+    }
+    match isolate_network_with_proxy(proxy_addr, proxy_socket) {
+        Ok(()) => Some(proxy_addr.port()),
+        Err(e) => {
+            eprintln!(
+                "codex-linux-sandbox: cannot confine the command to the network proxy ({e}), so network access is blocked entirely"
+            );
+            None
+        }
+    }
+}
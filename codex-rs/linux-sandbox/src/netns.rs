@@ -0,0 +1,158 @@
+//! Confines the command to a network namespace of its own so the network
+//! proxy is its only way out.
+//!
+//! Landlock can only allow TCP connections by port, on every address, so on
+//! its own it would let a command reach any host listening on the proxy's
+//! port. Instead the command runs in a new network namespace with nothing but
+//! a loopback interface, where a forwarder process, still holding the UNIX
+//! socket of the real proxy, listens on the proxy's address.
+
+use std::io;
+use std::net::Shutdown;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Moves this process into new user and network namespaces and forks. The
+/// child returns to go on sandboxing and exec the command; the parent stays
+/// behind forwarding connections to `proxy_addr` inside the namespace to
+/// `proxy_socket`, and exits the way the child does.
+///
+/// Must be called while the process is still single-threaded.
+pub(crate) fn isolate_network_with_proxy(
+    proxy_addr: SocketAddr,
+    proxy_socket: PathBuf,
+) -> io::Result<()> {
+    enter_network_namespace()?;
+    let listener = TcpListener::bind(proxy_addr)?;
+
+    let parent = unsafe { libc::getpid() };
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            drop(listener);
+            // Without the forwarder the command would have no network, but
+            // it should not outlive it either.
+            unsafe {
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL as libc::c_ulong);
+                if libc::getppid() != parent {
+                    libc::_exit(1);
+                }
+            }
+            Ok(())
+        }
+        child => {
+            std::thread::spawn(move || forward_connections(listener, proxy_socket));
+            match wait_for(child) {
+                Ok(status) => exit_like(status),
+                Err(e) => {
+                    eprintln!("codex-linux-sandbox: failed to wait for the command: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn enter_network_namespace() -> io::Result<()> {
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Keep the same ids inside the namespace so files the command creates
+    // are owned by the user, as they would be without it.
+    std::fs::write("/proc/self/setgroups", "deny")?;
+    std::fs::write("/proc/self/uid_map", format!("{uid} {uid} 1"))?;
+    std::fs::write("/proc/self/gid_map", format!("{gid} {gid} 1"))?;
+    bring_up_loopback()
+}
+
+/// A new network namespace starts with its loopback interface down.
+fn bring_up_loopback() -> io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let result = unsafe {
+        let mut request: libc::ifreq = std::mem::zeroed();
+        for (dst, src) in request.ifr_name.iter_mut().zip(b"lo\0") {
+            *dst = *src as libc::c_char;
+        }
+        if libc::ioctl(
+            fd,
+            libc::SIOCGIFFLAGS as _,
+            &mut request as *mut libc::ifreq,
+        ) != 0
+        {
+            Err(io::Error::last_os_error())
+        } else {
+            request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            if libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &request as *const libc::ifreq) != 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+    };
+    unsafe { libc::close(fd) };
+    result
+}
+
+fn forward_connections(listener: TcpListener, proxy_socket: PathBuf) {
+    for client in listener.incoming() {
+        let Ok(client) = client else {
+            continue;
+        };
+        let proxy_socket = proxy_socket.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = relay(client, &proxy_socket) {
+                eprintln!("codex-linux-sandbox: network proxy connection failed: {e}");
+            }
+        });
+    }
+}
+
+fn relay(client: TcpStream, proxy_socket: &Path) -> io::Result<()> {
+    let proxy = UnixStream::connect(proxy_socket)?;
+    let (mut client_reader, mut proxy_writer) = (client.try_clone()?, proxy.try_clone()?);
+    let upstream = std::thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut proxy_writer);
+        let _ = proxy_writer.shutdown(Shutdown::Write);
+    });
+    let (mut proxy_reader, mut client_writer) = (proxy, client);
+    let _ = io::copy(&mut proxy_reader, &mut client_writer);
+    let _ = client_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    Ok(())
+}
+
+fn wait_for(child: libc::pid_t) -> io::Result<libc::c_int> {
+    let mut status = 0;
+    loop {
+        if unsafe { libc::waitpid(child, &mut status, 0) } == child {
+            return Ok(status);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Exits with the child's status, re-raising the signal that killed it if
+/// any, so Codex sees the command's outcome rather than the forwarder's.
+fn exit_like(status: libc::c_int) -> ! {
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+        std::process::exit(128 + signal);
+    }
+    std::process::exit(libc::WEXITSTATUS(status));
+}
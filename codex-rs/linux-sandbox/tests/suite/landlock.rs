@@ -2,10 +2,15 @@
 use codex_core::config_types::ShellEnvironmentPolicy;
 use codex_core::error::CodexErr;
 use codex_core::error::SandboxErr;
+use codex_core::exec::ExecLimits;
 use codex_core::exec::ExecParams;
+use codex_core::exec::ExecPty;
 use codex_core::exec::SandboxType;
 use codex_core::exec::process_exec_tool_call;
 use codex_core::exec_env::create_env;
+use codex_core::landlock::NetworkEnforcement;
+use codex_core::landlock::network_enforcement;
+use codex_core::protocol::ExecTermination;
 use codex_core::protocol::SandboxPolicy;
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -42,11 +47,16 @@ async fn run_cmd(cmd: &[&str], writable_roots: &[PathBuf], timeout_ms: u64) {
         env: create_env_from_core_vars(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let sandbox_policy = SandboxPolicy::WorkspaceWrite {
         writable_roots: writable_roots.to_vec(),
         network_access: false,
+        allowed_domains: vec![],
         // Exclude tmp-related folders from writable roots because we need a
         // folder that is writable by tests but that we intentionally disallow
         // writing to in the sandbox.
@@ -121,9 +131,37 @@ async fn test_writable_root() {
 }
 
 #[tokio::test]
-#[should_panic(expected = "Sandbox(Timeout)")]
+#[expect(clippy::expect_used)]
 async fn test_timeout() {
-    run_cmd(&["sleep", "2"], &[], 50).await;
+    let params = ExecParams {
+        command: vec!["sleep".to_string(), "2".to_string()],
+        cwd: std::env::current_dir().expect("cwd should exist"),
+        timeout_ms: Some(50),
+        env: create_env_from_core_vars(),
+        with_escalated_permissions: None,
+        justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
+    };
+    let sandbox_policy = SandboxPolicy::new_read_only_policy();
+    let sandbox_program = env!("CARGO_BIN_EXE_codex-linux-sandbox");
+    let codex_linux_sandbox_exe = Some(PathBuf::from(sandbox_program));
+    let output = process_exec_tool_call(
+        params,
+        SandboxType::LinuxSeccomp,
+        &sandbox_policy,
+        &codex_linux_sandbox_exe,
+        None,
+    )
+    .await
+    .expect("timed out commands should still produce output");
+
+    assert_eq!(
+        output.terminated,
+        Some(ExecTermination::TimedOut { timeout_ms: 50 })
+    );
 }
 
 /// Helper that runs `cmd` under the Linux sandbox and asserts that the command
@@ -132,6 +170,13 @@ async fn test_timeout() {
 /// suite remains green on leaner CI images.
 #[expect(clippy::expect_used)]
 async fn assert_network_blocked(cmd: &[&str]) {
+    // Codex asks before running network commands when the kernel offers no
+    // way to block them.
+    if network_enforcement() == NetworkEnforcement::Unavailable {
+        eprintln!("skipping: this kernel cannot restrict network access");
+        return;
+    }
+
     let cwd = std::env::current_dir().expect("cwd should exist");
     let params = ExecParams {
         command: cmd.iter().map(|s| s.to_string()).collect(),
@@ -142,6 +187,10 @@ async fn assert_network_blocked(cmd: &[&str]) {
         env: create_env_from_core_vars(),
         with_escalated_permissions: None,
         justification: None,
+        limits: ExecLimits::default(),
+        use_pty: None,
+        pty: ExecPty::default(),
+        stdin: None,
     };
 
     let sandbox_policy = SandboxPolicy::new_read_only_policy();
@@ -228,3 +277,25 @@ async fn sandbox_blocks_dev_tcp_redirection() {
     // all images ship bash, so we guard against 127 as well.
     assert_network_blocked(&["bash", "-c", "echo hi > /dev/tcp/127.0.0.1/80"]).await;
 }
+
+#[tokio::test]
+async fn sandbox_blocks_tcp_to_local_listener() {
+    if network_enforcement() != NetworkEnforcement::Landlock {
+        eprintln!("skipping: Landlock network rules require ABI 4 or later");
+        return;
+    }
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert_network_blocked(&[
+        "bash",
+        "-c",
+        &format!("echo hi > /dev/tcp/127.0.0.1/{port}"),
+    ])
+    .await;
+}
+
+#[tokio::test]
+async fn sandbox_blocks_udp() {
+    // UDP is left to the seccomp filter even when Landlock is available.
+    assert_network_blocked(&["bash", "-c", "echo hi > /dev/udp/127.0.0.1/53"]).await;
+}
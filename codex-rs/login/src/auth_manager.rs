@@ -62,6 +62,11 @@ impl AuthManager {
         self.inner.read().ok().and_then(|c| c.auth.clone())
     }
 
+    /// The `auth.json` this manager loads.
+    pub fn auth_file(&self) -> PathBuf {
+        crate::get_auth_file(&self.codex_home)
+    }
+
     /// Preferred auth method used when (re)loading.
     pub fn preferred_auth_method(&self) -> AuthMode {
         self.inner
@@ -2,6 +2,7 @@ use chrono::DateTime;
 use chrono::Utc;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
 use std::fs::OpenOptions;
@@ -59,40 +60,56 @@ impl CodexAuth {
     }
 
     pub async fn refresh_token(&self) -> Result<String, std::io::Error> {
-        let token_data = self
-            .get_current_token_data()
-            .ok_or(std::io::Error::other("Token data is not available."))?;
-        let token = token_data.refresh_token;
-
-        let refresh_response = try_refresh_token(token)
-            .await
-            .map_err(std::io::Error::other)?;
+        let tokens = self.refresh_tokens().await?;
+        Ok(tokens.access_token)
+    }
 
-        let updated = update_tokens(
-            &self.auth_file,
-            refresh_response.id_token,
-            refresh_response.access_token,
-            refresh_response.refresh_token,
-        )
-        .await?;
+    /// Refreshes the tokens and saves them to `auth.json`, holding the auth
+    /// file lock throughout. If another process changed the file since it
+    /// was loaded, its tokens are used instead: a refresh token is only good
+    /// once, so the one held here may already have been spent.
+    async fn refresh_tokens(&self) -> Result<TokenData, std::io::Error> {
+        let _lock = lock_auth_file(&self.auth_file).await?;
+        let current = self.get_current_auth_json();
+        let on_disk = try_read_auth_json(&self.auth_file)?;
+        let refreshed_elsewhere = on_disk.tokens.is_some()
+            && current
+                .as_ref()
+                .is_none_or(|current| current.tokens != on_disk.tokens);
+        let updated = if refreshed_elsewhere {
+            on_disk
+        } else {
+            let refresh_token = current
+                .and_then(|auth| auth.tokens)
+                .ok_or(std::io::Error::other("Token data is not available."))?
+                .refresh_token;
+            let refresh_response =
+                tokio::time::timeout(Duration::from_secs(60), try_refresh_token(refresh_token))
+                    .await
+                    .map_err(|_| {
+                        std::io::Error::other("timed out while refreshing OpenAI API key")
+                    })??;
+            update_tokens(
+                &self.auth_file,
+                refresh_response.id_token,
+                refresh_response.access_token,
+                refresh_response.refresh_token,
+            )
+            .await?
+        };
 
+        let tokens = updated.tokens.clone().ok_or(std::io::Error::other(
+            "Token data is not available after refresh.",
+        ))?;
         if let Ok(mut auth_lock) = self.auth_dot_json.lock() {
-            *auth_lock = Some(updated.clone());
+            *auth_lock = Some(updated);
         }
-
-        let access = match updated.tokens {
-            Some(t) => t.access_token,
-            None => {
-                return Err(std::io::Error::other(
-                    "Token data is not available after refresh.",
-                ));
-            }
-        };
-        Ok(access)
+        Ok(tokens)
     }
 
     /// Loads the available auth information from the auth.json or
-    /// OPENAI_API_KEY environment variable.
+    /// OPENAI_API_KEY environment variable. Keys stored for a single model
+    /// provider are not part of it; see [`read_provider_api_key`].
     pub fn from_codex_home(
         codex_home: &Path,
         preferred_auth_method: AuthMode,
@@ -104,39 +121,12 @@ impl CodexAuth {
         let auth_dot_json: Option<AuthDotJson> = self.get_current_auth_json();
         match auth_dot_json {
             Some(AuthDotJson {
-                tokens: Some(mut tokens),
+                tokens: Some(tokens),
                 last_refresh: Some(last_refresh),
                 ..
             }) => {
                 if last_refresh < Utc::now() - chrono::Duration::days(28) {
-                    let refresh_response = tokio::time::timeout(
-                        Duration::from_secs(60),
-                        try_refresh_token(tokens.refresh_token.clone()),
-                    )
-                    .await
-                    .map_err(|_| {
-                        std::io::Error::other("timed out while refreshing OpenAI API key")
-                    })?
-                    .map_err(std::io::Error::other)?;
-
-                    let updated_auth_dot_json = update_tokens(
-                        &self.auth_file,
-                        refresh_response.id_token,
-                        refresh_response.access_token,
-                        refresh_response.refresh_token,
-                    )
-                    .await?;
-
-                    tokens = updated_auth_dot_json
-                        .tokens
-                        .clone()
-                        .ok_or(std::io::Error::other(
-                            "Token data is not available after refresh.",
-                        ))?;
-
-                    #[expect(clippy::unwrap_used)]
-                    let mut auth_lock = self.auth_dot_json.lock().unwrap();
-                    *auth_lock = Some(updated_auth_dot_json);
+                    return self.refresh_tokens().await;
                 }
 
                 Ok(tokens)
@@ -186,6 +176,7 @@ impl CodexAuth {
                 account_id: Some("account_id".to_string()),
             }),
             last_refresh: Some(Utc::now()),
+            provider_api_keys: BTreeMap::new(),
         };
 
         let auth_dot_json = Arc::new(Mutex::new(Some(auth_dot_json)));
@@ -212,10 +203,7 @@ fn load_auth(
         // If auth.json does not exist, try to read the OPENAI_API_KEY from the
         // environment variable.
         Err(e) if e.kind() == std::io::ErrorKind::NotFound && include_env_var => {
-            return match read_openai_api_key_from_env() {
-                Some(api_key) => Ok(Some(CodexAuth::from_api_key(&api_key))),
-                None => Ok(None),
-            };
+            return Ok(read_openai_api_key_from_env().map(|key| CodexAuth::from_api_key(&key)));
         }
         // Though if auth.json exists but is malformed, do not fall back to the
         // env var because the user may be expecting to use AuthMode::ChatGPT.
@@ -228,8 +216,18 @@ fn load_auth(
         openai_api_key: auth_json_api_key,
         tokens,
         last_refresh,
+        provider_api_keys,
     } = auth_dot_json;
 
+    // A file that only holds the keys of other model providers says nothing
+    // about the OpenAI login.
+    if auth_json_api_key.is_none() && tokens.is_none() {
+        return Ok(include_env_var
+            .then(read_openai_api_key_from_env)
+            .flatten()
+            .map(|key| CodexAuth::from_api_key(&key)));
+    }
+
     // If the auth.json has an API key AND does not appear to be on a plan that
     // should prefer AuthMode::ChatGPT, use AuthMode::ApiKey.
     if let Some(api_key) = &auth_json_api_key {
@@ -264,6 +262,7 @@ fn load_auth(
             openai_api_key: None,
             tokens,
             last_refresh,
+            provider_api_keys,
         }))),
     }))
 }
@@ -289,13 +288,88 @@ pub fn logout(codex_home: &Path) -> std::io::Result<bool> {
     }
 }
 
+/// Replaces the login in `auth.json` with `api_key`, keeping the keys stored
+/// for other model providers.
 pub fn login_with_api_key(codex_home: &Path, api_key: &str) -> std::io::Result<()> {
+    let auth_file = get_auth_file(codex_home);
+    let _lock = lock_auth_file_blocking(&auth_file)?;
+    let provider_api_keys = try_read_auth_json(&auth_file)
+        .map(|auth| auth.provider_api_keys)
+        .unwrap_or_default();
     let auth_dot_json = AuthDotJson {
         openai_api_key: Some(api_key.to_string()),
         tokens: None,
         last_refresh: None,
+        provider_api_keys,
     };
-    write_auth_json(&get_auth_file(codex_home), &auth_dot_json)
+    write_auth_json(&auth_file, &auth_dot_json)
+}
+
+/// Stores `api_key` in `auth.json` for the model provider `provider_id` (a key
+/// of `model_providers`), next to the login and the keys of other providers.
+pub fn login_with_provider_api_key(
+    codex_home: &Path,
+    provider_id: &str,
+    api_key: &str,
+) -> std::io::Result<()> {
+    let auth_file = get_auth_file(codex_home);
+    let _lock = lock_auth_file_blocking(&auth_file)?;
+    let mut auth_dot_json = match try_read_auth_json(&auth_file) {
+        Ok(auth) => auth,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => AuthDotJson {
+            openai_api_key: None,
+            tokens: None,
+            last_refresh: None,
+            provider_api_keys: BTreeMap::new(),
+        },
+        Err(err) => return Err(err),
+    };
+    auth_dot_json
+        .provider_api_keys
+        .insert(provider_id.to_string(), api_key.to_string());
+    write_auth_json(&auth_file, &auth_dot_json)
+}
+
+/// Removes the key stored for the model provider `provider_id`, and
+/// `auth.json` with it when nothing else is left in it. Returns `Ok(false)`
+/// if no key was stored for the provider.
+pub fn logout_provider(codex_home: &Path, provider_id: &str) -> std::io::Result<bool> {
+    let auth_file = get_auth_file(codex_home);
+    let _lock = lock_auth_file_blocking(&auth_file)?;
+    let mut auth_dot_json = match try_read_auth_json(&auth_file) {
+        Ok(auth) => auth,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    if auth_dot_json
+        .provider_api_keys
+        .remove(provider_id)
+        .is_none()
+    {
+        return Ok(false);
+    }
+    if auth_dot_json.openai_api_key.is_none()
+        && auth_dot_json.tokens.is_none()
+        && auth_dot_json.provider_api_keys.is_empty()
+    {
+        remove_file(&auth_file)?;
+    } else {
+        write_auth_json(&auth_file, &auth_dot_json)?;
+    }
+    Ok(true)
+}
+
+/// The key stored for the model provider `provider_id` with
+/// [`login_with_provider_api_key`], if any.
+pub fn read_provider_api_key(
+    codex_home: &Path,
+    provider_id: &str,
+) -> std::io::Result<Option<String>> {
+    match try_read_auth_json(&get_auth_file(codex_home)) {
+        Ok(mut auth) => Ok(auth.provider_api_keys.remove(provider_id)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
 }
 
 /// Attempt to read and refresh the `auth.json` file in the given `CODEX_HOME` directory.
@@ -309,18 +383,45 @@ pub fn try_read_auth_json(auth_file: &Path) -> std::io::Result<AuthDotJson> {
     Ok(auth_dot_json)
 }
 
+/// Replaces `auth_file` in one step, so that other processes never read a
+/// partially written file. The new file is only readable by its owner.
 fn write_auth_json(auth_file: &Path, auth_dot_json: &AuthDotJson) -> std::io::Result<()> {
     let json_data = serde_json::to_string_pretty(auth_dot_json)?;
+    let dir = match auth_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // Temporary files are created with mode 0o600 on Unix.
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(json_data.as_bytes())?;
+    file.as_file().sync_all()?;
+    file.persist(auth_file).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Takes an exclusive lock on `auth.json.lock` next to `auth_file`, released
+/// when the returned file is dropped. Codex processes hold it while they
+/// read, update and write back `auth.json`, so that they do not overwrite
+/// each other's changes.
+async fn lock_auth_file(auth_file: &Path) -> std::io::Result<File> {
+    let auth_file = auth_file.to_path_buf();
+    tokio::task::spawn_blocking(move || lock_auth_file_blocking(&auth_file))
+        .await
+        .map_err(|e| std::io::Error::other(format!("lock task failed: {e}")))?
+}
+
+/// Like [`lock_auth_file`], blocking the current thread until the lock is
+/// taken.
+pub(crate) fn lock_auth_file_blocking(auth_file: &Path) -> std::io::Result<File> {
     let mut options = OpenOptions::new();
-    options.truncate(true).write(true).create(true);
+    options.create(true).truncate(false).write(true);
     #[cfg(unix)]
     {
         options.mode(0o600);
     }
-    let mut file = options.open(auth_file)?;
-    file.write_all(json_data.as_bytes())?;
-    file.flush()?;
-    Ok(())
+    let file = options.open(auth_file.with_extension("json.lock"))?;
+    file.lock()?;
+    Ok(file)
 }
 
 async fn update_tokens(
@@ -401,6 +502,10 @@ pub struct AuthDotJson {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_refresh: Option<DateTime<Utc>>,
+
+    /// API keys stored for single model providers, by provider id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_api_keys: BTreeMap<String, String>,
 }
 
 #[cfg(test)]
@@ -505,6 +610,7 @@ mod tests {
                         .unwrap()
                         .with_timezone(&Utc)
                 ),
+                provider_api_keys: BTreeMap::new(),
             },
             auth_dot_json
         )
@@ -556,6 +662,7 @@ mod tests {
                         .unwrap()
                         .with_timezone(&Utc)
                 ),
+                provider_api_keys: BTreeMap::new(),
             },
             auth_dot_json
         )
@@ -590,6 +697,52 @@ mod tests {
         assert!(guard.is_none(), "auth_dot_json should be None");
     }
 
+    #[tokio::test]
+    async fn refresh_uses_tokens_refreshed_by_another_process() {
+        let codex_home = tempdir().unwrap();
+        write_auth_file(
+            AuthFileParams {
+                openai_api_key: None,
+                chatgpt_plan_type: "pro".to_string(),
+            },
+            codex_home.path(),
+        )
+        .expect("failed to write auth file");
+        let auth = load_auth(codex_home.path(), false, AuthMode::ChatGPT)
+            .unwrap()
+            .unwrap();
+
+        // Another process refreshes the tokens, spending the refresh token.
+        let auth_file = get_auth_file(codex_home.path());
+        let mut refreshed = try_read_auth_json(&auth_file).unwrap();
+        if let Some(tokens) = refreshed.tokens.as_mut() {
+            tokens.access_token = "new-access-token".to_string();
+            tokens.refresh_token = "new-refresh-token".to_string();
+        }
+        refreshed.last_refresh = Some(Utc::now());
+        write_auth_json(&auth_file, &refreshed).unwrap();
+
+        assert_eq!(auth.refresh_token().await.unwrap(), "new-access-token");
+        assert_eq!(
+            auth.get_current_token_data().map(|t| t.refresh_token),
+            Some("new-refresh-token".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn auth_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        login_with_api_key(dir.path(), "sk-test-key").unwrap();
+        let mode = std::fs::metadata(get_auth_file(dir.path()))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
     struct AuthFileParams {
         openai_api_key: Option<String>,
         chatgpt_plan_type: String,
@@ -680,6 +833,74 @@ mod tests {
         assert!(auth.get_token_data().await.is_err());
     }
 
+    #[test]
+    fn provider_api_keys_are_stored_next_to_the_login() {
+        let dir = tempdir().unwrap();
+        login_with_provider_api_key(dir.path(), "anthropic", "sk-ant-key").unwrap();
+        login_with_provider_api_key(dir.path(), "mistral", "mistral-key").unwrap();
+
+        // Provider keys alone are not an OpenAI login.
+        assert_eq!(
+            load_auth(dir.path(), false, AuthMode::ApiKey).unwrap(),
+            None
+        );
+
+        login_with_api_key(dir.path(), "sk-test-key").unwrap();
+        let auth = load_auth(dir.path(), false, AuthMode::ApiKey)
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.api_key.as_deref(), Some("sk-test-key"));
+        assert_eq!(
+            read_provider_api_key(dir.path(), "anthropic").unwrap(),
+            Some("sk-ant-key".to_string())
+        );
+        assert_eq!(read_provider_api_key(dir.path(), "openai").unwrap(), None);
+    }
+
+    #[test]
+    fn logout_provider_removes_only_that_key() {
+        let dir = tempdir().unwrap();
+        let auth_file = get_auth_file(dir.path());
+        assert!(!logout_provider(dir.path(), "anthropic").unwrap());
+
+        login_with_provider_api_key(dir.path(), "anthropic", "sk-ant-key").unwrap();
+        login_with_provider_api_key(dir.path(), "mistral", "mistral-key").unwrap();
+        assert!(logout_provider(dir.path(), "anthropic").unwrap());
+        assert!(!logout_provider(dir.path(), "anthropic").unwrap());
+        assert_eq!(
+            read_provider_api_key(dir.path(), "anthropic").unwrap(),
+            None
+        );
+        assert_eq!(
+            read_provider_api_key(dir.path(), "mistral").unwrap(),
+            Some("mistral-key".to_string())
+        );
+
+        // Removing the last key removes the file.
+        assert!(logout_provider(dir.path(), "mistral").unwrap());
+        assert!(!auth_file.exists());
+    }
+
+    #[test]
+    fn concurrent_provider_logins_keep_every_key() {
+        let dir = tempdir().unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let dir = dir.path();
+                scope.spawn(move || {
+                    login_with_provider_api_key(dir, &format!("provider-{i}"), &format!("key-{i}"))
+                        .unwrap();
+                });
+            }
+        });
+        for i in 0..8 {
+            assert_eq!(
+                read_provider_api_key(dir.path(), &format!("provider-{i}")).unwrap(),
+                Some(format!("key-{i}"))
+            );
+        }
+    }
+
     #[test]
     fn logout_removes_auth_file() -> Result<(), std::io::Error> {
         let dir = tempdir()?;
@@ -370,6 +370,7 @@ async fn persist_tokens_async(
             std::fs::create_dir_all(parent).map_err(io::Error::other)?;
         }
 
+        let _lock = super::lock_auth_file_blocking(&auth_file)?;
         let mut auth = read_or_default(&auth_file);
         if let Some(key) = api_key {
             auth.openai_api_key = Some(key);
@@ -405,6 +406,7 @@ fn read_or_default(path: &Path) -> AuthDotJson {
             openai_api_key: None,
             tokens: None,
             last_refresh: None,
+            provider_api_keys: Default::default(),
         },
     }
 }
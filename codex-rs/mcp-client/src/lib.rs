@@ -1,3 +1,10 @@
 mod mcp_client;
+mod oauth;
+mod streamable_http;
 
 pub use mcp_client::McpClient;
+pub use oauth::OAuthCredentialStore;
+pub use oauth::OAuthLoginOptions;
+pub use oauth::OAuthSession;
+pub use oauth::StoredOAuthCredentials;
+pub use oauth::perform_oauth_login;
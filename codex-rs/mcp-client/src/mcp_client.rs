@@ -2,7 +2,8 @@
 //!
 //! The client is intentionally lightweight – it is only capable of:
 //!   1. Spawning a subprocess that launches a conforming MCP server that
-//!      communicates over stdio.
+//!      communicates over stdio, or connecting to a remote server over the
+//!      Streamable HTTP transport.
 //!   2. Sending MCP requests and pairing them with their corresponding
 //!      responses.
 //!   3. Offering a convenience helper for the common `tools/list` request.
@@ -14,6 +15,7 @@
 use std::collections::HashMap;
 use std::ffi::OsString;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
@@ -23,6 +25,10 @@ use anyhow::Result;
 use anyhow::anyhow;
 use mcp_types::CallToolRequest;
 use mcp_types::CallToolRequestParams;
+use mcp_types::CallToolResult;
+use mcp_types::GetPromptRequest;
+use mcp_types::GetPromptRequestParams;
+use mcp_types::GetPromptResult;
 use mcp_types::InitializeRequest;
 use mcp_types::InitializeRequestParams;
 use mcp_types::InitializedNotification;
@@ -31,12 +37,26 @@ use mcp_types::JSONRPCMessage;
 use mcp_types::JSONRPCNotification;
 use mcp_types::JSONRPCRequest;
 use mcp_types::JSONRPCResponse;
+use mcp_types::ListPromptsRequest;
+use mcp_types::ListPromptsRequestParams;
+use mcp_types::ListPromptsResult;
+use mcp_types::ListResourcesRequest;
+use mcp_types::ListResourcesRequestParams;
+use mcp_types::ListResourcesResult;
 use mcp_types::ListToolsRequest;
 use mcp_types::ListToolsRequestParams;
 use mcp_types::ListToolsResult;
 use mcp_types::ModelContextProtocolNotification;
 use mcp_types::ModelContextProtocolRequest;
+use mcp_types::ProgressNotification;
+use mcp_types::ProgressNotificationParams;
+use mcp_types::ProgressToken;
+use mcp_types::ReadResourceRequest;
+use mcp_types::ReadResourceRequestParams;
+use mcp_types::ReadResourceResult;
 use mcp_types::RequestId;
+use mcp_types::ToolListChangedNotification;
+use serde::Deserialize;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use tokio::io::AsyncBufReadExt;
@@ -52,6 +72,9 @@ use tracing::error;
 use tracing::info;
 use tracing::warn;
 
+use crate::oauth::OAuthSession;
+use crate::streamable_http::StreamableHttpTransport;
+
 /// Capacity of the bounded channels used for transporting messages between the
 /// client API and the IO tasks.
 const CHANNEL_CAPACITY: usize = 128;
@@ -59,13 +82,18 @@ const CHANNEL_CAPACITY: usize = 128;
 /// Internal representation of a pending request sender.
 type PendingSender = oneshot::Sender<JSONRPCMessage>;
 
+/// Map of `progressToken -> sender` for requests that asked the server for
+/// `notifications/progress`.
+type ProgressListeners =
+    Arc<Mutex<HashMap<ProgressToken, mpsc::UnboundedSender<ProgressNotificationParams>>>>;
+
 /// A running MCP client instance.
 pub struct McpClient {
     /// Retain this child process until the client is dropped. The Tokio runtime
     /// will make a "best effort" to reap the process after it exits, but it is
     /// not a guarantee. See the `kill_on_drop` documentation for details.
-    #[allow(dead_code)]
-    child: tokio::process::Child,
+    /// `None` for servers reached over HTTP.
+    child: Option<tokio::process::Child>,
 
     /// Channel for sending JSON-RPC messages *to* the background writer task.
     outgoing_tx: mpsc::Sender<JSONRPCMessage>,
@@ -76,6 +104,12 @@ pub struct McpClient {
 
     /// Monotonically increasing counter used to generate request IDs.
     id_counter: AtomicI64,
+
+    /// Set when the server sends `notifications/tools/list_changed`.
+    tools_list_changed: Arc<AtomicBool>,
+
+    /// Where to forward progress notifications for in-flight requests.
+    progress: ProgressListeners,
 }
 
 impl McpClient {
@@ -86,11 +120,21 @@ impl McpClient {
         program: OsString,
         args: Vec<OsString>,
         env: Option<HashMap<String, String>>,
+    ) -> std::io::Result<Self> {
+        Self::new_stdio_client_with_env(program, args, create_env_for_mcp_server(env)).await
+    }
+
+    /// Like [`new_stdio_client`](Self::new_stdio_client), but the server sees
+    /// exactly `env` and nothing else from this process's environment.
+    pub async fn new_stdio_client_with_env(
+        program: OsString,
+        args: Vec<OsString>,
+        env: HashMap<String, String>,
     ) -> std::io::Result<Self> {
         let mut child = Command::new(program)
             .args(args)
             .env_clear()
-            .envs(create_env_for_mcp_server(env))
+            .envs(env)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::null())
@@ -111,6 +155,8 @@ impl McpClient {
 
         let (outgoing_tx, mut outgoing_rx) = mpsc::channel::<JSONRPCMessage>(CHANNEL_CAPACITY);
         let pending: Arc<Mutex<HashMap<i64, PendingSender>>> = Arc::new(Mutex::new(HashMap::new()));
+        let tools_list_changed = Arc::new(AtomicBool::new(false));
+        let progress: ProgressListeners = Arc::new(Mutex::new(HashMap::new()));
 
         // Spawn writer task. It listens on the `outgoing_rx` channel and
         // writes messages to the child's STDIN.
@@ -141,26 +187,17 @@ impl McpClient {
         // STDOUT and dispatches responses to the pending map.
         let reader_handle = {
             let pending = pending.clone();
+            let tools_list_changed = tools_list_changed.clone();
+            let progress = progress.clone();
             let mut lines = BufReader::new(stdout).lines();
 
             tokio::spawn(async move {
                 while let Ok(Some(line)) = lines.next_line().await {
                     debug!("MCP message from server: {line}");
                     match serde_json::from_str::<JSONRPCMessage>(&line) {
-                        Ok(JSONRPCMessage::Response(resp)) => {
-                            Self::dispatch_response(resp, &pending).await;
-                        }
-                        Ok(JSONRPCMessage::Error(err)) => {
-                            Self::dispatch_error(err, &pending).await;
-                        }
-                        Ok(JSONRPCMessage::Notification(JSONRPCNotification { .. })) => {
-                            // For now we only log server-initiated notifications.
-                            info!("<- notification: {}", line);
-                        }
-                        Ok(other) => {
-                            // Batch responses and requests are currently not
-                            // expected from the server – log and ignore.
-                            info!("<- unhandled message: {:?}", other);
+                        Ok(msg) => {
+                            Self::dispatch_message(msg, &pending, &tools_list_changed, &progress)
+                                .await
                         }
                         Err(e) => {
                             error!("failed to deserialize JSONRPCMessage: {e}; line = {}", line)
@@ -177,13 +214,55 @@ impl McpClient {
         let _ = (writer_handle, reader_handle);
 
         Ok(Self {
-            child,
+            child: Some(child),
             outgoing_tx,
             pending,
             id_counter: AtomicI64::new(1),
+            tools_list_changed,
+            progress,
         })
     }
 
+    /// Connect to an MCP server that speaks the Streamable HTTP transport at
+    /// `url`. With `oauth`, every request carries its access token, which is
+    /// refreshed when it expires. As for stdio clients, the caller is
+    /// responsible for sending the `initialize` request.
+    pub fn new_streamable_http_client(url: String, oauth: Option<OAuthSession>) -> Self {
+        let (outgoing_tx, outgoing_rx) = mpsc::channel::<JSONRPCMessage>(CHANNEL_CAPACITY);
+        let (incoming_tx, mut incoming_rx) = mpsc::channel::<JSONRPCMessage>(CHANNEL_CAPACITY);
+        let pending: Arc<Mutex<HashMap<i64, PendingSender>>> = Arc::new(Mutex::new(HashMap::new()));
+        let tools_list_changed = Arc::new(AtomicBool::new(false));
+        let progress: ProgressListeners = Arc::new(Mutex::new(HashMap::new()));
+
+        // The transport task lives until `outgoing_tx` is dropped with the
+        // client. The reader task ends once the transport is gone.
+        let transport = Arc::new(StreamableHttpTransport::new(
+            url,
+            oauth.map(Arc::new),
+            incoming_tx,
+        ));
+        tokio::spawn(transport.run(outgoing_rx));
+        {
+            let pending = pending.clone();
+            let tools_list_changed = tools_list_changed.clone();
+            let progress = progress.clone();
+            tokio::spawn(async move {
+                while let Some(msg) = incoming_rx.recv().await {
+                    Self::dispatch_message(msg, &pending, &tools_list_changed, &progress).await;
+                }
+            });
+        }
+
+        Self {
+            child: None,
+            outgoing_tx,
+            pending,
+            id_counter: AtomicI64::new(1),
+            tools_list_changed,
+            progress,
+        }
+    }
+
     /// Send an arbitrary MCP request and await the typed result.
     ///
     /// If `timeout` is `None` the call waits indefinitely. If `Some(duration)`
@@ -347,6 +426,139 @@ impl McpClient {
         self.send_request::<CallToolRequest>(params, timeout).await
     }
 
+    /// Like [`call_tool`](Self::call_tool), but asks the server to report
+    /// progress and forwards each `notifications/progress` it sends for this
+    /// call to `progress` until the call returns.
+    pub async fn call_tool_with_progress(
+        &self,
+        name: String,
+        arguments: Option<serde_json::Value>,
+        timeout: Option<Duration>,
+        progress: mpsc::UnboundedSender<ProgressNotificationParams>,
+    ) -> Result<CallToolResult> {
+        let progress_token = ProgressToken::Integer(self.id_counter.fetch_add(1, Ordering::SeqCst));
+        self.progress
+            .lock()
+            .await
+            .insert(progress_token.clone(), progress);
+
+        let params = CallToolWithProgressParams {
+            params: CallToolRequestParams { name, arguments },
+            meta: ProgressMeta {
+                progress_token: progress_token.clone(),
+            },
+        };
+        debug!("MCP tool call: {:?}", params.params);
+        let result = self
+            .send_request::<CallToolWithProgressRequest>(params, timeout)
+            .await;
+        self.progress.lock().await.remove(&progress_token);
+        result
+    }
+
+    /// Convenience wrapper around `resources/list`.
+    pub async fn list_resources(
+        &self,
+        params: Option<ListResourcesRequestParams>,
+        timeout: Option<Duration>,
+    ) -> Result<ListResourcesResult> {
+        self.send_request::<ListResourcesRequest>(params, timeout)
+            .await
+    }
+
+    /// Convenience wrapper around `resources/read`.
+    pub async fn read_resource(
+        &self,
+        uri: String,
+        timeout: Option<Duration>,
+    ) -> Result<ReadResourceResult> {
+        let params = ReadResourceRequestParams { uri };
+        self.send_request::<ReadResourceRequest>(params, timeout)
+            .await
+    }
+
+    /// Convenience wrapper around `prompts/list`.
+    pub async fn list_prompts(
+        &self,
+        params: Option<ListPromptsRequestParams>,
+        timeout: Option<Duration>,
+    ) -> Result<ListPromptsResult> {
+        self.send_request::<ListPromptsRequest>(params, timeout)
+            .await
+    }
+
+    /// Convenience wrapper around `prompts/get`.
+    pub async fn get_prompt(
+        &self,
+        name: String,
+        arguments: Option<serde_json::Value>,
+        timeout: Option<Duration>,
+    ) -> Result<GetPromptResult> {
+        let params = GetPromptRequestParams { arguments, name };
+        self.send_request::<GetPromptRequest>(params, timeout).await
+    }
+
+    /// Whether the server said its tools changed since the last call.
+    /// Servers only send this notification when they declared the
+    /// `tools.listChanged` capability.
+    pub fn take_tools_list_changed(&self) -> bool {
+        self.tools_list_changed.swap(false, Ordering::SeqCst)
+    }
+
+    /// Internal helper: route a message received from the server.
+    async fn dispatch_message(
+        msg: JSONRPCMessage,
+        pending: &Arc<Mutex<HashMap<i64, PendingSender>>>,
+        tools_list_changed: &AtomicBool,
+        progress: &ProgressListeners,
+    ) {
+        match msg {
+            JSONRPCMessage::Response(resp) => {
+                Self::dispatch_response(resp, pending).await;
+            }
+            JSONRPCMessage::Error(err) => {
+                Self::dispatch_error(err, pending).await;
+            }
+            JSONRPCMessage::Notification(notification) => {
+                if notification.method == ToolListChangedNotification::METHOD {
+                    tools_list_changed.store(true, Ordering::SeqCst);
+                } else if notification.method == ProgressNotification::METHOD {
+                    Self::dispatch_progress(notification, progress).await;
+                    return;
+                }
+                info!("<- notification: {notification:?}");
+            }
+            other => {
+                // Batch responses and requests are currently not
+                // expected from the server – log and ignore.
+                info!("<- unhandled message: {:?}", other);
+            }
+        }
+    }
+
+    /// Internal helper: forward a `notifications/progress` to the request that
+    /// asked for it. Progress for a request that already returned is dropped.
+    async fn dispatch_progress(notification: JSONRPCNotification, progress: &ProgressListeners) {
+        let params = notification
+            .params
+            .map(serde_json::from_value::<ProgressNotificationParams>);
+        let params = match params {
+            Some(Ok(params)) => params,
+            Some(Err(e)) => {
+                warn!("invalid progress notification: {e}");
+                return;
+            }
+            None => {
+                warn!("progress notification without params");
+                return;
+            }
+        };
+        debug!("<- progress: {params:?}");
+        if let Some(tx) = progress.lock().await.get(&params.progress_token) {
+            let _ = tx.send(params);
+        }
+    }
+
     /// Internal helper: route a JSON-RPC *response* object to the pending map.
     async fn dispatch_response(
         resp: JSONRPCResponse,
@@ -400,7 +612,9 @@ impl Drop for McpClient {
         // `kill_on_drop(true)` above, this extra check has the benefit of
         // forcing the process to be reaped immediately if it has already exited
         // instead of waiting for the Tokio runtime to reap it later.
-        let _ = self.child.try_wait();
+        if let Some(child) = &mut self.child {
+            let _ = child.try_wait();
+        }
     }
 }
 
@@ -445,6 +659,30 @@ const DEFAULT_ENV_VARS: &[&str] = &[
     "TMP",
 ];
 
+/// `tools/call` with a `_meta.progressToken`, which `CallToolRequestParams`
+/// has no field for.
+enum CallToolWithProgressRequest {}
+
+impl ModelContextProtocolRequest for CallToolWithProgressRequest {
+    const METHOD: &'static str = CallToolRequest::METHOD;
+    type Params = CallToolWithProgressParams;
+    type Result = CallToolResult;
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CallToolWithProgressParams {
+    #[serde(flatten)]
+    params: CallToolRequestParams,
+    #[serde(rename = "_meta")]
+    meta: ProgressMeta,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ProgressMeta {
+    #[serde(rename = "progressToken")]
+    progress_token: ProgressToken,
+}
+
 /// `extra_env` comes from the config for an entry in `mcp_servers` in
 /// `config.toml`.
 fn create_env_for_mcp_server(
@@ -474,4 +712,51 @@ mod tests {
         assert!(mcp_server_env.contains_key("PATH"));
         assert_eq!(Some(&env_var_new_value), mcp_server_env.get(env_var));
     }
+
+    #[test]
+    fn call_tool_with_progress_sends_a_progress_token() {
+        let params = CallToolWithProgressParams {
+            params: CallToolRequestParams {
+                name: "search".to_string(),
+                arguments: Some(serde_json::json!({ "query": "codex" })),
+            },
+            meta: ProgressMeta {
+                progress_token: ProgressToken::Integer(7),
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&params).unwrap(),
+            serde_json::json!({
+                "name": "search",
+                "arguments": { "query": "codex" },
+                "_meta": { "progressToken": 7 },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn progress_is_forwarded_to_the_request_that_asked_for_it() {
+        let progress: ProgressListeners = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        progress.lock().await.insert(ProgressToken::Integer(7), tx);
+        let notification = |token: i64| JSONRPCNotification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: ProgressNotification::METHOD.to_string(),
+            params: Some(serde_json::json!({
+                "progressToken": token,
+                "progress": 1.0,
+                "total": 4.0,
+                "message": "indexing",
+            })),
+        };
+
+        McpClient::dispatch_progress(notification(8), &progress).await;
+        McpClient::dispatch_progress(notification(7), &progress).await;
+
+        let forwarded = rx.try_recv().unwrap();
+        assert_eq!(forwarded.progress_token, ProgressToken::Integer(7));
+        assert_eq!(forwarded.total, Some(4.0));
+        assert_eq!(forwarded.message.as_deref(), Some("indexing"));
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -0,0 +1,652 @@
+//! OAuth support for MCP servers reached over Streamable HTTP.
+//!
+//! Implements the pieces of the MCP authorization spec that a desktop client
+//! needs:
+//!   1. Discovering the authorization server through the protected resource
+//!      metadata (RFC 9728) and authorization server metadata (RFC 8414).
+//!   2. Registering Codex as a public client when no `client_id` is configured
+//!      (RFC 7591).
+//!   3. The authorization-code flow with PKCE, using a local callback server to
+//!      receive the code.
+//!   4. Persisting the resulting tokens under `CODEX_HOME` and refreshing them
+//!      before they expire.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use base64::Engine;
+use rand::RngCore;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tiny_http::Header;
+use tiny_http::Response;
+use tiny_http::Server;
+use tokio::sync::Mutex;
+use tracing::debug;
+use tracing::warn;
+use url::Url;
+
+/// File in `CODEX_HOME` that holds the tokens of every MCP server.
+const CREDENTIALS_FILE: &str = "mcp_credentials.json";
+
+/// Path the local callback server expects the authorization server to
+/// redirect to.
+const CALLBACK_PATH: &str = "/callback";
+
+/// How long `codex mcp login` waits for the user to finish in the browser.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Access tokens that expire within this window are refreshed before use.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Tokens and client registration for one MCP server, as stored on disk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredOAuthCredentials {
+    /// URL of the MCP server the tokens were issued for. Credentials are
+    /// ignored when the configured URL changes.
+    pub server_url: String,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub token_endpoint: String,
+    pub access_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Seconds since the Unix epoch at which `access_token` expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl StoredOAuthCredentials {
+    fn expires_soon(&self, now: u64) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= now.saturating_add(REFRESH_MARGIN.as_secs()))
+    }
+}
+
+/// The `mcp_credentials.json` file in `CODEX_HOME`, keyed by server name.
+#[derive(Debug, Clone)]
+pub struct OAuthCredentialStore {
+    path: PathBuf,
+}
+
+impl OAuthCredentialStore {
+    pub fn new(codex_home: &Path) -> Self {
+        Self {
+            path: codex_home.join(CREDENTIALS_FILE),
+        }
+    }
+
+    /// Credentials for `server_name`, if they were issued for `server_url`.
+    pub fn load(
+        &self,
+        server_name: &str,
+        server_url: &str,
+    ) -> Result<Option<StoredOAuthCredentials>> {
+        Ok(self
+            .read_all()?
+            .remove(server_name)
+            .filter(|credentials| credentials.server_url == server_url))
+    }
+
+    pub fn save(&self, server_name: &str, credentials: &StoredOAuthCredentials) -> Result<()> {
+        let mut all = self.read_all()?;
+        all.insert(server_name.to_string(), credentials.clone());
+        self.write_all(&all)
+    }
+
+    /// Removes the credentials for `server_name`. Returns whether there were
+    /// any.
+    pub fn remove(&self, server_name: &str) -> Result<bool> {
+        let mut all = self.read_all()?;
+        let removed = all.remove(server_name).is_some();
+        if removed {
+            self.write_all(&all)?;
+        }
+        Ok(removed)
+    }
+
+    fn read_all(&self) -> Result<HashMap<String, StoredOAuthCredentials>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", self.path.display())),
+        }
+    }
+
+    fn write_all(&self, all: &HashMap<String, StoredOAuthCredentials>) -> Result<()> {
+        let json_data = serde_json::to_string_pretty(all)?;
+        let mut options = OpenOptions::new();
+        options.truncate(true).write(true).create(true);
+        #[cfg(unix)]
+        {
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        file.write_all(json_data.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// The OAuth state of a connected server. Hands out access tokens to the
+/// transport and refreshes them, persisting the new tokens, when they expire.
+pub struct OAuthSession {
+    server_name: String,
+    store: OAuthCredentialStore,
+    credentials: Mutex<StoredOAuthCredentials>,
+    http: reqwest::Client,
+}
+
+impl OAuthSession {
+    pub fn new(
+        server_name: String,
+        store: OAuthCredentialStore,
+        credentials: StoredOAuthCredentials,
+    ) -> Self {
+        Self {
+            server_name,
+            store,
+            credentials: Mutex::new(credentials),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// A token to send with the next request, refreshed first if it is about
+    /// to expire.
+    pub async fn access_token(&self) -> Result<String> {
+        let mut credentials = self.credentials.lock().await;
+        if credentials.expires_soon(unix_now()) && credentials.refresh_token.is_some() {
+            self.refresh_locked(&mut credentials).await?;
+        }
+        Ok(credentials.access_token.clone())
+    }
+
+    /// Called when the server rejected `rejected_token`. Refreshes it unless a
+    /// concurrent request already did, and returns the token to retry with.
+    pub async fn refresh(&self, rejected_token: &str) -> Result<String> {
+        let mut credentials = self.credentials.lock().await;
+        if credentials.access_token == rejected_token {
+            self.refresh_locked(&mut credentials).await?;
+        }
+        Ok(credentials.access_token.clone())
+    }
+
+    async fn refresh_locked(&self, credentials: &mut StoredOAuthCredentials) -> Result<()> {
+        let refresh_token = credentials.refresh_token.clone().ok_or_else(|| {
+            anyhow!(
+                "the access token for MCP server `{}` expired; run `codex mcp login {}`",
+                self.server_name,
+                self.server_name
+            )
+        })?;
+        debug!(
+            "refreshing OAuth token for MCP server `{}`",
+            self.server_name
+        );
+
+        let mut form = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token.clone()),
+            ("client_id", credentials.client_id.clone()),
+            ("resource", credentials.server_url.clone()),
+        ];
+        if let Some(secret) = &credentials.client_secret {
+            form.push(("client_secret", secret.clone()));
+        }
+        let tokens = request_tokens(&self.http, &credentials.token_endpoint, &form)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to refresh the access token for MCP server `{}`; run `codex mcp login {}`",
+                    self.server_name, self.server_name
+                )
+            })?;
+
+        credentials.access_token = tokens.access_token;
+        // Servers that do not rotate refresh tokens omit them from the reply.
+        credentials.refresh_token = tokens.refresh_token.or(Some(refresh_token));
+        credentials.expires_at = tokens
+            .expires_in
+            .map(|secs| unix_now().saturating_add(secs));
+        if let Err(e) = self.store.save(&self.server_name, credentials) {
+            warn!("failed to persist refreshed OAuth token: {e:#}");
+        }
+        Ok(())
+    }
+}
+
+/// Settings for [`perform_oauth_login`], from the server's `oauth` table in
+/// `config.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct OAuthLoginOptions {
+    /// Pre-registered client; when unset Codex registers itself dynamically.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    /// Port for the local callback server; `None` picks a free port.
+    pub callback_port: Option<u16>,
+    pub open_browser: bool,
+}
+
+/// Runs the authorization-code flow for the MCP server at `server_url` and
+/// stores the resulting tokens in `CODEX_HOME`. `on_authorize_url` is called
+/// with the URL the user has to visit.
+pub async fn perform_oauth_login(
+    server_name: &str,
+    server_url: &str,
+    codex_home: &Path,
+    options: OAuthLoginOptions,
+    on_authorize_url: impl FnOnce(&str),
+) -> Result<()> {
+    let http = reqwest::Client::new();
+    let metadata = discover_authorization_server(&http, server_url).await?;
+
+    let server = Server::http(format!("127.0.0.1:{}", options.callback_port.unwrap_or(0)))
+        .map_err(|e| anyhow!("failed to start the OAuth callback server: {e}"))?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or_else(|| anyhow!("unable to determine the OAuth callback server port"))?;
+    let redirect_uri = format!("http://127.0.0.1:{port}{CALLBACK_PATH}");
+
+    let (client_id, client_secret) = match options.client_id {
+        Some(client_id) => (client_id, options.client_secret),
+        None => register_client(&http, &metadata, &redirect_uri).await?,
+    };
+
+    let pkce = generate_pkce();
+    let state = random_token();
+    let authorize_url = build_authorize_url(
+        &metadata.authorization_endpoint,
+        &client_id,
+        &redirect_uri,
+        &pkce,
+        &state,
+        &options.scopes,
+        server_url,
+    )?;
+    on_authorize_url(&authorize_url);
+    if options.open_browser {
+        let _ = webbrowser::open(&authorize_url);
+    }
+
+    let code = tokio::task::spawn_blocking(move || wait_for_callback(&server, &state))
+        .await
+        .context("OAuth callback server panicked")??;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("client_id", client_id.clone()),
+        ("code_verifier", pkce.code_verifier),
+        ("resource", server_url.to_string()),
+    ];
+    if let Some(secret) = &client_secret {
+        form.push(("client_secret", secret.clone()));
+    }
+    let tokens = request_tokens(&http, &metadata.token_endpoint, &form)
+        .await
+        .context("failed to exchange the authorization code for tokens")?;
+
+    OAuthCredentialStore::new(codex_home).save(
+        server_name,
+        &StoredOAuthCredentials {
+            server_url: server_url.to_string(),
+            client_id,
+            client_secret,
+            token_endpoint: metadata.token_endpoint,
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens
+                .expires_in
+                .map(|secs| unix_now().saturating_add(secs)),
+        },
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtectedResourceMetadata {
+    #[serde(default)]
+    authorization_servers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorizationServerMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    registration_endpoint: Option<String>,
+}
+
+/// Finds the authorization server of the MCP server at `server_url`. Servers
+/// that do not publish protected resource metadata are assumed to be their
+/// own authorization server.
+async fn discover_authorization_server(
+    http: &reqwest::Client,
+    server_url: &str,
+) -> Result<AuthorizationServerMetadata> {
+    let server_url = Url::parse(server_url).context("invalid MCP server URL")?;
+
+    let resource_metadata = fetch_json::<ProtectedResourceMetadata>(
+        http,
+        well_known_url(&server_url, "oauth-protected-resource"),
+    )
+    .await
+    .ok();
+    let issuer = match resource_metadata
+        .and_then(|metadata| metadata.authorization_servers.into_iter().next())
+    {
+        Some(issuer) => Url::parse(&issuer).context("invalid authorization server URL")?,
+        None => server_url,
+    };
+
+    match fetch_json(http, well_known_url(&issuer, "oauth-authorization-server")).await {
+        Ok(metadata) => Ok(metadata),
+        Err(e) => {
+            // Some providers only publish OpenID Connect discovery.
+            fetch_json(http, well_known_url(&issuer, "openid-configuration"))
+                .await
+                .map_err(|_| e)
+                .context("failed to discover the OAuth authorization server")
+        }
+    }
+}
+
+/// `https://host/.well-known/<suffix>/<path>` as described in RFC 8414.
+fn well_known_url(url: &Url, suffix: &str) -> Url {
+    let mut well_known = url.clone();
+    let path = url.path().trim_end_matches('/');
+    well_known.set_path(&format!("/.well-known/{suffix}{path}"));
+    well_known.set_query(None);
+    well_known.set_fragment(None);
+    well_known
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(http: &reqwest::Client, url: Url) -> Result<T> {
+    let response = http.get(url.clone()).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("GET {url} returned {}", response.status()));
+    }
+    Ok(response.json().await?)
+}
+
+/// Registers Codex as a public client. Returns the client id and secret.
+async fn register_client(
+    http: &reqwest::Client,
+    metadata: &AuthorizationServerMetadata,
+    redirect_uri: &str,
+) -> Result<(String, Option<String>)> {
+    #[derive(Deserialize)]
+    struct RegistrationResponse {
+        client_id: String,
+        client_secret: Option<String>,
+    }
+
+    let registration_endpoint = metadata.registration_endpoint.as_deref().ok_or_else(|| {
+        anyhow!(
+            "the authorization server does not support dynamic client registration; set `client_id` in the server's `oauth` table"
+        )
+    })?;
+    let response = http
+        .post(registration_endpoint)
+        .json(&serde_json::json!({
+            "client_name": "Codex",
+            "redirect_uris": [redirect_uri],
+            "grant_types": ["authorization_code", "refresh_token"],
+            "response_types": ["code"],
+            "token_endpoint_auth_method": "none",
+        }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("client registration failed ({status}): {body}"));
+    }
+    let registration: RegistrationResponse = response.json().await?;
+    Ok((registration.client_id, registration.client_secret))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+async fn request_tokens(
+    http: &reqwest::Client,
+    token_endpoint: &str,
+    form: &[(&str, String)],
+) -> Result<TokenResponse> {
+    let response = http.post(token_endpoint).form(form).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("token endpoint returned {status}: {body}"));
+    }
+    Ok(response.json().await?)
+}
+
+struct PkceCodes {
+    code_verifier: String,
+    code_challenge: String,
+}
+
+fn generate_pkce() -> PkceCodes {
+    let code_verifier = random_token();
+    let digest = Sha256::digest(code_verifier.as_bytes());
+    let code_challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest);
+    PkceCodes {
+        code_verifier,
+        code_challenge,
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn build_authorize_url(
+    authorization_endpoint: &str,
+    client_id: &str,
+    redirect_uri: &str,
+    pkce: &PkceCodes,
+    state: &str,
+    scopes: &[String],
+    resource: &str,
+) -> Result<String> {
+    let mut url = Url::parse(authorization_endpoint).context("invalid authorization endpoint")?;
+    {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("response_type", "code")
+            .append_pair("client_id", client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("code_challenge", &pkce.code_challenge)
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("state", state)
+            .append_pair("resource", resource);
+        if !scopes.is_empty() {
+            query.append_pair("scope", &scopes.join(" "));
+        }
+    }
+    Ok(url.into())
+}
+
+/// Serves the local callback until the authorization server redirects back
+/// with a code for `state`, and returns that code.
+fn wait_for_callback(server: &Server, state: &str) -> Result<String> {
+    let deadline = Instant::now() + LOGIN_TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Some(request) = server.recv_timeout(remaining)? else {
+            return Err(anyhow!("timed out waiting for the OAuth authorization"));
+        };
+        let url = match Url::parse(&format!("http://127.0.0.1{}", request.url())) {
+            Ok(url) if url.path() == CALLBACK_PATH => url,
+            _ => {
+                let _ = request.respond(Response::from_string("Not Found").with_status_code(404));
+                continue;
+            }
+        };
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        if params.get("state").map(String::as_str) != Some(state) {
+            let _ = request.respond(Response::from_string("State mismatch").with_status_code(400));
+            continue;
+        }
+
+        let result = match (params.get("code"), params.get("error")) {
+            (Some(code), _) if !code.is_empty() => Ok(code.clone()),
+            (_, Some(error)) => Err(anyhow!(
+                "authorization failed: {error} {}",
+                params
+                    .get("error_description")
+                    .map(String::as_str)
+                    .unwrap_or_default()
+            )),
+            _ => Err(anyhow!("the authorization server did not return a code")),
+        };
+        let message = match &result {
+            Ok(_) => "Codex is now authorized. You can close this window.".to_string(),
+            Err(e) => format!("Authorization failed: {e}"),
+        };
+        let mut response = Response::from_string(format!(
+            "<!doctype html><html><body><p>{}</p></body></html>",
+            html_escape(&message)
+        ));
+        if let Ok(header) =
+            Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+        {
+            response.add_header(header);
+        }
+        let _ = request.respond(response);
+        return result;
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn credentials(server_url: &str) -> StoredOAuthCredentials {
+        StoredOAuthCredentials {
+            server_url: server_url.to_string(),
+            client_id: "client".to_string(),
+            client_secret: None,
+            token_endpoint: "https://auth.example.com/token".to_string(),
+            access_token: "access".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            expires_at: Some(1_000),
+        }
+    }
+
+    #[test]
+    fn credential_store_round_trip() -> Result<()> {
+        let codex_home = TempDir::new()?;
+        let store = OAuthCredentialStore::new(codex_home.path());
+        assert_eq!(store.load("docs", "https://mcp.example.com/mcp")?, None);
+
+        let saved = credentials("https://mcp.example.com/mcp");
+        store.save("docs", &saved)?;
+        assert_eq!(
+            store.load("docs", "https://mcp.example.com/mcp")?,
+            Some(saved)
+        );
+        // Tokens issued for another URL are not sent to a new one.
+        assert_eq!(store.load("docs", "https://evil.example.com/mcp")?, None);
+
+        assert!(store.remove("docs")?);
+        assert_eq!(store.load("docs", "https://mcp.example.com/mcp")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_close_to_expiry_are_refreshed() {
+        let credentials = credentials("https://mcp.example.com/mcp");
+        assert!(!credentials.expires_soon(900));
+        assert!(credentials.expires_soon(950));
+        assert!(
+            !StoredOAuthCredentials {
+                expires_at: None,
+                ..credentials
+            }
+            .expires_soon(u64::MAX / 2)
+        );
+    }
+
+    #[test]
+    fn well_known_urls_keep_the_resource_path() {
+        let url = Url::parse("https://mcp.example.com/v1/mcp?x=1").unwrap();
+        assert_eq!(
+            well_known_url(&url, "oauth-protected-resource").as_str(),
+            "https://mcp.example.com/.well-known/oauth-protected-resource/v1/mcp"
+        );
+        let issuer = Url::parse("https://auth.example.com/").unwrap();
+        assert_eq!(
+            well_known_url(&issuer, "oauth-authorization-server").as_str(),
+            "https://auth.example.com/.well-known/oauth-authorization-server"
+        );
+    }
+
+    #[test]
+    fn authorize_url_includes_pkce_and_resource() -> Result<()> {
+        let pkce = PkceCodes {
+            code_verifier: "verifier".to_string(),
+            code_challenge: "challenge".to_string(),
+        };
+        let url = build_authorize_url(
+            "https://auth.example.com/authorize",
+            "client",
+            "http://127.0.0.1:1234/callback",
+            &pkce,
+            "state",
+            &["read".to_string(), "write".to_string()],
+            "https://mcp.example.com/mcp",
+        )?;
+        let url = Url::parse(&url)?;
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["code_challenge"], "challenge");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["redirect_uri"], "http://127.0.0.1:1234/callback");
+        assert_eq!(params["resource"], "https://mcp.example.com/mcp");
+        assert_eq!(params["scope"], "read write");
+        Ok(())
+    }
+}
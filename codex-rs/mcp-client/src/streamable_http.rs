@@ -0,0 +1,250 @@
+//! The Streamable HTTP transport: every client message is POSTed to the
+//! server's endpoint, which answers with either a JSON body or an SSE stream
+//! of messages. Messages the server sends on its own arrive on an SSE stream
+//! opened with GET.
+//!
+//! See https://modelcontextprotocol.io/specification/2025-06-18/basic/transports#streamable-http
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use anyhow::anyhow;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use mcp_types::InitializedNotification;
+use mcp_types::JSONRPC_VERSION;
+use mcp_types::JSONRPCError;
+use mcp_types::JSONRPCErrorError;
+use mcp_types::JSONRPCMessage;
+use mcp_types::MCP_SCHEMA_VERSION;
+use mcp_types::ModelContextProtocolNotification;
+use mcp_types::RequestId;
+use reqwest::StatusCode;
+use reqwest::header::ACCEPT;
+use reqwest::header::CONTENT_TYPE;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+
+use crate::oauth::OAuthSession;
+
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// JSON-RPC error code used to report transport failures for a request, so
+/// that they surface through the same path as errors returned by the server.
+const TRANSPORT_ERROR_CODE: i64 = -32603;
+
+/// State shared by the tasks that talk to one Streamable HTTP server.
+pub(crate) struct StreamableHttpTransport {
+    http: reqwest::Client,
+    url: String,
+    oauth: Option<Arc<OAuthSession>>,
+    /// Assigned by the server in its reply to `initialize`.
+    session_id: std::sync::Mutex<Option<String>>,
+    /// Messages received from the server, consumed by the client's reader
+    /// task.
+    incoming_tx: mpsc::Sender<JSONRPCMessage>,
+}
+
+impl StreamableHttpTransport {
+    pub(crate) fn new(
+        url: String,
+        oauth: Option<Arc<OAuthSession>>,
+        incoming_tx: mpsc::Sender<JSONRPCMessage>,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url,
+            oauth,
+            session_id: std::sync::Mutex::new(None),
+            incoming_tx,
+        }
+    }
+
+    /// Sends every message from `outgoing_rx` until the channel closes.
+    /// Requests are sent concurrently so a slow tool call does not hold up
+    /// the others; notifications are sent in order. Requests still in flight
+    /// and the server's stream are dropped together with this future.
+    pub(crate) async fn run(self: Arc<Self>, mut outgoing_rx: mpsc::Receiver<JSONRPCMessage>) {
+        let mut tasks = JoinSet::new();
+        while let Some(msg) = outgoing_rx.recv().await {
+            while tasks.try_join_next().is_some() {}
+            match msg {
+                JSONRPCMessage::Request(request) => {
+                    let transport = self.clone();
+                    tasks.spawn(async move {
+                        let id = request.id.clone();
+                        if let Err(e) = transport.post(&JSONRPCMessage::Request(request)).await {
+                            transport.fail_request(id, e).await;
+                        }
+                    });
+                }
+                JSONRPCMessage::Notification(notification) => {
+                    let opens_stream = notification.method == InitializedNotification::METHOD;
+                    if let Err(e) = self.post(&JSONRPCMessage::Notification(notification)).await {
+                        error!("failed to send notification to MCP server: {e:#}");
+                    }
+                    if opens_stream {
+                        tasks.spawn(self.clone().listen());
+                    }
+                }
+                other => {
+                    if let Err(e) = self.post(&other).await {
+                        error!("failed to send message to MCP server: {e:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// POSTs `msg` and forwards whatever the server replies with.
+    async fn post(&self, msg: &JSONRPCMessage) -> Result<()> {
+        let body = serde_json::to_string(msg)?;
+        debug!("MCP message to server: {body}");
+        let response = self
+            .send_authorized(|| {
+                self.with_session(self.http.post(&self.url))
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json, text/event-stream")
+                    .body(body.clone())
+            })
+            .await?;
+
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.lock_session_id() = Some(session_id.to_string());
+        }
+        if response.status() == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+        self.forward_body(response).await
+    }
+
+    /// Opens the stream on which the server sends requests and notifications
+    /// of its own. Servers that do not offer one answer 405.
+    async fn listen(self: Arc<Self>) {
+        let response = self
+            .send_authorized(|| {
+                self.with_session(self.http.get(&self.url))
+                    .header(ACCEPT, "text/event-stream")
+            })
+            .await;
+        match response {
+            Ok(response) => {
+                if let Err(e) = self.forward_body(response).await {
+                    debug!("MCP server stream closed: {e:#}");
+                }
+            }
+            Err(e) => debug!("MCP server does not offer a stream: {e:#}"),
+        }
+    }
+
+    /// Sends the request built by `build`, refreshing the OAuth token and
+    /// retrying once if the server rejects it.
+    async fn send_authorized(
+        &self,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let Some(oauth) = &self.oauth else {
+            return check_status(build().send().await?).await;
+        };
+
+        let token = oauth.access_token().await?;
+        let response = build().bearer_auth(&token).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return check_status(response).await;
+        }
+        let token = oauth.refresh(&token).await?;
+        check_status(build().bearer_auth(&token).send().await?).await
+    }
+
+    fn with_session(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let builder = builder.header(PROTOCOL_VERSION_HEADER, MCP_SCHEMA_VERSION);
+        match self.lock_session_id().clone() {
+            Some(session_id) => builder.header(SESSION_ID_HEADER, session_id),
+            None => builder,
+        }
+    }
+
+    async fn forward_body(&self, response: reqwest::Response) -> Result<()> {
+        let is_event_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+
+        if is_event_stream {
+            let mut events = response.bytes_stream().eventsource();
+            while let Some(event) = events.next().await {
+                let event = event.map_err(|e| anyhow!("invalid SSE event: {e}"))?;
+                if event.data.is_empty() {
+                    continue;
+                }
+                self.forward(&event.data).await;
+            }
+        } else {
+            let body = response.text().await?;
+            if !body.trim().is_empty() {
+                self.forward(&body).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn forward(&self, data: &str) {
+        debug!("MCP message from server: {data}");
+        let messages = match serde_json::from_str::<JSONRPCMessage>(data) {
+            Ok(msg) => vec![msg],
+            // A batch is an array of messages.
+            Err(_) => match serde_json::from_str::<Vec<JSONRPCMessage>>(data) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!("failed to deserialize JSONRPCMessage: {e}; data = {data}");
+                    return;
+                }
+            },
+        };
+        for msg in messages {
+            if self.incoming_tx.send(msg).await.is_err() {
+                info!("MCP client dropped; discarding message from server");
+                return;
+            }
+        }
+    }
+
+    /// Reports a failed POST as a JSON-RPC error for the request, so the
+    /// caller sees it instead of waiting for a reply that will never come.
+    async fn fail_request(&self, id: RequestId, e: anyhow::Error) {
+        let error = JSONRPCMessage::Error(JSONRPCError {
+            error: JSONRPCErrorError {
+                code: TRANSPORT_ERROR_CODE,
+                data: None,
+                message: format!("{e:#}"),
+            },
+            id,
+            jsonrpc: JSONRPC_VERSION.to_string(),
+        });
+        let _ = self.incoming_tx.send(error).await;
+    }
+
+    fn lock_session_id(&self) -> std::sync::MutexGuard<'_, Option<String>> {
+        self.session_id
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow!("MCP server returned {status}: {body}"))
+}
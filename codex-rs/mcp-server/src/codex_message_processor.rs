@@ -9,7 +9,7 @@ use codex_core::NewConversation;
 use codex_core::config::Config;
 use codex_core::config::ConfigOverrides;
 use codex_core::config::ConfigToml;
-use codex_core::config::load_config_as_toml;
+use codex_core::config::load_config_as_toml_with_cli_overrides;
 use codex_core::git_info::git_diff_to_remote;
 use codex_core::protocol::ApplyPatchApprovalRequestEvent;
 use codex_core::protocol::Event;
@@ -42,6 +42,8 @@ use codex_protocol::mcp_protocol::AddConversationListenerParams;
 use codex_protocol::mcp_protocol::AddConversationSubscriptionResponse;
 use codex_protocol::mcp_protocol::ApplyPatchApprovalParams;
 use codex_protocol::mcp_protocol::ApplyPatchApprovalResponse;
+use codex_protocol::mcp_protocol::ArchiveConversationParams;
+use codex_protocol::mcp_protocol::ArchiveConversationResponse;
 use codex_protocol::mcp_protocol::AuthStatusChangeNotification;
 use codex_protocol::mcp_protocol::ClientRequest;
 use codex_protocol::mcp_protocol::ConversationId;
@@ -128,6 +130,9 @@ impl CodexMessageProcessor {
             ClientRequest::InterruptConversation { request_id, params } => {
                 self.interrupt_conversation(request_id, params).await;
             }
+            ClientRequest::ArchiveConversation { request_id, params } => {
+                self.archive_conversation(request_id, params).await;
+            }
             ClientRequest::AddConversationListener { request_id, params } => {
                 self.add_conversation_listener(request_id, params).await;
             }
@@ -160,7 +165,10 @@ impl CodexMessageProcessor {
 
         let opts = LoginServerOptions {
             open_browser: false,
-            ..LoginServerOptions::new(config.codex_home.clone(), CLIENT_ID.to_string())
+            ..LoginServerOptions::new(
+                config.dirs.config_dir().to_path_buf(),
+                CLIENT_ID.to_string(),
+            )
         };
 
         enum LoginChatGptReply {
@@ -361,31 +369,20 @@ impl CodexMessageProcessor {
     }
 
     async fn get_config_toml(&self, request_id: RequestId) {
-        let toml_value = match load_config_as_toml(&self.config.codex_home) {
-            Ok(val) => val,
-            Err(err) => {
-                let error = JSONRPCErrorError {
-                    code: INTERNAL_ERROR_CODE,
-                    message: format!("failed to load config.toml: {err}"),
-                    data: None,
-                };
-                self.outgoing.send_error(request_id, error).await;
-                return;
-            }
-        };
-
-        let cfg: ConfigToml = match toml_value.try_into() {
-            Ok(cfg) => cfg,
-            Err(err) => {
-                let error = JSONRPCErrorError {
-                    code: INTERNAL_ERROR_CODE,
-                    message: format!("failed to parse config.toml: {err}"),
-                    data: None,
-                };
-                self.outgoing.send_error(request_id, error).await;
-                return;
-            }
-        };
+        let cfg: ConfigToml =
+            match load_config_as_toml_with_cli_overrides(self.config.dirs.config_dir(), Vec::new())
+            {
+                Ok(cfg) => cfg,
+                Err(err) => {
+                    let error = JSONRPCErrorError {
+                        code: INTERNAL_ERROR_CODE,
+                        message: format!("failed to parse config.toml: {err}"),
+                        data: None,
+                    };
+                    self.outgoing.send_error(request_id, error).await;
+                    return;
+                }
+            };
 
         let profiles: HashMap<String, codex_protocol::config_types::ConfigProfile> = cfg
             .profiles
@@ -537,8 +534,9 @@ impl CodexMessageProcessor {
                 approval_policy,
                 sandbox_policy,
                 model,
-                effort,
+                effort: Some(effort),
                 summary,
+                final_output_json_schema: None,
             })
             .await;
 
@@ -577,6 +575,44 @@ impl CodexMessageProcessor {
         let _ = conversation.submit(Op::Interrupt).await;
     }
 
+    async fn archive_conversation(
+        &mut self,
+        request_id: RequestId,
+        params: ArchiveConversationParams,
+    ) {
+        let ArchiveConversationParams { conversation_id } = params;
+        let Ok(conversation) = self
+            .conversation_manager
+            .get_conversation(conversation_id.0)
+            .await
+        else {
+            let error = JSONRPCErrorError {
+                code: INVALID_REQUEST_ERROR_CODE,
+                message: format!("conversation not found: {conversation_id}"),
+                data: None,
+            };
+            self.outgoing.send_error(request_id, error).await;
+            return;
+        };
+
+        self.conversation_manager
+            .remove_conversation(conversation_id.0)
+            .await;
+        self.pending_interrupts
+            .lock()
+            .await
+            .remove(&conversation_id.0);
+
+        // Listeners see ShutdownComplete, after which the conversation's
+        // event stream ends and their tasks exit.
+        if let Err(err) = conversation.submit(Op::Shutdown).await {
+            tracing::warn!("failed to shut down conversation {conversation_id}: {err}");
+        }
+        self.outgoing
+            .send_response(request_id, ArchiveConversationResponse {})
+            .await;
+    }
+
     async fn add_conversation_listener(
         &mut self,
         request_id: RequestId,
@@ -734,6 +770,8 @@ async fn apply_bespoke_event_handling(
             command,
             cwd,
             reason,
+            escalation_reason,
+            session_prefix,
         }) => {
             let params = ExecCommandApprovalParams {
                 conversation_id,
@@ -741,6 +779,8 @@ async fn apply_bespoke_event_handling(
                 command,
                 cwd,
                 reason,
+                escalation_reason,
+                session_prefix,
             };
             let value = serde_json::to_value(&params).unwrap_or_default();
             let rx = outgoing
@@ -802,6 +842,7 @@ fn derive_config_from_params(
         disable_response_storage: None,
         show_raw_agent_reasoning: None,
         tools_web_search_request: None,
+        skip_git_repo_check: None,
     };
 
     let cli_overrides = cli_overrides
@@ -827,6 +868,8 @@ async fn on_patch_approval_response(
                 .submit(Op::PatchApproval {
                     id: event_id.clone(),
                     decision: ReviewDecision::Denied,
+                    selection: None,
+                    feedback: None,
                 })
                 .await
             {
@@ -848,6 +891,8 @@ async fn on_patch_approval_response(
         .submit(Op::PatchApproval {
             id: event_id,
             decision: response.decision,
+            selection: None,
+            feedback: None,
         })
         .await
     {
@@ -884,6 +929,7 @@ async fn on_exec_approval_response(
         .submit(Op::ExecApproval {
             id: event_id,
             decision: response.decision,
+            feedback: None,
         })
         .await
     {
@@ -165,6 +165,7 @@ impl CodexToolCallParam {
             disable_response_storage: None,
             show_raw_agent_reasoning: None,
             tools_web_search_request: None,
+            skip_git_repo_check: None,
         };
 
         let cli_overrides = cli_overrides
@@ -179,6 +179,8 @@ async fn run_codex_tool_session_inner(
                         cwd,
                         call_id,
                         reason: _,
+                        escalation_reason: _,
+                        session_prefix: _,
                     }) => {
                         handle_exec_approval_request(
                             command,
@@ -259,23 +261,46 @@ async fn run_codex_tool_session_inner(
                     | EventMsg::AgentReasoningRawContentDelta(_)
                     | EventMsg::TaskStarted(_)
                     | EventMsg::TokenCount(_)
+                    | EventMsg::PromptTokenEstimate(_)
+                    | EventMsg::ContextUsage(_)
+                    | EventMsg::ContextWindowWarning(_)
+                    | EventMsg::ConversationCompacted(_)
+                    | EventMsg::PatchUndone(_)
+                    | EventMsg::UsageUpdate(_)
+                    | EventMsg::RateLimits(_)
+                    | EventMsg::TurnMetrics(_)
+                    | EventMsg::FunctionCallArgumentsDelta(_)
                     | EventMsg::AgentReasoning(_)
                     | EventMsg::AgentReasoningSectionBreak(_)
                     | EventMsg::McpToolCallBegin(_)
                     | EventMsg::McpToolCallEnd(_)
+                    | EventMsg::McpToolCallProgress(_)
                     | EventMsg::McpListToolsResponse(_)
+                    | EventMsg::StatusResponse(_)
+                    | EventMsg::QueuedInputUpdated(_)
+                    | EventMsg::McpListResourcesResponse(_)
+                    | EventMsg::McpListPromptsResponse(_)
+                    | EventMsg::McpStartupWarning(_)
+                    | EventMsg::AuthExpired(_)
+                    | EventMsg::GitRepoWarning(_)
+                    | EventMsg::ProjectDocTruncated(_)
+                    | EventMsg::PullRequest(_)
+                    | EventMsg::ToolOutputBudgetExceeded(_)
                     | EventMsg::ListCustomPromptsResponse(_)
                     | EventMsg::ExecCommandBegin(_)
                     | EventMsg::ExecCommandOutputDelta(_)
                     | EventMsg::ExecCommandEnd(_)
                     | EventMsg::BackgroundEvent(_)
                     | EventMsg::StreamError(_)
+                    | EventMsg::BackgroundResponseProgress(_)
                     | EventMsg::PatchApplyBegin(_)
                     | EventMsg::PatchApplyEnd(_)
                     | EventMsg::TurnDiff(_)
+                    | EventMsg::ReviewFindings(_)
                     | EventMsg::WebSearchBegin(_)
                     | EventMsg::WebSearchEnd(_)
                     | EventMsg::GetHistoryEntryResponse(_)
+                    | EventMsg::SearchHistoryResponse(_)
                     | EventMsg::PlanUpdate(_)
                     | EventMsg::TurnAborted(_)
                     | EventMsg::ConversationHistory(_)
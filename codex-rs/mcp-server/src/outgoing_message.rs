@@ -260,6 +260,8 @@ mod tests {
                 model: "gpt-4o".to_string(),
                 history_log_id: 1,
                 history_entry_count: 1000,
+                project_docs: Vec::new(),
+                rollout_path: None,
             }),
         };
 
@@ -289,6 +291,8 @@ mod tests {
             model: "gpt-4o".to_string(),
             history_log_id: 1,
             history_entry_count: 1000,
+            project_docs: Vec::new(),
+            rollout_path: None,
         };
         let event = Event {
             id: "1".to_string(),
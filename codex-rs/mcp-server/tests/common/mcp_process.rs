@@ -13,6 +13,7 @@ use anyhow::Context;
 use assert_cmd::prelude::*;
 use codex_mcp_server::CodexToolCallParam;
 use codex_protocol::mcp_protocol::AddConversationListenerParams;
+use codex_protocol::mcp_protocol::ArchiveConversationParams;
 use codex_protocol::mcp_protocol::CancelLoginChatGptParams;
 use codex_protocol::mcp_protocol::GetAuthStatusParams;
 use codex_protocol::mcp_protocol::InterruptConversationParams;
@@ -231,6 +232,15 @@ impl McpProcess {
         self.send_request("interruptConversation", params).await
     }
 
+    /// Send an `archiveConversation` JSON-RPC request.
+    pub async fn send_archive_conversation_request(
+        &mut self,
+        params: ArchiveConversationParams,
+    ) -> anyhow::Result<i64> {
+        let params = Some(serde_json::to_value(params)?);
+        self.send_request("archiveConversation", params).await
+    }
+
     /// Send a `getAuthStatus` JSON-RPC request.
     pub async fn send_get_auth_status_request(
         &mut self,
@@ -413,6 +423,37 @@ impl McpProcess {
         }
     }
 
+    /// Reads messages until `count` notifications with `method` have arrived,
+    /// skipping responses and other notifications. Useful when several
+    /// requests are in flight at once.
+    pub async fn read_stream_until_notification_messages(
+        &mut self,
+        method: &str,
+        count: usize,
+    ) -> anyhow::Result<Vec<JSONRPCNotification>> {
+        eprintln!("in read_stream_until_notification_messages({method}, {count})");
+
+        let mut notifications = Vec::with_capacity(count);
+        while notifications.len() < count {
+            let message = self.read_jsonrpc_message().await?;
+            match message {
+                JSONRPCMessage::Notification(notification) => {
+                    if notification.method == method {
+                        notifications.push(notification);
+                    }
+                }
+                JSONRPCMessage::Response(_) => {}
+                JSONRPCMessage::Request(_) => {
+                    anyhow::bail!("unexpected JSONRPCMessage::Request: {message:?}");
+                }
+                JSONRPCMessage::Error(_) => {
+                    anyhow::bail!("unexpected JSONRPCMessage::Error: {message:?}");
+                }
+            }
+        }
+        Ok(notifications)
+    }
+
     /// Reads notifications until a legacy TaskComplete event is observed:
     /// Method "codex/event" with params.msg.type == "task_complete".
     pub async fn read_stream_until_legacy_task_complete_notification(
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use codex_protocol::mcp_protocol::AddConversationListenerParams;
+use codex_protocol::mcp_protocol::ArchiveConversationParams;
+use codex_protocol::mcp_protocol::ArchiveConversationResponse;
+use codex_protocol::mcp_protocol::ConversationId;
+use codex_protocol::mcp_protocol::InputItem;
+use codex_protocol::mcp_protocol::NewConversationParams;
+use codex_protocol::mcp_protocol::NewConversationResponse;
+use codex_protocol::mcp_protocol::SendUserMessageParams;
+use mcp_test_support::McpProcess;
+use mcp_test_support::create_final_assistant_message_sse_response;
+use mcp_test_support::to_response;
+use mcp_types::RequestId;
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::time::timeout;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::Respond;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+const DEFAULT_READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+const CONVERSATIONS: usize = 3;
+const TURNS: usize = 3;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_interleaved_conversations_keep_separate_histories() {
+    if let Err(err) = interleaved_conversations_keep_separate_histories().await {
+        panic!("failure: {err}");
+    }
+}
+
+async fn interleaved_conversations_keep_separate_histories() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(EchoResponder)
+        .expect((CONVERSATIONS * TURNS) as u64)
+        .mount(&server)
+        .await;
+
+    let codex_home = TempDir::new()?;
+    create_config_toml(codex_home.path(), &server.uri())?;
+    let mut mcp = McpProcess::new(codex_home.path()).await?;
+    timeout(DEFAULT_READ_TIMEOUT, mcp.initialize()).await??;
+
+    let mut conversation_ids = Vec::new();
+    for _ in 0..CONVERSATIONS {
+        let request_id = mcp
+            .send_new_conversation_request(NewConversationParams::default())
+            .await?;
+        let response = timeout(
+            DEFAULT_READ_TIMEOUT,
+            mcp.read_stream_until_response_message(RequestId::Integer(request_id)),
+        )
+        .await??;
+        let NewConversationResponse {
+            conversation_id, ..
+        } = to_response(response)?;
+
+        let request_id = mcp
+            .send_add_conversation_listener_request(AddConversationListenerParams {
+                conversation_id,
+            })
+            .await?;
+        timeout(
+            DEFAULT_READ_TIMEOUT,
+            mcp.read_stream_until_response_message(RequestId::Integer(request_id)),
+        )
+        .await??;
+        conversation_ids.push(conversation_id);
+    }
+
+    // Start a turn in every conversation before any of them finishes, in a
+    // different order each round.
+    for turn in 0..TURNS {
+        for offset in 0..CONVERSATIONS {
+            let index = (turn + offset) % CONVERSATIONS;
+            mcp.send_send_user_message_request(SendUserMessageParams {
+                conversation_id: conversation_ids[index],
+                items: vec![InputItem::Text {
+                    text: format!("conversation {index} turn {turn}"),
+                }],
+            })
+            .await?;
+        }
+
+        let completed = timeout(
+            DEFAULT_READ_TIMEOUT,
+            mcp.read_stream_until_notification_messages("codex/event/task_complete", CONVERSATIONS),
+        )
+        .await??;
+        let completed: HashSet<String> = completed
+            .into_iter()
+            .filter_map(|notification| {
+                notification
+                    .params?
+                    .get("conversationId")?
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect();
+        let expected: HashSet<String> = conversation_ids.iter().map(|id| id.to_string()).collect();
+        assert_eq!(completed, expected, "turn {turn}");
+    }
+
+    // Every request carries the history of exactly one conversation, with
+    // one user message per turn so far and the replies to the earlier ones.
+    let requests = server
+        .received_requests()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("request recording is disabled"))?;
+    assert_eq!(requests.len(), CONVERSATIONS * TURNS);
+    let mut seen = HashSet::new();
+    for request in requests {
+        let body: Value = serde_json::from_slice(&request.body)?;
+        let history = conversation_history(&body);
+        let (last_role, last_text) = history
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("request without a turn: {body}"))?;
+        assert_eq!(last_role, "user");
+        let index = conversation_index(last_text);
+        let turn = history.len() / 2;
+        let expected: Vec<(String, String)> = (0..=turn)
+            .flat_map(|t| {
+                let user = format!("conversation {index} turn {t}");
+                let reply = format!("reply to {user}");
+                [("user".to_string(), user), ("assistant".to_string(), reply)]
+            })
+            .take(turn * 2 + 1)
+            .collect();
+        assert_eq!(history, expected);
+        seen.insert((index, turn));
+    }
+    assert_eq!(seen.len(), CONVERSATIONS * TURNS);
+
+    // An archived conversation no longer accepts messages.
+    let request_id = mcp
+        .send_archive_conversation_request(ArchiveConversationParams {
+            conversation_id: conversation_ids[0],
+        })
+        .await?;
+    let response = timeout(
+        DEFAULT_READ_TIMEOUT,
+        mcp.read_stream_until_response_message(RequestId::Integer(request_id)),
+    )
+    .await??;
+    let ArchiveConversationResponse {} = to_response(response)?;
+
+    let request_id = send_text(&mut mcp, conversation_ids[0], "hello?").await?;
+    let error = timeout(
+        DEFAULT_READ_TIMEOUT,
+        mcp.read_stream_until_error_message(RequestId::Integer(request_id)),
+    )
+    .await??;
+    assert_eq!(
+        error.error.message,
+        format!("conversation not found: {}", conversation_ids[0])
+    );
+    Ok(())
+}
+
+async fn send_text(
+    mcp: &mut McpProcess,
+    conversation_id: ConversationId,
+    text: &str,
+) -> anyhow::Result<i64> {
+    mcp.send_send_user_message_request(SendUserMessageParams {
+        conversation_id,
+        items: vec![InputItem::Text {
+            text: text.to_string(),
+        }],
+    })
+    .await
+}
+
+/// The (role, text) pairs of the test's own messages in a Chat Completions
+/// request, leaving out the system prompt and environment context.
+fn conversation_history(body: &Value) -> Vec<(String, String)> {
+    body["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|message| {
+            let role = message["role"].as_str()?;
+            let text = message["content"].as_str()?;
+            let ours = text.starts_with("conversation ") || text.starts_with("reply to ");
+            ours.then(|| (role.to_string(), text.to_string()))
+        })
+        .collect()
+}
+
+fn conversation_index(text: &str) -> usize {
+    text.split_whitespace()
+        .nth(1)
+        .and_then(|index| index.parse().ok())
+        .unwrap_or(usize::MAX)
+}
+
+/// Answers each request with "reply to <last user message>".
+struct EchoResponder;
+
+impl Respond for EchoResponder {
+    fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+        let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+        let last_user_message = conversation_history(&body)
+            .into_iter()
+            .rev()
+            .find(|(role, _)| role == "user")
+            .map(|(_, text)| text)
+            .unwrap_or_default();
+        match create_final_assistant_message_sse_response(&format!("reply to {last_user_message}"))
+        {
+            Ok(sse) => ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse, "text/event-stream"),
+            Err(err) => ResponseTemplate::new(500).set_body_string(err.to_string()),
+        }
+    }
+}
+
+fn create_config_toml(codex_home: &Path, server_uri: &str) -> std::io::Result<()> {
+    let config_toml = codex_home.join("config.toml");
+    std::fs::write(
+        config_toml,
+        format!(
+            r#"
+model = "mock-model"
+approval_policy = "never"
+sandbox_mode = "danger-full-access"
+
+model_provider = "mock_provider"
+
+[model_providers.mock_provider]
+name = "Mock provider for test"
+base_url = "{server_uri}/v1"
+wire_api = "chat"
+request_max_retries = 0
+stream_max_retries = 0
+"#
+        ),
+    )
+}
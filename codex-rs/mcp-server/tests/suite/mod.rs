@@ -2,6 +2,7 @@
 mod auth;
 mod codex_message_processor_flow;
 mod codex_tool;
+mod concurrent_conversations;
 mod config;
 mod create_conversation;
 mod interrupt;
@@ -3,7 +3,13 @@ mod parser;
 mod pull;
 mod url;
 
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+
 pub use client::OllamaClient;
+use codex_core::BUILT_IN_OSS_MODEL_PROVIDER_ID;
+use codex_core::ProviderKind;
 use codex_core::config::Config;
 pub use pull::CliProgressReporter;
 pub use pull::PullEvent;
@@ -13,25 +19,40 @@ pub use pull::TuiProgressReporter;
 /// Default OSS model to use when `--oss` is passed without an explicit `-m`.
 pub const DEFAULT_OSS_MODEL: &str = "gpt-oss:20b";
 
-/// Prepare the local OSS environment when `--oss` is selected.
+/// Whether the session talks to an Ollama server: either a provider declared
+/// with `kind = "ollama"` or the built-in `oss` provider.
+pub fn uses_ollama(config: &Config) -> bool {
+    config.model_provider.kind == ProviderKind::Ollama
+        || config.model_provider_id == BUILT_IN_OSS_MODEL_PROVIDER_ID
+}
+
+/// Prepare an Ollama-backed session before it starts.
 ///
-/// - Ensures a local Ollama server is reachable.
-/// - Checks if the model exists locally and pulls it if missing.
-pub async fn ensure_oss_ready(config: &Config) -> std::io::Result<()> {
-    // Only download when the requested model is the default OSS model (or when -m is not provided).
-    let model = config.model.as_ref();
+/// - Does nothing unless [`uses_ollama`] is true.
+/// - Ensures the Ollama server is reachable.
+/// - Checks if the model exists locally and, if `confirm_pull` agrees, pulls
+///   it while reporting progress to `reporter`.
+pub async fn ensure_model_ready(
+    config: &Config,
+    confirm_pull: impl FnOnce(&str) -> bool,
+    reporter: &mut dyn PullProgressReporter,
+) -> io::Result<()> {
+    if !uses_ollama(config) {
+        return Ok(());
+    }
+    let model = config.model.as_str();
 
     // Verify local Ollama is reachable.
-    let ollama_client = crate::OllamaClient::try_from_oss_provider(config).await?;
+    let ollama_client = OllamaClient::try_from_provider(&config.model_provider).await?;
 
-    // If the model is not present locally, pull it.
+    // If the model is not present locally, offer to pull it.
     match ollama_client.fetch_models().await {
         Ok(models) => {
-            if !models.iter().any(|m| m == model) {
-                let mut reporter = crate::CliProgressReporter::new();
-                ollama_client
-                    .pull_with_reporter(model, &mut reporter)
-                    .await?;
+            if !is_model_installed(&models, model) {
+                if !confirm_pull(model) {
+                    return Err(io::Error::other(missing_model_message(model, &models)));
+                }
+                ollama_client.pull_with_reporter(model, reporter).await?;
             }
         }
         Err(err) => {
@@ -42,3 +63,78 @@ pub async fn ensure_oss_ready(config: &Config) -> std::io::Result<()> {
 
     Ok(())
 }
+
+/// Asks on the terminal whether to pull `model`. Returns `false` without
+/// asking when stdin or stderr is not a terminal.
+pub fn confirm_pull_on_terminal(model: &str) -> bool {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return false;
+    }
+    let mut err = io::stderr();
+    if write!(
+        err,
+        "Model `{model}` is not available in Ollama. Pull it now? [Y/n] "
+    )
+    .and_then(|()| err.flush())
+    .is_err()
+    {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "" | "y" | "yes"
+    )
+}
+
+/// Ollama lists every model with an explicit tag, so `llama3` is installed
+/// as `llama3:latest`.
+fn is_model_installed(installed: &[String], model: &str) -> bool {
+    installed.iter().any(|name| {
+        name == model || (!model.contains(':') && name.strip_suffix(":latest") == Some(model))
+    })
+}
+
+fn missing_model_message(model: &str, installed: &[String]) -> String {
+    let mut message =
+        format!("Model `{model}` is not available in Ollama. Pull it with `ollama pull {model}`");
+    if installed.is_empty() {
+        message.push('.');
+    } else {
+        message.push_str(&format!(
+            " or use one of the installed models: {}.",
+            installed.join(", ")
+        ));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untagged_model_matches_latest() {
+        let installed = vec!["llama3:latest".to_string(), "gpt-oss:20b".to_string()];
+        assert!(is_model_installed(&installed, "llama3"));
+        assert!(is_model_installed(&installed, "llama3:latest"));
+        assert!(is_model_installed(&installed, "gpt-oss:20b"));
+        assert!(!is_model_installed(&installed, "gpt-oss"));
+        assert!(!is_model_installed(&installed, "gpt-oss:120b"));
+    }
+
+    #[test]
+    fn missing_model_message_lists_installed_models() {
+        assert_eq!(
+            missing_model_message("qwen3", &["llama3:latest".to_string()]),
+            "Model `qwen3` is not available in Ollama. Pull it with `ollama pull qwen3` or use one of the installed models: llama3:latest."
+        );
+        assert_eq!(
+            missing_model_message("qwen3", &[]),
+            "Model `qwen3` is not available in Ollama. Pull it with `ollama pull qwen3`."
+        );
+    }
+}
@@ -135,13 +135,106 @@ impl PullProgressReporter for CliProgressReporter {
     }
 }
 
-/// For now the TUI reporter delegates to the CLI reporter. This keeps UI and
-/// CLI behavior aligned until a dedicated TUI integration is implemented.
+/// Width of the bar drawn by [`TuiProgressReporter`], in cells.
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Renders pull progress as a single-line progress bar on stderr. The TUI
+/// runs this before it takes over the terminal.
 #[derive(Default)]
-pub struct TuiProgressReporter(CliProgressReporter);
+pub struct TuiProgressReporter {
+    last_line_len: usize,
+    totals_by_digest: HashMap<String, (u64, u64)>,
+}
+
+impl TuiProgressReporter {
+    fn write_line(&mut self, text: &str) -> io::Result<()> {
+        let mut out = std::io::stderr();
+        let pad = self.last_line_len.saturating_sub(text.chars().count());
+        self.last_line_len = text.chars().count();
+        out.write_all(format!("\r{text}{}", " ".repeat(pad)).as_bytes())?;
+        out.flush()
+    }
+}
 
 impl PullProgressReporter for TuiProgressReporter {
     fn on_event(&mut self, event: &PullEvent) -> io::Result<()> {
-        self.0.on_event(event)
+        match event {
+            PullEvent::Status(status) => {
+                if status.eq_ignore_ascii_case("pulling manifest") {
+                    return Ok(());
+                }
+                self.write_line(status)
+            }
+            PullEvent::ChunkProgress {
+                digest,
+                total,
+                completed,
+            } => {
+                let entry = self
+                    .totals_by_digest
+                    .entry(digest.clone())
+                    .or_insert((0, 0));
+                if let Some(t) = *total {
+                    entry.0 = t;
+                }
+                if let Some(c) = *completed {
+                    entry.1 = c;
+                }
+                let (sum_total, sum_completed) = self
+                    .totals_by_digest
+                    .values()
+                    .fold((0u64, 0u64), |acc, (t, c)| (acc.0 + *t, acc.1 + *c));
+                if sum_total == 0 {
+                    return Ok(());
+                }
+                let gb = |bytes: u64| (bytes as f64) / (1024.0 * 1024.0 * 1024.0);
+                let pct = (sum_completed as f64) * 100.0 / (sum_total as f64);
+                let line = format!(
+                    "{} {pct:5.1}% {:.2}/{:.2} GB",
+                    progress_bar(sum_completed, sum_total, PROGRESS_BAR_WIDTH),
+                    gb(sum_completed),
+                    gb(sum_total)
+                );
+                self.write_line(&line)
+            }
+            // Reported by the caller.
+            PullEvent::Error(_) => Ok(()),
+            PullEvent::Success => {
+                let mut out = std::io::stderr();
+                out.write_all(b"\n")?;
+                out.flush()
+            }
+        }
+    }
+}
+
+/// `[=====>    ]`-style bar for `completed` out of `total`.
+fn progress_bar(completed: u64, total: u64, width: usize) -> String {
+    let filled = if total == 0 {
+        0
+    } else {
+        ((completed.min(total) as f64 / total as f64) * width as f64) as usize
+    };
+    let mut bar = String::with_capacity(width + 2);
+    bar.push('[');
+    bar.push_str(&"=".repeat(filled));
+    if filled < width {
+        bar.push('>');
+        bar.push_str(&" ".repeat(width - filled - 1));
+    }
+    bar.push(']');
+    bar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_bar_fills_proportionally() {
+        assert_eq!(progress_bar(0, 100, 10), "[>         ]");
+        assert_eq!(progress_bar(50, 100, 10), "[=====>    ]");
+        assert_eq!(progress_bar(100, 100, 10), "[==========]");
+        assert_eq!(progress_bar(150, 100, 10), "[==========]");
     }
 }
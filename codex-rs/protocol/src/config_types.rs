@@ -59,3 +59,25 @@ pub struct ConfigProfile {
     pub approval_policy: Option<AskForApproval>,
     pub model_reasoning_effort: Option<ReasoningEffort>,
 }
+
+/// Where the `create_pull_request` tool opens pull requests.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Display, TS)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum PullRequestProvider {
+    /// GitHub, through `gh pr create`.
+    #[default]
+    Github,
+    /// GitLab, through `glab mr create`.
+    Gitlab,
+}
+
+impl PullRequestProvider {
+    /// What the provider calls a pull request.
+    pub fn request_noun(self) -> &'static str {
+        match self {
+            PullRequestProvider::Github => "pull request",
+            PullRequestProvider::Gitlab => "merge request",
+        }
+    }
+}
@@ -7,6 +7,7 @@ use crate::config_types::ReasoningEffort;
 use crate::config_types::ReasoningSummary;
 use crate::config_types::SandboxMode;
 use crate::protocol::AskForApproval;
+use crate::protocol::EscalationReason;
 use crate::protocol::FileChange;
 use crate::protocol::ReviewDecision;
 use crate::protocol::SandboxPolicy;
@@ -69,6 +70,11 @@ pub enum ClientRequest {
         request_id: RequestId,
         params: InterruptConversationParams,
     },
+    ArchiveConversation {
+        #[serde(rename = "id")]
+        request_id: RequestId,
+        params: ArchiveConversationParams,
+    },
     AddConversationListener {
         #[serde(rename = "id")]
         request_id: RequestId,
@@ -284,6 +290,19 @@ pub struct InterruptConversationResponse {
     pub abort_reason: TurnAbortReason,
 }
 
+/// Shuts down a conversation and forgets it. Its listeners receive the
+/// final `shutdown_complete` event; later requests for it fail with
+/// "conversation not found".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveConversationParams {
+    pub conversation_id: ConversationId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveConversationResponse {}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct SendUserMessageResponse {}
@@ -368,6 +387,12 @@ pub struct ExecCommandApprovalParams {
     pub cwd: PathBuf,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation_reason: Option<EscalationReason>,
+    /// What `ReviewDecision::ApprovedForSession` would approve: commands
+    /// starting with these words, or only this command when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_prefix: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
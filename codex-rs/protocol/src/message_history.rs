@@ -7,3 +7,11 @@ pub struct HistoryEntry {
     pub ts: u64,
     pub text: String,
 }
+
+/// An entry of the message history together with its position in the file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryMatch {
+    /// Zero-based offset of the entry, as used by `Op::GetHistoryEntryRequest`.
+    pub offset: usize,
+    pub entry: HistoryEntry,
+}
@@ -47,6 +47,8 @@ pub enum ResponseItem {
         content: Vec<ContentItem>,
     },
     Reasoning {
+        /// Empty when the item is replayed without server-side state.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         id: String,
         summary: Vec<ReasoningItemReasoningSummary>,
         #[serde(default, skip_serializing_if = "should_serialize_reasoning_content")]
@@ -250,6 +252,10 @@ pub struct ShellToolCallParams {
     pub with_escalated_permissions: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub justification: Option<String>,
+    /// Run the command under a pseudo-terminal so that it behaves as it would
+    /// in an interactive terminal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -362,6 +368,7 @@ mod tests {
                 timeout_ms: Some(1000),
                 with_escalated_permissions: None,
                 justification: None,
+                tty: None,
             },
             params
         );
@@ -12,6 +12,8 @@ use std::time::Duration;
 
 use crate::custom_prompts::CustomPrompt;
 use mcp_types::CallToolResult;
+use mcp_types::Prompt as McpPrompt;
+use mcp_types::Resource as McpResource;
 use mcp_types::Tool as McpTool;
 use serde::Deserialize;
 use serde::Serialize;
@@ -20,9 +22,12 @@ use strum_macros::Display;
 use ts_rs::TS;
 use uuid::Uuid;
 
+use crate::config_types::PullRequestProvider;
 use crate::config_types::ReasoningEffort as ReasoningEffortConfig;
 use crate::config_types::ReasoningSummary as ReasoningSummaryConfig;
+use crate::mcp_protocol::AuthMode;
 use crate::message_history::HistoryEntry;
+use crate::message_history::HistoryMatch;
 use crate::models::ResponseItem;
 use crate::parse_command::ParsedCommand;
 use crate::plan_tool::UpdatePlanArgs;
@@ -72,13 +77,42 @@ pub enum Op {
         /// associated with this conversation.
         model: String,
 
-        /// Will only be honored if the model is configured to use reasoning.
-        effort: ReasoningEffortConfig,
+        /// Reasoning effort for this turn only, taking precedence over the
+        /// configured one. Will only be honored if the model is configured to
+        /// use reasoning.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        effort: Option<ReasoningEffortConfig>,
 
         /// Will only be honored if the model is configured to use reasoning.
         summary: ReasoningSummaryConfig,
+
+        /// JSON schema the final assistant message of this turn must match.
+        /// Sent as a strict `json_schema` text format with the Responses API;
+        /// ignored by other wire APIs.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        final_output_json_schema: Option<serde_json::Value>,
     },
 
+    /// Input from the user that waits for the running task to finish. When
+    /// the session is idle it starts a task right away, like
+    /// [`Op::UserInput`]. Otherwise it joins the queue, and once the task
+    /// completes every queued entry is sent, in order, as the input of the
+    /// next task. Each change to the queue is reported via
+    /// `EventMsg::QueuedInputUpdated`; the submission id identifies the entry.
+    QueueUserInput {
+        /// User input items, see `InputItem`
+        items: Vec<InputItem>,
+    },
+
+    /// Request the input waiting for the running task to finish.
+    /// Reply is delivered via `EventMsg::QueuedInputUpdated`.
+    ListQueuedInput,
+
+    /// Take an entry out of the queue, e.g. to let the user edit it. `id` is
+    /// the id of the `Op::QueueUserInput` submission. The updated queue is
+    /// reported via `EventMsg::QueuedInputUpdated`.
+    RemoveQueuedInput { id: String },
+
     /// Override parts of the persistent turn context for subsequent turns.
     ///
     /// All fields are optional; when omitted, the existing value is preserved.
@@ -117,6 +151,10 @@ pub enum Op {
         id: String,
         /// The user's decision in response to the request.
         decision: ReviewDecision,
+        /// When denying, what to tell the model instead of the default
+        /// rejection message.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        feedback: Option<String>,
     },
 
     /// Approve a code patch
@@ -125,6 +163,14 @@ pub enum Op {
         id: String,
         /// The user's decision in response to the request.
         decision: ReviewDecision,
+        /// When approving, the parts of the patch the user rejected. `None`
+        /// applies the whole patch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        selection: Option<PatchSelection>,
+        /// When denying, what to tell the model instead of the default
+        /// rejection message.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        feedback: Option<String>,
     },
 
     /// Append an entry to the persistent cross-session message history.
@@ -139,6 +185,12 @@ pub enum Op {
     /// Request a single history entry identified by `log_id` + `offset`.
     GetHistoryEntryRequest { offset: usize, log_id: u64 },
 
+    /// Search the persistent message history for entries that contain
+    /// `query`, ignoring case, newest first and without repeats. An empty
+    /// query returns the most recent entries. At most `limit` entries are
+    /// returned. Reply is delivered via `EventMsg::SearchHistoryResponse`.
+    SearchHistory { query: String, limit: usize },
+
     /// Request the full in-memory conversation transcript for the current session.
     /// Reply is delivered via `EventMsg::ConversationHistory`.
     GetHistory,
@@ -147,13 +199,54 @@ pub enum Op {
     /// Reply is delivered via `EventMsg::McpListToolsResponse`.
     ListMcpTools,
 
+    /// Request the resources exposed by every configured MCP server.
+    /// Reply is delivered via `EventMsg::McpListResourcesResponse`.
+    ListMcpResources,
+
+    /// Request the prompts exposed by every configured MCP server.
+    /// Reply is delivered via `EventMsg::McpListPromptsResponse`.
+    ListMcpPrompts,
+
+    /// Render an MCP prompt and submit its messages as user input, starting a
+    /// new task. `name` is the fully qualified prompt name as reported by
+    /// `EventMsg::McpListPromptsResponse`, or the bare prompt name when only
+    /// one server offers it.
+    McpPrompt {
+        name: String,
+        arguments: HashMap<String, String>,
+    },
+
     /// Request the list of available custom prompts.
     ListCustomPrompts,
 
-    /// Request the agent to summarize the current conversation context.
-    /// The agent will use its existing context (either conversation history or previous response id)
-    /// to generate a summary which will be returned as an AgentMessage event.
+    /// Request the running token usage totals for the session.
+    /// Reply is delivered via `EventMsg::UsageUpdate`, followed by
+    /// `EventMsg::RateLimits` once the model has reported any.
+    GetUsage,
+
+    /// Request a snapshot of what the session works with: model, provider,
+    /// sandbox, instruction files, MCP servers and token usage.
+    /// Reply is delivered via `EventMsg::StatusResponse`.
+    GetStatus,
+
+    /// Request the agent to summarize the conversation history so far and
+    /// replace it with the summary. If a task is running, this happens before
+    /// its next request. Completion is reported via
+    /// `EventMsg::ConversationCompacted`.
     Compact,
+
+    /// Review the changes in the working tree against `base`, a git ref,
+    /// without modifying it. The task runs in a read-only sandbox and ends
+    /// with `EventMsg::ReviewFindings` before `EventMsg::TaskComplete`.
+    Review { base: String },
+
+    /// Restore the files changed by the most recent patch applied in this
+    /// session to what they were before it. Repeating it walks back through
+    /// earlier patches. Reported via `EventMsg::PatchUndone`, or
+    /// `EventMsg::Error` when there is nothing to undo or a file has changed
+    /// since the patch was applied.
+    UndoLastPatch,
+
     /// Request to shut down codex instance.
     Shutdown,
 }
@@ -213,6 +306,12 @@ pub enum SandboxPolicy {
         #[serde(default)]
         network_access: bool,
 
+        /// When `network_access` is `false`, hosts that commands may still
+        /// reach through a local HTTP(S) proxy. Each entry also allows its
+        /// subdomains.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        allowed_domains: Vec<String>,
+
         /// When set to `true`, will NOT include the per-user `TMPDIR`
         /// environment variable among the default writable roots. Defaults to
         /// `false`.
@@ -278,6 +377,7 @@ impl SandboxPolicy {
         SandboxPolicy::WorkspaceWrite {
             writable_roots: vec![],
             network_access: false,
+            allowed_domains: vec![],
             exclude_tmpdir_env_var: false,
             exclude_slash_tmp: false,
         }
@@ -304,6 +404,19 @@ impl SandboxPolicy {
         }
     }
 
+    /// Domains reachable through the network proxy, or `None` when network
+    /// access is either unrestricted or blocked entirely.
+    pub fn network_allowlist(&self) -> Option<&[String]> {
+        match self {
+            SandboxPolicy::WorkspaceWrite {
+                network_access: false,
+                allowed_domains,
+                ..
+            } if !allowed_domains.is_empty() => Some(allowed_domains),
+            _ => None,
+        }
+    }
+
     /// Returns the list of writable roots (tailored to the current working
     /// directory) together with subpaths that should remain read‑only under
     /// each writable root.
@@ -316,6 +429,7 @@ impl SandboxPolicy {
                 exclude_tmpdir_env_var,
                 exclude_slash_tmp,
                 network_access: _,
+                allowed_domains: _,
             } => {
                 // Start from explicitly configured writable roots.
                 let mut roots: Vec<PathBuf> = writable_roots.clone();
@@ -414,6 +528,34 @@ pub enum EventMsg {
     /// used in the current session.
     TokenCount(TokenUsage),
 
+    /// Running token totals for the session, sent whenever the provider
+    /// reports usage (possibly before the response completes) and in reply
+    /// to [`Op::GetUsage`].
+    UsageUpdate(UsageUpdateEvent),
+
+    /// Rate limit and quota status reported by the provider with the most
+    /// recent model response.
+    RateLimits(RateLimitSnapshot),
+
+    /// Latency of a model response, sent once it completes.
+    TurnMetrics(TurnMetrics),
+
+    /// Client-side estimate of the size of the prompt that is about to be
+    /// sent to the model, emitted before each request.
+    PromptTokenEstimate(PromptTokenEstimateEvent),
+
+    /// How full the context window is, sent after every model response of
+    /// a turn when the context window is known.
+    ContextUsage(ContextUsageEvent),
+
+    /// The context window filled past one of the configured
+    /// `context_warning_thresholds`.
+    ContextWindowWarning(ContextWindowWarningEvent),
+
+    /// Older conversation history was replaced by a summary, either on
+    /// request or because the next prompt would not fit the context window.
+    ConversationCompacted(ConversationCompactedEvent),
+
     /// Agent text output message
     AgentMessage(AgentMessageEvent),
 
@@ -441,10 +583,17 @@ pub enum EventMsg {
 
     McpToolCallEnd(McpToolCallEndEvent),
 
+    /// Progress an MCP server reported for an in-flight tool call.
+    McpToolCallProgress(McpToolCallProgressEvent),
+
     WebSearchBegin(WebSearchBeginEvent),
 
     WebSearchEnd(WebSearchEndEvent),
 
+    /// Partial arguments of a function call the model is still streaming.
+    /// The complete call is reported once the item finishes.
+    FunctionCallArgumentsDelta(FunctionCallArgumentsDeltaEvent),
+
     /// Notification that the server is about to execute a command.
     ExecCommandBegin(ExecCommandBeginEvent),
 
@@ -463,6 +612,14 @@ pub enum EventMsg {
     /// and the system is handling it (e.g., retrying with backoff).
     StreamError(StreamErrorEvent),
 
+    /// The model response, run in background mode, is still queued or in
+    /// progress on the server. Sent periodically while its stream is quiet.
+    BackgroundResponseProgress(BackgroundResponseProgressEvent),
+
+    /// Tool output of the current turn exceeded its budget; further output
+    /// is truncated. Sent at most once per turn.
+    ToolOutputBudgetExceeded(ToolOutputBudgetExceededEvent),
+
     /// Notification that the agent is about to apply a code patch. Mirrors
     /// `ExecCommandBegin` so front‑ends can show progress indicators.
     PatchApplyBegin(PatchApplyBeginEvent),
@@ -470,14 +627,59 @@ pub enum EventMsg {
     /// Notification that a patch application has finished.
     PatchApplyEnd(PatchApplyEndEvent),
 
+    /// The files of a previously applied patch were restored by
+    /// `Op::UndoLastPatch`.
+    PatchUndone(PatchUndoneEvent),
+
     TurnDiff(TurnDiffEvent),
 
+    /// Findings reported by the model at the end of an `Op::Review` task.
+    ReviewFindings(ReviewFindingsEvent),
+
     /// Response to GetHistoryEntryRequest.
     GetHistoryEntryResponse(GetHistoryEntryResponseEvent),
 
+    /// Response to SearchHistory.
+    SearchHistoryResponse(SearchHistoryResponseEvent),
+
     /// List of MCP tools available to the agent.
     McpListToolsResponse(McpListToolsResponseEvent),
 
+    /// Response to `Op::GetStatus`.
+    StatusResponse(SessionStatus),
+
+    /// The input queued with `Op::QueueUserInput` changed, or was requested
+    /// with `Op::ListQueuedInput`.
+    QueuedInputUpdated(QueuedInputEvent),
+
+    /// List of resources offered by the configured MCP servers.
+    McpListResourcesResponse(McpListResourcesResponseEvent),
+
+    /// List of prompts offered by the configured MCP servers.
+    McpListPromptsResponse(McpListPromptsResponseEvent),
+
+    /// MCP servers that could not be started. The session carries on
+    /// without their tools.
+    McpStartupWarning(McpStartupWarningEvent),
+
+    /// The provider rejected the credentials, even after refreshing the
+    /// token. The turn waits until `auth.json` changes, e.g. after
+    /// `codex login` in another terminal, and is then sent again.
+    /// `Op::Interrupt` abandons it.
+    AuthExpired(AuthExpiredEvent),
+
+    /// The session's working directory is not a git repository, or has
+    /// uncommitted changes while the sandbox allows writes.
+    GitRepoWarning(GitRepoWarningEvent),
+
+    /// `AGENTS.md` files did not fit `project_doc_max_bytes` and were cut
+    /// short or left out of the instructions.
+    ProjectDocTruncated(ProjectDocTruncatedEvent),
+
+    /// The `create_pull_request` tool opened a pull request, or showed what
+    /// it would have run in a dry run.
+    PullRequest(PullRequestEvent),
+
     /// List of custom prompts available to the agent.
     ListCustomPromptsResponse(ListCustomPromptsResponseEvent),
 
@@ -496,6 +698,31 @@ pub enum EventMsg {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ErrorEvent {
     pub message: String,
+    /// Category of an error reported by the model provider, so clients can
+    /// react to it without parsing `message`. `None` for other errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ErrorKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The conversation no longer fits the model's context window.
+    ContextWindowExceeded,
+    /// The account is out of quota or has hit its usage limit.
+    QuotaExceeded,
+    /// The provider rejected the credentials.
+    AuthExpired,
+    /// The response stream ended early and retrying did not help.
+    StreamDisconnected,
+    /// The provider rejected the request for another reason.
+    ProviderError,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolOutputBudgetExceededEvent {
+    /// Bytes of tool output the turn may add to the conversation.
+    pub budget_bytes: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -508,7 +735,82 @@ pub struct TaskStartedEvent {
     pub model_context_window: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PromptTokenEstimateEvent {
+    /// Approximate number of input tokens in the next request.
+    pub estimated_tokens: u64,
+    pub model_context_window: Option<u64>,
+}
+
+/// Client-side estimate of where the tokens of a prompt go.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+pub struct ContextBreakdown {
+    /// Base instructions, including tool-specific instructions.
+    pub instructions_tokens: u64,
+    /// JSON schemas of the tools offered to the model.
+    pub tool_schema_tokens: u64,
+    /// User instructions (`AGENTS.md`) and the environment context.
+    pub environment_tokens: u64,
+    /// Every other item of the conversation history.
+    pub conversation_tokens: u64,
+}
+
+impl ContextBreakdown {
+    /// Tokens every request carries regardless of the conversation.
+    pub fn overhead_tokens(&self) -> u64 {
+        self.instructions_tokens + self.tool_schema_tokens + self.environment_tokens
+    }
+
+    pub fn total(&self) -> u64 {
+        self.overhead_tokens() + self.conversation_tokens
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContextUsageEvent {
+    /// Tokens the next request is projected to take, including the
+    /// instructions, tool schemas and environment context.
+    pub tokens_in_context: u64,
+    pub context_window: u64,
+    /// Share of the context window still free, from 0 to 100.
+    pub percent_left: u8,
+    /// Estimated split of the most recent prompt.
+    pub breakdown: ContextBreakdown,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContextWindowWarningEvent {
+    /// The threshold that was crossed, as a percentage of the context window
+    /// in use.
+    pub threshold_percent: u8,
+    /// Share of the context window still free, from 0 to 100.
+    pub percent_left: u8,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConversationCompactedEvent {
+    /// Number of history items replaced by the summary.
+    pub summarized_items: usize,
+    /// Approximate tokens of the replaced items.
+    pub tokens_before: u64,
+    /// Approximate tokens of the summary that replaced them.
+    pub tokens_after: u64,
+    /// `true` when triggered by nearing the context window rather than by
+    /// `Op::Compact`.
+    pub automatic: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PatchUndoneEvent {
+    /// Identifier of the `apply_patch` call that was undone.
+    pub call_id: String,
+    /// Files written back with their contents from before the patch.
+    pub restored: Vec<PathBuf>,
+    /// Files the patch created, or moved a file to, that were removed.
+    pub removed: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq)]
 pub struct TokenUsage {
     pub input_tokens: u64,
     pub cached_input_tokens: Option<u64>,
@@ -522,6 +824,27 @@ impl TokenUsage {
         self.total_tokens == 0
     }
 
+    /// Returns the field-wise sum of `self` and `other`.
+    pub fn add_usage(&self, other: &TokenUsage) -> TokenUsage {
+        fn add_optional(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, None) => a,
+                (None, b) => b,
+            }
+        }
+        TokenUsage {
+            input_tokens: self.input_tokens + other.input_tokens,
+            cached_input_tokens: add_optional(self.cached_input_tokens, other.cached_input_tokens),
+            output_tokens: self.output_tokens + other.output_tokens,
+            reasoning_output_tokens: add_optional(
+                self.reasoning_output_tokens,
+                other.reasoning_output_tokens,
+            ),
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+
     pub fn cached_input(&self) -> u64 {
         self.cached_input_tokens.unwrap_or(0)
     }
@@ -543,35 +866,99 @@ impl TokenUsage {
         self.total_tokens
             .saturating_sub(self.reasoning_output_tokens.unwrap_or(0))
     }
+}
 
-    /// Estimate the remaining user-controllable percentage of the model's context window.
-    ///
-    /// `context_window` is the total size of the model's context window.
-    /// `baseline_used_tokens` should capture tokens that are always present in
-    /// the context (e.g., system prompt and fixed tool instructions) so that
-    /// the percentage reflects the portion the user can influence.
-    ///
-    /// This normalizes both the numerator and denominator by subtracting the
-    /// baseline, so immediately after the first prompt the UI shows 100% left
-    /// and trends toward 0% as the user fills the effective window.
-    pub fn percent_of_context_window_remaining(
-        &self,
-        context_window: u64,
-        baseline_used_tokens: u64,
-    ) -> u8 {
-        if context_window <= baseline_used_tokens {
-            return 0;
-        }
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UsageUpdateEvent {
+    /// Usage summed over every model response in the session so far,
+    /// including the one currently streaming.
+    pub total_token_usage: TokenUsage,
+    /// Usage of the most recent (or in-flight) model response.
+    pub last_token_usage: TokenUsage,
+    /// Estimated cost in US dollars of the completed model responses in the
+    /// session. `None` once a response came from a model without known
+    /// prices.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
+    /// Estimated cost in US dollars of the most recent completed response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_cost_usd: Option<f64>,
+}
+
+/// How long a model response took, measured by the client from the time it
+/// started sending the request.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TurnMetrics {
+    /// Until the first event of the response stream arrived, i.e. after any
+    /// retries.
+    pub time_to_first_byte_ms: Option<u64>,
+    /// Until the first delta of the assistant message arrived. `None` when
+    /// the response had no message text.
+    pub time_to_first_token_ms: Option<u64>,
+    /// Until the response completed.
+    pub stream_duration_ms: u64,
+    /// Requests, dropped streams and turn attempts that were retried before
+    /// the response completed.
+    pub retries: u64,
+    /// History items left out of the request because the provider would
+    /// reject them, such as items of unknown types or reasoning the model
+    /// cannot take back.
+    #[serde(default)]
+    pub dropped_input_items: u64,
+}
 
-        let effective_window = context_window - baseline_used_tokens;
-        let used = self
-            .tokens_in_context_window()
-            .saturating_sub(baseline_used_tokens);
-        let remaining = effective_window.saturating_sub(used);
-        ((remaining as f32 / effective_window as f32) * 100.0).clamp(0.0, 100.0) as u8
+/// Rate limits parsed from the headers of a model response. Windows the
+/// provider did not report are `None`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct RateLimitSnapshot {
+    /// Request limit (`x-ratelimit-*-requests`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests: Option<RateLimitWindow>,
+    /// Token limit (`x-ratelimit-*-tokens`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<RateLimitWindow>,
+    /// ChatGPT plan usage over its short window (`x-codex-primary-*`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<RateLimitWindow>,
+    /// ChatGPT plan usage over the week (`x-codex-secondary-*`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly: Option<RateLimitWindow>,
+}
+
+impl RateLimitSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.windows().next().is_none()
+    }
+
+    /// The reported windows with their names: `requests`, `tokens`, `plan`
+    /// and `weekly`.
+    pub fn windows(&self) -> impl Iterator<Item = (&'static str, &RateLimitWindow)> {
+        [
+            ("requests", &self.requests),
+            ("tokens", &self.tokens),
+            ("plan", &self.plan),
+            ("weekly", &self.weekly),
+        ]
+        .into_iter()
+        .filter_map(|(name, window)| window.as_ref().map(|window| (name, window)))
     }
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct RateLimitWindow {
+    /// Share of the limit already used, from 0 to 100.
+    pub used_percent: f64,
+    /// Unix time, in seconds, at which the window resets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_resets_at: Option<u64>,
+    /// Size of the limit, when the provider reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// What is left of the limit, when the provider reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining: Option<u64>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FinalOutput {
     pub token_usage: TokenUsage,
@@ -610,6 +997,14 @@ pub struct AgentMessageEvent {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FunctionCallArgumentsDeltaEvent {
+    /// Identifier of the function call the arguments belong to.
+    pub call_id: String,
+    /// Next chunk of the (JSON) arguments string.
+    pub delta: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AgentMessageDeltaEvent {
     pub delta: String,
@@ -644,8 +1039,13 @@ pub struct McpInvocation {
     pub server: String,
     /// Name of the tool as given by the MCP server.
     pub tool: String,
-    /// Arguments to the tool call.
+    /// Arguments to the tool call. When `arguments_truncated` is set, this is
+    /// a string holding the start of the arguments' JSON instead.
     pub arguments: Option<serde_json::Value>,
+    /// Whether `arguments` was cut to `mcp_event_max_argument_bytes`. The
+    /// server always receives the full arguments.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub arguments_truncated: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -665,6 +1065,19 @@ pub struct McpToolCallEndEvent {
     pub result: Result<CallToolResult, String>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpToolCallProgressEvent {
+    /// Identifier of the McpToolCallBegin this progress belongs to.
+    pub call_id: String,
+    /// Progress so far. Increases with every notification, even when `total`
+    /// is unknown.
+    pub progress: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
 impl McpToolCallEndEvent {
     pub fn is_success(&self) -> bool {
         match &self.result {
@@ -721,6 +1134,40 @@ pub struct ExecCommandEndEvent {
     pub duration: Duration,
     /// Formatted output from the command, as seen by the model.
     pub formatted_output: String,
+    /// Set when Codex killed the command because it hit a limit, in which
+    /// case the output is partial.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminated: Option<ExecTermination>,
+}
+
+/// Why Codex killed a command before it exited on its own. The whole process
+/// group is killed, so commands the command started are stopped too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum ExecTermination {
+    /// The command ran for longer than its timeout.
+    TimedOut { timeout_ms: u64 },
+    /// The command wrote more than this many bytes of stdout and stderr.
+    OutputLimitExceeded { max_bytes: u64 },
+}
+
+impl fmt::Display for ExecTermination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecTermination::TimedOut { timeout_ms } if timeout_ms % 1000 == 0 => {
+                write!(f, "timed out after {} seconds", timeout_ms / 1000)
+            }
+            ExecTermination::TimedOut { timeout_ms } => {
+                write!(f, "timed out after {timeout_ms} milliseconds")
+            }
+            ExecTermination::OutputLimitExceeded { max_bytes } => {
+                write!(
+                    f,
+                    "killed after writing more than {max_bytes} bytes of output"
+                )
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -752,6 +1199,102 @@ pub struct ExecApprovalRequestEvent {
     /// Optional human-readable reason for the approval (e.g. retry without sandbox).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
+    /// Why the command cannot run in the sandbox without approval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation_reason: Option<EscalationReason>,
+    /// The words a command must start with to run without asking once this
+    /// one is approved for the session; `None` when only this exact command
+    /// would be.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_prefix: Option<Vec<String>>,
+}
+
+/// Why a command needs the user's approval to run, or to run again, outside
+/// the sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EscalationReason {
+    /// The command is not known to be safe and the approval policy asks
+    /// about every such command.
+    UnrecognizedCommand,
+    /// The model asked to run the command outside the sandbox.
+    EscalationRequested,
+    /// There is no sandbox on this platform to confine the command.
+    NoSandbox,
+    /// The command looks like it uses the network, which the sandbox cannot
+    /// restrict on this system.
+    NetworkNeeded,
+    /// The change writes outside the workspace and the writable roots.
+    WritesOutsideWorkspace { paths: Vec<PathBuf> },
+    /// The first change in a directory that is not a clean git repository.
+    FirstWriteOutsideGit,
+    /// A custom tool whose configuration asks for approval of every call.
+    ToolRequiresApproval { tool: String },
+    /// The command failed in the sandbox, most likely because the sandbox
+    /// blocked it. Whatever could be read from its error output is filled in.
+    SandboxDenied {
+        /// The path the command was denied access to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+        /// The system call that failed, e.g. `open` or `connect`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        syscall: Option<String>,
+        /// The symbolic errno the command reported, e.g. `EPERM`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        errno: Option<String>,
+    },
+}
+
+impl fmt::Display for EscalationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscalationReason::UnrecognizedCommand => {
+                write!(f, "the command is not on the list of trusted commands")
+            }
+            EscalationReason::EscalationRequested => {
+                write!(f, "Codex asked to run the command outside the sandbox")
+            }
+            EscalationReason::NoSandbox => {
+                write!(f, "no sandbox is available on this platform")
+            }
+            EscalationReason::NetworkNeeded => write!(
+                f,
+                "the command may use the network, which the sandbox cannot restrict on this system"
+            ),
+            EscalationReason::WritesOutsideWorkspace { paths } => {
+                write!(f, "writes outside the workspace")?;
+                for (i, path) in paths.iter().enumerate() {
+                    let separator = if i == 0 { ": " } else { ", " };
+                    write!(f, "{separator}{}", path.display())?;
+                }
+                Ok(())
+            }
+            EscalationReason::FirstWriteOutsideGit => write!(
+                f,
+                "first change in a directory that is not a clean git repository"
+            ),
+            EscalationReason::ToolRequiresApproval { tool } => {
+                write!(f, "the `{tool}` tool is configured to require approval")
+            }
+            EscalationReason::SandboxDenied {
+                path,
+                syscall,
+                errno,
+            } => {
+                write!(f, "the sandbox blocked ")?;
+                match (syscall, path) {
+                    (Some(syscall), Some(path)) => write!(f, "{syscall} on {}", path.display())?,
+                    (None, Some(path)) => write!(f, "access to {}", path.display())?,
+                    (Some(syscall), None) => write!(f, "{syscall}")?,
+                    (None, None) => write!(f, "the command")?,
+                }
+                if let Some(errno) = errno {
+                    write!(f, " ({errno})")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -777,6 +1320,14 @@ pub struct StreamErrorEvent {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackgroundResponseProgressEvent {
+    /// Status reported by the server: `queued` or `in_progress`.
+    pub status: String,
+    /// Seconds since the request was sent.
+    pub elapsed_seconds: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PatchApplyBeginEvent {
     /// Identifier so this can be paired with the PatchApplyEnd event.
@@ -797,6 +1348,24 @@ pub struct PatchApplyEndEvent {
     pub stderr: String,
     /// Whether the patch was applied successfully.
     pub success: bool,
+    /// How closely the context of each updated file matched, so fuzzy
+    /// applications can be audited.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub match_strategies: HashMap<PathBuf, PatchMatchStrategy>,
+}
+
+/// How the lines an `apply_patch` chunk replaces were located in the file,
+/// from strictest to loosest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchMatchStrategy {
+    /// The lines matched byte for byte.
+    Exact,
+    /// The lines matched once whitespace and typographic punctuation were
+    /// ignored.
+    IgnoreWhitespace,
+    /// The lines were the most similar region near the expected position.
+    Fuzzy,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -804,6 +1373,48 @@ pub struct TurnDiffEvent {
     pub unified_diff: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReviewFindingsEvent {
+    /// Ref the working tree was compared against.
+    pub base: String,
+    pub findings: Vec<ReviewFinding>,
+}
+
+impl ReviewFindingsEvent {
+    /// Number of findings that should block the change from merging.
+    pub fn blocking_count(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == ReviewSeverity::Blocking)
+            .count()
+    }
+}
+
+/// One comment on the reviewed diff.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, TS)]
+pub struct ReviewFinding {
+    /// Path relative to the repository root.
+    pub file: String,
+    /// First line the comment applies to, in the new version of the file.
+    pub line_start: u32,
+    /// Last line the comment applies to (inclusive).
+    pub line_end: u32,
+    pub severity: ReviewSeverity,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Display, TS)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ReviewSeverity {
+    /// Must be fixed before the change can merge.
+    Blocking,
+    /// Likely a problem, but not a merge blocker.
+    Warning,
+    /// Style or minor suggestions.
+    Nit,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GetHistoryEntryResponseEvent {
     pub offset: usize,
@@ -813,11 +1424,185 @@ pub struct GetHistoryEntryResponseEvent {
     pub entry: Option<HistoryEntry>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SearchHistoryResponseEvent {
+    /// The query this is a response to.
+    pub query: String,
+    pub log_id: u64,
+    /// Matching entries, newest first.
+    pub matches: Vec<HistoryMatch>,
+}
+
 /// Response payload for `Op::ListMcpTools`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct McpListToolsResponseEvent {
     /// Fully qualified tool name -> tool definition.
     pub tools: std::collections::HashMap<String, McpTool>,
+
+    /// Server name -> how its tools are offered to the model. Servers that
+    /// are not running are left out.
+    #[serde(default)]
+    pub servers: std::collections::HashMap<String, McpServerTools>,
+}
+
+/// The tools of one MCP server after `enabled_tools`, `disabled_tools`,
+/// `tool_prefix` and `tool_names` are applied.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct McpServerTools {
+    /// Name offered to the model -> name on the server.
+    pub tools: std::collections::BTreeMap<String, String>,
+
+    /// Tools on the server that are not offered to the model.
+    pub hidden: Vec<String>,
+}
+
+/// Snapshot of a session returned for `Op::GetStatus`. Describes the
+/// persistent turn context, i.e. including any `Op::OverrideTurnContext`
+/// applied so far.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionStatus {
+    pub session_id: Uuid,
+    pub model: ModelStatus,
+    pub provider: ProviderStatus,
+    pub reasoning_effort: ReasoningEffortConfig,
+    pub reasoning_summary: ReasoningSummaryConfig,
+    pub approval_policy: AskForApproval,
+    pub sandbox_policy: SandboxPolicy,
+    /// Directories commands may write to under `sandbox_policy`, resolved
+    /// against `cwd`. Empty for read-only and full access.
+    pub writable_roots: Vec<PathBuf>,
+    /// Per-session directory that is writable under every sandbox policy.
+    pub scratch_dir: Option<PathBuf>,
+    /// Whether commands may reach the network.
+    pub network_access: bool,
+    pub cwd: PathBuf,
+    /// Project instruction files (`AGENTS.md`) merged into the user
+    /// instructions, ordered from the repository root down to the cwd.
+    pub instruction_files: Vec<PathBuf>,
+    /// Configured MCP servers, sorted by name.
+    pub mcp_servers: Vec<McpServerStatus>,
+    /// Token usage summed over the session.
+    pub token_usage: TokenUsage,
+    /// Estimated cost in US dollars of `token_usage`, when the prices of
+    /// every model used are known.
+    #[serde(default)]
+    pub total_cost_usd: Option<f64>,
+    /// File the conversation is recorded to, if recording works.
+    pub rollout_path: Option<PathBuf>,
+    /// How full the context window was after the latest model response.
+    /// `None` before the first response or when the context window is not
+    /// known.
+    #[serde(default)]
+    pub context_usage: Option<ContextUsageEvent>,
+    /// How requests to the model are authenticated. `None` without a stored
+    /// login, e.g. for providers that take their key from the environment.
+    pub auth_mode: Option<AuthMode>,
+}
+
+/// The model a session talks to and what its family supports.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModelStatus {
+    pub slug: String,
+    pub family: String,
+    pub context_window: Option<u64>,
+    pub max_output_tokens: Option<u64>,
+    pub supports_reasoning_summaries: bool,
+    pub supports_image_input: bool,
+    pub uses_local_shell_tool: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProviderStatus {
+    /// Key of the provider in `model_providers`.
+    pub id: String,
+    pub name: String,
+    /// `"responses"`, `"chat"` or `"anthropic"`.
+    pub wire_api: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpServerStatus {
+    pub name: String,
+    /// False for servers that failed to start and for lazily started ones
+    /// that have not been needed yet.
+    pub running: bool,
+    /// Number of tools offered to the model.
+    pub tool_count: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthExpiredEvent {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpStartupWarningEvent {
+    /// Server name -> why it failed to start.
+    pub failures: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitRepoWarningEvent {
+    pub cwd: PathBuf,
+    pub kind: GitRepoWarningKind,
+    /// Whether the first command or patch that can write will ask for
+    /// approval, whatever the sandbox policy allows.
+    pub confirmation_required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitRepoWarningKind {
+    /// The working directory is not inside a git repository, so changes
+    /// cannot be reviewed or reverted with git.
+    NotARepo,
+    /// Tracked files have uncommitted changes that Codex's edits would be
+    /// mixed with.
+    Dirty,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProjectDocTruncatedEvent {
+    /// The byte budget the docs share.
+    pub limit_bytes: usize,
+    pub files: Vec<TruncatedProjectDoc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TruncatedProjectDoc {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Bytes of the file that the model does not see; `size_bytes` when the
+    /// whole file was left out.
+    pub omitted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PullRequestEvent {
+    pub call_id: String,
+    pub provider: PullRequestProvider,
+    pub title: String,
+    pub head: String,
+    /// The branch to merge into; `None` leaves it to the provider's default.
+    pub base: Option<String>,
+    /// The new pull request; `None` in a dry run.
+    pub url: Option<String>,
+    /// In a dry run, the script that would have run.
+    pub dry_run_script: Option<String>,
+}
+
+/// Response payload for `Op::ListMcpResources`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpListResourcesResponseEvent {
+    /// Server name -> resources offered by that server.
+    pub resources: std::collections::HashMap<String, Vec<McpResource>>,
+}
+
+/// Response payload for `Op::ListMcpPrompts`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpListPromptsResponseEvent {
+    /// Fully qualified prompt name -> prompt definition.
+    pub prompts: std::collections::HashMap<String, McpPrompt>,
 }
 
 /// Response payload for `Op::ListCustomPrompts`.
@@ -839,6 +1624,15 @@ pub struct SessionConfiguredEvent {
 
     /// Current number of entries in the history log.
     pub history_entry_count: usize,
+
+    /// Project instruction files (`AGENTS.md`) merged into the user
+    /// instructions, ordered from the repository root down to the cwd.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub project_docs: Vec<PathBuf>,
+
+    /// File the conversation is recorded to, if recording works.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollout_path: Option<PathBuf>,
 }
 
 /// User's decision in response to an ExecApprovalRequest.
@@ -863,13 +1657,76 @@ pub enum ReviewDecision {
     Abort,
 }
 
+/// The parts of a patch the user rejected while reviewing it; everything
+/// else is applied.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq, TS)]
+pub struct PatchSelection {
+    /// Files the patch must leave alone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected_files: Vec<PathBuf>,
+    /// For updated files, the `@@` hunks of their [`FileChange::Update`]
+    /// `unified_diff` to leave out.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rejected_hunks: HashMap<PathBuf, Vec<HunkRange>>,
+}
+
+/// Identifies a hunk of a unified diff by the lines of the original file it
+/// covers, as given in its `@@ -start,lines` header. Line numbers are 1-based;
+/// an empty range is numbered by the line before it.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, TS)]
+pub struct HunkRange {
+    pub old_start: usize,
+    pub old_lines: usize,
+}
+
+impl HunkRange {
+    /// Reads the old range of a header such as `@@ -3,2 +3,4 @@`, where a
+    /// missing length means one line.
+    pub fn from_header(header: &str) -> Option<Self> {
+        let range = header.strip_prefix("@@ -")?.split(' ').next()?;
+        let (start, lines) = match range.split_once(',') {
+            Some((start, lines)) => (start, lines.parse().ok()?),
+            None => (range, 1),
+        };
+        Some(Self {
+            old_start: start.parse().ok()?,
+            old_lines: lines,
+        })
+    }
+}
+
+impl fmt::Display for HunkRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-{},{}", self.old_start, self.old_lines)
+    }
+}
+
+impl PatchSelection {
+    /// Whether nothing was rejected.
+    pub fn is_empty(&self) -> bool {
+        self.rejected_files.is_empty() && self.rejected_hunks.values().all(Vec::is_empty)
+    }
+
+    pub fn is_file_rejected(&self, path: &Path) -> bool {
+        self.rejected_files.iter().any(|rejected| rejected == path)
+    }
+
+    pub fn is_hunk_rejected(&self, path: &Path, hunk: HunkRange) -> bool {
+        self.rejected_hunks
+            .get(path)
+            .is_some_and(|hunks| hunks.contains(&hunk))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, TS)]
 #[serde(rename_all = "snake_case")]
 pub enum FileChange {
     Add {
         content: String,
     },
-    Delete,
+    Delete {
+        content: String,
+    },
     Update {
         unified_diff: String,
         move_path: Option<PathBuf>,
@@ -884,6 +1741,25 @@ pub struct Chunk {
     pub inserted_lines: Vec<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QueuedInputEvent {
+    /// Input still waiting, oldest first.
+    pub queue: Vec<QueuedInput>,
+    /// Entries that just left the queue as the input of a new task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub started: Vec<QueuedInput>,
+    /// Entries dropped because the running task was interrupted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cleared: Vec<QueuedInput>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct QueuedInput {
+    /// Id of the `Op::QueueUserInput` submission.
+    pub id: String,
+    pub items: Vec<InputItem>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TurnAbortedEvent {
     pub reason: TurnAbortReason,
@@ -912,6 +1788,8 @@ mod tests {
                 model: "codex-mini-latest".to_string(),
                 history_log_id: 0,
                 history_entry_count: 0,
+                project_docs: Vec::new(),
+                rollout_path: None,
             }),
         };
         let serialized = serde_json::to_string(&event).unwrap();
@@ -920,4 +1798,91 @@ mod tests {
             r#"{"id":"1234","msg":{"type":"session_configured","session_id":"67e55044-10b1-426f-9247-bb680e5fe0c8","model":"codex-mini-latest","history_log_id":0,"history_entry_count":0}}"#
         );
     }
+
+    #[test]
+    fn serialize_mcp_tool_call_events() {
+        let begin = EventMsg::McpToolCallBegin(McpToolCallBeginEvent {
+            call_id: "call_1".to_string(),
+            invocation: McpInvocation {
+                server: "docs".to_string(),
+                tool: "search".to_string(),
+                arguments: Some(serde_json::json!("{\"query\":\"cod")),
+                arguments_truncated: true,
+            },
+        });
+        assert_eq!(
+            serde_json::to_string(&begin).unwrap(),
+            r#"{"type":"mcp_tool_call_begin","call_id":"call_1","invocation":{"server":"docs","tool":"search","arguments":"{\"query\":\"cod","arguments_truncated":true}}"#
+        );
+
+        let progress = EventMsg::McpToolCallProgress(McpToolCallProgressEvent {
+            call_id: "call_1".to_string(),
+            progress: 2.0,
+            total: Some(5.0),
+            message: None,
+        });
+        assert_eq!(
+            serde_json::to_string(&progress).unwrap(),
+            r#"{"type":"mcp_tool_call_progress","call_id":"call_1","progress":2.0,"total":5.0}"#
+        );
+    }
+
+    #[test]
+    fn serialize_exec_approval_escalation_reason() {
+        let event = EventMsg::ExecApprovalRequest(ExecApprovalRequestEvent {
+            call_id: "call_1".to_string(),
+            command: vec!["touch".to_string(), "/etc/motd".to_string()],
+            cwd: PathBuf::from("/repo"),
+            reason: None,
+            escalation_reason: Some(EscalationReason::SandboxDenied {
+                path: Some(PathBuf::from("/etc/motd")),
+                syscall: Some("open".to_string()),
+                errno: Some("EROFS".to_string()),
+            }),
+        });
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"exec_approval_request","call_id":"call_1","command":["touch","/etc/motd"],"cwd":"/repo","escalation_reason":{"type":"sandbox_denied","path":"/etc/motd","syscall":"open","errno":"EROFS"}}"#
+        );
+
+        let reasons = [
+            (
+                EscalationReason::SandboxDenied {
+                    path: Some(PathBuf::from("/etc/motd")),
+                    syscall: Some("open".to_string()),
+                    errno: Some("EROFS".to_string()),
+                },
+                "the sandbox blocked open on /etc/motd (EROFS)",
+            ),
+            (
+                EscalationReason::SandboxDenied {
+                    path: None,
+                    syscall: None,
+                    errno: None,
+                },
+                "the sandbox blocked the command",
+            ),
+            (
+                EscalationReason::WritesOutsideWorkspace {
+                    paths: vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")],
+                },
+                "writes outside the workspace: /tmp/a, /tmp/b",
+            ),
+        ];
+        for (reason, expected) in reasons {
+            assert_eq!(reason.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn hunk_ranges_are_read_from_headers() {
+        let range = |old_start, old_lines| HunkRange {
+            old_start,
+            old_lines,
+        };
+        assert_eq!(HunkRange::from_header("@@ -3,2 +3,4 @@"), Some(range(3, 2)));
+        assert_eq!(HunkRange::from_header("@@ -9 +9 @@"), Some(range(9, 1)));
+        assert_eq!(HunkRange::from_header("@@ -0,0 +1,3 @@"), Some(range(0, 0)));
+        assert_eq!(HunkRange::from_header(" context"), None);
+    }
 }
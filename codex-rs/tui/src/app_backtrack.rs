@@ -25,6 +25,8 @@ pub(crate) struct BacktrackState {
 
 impl App {
     /// Route overlay events when transcript overlay is active.
+    /// - While a search query is typed, every key goes to the overlay; while
+    ///   matches are highlighted, Esc ends the search instead of backtracking.
     /// - If backtrack preview is active: Esc steps selection; Enter confirms.
     /// - Otherwise: Esc begins preview; all other events forward to overlay.
     ///   interactions (Esc to step target, Enter to confirm) and overlay lifecycle.
@@ -33,6 +35,20 @@ impl App {
         tui: &mut tui::Tui,
         event: TuiEvent,
     ) -> Result<bool> {
+        if let Some(Overlay::Transcript(t)) = &self.overlay
+            && (t.is_editing_search()
+                || (t.search_is_active()
+                    && matches!(
+                        event,
+                        TuiEvent::Key(KeyEvent {
+                            code: KeyCode::Esc,
+                            ..
+                        })
+                    )))
+        {
+            self.overlay_forward_event(tui, event)?;
+            return Ok(true);
+        }
         if self.backtrack.overlay_preview_active {
             match event {
                 TuiEvent::Key(KeyEvent {
@@ -82,6 +82,8 @@ mod tests {
             id: "test".to_string(),
             command: vec!["echo".to_string(), "hi".to_string()],
             reason: None,
+            escalation_reason: None,
+            session_prefix: None,
         }
     }
 
@@ -1,3 +1,5 @@
+use codex_core::model_pricing::format_usd;
+use codex_core::protocol::RateLimitSnapshot;
 use codex_core::protocol::TokenUsage;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
@@ -53,6 +55,8 @@ const LARGE_PASTE_CHAR_THRESHOLD: usize = 1000;
 pub enum InputResult {
     Submitted(String),
     Command(SlashCommand),
+    /// A slash command followed by arguments, e.g. `/prompt review base=main`.
+    CommandWithArgs(SlashCommand, String),
     None,
 }
 
@@ -64,17 +68,15 @@ struct AttachedImage {
 
 struct TokenUsageInfo {
     total_token_usage: TokenUsage,
-    last_token_usage: TokenUsage,
+    /// Estimated cost of `total_token_usage`; `None` when the model's
+    /// prices are not known.
+    total_cost_usd: Option<f64>,
+}
+
+/// Client-side estimate of the size of the prompt sent on the latest turn.
+struct PromptTokenEstimate {
+    estimated_tokens: u64,
     model_context_window: Option<u64>,
-    /// Baseline token count present in the context before the user's first
-    /// message content is considered. This is used to normalize the
-    /// "context left" percentage so it reflects the portion the user can
-    /// influence rather than fixed prompt overhead (system prompt, tool
-    /// instructions, etc.).
-    ///
-    /// Preferred source is `cached_input_tokens` from the first turn (when
-    /// available), otherwise we fall back to 0.
-    initial_prompt_tokens: u64,
 }
 
 pub(crate) struct ChatComposer {
@@ -90,8 +92,15 @@ pub(crate) struct ChatComposer {
     current_file_query: Option<String>,
     pending_pastes: Vec<(String, String)>,
     token_usage_info: Option<TokenUsageInfo>,
+    /// Share of the context window left after the latest model response,
+    /// as computed by the core.
+    context_left_percent: Option<u8>,
+    prompt_token_estimate: Option<PromptTokenEstimate>,
+    rate_limits: Option<RateLimitSnapshot>,
     has_focus: bool,
     attached_images: Vec<AttachedImage>,
+    /// Paths inserted from the `@` file popup since the last submission.
+    mentioned_paths: Vec<String>,
     placeholder_text: String,
     // Non-bracketed paste burst tracker.
     paste_burst: PasteBurst,
@@ -130,8 +139,12 @@ impl ChatComposer {
             current_file_query: None,
             pending_pastes: Vec::new(),
             token_usage_info: None,
+            context_left_percent: None,
+            prompt_token_estimate: None,
+            rate_limits: None,
             has_focus: has_input_focus,
             attached_images: Vec::new(),
+            mentioned_paths: Vec::new(),
             placeholder_text,
             paste_burst: PasteBurst::default(),
             disable_paste_burst: false,
@@ -171,29 +184,42 @@ impl ChatComposer {
         self.textarea.is_empty()
     }
 
-    /// Update the cached *context-left* percentage and refresh the placeholder
-    /// text. The UI relies on the placeholder to convey the remaining
-    /// context when the composer is empty.
+    /// Update the session's token usage shown in the footer.
     pub(crate) fn set_token_usage(
         &mut self,
         total_token_usage: TokenUsage,
-        last_token_usage: TokenUsage,
-        model_context_window: Option<u64>,
+        total_cost_usd: Option<f64>,
     ) {
-        let initial_prompt_tokens = self
-            .token_usage_info
-            .as_ref()
-            .map(|info| info.initial_prompt_tokens)
-            .unwrap_or_else(|| last_token_usage.cached_input_tokens.unwrap_or(0));
-
         self.token_usage_info = Some(TokenUsageInfo {
             total_token_usage,
-            last_token_usage,
-            model_context_window,
-            initial_prompt_tokens,
+            total_cost_usd,
         });
     }
 
+    /// Update the footer's "context left: 18%" indicator. Pass `None` to
+    /// hide it.
+    pub(crate) fn set_context_left_percent(&mut self, percent: Option<u8>) {
+        self.context_left_percent = percent;
+    }
+
+    /// Update the estimated size of the prompt shown in the footer as
+    /// "~42k / 200k tokens". Pass `None` to hide it.
+    pub(crate) fn set_prompt_token_estimate(&mut self, estimate: Option<(u64, Option<u64>)>) {
+        self.prompt_token_estimate =
+            estimate.map(
+                |(estimated_tokens, model_context_window)| PromptTokenEstimate {
+                    estimated_tokens,
+                    model_context_window,
+                },
+            );
+    }
+
+    /// Update the rate limits whose most used window is shown in the footer
+    /// as "weekly 42% used".
+    pub(crate) fn set_rate_limits(&mut self, rate_limits: Option<RateLimitSnapshot>) {
+        self.rate_limits = rate_limits;
+    }
+
     /// Record the history metadata advertised by `SessionConfiguredEvent` so
     /// that the composer can navigate cross-session history.
     pub(crate) fn set_history_metadata(&mut self, log_id: u64, entry_count: usize) {
@@ -217,6 +243,20 @@ impl ChatComposer {
         true
     }
 
+    /// Integrate the persistent matches of a Ctrl-R search. The first match is
+    /// shown unless the composer has been edited since the search started.
+    pub(crate) fn on_history_search_response(&mut self, query: &str, matches: Vec<String>) -> bool {
+        let Some(text) = self
+            .history
+            .on_search_response(query, matches, self.textarea.text())
+        else {
+            return false;
+        };
+        self.textarea.set_text(&text);
+        self.textarea.set_cursor(0);
+        true
+    }
+
     pub fn handle_paste(&mut self, pasted: String) -> bool {
         let char_count = pasted.chars().count();
         if char_count > LARGE_PASTE_CHAR_THRESHOLD {
@@ -297,6 +337,12 @@ impl ChatComposer {
         images.into_iter().map(|img| img.path).collect()
     }
 
+    /// Paths inserted from the `@` file popup for the last submission. Some
+    /// may since have been edited out of the text.
+    pub fn take_recent_submission_mentions(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.mentioned_paths)
+    }
+
     pub(crate) fn flush_paste_burst_if_due(&mut self) -> bool {
         let now = Instant::now();
         if let Some(pasted) = self.paste_burst.flush_if_due(now) {
@@ -432,6 +478,12 @@ impl ChatComposer {
                 ..
             } => {
                 if let Some(sel) = popup.selected_item() {
+                    let args = match sel {
+                        CommandItem::Builtin(cmd) => {
+                            command_args(self.textarea.text(), cmd.command())
+                        }
+                        CommandItem::UserPrompt(_) => None,
+                    };
                     // Clear textarea so no residual text remains.
                     self.textarea.set_text("");
                     // Capture any needed data from popup before clearing it.
@@ -446,7 +498,11 @@ impl ChatComposer {
 
                     match sel {
                         CommandItem::Builtin(cmd) => {
-                            return (InputResult::Command(cmd), true);
+                            let result = match args {
+                                Some(args) => InputResult::CommandWithArgs(cmd, args),
+                                None => InputResult::Command(cmd),
+                            };
+                            return (result, true);
                         }
                         CommandItem::UserPrompt(_) => {
                             if let Some(contents) = prompt_content {
@@ -737,6 +793,7 @@ impl ChatComposer {
         self.textarea.set_text(&new_text);
         let new_cursor = start_idx.saturating_add(path.len()).saturating_add(1);
         self.textarea.set_cursor(new_cursor);
+        self.mentioned_paths.push(path.to_string());
     }
 
     /// Handle key event when no popup is visible.
@@ -751,6 +808,23 @@ impl ChatComposer {
                 self.app_event_tx.send(AppEvent::ExitRequest);
                 (InputResult::None, true)
             }
+            // Reverse history search: show the newest entry containing the
+            // current text, or the next older one on repeated presses.
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: crossterm::event::KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                if let Some(text) = self
+                    .history
+                    .search(self.textarea.text(), &self.app_event_tx)
+                {
+                    self.textarea.set_text(&text);
+                    self.textarea.set_cursor(0);
+                }
+                (InputResult::None, true)
+            }
             // -------------------------------------------------------------
             // History navigation (Up / Down) – only when the composer is not
             // empty or when the cursor is at the correct position, to avoid
@@ -1217,6 +1291,17 @@ impl ChatComposer {
     }
 }
 
+/// The text following `/<command>` in `text`, if the command was typed in full
+/// and followed by anything.
+fn command_args(text: &str, command: &str) -> Option<String> {
+    let rest = text.trim_start().strip_prefix('/')?.strip_prefix(command)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let args = rest.trim();
+    (!args.is_empty()).then(|| args.to_string())
+}
+
 impl WidgetRef for ChatComposer {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
         let popup_height = match &self.active_popup {
@@ -1270,27 +1355,48 @@ impl WidgetRef for ChatComposer {
                 // Append token/context usage info to the footer hints when available.
                 if let Some(token_usage_info) = &self.token_usage_info {
                     let token_usage = &token_usage_info.total_token_usage;
+                    hint.push(Span::from("   "));
+                    let mut used = format!("{} tokens used", token_usage.blended_total());
+                    if token_usage.cached_input() > 0 {
+                        used.push_str(&format!(" (+ {} cached)", token_usage.cached_input()));
+                    }
+                    if let Some(cost) = token_usage_info.total_cost_usd {
+                        used.push_str(&format!(" · {}", format_usd(cost)));
+                    }
+                    hint.push(Span::from(used).style(Style::default().add_modifier(Modifier::DIM)));
+                }
+
+                if let Some(percent_left) = self.context_left_percent {
                     hint.push(Span::from("   "));
                     hint.push(
-                        Span::from(format!("{} tokens used", token_usage.blended_total()))
+                        Span::from(format!("context left: {percent_left}%"))
                             .style(Style::default().add_modifier(Modifier::DIM)),
                     );
-                    let last_token_usage = &token_usage_info.last_token_usage;
-                    if let Some(context_window) = token_usage_info.model_context_window {
-                        let percent_remaining: u8 = if context_window > 0 {
-                            last_token_usage.percent_of_context_window_remaining(
-                                context_window,
-                                token_usage_info.initial_prompt_tokens,
-                            )
-                        } else {
-                            100
-                        };
-                        hint.push(Span::from("   "));
-                        hint.push(
-                            Span::from(format!("{percent_remaining}% context left"))
-                                .style(Style::default().add_modifier(Modifier::DIM)),
-                        );
+                }
+
+                if let Some(estimate) = &self.prompt_token_estimate {
+                    let mut text = format!("~{}", format_token_count(estimate.estimated_tokens));
+                    if let Some(context_window) = estimate.model_context_window {
+                        text.push_str(&format!(" / {}", format_token_count(context_window)));
                     }
+                    text.push_str(" tokens");
+                    hint.push(Span::from("   "));
+                    hint.push(Span::from(text).style(Style::default().add_modifier(Modifier::DIM)));
+                }
+
+                if let Some((name, window)) = self.rate_limits.as_ref().and_then(|limits| {
+                    limits
+                        .windows()
+                        .max_by(|(_, a), (_, b)| a.used_percent.total_cmp(&b.used_percent))
+                }) {
+                    let text = format!("{name} {:.0}% used", window.used_percent);
+                    let style = if window.used_percent >= 90.0 {
+                        Style::default().fg(Color::Red)
+                    } else {
+                        Style::default().add_modifier(Modifier::DIM)
+                    };
+                    hint.push(Span::from("   "));
+                    hint.push(Span::from(text).style(style));
                 }
 
                 Line::from(hint)
@@ -1325,6 +1431,17 @@ impl WidgetRef for ChatComposer {
     }
 }
 
+/// Formats a token count compactly, e.g. `950`, `42k`, `1.0M`.
+pub(crate) fn format_token_count(tokens: u64) -> String {
+    if tokens >= 1_000_000 {
+        format!("{:.1}M", tokens as f64 / 1_000_000.0)
+    } else if tokens >= 1_000 {
+        format!("{}k", (tokens + 500) / 1_000)
+    } else {
+        tokens.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1342,6 +1459,14 @@ mod tests {
     use crate::bottom_pane::textarea::TextArea;
     use tokio::sync::mpsc::unbounded_channel;
 
+    #[test]
+    fn format_token_count_is_compact() {
+        assert_eq!(format_token_count(950), "950");
+        assert_eq!(format_token_count(41_600), "42k");
+        assert_eq!(format_token_count(200_000), "200k");
+        assert_eq!(format_token_count(1_047_576), "1.0M");
+    }
+
     #[test]
     fn test_current_at_token_basic_cases() {
         let test_cases = vec![
@@ -1684,6 +1809,9 @@ mod tests {
             InputResult::Submitted(text) => {
                 panic!("expected command dispatch, but composer submitted literal text: {text}")
             }
+            InputResult::CommandWithArgs(cmd, args) => {
+                panic!("expected no arguments, got /{} {args}", cmd.command())
+            }
             InputResult::None => panic!("expected Command result for '/init'"),
         }
         assert!(composer.textarea.is_empty(), "composer should be cleared");
@@ -1742,6 +1870,9 @@ mod tests {
             InputResult::Submitted(text) => {
                 panic!("expected command dispatch, but composer submitted literal text: {text}")
             }
+            InputResult::CommandWithArgs(cmd, args) => {
+                panic!("expected no arguments, got /{} {args}", cmd.command())
+            }
             InputResult::None => panic!("expected Command result for '/mention'"),
         }
         assert!(composer.textarea.is_empty(), "composer should be cleared");
@@ -1749,6 +1880,44 @@ mod tests {
         assert_eq!(composer.textarea.text(), "@");
     }
 
+    #[test]
+    fn slash_command_keeps_its_arguments() {
+        use crossterm::event::KeyCode;
+        use crossterm::event::KeyEvent;
+        use crossterm::event::KeyModifiers;
+
+        let (tx, _rx) = unbounded_channel::<AppEvent>();
+        let sender = AppEventSender::new(tx);
+        let mut composer = ChatComposer::new(
+            true,
+            sender,
+            false,
+            "Ask Codex to do anything".to_string(),
+            false,
+        );
+
+        composer.handle_paste("/prompt review base=main".to_string());
+        let (result, _needs_redraw) =
+            composer.handle_key_event(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        assert_eq!(
+            result,
+            InputResult::CommandWithArgs(SlashCommand::Prompt, "review base=main".to_string())
+        );
+        assert!(composer.textarea.is_empty(), "composer should be cleared");
+    }
+
+    #[test]
+    fn command_args_require_the_full_command() {
+        assert_eq!(
+            command_args("/mcp resources", "mcp"),
+            Some("resources".to_string())
+        );
+        assert_eq!(command_args("/mcp  ", "mcp"), None);
+        assert_eq!(command_args("/mcpx resources", "mcp"), None);
+        assert_eq!(command_args("/mc", "mcp"), None);
+    }
+
     #[test]
     fn test_multiple_pastes_submission() {
         use crossterm::event::KeyCode;
@@ -1951,6 +2120,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tab_inserts_the_selected_file_and_records_the_mention() {
+        let (tx, _rx) = unbounded_channel::<AppEvent>();
+        let sender = AppEventSender::new(tx);
+        let mut composer = ChatComposer::new(
+            true,
+            sender,
+            false,
+            "Ask Codex to do anything".to_string(),
+            false,
+        );
+        composer.insert_str("look at @src/cl");
+        composer.on_file_search_result(
+            "src/cl".to_string(),
+            vec![FileMatch {
+                score: 1,
+                path: "src/client.rs".to_string(),
+                indices: None,
+            }],
+        );
+        let _ = composer.handle_key_event(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert_eq!(composer.textarea.text(), "look at src/client.rs ");
+
+        let (result, _) =
+            composer.handle_key_event(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        match result {
+            InputResult::Submitted(text) => assert_eq!(text, "look at src/client.rs"),
+            _ => panic!("expected Submitted"),
+        }
+        assert_eq!(
+            composer.take_recent_submission_mentions(),
+            vec!["src/client.rs".to_string()]
+        );
+        assert!(composer.take_recent_submission_mentions().is_empty());
+    }
+
     // --- Image attachment tests ---
     #[test]
     fn attach_image_and_submit_includes_image_paths() {
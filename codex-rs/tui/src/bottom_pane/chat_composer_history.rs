@@ -4,6 +4,22 @@ use crate::app_event::AppEvent;
 use crate::app_event_sender::AppEventSender;
 use codex_core::protocol::Op;
 
+/// Number of entries requested from the persistent history per Ctrl-R search.
+const SEARCH_LIMIT: usize = 50;
+
+/// State of a reverse history search (Ctrl-R).
+struct HistorySearch {
+    /// The composer's text when the search started.
+    query: String,
+
+    /// Matching entries, newest first: this session's submissions followed
+    /// by the persistent entries received so far.
+    matches: Vec<String>,
+
+    /// Index into `matches` of the entry shown in the composer, if any.
+    shown: Option<usize>,
+}
+
 /// State machine that manages shell-style history navigation (Up/Down) inside
 /// the chat composer. This struct is intentionally decoupled from the
 /// rendering widget so the logic remains isolated and easier to test.
@@ -28,6 +44,9 @@ pub(crate) struct ChatComposerHistory {
     /// history navigation. Used to decide if further Up/Down presses should be
     /// treated as navigation versus normal cursor movement.
     last_history_text: Option<String>,
+
+    /// The reverse search in progress, if any.
+    search: Option<HistorySearch>,
 }
 
 impl ChatComposerHistory {
@@ -39,6 +58,7 @@ impl ChatComposerHistory {
             fetched_history: HashMap::new(),
             history_cursor: None,
             last_history_text: None,
+            search: None,
         }
     }
 
@@ -50,6 +70,7 @@ impl ChatComposerHistory {
         self.local_history.clear();
         self.history_cursor = None;
         self.last_history_text = None;
+        self.search = None;
     }
 
     /// Record a message submitted by the user in the current session so it can
@@ -67,6 +88,7 @@ impl ChatComposerHistory {
         self.local_history.push(text.to_string());
         self.history_cursor = None;
         self.last_history_text = None;
+        self.search = None;
     }
 
     /// Should Up/Down key presses be interpreted as history navigation given
@@ -93,6 +115,7 @@ impl ChatComposerHistory {
     /// Handle <Up>. Returns true when the key was consumed and the caller
     /// should request a redraw.
     pub fn navigate_up(&mut self, app_event_tx: &AppEventSender) -> Option<String> {
+        self.search = None;
         let total_entries = self.history_entry_count + self.local_history.len();
         if total_entries == 0 {
             return None;
@@ -110,6 +133,7 @@ impl ChatComposerHistory {
 
     /// Handle <Down>.
     pub fn navigate_down(&mut self, app_event_tx: &AppEventSender) -> Option<String> {
+        self.search = None;
         let total_entries = self.history_entry_count + self.local_history.len();
         if total_entries == 0 {
             return None;
@@ -155,10 +179,80 @@ impl ChatComposerHistory {
         None
     }
 
+    /// Handle Ctrl-R. The first press searches for entries containing `text`;
+    /// each further press, while the composer still shows the last match,
+    /// steps to the next older match. Returns the text to show, if any.
+    pub fn search(&mut self, text: &str, app_event_tx: &AppEventSender) -> Option<String> {
+        let continuing = matches!(&self.last_history_text, Some(prev) if prev == text);
+        if let Some(search) = self.search.as_mut()
+            && continuing
+        {
+            let next = search.shown.map_or(0, |idx| idx + 1);
+            let entry = search.matches.get(next)?.clone();
+            search.shown = Some(next);
+            self.last_history_text = Some(entry.clone());
+            return Some(entry);
+        }
+
+        let needle = text.to_lowercase();
+        let mut matches: Vec<String> = Vec::new();
+        for entry in self.local_history.iter().rev() {
+            if entry.to_lowercase().contains(&needle) && !matches.contains(entry) {
+                matches.push(entry.clone());
+            }
+        }
+        app_event_tx.send(AppEvent::CodexOp(Op::SearchHistory {
+            query: text.to_string(),
+            limit: SEARCH_LIMIT,
+        }));
+        self.history_cursor = None;
+        self.search = Some(HistorySearch {
+            query: text.to_string(),
+            matches,
+            shown: None,
+        });
+        self.show_first_match()
+    }
+
+    /// Integrate a SearchHistoryResponse event. Returns the text to show when
+    /// this is the first match of the search and the composer still holds
+    /// its query.
+    pub fn on_search_response(
+        &mut self,
+        query: &str,
+        matches: Vec<String>,
+        composer_text: &str,
+    ) -> Option<String> {
+        let search = self
+            .search
+            .as_mut()
+            .filter(|search| search.query == query)?;
+        for text in matches {
+            if !search.matches.contains(&text) {
+                search.matches.push(text);
+            }
+        }
+        if composer_text != query {
+            return None;
+        }
+        self.show_first_match()
+    }
+
     // ---------------------------------------------------------------------
     // Internal helpers
     // ---------------------------------------------------------------------
 
+    fn show_first_match(&mut self) -> Option<String> {
+        let search = self.search.as_mut()?;
+        if search.shown.is_some() {
+            return None;
+        }
+        let entry = search.matches.first()?.clone();
+        search.shown = Some(0);
+        self.last_history_text = Some(entry.clone());
+        Some(entry)
+    }
+
     fn populate_history_at_index(
         &mut self,
         global_idx: usize,
@@ -270,4 +364,69 @@ mod tests {
             history.on_entry_response(1, 1, Some("older".into()))
         );
     }
+
+    #[test]
+    fn search_steps_through_local_and_persistent_matches() {
+        let (tx, mut rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx);
+
+        let mut history = ChatComposerHistory::new();
+        history.set_metadata(1, 3);
+        history.record_local_submission("cargo test -p tui");
+        history.record_local_submission("git status");
+
+        // The local match is shown right away while the file is searched.
+        assert_eq!(
+            Some("cargo test -p tui".to_string()),
+            history.search("cargo", &tx)
+        );
+        let AppEvent::CodexOp(op) = rx.try_recv().expect("expected search request") else {
+            panic!("unexpected event variant");
+        };
+        assert_eq!(
+            Op::SearchHistory {
+                query: "cargo".to_string(),
+                limit: SEARCH_LIMIT,
+            },
+            op
+        );
+
+        // Persistent matches are appended without repeating local ones, and
+        // do not replace what the composer already shows.
+        assert_eq!(
+            None,
+            history.on_search_response(
+                "cargo",
+                vec!["cargo test -p tui".to_string(), "cargo build".to_string()],
+                "cargo test -p tui",
+            )
+        );
+
+        // Further presses step to older matches and stop at the oldest.
+        assert_eq!(
+            Some("cargo build".to_string()),
+            history.search("cargo test -p tui", &tx)
+        );
+        assert_eq!(None, history.search("cargo build", &tx));
+    }
+
+    #[test]
+    fn search_response_fills_an_untouched_composer() {
+        let (tx, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx);
+
+        let mut history = ChatComposerHistory::new();
+        history.set_metadata(1, 1);
+        assert_eq!(None, history.search("deploy", &tx));
+
+        // A response for another query is ignored.
+        assert_eq!(
+            None,
+            history.on_search_response("other", vec!["other".to_string()], "deploy")
+        );
+        assert_eq!(
+            Some("deploy to staging".to_string()),
+            history.on_search_response("deploy", vec!["deploy to staging".to_string()], "deploy")
+        );
+    }
 }
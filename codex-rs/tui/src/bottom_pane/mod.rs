@@ -5,6 +5,8 @@ use crate::app_event_sender::AppEventSender;
 use crate::tui::FrameRequester;
 use crate::user_approval_widget::ApprovalRequest;
 use bottom_pane_view::BottomPaneView;
+use codex_core::plan_tool::PlanItemArg;
+use codex_core::protocol::RateLimitSnapshot;
 use codex_core::protocol::TokenUsage;
 use codex_file_search::FileMatch;
 use crossterm::event::KeyEvent;
@@ -36,6 +38,7 @@ pub(crate) enum CancellationEvent {
 
 pub(crate) use chat_composer::ChatComposer;
 pub(crate) use chat_composer::InputResult;
+pub(crate) use chat_composer::format_token_count;
 use codex_protocol::custom_prompts::CustomPrompt;
 
 use crate::status_indicator_widget::StatusIndicatorWidget;
@@ -64,6 +67,8 @@ pub(crate) struct BottomPane {
     status: Option<StatusIndicatorWidget>,
     /// Queued user messages to show under the status indicator.
     queued_user_messages: Vec<String>,
+    /// Latest plan from the agent, shown under the status indicator.
+    plan: Vec<PlanItemArg>,
 }
 
 pub(crate) struct BottomPaneParams {
@@ -95,6 +100,7 @@ impl BottomPane {
             ctrl_c_quit_hint: false,
             status: None,
             queued_user_messages: Vec::new(),
+            plan: Vec::new(),
             esc_backtrack_hint: false,
         }
     }
@@ -301,6 +307,7 @@ impl BottomPane {
             }
             if let Some(status) = self.status.as_mut() {
                 status.set_queued_messages(self.queued_user_messages.clone());
+                status.set_plan(self.plan.clone());
             }
             self.request_redraw();
         } else {
@@ -337,6 +344,15 @@ impl BottomPane {
         self.request_redraw();
     }
 
+    /// Update the plan checklist shown under the status header.
+    pub(crate) fn set_plan(&mut self, plan: Vec<PlanItemArg>) {
+        self.plan = plan.clone();
+        if let Some(status) = self.status.as_mut() {
+            status.set_plan(plan);
+        }
+        self.request_redraw();
+    }
+
     /// Update custom prompts available for the slash popup.
     pub(crate) fn set_custom_prompts(&mut self, prompts: Vec<CustomPrompt>) {
         self.composer.set_custom_prompts(prompts);
@@ -351,6 +367,11 @@ impl BottomPane {
         self.is_task_running
     }
 
+    /// Whether a modal view or a composer popup would take the next key.
+    pub(crate) fn has_active_popup(&self) -> bool {
+        self.active_view.is_some() || self.composer.popup_active()
+    }
+
     /// Return true when the pane is in the regular composer state without any
     /// overlays or popups and not running a task. This is the safe context to
     /// use Esc-Esc for backtracking from the main view.
@@ -358,16 +379,30 @@ impl BottomPane {
         !self.is_task_running && self.active_view.is_none() && !self.composer.popup_active()
     }
 
-    /// Update the *context-window remaining* indicator in the composer. This
-    /// is forwarded directly to the underlying `ChatComposer`.
+    /// Update the token usage shown in the composer footer. This is
+    /// forwarded directly to the underlying `ChatComposer`.
     pub(crate) fn set_token_usage(
         &mut self,
         total_token_usage: TokenUsage,
-        last_token_usage: TokenUsage,
-        model_context_window: Option<u64>,
+        total_cost_usd: Option<f64>,
     ) {
         self.composer
-            .set_token_usage(total_token_usage, last_token_usage, model_context_window);
+            .set_token_usage(total_token_usage, total_cost_usd);
+        self.request_redraw();
+    }
+
+    pub(crate) fn set_context_left_percent(&mut self, percent: Option<u8>) {
+        self.composer.set_context_left_percent(percent);
+        self.request_redraw();
+    }
+
+    pub(crate) fn set_prompt_token_estimate(&mut self, estimate: Option<(u64, Option<u64>)>) {
+        self.composer.set_prompt_token_estimate(estimate);
+        self.request_redraw();
+    }
+
+    pub(crate) fn set_rate_limits(&mut self, rate_limits: Option<RateLimitSnapshot>) {
+        self.composer.set_rate_limits(rate_limits);
         self.request_redraw();
     }
 
@@ -429,6 +464,12 @@ impl BottomPane {
         }
     }
 
+    pub(crate) fn on_history_search_response(&mut self, query: &str, matches: Vec<String>) {
+        if self.composer.on_history_search_response(query, matches) {
+            self.request_redraw();
+        }
+    }
+
     pub(crate) fn on_file_search_result(&mut self, query: String, matches: Vec<FileMatch>) {
         self.composer.on_file_search_result(query, matches);
         self.request_redraw();
@@ -451,6 +492,10 @@ impl BottomPane {
     pub(crate) fn take_recent_submission_images(&mut self) -> Vec<PathBuf> {
         self.composer.take_recent_submission_images()
     }
+
+    pub(crate) fn take_recent_submission_mentions(&mut self) -> Vec<String> {
+        self.composer.take_recent_submission_mentions()
+    }
 }
 
 impl WidgetRef for &BottomPane {
@@ -486,6 +531,8 @@ mod tests {
             id: "1".to_string(),
             command: vec!["echo".into(), "ok".into()],
             reason: None,
+            escalation_reason: None,
+            session_prefix: None,
         }
     }
 
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::collections::VecDeque;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -11,25 +11,49 @@ use codex_core::protocol::AgentReasoningEvent;
 use codex_core::protocol::AgentReasoningRawContentDeltaEvent;
 use codex_core::protocol::AgentReasoningRawContentEvent;
 use codex_core::protocol::ApplyPatchApprovalRequestEvent;
+use codex_core::protocol::AuthExpiredEvent;
 use codex_core::protocol::BackgroundEventEvent;
+use codex_core::protocol::BackgroundResponseProgressEvent;
+use codex_core::protocol::ContextUsageEvent;
+use codex_core::protocol::ContextWindowWarningEvent;
+use codex_core::protocol::ConversationCompactedEvent;
 use codex_core::protocol::ErrorEvent;
 use codex_core::protocol::Event;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::ExecApprovalRequestEvent;
 use codex_core::protocol::ExecCommandBeginEvent;
 use codex_core::protocol::ExecCommandEndEvent;
+use codex_core::protocol::FunctionCallArgumentsDeltaEvent;
+use codex_core::protocol::GitRepoWarningEvent;
 use codex_core::protocol::InputItem;
 use codex_core::protocol::ListCustomPromptsResponseEvent;
+use codex_core::protocol::McpListPromptsResponseEvent;
+use codex_core::protocol::McpListResourcesResponseEvent;
 use codex_core::protocol::McpListToolsResponseEvent;
+use codex_core::protocol::McpStartupWarningEvent;
 use codex_core::protocol::McpToolCallBeginEvent;
 use codex_core::protocol::McpToolCallEndEvent;
+use codex_core::protocol::McpToolCallProgressEvent;
 use codex_core::protocol::Op;
 use codex_core::protocol::PatchApplyBeginEvent;
+use codex_core::protocol::PatchUndoneEvent;
+use codex_core::protocol::ProjectDocTruncatedEvent;
+use codex_core::protocol::PromptTokenEstimateEvent;
+use codex_core::protocol::PullRequestEvent;
+use codex_core::protocol::QueuedInput;
+use codex_core::protocol::QueuedInputEvent;
+use codex_core::protocol::RateLimitSnapshot;
+use codex_core::protocol::ReviewFindingsEvent;
+use codex_core::protocol::SearchHistoryResponseEvent;
+use codex_core::protocol::SessionStatus;
 use codex_core::protocol::StreamErrorEvent;
 use codex_core::protocol::TaskCompleteEvent;
 use codex_core::protocol::TokenUsage;
+use codex_core::protocol::ToolOutputBudgetExceededEvent;
 use codex_core::protocol::TurnAbortReason;
 use codex_core::protocol::TurnDiffEvent;
+use codex_core::protocol::TurnMetrics;
+use codex_core::protocol::UsageUpdateEvent;
 use codex_core::protocol::WebSearchBeginEvent;
 use codex_core::protocol::WebSearchEndEvent;
 use codex_protocol::parse_command::ParsedCommand;
@@ -42,6 +66,8 @@ use ratatui::buffer::Buffer;
 use ratatui::layout::Constraint;
 use ratatui::layout::Layout;
 use ratatui::layout::Rect;
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
 use ratatui::widgets::Widget;
 use ratatui::widgets::WidgetRef;
 use tokio::sync::mpsc::UnboundedSender;
@@ -57,6 +83,7 @@ use crate::bottom_pane::SelectionAction;
 use crate::bottom_pane::SelectionItem;
 use crate::get_git_diff::get_git_diff;
 use crate::history_cell;
+use crate::history_cell::ActiveMcpToolCall;
 use crate::history_cell::CommandOutput;
 use crate::history_cell::ExecCell;
 use crate::history_cell::HistoryCell;
@@ -81,8 +108,11 @@ use codex_core::protocol::AskForApproval;
 use codex_core::protocol::SandboxPolicy;
 use codex_core::protocol_config_types::ReasoningEffort as ReasoningEffortConfig;
 use codex_file_search::FileMatch;
+use strum::IntoEnumIterator;
 use uuid::Uuid;
 
+const REASONING_USAGE: &str = "Usage: /reasoning <minimal|low|medium|high>";
+
 // Track information about an in-flight exec command.
 struct RunningCommand {
     command: Vec<String>,
@@ -97,30 +127,45 @@ pub(crate) struct ChatWidget {
     config: Config,
     initial_user_message: Option<UserMessage>,
     total_token_usage: TokenUsage,
-    last_token_usage: TokenUsage,
+    /// Rate limits reported with the latest model response.
+    rate_limits: Option<RateLimitSnapshot>,
+    /// Latency of the latest model response.
+    last_turn_metrics: Option<TurnMetrics>,
     // Stream lifecycle controller
     stream: StreamController,
     running_commands: HashMap<String, RunningCommand>,
+    // MCP tool calls in flight, in the order they started
+    active_mcp_calls: Vec<ActiveMcpToolCall>,
     pending_exec_completions: Vec<(Vec<String>, Vec<ParsedCommand>, CommandOutput)>,
     task_complete_pending: bool,
     // Queue of interruptive UI events deferred during an active write cycle
     interrupts: InterruptManager,
     // Accumulates the current reasoning block text to extract a header
     reasoning_buffer: String,
-    // Accumulates full reasoning content for transcript-only recording
-    full_reasoning_buffer: String,
+    // Completed parts of the current reasoning summary
+    reasoning_sections: Vec<String>,
+    // Whether new reasoning summaries show their full text (toggled with Ctrl+O)
+    expand_reasoning: bool,
+    // Streamed function call arguments keyed by call_id, used to preview
+    // the command the model is about to run
+    function_call_args: HashMap<String, String>,
     session_id: Option<Uuid>,
     frame_requester: FrameRequester,
     // Whether to include the initial welcome banner on session configured
     show_welcome_banner: bool,
     last_history_was_exec: bool,
-    // User messages queued while a turn is in progress
-    queued_user_messages: VecDeque<UserMessage>,
+    // User messages queued in the session while a turn is in progress,
+    // mirrored from `EventMsg::QueuedInputUpdated`
+    queued_user_messages: Vec<QueuedInput>,
+    // Reasoning effort for the next submitted message only, set with `/reasoning`
+    next_turn_effort: Option<ReasoningEffortConfig>,
 }
 
 struct UserMessage {
     text: String,
     image_paths: Vec<PathBuf>,
+    /// Paths inserted from the `@` file popup.
+    mentioned_files: Vec<String>,
 }
 
 impl From<String> for UserMessage {
@@ -128,6 +173,7 @@ impl From<String> for UserMessage {
         Self {
             text,
             image_paths: Vec::new(),
+            mentioned_files: Vec::new(),
         }
     }
 }
@@ -136,10 +182,44 @@ fn create_initial_user_message(text: String, image_paths: Vec<PathBuf>) -> Optio
     if text.is_empty() && image_paths.is_empty() {
         None
     } else {
-        Some(UserMessage { text, image_paths })
+        Some(UserMessage {
+            text,
+            image_paths,
+            mentioned_files: Vec::new(),
+        })
     }
 }
 
+/// The contents of the files in `paths`, relative to `cwd`, that the message
+/// still mentions and that are UTF-8 text of at most `max_bytes`.
+fn mentioned_file_items(
+    cwd: &Path,
+    text: &str,
+    paths: &[String],
+    max_bytes: u64,
+) -> Vec<InputItem> {
+    let mut seen = Vec::new();
+    let mut items = Vec::new();
+    for path in paths {
+        if seen.contains(&path) || !text.contains(path.as_str()) {
+            continue;
+        }
+        seen.push(path);
+        let full_path = cwd.join(path);
+        match std::fs::metadata(&full_path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() <= max_bytes => {}
+            _ => continue,
+        }
+        let Ok(contents) = std::fs::read_to_string(&full_path) else {
+            continue;
+        };
+        items.push(InputItem::Text {
+            text: format!("<mentioned_file path=\"{path}\">\n{contents}\n</mentioned_file>"),
+        });
+    }
+    items
+}
+
 impl ChatWidget {
     fn flush_answer_stream_with_separator(&mut self) {
         let sink = AppEventHistorySink(self.app_event_tx.clone());
@@ -189,25 +269,44 @@ impl ChatWidget {
         self.request_redraw();
     }
 
-    fn on_agent_reasoning_final(&mut self) {
-        // At the end of a reasoning block, record transcript-only content.
-        self.full_reasoning_buffer.push_str(&self.reasoning_buffer);
-        if !self.full_reasoning_buffer.is_empty() {
+    fn on_agent_reasoning_final(&mut self, text: String) {
+        // Without deltas, the final event carries the whole summary.
+        if self.reasoning_buffer.is_empty() && self.reasoning_sections.is_empty() {
+            self.reasoning_buffer = text;
+        }
+        self.flush_reasoning();
+        self.request_redraw();
+    }
+
+    fn on_reasoning_section_break(&mut self) {
+        // Close the current part; the next delta starts a new one with its
+        // own header.
+        let section = std::mem::take(&mut self.reasoning_buffer);
+        if !section.trim().is_empty() {
+            self.reasoning_sections.push(section);
+        }
+    }
+
+    /// Records the buffered reasoning summary, including a part that is still
+    /// streaming, as one history cell.
+    fn flush_reasoning(&mut self) {
+        self.on_reasoning_section_break();
+        let sections = std::mem::take(&mut self.reasoning_sections);
+        if !sections.is_empty() {
             self.add_to_history(history_cell::new_reasoning_block(
-                self.full_reasoning_buffer.clone(),
+                sections,
+                self.expand_reasoning,
                 &self.config,
             ));
         }
-        self.reasoning_buffer.clear();
-        self.full_reasoning_buffer.clear();
-        self.request_redraw();
     }
 
-    fn on_reasoning_section_break(&mut self) {
-        // Start a new reasoning block for header extraction and accumulate transcript.
-        self.full_reasoning_buffer.push_str(&self.reasoning_buffer);
-        self.full_reasoning_buffer.push_str("\n\n");
-        self.reasoning_buffer.clear();
+    fn toggle_expand_reasoning(&mut self) {
+        self.expand_reasoning = !self.expand_reasoning;
+        self.add_to_history(history_cell::new_reasoning_display_toggled(
+            self.expand_reasoning,
+        ));
+        self.request_redraw();
     }
 
     // Raw reasoning uses the same flow as summarized reasoning
@@ -216,12 +315,15 @@ impl ChatWidget {
         self.bottom_pane.clear_ctrl_c_quit_hint();
         self.bottom_pane.set_task_running(true);
         self.stream.reset_headers_for_new_turn();
-        self.full_reasoning_buffer.clear();
+        self.reasoning_sections.clear();
         self.reasoning_buffer.clear();
+        self.function_call_args.clear();
         self.request_redraw();
     }
 
     fn on_task_complete(&mut self) {
+        // Keep a reasoning summary that the end of the task cut off.
+        self.flush_reasoning();
         // If a stream is currently active, finalize only that stream to flush any tail
         // without emitting stray headers for other streams.
         if self.stream.is_write_cycle_active() {
@@ -231,25 +333,47 @@ impl ChatWidget {
         // Mark task stopped and request redraw now that all content is in history.
         self.bottom_pane.set_task_running(false);
         self.running_commands.clear();
+        self.active_mcp_calls.clear();
         self.request_redraw();
+    }
 
-        // If there is a queued user message, send exactly one now to begin the next turn.
-        self.maybe_send_next_queued_input();
+    fn on_usage_update(&mut self, ev: UsageUpdateEvent) {
+        let UsageUpdateEvent {
+            total_token_usage,
+            last_token_usage: _,
+            total_cost_usd,
+            last_cost_usd: _,
+        } = ev;
+        self.total_token_usage = total_token_usage;
+        self.bottom_pane
+            .set_token_usage(self.total_token_usage.clone(), total_cost_usd);
     }
 
-    fn on_token_count(&mut self, token_usage: TokenUsage) {
-        self.total_token_usage = add_token_usage(&self.total_token_usage, &token_usage);
-        self.last_token_usage = token_usage;
-        self.bottom_pane.set_token_usage(
-            self.total_token_usage.clone(),
-            self.last_token_usage.clone(),
-            self.config.model_context_window,
-        );
+    fn on_context_usage(&mut self, ev: ContextUsageEvent) {
+        self.bottom_pane
+            .set_context_left_percent(Some(ev.percent_left));
+    }
+
+    fn on_context_window_warning(&mut self, ev: ContextWindowWarningEvent) {
+        self.add_to_history(history_cell::new_context_window_warning(ev));
+        self.request_redraw();
+    }
+
+    fn on_rate_limits(&mut self, snapshot: RateLimitSnapshot) {
+        self.bottom_pane.set_rate_limits(Some(snapshot.clone()));
+        self.rate_limits = Some(snapshot);
+    }
+
+    fn on_prompt_token_estimate(&mut self, ev: PromptTokenEstimateEvent) {
+        let model_context_window = ev.model_context_window.or(self.config.model_context_window);
+        self.bottom_pane
+            .set_prompt_token_estimate(Some((ev.estimated_tokens, model_context_window)));
     }
 
     /// Finalize any active exec as failed, push an error message into history,
     /// and stop/clear running UI state.
     fn finalize_turn_with_error_message(&mut self, message: String) {
+        self.flush_reasoning();
         // Ensure any spinner is replaced by a red ✗ and flushed into history.
         self.finalize_active_exec_cell_as_failed();
         // Emit the provided error message/history cell.
@@ -257,42 +381,54 @@ impl ChatWidget {
         // Reset running state and clear streaming buffers.
         self.bottom_pane.set_task_running(false);
         self.running_commands.clear();
+        self.active_mcp_calls.clear();
         self.stream.clear_all();
     }
 
     fn on_error(&mut self, message: String) {
         self.finalize_turn_with_error_message(message);
         self.request_redraw();
-
-        // After an error ends the turn, try sending the next queued input.
-        self.maybe_send_next_queued_input();
     }
 
     /// Handle a turn aborted due to user interrupt (Esc).
-    /// When there are queued user messages, restore them into the composer
-    /// separated by newlines rather than auto‑submitting the next one.
     fn on_interrupted_turn(&mut self) {
+        // Keep whatever the model already streamed in the transcript; core
+        // records the same partial answer in the conversation history.
+        self.flush_answer_stream_with_separator();
         // Finalize, log a gentle prompt, and clear running state.
         self.finalize_turn_with_error_message("Tell the model what to do differently".to_owned());
+        self.request_redraw();
+    }
 
-        // If any messages were queued during the task, restore them into the composer.
-        if !self.queued_user_messages.is_empty() {
-            let combined = self
-                .queued_user_messages
+    /// Mirror the session's queue. Entries that started a turn join the
+    /// transcript; entries dropped by an interrupt are restored into the
+    /// composer, separated by newlines, rather than lost.
+    fn on_queued_input_updated(&mut self, ev: QueuedInputEvent) {
+        let QueuedInputEvent {
+            queue,
+            started,
+            cleared,
+        } = ev;
+        for entry in &started {
+            let text = queued_input_text(entry);
+            if !text.is_empty() {
+                self.add_to_history(history_cell::new_user_prompt(text));
+            }
+        }
+        if !cleared.is_empty() {
+            let combined = cleared
                 .iter()
-                .map(|m| m.text.clone())
+                .map(queued_input_text)
                 .collect::<Vec<_>>()
                 .join("\n");
             self.bottom_pane.set_composer_text(combined);
-            // Clear the queue and update the status indicator list.
-            self.queued_user_messages.clear();
-            self.refresh_queued_user_messages();
         }
-
-        self.request_redraw();
+        self.queued_user_messages = queue;
+        self.refresh_queued_user_messages();
     }
 
     fn on_plan_update(&mut self, update: codex_core::plan_tool::UpdatePlanArgs) {
+        self.bottom_pane.set_plan(update.plan.clone());
         self.add_to_history(history_cell::new_plan_update(update));
     }
 
@@ -314,7 +450,17 @@ impl ChatWidget {
         );
     }
 
+    fn on_function_call_arguments_delta(&mut self, ev: FunctionCallArgumentsDeltaEvent) {
+        let args = self.function_call_args.entry(ev.call_id).or_default();
+        args.push_str(&ev.delta);
+        if let Some(preview) = extract_command_preview(args) {
+            self.bottom_pane
+                .update_status_header(format!("Running: {preview}"));
+        }
+    }
+
     fn on_exec_command_begin(&mut self, ev: ExecCommandBeginEvent) {
+        self.function_call_args.remove(&ev.call_id);
         self.flush_answer_stream_with_separator();
         let ev2 = ev.clone();
         self.defer_or_handle(|q| q.push_exec_begin(ev), |s| s.handle_exec_begin_now(ev2));
@@ -359,6 +505,19 @@ impl ChatWidget {
         self.defer_or_handle(|q| q.push_mcp_end(ev), |s| s.handle_mcp_end_now(ev2));
     }
 
+    /// Progress only updates the call's spinner row, so it is never deferred.
+    /// Progress for a call whose begin is still queued is dropped.
+    fn on_mcp_tool_call_progress(&mut self, ev: McpToolCallProgressEvent) {
+        if let Some(call) = self
+            .active_mcp_calls
+            .iter_mut()
+            .find(|call| call.call_id == ev.call_id)
+        {
+            call.set_progress(ev);
+            self.request_redraw();
+        }
+    }
+
     fn on_web_search_begin(&mut self, _ev: WebSearchBeginEvent) {
         self.flush_answer_stream_with_separator();
     }
@@ -384,6 +543,12 @@ impl ChatWidget {
             .on_history_entry_response(log_id, offset, entry.map(|e| e.text));
     }
 
+    fn on_search_history_response(&mut self, event: SearchHistoryResponseEvent) {
+        let matches = event.matches.into_iter().map(|m| m.entry.text).collect();
+        self.bottom_pane
+            .on_history_search_response(&event.query, matches);
+    }
+
     fn on_shutdown_complete(&mut self) {
         self.app_event_tx.send(AppEvent::ExitRequest);
     }
@@ -392,10 +557,65 @@ impl ChatWidget {
         debug!("TurnDiffEvent: {unified_diff}");
     }
 
+    fn on_review_findings(&mut self, ev: ReviewFindingsEvent) {
+        // The TUI cannot start reviews yet; findings still reach the log.
+        debug!("ReviewFindingsEvent: {ev:?}");
+    }
+
     fn on_background_event(&mut self, message: String) {
         debug!("BackgroundEvent: {message}");
     }
 
+    fn on_conversation_compacted(&mut self, ev: ConversationCompactedEvent) {
+        self.add_to_history(history_cell::new_conversation_compacted(ev));
+        self.request_redraw();
+    }
+
+    fn on_patch_undone(&mut self, ev: PatchUndoneEvent) {
+        self.add_to_history(history_cell::new_patch_undone(ev));
+        self.request_redraw();
+    }
+
+    fn on_mcp_startup_warning(&mut self, ev: McpStartupWarningEvent) {
+        self.add_to_history(history_cell::new_mcp_startup_warning(ev));
+        self.request_redraw();
+    }
+
+    fn on_auth_expired(&mut self, ev: AuthExpiredEvent) {
+        self.add_to_history(history_cell::new_auth_expired(ev));
+        self.request_redraw();
+    }
+
+    fn on_git_repo_warning(&mut self, ev: GitRepoWarningEvent) {
+        self.add_to_history(history_cell::new_git_repo_warning(ev));
+        self.request_redraw();
+    }
+
+    fn on_project_doc_truncated(&mut self, ev: ProjectDocTruncatedEvent) {
+        self.add_to_history(history_cell::new_project_doc_truncated(ev));
+        self.request_redraw();
+    }
+
+    fn on_pull_request(&mut self, ev: PullRequestEvent) {
+        self.add_to_history(history_cell::new_pull_request(ev));
+        self.request_redraw();
+    }
+
+    fn on_tool_output_budget_exceeded(&mut self, ev: ToolOutputBudgetExceededEvent) {
+        self.add_to_history(history_cell::new_tool_output_budget_exceeded(ev));
+        self.request_redraw();
+    }
+
+    /// A quiet background response is still running; say so in the status
+    /// line rather than let the turn look stuck.
+    fn on_background_response_progress(&mut self, ev: BackgroundResponseProgressEvent) {
+        let header = match ev.status.as_str() {
+            "queued" => "Waiting for the model (queued)".to_string(),
+            _ => "Waiting for the model".to_string(),
+        };
+        self.bottom_pane.update_status_header(header);
+    }
+
     fn on_stream_error(&mut self, message: String) {
         // Show stream errors in the transcript so users see retry/backoff info.
         self.add_to_history(history_cell::new_stream_error_event(message));
@@ -470,6 +690,7 @@ impl ChatWidget {
                 stdout: ev.stdout.clone(),
                 stderr: ev.stderr.clone(),
                 formatted_output: ev.formatted_output.clone(),
+                terminated: ev.terminated,
             },
         ));
 
@@ -496,7 +717,10 @@ impl ChatWidget {
         event: codex_core::protocol::PatchApplyEndEvent,
     ) {
         if event.success {
-            self.add_to_history(history_cell::new_patch_apply_success(event.stdout));
+            self.add_to_history(history_cell::new_patch_apply_success(
+                event.stdout,
+                &event.match_strategies,
+            ));
         } else {
             self.add_to_history(history_cell::new_patch_apply_failure(event.stderr));
         }
@@ -509,6 +733,8 @@ impl ChatWidget {
             id,
             command: ev.command,
             reason: ev.reason,
+            escalation_reason: ev.escalation_reason,
+            session_prefix: ev.session_prefix,
         };
         self.bottom_pane.push_approval_request(request);
         self.request_redraw();
@@ -529,6 +755,7 @@ impl ChatWidget {
             id,
             reason: ev.reason,
             grant_root: ev.grant_root,
+            changes: ev.changes,
         };
         self.bottom_pane.push_approval_request(request);
         self.request_redraw();
@@ -564,10 +791,14 @@ impl ChatWidget {
 
     pub(crate) fn handle_mcp_begin_now(&mut self, ev: McpToolCallBeginEvent) {
         self.flush_answer_stream_with_separator();
-        self.add_to_history(history_cell::new_active_mcp_tool_call(ev.invocation));
+        self.active_mcp_calls
+            .push(ActiveMcpToolCall::new(ev.call_id, ev.invocation));
+        self.request_redraw();
     }
     pub(crate) fn handle_mcp_end_now(&mut self, ev: McpToolCallEndEvent) {
         self.flush_answer_stream_with_separator();
+        self.active_mcp_calls
+            .retain(|call| call.call_id != ev.call_id);
         self.add_boxed_history(history_cell::new_completed_mcp_tool_call(
             80,
             ev.invocation,
@@ -580,18 +811,28 @@ impl ChatWidget {
         ));
     }
 
-    fn layout_areas(&self, area: Rect) -> [Rect; 2] {
+    fn layout_areas(&self, area: Rect) -> [Rect; 3] {
         Layout::vertical([
             Constraint::Max(
                 self.active_exec_cell
                     .as_ref()
                     .map_or(0, |c| c.desired_height(area.width)),
             ),
+            Constraint::Max(self.active_mcp_calls_height()),
             Constraint::Min(self.bottom_pane.desired_height(area.width)),
         ])
         .areas(area)
     }
 
+    /// One row per in-flight MCP tool call, plus a blank row above them.
+    fn active_mcp_calls_height(&self) -> u16 {
+        if self.active_mcp_calls.is_empty() {
+            0
+        } else {
+            self.active_mcp_calls.len() as u16 + 1
+        }
+    }
+
     pub(crate) fn new(
         config: Config,
         conversation_manager: Arc<ConversationManager>,
@@ -604,6 +845,7 @@ impl ChatWidget {
         let mut rng = rand::rng();
         let placeholder = EXAMPLE_PROMPTS[rng.random_range(0..EXAMPLE_PROMPTS.len())].to_string();
         let codex_op_tx = spawn_agent(config.clone(), app_event_tx.clone(), conversation_manager);
+        let expand_reasoning = config.tui.expand_reasoning;
 
         Self {
             app_event_tx: app_event_tx.clone(),
@@ -624,17 +866,22 @@ impl ChatWidget {
                 initial_images,
             ),
             total_token_usage: TokenUsage::default(),
-            last_token_usage: TokenUsage::default(),
+            rate_limits: None,
+            last_turn_metrics: None,
             stream: StreamController::new(config),
             running_commands: HashMap::new(),
+            active_mcp_calls: Vec::new(),
             pending_exec_completions: Vec::new(),
             task_complete_pending: false,
             interrupts: InterruptManager::new(),
             reasoning_buffer: String::new(),
-            full_reasoning_buffer: String::new(),
+            reasoning_sections: Vec::new(),
+            expand_reasoning,
+            function_call_args: HashMap::new(),
             session_id: None,
             last_history_was_exec: false,
-            queued_user_messages: VecDeque::new(),
+            queued_user_messages: Vec::new(),
+            next_turn_effort: None,
             show_welcome_banner: true,
         }
     }
@@ -653,6 +900,7 @@ impl ChatWidget {
 
         let codex_op_tx =
             spawn_agent_from_existing(conversation, session_configured, app_event_tx.clone());
+        let expand_reasoning = config.tui.expand_reasoning;
 
         Self {
             app_event_tx: app_event_tx.clone(),
@@ -670,17 +918,22 @@ impl ChatWidget {
             config: config.clone(),
             initial_user_message: None,
             total_token_usage: TokenUsage::default(),
-            last_token_usage: TokenUsage::default(),
+            rate_limits: None,
+            last_turn_metrics: None,
             stream: StreamController::new(config),
             running_commands: HashMap::new(),
+            active_mcp_calls: Vec::new(),
             pending_exec_completions: Vec::new(),
             task_complete_pending: false,
             interrupts: InterruptManager::new(),
             reasoning_buffer: String::new(),
-            full_reasoning_buffer: String::new(),
+            reasoning_sections: Vec::new(),
+            expand_reasoning,
+            function_call_args: HashMap::new(),
             session_id: None,
             last_history_was_exec: false,
-            queued_user_messages: VecDeque::new(),
+            queued_user_messages: Vec::new(),
+            next_turn_effort: None,
             show_welcome_banner: false,
         }
     }
@@ -691,6 +944,7 @@ impl ChatWidget {
                 .active_exec_cell
                 .as_ref()
                 .map_or(0, |c| c.desired_height(width))
+            + self.active_mcp_calls_height()
     }
 
     pub(crate) fn handle_key_event(&mut self, key_event: KeyEvent) {
@@ -711,18 +965,28 @@ impl ChatWidget {
         }
 
         match key_event {
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.toggle_expand_reasoning(),
             KeyEvent {
                 code: KeyCode::Up,
                 modifiers: KeyModifiers::ALT,
                 kind: KeyEventKind::Press,
                 ..
-            } if !self.queued_user_messages.is_empty() => {
-                // Prefer the most recently queued item.
-                if let Some(user_message) = self.queued_user_messages.pop_back() {
-                    self.bottom_pane.set_composer_text(user_message.text);
-                    self.refresh_queued_user_messages();
-                    self.request_redraw();
-                }
+            } if !self.queued_user_messages.is_empty() => self.edit_last_queued_input(),
+            KeyEvent {
+                code: KeyCode::Esc,
+                kind: KeyEventKind::Press,
+                ..
+            } if !self.queued_user_messages.is_empty()
+                && self.bottom_pane.is_task_running()
+                && self.bottom_pane.composer_is_empty()
+                && !self.bottom_pane.has_active_popup() =>
+            {
+                self.edit_last_queued_input()
             }
             _ => {
                 match self.bottom_pane.handle_key_event(key_event) {
@@ -731,10 +995,10 @@ impl ChatWidget {
                         let user_message = UserMessage {
                             text,
                             image_paths: self.bottom_pane.take_recent_submission_images(),
+                            mentioned_files: self.bottom_pane.take_recent_submission_mentions(),
                         };
                         if self.bottom_pane.is_task_running() {
-                            self.queued_user_messages.push_back(user_message);
-                            self.refresh_queued_user_messages();
+                            self.queue_user_message(user_message);
                         } else {
                             self.submit_user_message(user_message);
                         }
@@ -742,12 +1006,60 @@ impl ChatWidget {
                     InputResult::Command(cmd) => {
                         self.dispatch_command(cmd);
                     }
+                    InputResult::CommandWithArgs(cmd, args) => {
+                        self.dispatch_command_with_args(cmd, args);
+                    }
                     InputResult::None => {}
                 }
             }
         }
     }
 
+    fn dispatch_command_with_args(&mut self, cmd: SlashCommand, args: String) {
+        if !cmd.available_during_task() && self.bottom_pane.is_task_running() {
+            self.dispatch_command(cmd);
+            return;
+        }
+        match cmd {
+            SlashCommand::Mcp => match args.as_str() {
+                "tools" | "status" => self.add_mcp_output(),
+                "resources" => self.add_mcp_request_output(Op::ListMcpResources),
+                "prompts" => self.add_mcp_request_output(Op::ListMcpPrompts),
+                _ => {
+                    self.add_to_history(history_cell::new_error_event(format!(
+                        "Unknown argument '{args}'. Usage: /mcp [tools|status|resources|prompts]"
+                    )));
+                    self.request_redraw();
+                }
+            },
+            SlashCommand::Reasoning => {
+                match ReasoningEffortConfig::iter().find(|effort| effort.to_string() == args) {
+                    Some(effort) => {
+                        self.next_turn_effort = Some(effort);
+                        self.add_to_history(history_cell::new_next_turn_effort(effort));
+                    }
+                    None => {
+                        self.add_to_history(history_cell::new_error_event(format!(
+                            "Unknown reasoning effort '{args}'. {REASONING_USAGE}"
+                        )));
+                    }
+                }
+                self.request_redraw();
+            }
+            SlashCommand::Prompt => match parse_prompt_args(&args) {
+                Ok((name, arguments)) => {
+                    self.submit_op(Op::McpPrompt { name, arguments });
+                }
+                Err(message) => {
+                    self.add_to_history(history_cell::new_error_event(message));
+                    self.request_redraw();
+                }
+            },
+            // The remaining commands take no arguments.
+            _ => self.dispatch_command(cmd),
+        }
+    }
+
     pub(crate) fn attach_image(
         &mut self,
         path: PathBuf,
@@ -785,6 +1097,9 @@ impl ChatWidget {
                 self.clear_token_usage();
                 self.app_event_tx.send(AppEvent::CodexOp(Op::Compact));
             }
+            SlashCommand::Undo => {
+                self.app_event_tx.send(AppEvent::CodexOp(Op::UndoLastPatch));
+            }
             SlashCommand::Model => {
                 self.open_model_popup();
             }
@@ -795,7 +1110,7 @@ impl ChatWidget {
                 self.app_event_tx.send(AppEvent::ExitRequest);
             }
             SlashCommand::Logout => {
-                if let Err(e) = codex_login::logout(&self.config.codex_home) {
+                if let Err(e) = codex_login::logout(self.config.dirs.config_dir()) {
                     tracing::error!("failed to logout: {e}");
                 }
                 self.app_event_tx.send(AppEvent::ExitRequest);
@@ -826,6 +1141,13 @@ impl ChatWidget {
             SlashCommand::Mcp => {
                 self.add_mcp_output();
             }
+            SlashCommand::Prompt => {
+                self.add_mcp_request_output(Op::ListMcpPrompts);
+            }
+            SlashCommand::Reasoning => {
+                self.add_to_history(history_cell::new_error_event(REASONING_USAGE.to_string()));
+                self.request_redraw();
+            }
             #[cfg(debug_assertions)]
             SlashCommand::TestApproval => {
                 use codex_core::protocol::EventMsg;
@@ -914,7 +1236,11 @@ impl ChatWidget {
     }
 
     fn submit_user_message(&mut self, user_message: UserMessage) {
-        let UserMessage { text, image_paths } = user_message;
+        let UserMessage {
+            text,
+            image_paths,
+            mentioned_files,
+        } = user_message;
         let mut items: Vec<InputItem> = Vec::new();
 
         if !text.is_empty() {
@@ -925,15 +1251,35 @@ impl ChatWidget {
             items.push(InputItem::LocalImage { path });
         }
 
+        if let Some(max_bytes) = self.config.tui.attach_mentioned_files_max_bytes {
+            items.extend(mentioned_file_items(
+                &self.config.cwd,
+                &text,
+                &mentioned_files,
+                max_bytes,
+            ));
+        }
+
         if items.is_empty() {
             return;
         }
 
-        self.codex_op_tx
-            .send(Op::UserInput { items })
-            .unwrap_or_else(|e| {
-                tracing::error!("failed to send message: {e}");
-            });
+        let op = match self.next_turn_effort.take() {
+            Some(effort) => Op::UserTurn {
+                items,
+                cwd: self.config.cwd.clone(),
+                approval_policy: self.config.approval_policy,
+                sandbox_policy: self.config.sandbox_policy.clone(),
+                model: self.config.model.clone(),
+                effort: Some(effort),
+                summary: self.config.model_reasoning_summary,
+                final_output_json_schema: None,
+            },
+            None => Op::UserInput { items },
+        };
+        self.codex_op_tx.send(op).unwrap_or_else(|e| {
+            tracing::error!("failed to send message: {e}");
+        });
 
         // Persist the text to cross-session message history.
         if !text.is_empty() {
@@ -950,6 +1296,38 @@ impl ChatWidget {
         }
     }
 
+    /// Hand a message submitted during a turn to the session, which sends it
+    /// once the turn completes. It shows up in the transcript then.
+    fn queue_user_message(&mut self, user_message: UserMessage) {
+        let UserMessage {
+            text,
+            image_paths,
+            mentioned_files,
+        } = user_message;
+        let mut items: Vec<InputItem> = Vec::new();
+        if !text.is_empty() {
+            items.push(InputItem::Text { text: text.clone() });
+        }
+        for path in image_paths {
+            items.push(InputItem::LocalImage { path });
+        }
+        if let Some(max_bytes) = self.config.tui.attach_mentioned_files_max_bytes {
+            items.extend(mentioned_file_items(
+                &self.config.cwd,
+                &text,
+                &mentioned_files,
+                max_bytes,
+            ));
+        }
+        if items.is_empty() {
+            return;
+        }
+        self.submit_op(Op::QueueUserInput { items });
+        if !text.is_empty() {
+            self.submit_op(Op::AddToHistory { text });
+        }
+    }
+
     pub(crate) fn handle_codex_event(&mut self, event: Event) {
         let Event { id, msg } = event;
 
@@ -972,15 +1350,25 @@ impl ChatWidget {
             | EventMsg::AgentReasoningRawContentDelta(AgentReasoningRawContentDeltaEvent {
                 delta,
             }) => self.on_agent_reasoning_delta(delta),
-            EventMsg::AgentReasoning(AgentReasoningEvent { .. })
-            | EventMsg::AgentReasoningRawContent(AgentReasoningRawContentEvent { .. }) => {
-                self.on_agent_reasoning_final()
+            EventMsg::AgentReasoning(AgentReasoningEvent { text })
+            | EventMsg::AgentReasoningRawContent(AgentReasoningRawContentEvent { text }) => {
+                self.on_agent_reasoning_final(text)
             }
             EventMsg::AgentReasoningSectionBreak(_) => self.on_reasoning_section_break(),
             EventMsg::TaskStarted(_) => self.on_task_started(),
             EventMsg::TaskComplete(TaskCompleteEvent { .. }) => self.on_task_complete(),
-            EventMsg::TokenCount(token_usage) => self.on_token_count(token_usage),
-            EventMsg::Error(ErrorEvent { message }) => self.on_error(message),
+            // Running totals arrive via `UsageUpdate`, which the core keeps
+            // for the whole session.
+            EventMsg::TokenCount(_) => {}
+            EventMsg::UsageUpdate(ev) => self.on_usage_update(ev),
+            EventMsg::RateLimits(ev) => self.on_rate_limits(ev),
+            EventMsg::TurnMetrics(metrics) => self.last_turn_metrics = Some(metrics),
+            EventMsg::PromptTokenEstimate(ev) => self.on_prompt_token_estimate(ev),
+            EventMsg::ContextUsage(ev) => self.on_context_usage(ev),
+            EventMsg::ContextWindowWarning(ev) => self.on_context_window_warning(ev),
+            EventMsg::ConversationCompacted(ev) => self.on_conversation_compacted(ev),
+            EventMsg::PatchUndone(ev) => self.on_patch_undone(ev),
+            EventMsg::Error(ErrorEvent { message, .. }) => self.on_error(message),
             EventMsg::TurnAborted(ev) => match ev.reason {
                 TurnAbortReason::Interrupted => {
                     self.on_interrupted_turn();
@@ -992,6 +1380,7 @@ impl ChatWidget {
             EventMsg::PlanUpdate(update) => self.on_plan_update(update),
             EventMsg::ExecApprovalRequest(ev) => self.on_exec_approval_request(id, ev),
             EventMsg::ApplyPatchApprovalRequest(ev) => self.on_apply_patch_approval_request(id, ev),
+            EventMsg::FunctionCallArgumentsDelta(ev) => self.on_function_call_arguments_delta(ev),
             EventMsg::ExecCommandBegin(ev) => self.on_exec_command_begin(ev),
             EventMsg::ExecCommandOutputDelta(delta) => self.on_exec_command_output_delta(delta),
             EventMsg::PatchApplyBegin(ev) => self.on_patch_apply_begin(ev),
@@ -999,17 +1388,31 @@ impl ChatWidget {
             EventMsg::ExecCommandEnd(ev) => self.on_exec_command_end(ev),
             EventMsg::McpToolCallBegin(ev) => self.on_mcp_tool_call_begin(ev),
             EventMsg::McpToolCallEnd(ev) => self.on_mcp_tool_call_end(ev),
+            EventMsg::McpToolCallProgress(ev) => self.on_mcp_tool_call_progress(ev),
             EventMsg::WebSearchBegin(ev) => self.on_web_search_begin(ev),
             EventMsg::WebSearchEnd(ev) => self.on_web_search_end(ev),
             EventMsg::GetHistoryEntryResponse(ev) => self.on_get_history_entry_response(ev),
+            EventMsg::SearchHistoryResponse(ev) => self.on_search_history_response(ev),
             EventMsg::McpListToolsResponse(ev) => self.on_list_mcp_tools(ev),
+            EventMsg::StatusResponse(status) => self.on_status(status),
+            EventMsg::QueuedInputUpdated(ev) => self.on_queued_input_updated(ev),
+            EventMsg::McpListResourcesResponse(ev) => self.on_list_mcp_resources(ev),
+            EventMsg::McpListPromptsResponse(ev) => self.on_list_mcp_prompts(ev),
+            EventMsg::McpStartupWarning(ev) => self.on_mcp_startup_warning(ev),
+            EventMsg::AuthExpired(ev) => self.on_auth_expired(ev),
+            EventMsg::GitRepoWarning(ev) => self.on_git_repo_warning(ev),
+            EventMsg::ProjectDocTruncated(ev) => self.on_project_doc_truncated(ev),
+            EventMsg::PullRequest(ev) => self.on_pull_request(ev),
+            EventMsg::ToolOutputBudgetExceeded(ev) => self.on_tool_output_budget_exceeded(ev),
             EventMsg::ListCustomPromptsResponse(ev) => self.on_list_custom_prompts(ev),
             EventMsg::ShutdownComplete => self.on_shutdown_complete(),
             EventMsg::TurnDiff(TurnDiffEvent { unified_diff }) => self.on_turn_diff(unified_diff),
+            EventMsg::ReviewFindings(ev) => self.on_review_findings(ev),
             EventMsg::BackgroundEvent(BackgroundEventEvent { message }) => {
                 self.on_background_event(message)
             }
             EventMsg::StreamError(StreamErrorEvent { message }) => self.on_stream_error(message),
+            EventMsg::BackgroundResponseProgress(ev) => self.on_background_response_progress(ev),
             EventMsg::ConversationHistory(ev) => {
                 // Forward to App so it can process backtrack flows.
                 self.app_event_tx
@@ -1032,16 +1435,18 @@ impl ChatWidget {
         }
     }
 
-    // If idle and there are queued inputs, submit exactly one to start the next turn.
-    fn maybe_send_next_queued_input(&mut self) {
-        if self.bottom_pane.is_task_running() {
-            return;
-        }
-        if let Some(user_message) = self.queued_user_messages.pop_front() {
-            self.submit_user_message(user_message);
+    /// Take the most recently queued message out of the session's queue and
+    /// put it back into the composer for editing.
+    fn edit_last_queued_input(&mut self) {
+        if let Some(entry) = self.queued_user_messages.pop() {
+            self.submit_op(Op::RemoveQueuedInput {
+                id: entry.id.clone(),
+            });
+            self.bottom_pane
+                .set_composer_text(queued_input_text(&entry));
+            self.refresh_queued_user_messages();
+            self.request_redraw();
         }
-        // Update the list to reflect the remaining queued messages (if any).
-        self.refresh_queued_user_messages();
     }
 
     /// Rebuild and update the queued user messages from the current queue.
@@ -1049,7 +1454,7 @@ impl ChatWidget {
         let messages: Vec<String> = self
             .queued_user_messages
             .iter()
-            .map(|m| m.text.clone())
+            .map(queued_input_text)
             .collect();
         self.bottom_pane.set_queued_user_messages(messages);
     }
@@ -1062,11 +1467,18 @@ impl ChatWidget {
         self.request_redraw();
     }
 
+    /// Asks the session for its status; the panel is added once
+    /// `EventMsg::StatusResponse` arrives.
     pub(crate) fn add_status_output(&mut self) {
+        self.submit_op(Op::GetStatus);
+    }
+
+    fn on_status(&mut self, status: SessionStatus) {
         self.add_to_history(history_cell::new_status_output(
             &self.config,
-            &self.total_token_usage,
-            &self.session_id,
+            &status,
+            self.rate_limits.as_ref(),
+            self.last_turn_metrics.as_ref(),
         ));
     }
 
@@ -1180,6 +1592,16 @@ impl ChatWidget {
         }
     }
 
+    /// Submits `op`, a request for MCP resources or prompts, unless no MCP
+    /// server is configured.
+    fn add_mcp_request_output(&mut self, op: Op) {
+        if self.config.mcp_servers.is_empty() {
+            self.add_to_history(history_cell::empty_mcp_output());
+        } else {
+            self.submit_op(op);
+        }
+    }
+
     /// Forward file-search results to the bottom pane.
     pub(crate) fn apply_file_search_result(&mut self, query: String, matches: Vec<FileMatch>) {
         self.bottom_pane.on_file_search_result(query, matches);
@@ -1230,7 +1652,15 @@ impl ChatWidget {
     }
 
     fn on_list_mcp_tools(&mut self, ev: McpListToolsResponseEvent) {
-        self.add_to_history(history_cell::new_mcp_tools_output(&self.config, ev.tools));
+        self.add_to_history(history_cell::new_mcp_tools_output(&self.config, ev));
+    }
+
+    fn on_list_mcp_resources(&mut self, ev: McpListResourcesResponseEvent) {
+        self.add_to_history(history_cell::new_mcp_resources_output(ev.resources));
+    }
+
+    fn on_list_mcp_prompts(&mut self, ev: McpListPromptsResponseEvent) {
+        self.add_to_history(history_cell::new_mcp_prompts_output(ev.prompts));
     }
 
     fn on_list_custom_prompts(&mut self, ev: ListCustomPromptsResponseEvent) {
@@ -1266,26 +1696,31 @@ impl ChatWidget {
 
     pub(crate) fn clear_token_usage(&mut self) {
         self.total_token_usage = TokenUsage::default();
-        self.bottom_pane.set_token_usage(
-            self.total_token_usage.clone(),
-            self.last_token_usage.clone(),
-            self.config.model_context_window,
-        );
+        self.bottom_pane.set_prompt_token_estimate(None);
+        self.bottom_pane.set_context_left_percent(None);
+        self.bottom_pane
+            .set_token_usage(self.total_token_usage.clone(), None);
     }
 
     pub fn cursor_pos(&self, area: Rect) -> Option<(u16, u16)> {
-        let [_, bottom_pane_area] = self.layout_areas(area);
+        let [_, _, bottom_pane_area] = self.layout_areas(area);
         self.bottom_pane.cursor_pos(bottom_pane_area)
     }
 }
 
 impl WidgetRef for &ChatWidget {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
-        let [active_cell_area, bottom_pane_area] = self.layout_areas(area);
+        let [active_cell_area, active_mcp_area, bottom_pane_area] = self.layout_areas(area);
         (&self.bottom_pane).render(bottom_pane_area, buf);
         if let Some(cell) = &self.active_exec_cell {
             cell.render_ref(active_cell_area, buf);
         }
+        if !self.active_mcp_calls.is_empty() {
+            let lines: Vec<Line<'static>> = std::iter::once(Line::from(""))
+                .chain(self.active_mcp_calls.iter().map(|call| call.display_line()))
+                .collect();
+            Paragraph::new(lines).render(active_mcp_area, buf);
+        }
     }
 }
 
@@ -1298,34 +1733,6 @@ const EXAMPLE_PROMPTS: [&str; 6] = [
     "Improve documentation in @filename",
 ];
 
-fn add_token_usage(current_usage: &TokenUsage, new_usage: &TokenUsage) -> TokenUsage {
-    let cached_input_tokens = match (
-        current_usage.cached_input_tokens,
-        new_usage.cached_input_tokens,
-    ) {
-        (Some(current), Some(new)) => Some(current + new),
-        (Some(current), None) => Some(current),
-        (None, Some(new)) => Some(new),
-        (None, None) => None,
-    };
-    let reasoning_output_tokens = match (
-        current_usage.reasoning_output_tokens,
-        new_usage.reasoning_output_tokens,
-    ) {
-        (Some(current), Some(new)) => Some(current + new),
-        (Some(current), None) => Some(current),
-        (None, Some(new)) => Some(new),
-        (None, None) => None,
-    };
-    TokenUsage {
-        input_tokens: current_usage.input_tokens + new_usage.input_tokens,
-        cached_input_tokens,
-        output_tokens: current_usage.output_tokens + new_usage.output_tokens,
-        reasoning_output_tokens,
-        total_tokens: current_usage.total_tokens + new_usage.total_tokens,
-    }
-}
-
 // Extract the first bold (Markdown) element in the form **...** from `s`.
 // Returns the inner text if found; otherwise `None`.
 fn extract_first_bold(s: &str) -> Option<String> {
@@ -1356,5 +1763,91 @@ fn extract_first_bold(s: &str) -> Option<String> {
     None
 }
 
+/// Extracts a human-readable preview of the `command` array from partially
+/// streamed shell tool arguments, e.g. `{"command":["git","di` yields
+/// `git di…`. A leading `bash -lc` wrapper is dropped. Returns `None` until at
+/// least part of the first argument has arrived.
+fn extract_command_preview(args: &str) -> Option<String> {
+    let after_key = &args[args.find("\"command\"")? + "\"command\"".len()..];
+    let after_bracket = &after_key[after_key.find('[')? + 1..];
+
+    let mut parts: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut complete = false;
+    let mut chars = after_bracket.chars();
+    while let Some(c) = chars.next() {
+        match current.as_mut() {
+            None => match c {
+                '"' => current = Some(String::new()),
+                ']' => {
+                    complete = true;
+                    break;
+                }
+                _ => {}
+            },
+            Some(part) => match c {
+                '"' => {
+                    parts.push(std::mem::take(part));
+                    current = None;
+                }
+                '\\' => match chars.next() {
+                    Some('n') => part.push('\n'),
+                    Some('t') => part.push('\t'),
+                    Some(escaped) => part.push(escaped),
+                    None => break,
+                },
+                c => part.push(c),
+            },
+        }
+    }
+    if let Some(part) = current {
+        parts.push(part);
+    }
+
+    if parts.len() >= 2 && parts[0] == "bash" && parts[1] == "-lc" {
+        parts.drain(..2);
+    }
+    let preview = parts.join(" ");
+    if preview.trim().is_empty() {
+        return None;
+    }
+    if complete {
+        Some(preview)
+    } else {
+        Some(format!("{preview}…"))
+    }
+}
+
+/// Splits the arguments of `/prompt` into the prompt name and its
+/// `key=value` arguments. Values may be quoted like shell words.
+fn parse_prompt_args(args: &str) -> Result<(String, HashMap<String, String>), String> {
+    const USAGE: &str = "Usage: /prompt <name> [arg=value ...]";
+    let words = shlex::split(args).ok_or_else(|| format!("Unbalanced quotes. {USAGE}"))?;
+    let Some((name, rest)) = words.split_first() else {
+        return Err(USAGE.to_string());
+    };
+    let mut arguments = HashMap::new();
+    for word in rest {
+        let Some((key, value)) = word.split_once('=') else {
+            return Err(format!("Expected arg=value, got '{word}'. {USAGE}"));
+        };
+        arguments.insert(key.to_string(), value.to_string());
+    }
+    Ok((name.clone(), arguments))
+}
+
+/// The text of a queued message, without its images.
+fn queued_input_text(entry: &QueuedInput) -> String {
+    entry
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            InputItem::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests;
@@ -11,13 +11,19 @@ use codex_core::protocol::AgentMessageDeltaEvent;
 use codex_core::protocol::AgentMessageEvent;
 use codex_core::protocol::AgentReasoningDeltaEvent;
 use codex_core::protocol::AgentReasoningEvent;
+use codex_core::protocol::AgentReasoningSectionBreakEvent;
 use codex_core::protocol::ApplyPatchApprovalRequestEvent;
+use codex_core::protocol::ContextBreakdown;
 use codex_core::protocol::Event;
 use codex_core::protocol::EventMsg;
 use codex_core::protocol::ExecApprovalRequestEvent;
 use codex_core::protocol::ExecCommandBeginEvent;
 use codex_core::protocol::ExecCommandEndEvent;
 use codex_core::protocol::FileChange;
+use codex_core::protocol::McpInvocation;
+use codex_core::protocol::McpToolCallBeginEvent;
+use codex_core::protocol::McpToolCallEndEvent;
+use codex_core::protocol::McpToolCallProgressEvent;
 use codex_core::protocol::PatchApplyBeginEvent;
 use codex_core::protocol::PatchApplyEndEvent;
 use codex_core::protocol::StreamErrorEvent;
@@ -174,19 +180,24 @@ fn make_chatwidget_manual() -> (
         config: cfg.clone(),
         initial_user_message: None,
         total_token_usage: TokenUsage::default(),
-        last_token_usage: TokenUsage::default(),
+        rate_limits: None,
+        last_turn_metrics: None,
         stream: StreamController::new(cfg),
         running_commands: HashMap::new(),
+        active_mcp_calls: Vec::new(),
         pending_exec_completions: Vec::new(),
         task_complete_pending: false,
         interrupts: InterruptManager::new(),
         reasoning_buffer: String::new(),
-        full_reasoning_buffer: String::new(),
+        reasoning_sections: Vec::new(),
+        expand_reasoning: false,
+        function_call_args: HashMap::new(),
         session_id: None,
         frame_requester: crate::tui::FrameRequester::test_dummy(),
         show_welcome_banner: true,
         last_history_was_exec: false,
-        queued_user_messages: std::collections::VecDeque::new(),
+        queued_user_messages: Vec::new(),
+        next_turn_effort: None,
     };
     (widget, rx, op_rx)
 }
@@ -240,34 +251,130 @@ fn open_fixture(name: &str) -> std::fs::File {
     File::open(name).expect("open fixture file")
 }
 
-#[test]
-fn alt_up_edits_most_recent_queued_message() {
-    let (mut chat, _rx, _op_rx) = make_chatwidget_manual();
+fn queued(id: &str, text: &str) -> QueuedInput {
+    QueuedInput {
+        id: id.to_string(),
+        items: vec![InputItem::Text {
+            text: text.to_string(),
+        }],
+    }
+}
 
-    // Simulate a running task so messages would normally be queued.
+fn queue_updated(
+    queue: Vec<QueuedInput>,
+    started: Vec<QueuedInput>,
+    cleared: Vec<QueuedInput>,
+) -> Event {
+    Event {
+        id: String::new(),
+        msg: EventMsg::QueuedInputUpdated(QueuedInputEvent {
+            queue,
+            started,
+            cleared,
+        }),
+    }
+}
+
+#[test]
+fn input_submitted_during_a_task_is_queued_in_the_session() {
+    let (mut chat, mut rx, mut op_rx) = make_chatwidget_manual();
     chat.bottom_pane.set_task_running(true);
 
-    // Seed two queued messages.
-    chat.queued_user_messages
-        .push_back(UserMessage::from("first queued".to_string()));
-    chat.queued_user_messages
-        .push_back(UserMessage::from("second queued".to_string()));
-    chat.refresh_queued_user_messages();
+    chat.bottom_pane.set_composer_text("follow-up".to_string());
+    chat.handle_key_event(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    match op_rx.try_recv() {
+        Ok(Op::QueueUserInput { items }) => assert_eq!(
+            items,
+            vec![InputItem::Text {
+                text: "follow-up".to_string()
+            }]
+        ),
+        other => panic!("expected QueueUserInput, got {other:?}"),
+    }
+    assert!(matches!(op_rx.try_recv(), Ok(Op::AddToHistory { .. })));
+    // Nothing joins the transcript until the session starts the turn.
+    assert!(drain_insert_history(&mut rx).is_empty());
+
+    chat.handle_codex_event(queue_updated(
+        vec![queued("2", "follow-up")],
+        vec![],
+        vec![],
+    ));
+    assert_eq!(chat.queued_user_messages, vec![queued("2", "follow-up")]);
+
+    chat.handle_codex_event(queue_updated(
+        vec![],
+        vec![queued("2", "follow-up")],
+        vec![],
+    ));
+    assert!(chat.queued_user_messages.is_empty());
+    let cells = drain_insert_history(&mut rx);
+    assert_eq!(cells.len(), 1);
+    assert!(lines_to_single_string(&cells[0]).contains("follow-up"));
+}
 
-    // Press Alt+Up to edit the most recent (last) queued message.
-    chat.handle_key_event(KeyEvent::new(KeyCode::Up, KeyModifiers::ALT));
+#[test]
+fn mentioned_files_are_attached_when_small_text_and_still_mentioned() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::create_dir(dir.path().join("src")).expect("mkdir");
+    std::fs::write(dir.path().join("src/small.rs"), "fn main() {}").expect("write");
+    std::fs::write(dir.path().join("big.txt"), "x".repeat(64)).expect("write");
+    std::fs::write(dir.path().join("removed.txt"), "gone").expect("write");
+    std::fs::write(dir.path().join("binary.bin"), [0xff, 0xfe]).expect("write");
+
+    let paths = [
+        "src/small.rs",
+        "big.txt",
+        "removed.txt",
+        "binary.bin",
+        "src/small.rs",
+    ]
+    .map(ToString::to_string);
+    let items = mentioned_file_items(
+        dir.path(),
+        "compare src/small.rs with big.txt and binary.bin",
+        &paths,
+        32,
+    );
+    assert_eq!(
+        items,
+        vec![InputItem::Text {
+            text: "<mentioned_file path=\"src/small.rs\">\nfn main() {}\n</mentioned_file>"
+                .to_string()
+        }]
+    );
+}
 
-    // Composer should now contain the last queued message.
+#[test]
+fn esc_and_alt_up_edit_most_recent_queued_message() {
+    let (mut chat, _rx, mut op_rx) = make_chatwidget_manual();
+    chat.bottom_pane.set_task_running(true);
+    chat.handle_codex_event(queue_updated(
+        vec![queued("1", "first queued"), queued("2", "second queued")],
+        vec![],
+        vec![],
+    ));
+
+    // Esc takes the most recent (last) queued message back for editing.
+    chat.handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
     assert_eq!(
         chat.bottom_pane.composer_text(),
         "second queued".to_string()
     );
-    // And the queue should now contain only the remaining (older) item.
-    assert_eq!(chat.queued_user_messages.len(), 1);
-    assert_eq!(
-        chat.queued_user_messages.front().unwrap().text,
-        "first queued"
-    );
+    assert_eq!(chat.queued_user_messages, vec![queued("1", "first queued")]);
+    match op_rx.try_recv() {
+        Ok(Op::RemoveQueuedInput { id }) => assert_eq!(id, "2"),
+        other => panic!("expected RemoveQueuedInput, got {other:?}"),
+    }
+
+    // With text in the composer, Alt+Up still reaches the queue.
+    chat.handle_key_event(KeyEvent::new(KeyCode::Up, KeyModifiers::ALT));
+    assert_eq!(chat.bottom_pane.composer_text(), "first queued".to_string());
+    assert!(chat.queued_user_messages.is_empty());
+    assert!(matches!(
+        op_rx.try_recv(),
+        Ok(Op::RemoveQueuedInput { id }) if id == "1"
+    ));
 }
 
 #[test]
@@ -301,6 +408,7 @@ fn exec_history_cell_shows_working_then_completed() {
             exit_code: 0,
             duration: std::time::Duration::from_millis(5),
             formatted_output: "done".into(),
+            terminated: None,
         }),
     });
 
@@ -352,6 +460,7 @@ fn exec_history_cell_shows_working_then_failed() {
             exit_code: 2,
             duration: std::time::Duration::from_millis(7),
             formatted_output: "".into(),
+            terminated: None,
         }),
     });
 
@@ -443,6 +552,7 @@ fn exec_history_extends_previous_when_consecutive() {
             exit_code: 0,
             duration: std::time::Duration::from_millis(5),
             formatted_output: "one".into(),
+            terminated: None,
         }),
     });
     let first_cells = drain_insert_history(&mut rx);
@@ -473,6 +583,7 @@ fn exec_history_extends_previous_when_consecutive() {
             exit_code: 0,
             duration: std::time::Duration::from_millis(5),
             formatted_output: "two".into(),
+            terminated: None,
         }),
     });
     let second_cells = drain_insert_history(&mut rx);
@@ -714,6 +825,8 @@ fn approval_modal_exec_snapshot() {
         command: vec!["bash".into(), "-lc".into(), "echo hello world".into()],
         cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
         reason: Some("Model wants to run a command".into()),
+        escalation_reason: None,
+        session_prefix: None,
     };
     chat.handle_codex_event(Event {
         id: "sub-approve".into(),
@@ -769,23 +882,26 @@ fn approval_modal_patch_snapshot() {
 fn interrupt_restores_queued_messages_into_composer() {
     let (mut chat, mut rx, mut op_rx) = make_chatwidget_manual();
 
-    // Simulate a running task to enable queuing of user inputs.
+    // Simulate a running task with two queued user messages.
     chat.bottom_pane.set_task_running(true);
+    chat.handle_codex_event(queue_updated(
+        vec![queued("1", "first queued"), queued("2", "second queued")],
+        vec![],
+        vec![],
+    ));
 
-    // Queue two user messages while the task is running.
-    chat.queued_user_messages
-        .push_back(UserMessage::from("first queued".to_string()));
-    chat.queued_user_messages
-        .push_back(UserMessage::from("second queued".to_string()));
-    chat.refresh_queued_user_messages();
-
-    // Deliver a TurnAborted event with Interrupted reason (as if Esc was pressed).
+    // Deliver the TurnAborted event and the cleared queue, as after Esc.
     chat.handle_codex_event(Event {
         id: "turn-1".into(),
         msg: EventMsg::TurnAborted(codex_core::protocol::TurnAbortedEvent {
             reason: codex_core::protocol::TurnAbortReason::Interrupted,
         }),
     });
+    chat.handle_codex_event(queue_updated(
+        vec![],
+        vec![],
+        vec![queued("1", "first queued"), queued("2", "second queued")],
+    ));
 
     // Composer should now contain the queued messages joined by newlines, in order.
     assert_eq!(
@@ -793,7 +909,7 @@ fn interrupt_restores_queued_messages_into_composer() {
         "first queued\nsecond queued"
     );
 
-    // Queue should be cleared and no new user input should have been auto-submitted.
+    // Queue should be cleared and no new user input should have been submitted.
     assert!(chat.queued_user_messages.is_empty());
     assert!(
         op_rx.try_recv().is_err(),
@@ -804,6 +920,36 @@ fn interrupt_restores_queued_messages_into_composer() {
     let _ = drain_insert_history(&mut rx);
 }
 
+#[test]
+fn interrupt_keeps_partially_streamed_answer() {
+    let (mut chat, mut rx, _op_rx) = make_chatwidget_manual();
+    chat.bottom_pane.set_task_running(true);
+
+    chat.handle_codex_event(Event {
+        id: "turn-1".into(),
+        msg: EventMsg::AgentMessageDelta(AgentMessageDeltaEvent {
+            delta: "Half of an ans".into(),
+        }),
+    });
+    chat.handle_codex_event(Event {
+        id: "turn-1".into(),
+        msg: EventMsg::TurnAborted(codex_core::protocol::TurnAbortedEvent {
+            reason: codex_core::protocol::TurnAbortReason::Interrupted,
+        }),
+    });
+
+    let blob = drain_insert_history(&mut rx)
+        .iter()
+        .map(Vec::as_slice)
+        .map(lines_to_single_string)
+        .collect::<String>();
+    assert!(
+        blob.contains("Half of an ans"),
+        "partial answer should be flushed into history: {blob}"
+    );
+    assert!(blob.contains("Tell the model what to do differently"));
+}
+
 // Snapshot test: ChatWidget at very small heights (idle)
 // Ensures overall layout behaves when terminal height is extremely constrained.
 #[test]
@@ -880,6 +1026,8 @@ fn status_widget_and_approval_modal_snapshot() {
         command: vec!["echo".into(), "hello world".into()],
         cwd: std::path::PathBuf::from("/tmp"),
         reason: Some("Codex wants to run a command".into()),
+        escalation_reason: None,
+        session_prefix: None,
     };
     chat.handle_codex_event(Event {
         id: "sub-approve-exec".into(),
@@ -986,6 +1134,7 @@ fn apply_patch_events_emit_history_cells() {
         stdout: "ok\n".into(),
         stderr: String::new(),
         success: true,
+        match_strategies: HashMap::new(),
     };
     chat.handle_codex_event(Event {
         id: "s1".into(),
@@ -1028,7 +1177,7 @@ fn apply_patch_approval_sends_op_with_submission_id() {
     // Expect a CodexOp with PatchApproval carrying the submission id, not call id
     let mut found = false;
     while let Ok(app_ev) = rx.try_recv() {
-        if let AppEvent::CodexOp(Op::PatchApproval { id, decision }) = app_ev {
+        if let AppEvent::CodexOp(Op::PatchApproval { id, decision, .. }) = app_ev {
             assert_eq!(id, "sub-123");
             assert!(matches!(
                 decision,
@@ -1078,7 +1227,7 @@ fn apply_patch_full_flow_integration_like() {
         .try_recv()
         .expect("expected op forwarded to codex channel");
     match forwarded {
-        Op::PatchApproval { id, decision } => {
+        Op::PatchApproval { id, decision, .. } => {
             assert_eq!(id, "sub-xyz");
             assert!(matches!(
                 decision,
@@ -1109,6 +1258,7 @@ fn apply_patch_full_flow_integration_like() {
             stdout: String::from("ok"),
             stderr: String::new(),
             success: true,
+            match_strategies: HashMap::new(),
         }),
     });
 }
@@ -1439,7 +1589,8 @@ fn final_reasoning_then_message_without_deltas_are_rendered() {
     let cells = drain_insert_history(&mut rx);
     let combined = cells
         .iter()
-        .map(|lines| lines_to_single_string(lines))
+        .map(Vec::as_slice)
+        .map(lines_to_single_string)
         .collect::<String>();
     assert_snapshot!(combined);
 }
@@ -1500,7 +1651,321 @@ fn deltas_then_same_final_message_are_rendered_snapshot() {
     let cells = drain_insert_history(&mut rx);
     let combined = cells
         .iter()
-        .map(|lines| lines_to_single_string(lines))
+        .map(Vec::as_slice)
+        .map(lines_to_single_string)
         .collect::<String>();
     assert_snapshot!(combined);
 }
+
+#[test]
+fn reasoning_summary_parts_are_flushed_when_the_task_completes() {
+    let (mut chat, mut rx, _op_rx) = make_chatwidget_manual();
+
+    let reasoning_delta = |chat: &mut ChatWidget, delta: &str| {
+        chat.handle_codex_event(Event {
+            id: "s1".into(),
+            msg: EventMsg::AgentReasoningDelta(AgentReasoningDeltaEvent {
+                delta: delta.into(),
+            }),
+        });
+    };
+    reasoning_delta(
+        &mut chat,
+        "**Reading the config**\n\nLooking at the config file.",
+    );
+    chat.handle_codex_event(Event {
+        id: "s1".into(),
+        msg: EventMsg::AgentReasoningSectionBreak(AgentReasoningSectionBreakEvent {}),
+    });
+    // The task ends while the second part is still streaming.
+    reasoning_delta(&mut chat, "Planning the change. First the");
+    chat.handle_codex_event(Event {
+        id: "s1".into(),
+        msg: EventMsg::TaskComplete(TaskCompleteEvent {
+            last_agent_message: None,
+        }),
+    });
+
+    let mut cells = Vec::new();
+    while let Ok(ev) = rx.try_recv() {
+        if let AppEvent::InsertHistoryCell(cell) = ev {
+            cells.push(cell);
+        }
+    }
+    assert_eq!(cells.len(), 1, "expected a single reasoning cell");
+    assert_eq!(
+        lines_to_single_string(&cells[0].display_lines()),
+        "\nthinking\n  ▸ Reading the config\n  ▸ Planning the change.\n"
+    );
+    let transcript = lines_to_single_string(&cells[0].transcript_lines());
+    assert!(
+        transcript.contains("Looking at the config file."),
+        "{transcript}"
+    );
+    assert!(
+        transcript.contains("  ───\nPlanning the change.\nFirst the"),
+        "{transcript}"
+    );
+}
+
+#[test]
+fn ctrl_o_expands_later_reasoning_summaries() {
+    let (mut chat, mut rx, _op_rx) = make_chatwidget_manual();
+
+    chat.handle_key_event(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+    chat.handle_codex_event(Event {
+        id: "s1".into(),
+        msg: EventMsg::AgentReasoning(AgentReasoningEvent {
+            text: "Checking the tests. They all pass.".into(),
+        }),
+    });
+
+    let cells = drain_insert_history(&mut rx);
+    let combined = cells
+        .iter()
+        .map(Vec::as_slice)
+        .map(lines_to_single_string)
+        .collect::<String>();
+    assert!(
+        combined.contains("reasoning summaries now show their full text"),
+        "{combined}"
+    );
+    assert!(
+        combined.contains("thinking\nChecking the tests.\nThey all pass."),
+        "{combined}"
+    );
+}
+
+#[test]
+fn command_preview_from_partial_arguments() {
+    assert_eq!(extract_command_preview(r#"{"comm"#), None);
+    assert_eq!(extract_command_preview(r#"{"command":["#), None);
+    assert_eq!(
+        extract_command_preview(r#"{"command":["git","di"#),
+        Some("git di…".to_string())
+    );
+    assert_eq!(
+        extract_command_preview(r#"{"command":["bash","-lc","echo \"hi\""]"#),
+        Some("echo \"hi\"".to_string())
+    );
+}
+
+#[test]
+fn function_call_argument_deltas_accumulate_per_call() {
+    let (mut chat, _rx, _op_rx) = make_chatwidget_manual();
+
+    for delta in [r#"{"command":["#, r#""git","di"#] {
+        chat.handle_codex_event(Event {
+            id: "sub".into(),
+            msg: EventMsg::FunctionCallArgumentsDelta(FunctionCallArgumentsDeltaEvent {
+                call_id: "call_1".into(),
+                delta: delta.into(),
+            }),
+        });
+    }
+    assert_eq!(
+        chat.function_call_args.get("call_1").map(String::as_str),
+        Some(r#"{"command":["git","di"#)
+    );
+
+    chat.handle_codex_event(Event {
+        id: "sub".into(),
+        msg: EventMsg::ExecCommandBegin(ExecCommandBeginEvent {
+            call_id: "call_1".into(),
+            command: vec!["git".into(), "diff".into()],
+            cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            parsed_cmd: vec![],
+        }),
+    });
+    assert!(chat.function_call_args.is_empty());
+}
+
+#[test]
+fn usage_updates_replace_running_totals() {
+    let (mut chat, _rx, _op_rx) = make_chatwidget_manual();
+
+    let usage = |input_tokens, cached_input_tokens, output_tokens| TokenUsage {
+        input_tokens,
+        cached_input_tokens: Some(cached_input_tokens),
+        output_tokens,
+        reasoning_output_tokens: None,
+        total_tokens: input_tokens + output_tokens,
+    };
+
+    // Mid-stream snapshot followed by the final one for the same response.
+    for (last, total) in [
+        (usage(1_000, 200, 10), usage(1_000, 200, 10)),
+        (usage(1_000, 200, 50), usage(1_000, 200, 50)),
+    ] {
+        chat.handle_codex_event(Event {
+            id: "sub".into(),
+            msg: EventMsg::UsageUpdate(UsageUpdateEvent {
+                total_token_usage: total,
+                last_token_usage: last,
+                total_cost_usd: None,
+                last_cost_usd: None,
+            }),
+        });
+    }
+
+    // Totals come straight from the core rather than being summed here.
+    assert_eq!(chat.token_usage().input_tokens, 1_000);
+    assert_eq!(chat.token_usage().cached_input(), 200);
+    assert_eq!(chat.token_usage().output_tokens, 50);
+
+    chat.handle_codex_event(Event {
+        id: "sub".into(),
+        msg: EventMsg::TokenCount(usage(1_000, 200, 50)),
+    });
+    assert_eq!(chat.token_usage().output_tokens, 50);
+}
+
+#[test]
+fn context_usage_shows_in_footer_and_warnings_in_history() {
+    let (mut chat, mut rx, _op_rx) = make_chatwidget_manual();
+    chat.handle_codex_event(Event {
+        id: "sub".into(),
+        msg: EventMsg::ContextUsage(ContextUsageEvent {
+            tokens_in_context: 164_000,
+            context_window: 200_000,
+            percent_left: 18,
+            breakdown: ContextBreakdown::default(),
+        }),
+    });
+    let area = ratatui::layout::Rect::new(0, 0, 120, chat.desired_height(120));
+    let rows = render_rows(&chat, area);
+    assert!(
+        rows.iter().any(|row| row.contains("context left: 18%")),
+        "footer should show the context left: {rows:#?}"
+    );
+
+    chat.handle_codex_event(Event {
+        id: "sub".into(),
+        msg: EventMsg::ContextWindowWarning(ContextWindowWarningEvent {
+            threshold_percent: 75,
+            percent_left: 18,
+        }),
+    });
+    let cells = drain_insert_history(&mut rx);
+    let text = lines_to_single_string(cells.last().expect("warning cell"));
+    assert!(text.contains("over 75% full (18% left)"), "{text}");
+}
+
+#[test]
+fn prompt_args_are_split_into_name_and_arguments() {
+    let (name, arguments) = parse_prompt_args("git__review base=main 'title=Fix the bug'").unwrap();
+    assert_eq!(name, "git__review");
+    assert_eq!(
+        arguments,
+        HashMap::from([
+            ("base".to_string(), "main".to_string()),
+            ("title".to_string(), "Fix the bug".to_string()),
+        ])
+    );
+
+    assert_eq!(
+        parse_prompt_args("review main"),
+        Err("Expected arg=value, got 'main'. Usage: /prompt <name> [arg=value ...]".to_string())
+    );
+}
+
+#[test]
+fn reasoning_command_overrides_effort_for_next_message_only() {
+    let (mut chat, mut rx, mut op_rx) = make_chatwidget_manual();
+
+    chat.dispatch_command_with_args(SlashCommand::Reasoning, "high".to_string());
+    chat.submit_user_message(UserMessage::from("hard question".to_string()));
+    match op_rx.try_recv() {
+        Ok(Op::UserTurn { effort, model, .. }) => {
+            assert_eq!(effort, Some(ReasoningEffortConfig::High));
+            assert_eq!(model, chat.config.model);
+        }
+        other => panic!("expected UserTurn, got {other:?}"),
+    }
+    assert!(matches!(op_rx.try_recv(), Ok(Op::AddToHistory { .. })));
+
+    chat.submit_user_message(UserMessage::from("easy question".to_string()));
+    assert!(matches!(op_rx.try_recv(), Ok(Op::UserInput { .. })));
+
+    chat.dispatch_command_with_args(SlashCommand::Reasoning, "extreme".to_string());
+    assert_eq!(chat.next_turn_effort, None);
+    let _ = drain_insert_history(&mut rx);
+}
+
+fn render_rows(chat: &ChatWidget, area: ratatui::layout::Rect) -> Vec<String> {
+    let mut buf = ratatui::buffer::Buffer::empty(area);
+    (&chat).render_ref(area, &mut buf);
+    (0..area.height)
+        .map(|y| {
+            (0..area.width)
+                .map(|x| buf[(x, y)].symbol().chars().next().unwrap_or(' '))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn mcp_tool_calls_show_a_spinner_row_while_in_flight() {
+    let (mut chat, mut rx, _op_rx) = make_chatwidget_manual();
+    let invocation = |tool: &str| McpInvocation {
+        server: "docs".into(),
+        tool: tool.into(),
+        arguments: Some(serde_json::json!({ "query": "codex" })),
+        arguments_truncated: false,
+    };
+    for (call_id, tool) in [("call-1", "search"), ("call-2", "fetch")] {
+        chat.handle_codex_event(Event {
+            id: "sub-1".into(),
+            msg: EventMsg::McpToolCallBegin(McpToolCallBeginEvent {
+                call_id: call_id.into(),
+                invocation: invocation(tool),
+            }),
+        });
+    }
+    chat.handle_codex_event(Event {
+        id: "sub-1".into(),
+        msg: EventMsg::McpToolCallProgress(McpToolCallProgressEvent {
+            call_id: "call-1".into(),
+            progress: 2.0,
+            total: Some(5.0),
+            message: Some("indexing".into()),
+        }),
+    });
+    assert!(drain_insert_history(&mut rx).is_empty());
+
+    let area = ratatui::layout::Rect::new(0, 0, 100, chat.desired_height(100));
+    let rows = render_rows(&chat, area);
+    let search = rows
+        .iter()
+        .find(|row| row.contains("docs.search"))
+        .expect("row for docs.search");
+    assert!(search.contains("2/5 indexing"), "{search:?}");
+    assert!(
+        rows.iter().any(|row| row.contains("docs.fetch")),
+        "{rows:?}"
+    );
+
+    chat.handle_codex_event(Event {
+        id: "sub-1".into(),
+        msg: EventMsg::McpToolCallEnd(McpToolCallEndEvent {
+            call_id: "call-1".into(),
+            invocation: invocation("search"),
+            duration: std::time::Duration::from_millis(5),
+            result: Err("boom".into()),
+        }),
+    });
+    let cells = drain_insert_history(&mut rx);
+    assert_eq!(cells.len(), 1);
+    assert!(lines_to_single_string(&cells[0]).contains("docs.search"));
+
+    let area = ratatui::layout::Rect::new(0, 0, 100, chat.desired_height(100));
+    let rows = render_rows(&chat, area);
+    assert!(
+        !rows.iter().any(|row| row.contains("docs.search")),
+        "{rows:?}"
+    );
+    assert!(
+        rows.iter().any(|row| row.contains("docs.fetch")),
+        "{rows:?}"
+    );
+}
@@ -58,6 +58,26 @@ pub struct Cli {
     #[arg(long = "search", default_value_t = false)]
     pub web_search: bool,
 
+    /// Do not warn or ask for confirmation when running outside a git
+    /// repository or in one with uncommitted changes.
+    #[arg(long = "skip-git-repo-check", default_value_t = false)]
+    pub skip_git_repo_check: bool,
+
     #[clap(skip)]
     pub config_overrides: CliConfigOverrides,
+
+    /// Session to resume, set by `codex resume`.
+    #[clap(skip)]
+    pub resume: Option<ResumeArgs>,
+}
+
+/// Which recorded session to continue and how.
+#[derive(Debug, Clone, Default)]
+pub struct ResumeArgs {
+    /// Id of the session to resume. When `None`, the user picks one of the
+    /// recent sessions.
+    pub session_id: Option<String>,
+
+    /// Continue in a new session instead of appending to the original one.
+    pub fork: bool,
 }
@@ -69,13 +69,10 @@ pub(crate) fn create_diff_summary(
                 added: content.lines().count(),
                 removed: 0,
             }),
-            FileChange::Delete => files.push(FileSummary {
+            FileChange::Delete { content } => files.push(FileSummary {
                 display_path: path.display().to_string(),
                 added: 0,
-                removed: std::fs::read_to_string(path)
-                    .ok()
-                    .map(|s| s.lines().count())
-                    .unwrap_or(0),
+                removed: content.lines().count(),
             }),
             FileChange::Update {
                 unified_diff,
@@ -174,7 +171,7 @@ fn render_patch_details(changes: &HashMap<PathBuf, FileChange>) -> Vec<RtLine<'s
         .map(|(w, _)| w as usize)
         .unwrap_or(DEFAULT_WRAP_COLS.into());
 
-    for (index, (path, change)) in changes.iter().enumerate() {
+    for (index, change) in changes.values().enumerate() {
         let is_first_file = index == 0;
         // Add separator only between files (not at the very start)
         if !is_first_file {
@@ -195,9 +192,8 @@ fn render_patch_details(changes: &HashMap<PathBuf, FileChange>) -> Vec<RtLine<'s
                     ));
                 }
             }
-            FileChange::Delete => {
-                let original = std::fs::read_to_string(path).unwrap_or_default();
-                for (i, raw) in original.lines().enumerate() {
+            FileChange::Delete { content } => {
+                for (i, raw) in content.lines().enumerate() {
                     let ln = i + 1;
                     out.extend(push_wrapped_diff_line(
                         ln,
@@ -17,8 +17,15 @@
 //!    recent query.
 //! 4. If there is a in-flight search that is not a prefix of the latest thing
 //!    the user typed, it is cancelled.
+//!
+//! Searches run over a [`FileIndex`] of the search directory, which is built
+//! in the background when the manager is created and rebuilt when a search
+//! finds it older than [`FILE_INDEX_MAX_AGE`]. Until the first index is
+//! ready, or when the directory has more than [`MAX_INDEXED_FILES`] files,
+//! each search walks the directory instead.
 
 use codex_file_search as file_search;
+use codex_file_search::FileIndex;
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -27,6 +34,7 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 
 use crate::app_event::AppEvent;
 use crate::app_event_sender::AppEventSender;
@@ -40,11 +48,20 @@ const FILE_SEARCH_DEBOUNCE: Duration = Duration::from_millis(100);
 
 const ACTIVE_SEARCH_COMPLETE_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
+/// Most files kept in the index. Larger trees are searched by walking them.
+const MAX_INDEXED_FILES: usize = 100_000;
+
+/// Age after which a search rebuilds the index, so that files created since
+/// show up. The stale index keeps serving searches in the meantime.
+const FILE_INDEX_MAX_AGE: Duration = Duration::from_secs(30);
+
 /// State machine for file-search orchestration.
 pub(crate) struct FileSearchManager {
     /// Unified state guarded by one mutex.
     state: Arc<Mutex<SearchState>>,
 
+    index: Arc<Mutex<IndexState>>,
+
     search_dir: PathBuf,
     app_tx: AppEventSender,
 }
@@ -65,17 +82,67 @@ struct ActiveSearch {
     cancellation_token: Arc<AtomicBool>,
 }
 
+#[derive(Default)]
+struct IndexState {
+    /// The latest index that was built, and when.
+    index: Option<(Arc<FileIndex>, Instant)>,
+
+    /// true while an index is being built.
+    is_building: bool,
+}
+
 impl FileSearchManager {
     pub fn new(search_dir: PathBuf, tx: AppEventSender) -> Self {
-        Self {
+        let manager = Self {
             state: Arc::new(Mutex::new(SearchState {
                 latest_query: String::new(),
                 is_search_scheduled: false,
                 active_search: None,
             })),
+            index: Arc::new(Mutex::new(IndexState::default())),
             search_dir,
             app_tx: tx,
+        };
+        manager.refresh_index();
+        manager
+    }
+
+    /// Rebuilds the file index in the background, unless that is already
+    /// under way.
+    fn refresh_index(&self) {
+        {
+            #[expect(clippy::unwrap_used)]
+            let mut st = self.index.lock().unwrap();
+            if st.is_building {
+                return;
+            }
+            st.is_building = true;
+        }
+
+        let index_state = self.index.clone();
+        let search_dir = self.search_dir.clone();
+        thread::spawn(move || {
+            let index = FileIndex::build(&search_dir, MAX_INDEXED_FILES, &AtomicBool::new(false));
+            #[expect(clippy::unwrap_used)]
+            let mut st = index_state.lock().unwrap();
+            st.index = Some((Arc::new(index), Instant::now()));
+            st.is_building = false;
+        });
+    }
+
+    /// The index to search, if one is ready and complete. Starts a rebuild
+    /// when it is stale.
+    fn current_index(&self) -> Option<Arc<FileIndex>> {
+        let (index, is_stale) = {
+            #[expect(clippy::unwrap_used)]
+            let st = self.index.lock().unwrap();
+            let (index, built_at) = st.index.as_ref()?;
+            (index.clone(), built_at.elapsed() > FILE_INDEX_MAX_AGE)
+        };
+        if is_stale {
+            self.refresh_index();
         }
+        (!index.is_truncated()).then_some(index)
     }
 
     /// Call whenever the user edits the `@` token.
@@ -116,6 +183,7 @@ impl FileSearchManager {
         // debounce timer.
         let state = self.state.clone();
         let search_dir = self.search_dir.clone();
+        let index = self.current_index();
         let tx_clone = self.app_tx.clone();
         thread::spawn(move || {
             // Always do a minimum debounce, but then poll until the
@@ -148,6 +216,7 @@ impl FileSearchManager {
             FileSearchManager::spawn_file_search(
                 query,
                 search_dir,
+                index,
                 tx_clone,
                 cancellation_token,
                 state,
@@ -158,23 +227,31 @@ impl FileSearchManager {
     fn spawn_file_search(
         query: String,
         search_dir: PathBuf,
+        index: Option<Arc<FileIndex>>,
         tx: AppEventSender,
         cancellation_token: Arc<AtomicBool>,
         search_state: Arc<Mutex<SearchState>>,
     ) {
         let compute_indices = true;
         std::thread::spawn(move || {
-            let matches = file_search::run(
-                &query,
-                MAX_FILE_SEARCH_RESULTS,
-                &search_dir,
-                Vec::new(),
-                NUM_FILE_SEARCH_THREADS,
-                cancellation_token.clone(),
-                compute_indices,
-            )
-            .map(|res| res.matches)
-            .unwrap_or_default();
+            let matches = match index {
+                Some(index) => {
+                    index
+                        .search(&query, MAX_FILE_SEARCH_RESULTS, compute_indices)
+                        .matches
+                }
+                None => file_search::run(
+                    &query,
+                    MAX_FILE_SEARCH_RESULTS,
+                    &search_dir,
+                    Vec::new(),
+                    NUM_FILE_SEARCH_THREADS,
+                    cancellation_token.clone(),
+                    compute_indices,
+                )
+                .map(|res| res.matches)
+                .unwrap_or_default(),
+            };
 
             let is_cancelled = cancellation_token.load(Ordering::Relaxed);
             if !is_cancelled {
@@ -1,3 +1,4 @@
+use crate::bottom_pane::format_token_count;
 use crate::diff_render::create_diff_summary;
 use crate::exec_command::relativize_to_home;
 use crate::exec_command::strip_bash_lc_and_escape;
@@ -5,19 +6,40 @@ use crate::markdown::append_markdown;
 use crate::slash_command::SlashCommand;
 use crate::text_formatting::format_and_truncate_tool_result;
 use base64::Engine;
+use chrono::DateTime;
+use chrono::Local;
 use codex_ansi_escape::ansi_escape_line;
-use codex_common::create_config_summary_entries;
 use codex_common::elapsed::format_duration;
 use codex_core::config::Config;
+use codex_core::config_types::McpServerTransport;
+use codex_core::model_pricing::format_usd;
 use codex_core::plan_tool::PlanItemArg;
 use codex_core::plan_tool::StepStatus;
 use codex_core::plan_tool::UpdatePlanArgs;
-use codex_core::project_doc::discover_project_doc_paths;
+use codex_core::protocol::AuthExpiredEvent;
+use codex_core::protocol::ContextWindowWarningEvent;
+use codex_core::protocol::ConversationCompactedEvent;
+use codex_core::protocol::ExecTermination;
 use codex_core::protocol::FileChange;
+use codex_core::protocol::GitRepoWarningEvent;
+use codex_core::protocol::GitRepoWarningKind;
 use codex_core::protocol::McpInvocation;
+use codex_core::protocol::McpListToolsResponseEvent;
+use codex_core::protocol::McpStartupWarningEvent;
+use codex_core::protocol::McpToolCallProgressEvent;
+use codex_core::protocol::PatchMatchStrategy;
+use codex_core::protocol::PatchUndoneEvent;
+use codex_core::protocol::ProjectDocTruncatedEvent;
+use codex_core::protocol::PullRequestEvent;
+use codex_core::protocol::RateLimitSnapshot;
 use codex_core::protocol::SandboxPolicy;
 use codex_core::protocol::SessionConfiguredEvent;
+use codex_core::protocol::SessionStatus;
 use codex_core::protocol::TokenUsage;
+use codex_core::protocol::ToolOutputBudgetExceededEvent;
+use codex_core::protocol::TurnMetrics;
+use codex_core::protocol_config_types::ReasoningEffort as ReasoningEffortConfig;
+use codex_login::AuthMode;
 use codex_login::get_auth_file;
 use codex_login::try_read_auth_json;
 use codex_protocol::parse_command::ParsedCommand;
@@ -35,6 +57,7 @@ use ratatui::widgets::WidgetRef;
 use ratatui::widgets::Wrap;
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
 use std::time::Instant;
@@ -47,6 +70,8 @@ pub(crate) struct CommandOutput {
     pub(crate) stdout: String,
     pub(crate) stderr: String,
     pub(crate) formatted_output: String,
+    /// Set when Codex killed the command, e.g. because it timed out.
+    pub(crate) terminated: Option<ExecTermination>,
 }
 
 pub(crate) enum PatchEventType {
@@ -84,18 +109,26 @@ impl HistoryCell for PlainHistoryCell {
     }
 }
 
+/// A reasoning summary, one block per summary part. Collapsed, only the
+/// heading of each part is shown; the transcript always has the full text.
 #[derive(Debug)]
-pub(crate) struct TranscriptOnlyHistoryCell {
-    lines: Vec<Line<'static>>,
+pub(crate) struct ReasoningSummaryCell {
+    collapsed_lines: Vec<Line<'static>>,
+    expanded_lines: Vec<Line<'static>>,
+    expanded: bool,
 }
 
-impl HistoryCell for TranscriptOnlyHistoryCell {
+impl HistoryCell for ReasoningSummaryCell {
     fn display_lines(&self) -> Vec<Line<'static>> {
-        Vec::new()
+        if self.expanded {
+            self.expanded_lines.clone()
+        } else {
+            self.collapsed_lines.clone()
+        }
     }
 
     fn transcript_lines(&self) -> Vec<Line<'static>> {
-        self.lines.clone()
+        self.expanded_lines.clone()
     }
 }
 
@@ -190,6 +223,7 @@ impl ExecCell {
             stdout: String::new(),
             stderr: String::new(),
             formatted_output: String::new(),
+            terminated: None,
         });
         self
     }
@@ -252,6 +286,8 @@ pub(crate) fn new_session_info(
         session_id: _,
         history_log_id: _,
         history_entry_count: _,
+        project_docs,
+        rollout_path: _,
     } = event;
     if is_first_event {
         let cwd_str = match relativize_to_home(&config.cwd) {
@@ -263,7 +299,7 @@ pub(crate) fn new_session_info(
             None => config.cwd.display().to_string(),
         };
 
-        let lines: Vec<Line<'static>> = vec![
+        let mut lines: Vec<Line<'static>> = vec![
             Line::from(Span::from("")),
             Line::from(vec![
                 Span::raw(">_ ").dim(),
@@ -273,6 +309,20 @@ pub(crate) fn new_session_info(
                 ),
                 Span::raw(format!(" {cwd_str}")).dim(),
             ]),
+        ];
+        if !project_docs.is_empty() {
+            let docs: Vec<String> = project_docs
+                .iter()
+                .map(|path| match relativize_to_home(path) {
+                    Some(rel) => format!("~{}{}", std::path::MAIN_SEPARATOR, rel.display()),
+                    None => path.display().to_string(),
+                })
+                .collect();
+            lines.push(Line::from(
+                format!("   instructions from {}", docs.join(", ")).dim(),
+            ));
+        }
+        lines.extend([
             Line::from("".dim()),
             Line::from(" To get started, describe a task or try one of these commands:".dim()),
             Line::from("".dim()),
@@ -324,7 +374,7 @@ pub(crate) fn new_session_info(
                     Style::default().dim(),
                 ),
             ]),
-        ];
+        ]);
         PlainHistoryCell { lines }
     } else if config.model == model {
         PlainHistoryCell { lines: Vec::new() }
@@ -408,15 +458,7 @@ fn new_parsed_command(
 
     // Determine the leading status marker: spinner while running, ✓ on success, ✗ on failure.
     let status_marker: Span<'static> = match output {
-        None => {
-            // Animated braille spinner – choose frame based on elapsed time.
-            const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-            let idx = start_time
-                .map(|st| ((st.elapsed().as_millis() / 100) as usize) % FRAMES.len())
-                .unwrap_or(0);
-            let ch = FRAMES[idx];
-            Span::raw(format!("{ch}"))
-        }
+        None => spinner(start_time),
         Some(o) if o.exit_code == 0 => Span::styled("✓", Style::default().fg(Color::Green)),
         Some(_) => Span::styled("✗", Style::default().fg(Color::Red)),
     };
@@ -479,14 +521,7 @@ fn new_exec_command_generic(
 
     // Determine marker: spinner while running, ✓/✗ when completed
     let status_marker: Span<'static> = match output {
-        None => {
-            const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-            let idx = start_time
-                .map(|st| ((st.elapsed().as_millis() / 100) as usize) % FRAMES.len())
-                .unwrap_or(0);
-            let ch = FRAMES[idx];
-            Span::raw(format!("{ch}"))
-        }
+        None => spinner(start_time),
         Some(o) if o.exit_code == 0 => Span::styled("✓", Style::default().fg(Color::Green)),
         Some(_) => Span::styled("✗", Style::default().fg(Color::Red)),
     };
@@ -512,15 +547,63 @@ fn new_exec_command_generic(
     lines
 }
 
-pub(crate) fn new_active_mcp_tool_call(invocation: McpInvocation) -> PlainHistoryCell {
-    let title_line = Line::from(vec!["tool".magenta(), " running...".dim()]);
-    let lines: Vec<Line> = vec![
-        Line::from(""),
-        title_line,
-        format_mcp_invocation(invocation.clone()),
-    ];
+/// Animated braille spinner – choose frame based on elapsed time.
+fn spinner(start_time: Option<Instant>) -> Span<'static> {
+    const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    let idx = start_time
+        .map(|st| ((st.elapsed().as_millis() / 100) as usize) % FRAMES.len())
+        .unwrap_or(0);
+    let ch = FRAMES[idx];
+    Span::raw(format!("{ch}"))
+}
 
-    PlainHistoryCell { lines }
+/// An MCP tool call that has not returned yet. It is shown as a spinner row
+/// above the composer, with the latest progress the server reported, until
+/// the call ends and is added to the history.
+pub(crate) struct ActiveMcpToolCall {
+    pub(crate) call_id: String,
+    invocation: McpInvocation,
+    start_time: Instant,
+    progress: Option<McpToolCallProgressEvent>,
+}
+
+impl ActiveMcpToolCall {
+    pub(crate) fn new(call_id: String, invocation: McpInvocation) -> Self {
+        Self {
+            call_id,
+            invocation,
+            start_time: Instant::now(),
+            progress: None,
+        }
+    }
+
+    pub(crate) fn set_progress(&mut self, progress: McpToolCallProgressEvent) {
+        self.progress = Some(progress);
+    }
+
+    pub(crate) fn display_line(&self) -> Line<'static> {
+        let mut spans = vec![
+            Span::raw("  "),
+            spinner(Some(self.start_time)),
+            Span::raw(" "),
+            "tool".magenta(),
+            Span::raw(" "),
+        ];
+        spans.extend(format_mcp_invocation(self.invocation.clone()).spans);
+        spans.push(format!(" · {}", format_duration(self.start_time.elapsed())).dim());
+        if let Some(progress) = &self.progress {
+            let mut status = match progress.total {
+                Some(total) => format!(" · {}/{total}", progress.progress),
+                None => format!(" · {}", progress.progress),
+            };
+            if let Some(message) = &progress.message {
+                status.push(' ');
+                status.push_str(message);
+            }
+            spans.push(status.dim());
+        }
+        Line::from(spans)
+    }
 }
 
 pub(crate) fn new_web_search_call(query: String) -> PlainHistoryCell {
@@ -654,44 +737,28 @@ pub(crate) fn new_completed_mcp_tool_call(
 
 pub(crate) fn new_status_output(
     config: &Config,
-    usage: &TokenUsage,
-    session_id: &Option<Uuid>,
+    status: &SessionStatus,
+    rate_limits: Option<&RateLimitSnapshot>,
+    turn_metrics: Option<&TurnMetrics>,
 ) -> PlainHistoryCell {
     let mut lines: Vec<Line<'static>> = Vec::new();
     lines.push(Line::from(""));
     lines.push(Line::from("/status".magenta()));
 
-    let config_entries = create_config_summary_entries(config);
-    let lookup = |k: &str| -> String {
-        config_entries
-            .iter()
-            .find(|(key, _)| *key == k)
-            .map(|(_, v)| v.clone())
-            .unwrap_or_default()
-    };
-
     // 📂 Workspace
     lines.push(Line::from(vec![
         padded_emoji("📂").into(),
         "Workspace".bold(),
     ]));
-    // Path (home-relative, e.g., ~/code/project)
-    let cwd_str = match relativize_to_home(&config.cwd) {
-        Some(rel) if !rel.as_os_str().is_empty() => {
-            let sep = std::path::MAIN_SEPARATOR;
-            format!("~{sep}{}", rel.display())
-        }
-        Some(_) => "~".to_string(),
-        None => config.cwd.display().to_string(),
-    };
-    lines.push(Line::from(vec!["  • Path: ".into(), cwd_str.into()]));
-    // Approval mode (as-is)
+    lines.push(Line::from(vec![
+        "  • Path: ".into(),
+        display_home_relative(&status.cwd).into(),
+    ]));
     lines.push(Line::from(vec![
         "  • Approval Mode: ".into(),
-        lookup("approval").into(),
+        status.approval_policy.to_string().into(),
     ]));
-    // Sandbox (simplified name only)
-    let sandbox_name = match &config.sandbox_policy {
+    let sandbox_name = match &status.sandbox_policy {
         SandboxPolicy::DangerFullAccess => "danger-full-access",
         SandboxPolicy::ReadOnly => "read-only",
         SandboxPolicy::WorkspaceWrite { .. } => "workspace-write",
@@ -700,47 +767,59 @@ pub(crate) fn new_status_output(
         "  • Sandbox: ".into(),
         sandbox_name.into(),
     ]));
+    if !status.writable_roots.is_empty() {
+        let roots: Vec<String> = status
+            .writable_roots
+            .iter()
+            .map(|root| display_home_relative(root))
+            .collect();
+        lines.push(Line::from(vec![
+            "  • Writable Roots: ".into(),
+            roots.join(", ").into(),
+        ]));
+    }
+    lines.push(Line::from(vec![
+        "  • Network Access: ".into(),
+        if status.network_access {
+            "enabled"
+        } else {
+            "disabled"
+        }
+        .into(),
+    ]));
 
-    // AGENTS.md files discovered via core's project_doc logic
-    let agents_list = {
-        match discover_project_doc_paths(config) {
-            Ok(paths) => {
-                let mut rels: Vec<String> = Vec::new();
-                for p in paths {
-                    let display = if let Some(parent) = p.parent() {
-                        if parent == config.cwd {
-                            "AGENTS.md".to_string()
-                        } else {
-                            let mut cur = config.cwd.as_path();
-                            let mut ups = 0usize;
-                            let mut reached = false;
-                            while let Some(c) = cur.parent() {
-                                if cur == parent {
-                                    reached = true;
-                                    break;
-                                }
-                                cur = c;
-                                ups += 1;
-                            }
-                            if reached {
-                                let up = format!("..{}", std::path::MAIN_SEPARATOR);
-                                format!("{}AGENTS.md", up.repeat(ups))
-                            } else if let Ok(stripped) = p.strip_prefix(&config.cwd) {
-                                stripped.display().to_string()
-                            } else {
-                                p.display().to_string()
-                            }
-                        }
-                    } else {
-                        p.display().to_string()
-                    };
-                    rels.push(display);
+    // AGENTS.md files merged into the instructions, relative to the cwd.
+    let agents_list: Vec<String> = status
+        .instruction_files
+        .iter()
+        .map(|p| {
+            let Some(parent) = p.parent() else {
+                return p.display().to_string();
+            };
+            if parent == status.cwd {
+                return "AGENTS.md".to_string();
+            }
+            let mut cur = status.cwd.as_path();
+            let mut ups = 0usize;
+            let mut reached = false;
+            while let Some(c) = cur.parent() {
+                if cur == parent {
+                    reached = true;
+                    break;
                 }
-                rels
+                cur = c;
+                ups += 1;
             }
-            Err(_) => Vec::new(),
-        }
-    };
+            if reached {
+                let up = format!("..{}", std::path::MAIN_SEPARATOR);
+                format!("{}AGENTS.md", up.repeat(ups))
+            } else if let Ok(stripped) = p.strip_prefix(&status.cwd) {
+                stripped.display().to_string()
+            } else {
+                p.display().to_string()
+            }
+        })
+        .collect();
     if agents_list.is_empty() {
         lines.push(Line::from("  • AGENTS files: (none)"));
     } else {
@@ -751,7 +830,7 @@ pub(crate) fn new_status_output(
     }
 
     // 👤 Account (only if ChatGPT tokens exist), shown under the first block
-    let auth_file = get_auth_file(&config.codex_home);
+    let auth_file = get_auth_file(config.dirs.config_dir());
     if let Ok(auth) = try_read_auth_json(&auth_file)
         && let Some(tokens) = auth.tokens.clone()
     {
@@ -766,8 +845,8 @@ pub(crate) fn new_status_output(
             lines.push(Line::from(vec!["  • Login: ".into(), email.clone().into()]));
         }
 
-        match auth.openai_api_key.as_deref() {
-            Some(key) if !key.is_empty() => {
+        match status.auth_mode {
+            Some(AuthMode::ApiKey) => {
                 lines.push(Line::from(
                     "  • Using API key. Run codex login to use ChatGPT plan",
                 ));
@@ -785,40 +864,91 @@ pub(crate) fn new_status_output(
     }
 
     // 🧠 Model
+    let model = &status.model;
     lines.push(Line::from(vec![padded_emoji("🧠").into(), "Model".bold()]));
     lines.push(Line::from(vec![
         "  • Name: ".into(),
-        config.model.clone().into(),
+        model.slug.clone().into(),
     ]));
-    let provider_disp = pretty_provider_name(&config.model_provider_id);
+    if model.family != model.slug {
+        lines.push(Line::from(vec![
+            "  • Family: ".into(),
+            model.family.clone().into(),
+        ]));
+    }
     lines.push(Line::from(vec![
         "  • Provider: ".into(),
-        provider_disp.into(),
+        pretty_provider_name(&status.provider.name).into(),
+        format!(" ({} API)", status.provider.wire_api).dim(),
     ]));
-    // Only show Reasoning fields if present in config summary
-    let reff = lookup("reasoning effort");
-    if !reff.is_empty() {
+    if let Some(context_window) = model.context_window {
         lines.push(Line::from(vec![
-            "  • Reasoning Effort: ".into(),
-            title_case(&reff).into(),
+            "  • Context Window: ".into(),
+            format_token_count(context_window).into(),
         ]));
     }
-    let rsum = lookup("reasoning summaries");
-    if !rsum.is_empty() {
+    if model.supports_reasoning_summaries && status.provider.wire_api == "responses" {
+        lines.push(Line::from(vec![
+            "  • Reasoning Effort: ".into(),
+            title_case(&status.reasoning_effort.to_string()).into(),
+        ]));
         lines.push(Line::from(vec![
             "  • Reasoning Summaries: ".into(),
-            title_case(&rsum).into(),
+            title_case(&status.reasoning_summary.to_string()).into(),
+        ]));
+    }
+    let mut capabilities = Vec::new();
+    if model.supports_image_input {
+        capabilities.push("images");
+    }
+    if model.uses_local_shell_tool {
+        capabilities.push("local shell");
+    }
+    if !capabilities.is_empty() {
+        lines.push(Line::from(vec![
+            "  • Supports: ".into(),
+            capabilities.join(", ").into(),
         ]));
     }
 
+    // 🔌 MCP Servers
+    if !status.mcp_servers.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            padded_emoji("🔌").into(),
+            "MCP Servers".bold(),
+        ]));
+        for server in &status.mcp_servers {
+            let state: Span<'static> = if server.running {
+                let noun = if server.tool_count == 1 {
+                    "tool"
+                } else {
+                    "tools"
+                };
+                format!("{} {noun}", server.tool_count).into()
+            } else {
+                "not running".dim()
+            };
+            lines.push(Line::from(vec![
+                format!("  • {}: ", server.name).into(),
+                state,
+            ]));
+        }
+    }
+
     lines.push(Line::from(""));
 
     // 📊 Token Usage
+    let usage = &status.token_usage;
     lines.push(Line::from(vec!["📊 ".into(), "Token Usage".bold()]));
-    if let Some(session_id) = session_id {
+    lines.push(Line::from(vec![
+        "  • Session ID: ".into(),
+        status.session_id.to_string().into(),
+    ]));
+    if let Some(rollout_path) = &status.rollout_path {
         lines.push(Line::from(vec![
-            "  • Session ID: ".into(),
-            session_id.to_string().into(),
+            "  • Transcript: ".into(),
+            display_home_relative(rollout_path).dim(),
         ]));
     }
     // Input: <input> [+ <cached> cached]
@@ -842,10 +972,110 @@ pub(crate) fn new_status_output(
         "  • Total: ".into(),
         usage.blended_total().to_string().into(),
     ]));
+    if let Some(cost) = status.total_cost_usd {
+        lines.push(Line::from(vec![
+            "  • Estimated Cost: ".into(),
+            format_usd(cost).into(),
+        ]));
+    }
+    if let Some(context) = &status.context_usage {
+        lines.push(Line::from(vec![
+            "  • Context Left: ".into(),
+            format!("{}%", context.percent_left).into(),
+            format!(
+                " ({} / {} tokens)",
+                format_token_count(context.tokens_in_context),
+                format_token_count(context.context_window)
+            )
+            .dim(),
+        ]));
+        let breakdown = &context.breakdown;
+        for (label, tokens) in [
+            ("Instructions", breakdown.instructions_tokens),
+            ("Tool Schemas", breakdown.tool_schema_tokens),
+            ("Environment", breakdown.environment_tokens),
+            ("Conversation", breakdown.conversation_tokens),
+        ] {
+            lines.push(Line::from(vec![
+                format!("    ◦ {label}: ").into(),
+                format!("~{}", format_token_count(tokens)).into(),
+            ]));
+        }
+    }
+
+    // ⏱ Latency of the latest model response
+    if let Some(metrics) = turn_metrics {
+        let millis = |ms: Option<u64>| {
+            ms.map_or_else(
+                || "n/a".to_string(),
+                |ms| format_duration(Duration::from_millis(ms)),
+            )
+        };
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            padded_emoji("⏱").into(),
+            "Last Turn".bold(),
+        ]));
+        lines.push(Line::from(vec![
+            "  • First Byte: ".into(),
+            millis(metrics.time_to_first_byte_ms).into(),
+        ]));
+        lines.push(Line::from(vec![
+            "  • First Token: ".into(),
+            millis(metrics.time_to_first_token_ms).into(),
+        ]));
+        lines.push(Line::from(vec![
+            "  • Duration: ".into(),
+            millis(Some(metrics.stream_duration_ms)).into(),
+        ]));
+        lines.push(Line::from(vec![
+            "  • Retries: ".into(),
+            metrics.retries.to_string().into(),
+        ]));
+    }
+
+    // ⏳ Rate Limits, as reported with the latest model response
+    if let Some(rate_limits) = rate_limits {
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            padded_emoji("⏳").into(),
+            "Rate Limits".bold(),
+        ]));
+        for (name, window) in rate_limits.windows() {
+            let mut spans: Vec<Span<'static>> = vec![
+                format!("  • {}: ", title_case(name)).into(),
+                format!("{:.0}% used", window.used_percent).into(),
+            ];
+            if let (Some(limit), Some(remaining)) = (window.limit, window.remaining) {
+                spans.push(format!(" ({remaining} of {limit} left)").dim());
+            }
+            if let Some(resets_at) = window
+                .window_resets_at
+                .and_then(|secs| DateTime::from_timestamp(i64::try_from(secs).ok()?, 0))
+            {
+                let resets_at = resets_at.with_timezone(&Local).format("%b %-d %H:%M");
+                spans.push(format!(", resets {resets_at}").dim());
+            }
+            lines.push(Line::from(spans));
+        }
+    }
 
     PlainHistoryCell { lines }
 }
 
+/// `path` relative to the home directory, e.g. `~/code/project`, when it is
+/// inside it.
+fn display_home_relative(path: &Path) -> String {
+    match relativize_to_home(path) {
+        Some(rel) if !rel.as_os_str().is_empty() => {
+            let sep = std::path::MAIN_SEPARATOR;
+            format!("~{sep}{}", rel.display())
+        }
+        Some(_) => "~".to_string(),
+        None => path.display().to_string(),
+    }
+}
+
 /// Render a summary of configured MCP servers from the current `Config`.
 pub(crate) fn empty_mcp_output() -> PlainHistoryCell {
     let lines: Vec<Line<'static>> = vec![
@@ -872,8 +1102,9 @@ pub(crate) fn empty_mcp_output() -> PlainHistoryCell {
 /// Render MCP tools grouped by connection using the fully-qualified tool names.
 pub(crate) fn new_mcp_tools_output(
     config: &Config,
-    tools: std::collections::HashMap<String, mcp_types::Tool>,
+    event: McpListToolsResponseEvent,
 ) -> PlainHistoryCell {
+    let McpListToolsResponseEvent { tools, servers } = event;
     let mut lines: Vec<Line<'static>> = vec![
         Line::from("/mcp".magenta()),
         Line::from(""),
@@ -888,37 +1119,58 @@ pub(crate) fn new_mcp_tools_output(
     }
 
     for (server, cfg) in config.mcp_servers.iter() {
-        let prefix = format!("{server}__");
-        let mut names: Vec<String> = tools
-            .keys()
-            .filter(|k| k.starts_with(&prefix))
-            .map(|k| k[prefix.len()..].to_string())
-            .collect();
-        names.sort();
+        let default_prefix = format!("{server}__");
+        let server_tools = servers.get(server);
+        // Tools keep their own name unless renamed away from the default.
+        let names: Vec<String> = server_tools
+            .map(|server_tools| {
+                server_tools
+                    .tools
+                    .iter()
+                    .map(|(name, original)| {
+                        if name.strip_prefix(&default_prefix) == Some(original.as_str())
+                            || name == original
+                        {
+                            original.clone()
+                        } else {
+                            format!("{name} ({original})")
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
         lines.push(Line::from(vec![
             "  • Server: ".into(),
             server.clone().into(),
         ]));
 
-        if !cfg.command.is_empty() {
-            let cmd_display = format!("{} {}", cfg.command, cfg.args.join(" "));
+        match &cfg.transport {
+            McpServerTransport::Stdio { command, args, env } => {
+                if !command.is_empty() {
+                    let cmd_display = format!("{} {}", command, args.join(" "));
 
-            lines.push(Line::from(vec![
-                "    • Command: ".into(),
-                cmd_display.into(),
-            ]));
-        }
+                    lines.push(Line::from(vec![
+                        "    • Command: ".into(),
+                        cmd_display.into(),
+                    ]));
+                }
 
-        if let Some(env) = cfg.env.as_ref()
-            && !env.is_empty()
-        {
-            let mut env_pairs: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
-            env_pairs.sort();
-            lines.push(Line::from(vec![
-                "    • Env: ".into(),
-                env_pairs.join(" ").into(),
-            ]));
+                if let Some(env) = env.as_ref()
+                    && !env.is_empty()
+                {
+                    let mut env_pairs: Vec<String> =
+                        env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                    env_pairs.sort();
+                    lines.push(Line::from(vec![
+                        "    • Env: ".into(),
+                        env_pairs.join(" ").into(),
+                    ]));
+                }
+            }
+            McpServerTransport::StreamableHttp { url, .. } => {
+                lines.push(Line::from(vec!["    • URL: ".into(), url.clone().into()]));
+            }
         }
 
         if names.is_empty() {
@@ -929,8 +1181,97 @@ pub(crate) fn new_mcp_tools_output(
                 names.join(", ").into(),
             ]));
         }
+        if let Some(server_tools) = server_tools
+            && !server_tools.hidden.is_empty()
+        {
+            lines.push(Line::from(vec![
+                "    • Hidden: ".into(),
+                server_tools.hidden.join(", ").dim(),
+            ]));
+        }
+        lines.push(Line::from(""));
+    }
+
+    PlainHistoryCell { lines }
+}
+
+/// Render the resources offered by each MCP server.
+pub(crate) fn new_mcp_resources_output(
+    resources: std::collections::HashMap<String, Vec<mcp_types::Resource>>,
+) -> PlainHistoryCell {
+    let mut lines: Vec<Line<'static>> = vec![
+        Line::from("/mcp resources".magenta()),
+        Line::from(""),
+        Line::from(vec!["🔌  ".into(), "MCP Resources".bold()]),
+        Line::from(""),
+    ];
+
+    let mut servers: Vec<_> = resources.into_iter().collect();
+    servers.sort_by(|(a, _), (b, _)| a.cmp(b));
+    servers.retain(|(_, resources)| !resources.is_empty());
+    if servers.is_empty() {
+        lines.push(Line::from("  • No MCP resources available.".italic()));
+        lines.push(Line::from(""));
+        return PlainHistoryCell { lines };
+    }
+
+    for (server, mut resources) in servers {
+        resources.sort_by(|a, b| a.uri.cmp(&b.uri));
+        lines.push(Line::from(vec!["  • Server: ".into(), server.into()]));
+        for resource in resources {
+            let mut spans: Vec<Span<'static>> = vec!["    • ".into(), resource.uri.into()];
+            let name = resource.title.unwrap_or(resource.name);
+            spans.push(format!(" ({name})").dim());
+            if let Some(mime_type) = resource.mime_type {
+                spans.push(format!(" {mime_type}").dim());
+            }
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(""));
+    }
+
+    PlainHistoryCell { lines }
+}
+
+/// Render MCP prompts with their arguments, using the fully-qualified prompt
+/// names accepted by `/prompt`.
+pub(crate) fn new_mcp_prompts_output(
+    prompts: std::collections::HashMap<String, mcp_types::Prompt>,
+) -> PlainHistoryCell {
+    let mut lines: Vec<Line<'static>> = vec![
+        Line::from("/prompt".magenta()),
+        Line::from(""),
+        Line::from(vec!["🔌  ".into(), "MCP Prompts".bold()]),
+        Line::from(""),
+    ];
+
+    if prompts.is_empty() {
+        lines.push(Line::from("  • No MCP prompts available.".italic()));
         lines.push(Line::from(""));
+        return PlainHistoryCell { lines };
+    }
+
+    let mut prompts: Vec<_> = prompts.into_iter().collect();
+    prompts.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, prompt) in prompts {
+        let mut spans: Vec<Span<'static>> = vec!["  • ".into(), name.into()];
+        for argument in prompt.arguments.unwrap_or_default() {
+            if argument.required == Some(true) {
+                spans.push(format!(" {}=…", argument.name).into());
+            } else {
+                spans.push(format!(" [{}=…]", argument.name).dim());
+            }
+        }
+        lines.push(Line::from(spans));
+        if let Some(description) = prompt.description {
+            lines.push(Line::from(format!("    {description}").dim()));
+        }
     }
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "  Run one with /prompt <name> [arg=value ...]".dim(),
+    ));
+    lines.push(Line::from(""));
 
     PlainHistoryCell { lines }
 }
@@ -946,6 +1287,177 @@ pub(crate) fn new_error_event(message: String) -> PlainHistoryCell {
     PlainHistoryCell { lines }
 }
 
+pub(crate) fn new_conversation_compacted(ev: ConversationCompactedEvent) -> PlainHistoryCell {
+    let ConversationCompactedEvent {
+        tokens_before,
+        tokens_after,
+        automatic,
+        ..
+    } = ev;
+    let saved = format_token_count(tokens_before.saturating_sub(tokens_after));
+    let message = if automatic {
+        format!("compacted {saved} tokens to stay within the context window")
+    } else {
+        format!("compacted {saved} tokens")
+    };
+    let lines: Vec<Line<'static>> = vec!["".into(), vec!["• ".dim(), message.dim()].into()];
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_next_turn_effort(effort: ReasoningEffortConfig) -> PlainHistoryCell {
+    let message = format!("your next message uses {effort} reasoning effort");
+    let lines: Vec<Line<'static>> = vec!["".into(), vec!["• ".dim(), message.dim()].into()];
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_context_window_warning(ev: ContextWindowWarningEvent) -> PlainHistoryCell {
+    let ContextWindowWarningEvent {
+        threshold_percent,
+        percent_left,
+    } = ev;
+    let lines: Vec<Line<'static>> = vec![
+        vec![
+            padded_emoji("⚠").magenta().bold(),
+            " ".into(),
+            format!("The context window is over {threshold_percent}% full ({percent_left}% left); use /compact or start a /new session to free it up").dim(),
+        ]
+        .into(),
+        "".into(),
+    ];
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_tool_output_budget_exceeded(
+    ev: ToolOutputBudgetExceededEvent,
+) -> PlainHistoryCell {
+    let ToolOutputBudgetExceededEvent { budget_bytes } = ev;
+    let lines: Vec<Line<'static>> = vec![
+        vec![
+            padded_emoji("⚠").magenta().bold(),
+            " ".into(),
+            format!("Tool output exceeded this turn's budget of {budget_bytes} bytes; further output is truncated").dim(),
+        ]
+        .into(),
+        "".into(),
+    ];
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_mcp_startup_warning(ev: McpStartupWarningEvent) -> PlainHistoryCell {
+    let mut lines: Vec<Line<'static>> = vec![
+        vec![
+            padded_emoji("⚠").magenta().bold(),
+            " ".into(),
+            "Some MCP servers failed to start; continuing without them".dim(),
+        ]
+        .into(),
+    ];
+    for (server_name, message) in ev.failures {
+        lines.push(Line::from(vec![
+            "  • ".dim(),
+            server_name.into(),
+            ": ".dim(),
+            message.dim(),
+        ]));
+    }
+    lines.push("".into());
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_auth_expired(ev: AuthExpiredEvent) -> PlainHistoryCell {
+    let lines: Vec<Line<'static>> = vec![
+        "".into(),
+        vec![padded_emoji("🖐").red().bold(), " ".into(), ev.message.into()].into(),
+        Line::from(
+            "  Run `codex login` in another terminal and the turn will resume; press Esc to cancel it"
+                .dim(),
+        ),
+    ];
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_git_repo_warning(ev: GitRepoWarningEvent) -> PlainHistoryCell {
+    let problem = match ev.kind {
+        GitRepoWarningKind::NotARepo => "is not a git repository",
+        GitRepoWarningKind::Dirty => "has uncommitted changes",
+    };
+    let mut lines: Vec<Line<'static>> = vec![
+        vec![
+            padded_emoji("⚠").magenta().bold(),
+            " ".into(),
+            format!("{} {problem}", ev.cwd.display()).into(),
+        ]
+        .into(),
+    ];
+    if ev.confirmation_required {
+        lines.push(Line::from(
+            "  Codex will ask before its first change to files here".dim(),
+        ));
+    }
+    lines.push("".into());
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_project_doc_truncated(ev: ProjectDocTruncatedEvent) -> PlainHistoryCell {
+    let mut lines: Vec<Line<'static>> = vec![
+        vec![
+            padded_emoji("⚠").magenta().bold(),
+            " ".into(),
+            format!(
+                "AGENTS.md files exceed project_doc_max_bytes ({} bytes); the model sees only part of them",
+                ev.limit_bytes
+            )
+            .dim(),
+        ]
+        .into(),
+    ];
+    for file in ev.files {
+        lines.push(Line::from(vec![
+            "  • ".dim(),
+            file.path.display().to_string().into(),
+            format!(
+                ": {} of {} bytes omitted",
+                file.omitted_bytes, file.size_bytes
+            )
+            .dim(),
+        ]));
+    }
+    lines.push("".into());
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_pull_request(ev: PullRequestEvent) -> PlainHistoryCell {
+    let noun = ev.provider.request_noun();
+    let target = match &ev.base {
+        Some(base) => format!("{} → {base}", ev.head),
+        None => ev.head.clone(),
+    };
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    match ev.url {
+        Some(url) => {
+            lines.push(Line::from(vec![
+                padded_emoji("🔀").into(),
+                " ".into(),
+                format!("Opened {noun} ").into(),
+                url.cyan().underlined(),
+            ]));
+            lines.push(Line::from(format!("  {} ({target})", ev.title).dim()));
+        }
+        None => {
+            lines.push(Line::from(vec![
+                padded_emoji("🔀").into(),
+                " ".into(),
+                format!("Dry run: would open {noun} \"{}\" ({target})", ev.title).into(),
+            ]));
+            for line in ev.dry_run_script.unwrap_or_default().lines() {
+                lines.push(Line::from(format!("  {line}").dim()));
+            }
+        }
+    }
+    lines.push("".into());
+    PlainHistoryCell { lines }
+}
+
 pub(crate) fn new_stream_error_event(message: String) -> PlainHistoryCell {
     let lines: Vec<Line<'static>> = vec![
         vec![
@@ -1102,6 +1614,7 @@ pub(crate) fn new_patch_apply_failure(stderr: String) -> PlainHistoryCell {
                 stdout: String::new(),
                 stderr,
                 formatted_output: String::new(),
+                terminated: None,
             }),
             true,
             true,
@@ -1113,7 +1626,31 @@ pub(crate) fn new_patch_apply_failure(stderr: String) -> PlainHistoryCell {
     PlainHistoryCell { lines }
 }
 
-pub(crate) fn new_patch_apply_success(stdout: String) -> PlainHistoryCell {
+pub(crate) fn new_patch_undone(ev: PatchUndoneEvent) -> PlainHistoryCell {
+    let PatchUndoneEvent {
+        restored, removed, ..
+    } = ev;
+    let mut lines: Vec<Line<'static>> =
+        vec![Line::from(""), Line::from("↶ Undid patch".magenta().bold())];
+    let files = restored
+        .into_iter()
+        .map(|path| ("restored ", path))
+        .chain(removed.into_iter().map(|path| ("removed ", path)));
+    for (i, (action, path)) in files.enumerate() {
+        let prefix = if i == 0 { "  └ " } else { "    " };
+        lines.push(Line::from(vec![
+            prefix.dim(),
+            action.dim(),
+            path.display().to_string().into(),
+        ]));
+    }
+    PlainHistoryCell { lines }
+}
+
+pub(crate) fn new_patch_apply_success(
+    stdout: String,
+    match_strategies: &HashMap<PathBuf, PatchMatchStrategy>,
+) -> PlainHistoryCell {
     let mut lines: Vec<Line<'static>> = Vec::new();
 
     // Success title
@@ -1156,20 +1693,83 @@ pub(crate) fn new_patch_apply_success(stdout: String) -> PlainHistoryCell {
             lines.push(Line::from(format!("... +{remaining} lines")).dim());
         }
     }
+
+    // Call out files whose context only matched approximately so the user
+    // can double-check where the change landed.
+    let mut fuzzy_paths: Vec<&PathBuf> = match_strategies
+        .iter()
+        .filter(|(_, strategy)| **strategy == PatchMatchStrategy::Fuzzy)
+        .map(|(path, _)| path)
+        .collect();
+    fuzzy_paths.sort();
+    for path in fuzzy_paths {
+        lines.push(Line::from(vec![
+            "  ⚠ ".cyan(),
+            format!("context matched fuzzily in {}", path.display()).dim(),
+        ]));
+    }
     // Leading blank separator
     lines.insert(0, Line::from(""));
     PlainHistoryCell { lines }
 }
 
 pub(crate) fn new_reasoning_block(
-    full_reasoning_buffer: String,
+    sections: Vec<String>,
+    expanded: bool,
     config: &Config,
-) -> TranscriptOnlyHistoryCell {
-    let mut lines: Vec<Line<'static>> = Vec::new();
-    lines.push(Line::from(""));
-    lines.push(Line::from("thinking".magenta().italic()));
-    append_markdown(&full_reasoning_buffer, &mut lines, config);
-    TranscriptOnlyHistoryCell { lines }
+) -> ReasoningSummaryCell {
+    let header = || vec![Line::from(""), Line::from("thinking".magenta().italic())];
+    let mut collapsed_lines = header();
+    let mut expanded_lines = header();
+    for (i, section) in sections.iter().enumerate() {
+        let (heading, body) = split_reasoning_heading(section);
+        collapsed_lines.push(Line::from(vec!["  ▸ ".dim(), heading.clone().dim()]));
+        if i > 0 {
+            expanded_lines.push(Line::from("  ───".dim()));
+        }
+        expanded_lines.push(Line::from(heading.bold()));
+        append_markdown(body, &mut expanded_lines, config);
+    }
+    ReasoningSummaryCell {
+        collapsed_lines,
+        expanded_lines,
+        expanded,
+    }
+}
+
+/// Splits a reasoning summary part into its heading and the rest. The
+/// heading is a leading `**bold**` span when the model wrote one, and the
+/// first sentence otherwise.
+fn split_reasoning_heading(section: &str) -> (String, &str) {
+    let section = section.trim();
+    if let Some(rest) = section.strip_prefix("**")
+        && let Some((heading, body)) = rest.split_once("**")
+        && !heading.trim().is_empty()
+    {
+        return (heading.trim().to_string(), body.trim_start());
+    }
+    let end = section
+        .char_indices()
+        .find_map(|(i, c)| match c {
+            '\n' => Some(i),
+            '.' | '!' | '?' if section[i + 1..].starts_with(char::is_whitespace) => Some(i + 1),
+            _ => None,
+        })
+        .unwrap_or(section.len());
+    (
+        section[..end].trim().to_string(),
+        section[end..].trim_start(),
+    )
+}
+
+pub(crate) fn new_reasoning_display_toggled(expanded: bool) -> PlainHistoryCell {
+    let message = if expanded {
+        "reasoning summaries now show their full text (ctrl+o to collapse)"
+    } else {
+        "reasoning summaries now show only their headings (ctrl+o to expand)"
+    };
+    let lines: Vec<Line<'static>> = vec!["".into(), vec!["• ".dim(), message.dim()].into()];
+    PlainHistoryCell { lines }
 }
 
 fn output_lines(
@@ -1181,6 +1781,7 @@ fn output_lines(
         exit_code,
         stdout,
         stderr,
+        terminated,
         ..
     } = match output {
         Some(output) if only_err && output.exit_code == 0 => return vec![],
@@ -1231,18 +1832,31 @@ fn output_lines(
         out.push(line);
     }
 
+    if let Some(terminated) = terminated {
+        let prefix = if out.is_empty() && include_angle_pipe {
+            "  └ "
+        } else {
+            "    "
+        };
+        out.push(Line::from(vec![
+            prefix.into(),
+            terminated.to_string().red(),
+        ]));
+    }
+
     out
 }
 
 fn format_mcp_invocation<'a>(invocation: McpInvocation) -> Line<'a> {
-    let args_str = invocation
-        .arguments
-        .as_ref()
-        .map(|v| {
-            // Use compact form to keep things short but readable.
-            serde_json::to_string(v).unwrap_or_else(|_| v.to_string())
-        })
-        .unwrap_or_default();
+    let args_str = match &invocation.arguments {
+        // Truncated arguments are already the start of their JSON.
+        Some(serde_json::Value::String(prefix)) if invocation.arguments_truncated => {
+            format!("{prefix}…")
+        }
+        // Use compact form to keep things short but readable.
+        Some(v) => serde_json::to_string(v).unwrap_or_else(|_| v.to_string()),
+        None => String::new(),
+    };
 
     let invocation_spans = vec![
         Span::styled(invocation.server.clone(), Style::default().fg(Color::Cyan)),
@@ -1259,6 +1873,26 @@ fn format_mcp_invocation<'a>(invocation: McpInvocation) -> Line<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn reasoning_heading_is_bold_span_or_first_sentence() {
+        assert_eq!(
+            split_reasoning_heading("**Reading files**\n\nStarting with main.rs."),
+            ("Reading files".to_string(), "Starting with main.rs.")
+        );
+        assert_eq!(
+            split_reasoning_heading("Checking tests. Then fixing them."),
+            ("Checking tests.".to_string(), "Then fixing them.")
+        );
+        assert_eq!(
+            split_reasoning_heading("Version 1.2 looks fine\nmore"),
+            ("Version 1.2 looks fine".to_string(), "more")
+        );
+        assert_eq!(
+            split_reasoning_heading("No punctuation"),
+            ("No punctuation".to_string(), "")
+        );
+    }
+
     #[test]
     fn parsed_command_with_newlines_starts_each_line_at_origin() {
         let parsed = vec![ParsedCommand::Unknown {
@@ -5,13 +5,16 @@
 #![deny(clippy::disallowed_methods)]
 use app::App;
 use codex_core::BUILT_IN_OSS_MODEL_PROVIDER_ID;
+use codex_core::codex_dirs::CodexDirs;
 use codex_core::config::Config;
 use codex_core::config::ConfigOverrides;
 use codex_core::config::ConfigToml;
-use codex_core::config::find_codex_home;
 use codex_core::config::load_config_as_toml_with_cli_overrides;
 use codex_core::protocol::AskForApproval;
 use codex_core::protocol::SandboxPolicy;
+use codex_core::rollout::find_rollout_by_id;
+use codex_core::rollout::read_session_meta;
+use codex_core::session_index::list_sessions;
 use codex_login::AuthManager;
 use codex_login::AuthMode;
 use codex_login::CodexAuth;
@@ -47,13 +50,16 @@ mod markdown;
 mod markdown_stream;
 pub mod onboarding;
 mod pager_overlay;
+mod patch_review;
 mod render;
+mod resume_picker;
 mod session_log;
 mod shimmer;
 mod slash_command;
 mod status_indicator_widget;
 mod streaming;
 mod text_formatting;
+mod transcript_search;
 mod tui;
 mod user_approval_widget;
 
@@ -66,6 +72,7 @@ mod chatwidget_stream_tests;
 mod updates;
 
 pub use cli::Cli;
+pub use cli::ResumeArgs;
 
 use crate::onboarding::TrustDirectorySelection;
 use crate::onboarding::onboarding_screen::OnboardingScreenArgs;
@@ -95,6 +102,31 @@ pub async fn run_main(
         )
     };
 
+    let raw_overrides = cli.config_overrides.raw_overrides.clone();
+    let overrides_cli = codex_common::CliConfigOverrides { raw_overrides };
+    let cli_kv_overrides = match overrides_cli.parse_overrides() {
+        Ok(v) => v,
+        #[allow(clippy::print_stderr)]
+        Err(e) => {
+            eprintln!("Error parsing -c overrides: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Resolve the session to resume before loading the config: unless
+    // overridden on the command line, it keeps its original model and
+    // provider.
+    let resume_path = match &cli.resume {
+        Some(resume) => match resolve_resume_path(resume).await? {
+            Some(path) => Some(path),
+            // The user cancelled the session picker.
+            None => return Ok(codex_core::protocol::TokenUsage::default()),
+        },
+        None => None,
+    };
+    let resumed_meta = resume_path.as_deref().map(read_session_meta).transpose()?;
+    let overridden_with_c = |key: &str| cli_kv_overrides.iter().any(|(k, _)| k == key);
+
     // When using `--oss`, let the bootstrapper pick the model (defaulting to
     // gpt-oss:20b) and ensure it is present locally. Also, force the built‑in
     // `oss` model provider.
@@ -103,13 +135,20 @@ pub async fn run_main(
     } else if cli.oss {
         Some(DEFAULT_OSS_MODEL.to_owned())
     } else {
-        None // No model specified, will use the default.
+        // No model specified: use the resumed session's model or the default.
+        resumed_meta
+            .as_ref()
+            .filter(|_| !overridden_with_c("model"))
+            .and_then(|meta| meta.model.clone())
     };
 
     let model_provider_override = if cli.oss {
         Some(BUILT_IN_OSS_MODEL_PROVIDER_ID.to_owned())
     } else {
-        None
+        resumed_meta
+            .as_ref()
+            .filter(|_| !overridden_with_c("model_provider"))
+            .and_then(|meta| meta.model_provider.clone())
     };
 
     // canonicalize the cwd
@@ -130,18 +169,8 @@ pub async fn run_main(
         disable_response_storage: cli.oss.then_some(true),
         show_raw_agent_reasoning: cli.oss.then_some(true),
         tools_web_search_request: cli.web_search.then_some(true),
+        skip_git_repo_check: cli.skip_git_repo_check.then_some(true),
     };
-    let raw_overrides = cli.config_overrides.raw_overrides.clone();
-    let overrides_cli = codex_common::CliConfigOverrides { raw_overrides };
-    let cli_kv_overrides = match overrides_cli.parse_overrides() {
-        Ok(v) => v,
-        #[allow(clippy::print_stderr)]
-        Err(e) => {
-            eprintln!("Error parsing -c overrides: {e}");
-            std::process::exit(1);
-        }
-    };
-
     let mut config = {
         // Load configuration and support CLI overrides.
 
@@ -154,19 +183,15 @@ pub async fn run_main(
             }
         }
     };
+    if let Some(resume_path) = resume_path {
+        config.experimental_resume = Some(resume_path);
+        config.experimental_fork = cli.resume.as_ref().is_some_and(|resume| resume.fork);
+    }
 
     // we load config.toml here to determine project state.
     #[allow(clippy::print_stderr)]
     let config_toml = {
-        let codex_home = match find_codex_home() {
-            Ok(codex_home) => codex_home,
-            Err(err) => {
-                eprintln!("Error finding codex home: {err}");
-                std::process::exit(1);
-            }
-        };
-
-        match load_config_as_toml_with_cli_overrides(&codex_home, cli_kv_overrides) {
+        match load_config_as_toml_with_cli_overrides(config.dirs.config_dir(), cli_kv_overrides) {
             Ok(config_toml) => config_toml,
             Err(err) => {
                 eprintln!("Error loading config.toml: {err}");
@@ -216,13 +241,24 @@ pub async fn run_main(
         .with_target(false)
         .with_filter(env_filter());
 
-    if cli.oss {
-        codex_ollama::ensure_oss_ready(&config)
-            .await
-            .map_err(|e| std::io::Error::other(format!("OSS setup failed: {e}")))?;
-    }
-
-    let _ = tracing_subscriber::registry().with(file_layer).try_init();
+    // `--oss` asked for the model explicitly, so pull it without asking.
+    codex_ollama::ensure_model_ready(
+        &config,
+        |model| cli.oss || codex_ollama::confirm_pull_on_terminal(model),
+        &mut codex_ollama::TuiProgressReporter::default(),
+    )
+    .await
+    .map_err(|e| std::io::Error::other(format!("Ollama setup failed: {e}")))?;
+
+    #[cfg(feature = "otel")]
+    let (otel_layer, _otel_guard) = codex_core::otel::layer(&config.otel)?.unzip();
+    #[cfg(not(feature = "otel"))]
+    let otel_layer: Option<tracing_subscriber::layer::Identity> = None;
+
+    let _ = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(otel_layer)
+        .try_init();
 
     run_ratatui_app(cli, config, should_show_trust_screen)
         .await
@@ -303,14 +339,17 @@ async fn run_ratatui_app(
 
     let Cli { prompt, images, .. } = cli;
 
-    let auth_manager = AuthManager::shared(config.codex_home.clone(), config.preferred_auth_method);
+    let auth_manager = AuthManager::shared(
+        config.dirs.config_dir().to_path_buf(),
+        config.preferred_auth_method,
+    );
     let login_status = get_login_status(&config);
     let should_show_onboarding =
         should_show_onboarding(login_status, &config, should_show_trust_screen);
     if should_show_onboarding {
         let directory_trust_decision = run_onboarding_app(
             OnboardingScreenArgs {
-                codex_home: config.codex_home.clone(),
+                codex_home: config.dirs.config_dir().to_path_buf(),
                 cwd: config.cwd.clone(),
                 show_login_screen: should_show_login_screen(login_status, &config),
                 show_trust_screen: should_show_trust_screen,
@@ -336,6 +375,44 @@ async fn run_ratatui_app(
     app_result
 }
 
+/// Maximum number of sessions offered by the `codex resume` picker.
+const RESUME_PICKER_LIMIT: usize = 50;
+
+/// Find the rollout to resume, asking the user to pick one when no session id
+/// was given. Returns `None` if the user cancels the picker.
+async fn resolve_resume_path(resume: &ResumeArgs) -> std::io::Result<Option<PathBuf>> {
+    let state_dir = CodexDirs::resolve()?.state_dir().to_path_buf();
+    match &resume.session_id {
+        Some(session_id) => {
+            let id = uuid::Uuid::parse_str(session_id).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid session id `{session_id}`: {e}"),
+                )
+            })?;
+            find_rollout_by_id(&state_dir, id)?
+                .map(Some)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("no recorded session with id {id}"),
+                    )
+                })
+        }
+        None => {
+            let mut sessions = list_sessions(&state_dir)?;
+            sessions.truncate(RESUME_PICKER_LIMIT);
+            if sessions.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no recorded sessions to resume",
+                ));
+            }
+            resume_picker::pick_session(sessions).await
+        }
+    }
+}
+
 #[expect(
     clippy::print_stderr,
     reason = "TUI should no longer be displayed, so we can write to stderr."
@@ -358,8 +435,7 @@ fn get_login_status(config: &Config) -> LoginStatus {
     if config.model_provider.requires_openai_auth {
         // Reading the OpenAI API key is an async operation because it may need
         // to refresh the token. Block on it.
-        let codex_home = config.codex_home.clone();
-        match CodexAuth::from_codex_home(&codex_home, config.preferred_auth_method) {
+        match CodexAuth::from_codex_home(config.dirs.config_dir(), config.preferred_auth_method) {
             Ok(Some(auth)) => LoginStatus::AuthMode(auth.mode),
             Ok(None) => LoginStatus::NotAuthenticated,
             Err(err) => {
@@ -422,8 +498,10 @@ fn should_show_onboarding(
 
 fn should_show_login_screen(login_status: LoginStatus, config: &Config) -> bool {
     // Only show the login screen for providers that actually require OpenAI auth
-    // (OpenAI or equivalents). For OSS/other providers, skip login entirely.
-    if !config.model_provider.requires_openai_auth {
+    // (OpenAI or equivalents). For OSS/other providers, skip login entirely,
+    // as when a key is stored for the provider, which takes precedence.
+    if !config.model_provider.requires_openai_auth || config.model_provider.stored_api_key.is_some()
+    {
         return false;
     }
 
@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
 use std::io::Result;
+use std::ops::Range;
 use std::time::Duration;
 
 use crate::insert_history;
+use crate::transcript_search::TranscriptSearch;
+use crate::transcript_search::highlight_line;
 use crate::tui;
 use crate::tui::TuiEvent;
 use crossterm::event::KeyCode;
@@ -77,6 +81,10 @@ struct PagerView {
     scroll_offset: usize,
     title: String,
     wrap_cache: Option<WrapCache>,
+    /// Char ranges to restyle, by source line (search matches).
+    marks: BTreeMap<usize, Vec<(Range<usize>, Style)>>,
+    /// Source line to bring into view on the next render.
+    pending_jump: Option<usize>,
 }
 
 impl PagerView {
@@ -86,6 +94,33 @@ impl PagerView {
             scroll_offset,
             title,
             wrap_cache: None,
+            marks: BTreeMap::new(),
+            pending_jump: None,
+        }
+    }
+
+    fn set_marks(&mut self, marks: BTreeMap<usize, Vec<(Range<usize>, Style)>>) {
+        if marks != self.marks {
+            self.marks = marks;
+            self.wrap_cache = None;
+        }
+    }
+
+    /// Source line of the first visible row, as of the last render.
+    fn top_line(&self) -> usize {
+        let (_, src_idx) = self.cached();
+        src_idx.get(self.scroll_offset).copied().unwrap_or(0)
+    }
+
+    /// Scrolls so that the pending jump target sits a third of the way down
+    /// the page, leaving some context above it.
+    fn apply_pending_jump(&mut self, height: u16) {
+        let Some(line) = self.pending_jump.take() else {
+            return;
+        };
+        let (_, src_idx) = self.cached();
+        if let Some(row) = src_idx.iter().position(|&src| src == line) {
+            self.scroll_offset = row.saturating_sub(height as usize / 3);
         }
     }
 
@@ -120,6 +155,7 @@ impl PagerView {
         self.render_header(area, buf);
         let content_area = self.scroll_area(area);
         self.ensure_wrapped(content_area.width);
+        self.apply_pending_jump(content_area.height);
         // Compute page bounds first to avoid borrow conflicts
         let wrapped_len = self
             .wrap_cache
@@ -279,7 +315,12 @@ impl PagerView {
         let mut wrapped: Vec<Line<'static>> = Vec::new();
         let mut src_idx: Vec<usize> = Vec::new();
         for (i, line) in self.lines.iter().enumerate() {
-            let ws = insert_history::word_wrap_lines(std::slice::from_ref(line), width);
+            let ws = match self.marks.get(&i) {
+                Some(marks) => {
+                    insert_history::word_wrap_lines(&[highlight_line(line, marks)], width)
+                }
+                None => insert_history::word_wrap_lines(std::slice::from_ref(line), width),
+            };
             src_idx.extend(std::iter::repeat_n(i, ws.len()));
             wrapped.extend(ws);
         }
@@ -342,12 +383,14 @@ impl PagerView {
 pub(crate) struct TranscriptOverlay {
     view: PagerView,
     highlight_range: Option<(usize, usize)>,
+    search: TranscriptSearch,
     is_done: bool,
 }
 
 impl TranscriptOverlay {
     pub(crate) fn new(transcript_lines: Vec<Line<'static>>) -> Self {
         Self {
+            search: TranscriptSearch::new(&transcript_lines),
             view: PagerView::new(
                 transcript_lines,
                 "T R A N S C R I P T".to_string(),
@@ -359,8 +402,58 @@ impl TranscriptOverlay {
     }
 
     pub(crate) fn insert_lines(&mut self, lines: Vec<Line<'static>>) {
+        self.search.push_lines(&lines);
         self.view.lines.extend(lines);
         self.view.wrap_cache = None;
+        self.view.set_marks(self.search.marks());
+    }
+
+    /// Whether a search query is being typed or highlighted, in which case
+    /// the overlay wants every key (Esc ends the search).
+    pub(crate) fn search_is_active(&self) -> bool {
+        self.search.status().is_some()
+    }
+
+    pub(crate) fn is_editing_search(&self) -> bool {
+        self.search.is_editing()
+    }
+
+    /// Highlights the matches and scrolls to the current one.
+    fn sync_search(&mut self) {
+        self.view.set_marks(self.search.marks());
+        self.view.pending_jump = self.search.current_match().map(|m| m.line);
+    }
+
+    fn handle_search_key(&mut self, tui: &mut tui::Tui, key_event: KeyEvent) -> bool {
+        if !matches!(key_event.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
+            return false;
+        }
+        if self.search.is_editing() {
+            match key_event.code {
+                KeyCode::Char(c)
+                    if !key_event
+                        .modifiers
+                        .contains(crossterm::event::KeyModifiers::CONTROL) =>
+                {
+                    self.search.push_char(c, self.view.top_line());
+                }
+                KeyCode::Backspace => self.search.pop_char(self.view.top_line()),
+                KeyCode::Enter => self.search.confirm(),
+                KeyCode::Esc => self.search.cancel(),
+                _ => return false,
+            }
+        } else {
+            match key_event.code {
+                KeyCode::Char('/') => self.search.start(),
+                KeyCode::Char('n') if self.search_is_active() => self.search.next(),
+                KeyCode::Char('N') if self.search_is_active() => self.search.previous(),
+                KeyCode::Esc if self.search_is_active() => self.search.cancel(),
+                _ => return false,
+            }
+        }
+        self.sync_search();
+        tui.frame_requester().schedule_frame();
+        true
     }
 
     pub(crate) fn set_highlight_range(&mut self, range: Option<(usize, usize)>) {
@@ -377,7 +470,12 @@ impl TranscriptOverlay {
         {
             pairs.push(("⏎", "edit message"));
         }
+        pairs.push(("/", "search"));
         render_key_hints(line2, buf, &pairs);
+        if let Some(status) = self.search.status() {
+            let line3 = Rect::new(area.x, area.y.saturating_add(2), area.width, 1);
+            Span::from(format!(" {status}")).render_ref(line3, buf);
+        }
     }
 
     pub(crate) fn render(&mut self, area: Rect, buf: &mut Buffer) {
@@ -393,6 +491,7 @@ impl TranscriptOverlay {
 impl TranscriptOverlay {
     pub(crate) fn handle_event(&mut self, tui: &mut tui::Tui, event: TuiEvent) -> Result<()> {
         match event {
+            TuiEvent::Key(key_event) if self.handle_search_key(tui, key_event) => Ok(()),
             TuiEvent::Key(key_event) => match key_event {
                 KeyEvent {
                     code: KeyCode::Char('q'),
@@ -541,6 +640,33 @@ mod tests {
         assert_snapshot!(term.backend());
     }
 
+    #[test]
+    fn transcript_search_highlights_match_and_shows_counter() {
+        let mut overlay = TranscriptOverlay::new(vec![
+            Line::from("alpha"),
+            Line::from("beta"),
+            Line::from("alphabet"),
+        ]);
+        overlay.search.start();
+        for c in "bet".chars() {
+            overlay.search.push_char(c, 0);
+        }
+        overlay.search.confirm();
+        overlay.sync_search();
+
+        let area = Rect::new(0, 0, 40, 10);
+        let mut buf = Buffer::empty(area);
+        overlay.render(area, &mut buf);
+
+        let status: String = (area.x..area.right())
+            .map(|x| buf[(x, area.bottom() - 1)].symbol().to_string())
+            .collect();
+        assert_eq!(status.trim_end(), " /bet  1/2");
+        // "beta" is on the second row; its first three cells are the current match.
+        assert_eq!(buf[(0, 2)].bg, Color::Yellow);
+        assert_eq!(buf[(3, 2)].bg, Color::Reset);
+    }
+
     #[test]
     fn static_overlay_snapshot_basic() {
         // Prepare a static overlay with a few lines and a title
@@ -0,0 +1,279 @@
+//! Lets the user accept or reject single files and hunks of a patch before
+//! approving it. Hunks are those of each file's unified diff, and are sent
+//! back by the old range of their `@@` header, which is how
+//! [`PatchSelection`] refers to them.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use codex_core::protocol::FileChange;
+use codex_core::protocol::HunkRange;
+use codex_core::protocol::PatchSelection;
+use ratatui::prelude::*;
+use ratatui::text::Line;
+
+/// Most rows shown at once; the list scrolls to keep the cursor visible.
+const MAX_VISIBLE_ROWS: usize = 12;
+
+pub(crate) struct PatchReview {
+    rows: Vec<ReviewRow>,
+    cursor: usize,
+}
+
+struct ReviewRow {
+    path: PathBuf,
+    /// `None` for the row that stands for the whole file.
+    hunk: Option<HunkRange>,
+    label: String,
+    accepted: bool,
+}
+
+impl PatchReview {
+    pub(crate) fn new(changes: &HashMap<PathBuf, FileChange>) -> Self {
+        let mut paths: Vec<&PathBuf> = changes.keys().collect();
+        paths.sort();
+        let mut rows = Vec::new();
+        for path in paths {
+            let file_row = |label: String| ReviewRow {
+                path: path.clone(),
+                hunk: None,
+                label,
+                accepted: true,
+            };
+            match &changes[path] {
+                FileChange::Add { content } => rows.push(file_row(format!(
+                    "{} (new, +{})",
+                    path.display(),
+                    content.lines().count()
+                ))),
+                FileChange::Delete { content } => rows.push(file_row(format!(
+                    "{} (deleted, -{})",
+                    path.display(),
+                    content.lines().count()
+                ))),
+                FileChange::Update {
+                    unified_diff,
+                    move_path,
+                } => {
+                    let label = match move_path {
+                        Some(move_path) => {
+                            format!("{} → {}", path.display(), move_path.display())
+                        }
+                        None => path.display().to_string(),
+                    };
+                    rows.push(file_row(label));
+                    rows.extend(hunk_labels(unified_diff).into_iter().map(|(hunk, label)| {
+                        ReviewRow {
+                            path: path.clone(),
+                            hunk: Some(hunk),
+                            label,
+                            accepted: true,
+                        }
+                    }));
+                }
+            }
+        }
+        Self { rows, cursor: 0 }
+    }
+
+    pub(crate) fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub(crate) fn move_down(&mut self) {
+        if self.cursor + 1 < self.rows.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Flips the row under the cursor. A file row takes all of its hunks
+    /// with it, and a file stays accepted while any of its hunks is.
+    pub(crate) fn toggle(&mut self) {
+        let Some(row) = self.rows.get(self.cursor) else {
+            return;
+        };
+        let path = row.path.clone();
+        let accepted = !row.accepted;
+        match row.hunk {
+            None => {
+                for row in self.rows.iter_mut().filter(|row| row.path == path) {
+                    row.accepted = accepted;
+                }
+            }
+            Some(_) => {
+                self.rows[self.cursor].accepted = accepted;
+                let any_hunk = self
+                    .rows
+                    .iter()
+                    .any(|row| row.path == path && row.hunk.is_some() && row.accepted);
+                if let Some(file) = self
+                    .rows
+                    .iter_mut()
+                    .find(|row| row.path == path && row.hunk.is_none())
+                {
+                    file.accepted = any_hunk;
+                }
+            }
+        }
+    }
+
+    /// Whether anything is left to apply.
+    pub(crate) fn any_accepted(&self) -> bool {
+        self.rows.iter().any(|row| row.accepted)
+    }
+
+    pub(crate) fn selection(&self) -> PatchSelection {
+        let mut selection = PatchSelection::default();
+        for row in self.rows.iter().filter(|row| !row.accepted) {
+            match row.hunk {
+                None => selection.rejected_files.push(row.path.clone()),
+                Some(hunk) if !selection.is_file_rejected(&row.path) => selection
+                    .rejected_hunks
+                    .entry(row.path.clone())
+                    .or_default()
+                    .push(hunk),
+                Some(_) => {}
+            }
+        }
+        selection
+    }
+
+    pub(crate) fn desired_height(&self) -> u16 {
+        self.rows.len().min(MAX_VISIBLE_ROWS) as u16
+    }
+
+    pub(crate) fn lines(&self) -> Vec<Line<'static>> {
+        let first = (self.cursor + 1).saturating_sub(MAX_VISIBLE_ROWS);
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_VISIBLE_ROWS)
+            .map(|(index, row)| {
+                let checkbox = if row.accepted { "[x] " } else { "[ ] " };
+                let indent = if row.hunk.is_some() { "    " } else { "" };
+                let line = Line::from(vec![
+                    indent.into(),
+                    checkbox.into(),
+                    row.label.clone().into(),
+                ]);
+                if index == self.cursor {
+                    line.style(Style::new().bg(Color::Cyan).fg(Color::Black))
+                } else if row.accepted {
+                    line
+                } else {
+                    line.style(Style::new().add_modifier(Modifier::DIM))
+                }
+            })
+            .collect()
+    }
+}
+
+/// One label per `@@` hunk of `unified_diff`, with the hunk's old range: its
+/// header followed by the first line it changes.
+fn hunk_labels(unified_diff: &str) -> Vec<(HunkRange, String)> {
+    let mut labels: Vec<(HunkRange, String)> = Vec::new();
+    let mut first_change: Option<&str> = None;
+    for line in unified_diff.lines() {
+        if let Some(hunk) = HunkRange::from_header(line) {
+            if let (Some((_, label)), Some(change)) = (labels.last_mut(), first_change.take()) {
+                label.push_str(&format!("  {change}"));
+            }
+            labels.push((hunk, line.to_string()));
+        } else if first_change.is_none()
+            && !labels.is_empty()
+            && (line.starts_with('+') || line.starts_with('-'))
+        {
+            first_change = Some(line);
+        }
+    }
+    if let (Some((_, label)), Some(change)) = (labels.last_mut(), first_change) {
+        label.push_str(&format!("  {change}"));
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn changes() -> HashMap<PathBuf, FileChange> {
+        HashMap::from([
+            (
+                PathBuf::from("/repo/a.rs"),
+                FileChange::Update {
+                    unified_diff: "@@ -1,2 +1,2 @@\n-one\n+ONE\n two\n@@ -9 +9 @@\n-nine\n+NINE\n"
+                        .to_string(),
+                    move_path: None,
+                },
+            ),
+            (
+                PathBuf::from("/repo/b.rs"),
+                FileChange::Add {
+                    content: "new\n".to_string(),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn rows_list_files_and_their_hunks() {
+        let review = PatchReview::new(&changes());
+        let labels: Vec<&str> = review.rows.iter().map(|row| row.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "/repo/a.rs",
+                "@@ -1,2 +1,2 @@  -one",
+                "@@ -9 +9 @@  -nine",
+                "/repo/b.rs (new, +1)",
+            ]
+        );
+        assert_eq!(review.selection(), PatchSelection::default());
+    }
+
+    #[test]
+    fn toggling_rejects_hunks_and_files() {
+        let mut review = PatchReview::new(&changes());
+        review.move_down();
+        review.move_down();
+        review.toggle();
+        review.move_down();
+        review.toggle();
+        assert_eq!(
+            review.selection(),
+            PatchSelection {
+                rejected_files: vec![PathBuf::from("/repo/b.rs")],
+                rejected_hunks: HashMap::from([(
+                    PathBuf::from("/repo/a.rs"),
+                    vec![HunkRange {
+                        old_start: 9,
+                        old_lines: 1,
+                    }],
+                )]),
+            }
+        );
+
+        // Rejecting the last hunk of a file rejects the file.
+        review.move_up();
+        review.move_up();
+        review.toggle();
+        assert_eq!(
+            review.selection().rejected_files,
+            vec![PathBuf::from("/repo/a.rs"), PathBuf::from("/repo/b.rs")]
+        );
+        assert!(!review.any_accepted());
+
+        // Accepting the file again accepts all of its hunks.
+        review.move_up();
+        review.toggle();
+        assert_eq!(
+            review.selection(),
+            PatchSelection {
+                rejected_files: vec![PathBuf::from("/repo/b.rs")],
+                rejected_hunks: HashMap::new(),
+            }
+        );
+    }
+}
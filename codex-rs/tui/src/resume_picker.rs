@@ -0,0 +1,234 @@
+//! List of recent sessions shown by `codex resume` when no session id is
+//! given.
+
+use std::path::PathBuf;
+
+use codex_core::session_index::SessionIndexEntry;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyEventKind;
+use crossterm::event::KeyModifiers;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::prelude::Widget;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::WidgetRef;
+
+use crate::tui;
+use crate::tui::FrameRequester;
+use crate::tui::Tui;
+use crate::tui::TuiEvent;
+
+/// Header and footer lines around the list of sessions.
+const CHROME_HEIGHT: u16 = 4;
+
+struct ResumePicker {
+    request_frame: FrameRequester,
+    sessions: Vec<SessionIndexEntry>,
+    selected: usize,
+    /// `Some` once the user has made a choice; the inner `None` means the
+    /// picker was cancelled.
+    choice: Option<Option<PathBuf>>,
+}
+
+impl ResumePicker {
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.kind == KeyEventKind::Release {
+            return;
+        }
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.sessions.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                self.choice = Some(self.sessions.get(self.selected).map(|s| s.path.clone()));
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.choice = Some(None),
+            KeyCode::Char('c') | KeyCode::Char('d')
+                if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.choice = Some(None);
+            }
+            _ => {}
+        }
+        self.request_frame.schedule_frame();
+    }
+
+    fn session_line(&self, idx: usize, session: &SessionIndexEntry) -> Line<'static> {
+        // "2025-08-01T10:00:00.000Z" -> "2025-08-01 10:00"
+        let started = session
+            .timestamp
+            .get(..16)
+            .unwrap_or(&session.timestamp)
+            .replace('T', " ");
+        let preview = session
+            .first_user_message
+            .as_deref()
+            .and_then(|message| message.lines().next())
+            .unwrap_or("(no messages)");
+        let mut text = format!("{started}  {preview}");
+        // Damaged rollouts are listed too: what can be read of them can
+        // still be resumed.
+        if let Some(problem) = &session.problem {
+            text.push_str(&format!("  [{problem}]"));
+        }
+        if idx == self.selected {
+            Line::from(format!("> {text}")).cyan()
+        } else {
+            Line::from(format!("  {text}"))
+        }
+    }
+}
+
+impl WidgetRef for &ResumePicker {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        // Scroll so that the selected session stays visible.
+        let visible = usize::from(area.height.saturating_sub(CHROME_HEIGHT)).max(1);
+        let first = self.selected.saturating_sub(visible - 1);
+
+        let mut lines = vec![
+            Line::from("> Resume a previous session".bold()),
+            Line::from(""),
+        ];
+        lines.extend(
+            self.sessions
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(visible)
+                .map(|(idx, session)| self.session_line(idx, session)),
+        );
+        lines.push(Line::from(""));
+        lines.push(Line::from("  ↑/↓ to select · Enter to resume · Esc to cancel").dim());
+
+        Paragraph::new(lines).render(area, buf);
+    }
+}
+
+/// Let the user choose one of `sessions`. Returns `None` if they cancel.
+pub(crate) async fn pick_session(
+    sessions: Vec<SessionIndexEntry>,
+) -> std::io::Result<Option<PathBuf>> {
+    use tokio_stream::StreamExt;
+
+    let mut terminal = tui::init()?;
+    terminal.clear()?;
+    let mut tui = Tui::new(terminal);
+
+    let mut picker = ResumePicker {
+        request_frame: tui.frame_requester(),
+        sessions,
+        selected: 0,
+        choice: None,
+    };
+
+    tui.draw(u16::MAX, |frame| {
+        frame.render_widget_ref(&picker, frame.area());
+    })?;
+
+    let tui_events = tui.event_stream();
+    tokio::pin!(tui_events);
+
+    while picker.choice.is_none() {
+        match tui_events.next().await {
+            Some(TuiEvent::Key(key_event)) => picker.handle_key_event(key_event),
+            Some(TuiEvent::Draw) => {
+                let _ = tui.draw(u16::MAX, |frame| {
+                    frame.render_widget_ref(&picker, frame.area());
+                });
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    tui::restore()?;
+    Ok(picker.choice.flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codex_core::protocol::TokenUsage;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    fn picker(count: usize) -> ResumePicker {
+        ResumePicker {
+            request_frame: FrameRequester::test_dummy(),
+            sessions: (0..count)
+                .map(|i| SessionIndexEntry {
+                    id: Uuid::new_v4(),
+                    path: PathBuf::from(format!("/sessions/rollout-{i}.jsonl")),
+                    timestamp: format!("2025-08-0{}T10:00:00.000Z", i + 1),
+                    cwd: None,
+                    model: None,
+                    turns: 1,
+                    first_user_message: Some(format!("task {i}\nmore detail")),
+                    last_user_message: None,
+                    token_usage: TokenUsage::default(),
+                    cost_usd: None,
+                    rollout_len: 0,
+                    problem: (i == 1).then(|| "1 unreadable entry".to_string()),
+                })
+                .collect(),
+            selected: 0,
+            choice: None,
+        }
+    }
+
+    fn press(picker: &mut ResumePicker, code: KeyCode) {
+        picker.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn enter_selects_highlighted_session() {
+        let mut picker = picker(3);
+        press(&mut picker, KeyCode::Down);
+        press(&mut picker, KeyCode::Down);
+        press(&mut picker, KeyCode::Down);
+        press(&mut picker, KeyCode::Up);
+        press(&mut picker, KeyCode::Enter);
+        assert_eq!(
+            picker.choice,
+            Some(Some(PathBuf::from("/sessions/rollout-1.jsonl")))
+        );
+    }
+
+    #[test]
+    fn escape_cancels() {
+        let mut picker = picker(2);
+        press(&mut picker, KeyCode::Esc);
+        assert_eq!(picker.choice, Some(None));
+    }
+
+    #[test]
+    fn renders_start_time_first_message_and_problems() {
+        let picker = picker(2);
+        let area = Rect::new(0, 0, 60, 8);
+        let mut buf = Buffer::empty(area);
+        (&picker).render_ref(area, &mut buf);
+        let rendered = (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rendered[2], "> 2025-08-01 10:00  task 0");
+        assert_eq!(
+            rendered[3],
+            "  2025-08-02 10:00  task 1  [1 unreadable entry]"
+        );
+    }
+}
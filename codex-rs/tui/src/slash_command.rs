@@ -13,14 +13,17 @@ pub enum SlashCommand {
     // DO NOT ALPHA-SORT! Enum order is presentation order in the popup, so
     // more frequently used commands should be listed first.
     Model,
+    Reasoning,
     Approvals,
     New,
     Init,
     Compact,
     Diff,
+    Undo,
     Mention,
     Status,
     Mcp,
+    Prompt,
     Logout,
     Quit,
     #[cfg(debug_assertions)]
@@ -36,11 +39,16 @@ impl SlashCommand {
             SlashCommand::Compact => "summarize conversation to prevent hitting the context limit",
             SlashCommand::Quit => "exit Codex",
             SlashCommand::Diff => "show git diff (including untracked files)",
+            SlashCommand::Undo => "undo the last patch Codex applied",
             SlashCommand::Mention => "mention a file",
             SlashCommand::Status => "show current session configuration and token usage",
             SlashCommand::Model => "choose what model and reasoning effort to use",
+            SlashCommand::Reasoning => {
+                "use a reasoning effort for your next message: /reasoning <minimal|low|medium|high>"
+            }
             SlashCommand::Approvals => "choose what Codex can do without approval",
-            SlashCommand::Mcp => "list configured MCP tools",
+            SlashCommand::Mcp => "list MCP tools, or resources with `/mcp resources`",
+            SlashCommand::Prompt => "run an MCP prompt: /prompt <name> [arg=value ...]",
             SlashCommand::Logout => "log out of Codex",
             #[cfg(debug_assertions)]
             SlashCommand::TestApproval => "test approval request",
@@ -59,13 +67,16 @@ impl SlashCommand {
             SlashCommand::New
             | SlashCommand::Init
             | SlashCommand::Compact
+            | SlashCommand::Undo
             | SlashCommand::Model
             | SlashCommand::Approvals
+            | SlashCommand::Prompt
             | SlashCommand::Logout => false,
             SlashCommand::Diff
             | SlashCommand::Mention
             | SlashCommand::Status
             | SlashCommand::Mcp
+            | SlashCommand::Reasoning
             | SlashCommand::Quit => true,
 
             #[cfg(debug_assertions)]
@@ -4,6 +4,8 @@
 use std::time::Duration;
 use std::time::Instant;
 
+use codex_core::plan_tool::PlanItemArg;
+use codex_core::plan_tool::StepStatus;
 use codex_core::protocol::Op;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
@@ -24,6 +26,9 @@ pub(crate) struct StatusIndicatorWidget {
     header: String,
     /// Queued user messages to display under the status line.
     queued_messages: Vec<String>,
+    /// Latest plan published by the agent, shown as a checklist under the
+    /// status line until every step is completed.
+    plan: Vec<PlanItemArg>,
 
     start_time: Instant,
     app_event_tx: AppEventSender,
@@ -35,6 +40,7 @@ impl StatusIndicatorWidget {
         Self {
             header: String::from("Working"),
             queued_messages: Vec::new(),
+            plan: Vec::new(),
             start_time: Instant::now(),
 
             app_event_tx,
@@ -47,6 +53,7 @@ impl StatusIndicatorWidget {
         // + optional ellipsis line per truncated message + 1 spacer line
         let inner_width = width.max(1) as usize;
         let mut total: u16 = 1; // status line
+        total = total.saturating_add(self.plan_lines().len() as u16);
         let text_width = inner_width.saturating_sub(3); // account for " ↳ " prefix
         if text_width > 0 {
             let opts = TwOptions::new(text_width)
@@ -60,9 +67,6 @@ impl StatusIndicatorWidget {
                     total = total.saturating_add(1); // ellipsis line
                 }
             }
-            if !self.queued_messages.is_empty() {
-                total = total.saturating_add(1); // keybind hint line
-            }
         } else {
             // At least one line per message if width is extremely narrow
             total = total.saturating_add(self.queued_messages.len() as u16);
@@ -87,6 +91,33 @@ impl StatusIndicatorWidget {
         // Ensure a redraw so changes are visible.
         self.frame_requester.schedule_frame();
     }
+
+    /// Replace the plan displayed beneath the header.
+    pub(crate) fn set_plan(&mut self, plan: Vec<PlanItemArg>) {
+        self.plan = plan;
+        self.frame_requester.schedule_frame();
+    }
+
+    fn plan_lines(&self) -> Vec<Line<'static>> {
+        if self
+            .plan
+            .iter()
+            .all(|item| matches!(item.status, StepStatus::Completed))
+        {
+            return Vec::new();
+        }
+        self.plan
+            .iter()
+            .map(|PlanItemArg { step, status }| {
+                let (mark, text) = match status {
+                    StepStatus::Completed => ("✔".green(), step.clone().dim().crossed_out()),
+                    StepStatus::InProgress => ("□".into(), step.clone().cyan().bold()),
+                    StepStatus::Pending => ("□".dim(), step.clone().dim()),
+                };
+                Line::from(vec!["   ".into(), mark, " ".into(), text])
+            })
+            .collect()
+    }
 }
 
 impl WidgetRef for StatusIndicatorWidget {
@@ -103,16 +134,23 @@ impl WidgetRef for StatusIndicatorWidget {
         // Plain rendering: no borders or padding so the live cell is visually indistinguishable from terminal scrollback.
         let mut spans = vec![" ".into()];
         spans.extend(shimmer_spans(&self.header));
+        // Esc takes queued messages back for editing before it interrupts.
+        let esc_action = if self.queued_messages.is_empty() {
+            " to interrupt)"
+        } else {
+            " to edit queued)"
+        };
         spans.extend(vec![
             " ".into(),
             format!("({elapsed}s • ").dim(),
             "Esc".dim().bold(),
-            " to interrupt)".dim(),
+            esc_action.dim(),
         ]);
 
-        // Build lines: status, then queued messages, then spacer.
+        // Build lines: status, then the plan, then queued messages, then spacer.
         let mut lines: Vec<Line<'static>> = Vec::new();
         lines.push(Line::from(spans));
+        lines.extend(self.plan_lines());
         // Wrap queued messages using textwrap and show up to the first 3 lines per message.
         let text_width = area.width.saturating_sub(3); // " ↳ " prefix
         let opts = TwOptions::new(text_width as usize)
@@ -129,9 +167,6 @@ impl WidgetRef for StatusIndicatorWidget {
                 lines.push(Line::from("   …".dim().italic()));
             }
         }
-        if !self.queued_messages.is_empty() {
-            lines.push(Line::from(vec!["   ".into(), "Alt+↑".cyan(), " edit".into()]).dim());
-        }
 
         let paragraph = Paragraph::new(lines);
         paragraph.render_ref(area, buf);
@@ -190,4 +225,32 @@ mod tests {
             .expect("draw");
         assert_snapshot!(terminal.backend());
     }
+
+    #[test]
+    fn renders_with_plan() {
+        let (tx_raw, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let mut w = StatusIndicatorWidget::new(tx, crate::tui::FrameRequester::test_dummy());
+        w.set_plan(vec![
+            PlanItemArg {
+                step: "Read the code".to_string(),
+                status: StepStatus::Completed,
+            },
+            PlanItemArg {
+                step: "Write the fix".to_string(),
+                status: StepStatus::InProgress,
+            },
+            PlanItemArg {
+                step: "Run the tests".to_string(),
+                status: StepStatus::Pending,
+            },
+        ]);
+        assert_eq!(w.desired_height(80), 5);
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 5)).expect("terminal");
+        terminal
+            .draw(|f| w.render_ref(f.area(), f.buffer_mut()))
+            .expect("draw");
+        assert_snapshot!(terminal.backend());
+    }
 }
@@ -0,0 +1,348 @@
+//! `/` search in the transcript overlay. The overlay keeps a case-folded copy
+//! of every transcript line, extended as new history streams in, so typing a
+//! query only scans text and never re-renders history cells.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::text::Span;
+
+/// A match of the query, as a range of chars in one transcript line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SearchMatch {
+    pub line: usize,
+    pub chars: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TranscriptSearch {
+    /// The text of each transcript line, folded to lower case one char per
+    /// char so that positions in it are positions in the line.
+    folded_lines: Vec<String>,
+    query: String,
+    /// Whether the query is still being typed.
+    editing: bool,
+    /// Every match of `query`, in transcript order.
+    matches: Vec<SearchMatch>,
+    /// Index into `matches` of the match the view jumps to.
+    current: Option<usize>,
+}
+
+impl TranscriptSearch {
+    pub(crate) fn new(lines: &[Line<'_>]) -> Self {
+        Self {
+            folded_lines: lines.iter().map(fold_line).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Indexes lines appended to the transcript, matching them against the
+    /// current query.
+    pub(crate) fn push_lines(&mut self, lines: &[Line<'_>]) {
+        let query = fold(&self.query);
+        for line in lines {
+            let folded = fold_line(line);
+            if !query.is_empty() {
+                self.matches
+                    .extend(find_in_line(self.folded_lines.len(), &folded, &query));
+            }
+            self.folded_lines.push(folded);
+        }
+        if self.current.is_none() && !self.matches.is_empty() {
+            self.current = Some(0);
+        }
+    }
+
+    /// Starts typing a new query.
+    pub(crate) fn start(&mut self) {
+        self.cancel();
+        self.editing = true;
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.query.clear();
+        self.editing = false;
+        self.matches.clear();
+        self.current = None;
+    }
+
+    /// Stops typing the query; an empty query ends the search.
+    pub(crate) fn confirm(&mut self) {
+        if self.query.is_empty() {
+            self.cancel();
+        } else {
+            self.editing = false;
+        }
+    }
+
+    pub(crate) fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    /// Extends the query. Only lines that matched the shorter query can
+    /// match the longer one, so only those are searched again.
+    pub(crate) fn push_char(&mut self, c: char, from_line: usize) {
+        let narrowing = !self.query.is_empty();
+        self.query.push(c);
+        let query = fold(&self.query);
+        let candidates: Vec<usize> = if narrowing {
+            let mut lines: Vec<usize> = self.matches.iter().map(|m| m.line).collect();
+            lines.dedup();
+            lines
+        } else {
+            (0..self.folded_lines.len()).collect()
+        };
+        self.matches = candidates
+            .into_iter()
+            .flat_map(|line| find_in_line(line, &self.folded_lines[line], &query))
+            .collect();
+        self.select_from(from_line);
+    }
+
+    pub(crate) fn pop_char(&mut self, from_line: usize) {
+        self.query.pop();
+        let query = fold(&self.query);
+        self.matches = if query.is_empty() {
+            Vec::new()
+        } else {
+            self.folded_lines
+                .iter()
+                .enumerate()
+                .flat_map(|(line, folded)| find_in_line(line, folded, &query))
+                .collect()
+        };
+        self.select_from(from_line);
+    }
+
+    /// Selects the first match at or after `from_line`, wrapping around to
+    /// the first match in the transcript.
+    fn select_from(&mut self, from_line: usize) {
+        self.current = if self.matches.is_empty() {
+            None
+        } else {
+            Some(
+                self.matches
+                    .iter()
+                    .position(|m| m.line >= from_line)
+                    .unwrap_or(0),
+            )
+        };
+    }
+
+    pub(crate) fn next(&mut self) {
+        if let Some(current) = self.current {
+            self.current = Some((current + 1) % self.matches.len());
+        }
+    }
+
+    pub(crate) fn previous(&mut self) {
+        if let Some(current) = self.current {
+            self.current = Some(current.checked_sub(1).unwrap_or(self.matches.len() - 1));
+        }
+    }
+
+    pub(crate) fn current_match(&self) -> Option<&SearchMatch> {
+        self.current.and_then(|current| self.matches.get(current))
+    }
+
+    /// How to highlight the matches, by transcript line.
+    pub(crate) fn marks(&self) -> BTreeMap<usize, Vec<(Range<usize>, Style)>> {
+        let mut marks: BTreeMap<usize, Vec<(Range<usize>, Style)>> = BTreeMap::new();
+        for (i, m) in self.matches.iter().enumerate() {
+            let style = if Some(i) == self.current {
+                Style::new().black().on_yellow()
+            } else {
+                Style::new().reversed()
+            };
+            marks
+                .entry(m.line)
+                .or_default()
+                .push((m.chars.clone(), style));
+        }
+        marks
+    }
+
+    /// The query and match counter for the overlay's status line, e.g.
+    /// `/timeout  3/17`. `None` when no search is active.
+    pub(crate) fn status(&self) -> Option<String> {
+        if !self.editing && self.query.is_empty() {
+            return None;
+        }
+        let cursor = if self.editing { "▌" } else { "" };
+        let counter = match self.current {
+            Some(current) => format!("{}/{}", current + 1, self.matches.len()),
+            None if self.query.is_empty() => String::new(),
+            None => "no matches".to_string(),
+        };
+        Some(format!("/{}{cursor}  {counter}", self.query))
+    }
+}
+
+fn fold(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+fn fold_line(line: &Line<'_>) -> String {
+    line.spans.iter().map(|span| fold(&span.content)).collect()
+}
+
+fn find_in_line(line: usize, folded: &str, query: &str) -> Vec<SearchMatch> {
+    let query_chars = query.chars().count();
+    folded
+        .match_indices(query)
+        .map(|(start, _)| {
+            let start = folded[..start].chars().count();
+            SearchMatch {
+                line,
+                chars: start..start + query_chars,
+            }
+        })
+        .collect()
+}
+
+/// Restyles the chars of `line` that fall in one of the `marks` ranges,
+/// splitting spans where a range starts or ends.
+pub(crate) fn highlight_line(
+    line: &Line<'static>,
+    marks: &[(Range<usize>, Style)],
+) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut position = 0;
+    for span in &line.spans {
+        let mut run: Option<(Style, String)> = None;
+        for c in span.content.chars() {
+            let style = marks
+                .iter()
+                .find(|(range, _)| range.contains(&position))
+                .map_or(span.style, |(_, mark)| span.style.patch(*mark));
+            position += 1;
+            match &mut run {
+                Some((run_style, text)) if *run_style == style => text.push(c),
+                _ => {
+                    if let Some((run_style, text)) = run.take() {
+                        spans.push(Span::styled(text, run_style));
+                    }
+                    run = Some((style, c.to_string()));
+                }
+            }
+        }
+        if let Some((run_style, text)) = run {
+            spans.push(Span::styled(text, run_style));
+        }
+    }
+    Line {
+        spans,
+        style: line.style,
+        alignment: line.alignment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn lines() -> Vec<Line<'static>> {
+        vec![
+            Line::from(vec!["$ ".into(), "cargo test".into()]),
+            Line::from("test parser::nested ... FAILED"),
+            Line::from("thinking"),
+            Line::from("The Failed test needs a fixture."),
+        ]
+    }
+
+    fn type_query(search: &mut TranscriptSearch, query: &str) {
+        search.start();
+        for c in query.chars() {
+            search.push_char(c, 0);
+        }
+    }
+
+    #[test]
+    fn matches_case_insensitively_and_narrows_as_the_query_grows() {
+        let mut search = TranscriptSearch::new(&lines());
+        type_query(&mut search, "fail");
+        assert_eq!(
+            search.matches,
+            vec![
+                SearchMatch {
+                    line: 1,
+                    chars: 24..28,
+                },
+                SearchMatch {
+                    line: 3,
+                    chars: 4..8,
+                },
+            ]
+        );
+        search.push_char('e', 0);
+        search.push_char('d', 0);
+        search.push_char(' ', 0);
+        assert_eq!(search.matches.len(), 1);
+        assert_eq!(search.status(), Some("/failed ▌  1/1".to_string()));
+
+        search.pop_char(0);
+        search.confirm();
+        assert_eq!(search.status(), Some("/failed  1/2".to_string()));
+    }
+
+    #[test]
+    fn navigation_wraps_and_starts_at_the_visible_line() {
+        let mut search = TranscriptSearch::new(&lines());
+        search.start();
+        for c in "test".chars() {
+            search.push_char(c, 2);
+        }
+        // Lines 0 and 1 are above the view, so the match on line 3 is first.
+        assert_eq!(search.current_match().map(|m| m.line), Some(3));
+        search.next();
+        assert_eq!(search.current_match().map(|m| m.line), Some(0));
+        search.previous();
+        search.previous();
+        assert_eq!(
+            search.current_match(),
+            Some(&SearchMatch {
+                line: 1,
+                chars: 0..4,
+            })
+        );
+    }
+
+    #[test]
+    fn lines_streamed_in_later_are_searched() {
+        let mut search = TranscriptSearch::new(&lines());
+        type_query(&mut search, "panicked");
+        search.confirm();
+        assert_eq!(search.status(), Some("/panicked  no matches".to_string()));
+
+        search.push_lines(&[Line::from("thread 'main' PANICKED at src/lib.rs:3:5")]);
+        assert_eq!(
+            search.matches,
+            vec![SearchMatch {
+                line: 4,
+                chars: 14..22,
+            }]
+        );
+        assert_eq!(search.status(), Some("/panicked  1/1".to_string()));
+    }
+
+    #[test]
+    fn highlight_splits_spans_at_match_boundaries() {
+        let line = Line::from(vec!["$ ".into(), "cargo test".red()]);
+        let highlighted = highlight_line(&line, &[(4..7, Style::new().reversed())]);
+        assert_eq!(
+            highlighted.spans,
+            vec![
+                Span::from("$ "),
+                Span::from("ca").red(),
+                Span::from("rgo").red().reversed(),
+                Span::from(" test").red(),
+            ]
+        );
+    }
+}
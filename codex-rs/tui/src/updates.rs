@@ -55,7 +55,7 @@ const VERSION_FILENAME: &str = "version.json";
 const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/openai/codex/releases/latest";
 
 fn version_filepath(config: &Config) -> PathBuf {
-    config.codex_home.join(VERSION_FILENAME)
+    config.dirs.cache_dir().join(VERSION_FILENAME)
 }
 
 fn read_version_info(version_file: &Path) -> anyhow::Result<VersionInfo> {
@@ -68,7 +68,7 @@ async fn check_for_update(version_file: &Path) -> anyhow::Result<()> {
         tag_name: latest_tag_name,
     } = reqwest::Client::new()
         .get(LATEST_RELEASE_URL)
-        .header("User-Agent", get_codex_user_agent(None))
+        .header("User-Agent", get_codex_user_agent(None, None))
         .send()
         .await?
         .error_for_status()?
@@ -6,10 +6,14 @@
 //! UI to Rust using [`ratatui`]. The goal is feature‑parity for the keyboard
 //! driven workflow – a fully‑fledged visual match is not required.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::LazyLock;
 
+use codex_core::protocol::EscalationReason;
+use codex_core::protocol::FileChange;
 use codex_core::protocol::Op;
+use codex_core::protocol::PatchSelection;
 use codex_core::protocol::ReviewDecision;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
@@ -28,7 +32,9 @@ use ratatui::widgets::Wrap;
 
 use crate::app_event::AppEvent;
 use crate::app_event_sender::AppEventSender;
+use crate::exec_command::escape_command;
 use crate::exec_command::strip_bash_lc_and_escape;
+use crate::patch_review::PatchReview;
 
 /// Request coming from the agent that needs user approval.
 pub(crate) enum ApprovalRequest {
@@ -36,11 +42,16 @@ pub(crate) enum ApprovalRequest {
         id: String,
         command: Vec<String>,
         reason: Option<String>,
+        escalation_reason: Option<EscalationReason>,
+        /// What approving for the session covers; `None` for only this
+        /// exact command.
+        session_prefix: Option<Vec<String>>,
     },
     ApplyPatch {
         id: String,
         reason: Option<String>,
         grant_root: Option<PathBuf>,
+        changes: HashMap<PathBuf, FileChange>,
     },
 }
 
@@ -51,7 +62,14 @@ struct SelectOption {
     label: Line<'static>,
     description: &'static str,
     key: KeyCode,
-    decision: ReviewDecision,
+    action: SelectAction,
+}
+
+#[derive(Clone, Copy)]
+enum SelectAction {
+    Decide(ReviewDecision),
+    /// Pick the files and hunks of a patch to apply.
+    ReviewHunks,
 }
 
 static COMMAND_SELECT_OPTIONS: LazyLock<Vec<SelectOption>> = LazyLock::new(|| {
@@ -60,19 +78,21 @@ static COMMAND_SELECT_OPTIONS: LazyLock<Vec<SelectOption>> = LazyLock::new(|| {
             label: Line::from(vec!["Y".underlined(), "es".into()]),
             description: "Approve and run the command",
             key: KeyCode::Char('y'),
-            decision: ReviewDecision::Approved,
+            action: SelectAction::Decide(ReviewDecision::Approved),
         },
         SelectOption {
             label: Line::from(vec!["A".underlined(), "lways".into()]),
-            description: "Approve the command for the remainder of this session",
+            // Replaced by `UserApprovalWidget::description`, which names
+            // what is approved.
+            description: "Approve for the remainder of this session",
             key: KeyCode::Char('a'),
-            decision: ReviewDecision::ApprovedForSession,
+            action: SelectAction::Decide(ReviewDecision::ApprovedForSession),
         },
         SelectOption {
             label: Line::from(vec!["N".underlined(), "o, provide feedback".into()]),
             description: "Do not run the command; provide feedback",
             key: KeyCode::Char('n'),
-            decision: ReviewDecision::Abort,
+            action: SelectAction::Decide(ReviewDecision::Abort),
         },
     ]
 });
@@ -83,13 +103,19 @@ static PATCH_SELECT_OPTIONS: LazyLock<Vec<SelectOption>> = LazyLock::new(|| {
             label: Line::from(vec!["Y".underlined(), "es".into()]),
             description: "Approve and apply the changes",
             key: KeyCode::Char('y'),
-            decision: ReviewDecision::Approved,
+            action: SelectAction::Decide(ReviewDecision::Approved),
+        },
+        SelectOption {
+            label: Line::from(vec!["R".underlined(), "eview hunks".into()]),
+            description: "Choose which files and hunks to apply",
+            key: KeyCode::Char('r'),
+            action: SelectAction::ReviewHunks,
         },
         SelectOption {
             label: Line::from(vec!["N".underlined(), "o, provide feedback".into()]),
             description: "Do not apply the changes; provide feedback",
             key: KeyCode::Char('n'),
-            decision: ReviewDecision::Abort,
+            action: SelectAction::Decide(ReviewDecision::Abort),
         },
     ]
 });
@@ -104,6 +130,9 @@ pub(crate) struct UserApprovalWidget {
     /// Currently selected index in *select* mode.
     selected_option: usize,
 
+    /// Set while the user picks the hunks of a patch to apply.
+    patch_review: Option<PatchReview>,
+
     /// Set to `true` once a decision has been sent – the parent view can then
     /// remove this widget from its queue.
     done: bool,
@@ -141,7 +170,11 @@ impl UserApprovalWidget {
     pub(crate) fn new(approval_request: ApprovalRequest, app_event_tx: AppEventSender) -> Self {
         let confirmation_prompt = match &approval_request {
             ApprovalRequest::Exec {
-                command, reason, ..
+                command,
+                reason,
+                escalation_reason,
+                session_prefix,
+                ..
             } => {
                 let cmd = strip_bash_lc_and_escape(command);
                 let mut contents: Vec<Line> = to_command_display(
@@ -149,6 +182,18 @@ impl UserApprovalWidget {
                     cmd,
                     vec![],
                 );
+                if let Some(escalation_reason) = escalation_reason {
+                    contents.push(Line::from(vec![
+                        "  Why: ".dim(),
+                        escalation_reason.to_string().into(),
+                    ]));
+                }
+                if let Some(prefix) = session_prefix {
+                    contents.push(Line::from(vec![
+                        "  Always approves: ".dim(),
+                        format!("{} …", escape_command(prefix)).into(),
+                    ]));
+                }
 
                 contents.push(Line::from(""));
                 if let Some(reason) = reason {
@@ -188,6 +233,7 @@ impl UserApprovalWidget {
             app_event_tx,
             confirmation_prompt,
             selected_option: 0,
+            patch_review: None,
             done: false,
         }
     }
@@ -203,7 +249,12 @@ impl UserApprovalWidget {
     /// captures input while visible, we don’t need to report whether the event
     /// was consumed—callers can assume it always is.
     pub(crate) fn handle_key_event(&mut self, key: KeyEvent) {
-        if key.kind == KeyEventKind::Press {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if self.patch_review.is_some() {
+            self.handle_review_key(key);
+        } else {
             self.handle_select_key(key);
         }
     }
@@ -235,7 +286,7 @@ impl UserApprovalWidget {
             }
             KeyCode::Enter => {
                 let opt = &self.select_options[self.selected_option];
-                self.send_decision(opt.decision);
+                self.select(opt.action);
             }
             KeyCode::Esc => {
                 self.send_decision(ReviewDecision::Abort);
@@ -247,9 +298,44 @@ impl UserApprovalWidget {
                     .iter()
                     .find(|opt| Self::normalize_keycode(opt.key) == normalized)
                 {
-                    self.send_decision(opt.decision);
+                    self.select(opt.action);
+                }
+            }
+        }
+    }
+
+    fn select(&mut self, action: SelectAction) {
+        match action {
+            SelectAction::Decide(decision) => self.send_decision(decision),
+            SelectAction::ReviewHunks => {
+                if let ApprovalRequest::ApplyPatch { changes, .. } = &self.approval_request {
+                    self.patch_review = Some(PatchReview::new(changes));
+                }
+            }
+        }
+    }
+
+    /// Keys while picking hunks: arrows move, space toggles, Enter applies
+    /// what is left and Esc goes back to the options.
+    fn handle_review_key(&mut self, key_event: KeyEvent) {
+        let Some(review) = self.patch_review.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => review.move_up(),
+            KeyCode::Down | KeyCode::Char('j') => review.move_down(),
+            KeyCode::Char(' ') => review.toggle(),
+            KeyCode::Esc => self.patch_review = None,
+            KeyCode::Enter => {
+                // Rejecting everything is the same as denying the patch.
+                if review.any_accepted() {
+                    self.send_decision(ReviewDecision::Approved);
+                } else {
+                    self.patch_review = None;
+                    self.send_decision(ReviewDecision::Denied);
                 }
             }
+            _ => {}
         }
     }
 
@@ -319,6 +405,15 @@ impl UserApprovalWidget {
             }
             ApprovalRequest::ApplyPatch { .. } => {
                 lines.push(Line::from(format!("patch approval decision: {decision:?}")));
+                if let Some(selection) = self.selection()
+                    && decision == ReviewDecision::Approved
+                {
+                    let hunks: usize = selection.rejected_hunks.values().map(Vec::len).sum();
+                    lines.push(Line::from(format!(
+                        "rejected {} file(s) and {hunks} hunk(s)",
+                        selection.rejected_files.len()
+                    )));
+                }
             }
         }
         if !feedback.trim().is_empty() {
@@ -333,10 +428,13 @@ impl UserApprovalWidget {
             ApprovalRequest::Exec { id, .. } => Op::ExecApproval {
                 id: id.clone(),
                 decision,
+                feedback: None,
             },
             ApprovalRequest::ApplyPatch { id, .. } => Op::PatchApproval {
                 id: id.clone(),
                 decision,
+                selection: self.selection(),
+                feedback: None,
             },
         };
 
@@ -344,6 +442,32 @@ impl UserApprovalWidget {
         self.done = true;
     }
 
+    /// What choosing `option` does, naming exactly which commands "Always"
+    /// approves.
+    fn description(&self, option: &SelectOption) -> String {
+        match (&self.approval_request, option.action) {
+            (
+                ApprovalRequest::Exec { session_prefix, .. },
+                SelectAction::Decide(ReviewDecision::ApprovedForSession),
+            ) => match session_prefix {
+                Some(prefix) => format!(
+                    "Approve commands starting with `{}` for the remainder of this session",
+                    escape_command(prefix)
+                ),
+                None => "Approve this exact command for the remainder of this session".to_string(),
+            },
+            _ => option.description.to_string(),
+        }
+    }
+
+    /// The parts of the patch rejected in the hunk review, if any.
+    fn selection(&self) -> Option<PatchSelection> {
+        self.patch_review
+            .as_ref()
+            .map(PatchReview::selection)
+            .filter(|selection| !selection.is_empty())
+    }
+
     /// Returns `true` once the user has made a decision and the widget no
     /// longer needs to be displayed.
     pub(crate) fn is_complete(&self) -> bool {
@@ -351,6 +475,10 @@ impl UserApprovalWidget {
     }
 
     pub(crate) fn desired_height(&self, width: u16) -> u16 {
+        if let Some(review) = &self.patch_review {
+            // Title, the rows and a line of key hints.
+            return review.desired_height() + 2;
+        }
         // Reserve space for:
         // - 1 title line ("Allow command?" or "Apply changes?")
         // - 1 buttons line (options rendered horizontally on a single row)
@@ -361,6 +489,10 @@ impl UserApprovalWidget {
 
 impl WidgetRef for &UserApprovalWidget {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        if let Some(review) = &self.patch_review {
+            render_patch_review(review, area, buf);
+            return;
+        }
         let prompt_height = self.get_confirmation_prompt_height(area.width);
         let [prompt_chunk, response_chunk] = Layout::default()
             .direction(Direction::Vertical)
@@ -406,7 +538,7 @@ impl WidgetRef for &UserApprovalWidget {
             line.render(*area, buf);
         }
 
-        Line::from(self.select_options[self.selected_option].description)
+        Line::from(self.description(&self.select_options[self.selected_option]))
             .style(Style::new().italic().add_modifier(Modifier::DIM))
             .render(description_area.inner(Margin::new(1, 0)), buf);
 
@@ -421,9 +553,29 @@ impl WidgetRef for &UserApprovalWidget {
     }
 }
 
+fn render_patch_review(review: &PatchReview, area: Rect, buf: &mut Buffer) {
+    let [title_area, rows_area, hint_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(review.desired_height()),
+        Constraint::Min(0),
+    ])
+    .areas(area.inner(Margin::new(1, 0)));
+    Line::from("Select changes to apply").render(title_area, buf);
+    Paragraph::new(review.lines()).render(rows_area, buf);
+    Line::from("↑/↓ move · space toggle · enter apply selected · esc back")
+        .style(Style::new().italic().add_modifier(Modifier::DIM))
+        .render(hint_area, buf);
+    Block::bordered()
+        .border_type(BorderType::QuadrantOutside)
+        .border_style(Style::default().fg(Color::Cyan))
+        .borders(Borders::LEFT)
+        .render_ref(Rect::new(0, area.y, 1, area.height), buf);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use codex_core::protocol::HunkRange;
     use crossterm::event::KeyCode;
     use crossterm::event::KeyEvent;
     use crossterm::event::KeyModifiers;
@@ -437,6 +589,8 @@ mod tests {
             id: "1".to_string(),
             command: vec!["echo".to_string()],
             reason: None,
+            escalation_reason: None,
+            session_prefix: None,
         };
         let mut widget = UserApprovalWidget::new(req, tx);
         widget.handle_key_event(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
@@ -454,6 +608,58 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn hunk_review_sends_the_rejected_hunks() {
+        let (tx_raw, mut rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let path = PathBuf::from("/repo/a.rs");
+        let req = ApprovalRequest::ApplyPatch {
+            id: "3".to_string(),
+            reason: None,
+            grant_root: None,
+            changes: HashMap::from([(
+                path.clone(),
+                FileChange::Update {
+                    unified_diff: "@@ -1 +1 @@\n-a\n+A\n@@ -9 +9 @@\n-i\n+I\n".to_string(),
+                    move_path: None,
+                },
+            )]),
+        };
+        let mut widget = UserApprovalWidget::new(req, tx);
+        for code in [
+            KeyCode::Char('r'),
+            KeyCode::Down,
+            KeyCode::Down,
+            KeyCode::Char(' '),
+            KeyCode::Enter,
+        ] {
+            widget.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+        assert!(widget.is_complete());
+        let mut events: Vec<AppEvent> = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            events.push(ev);
+        }
+        let expected = PatchSelection {
+            rejected_files: Vec::new(),
+            rejected_hunks: HashMap::from([(
+                path,
+                vec![HunkRange {
+                    old_start: 9,
+                    old_lines: 1,
+                }],
+            )]),
+        };
+        assert!(events.iter().any(|e| matches!(
+            e,
+            AppEvent::CodexOp(Op::PatchApproval {
+                decision: ReviewDecision::Approved,
+                selection: Some(selection),
+                ..
+            }) if *selection == expected
+        )));
+    }
+
     #[test]
     fn uppercase_shortcut_is_accepted() {
         let (tx_raw, mut rx) = unbounded_channel::<AppEvent>();
@@ -462,6 +668,8 @@ mod tests {
             id: "2".to_string(),
             command: vec!["echo".to_string()],
             reason: None,
+            escalation_reason: None,
+            session_prefix: None,
         };
         let mut widget = UserApprovalWidget::new(req, tx);
         widget.handle_key_event(KeyEvent::new(KeyCode::Char('Y'), KeyModifiers::NONE));
@@ -478,4 +686,71 @@ mod tests {
             })
         )));
     }
+
+    #[test]
+    fn escalation_reason_is_shown_under_the_command() {
+        let (tx_raw, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let req = ApprovalRequest::Exec {
+            id: "4".to_string(),
+            command: vec!["touch".to_string(), "/etc/motd".to_string()],
+            reason: Some("command failed; retry without sandbox?".to_string()),
+            escalation_reason: Some(EscalationReason::SandboxDenied {
+                path: Some(PathBuf::from("/etc/motd")),
+                syscall: Some("open".to_string()),
+                errno: Some("EROFS".to_string()),
+            }),
+            session_prefix: None,
+        };
+        let widget = UserApprovalWidget::new(req, tx);
+        let area = Rect::new(0, 0, 80, widget.desired_height(80));
+        let mut buf = Buffer::empty(area);
+        (&widget).render_ref(area, &mut buf);
+
+        let rows: Vec<String> = (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        let command_row = rows
+            .iter()
+            .position(|row| row.contains("touch /etc/motd"))
+            .unwrap();
+        assert_eq!(
+            rows[command_row + 1],
+            "  Why: the sandbox blocked open on /etc/motd (EROFS)"
+        );
+    }
+
+    #[test]
+    fn always_names_the_approved_prefix() {
+        let (tx_raw, _rx) = unbounded_channel::<AppEvent>();
+        let tx = AppEventSender::new(tx_raw);
+        let exec = |session_prefix| ApprovalRequest::Exec {
+            id: "5".to_string(),
+            command: vec!["cargo".to_string(), "test".to_string()],
+            reason: None,
+            escalation_reason: None,
+            session_prefix,
+        };
+        let always = &COMMAND_SELECT_OPTIONS[1];
+
+        let widget = UserApprovalWidget::new(
+            exec(Some(vec!["cargo".to_string(), "test".to_string()])),
+            tx.clone(),
+        );
+        assert_eq!(
+            widget.description(always),
+            "Approve commands starting with `cargo test` for the remainder of this session"
+        );
+        let widget = UserApprovalWidget::new(exec(None), tx);
+        assert_eq!(
+            widget.description(always),
+            "Approve this exact command for the remainder of this session"
+        );
+    }
 }